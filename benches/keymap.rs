@@ -0,0 +1,40 @@
+//! How long a key lookup takes: the built-in table, and with overrides on
+//! top, empty and with a few hundred. `cargo bench --bench keymap` prints
+//! the time per character; next to the character delay of a run, which is
+//! milliseconds, it should be nanoseconds.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use evdev_rs::enums::EV_KEY;
+use pasteclipboard::keymap::{char_to_key_event, Keymap};
+
+/// Lookups timed for each case.
+const LOOKUPS: usize = 10_000_000;
+
+/// Every printable ASCII character and some beyond, as a text would have
+/// them.
+fn sample() -> Vec<char> {
+    (' '..='~').chain(['\n', '\t', '\u{e9}', '\u{2014}']).collect()
+}
+
+fn time(name: &str, lookup: impl Fn(char) -> (EV_KEY, bool)) {
+    let chars = sample();
+    let start = Instant::now();
+    for &c in chars.iter().cycle().take(LOOKUPS) {
+        black_box(lookup(black_box(c)));
+    }
+    let nanos = start.elapsed().as_secs_f64() * 1e9 / LOOKUPS as f64;
+    println!("{:<16} {:>6.2} ns per character", name, nanos);
+}
+
+fn main() {
+    time("table", char_to_key_event);
+    let empty = Keymap::default();
+    time("no overrides", |c| empty.get(c));
+    let mut overridden = Keymap::default();
+    for c in ('\u{c0}'..'\u{17f}').chain(['z', 'y', 'Z', 'Y']) {
+        overridden.set(c, EV_KEY::KEY_102ND, false);
+    }
+    time("195 overrides", |c| overridden.get(c));
+}
@@ -1,15 +1,18 @@
 fn main() {
-    // This build script is for linking to libxdo
-    // It's a C library for simulating keyboard input
-    
-    // Use pkg-config to find libxdo
-    if let Err(e) = pkg_config::probe_library("libxdo") {
-        // If pkg-config fails, print a helpful error message
-        eprintln!("Failed to find libxdo using pkg-config: {}", e);
-        eprintln!("Please ensure libxdo is installed and configured correctly.");
-        eprintln!("On Debian/Ubuntu, you can install it with: sudo apt-get install libxdo-dev");
-        std::process::exit(1);
+    // This build script is for linking to libxdo, which only the xdo
+    // backend (the x11-xdo feature) uses.
+    #[cfg(feature = "x11-xdo")]
+    {
+        // Use pkg-config to find libxdo
+        if let Err(e) = pkg_config::probe_library("libxdo") {
+            // If pkg-config fails, print a helpful error message
+            eprintln!("Failed to find libxdo using pkg-config: {}", e);
+            eprintln!("Please ensure libxdo is installed and configured correctly.");
+            eprintln!("On Debian/Ubuntu, you can install it with: sudo apt-get install libxdo-dev");
+            eprintln!("Or build without the xdo backend by leaving out the x11-xdo feature.");
+            std::process::exit(1);
+        }
+
+        println!("cargo:rustc-link-lib=xdo");
     }
-    
-    println!("cargo:rustc-link-lib=xdo");
-}
\ No newline at end of file
+}
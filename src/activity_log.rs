@@ -0,0 +1,67 @@
+//! The session's activity log: timestamped notes about each run, kept in a
+//! bounded ring so a long session cannot grow it without limit.
+
+use std::collections::VecDeque;
+
+/// Entries kept before the oldest are dropped.
+pub const LOG_CAPACITY: usize = 500;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Local wall-clock time, e.g. "14:03:27".
+    pub time: String,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct ActivityLog {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+}
+
+impl Default for ActivityLog {
+    fn default() -> Self {
+        ActivityLog::new(LOG_CAPACITY)
+    }
+}
+
+impl ActivityLog {
+    pub fn new(capacity: usize) -> ActivityLog {
+        ActivityLog { entries: VecDeque::with_capacity(capacity.min(LOG_CAPACITY)), capacity: capacity.max(1) }
+    }
+
+    /// Adds an entry; returns whether the oldest one was dropped to make room.
+    pub fn push(&mut self, time: &str, message: &str) -> bool {
+        let full = self.entries.len() == self.capacity;
+        if full {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry { time: time.to_string(), message: message.to_string() });
+        full
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// One "time  message" line per entry, oldest first.
+    pub fn to_text(&self) -> String {
+        self.entries.iter().map(|e| format!("{}  {}\n", e.time, e.message)).collect()
+    }
+}
+
+/// The quarter (25, 50 or 75 percent) that typing from `before` to `after`
+/// of `total` characters passed, if any.
+pub fn milestone(before: usize, after: usize, total: usize) -> Option<u32> {
+    if total == 0 {
+        return None;
+    }
+    [75, 50, 25].into_iter().find(|&percent| {
+        let mark = total * percent as usize / 100;
+        before < mark && after >= mark
+    })
+}
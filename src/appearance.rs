@@ -0,0 +1,43 @@
+// Light, dark or the desktop's style. Without libadwaita this goes through
+// GtkSettings: dark asks the theme for its dark variant, light additionally
+// switches a "-dark" theme to its light counterpart.
+
+use std::cell::OnceCell;
+use pasteclipboard::settings::Appearance;
+
+thread_local! {
+    /// The desktop's own prefer-dark flag and theme, to return to for System.
+    static DESKTOP: OnceCell<(bool, Option<String>)> = const { OnceCell::new() };
+}
+
+/// Applies `appearance`; call it before the first window is shown so it
+/// opens in the right style.
+pub fn apply(appearance: Appearance) {
+    let Some(settings) = gtk4::Settings::default() else {
+        return;
+    };
+    let (prefer_dark, theme) = DESKTOP.with(|desktop| {
+        desktop
+            .get_or_init(|| {
+                (settings.is_gtk_application_prefer_dark_theme(), settings.gtk_theme_name().map(|t| t.to_string()))
+            })
+            .clone()
+    });
+    let light_theme = theme.as_deref().and_then(|t| t.strip_suffix("-dark")).map(str::to_string);
+    match appearance {
+        Appearance::System => {
+            settings.set_gtk_application_prefer_dark_theme(prefer_dark);
+            settings.set_gtk_theme_name(theme.as_deref());
+        }
+        Appearance::Light => {
+            settings.set_gtk_application_prefer_dark_theme(false);
+            if light_theme.is_some() {
+                settings.set_gtk_theme_name(light_theme.as_deref());
+            }
+        }
+        Appearance::Dark => {
+            settings.set_gtk_theme_name(theme.as_deref());
+            settings.set_gtk_application_prefer_dark_theme(true);
+        }
+    }
+}
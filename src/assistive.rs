@@ -0,0 +1,171 @@
+//! Assistive mode (`assistive_mode`), for someone who enters long texts
+//! with this app as their main way of typing. A run then never types
+//! faster than [`ASSISTIVE_MIN_CHAR_DELAY_MS`], waits before each line
+//! after the first until the user confirms it, and stops to wait the same
+//! way whenever a physical keyboard or mouse is used while it types. The
+//! window confirms through [`Confirmations`]; the larger countdown overlay
+//! and getting the window out of the way are its part too.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use evdev_rs::enums::EventCode;
+use evdev_rs::{Device, DeviceWrapper, ReadFlag, ReadStatus};
+
+use crate::backend::UINPUT_DEVICE_NAME;
+use crate::typing::TypingError;
+
+/// The shortest pause between characters assistive mode allows; a lower
+/// `char_delay_ms` is raised to it.
+pub const ASSISTIVE_MIN_CHAR_DELAY_MS: u32 = 150;
+
+/// How long typing waits after a confirmation, for the focus to go back to
+/// the target window.
+pub const SETTLE_TIME: Duration = Duration::from_millis(1500);
+
+/// How often a waiting run looks at the cancel flag.
+const CONFIRM_CHECK: Duration = Duration::from_millis(20);
+
+const INPUT_DIR: &str = "/dev/input";
+
+/// What a run waits to be confirmed for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Wait {
+    /// The next line is line `line` (from 1) of `lines`, which a stream
+    /// does not know.
+    NextLine { line: usize, lines: Option<usize> },
+    /// A physical keyboard or mouse was used while it typed.
+    Input,
+    /// Line `line` (from 1) was typed and the Enter after it waits, in
+    /// confirm-before-Enter mode (`confirm_enter`); `preview` is the line,
+    /// shortened by [`line_preview`].
+    Enter { line: usize, preview: String },
+    /// A stream's `WAIT-ENTER` line.
+    Requested,
+}
+
+impl fmt::Display for Wait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Wait::NextLine { line, lines: Some(lines) } => {
+                write!(f, "Waiting for confirmation to type line {} of {}", line, lines)
+            }
+            Wait::NextLine { line, lines: None } => write!(f, "Waiting for confirmation to type line {}", line),
+            Wait::Input => write!(f, "Paused: a keyboard or mouse was used while typing"),
+            // Not the line itself, which would put it in the log.
+            Wait::Enter { line, .. } => write!(f, "Waiting for confirmation to press Enter after line {}", line),
+            Wait::Requested => write!(f, "Waiting for confirmation, as the stream asks"),
+        }
+    }
+}
+
+/// How many characters of a line [`line_preview`] keeps.
+pub const PREVIEW_CHARS: usize = 60;
+
+/// `line` shortened to [`PREVIEW_CHARS`] by leaving out its middle, so the
+/// end of a long command, often the part that matters, still shows.
+pub fn line_preview(line: &str) -> String {
+    let count = line.chars().count();
+    if count <= PREVIEW_CHARS {
+        return line.to_string();
+    }
+    let head = PREVIEW_CHARS * 2 / 3;
+    let tail = PREVIEW_CHARS - head - 1;
+    let mut preview: String = line.chars().take(head).collect();
+    preview.push('…');
+    preview.extend(line.chars().skip(count - tail));
+    preview
+}
+
+/// The window's answer to a waiting run. A confirmation given before the
+/// run asked counts for nothing, so a stray double click cannot let a line
+/// through unseen.
+#[derive(Debug, Default)]
+pub struct Confirmations {
+    confirmed: AtomicBool,
+}
+
+impl Confirmations {
+    /// Lets the waiting run go on.
+    pub fn confirm(&self) {
+        self.confirmed.store(true, Ordering::SeqCst);
+    }
+
+    /// Forgets earlier confirmations; then [`Confirmations::wait`] waits for
+    /// a new one.
+    pub(crate) fn reset(&self) {
+        self.confirmed.store(false, Ordering::SeqCst);
+    }
+
+    /// Waits for a confirmation, failing as cancelled after `typed`
+    /// characters when `cancel` is set.
+    pub(crate) fn wait(&self, cancel: &AtomicBool, typed: usize) -> Result<(), TypingError> {
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(TypingError::Cancelled { at_char: typed });
+            }
+            if self.confirmed.swap(false, Ordering::SeqCst) {
+                return Ok(());
+            }
+            thread::sleep(CONFIRM_CHECK);
+        }
+    }
+}
+
+/// The physical input devices this user may read, all but the app's own
+/// virtual keyboard. Most systems only let root and the `input` group read
+/// them, so there may be none.
+pub(crate) struct InputWatch {
+    devices: Vec<Device>,
+}
+
+impl InputWatch {
+    pub(crate) fn open() -> Self {
+        let mut devices = Vec::new();
+        let entries = fs::read_dir(INPUT_DIR).into_iter().flatten().filter_map(|entry| entry.ok());
+        for entry in entries.filter(|entry| entry.file_name().to_string_lossy().starts_with("event")) {
+            let Ok(file) = OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(entry.path()) else {
+                continue;
+            };
+            match Device::new_from_file(file) {
+                Ok(device) if device.name() != Some(UINPUT_DEVICE_NAME) => devices.push(device),
+                _ => {}
+            }
+        }
+        InputWatch { devices }
+    }
+
+    /// Whether it has a device to watch.
+    pub(crate) fn is_watching(&self) -> bool {
+        !self.devices.is_empty()
+    }
+
+    /// Whether a key, button, pointer or touch event arrived since the last
+    /// call. Reads everything waiting.
+    pub(crate) fn touched(&mut self) -> bool {
+        let mut touched = false;
+        for device in &self.devices {
+            loop {
+                match device.next_event(ReadFlag::NORMAL) {
+                    Ok((ReadStatus::Success, event)) => {
+                        touched |= matches!(
+                            event.event_code,
+                            EventCode::EV_KEY(_) | EventCode::EV_REL(_) | EventCode::EV_ABS(_)
+                        );
+                    }
+                    // Events were dropped; there were plenty of them.
+                    Ok((ReadStatus::Sync, _)) => {
+                        touched = true;
+                        while let Ok((ReadStatus::Sync, _)) = device.next_event(ReadFlag::SYNC) {}
+                    }
+                    Err(_) => break,
+                }
+            }
+        }
+        touched
+    }
+}
@@ -0,0 +1,192 @@
+// Assistive mode in the window: the header bar toggle that turns it on and
+// off and shows that it is on, and the large button a waiting run is
+// confirmed with, in assistive mode or at a stream's WAIT-ENTER line. It
+// is a single button that takes the keyboard focus when
+// a run waits, so one switch (Space or Enter) is enough to confirm; the
+// window then minimizes itself so the focus goes back to the target.
+// In confirm-before-Enter mode the window stays where it is, as the focus
+// should not leave the target between a line and its Enter: a
+// notification's button, or the D-Bus Confirm method bound to a hotkey,
+// confirms as well as the button.
+
+use gtk4::prelude::*;
+use gtk4::{gio::{Notification, SimpleAction}, Button, Label, Orientation, ToggleButton};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::Arc;
+use pasteclipboard::assistive::{Confirmations, Wait};
+use pasteclipboard::i18n::{args, gettext};
+use pasteclipboard::settings::{save_setting, Settings};
+
+use crate::window::MainWindow;
+
+const CONFIRM_NOTIFICATION_ID: &str = "confirm";
+
+pub struct AssistiveMode {
+    pub toggle: ToggleButton,
+    pub btn_confirm: Button,
+    lbl_confirm: Label,
+    /// The current run's, while there is one.
+    confirmations: RefCell<Option<Arc<Confirmations>>>,
+    /// Set while `refresh` changes the toggle.
+    updating: Cell<bool>,
+}
+
+impl AssistiveMode {
+    pub fn new() -> Self {
+        let content = gtk4::Box::new(Orientation::Horizontal, 6);
+        content.append(&gtk4::Image::from_icon_name("preferences-desktop-accessibility-symbolic"));
+        content.append(&Label::new(Some(&gettext("Assistive"))));
+        let toggle = ToggleButton::builder()
+            .child(&content)
+            .tooltip_text(gettext("Assistive mode: slow typing, a large countdown and a confirmation before each line"))
+            .build();
+
+        let lbl_confirm = Label::new(None);
+        lbl_confirm.set_wrap(true);
+        let btn_confirm = Button::builder().child(&lbl_confirm).visible(false).build();
+        btn_confirm.add_css_class("suggested-action");
+        btn_confirm.add_css_class("assistive-confirm");
+        AssistiveMode {
+            toggle,
+            btn_confirm,
+            lbl_confirm,
+            confirmations: RefCell::default(),
+            updating: Cell::new(false),
+        }
+    }
+
+    /// Follows `assistive_mode` in the settings in effect, e.g. after
+    /// switching profiles.
+    pub fn refresh(&self) {
+        let on = Settings::load().assistive_mode;
+        self.updating.set(true);
+        self.toggle.set_active(on);
+        self.updating.set(false);
+        if on {
+            self.toggle.add_css_class("assistive-on");
+        } else {
+            self.toggle.remove_css_class("assistive-on");
+        }
+    }
+
+    /// A run starts that `confirmations` answer.
+    pub fn start(&self, confirmations: Arc<Confirmations>) {
+        self.btn_confirm.set_visible(false);
+        *self.confirmations.borrow_mut() = Some(confirmations);
+    }
+
+    /// The run is over.
+    pub fn finish(&self, ui: &MainWindow) {
+        self.btn_confirm.set_visible(false);
+        self.confirmations.borrow_mut().take();
+        if let Some(app) = ui.window.application() {
+            app.withdraw_notification(CONFIRM_NOTIFICATION_ID);
+        }
+    }
+
+    /// Lets the waiting run go on; returns whether one was waiting.
+    pub fn confirm(&self, ui: &MainWindow) -> bool {
+        let Some(confirmations) = self.confirmations.borrow().clone() else {
+            return false;
+        };
+        if !self.btn_confirm.is_visible() {
+            return false;
+        }
+        self.btn_confirm.set_visible(false);
+        if let Some(app) = ui.window.application() {
+            app.withdraw_notification(CONFIRM_NOTIFICATION_ID);
+        }
+        ui.status.info(&gettext("Typing goes on shortly..."));
+        // The focus goes back to the window that had it before ours.
+        if ui.window.is_active() {
+            ui.minimize_for_run();
+        }
+        confirmations.confirm();
+        true
+    }
+
+    /// Shows the confirm button for what the run waits for and gives it the
+    /// focus; before an Enter, see [`AssistiveMode::ask_enter`].
+    pub fn ask(&self, ui: &MainWindow, wait: &Wait, secret: bool) {
+        let text = match wait {
+            Wait::NextLine { line, lines: Some(lines) } => {
+                args(&gettext("Type line {0} of {1}"), &[&line.to_string(), &lines.to_string()])
+            }
+            Wait::NextLine { line, lines: None } => args(&gettext("Type line {0}"), &[&line.to_string()]),
+            Wait::Input => gettext("A keyboard or mouse was used. Resume typing"),
+            Wait::Requested => gettext("Continue typing"),
+            Wait::Enter { line, preview } => return self.ask_enter(ui, *line, preview, secret),
+        };
+        ui.status.info(&gettext("Waiting for you to confirm; typing is paused."));
+        self.show_button(&text);
+        ui.window.present();
+        self.btn_confirm.grab_focus();
+    }
+
+    /// Asks to confirm the Enter after line `line`, in the status line and
+    /// a notification, leaving the focus in the target; a `secret` run's
+    /// line is not shown.
+    fn ask_enter(&self, ui: &MainWindow, line: usize, preview: &str, secret: bool) {
+        let line = line.to_string();
+        let shown = (!secret && !preview.is_empty()).then_some(preview);
+        ui.status.info(&match shown {
+            Some(preview) => args(&gettext("Line {0} is typed; confirm to press Enter: {1}"), &[&line, &preview]),
+            None => args(&gettext("Line {0} is typed; confirm to press Enter."), &[&line]),
+        });
+        self.show_button(&args(&gettext("Press Enter after line {0}"), &[&line]));
+        let Some(app) = ui.window.application() else {
+            return;
+        };
+        let notification = Notification::new(&gettext("Press Enter?"));
+        notification.set_body(Some(&match shown {
+            Some(preview) => preview.to_string(),
+            None => args(&gettext("Line {0} is typed."), &[&line]),
+        }));
+        notification.add_button(&gettext("Press Enter"), "app.confirm-run");
+        app.send_notification(Some(CONFIRM_NOTIFICATION_ID), &notification);
+    }
+
+    fn show_button(&self, text: &str) {
+        let text = gtk4::glib::markup_escape_text(text);
+        self.lbl_confirm.set_markup(&format!("<span size=\"200%\" weight=\"bold\">{}</span>", text));
+        self.btn_confirm.set_visible(true);
+    }
+}
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let weak = Rc::downgrade(ui);
+    ui.assistive.toggle.connect_toggled(move |toggle| {
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        if ui.assistive.updating.get() {
+            return;
+        }
+        let on = toggle.is_active();
+        save_setting("assistive_mode", if on { "true" } else { "false" });
+        ui.log.add(if on { "Assistive mode is on." } else { "Assistive mode is off." });
+        ui.settings_changed();
+    });
+
+    let weak = Rc::downgrade(ui);
+    ui.assistive.btn_confirm.connect_clicked(move |_| {
+        if let Some(ui) = weak.upgrade() {
+            ui.assistive.confirm(&ui);
+        }
+    });
+
+    // The notification's button. Anything on the session bus can activate
+    // it, as it can call Confirm; either only lets a waiting run go on.
+    if let Some(app) = ui.window.application() {
+        let action = SimpleAction::new("confirm-run", None);
+        let weak = Rc::downgrade(ui);
+        action.connect_activate(move |_, _| {
+            if let Some(ui) = weak.upgrade() {
+                ui.assistive.confirm(&ui);
+            }
+        });
+        app.add_action(&action);
+    }
+    ui.assistive.refresh();
+}
@@ -0,0 +1,329 @@
+//! Auto-type sequences, as password managers have them: a snippet with
+//! auto-type turned on spells out keys and login fields in braces, like
+//! `{USERNAME}{TAB}{PASSWORD}{ENTER}`, with `{DELAY 500}` for a pause of
+//! that many milliseconds (login forms often need one after Tab). `{{}` and
+//! `{}}` stand for literal braces. The fields are filled in right before
+//! the run, from the login saved with the snippet or from a prompt, and
+//! their values are wiped from memory once the run is done with them.
+//! `{TOTP}` types a one-time code from the seed saved with the login (see
+//! `totp`), worked out only when typing gets to it, and `{PASS:work/vpn}`
+//! the password `pass` has for that entry (see `pass`), fetched before the
+//! run so a failure leaves nothing half typed. `{HOTP:vpn}` types the next
+//! counter-based code of the token saved as `vpn` (see `hotp`); its counter
+//! moves on only once the run has typed to the end.
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use zeroize::Zeroizing;
+
+use crate::hotp::{self, Hotp};
+use crate::pass::{pass_show, validate_path};
+use crate::totp::{CodeSlot, Totp};
+use crate::typing::MAX_CHAR_DELAY_MS;
+
+/// A login field filled in at run time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Username,
+    Password,
+}
+
+impl Field {
+    /// The name it has in a sequence, between braces.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Username => "USERNAME",
+            Field::Password => "PASSWORD",
+        }
+    }
+}
+
+/// One piece of a sequence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    /// Typed as written; `{TAB}` and `{ENTER}` become a tab and a line break.
+    Text(String),
+    Field(Field),
+    /// A wait before whatever comes next.
+    Delay(Duration),
+    /// The current code of the login's TOTP seed.
+    Totp,
+    /// The password of this `pass` entry.
+    Pass(String),
+    /// The next code of the HOTP token with this label.
+    Hotp(String),
+}
+
+/// Splits an auto-type sequence into its tokens, or says which brace it
+/// cannot read.
+pub fn parse_sequence(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find('{') {
+        literal.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(after) = rest.strip_prefix("{{}") {
+            literal.push('{');
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("{}}") {
+            literal.push('}');
+            rest = after;
+            continue;
+        }
+        let Some(end) = rest.find('}') else {
+            bail!("The auto-type sequence has a {{ without its }} (type {{{{}} for a literal brace)");
+        };
+        let inner = rest[1..end].trim();
+        let pass = inner.get(..5).filter(|prefix| prefix.eq_ignore_ascii_case("PASS:"));
+        if let Some(path) = pass.map(|_| inner[5..].trim()) {
+            validate_path(path)?;
+            if !literal.is_empty() {
+                tokens.push(Token::Text(std::mem::take(&mut literal)));
+            }
+            tokens.push(Token::Pass(path.to_string()));
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let hotp = inner.get(..5).filter(|prefix| prefix.eq_ignore_ascii_case("HOTP:"));
+        if let Some(label) = hotp.map(|_| inner[5..].trim()) {
+            hotp::validate_label(label)?;
+            if !literal.is_empty() {
+                tokens.push(Token::Text(std::mem::take(&mut literal)));
+            }
+            tokens.push(Token::Hotp(label.to_string()));
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let mut words = inner.split_whitespace();
+        let token = match (words.next().map(str::to_ascii_uppercase).as_deref(), words.next(), words.next()) {
+            (Some("USERNAME"), None, _) => Token::Field(Field::Username),
+            (Some("PASSWORD"), None, _) => Token::Field(Field::Password),
+            (Some("TAB"), None, _) => Token::Text("\t".to_string()),
+            (Some("ENTER"), None, _) => Token::Text("\n".to_string()),
+            (Some("TOTP"), None, _) => Token::Totp,
+            (Some("DELAY"), Some(ms), None) => match ms.parse::<u32>() {
+                Ok(ms) if ms <= MAX_CHAR_DELAY_MS => Token::Delay(Duration::from_millis(u64::from(ms))),
+                _ => bail!("Invalid {{{}}} (the pause must be a number from 0–{} ms)", inner, MAX_CHAR_DELAY_MS),
+            },
+            _ => bail!("Unknown auto-type key {{{}}} (expected USERNAME, PASSWORD, TOTP, HOTP:<label>, PASS:<entry>, TAB, ENTER or DELAY <ms>)", inner),
+        };
+        match token {
+            Token::Text(text) => literal.push_str(&text),
+            token => {
+                if !literal.is_empty() {
+                    tokens.push(Token::Text(std::mem::take(&mut literal)));
+                }
+                tokens.push(token);
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        tokens.push(Token::Text(literal));
+    }
+    Ok(tokens)
+}
+
+/// The fields `tokens` use, each once, in order of first appearance.
+pub fn fields(tokens: &[Token]) -> Vec<Field> {
+    let mut fields = Vec::new();
+    for token in tokens {
+        if let Token::Field(field) = token {
+            if !fields.contains(field) {
+                fields.push(*field);
+            }
+        }
+    }
+    fields
+}
+
+/// The `pass` entries `tokens` use, each once, in order of first
+/// appearance.
+pub fn pass_paths(tokens: &[Token]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for token in tokens {
+        if let Token::Pass(path) = token {
+            if !paths.contains(path) {
+                paths.push(path.clone());
+            }
+        }
+    }
+    paths
+}
+
+/// The HOTP labels `tokens` use, each once, in order of first appearance.
+pub fn hotp_labels(tokens: &[Token]) -> Vec<String> {
+    let mut labels = Vec::new();
+    for token in tokens {
+        if let Token::Hotp(label) = token {
+            if !labels.contains(label) {
+                labels.push(label.clone());
+            }
+        }
+    }
+    labels
+}
+
+/// Runs `command show` for each of `paths`, stopping at the first that
+/// fails. It blocks until they are all done.
+pub fn fetch_pass(command: &str, paths: &[String]) -> Result<Vec<(String, Zeroizing<String>)>> {
+    paths.iter().map(|path| Ok((path.clone(), pass_show(command, path)?))).collect()
+}
+
+/// Values for the login fields, the seed `{TOTP}` codes come from, the
+/// passwords fetched from `pass` and the HOTP tokens loaded from the
+/// keyring; they are wiped when dropped.
+#[derive(Default)]
+pub struct Credentials {
+    pub username: Option<Zeroizing<String>>,
+    pub password: Option<Zeroizing<String>>,
+    pub totp_seed: Option<Zeroizing<String>>,
+    /// By entry path.
+    pub pass: Vec<(String, Zeroizing<String>)>,
+    /// By label.
+    pub hotp: Vec<(String, Hotp)>,
+}
+
+impl Credentials {
+    pub fn get(&self, field: Field) -> Option<&str> {
+        match field {
+            Field::Username => self.username.as_deref().map(String::as_str),
+            Field::Password => self.password.as_deref().map(String::as_str),
+        }
+    }
+
+    pub fn set(&mut self, field: Field, value: Zeroizing<String>) {
+        match field {
+            Field::Username => self.username = Some(value),
+            Field::Password => self.password = Some(value),
+        }
+    }
+
+    /// The generator for `{TOTP}`, from the saved seed.
+    pub fn totp(&self) -> Result<Totp> {
+        match &self.totp_seed {
+            Some(seed) => Totp::parse(seed),
+            None => bail!("No TOTP seed is saved for {{TOTP}}; add one in the snippet's properties"),
+        }
+    }
+
+    /// The password fetched for the `pass` entry at `path`.
+    pub fn get_pass(&self, path: &str) -> Option<&str> {
+        self.pass.iter().find(|(entry, _)| entry == path).map(|(_, value)| value.as_str())
+    }
+
+    /// The HOTP token loaded for `label`.
+    pub fn get_hotp(&self, label: &str) -> Option<&Hotp> {
+        self.hotp.iter().find(|(saved, _)| saved == label).map(|(_, token)| token)
+    }
+
+    /// What the activity log may say about a field: its name and length,
+    /// never the value.
+    pub fn describe(&self, field: Field) -> String {
+        described(field.name(), self.get(field))
+    }
+
+    /// The same for a `pass` entry, by its path.
+    pub fn describe_pass(&self, path: &str) -> String {
+        described(&format!("PASS:{}", path), self.get_pass(path))
+    }
+}
+
+fn described(name: &str, value: Option<&str>) -> String {
+    let chars = value.map_or(0, |value| value.chars().count());
+    format!("placeholder {} ({} char{})", name, chars, if chars == 1 { "" } else { "s" })
+}
+
+/// A sequence with its fields filled in: the text to type, the pauses
+/// before characters of it (see `TypingOptions::pauses`) and where its
+/// TOTP codes go (see `TypingOptions::codes`), and the counter each HOTP
+/// token moves on to once the run is done.
+pub struct Resolved {
+    pub text: Zeroizing<String>,
+    pub pauses: Vec<(usize, Duration)>,
+    pub codes: Vec<CodeSlot>,
+    pub hotp: Vec<(String, u64)>,
+}
+
+/// What `token` types, if anything.
+fn value<'a>(token: &'a Token, credentials: &'a Credentials) -> Result<Option<&'a str>> {
+    match token {
+        Token::Text(value) => Ok(Some(value)),
+        Token::Field(field) => match credentials.get(*field) {
+            Some(value) => Ok(Some(value)),
+            None => bail!("No value for {{{}}}", field.name()),
+        },
+        Token::Pass(path) => match credentials.get_pass(path) {
+            Some(value) => Ok(Some(value)),
+            None => bail!("No value for {{PASS:{}}}", path),
+        },
+        Token::Delay(_) | Token::Totp | Token::Hotp(_) => Ok(None),
+    }
+}
+
+/// Fills `tokens` in from `credentials`, which must have every field they
+/// use, the TOTP seed if they use `{TOTP}` and each HOTP token they use.
+/// Each TOTP code is left as a row of zeros for the run to type over; HOTP
+/// codes are typed as they are, a label used twice getting two codes.
+pub fn resolve(tokens: &[Token], credentials: &Credentials) -> Result<Resolved> {
+    let totp = if tokens.contains(&Token::Totp) { Some(credentials.totp()?) } else { None };
+    let placeholder = "0".repeat(totp.as_ref().map_or(0, |totp| totp.digits as usize));
+    let mut counters: Vec<(String, u64)> = Vec::new();
+    let mut hotp_codes = Vec::new();
+    for label in tokens.iter().filter_map(|token| if let Token::Hotp(label) = token { Some(label) } else { None }) {
+        let Some(token) = credentials.get_hotp(label) else {
+            bail!("No HOTP token is loaded for {{HOTP:{}}}", label);
+        };
+        let index = match counters.iter().position(|(saved, _)| saved == label) {
+            Some(index) => index,
+            None => {
+                counters.push((label.clone(), token.counter));
+                counters.len() - 1
+            }
+        };
+        let counter = &mut counters[index].1;
+        hotp_codes.push(token.code(*counter));
+        *counter += 1;
+    }
+    let mut len = hotp_codes.iter().map(|code| code.len()).sum();
+    for token in tokens {
+        len += match token {
+            Token::Totp => placeholder.len(),
+            token => value(token, credentials)?.map_or(0, str::len),
+        };
+    }
+    // Room for all of it up front, so no copy of a value is left behind
+    // unwiped when the string grows.
+    let mut text = Zeroizing::new(String::with_capacity(len));
+    let mut chars = 0;
+    let mut pauses: Vec<(usize, Duration)> = Vec::new();
+    let mut codes = Vec::new();
+    let mut hotp_codes = hotp_codes.iter();
+    for token in tokens {
+        let value = match token {
+            Token::Delay(delay) => {
+                match pauses.last_mut() {
+                    Some((at, pause)) if *at == chars => *pause += *delay,
+                    _ => pauses.push((chars, *delay)),
+                }
+                continue;
+            }
+            Token::Totp => {
+                if let Some(totp) = &totp {
+                    codes.push(CodeSlot { at: chars, totp: totp.clone() });
+                }
+                placeholder.as_str()
+            }
+            Token::Hotp(_) => hotp_codes.next().map_or("", |code| code.as_str()),
+            token => value(token, credentials)?.unwrap_or_default(),
+        };
+        text.push_str(value);
+        chars += value.chars().count();
+    }
+    Ok(Resolved { text, pauses, codes, hotp: counters })
+}
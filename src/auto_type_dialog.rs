@@ -0,0 +1,111 @@
+// Asks for the login fields an auto-type snippet needs and has no saved
+// value for, the password masked, right before the run. Nothing entered
+// here is kept.
+
+use gtk4::prelude::*;
+use gtk4::{Button, Entry, Grid, Label, Orientation, PasswordEntry, Window};
+use std::cell::Cell;
+use std::rc::Rc;
+use pasteclipboard::auto_type::{Credentials, Field};
+use zeroize::Zeroizing;
+
+use crate::window::MainWindow;
+
+/// Shows the dialog for snippet `name`'s `fields` and calls `then` with
+/// their values once the user confirms.
+pub fn show(
+    ui: &Rc<MainWindow>,
+    name: &str,
+    fields: Vec<Field>,
+    then: impl FnOnce(&Rc<MainWindow>, Credentials) + 'static,
+) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title(format!("Log in with '{}'", name))
+        .default_width(380)
+        .build();
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let grid = Grid::builder().row_spacing(6).column_spacing(12).build();
+    let entries: Vec<(Field, gtk4::Editable)> = fields
+        .into_iter()
+        .enumerate()
+        .map(|(row, field)| {
+            let (text, entry): (&str, gtk4::Editable) = match field {
+                Field::Username => ("User name:", Entry::builder().activates_default(true).build().upcast()),
+                Field::Password => {
+                    ("Password:", PasswordEntry::builder().activates_default(true).show_peek_icon(true).build().upcast())
+                }
+            };
+            entry.set_hexpand(true);
+            let label = Label::new(Some(text));
+            label.set_xalign(0.0);
+            grid.attach(&label, 0, row as i32, 1, 1);
+            grid.attach(&entry, 1, row as i32, 1, 1);
+            (field, entry)
+        })
+        .collect();
+    vbox.append(&grid);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label("Cancel");
+    let btn_type = Button::with_label("Type");
+    btn_type.add_css_class("suggested-action");
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_type);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_type));
+
+    // Every field needs a value before the run may start.
+    let entries = Rc::new(entries);
+    let update = {
+        let entries = entries.clone();
+        let btn_type = btn_type.clone();
+        move || btn_type.set_sensitive(entries.iter().all(|(_, entry)| !entry.text().is_empty()))
+    };
+    update();
+    for (_, entry) in entries.iter() {
+        let update = update.clone();
+        entry.connect_changed(move |_| update());
+    }
+
+    if let Some((_, first)) = entries.first() {
+        first.grab_focus();
+    }
+
+    // The entries are emptied as the dialog closes, whichever way it does.
+    let entries_clone = entries.clone();
+    dialog.connect_close_request(move |_| {
+        for (_, entry) in entries_clone.iter() {
+            entry.set_text("");
+        }
+        gtk4::glib::Propagation::Proceed
+    });
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    let ui = ui.clone();
+    let then = Cell::new(Some(then));
+    let dialog_clone = dialog.clone();
+    btn_type.connect_clicked(move |_| {
+        let Some(then) = then.take() else {
+            return;
+        };
+        let mut credentials = Credentials::default();
+        for (field, entry) in entries.iter() {
+            credentials.set(*field, Zeroizing::new(entry.text().to_string()));
+        }
+        dialog_clone.close();
+        then(&ui, credentials);
+    });
+
+    dialog.present();
+}
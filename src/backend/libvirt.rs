@@ -0,0 +1,150 @@
+//! Typing straight into a local libvirt domain with `virsh send-key`,
+//! bypassing the VM console window, whose focus and grab handling can drop
+//! keys. evdev key codes are libvirt's `linux` code set, so the keymap's
+//! keys go through unchanged; each character is one `virsh send-key` with
+//! Shift pressed along with the key when it needs it, held for
+//! `libvirt_hold_ms`. The domain is looked up when the run connects, during
+//! the countdown, so a missing domain or an unreachable libvirt ends the run
+//! before it would start typing.
+
+use std::io;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use evdev_rs::enums::EV_KEY;
+
+use super::{Availability, Keyboard};
+use crate::command::run_program;
+use crate::readback::Chord;
+use crate::settings::Settings;
+
+/// How long each key is held when `libvirt_hold_ms` is not set.
+pub const DEFAULT_LIBVIRT_HOLD_MS: u64 = 50;
+
+/// Longest accepted `libvirt_hold_ms`.
+pub const MAX_LIBVIRT_HOLD_MS: u64 = 1000;
+
+/// How long one `virsh` call may take.
+const VIRSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and how to type, from config.toml.
+struct Target {
+    domain: String,
+    /// The connection URI, such as `qemu:///system`; virsh's default when
+    /// unset.
+    uri: Option<String>,
+    hold_ms: u64,
+}
+
+impl Target {
+    fn load() -> Result<Self> {
+        let settings = Settings::load();
+        if settings.libvirt_domain.is_empty() {
+            bail!("Set libvirt_domain in config.toml to the name of the virtual machine to type into");
+        }
+        let uri = Some(settings.libvirt_uri).filter(|uri| !uri.is_empty());
+        Ok(Target { domain: settings.libvirt_domain, uri, hold_ms: settings.libvirt_hold_ms })
+    }
+
+    /// Runs `virsh` against the configured connection.
+    fn virsh(&self, args: &[String]) -> Result<String> {
+        let mut argv = vec!["virsh".to_string()];
+        if let Some(uri) = &self.uri {
+            argv.extend(["--connect".to_string(), uri.clone()]);
+        }
+        argv.extend(args.iter().cloned());
+        let output = run_program(&argv, None, VIRSH_TIMEOUT).map_err(|e| self.describe(e))?;
+        Ok(String::from_utf8_lossy(&output).trim().to_string())
+    }
+
+    /// Says what went wrong in the terms of the setting to fix.
+    fn describe(&self, error: anyhow::Error) -> anyhow::Error {
+        let missing = |e: &(dyn std::error::Error + 'static)| {
+            e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+        };
+        if error.chain().any(missing) {
+            return anyhow!("virsh is not installed (it comes with libvirt-clients or libvirt)");
+        }
+        let text = format!("{:#}", error);
+        let uri = self.uri.as_deref().unwrap_or("virsh's default connection");
+        if text.contains("failed to get domain") || text.contains("Domain not found") {
+            anyhow!("There is no libvirt domain '{}' at {} (check libvirt_domain and libvirt_uri)", self.domain, uri)
+        } else if text.contains("failed to connect to the hypervisor") {
+            anyhow!("Cannot connect to libvirt at {}: {}", uri, last_line(&text))
+        } else {
+            error
+        }
+    }
+
+    /// The domain's state as virsh names it, e.g. `running` or `shut off`.
+    fn state(&self) -> Result<String> {
+        self.virsh(&["domstate".to_string(), self.domain.clone()])
+    }
+}
+
+/// virsh's error messages end with the part that says what happened.
+fn last_line(text: &str) -> &str {
+    text.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or(text).trim()
+}
+
+pub fn probe() -> Availability {
+    let target = match Target::load() {
+        Ok(target) => target,
+        Err(e) => return Availability::unavailable(format!("{}", e)),
+    };
+    match target.state() {
+        Ok(state) if state == "running" => Availability::available(format!("domain '{}' is running", target.domain)),
+        Ok(state) => Availability::unavailable(format!("domain '{}' is {}", target.domain, state)),
+        Err(e) => Availability::unavailable(format!("{:#}", e)),
+    }
+}
+
+pub struct LibvirtKeyboard {
+    target: Target,
+}
+
+impl LibvirtKeyboard {
+    /// Checks that the configured domain exists and is running.
+    pub fn open() -> Result<Self> {
+        let target = Target::load()?;
+        let state = target.state()?;
+        if state != "running" {
+            bail!("The libvirt domain '{}' is {}, not running", target.domain, state);
+        }
+        Ok(LibvirtKeyboard { target })
+    }
+
+    /// `virsh send-key`'s arguments up to the key codes.
+    fn send_key_args(&self) -> Vec<String> {
+        vec![
+            "send-key".to_string(),
+            self.target.domain.clone(),
+            "--codeset".to_string(),
+            "linux".to_string(),
+            "--holdtime".to_string(),
+            self.target.hold_ms.to_string(),
+        ]
+    }
+
+    fn send_keys(&self, args: Vec<String>) -> io::Result<()> {
+        self.target.virsh(&args).map(|_| ()).map_err(|e| io::Error::other(format!("{:#}", e)))
+    }
+}
+
+impl Keyboard for LibvirtKeyboard {
+    fn type_key(&mut self, _c: char, key: EV_KEY, shift: bool) -> io::Result<()> {
+        let mut args = self.send_key_args();
+        if shift {
+            args.push((EV_KEY::KEY_LEFTSHIFT as u32).to_string());
+        }
+        args.push((key as u32).to_string());
+        self.send_keys(args)
+    }
+
+    fn press_chord(&mut self, chord: &Chord) -> io::Result<()> {
+        // virsh presses the keys in order and releases them together.
+        let mut args = self.send_key_args();
+        args.extend(chord.keys().map(|key| (key as u32).to_string()));
+        self.send_keys(args)
+    }
+}
@@ -0,0 +1,235 @@
+//! Typing backends: the ways PasteClipboard can deliver key presses, how to
+//! tell which of them work in the current session, and which one to use.
+
+mod libvirt;
+mod portal;
+mod uinput;
+#[cfg(feature = "x11-xdo")]
+mod xdo;
+
+use std::ffi::c_ulong;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use evdev_rs::enums::EV_KEY;
+use serde::Serialize;
+
+use crate::readback::Chord;
+use crate::typing::TypingError;
+
+pub use libvirt::{DEFAULT_LIBVIRT_HOLD_MS, MAX_LIBVIRT_HOLD_MS};
+pub use uinput::{EventRecorder, UINPUT_DEVICE_NAME};
+
+/// Something that can type a single mapped character into the focused window.
+pub trait Keyboard {
+    fn type_key(&mut self, c: char, key: EV_KEY, shift: bool) -> io::Result<()>;
+
+    /// Presses a shortcut, such as the Ctrl+A and Ctrl+C a run that is read
+    /// back ends with.
+    fn press_chord(&mut self, chord: &Chord) -> io::Result<()>;
+}
+
+/// The errors writes to a virtual device fail with once it is gone, e.g.
+/// after the compositor restarted. std has no kind for them.
+const DEVICE_LOST_ERRNOS: [i32; 2] = [libc::ENODEV, libc::EIO];
+
+/// Whether a key press failed because the device is gone, so a new one may
+/// work where this one cannot.
+pub fn is_device_lost(error: &io::Error) -> bool {
+    error.raw_os_error().is_some_and(|errno| DEVICE_LOST_ERRNOS.contains(&errno))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// A virtual keyboard created through /dev/uinput; works everywhere the
+    /// device is writable.
+    Uinput,
+    /// libxdo's XTest typing; X11 sessions only, and only built with the
+    /// x11-xdo feature.
+    #[cfg(feature = "x11-xdo")]
+    Xdo,
+    /// The xdg-desktop-portal RemoteDesktop interface; asks the user for
+    /// permission on every run.
+    Portal,
+    /// `virsh send-key` into the libvirt domain `libvirt_domain`; only used
+    /// when configured, never picked automatically.
+    Libvirt,
+}
+
+impl Backend {
+    /// All backends built in, in the order automatic selection tries them
+    /// (see [`Backend::automatic`]).
+    pub const ALL: &'static [Backend] = &[
+        Backend::Uinput,
+        #[cfg(feature = "x11-xdo")]
+        Backend::Xdo,
+        Backend::Portal,
+        Backend::Libvirt,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Uinput => "uinput",
+            #[cfg(feature = "x11-xdo")]
+            Backend::Xdo => "xdo",
+            Backend::Portal => "portal",
+            Backend::Libvirt => "libvirt",
+        }
+    }
+
+    /// Whether automatic selection may pick it. libvirt types into one
+    /// virtual machine rather than the focused window, so it must be asked
+    /// for.
+    pub fn automatic(self) -> bool {
+        self != Backend::Libvirt
+    }
+
+    /// Checks whether this backend can be used right now, without typing anything.
+    pub fn probe(self) -> Availability {
+        match self {
+            Backend::Uinput => uinput::probe(),
+            #[cfg(feature = "x11-xdo")]
+            Backend::Xdo => xdo::probe(),
+            Backend::Portal => portal::probe(),
+            Backend::Libvirt => libvirt::probe(),
+        }
+    }
+
+    /// Prepares the backend for a run. This may take a while (device
+    /// warm-up, retries, permission dialogs), so call it from the worker
+    /// thread. With `keys`, a virtual device only gets those keys rather
+    /// than every supported one, and with `timestamps` its events carry the
+    /// time they were written. `log` hears about retries.
+    pub fn connect(
+        self,
+        keys: Option<&[EV_KEY]>,
+        timestamps: bool,
+        log: &dyn Fn(String),
+    ) -> Result<Box<dyn Keyboard>, TypingError> {
+        let backend_error = |e: anyhow::Error| TypingError::Backend(format!("{:#}", e));
+        Ok(match self {
+            Backend::Uinput => Box::new(uinput::UinputKeyboard::create(keys, timestamps, log)?),
+            #[cfg(feature = "x11-xdo")]
+            Backend::Xdo => Box::new(xdo::XdoKeyboard::open().map_err(backend_error)?),
+            Backend::Portal => Box::new(portal::PortalKeyboard::start().map_err(backend_error)?),
+            Backend::Libvirt => Box::new(libvirt::LibvirtKeyboard::open().map_err(backend_error)?),
+        })
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim();
+        if let Some(backend) = Backend::ALL.iter().find(|b| b.name().eq_ignore_ascii_case(name)) {
+            return Ok(*backend);
+        }
+        if cfg!(not(feature = "x11-xdo")) && name.eq_ignore_ascii_case("xdo") {
+            bail!("This build has no xdo backend (it was built without the x11-xdo feature)");
+        }
+        let names: Vec<&str> = Backend::ALL.iter().map(|b| b.name()).collect();
+        bail!("Unknown backend '{}' (expected {})", name, names.join(", "))
+    }
+}
+
+/// Result of probing a backend, with a short human-readable reason.
+#[derive(Clone, Debug, Serialize)]
+pub struct Availability {
+    pub available: bool,
+    pub detail: String,
+}
+
+impl Availability {
+    pub fn available(detail: impl Into<String>) -> Self {
+        Availability { available: true, detail: detail.into() }
+    }
+
+    pub fn unavailable(detail: impl Into<String>) -> Self {
+        Availability { available: false, detail: detail.into() }
+    }
+}
+
+impl fmt::Display for Availability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.available { "available" } else { "unavailable" };
+        write!(f, "{} ({})", state, self.detail)
+    }
+}
+
+/// The title of the window that has the keyboard focus. Only X11 lets a
+/// client ask; elsewhere this fails.
+#[cfg(feature = "x11-xdo")]
+pub fn focused_window_title() -> Result<String> {
+    xdo::focused_window_title()
+}
+
+#[cfg(not(feature = "x11-xdo"))]
+pub fn focused_window_title() -> Result<String> {
+    bail!("The focused window's title can only be checked by a build with the x11-xdo feature")
+}
+
+/// An application's window, as the window picker lists it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopLevelWindow {
+    /// The X window id.
+    pub id: c_ulong,
+    pub title: String,
+}
+
+/// The other applications' windows that have a title, top-most first.
+/// Only X11 lets a client list them; elsewhere this fails.
+#[cfg(feature = "x11-xdo")]
+pub fn top_level_windows() -> Result<Vec<TopLevelWindow>> {
+    xdo::top_level_windows()
+}
+
+#[cfg(not(feature = "x11-xdo"))]
+pub fn top_level_windows() -> Result<Vec<TopLevelWindow>> {
+    bail!("Windows can only be listed by a build with the x11-xdo feature")
+}
+
+/// Brings the window with id `window` to the front and waits until it has
+/// the focus; fails if it is gone or does not get it.
+#[cfg(feature = "x11-xdo")]
+pub fn activate_window(window: c_ulong) -> Result<()> {
+    xdo::activate_window(window)
+}
+
+#[cfg(not(feature = "x11-xdo"))]
+pub fn activate_window(_window: c_ulong) -> Result<()> {
+    bail!("Windows can only be brought to the front by a build with the x11-xdo feature")
+}
+
+/// Probes every backend, in selection order.
+pub fn probe_all() -> Vec<(Backend, Availability)> {
+    Backend::ALL.iter().map(|b| (*b, b.probe())).collect()
+}
+
+/// The backend automatic selection picks from a `probe_all` result.
+pub fn first_available(statuses: &[(Backend, Availability)]) -> Option<Backend> {
+    statuses.iter().find(|(b, status)| b.automatic() && status.available).map(|(b, _)| *b)
+}
+
+/// Picks the backend for a run: the configured one, or with `None` the
+/// first available one in `Backend::ALL` order.
+pub fn select_backend(preferred: Option<Backend>) -> Result<Backend> {
+    if let Some(backend) = preferred {
+        return Ok(backend);
+    }
+    let statuses = probe_all();
+    if let Some(backend) = first_available(&statuses) {
+        return Ok(backend);
+    }
+    let reasons: Vec<String> = statuses.iter().map(|(b, status)| format!("{}: {}", b, status.detail)).collect();
+    bail!("No typing backend is available ({})", reasons.join("; "))
+}
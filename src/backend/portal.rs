@@ -0,0 +1,235 @@
+//! Keyboard input through the xdg-desktop-portal RemoteDesktop interface.
+//!
+//! Every run opens a portal session, which makes the desktop ask the user to
+//! allow keyboard control. The portal's request/response pattern is driven
+//! synchronously from the worker thread with a private main context.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use evdev_rs::enums::EV_KEY;
+use gio::glib::{self, variant::ObjectPath, MainContext, Variant, VariantDict};
+use gio::prelude::*;
+use gio::{BusType, DBusCallFlags, DBusConnection, DBusSignalFlags};
+
+use super::{Availability, Keyboard};
+use crate::readback::Chord;
+
+const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const REMOTE_DESKTOP: &str = "org.freedesktop.portal.RemoteDesktop";
+/// The KEYBOARD bit of the portal's device type mask.
+const DEVICE_KEYBOARD: u32 = 1;
+
+fn session_bus() -> Result<DBusConnection> {
+    gio::bus_get_sync(BusType::Session, gio::Cancellable::NONE).context("Failed to connect to the session bus")
+}
+
+fn available_device_types(conn: &DBusConnection) -> Result<u32> {
+    let reply = conn.call_sync(
+        Some(PORTAL_BUS_NAME),
+        PORTAL_PATH,
+        "org.freedesktop.DBus.Properties",
+        "Get",
+        Some(&(REMOTE_DESKTOP, "AvailableDeviceTypes").to_variant()),
+        None,
+        DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+    )?;
+    let (value,): (Variant,) = reply.get().ok_or_else(|| anyhow!("Unexpected reply from the portal"))?;
+    value.get::<u32>().ok_or_else(|| anyhow!("Unexpected AvailableDeviceTypes type"))
+}
+
+pub fn probe() -> Availability {
+    let types = session_bus().and_then(|conn| available_device_types(&conn));
+    match types {
+        Ok(types) if types & DEVICE_KEYBOARD != 0 => Availability::available("RemoteDesktop portal"),
+        Ok(_) => Availability::unavailable("the RemoteDesktop portal does not offer keyboard input"),
+        Err(_) => Availability::unavailable("no RemoteDesktop portal on the session bus"),
+    }
+}
+
+pub struct PortalKeyboard {
+    conn: DBusConnection,
+    session: String,
+}
+
+impl PortalKeyboard {
+    /// Creates a session, selects keyboard input and starts it, which shows
+    /// the desktop's permission dialog.
+    pub fn start() -> Result<Self> {
+        let conn = session_bus()?;
+        let context = MainContext::new();
+        context
+            .with_thread_default(|| -> Result<Self> {
+                let results = request(&conn, &context, "CreateSession", |options| {
+                    options.insert("session_handle_token", "pasteclipboard");
+                    vec![]
+                })?;
+                let session: String = results
+                    .get("session_handle")
+                    .and_then(|v| v.get::<String>())
+                    .ok_or_else(|| anyhow!("The portal did not return a session handle"))?;
+                let keyboard = PortalKeyboard { conn: conn.clone(), session };
+
+                let session_path = keyboard.session_path()?;
+                request(&conn, &context, "SelectDevices", |options| {
+                    options.insert("types", DEVICE_KEYBOARD);
+                    vec![session_path.to_variant()]
+                })?;
+                let results = request(&conn, &context, "Start", |_| {
+                    vec![session_path.to_variant(), "".to_variant()]
+                })?;
+                let devices = results.get("devices").and_then(|v| v.get::<u32>()).unwrap_or(0);
+                if devices & DEVICE_KEYBOARD == 0 {
+                    bail!("Keyboard control was not granted by the portal");
+                }
+                Ok(keyboard)
+            })
+            .map_err(|e| anyhow!("Failed to use a private main context: {}", e))?
+    }
+
+    fn session_path(&self) -> Result<ObjectPath> {
+        ObjectPath::try_from(self.session.clone()).map_err(|e| anyhow!("Invalid portal session handle: {}", e))
+    }
+
+    fn notify_keycode(&self, key: EV_KEY, pressed: bool) -> Result<()> {
+        let args = Variant::tuple_from_iter([
+            self.session_path()?.to_variant(),
+            VariantDict::new(None).end(),
+            (key as i32).to_variant(),
+            u32::from(pressed).to_variant(),
+        ]);
+        self.conn
+            .call_sync(
+                Some(PORTAL_BUS_NAME),
+                PORTAL_PATH,
+                REMOTE_DESKTOP,
+                "NotifyKeyboardKeycode",
+                Some(&args),
+                None,
+                DBusCallFlags::NONE,
+                -1,
+                gio::Cancellable::NONE,
+            )
+            .context("The portal rejected a key event")?;
+        Ok(())
+    }
+}
+
+impl Drop for PortalKeyboard {
+    fn drop(&mut self) {
+        let _ = self.conn.call_sync(
+            Some(PORTAL_BUS_NAME),
+            &self.session,
+            "org.freedesktop.portal.Session",
+            "Close",
+            None,
+            None,
+            DBusCallFlags::NONE,
+            -1,
+            gio::Cancellable::NONE,
+        );
+    }
+}
+
+impl Keyboard for PortalKeyboard {
+    fn type_key(&mut self, _c: char, key: EV_KEY, shift: bool) -> io::Result<()> {
+        let press = |key, pressed| self.notify_keycode(key, pressed).map_err(|e| io::Error::other(format!("{:#}", e)));
+        if shift {
+            press(EV_KEY::KEY_LEFTSHIFT, true)?;
+        }
+        press(key, true)?;
+        press(key, false)?;
+        if shift {
+            press(EV_KEY::KEY_LEFTSHIFT, false)?;
+        }
+        Ok(())
+    }
+
+    fn press_chord(&mut self, chord: &Chord) -> io::Result<()> {
+        let press = |key, pressed| self.notify_keycode(key, pressed).map_err(|e| io::Error::other(format!("{:#}", e)));
+        for &modifier in &chord.modifiers {
+            press(modifier, true)?;
+        }
+        press(chord.key, true)?;
+        press(chord.key, false)?;
+        for &modifier in chord.modifiers.iter().rev() {
+            press(modifier, false)?;
+        }
+        Ok(())
+    }
+}
+
+/// Calls a RemoteDesktop method that answers through a Request object and
+/// waits for its Response. `build` fills in extra options and returns the
+/// arguments that precede the options dictionary.
+fn request(
+    conn: &DBusConnection,
+    context: &MainContext,
+    method: &str,
+    build: impl FnOnce(&VariantDict) -> Vec<Variant>,
+) -> Result<HashMap<String, Variant>> {
+    let token = format!("pasteclipboard_{}", method.to_ascii_lowercase());
+    let sender = conn
+        .unique_name()
+        .ok_or_else(|| anyhow!("The session bus connection has no name"))?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let request_path = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+
+    // Subscribe before calling so a fast response cannot be missed.
+    let response: Rc<RefCell<Option<Variant>>> = Rc::default();
+    let subscription = conn.signal_subscribe(
+        Some(PORTAL_BUS_NAME),
+        Some("org.freedesktop.portal.Request"),
+        Some("Response"),
+        Some(&request_path),
+        None,
+        DBusSignalFlags::NONE,
+        glib::clone!(@strong response => move |_, _, _, _, _, params| {
+            response.replace(Some(params.clone()));
+        }),
+    );
+
+    let options = VariantDict::new(None);
+    options.insert("handle_token", token.as_str());
+    let mut args = build(&options);
+    args.push(options.end());
+
+    let call = conn.call_sync(
+        Some(PORTAL_BUS_NAME),
+        PORTAL_PATH,
+        REMOTE_DESKTOP,
+        method,
+        Some(&Variant::tuple_from_iter(args)),
+        None,
+        DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+    );
+    if let Err(e) = call {
+        conn.signal_unsubscribe(subscription);
+        return Err(anyhow!(e).context(format!("The portal's {} call failed", method)));
+    }
+
+    let params = loop {
+        if let Some(params) = response.take() {
+            break params;
+        }
+        context.iteration(true);
+    };
+    conn.signal_unsubscribe(subscription);
+
+    let (code, results): (u32, HashMap<String, Variant>) =
+        params.get().ok_or_else(|| anyhow!("Unexpected {} response from the portal", method))?;
+    match code {
+        0 => Ok(results),
+        1 => bail!("The portal request was cancelled"),
+        _ => bail!("The portal's {} request failed", method),
+    }
+}
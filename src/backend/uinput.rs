@@ -0,0 +1,317 @@
+//! Virtual keyboard through /dev/uinput (works on Wayland and X11).
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::mem::{size_of_val, ManuallyDrop};
+use std::os::fd::FromRawFd;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+use evdev_rs::{
+    enums::{EventCode, EV_KEY, EV_SYN},
+    DeviceWrapper, InputEvent, TimeVal, UInputDevice, UninitDevice,
+};
+use gio::glib;
+
+use super::{Availability, Keyboard};
+use crate::keymap::{char_to_key_event, SUPPORTED_CHARS};
+use crate::readback::Chord;
+use crate::typing::TypingError;
+use crate::uinput_access::UINPUT_PATH;
+
+/// The name the virtual keyboard shows up with, e.g. in `/proc/bus/input/devices`.
+pub const UINPUT_DEVICE_NAME: &str = "PasteClipboard-Virtual-Keyboard";
+
+pub fn probe() -> Availability {
+    match OpenOptions::new().write(true).open(UINPUT_PATH) {
+        Ok(_) => Availability::available(format!("{} is writable", UINPUT_PATH)),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Availability::unavailable(format!("{} missing; is the 'uinput' kernel module loaded?", UINPUT_PATH))
+        }
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            Availability::unavailable(format!("permission denied on {}", UINPUT_PATH))
+        }
+        Err(e) => Availability::unavailable(format!("cannot open {}: {}", UINPUT_PATH, e)),
+    }
+}
+
+/// The waits before each new attempt at creating the device, which can fail
+/// for a moment after boot while udev settles.
+const RETRY_DELAYS: [Duration; 3] = [Duration::from_millis(100), Duration::from_millis(300), Duration::from_millis(900)];
+
+/// Whether creating the device may work when tried again: busy, or not
+/// there yet. A permission problem will not go away by itself.
+fn is_retryable(error: &io::Error) -> bool {
+    matches!(error.kind(), ErrorKind::ResourceBusy | ErrorKind::NotFound | ErrorKind::Interrupted)
+}
+
+/// Every key a run can press: those of the supported characters, Shift and
+/// the keypad's Enter.
+fn all_keys() -> Vec<EV_KEY> {
+    let mut keys: Vec<EV_KEY> = SUPPORTED_CHARS
+        .chars()
+        .map(|c| char_to_key_event(c).0)
+        .filter(|key| *key != EV_KEY::KEY_RESERVED)
+        .chain([EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_KPENTER])
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// The time now on CLOCK_MONOTONIC, which GLib's monotonic clock reads on
+/// Linux, as evdev timestamps its own events.
+fn monotonic_now() -> TimeVal {
+    let micros = glib::monotonic_time();
+    TimeVal::new((micros / 1_000_000) as _, (micros % 1_000_000) as _)
+}
+
+/// The time events written now carry: the monotonic clock's with
+/// `timestamps`, zero without.
+fn event_time(timestamps: bool) -> TimeVal {
+    if timestamps {
+        monotonic_now()
+    } else {
+        TimeVal::new(0, 0)
+    }
+}
+
+/// The events for one character: Shift down if needed, the key down and
+/// up, Shift up, each change followed by its own SYN_REPORT so clients
+/// never see a press and its release in the same frame.
+fn char_events(key: EV_KEY, shift: bool, time: &TimeVal) -> Vec<InputEvent> {
+    let mut changes = Vec::with_capacity(4);
+    if shift {
+        changes.push((EV_KEY::KEY_LEFTSHIFT, 1));
+    }
+    changes.push((key, 1));
+    changes.push((key, 0));
+    if shift {
+        changes.push((EV_KEY::KEY_LEFTSHIFT, 0));
+    }
+    synced(changes, time)
+}
+
+/// The events for a chord: its modifiers down in order, the key down and
+/// up, the modifiers up in reverse, each change synced as above.
+fn chord_events(chord: &Chord, time: &TimeVal) -> Vec<InputEvent> {
+    let mut changes: Vec<(EV_KEY, i32)> = chord.modifiers.iter().map(|&key| (key, 1)).collect();
+    changes.push((chord.key, 1));
+    changes.push((chord.key, 0));
+    changes.extend(chord.modifiers.iter().rev().map(|&key| (key, 0)));
+    synced(changes, time)
+}
+
+/// Each key change, with a SYN_REPORT after it.
+fn synced(changes: Vec<(EV_KEY, i32)>, time: &TimeVal) -> Vec<InputEvent> {
+    changes
+        .into_iter()
+        .flat_map(|(key, value)| {
+            [
+                InputEvent::new(time, &EventCode::EV_KEY(key), value),
+                InputEvent::new(time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+            ]
+        })
+        .collect()
+}
+
+pub struct UinputKeyboard {
+    device: UInputDevice,
+    /// Whether events carry the time they were written, rather than zero.
+    timestamps: bool,
+}
+
+impl UinputKeyboard {
+    /// A device with `keys`, or with `None` every key the keymap has, whose
+    /// events carry real times with `timestamps`.
+    pub fn create(keys: Option<&[EV_KEY]>, timestamps: bool, log: &dyn Fn(String)) -> Result<Self, TypingError> {
+        let started = std::time::Instant::now();
+        tracing::debug!("Creating the uinput device");
+        let dev = UninitDevice::new()
+            .ok_or_else(|| TypingError::DeviceCreation(io::Error::other("libevdev could not allocate a device")))?;
+        dev.set_name(UINPUT_DEVICE_NAME);
+
+        let keys = match keys {
+            Some(keys) => keys.to_vec(),
+            None => all_keys(),
+        };
+        for &key in &keys {
+            dev.enable(EventCode::EV_KEY(key)).map_err(TypingError::DeviceCreation)?;
+        }
+
+        let mut attempt = 1;
+        let device = loop {
+            match UInputDevice::create_from_device(&dev) {
+                Ok(device) => break device,
+                Err(e) if is_retryable(&e) && attempt <= RETRY_DELAYS.len() => {
+                    let delay = RETRY_DELAYS[attempt - 1];
+                    log(format!("Creating the virtual keyboard failed ({}); trying again in {} ms.", e, delay.as_millis()));
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(e) if attempt > 1 => {
+                    let e = io::Error::new(e.kind(), format!("{} (after {} attempts)", e, attempt));
+                    return Err(TypingError::from_device_error(e));
+                }
+                Err(e) => return Err(TypingError::from_device_error(e)),
+            }
+        };
+
+        // Give the compositor a moment to pick up the new device.
+        thread::sleep(Duration::from_millis(200));
+        tracing::debug!(keys = keys.len(), ms = started.elapsed().as_millis() as u64, attempts = attempt, "Created the uinput device");
+
+        Ok(UinputKeyboard { device, timestamps })
+    }
+
+    /// Writes `events` to the uinput fd in one write(), which the kernel
+    /// takes as that many input_event structs, rather than one call each.
+    fn write_events(&self, events: &[InputEvent]) -> io::Result<()> {
+        let Some(fd) = self.device.as_fd() else {
+            return events.iter().try_for_each(|event| self.device.write_event(event));
+        };
+        let mut bytes = Vec::new();
+        for event in events {
+            let raw = event.as_raw();
+            // SAFETY: input_event is plain C data; this reads its bytes.
+            bytes.extend_from_slice(unsafe { std::slice::from_raw_parts(&raw as *const _ as *const u8, size_of_val(&raw)) });
+        }
+        // The device owns the fd and closes it when dropped.
+        let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+        file.write_all(&bytes)
+    }
+}
+
+impl Keyboard for UinputKeyboard {
+    fn type_key(&mut self, _c: char, key: EV_KEY, shift: bool) -> io::Result<()> {
+        self.write_events(&char_events(key, shift, &event_time(self.timestamps)))
+    }
+
+    fn press_chord(&mut self, chord: &Chord) -> io::Result<()> {
+        self.write_events(&chord_events(chord, &event_time(self.timestamps)))
+    }
+}
+
+/// A keyboard for dry runs: it keeps the events the uinput keyboard would
+/// write, times included, instead of sending them anywhere. Clones share
+/// what was recorded, so a run can be given one and the trace read from
+/// another.
+#[derive(Clone, Default)]
+pub struct EventRecorder {
+    timestamps: bool,
+    events: Rc<RefCell<Vec<InputEvent>>>,
+}
+
+impl EventRecorder {
+    /// A recorder whose events carry the time they were recorded with
+    /// `timestamps`, as the device's would (`event_timestamps`).
+    pub fn new(timestamps: bool) -> Self {
+        EventRecorder { timestamps, events: Rc::default() }
+    }
+
+    /// The events recorded so far, in order.
+    pub fn events(&self) -> Vec<InputEvent> {
+        self.events.borrow().clone()
+    }
+
+    /// The recorded events as the event lines of an evemu recording, which
+    /// `evemu-play` replays on a device: `E: <seconds> <type> <code> <value>`.
+    pub fn trace(&self) -> String {
+        let mut trace = String::new();
+        for event in self.events.borrow().iter() {
+            let raw = event.as_raw();
+            trace.push_str(&format!(
+                "E: {}.{:06} {:04x} {:04x} {:04}\n",
+                raw.time.tv_sec, raw.time.tv_usec, raw.type_, raw.code, raw.value
+            ));
+        }
+        trace
+    }
+}
+
+impl Keyboard for EventRecorder {
+    fn type_key(&mut self, _c: char, key: EV_KEY, shift: bool) -> io::Result<()> {
+        self.events.borrow_mut().extend(char_events(key, shift, &event_time(self.timestamps)));
+        Ok(())
+    }
+
+    fn press_chord(&mut self, chord: &Chord) -> io::Result<()> {
+        self.events.borrow_mut().extend(chord_events(chord, &event_time(self.timestamps)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_events_carry_the_time_only_with_timestamps() {
+        let mut off = EventRecorder::new(false);
+        off.type_key('a', EV_KEY::KEY_A, false).unwrap();
+        assert!(off.events().iter().all(|event| event.as_raw().time.tv_sec == 0 && event.as_raw().time.tv_usec == 0));
+
+        let before = glib::monotonic_time();
+        let mut on = EventRecorder::new(true);
+        on.type_key('a', EV_KEY::KEY_A, false).unwrap();
+        on.type_key('b', EV_KEY::KEY_B, false).unwrap();
+        let micros: Vec<i64> = on
+            .events()
+            .iter()
+            .map(|event| event.as_raw().time.tv_sec * 1_000_000 + event.as_raw().time.tv_usec)
+            .collect();
+        assert!(micros.iter().all(|&time| time >= before && time <= glib::monotonic_time()), "{:?}", micros);
+        assert!(micros.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", micros);
+    }
+
+    #[test]
+    fn the_trace_has_an_evemu_line_per_event() {
+        let mut recorder = EventRecorder::new(false);
+        recorder.clone().type_key('A', EV_KEY::KEY_A, true).unwrap();
+        let trace = recorder.trace();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(&lines[..2], ["E: 0.000000 0001 002a 0001", "E: 0.000000 0000 0000 0000"]);
+        assert_eq!(lines[2], "E: 0.000000 0001 001e 0001");
+        assert_eq!(lines[7], "E: 0.000000 0000 0000 0000");
+        recorder.press_chord(&Chord::parse("ctrl+c").unwrap()).unwrap();
+        assert_eq!(recorder.events().len(), 16);
+    }
+
+    #[test]
+    fn a_run_sends_each_change_in_order_with_its_own_sync() {
+        use std::sync::atomic::AtomicBool;
+        use std::time::Instant;
+
+        use crate::typing::{simulate_typing_with, TypingEvent, TypingOptions};
+
+        let recorder = EventRecorder::new(false);
+        // The session lock check needs D-Bus.
+        let options = TypingOptions { watch_lock: false, char_delay: Duration::ZERO, ..TypingOptions::default() };
+        let mut connect =
+            |_: &dyn Fn(String)| -> Result<Box<dyn Keyboard>, TypingError> { Ok(Box::new(recorder.clone())) };
+        let (sender, receiver) = flume::unbounded();
+        simulate_typing_with("aB\n", &options, Instant::now(), &AtomicBool::new(false), &sender, &mut connect);
+        assert!(matches!(receiver.try_iter().last(), Some(TypingEvent::Finished { .. })));
+
+        let changes = [
+            (EV_KEY::KEY_A, 1),
+            (EV_KEY::KEY_A, 0),
+            (EV_KEY::KEY_LEFTSHIFT, 1),
+            (EV_KEY::KEY_B, 1),
+            (EV_KEY::KEY_B, 0),
+            (EV_KEY::KEY_LEFTSHIFT, 0),
+            (EV_KEY::KEY_ENTER, 1),
+            (EV_KEY::KEY_ENTER, 0),
+        ];
+        let expected: Vec<(EventCode, i32)> = changes
+            .into_iter()
+            .flat_map(|(key, value)| [(EventCode::EV_KEY(key), value), (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)])
+            .collect();
+        let sent: Vec<(EventCode, i32)> =
+            recorder.events().into_iter().map(|event| (event.event_code, event.value)).collect();
+        assert_eq!(sent, expected);
+    }
+}
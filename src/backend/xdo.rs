@@ -0,0 +1,270 @@
+//! XTest typing through libxdo (X11 sessions only).
+
+use std::ffi::{c_char, c_int, c_long, c_ulong, c_void, CStr, CString};
+use std::io;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use evdev_rs::enums::EV_KEY;
+
+use super::{Availability, Keyboard, TopLevelWindow};
+use crate::readback::Chord;
+
+#[repr(C)]
+struct XdoHandle {
+    _private: [u8; 0],
+}
+
+/// libxdo's CURRENTWINDOW: type into whatever window has focus.
+const CURRENT_WINDOW: c_ulong = 0;
+
+extern "C" {
+    fn xdo_new(display: *const c_char) -> *mut XdoHandle;
+    fn xdo_free(xdo: *mut XdoHandle);
+    fn xdo_enter_text_window(xdo: *const XdoHandle, window: c_ulong, string: *const c_char, delay: u32) -> c_int;
+    fn xdo_send_keysequence_window(xdo: *const XdoHandle, window: c_ulong, keyseq: *const c_char, delay: u32) -> c_int;
+    fn xdo_get_active_window(xdo: *const XdoHandle, window_ret: *mut c_ulong) -> c_int;
+    fn xdo_activate_window(xdo: *const XdoHandle, window: c_ulong) -> c_int;
+    fn xdo_get_pid_window(xdo: *const XdoHandle, window: c_ulong) -> c_int;
+    fn xdo_get_window_name(
+        xdo: *const XdoHandle,
+        window: c_ulong,
+        name_ret: *mut *mut u8,
+        name_len_ret: *mut c_int,
+        name_type: *mut c_int,
+    ) -> c_int;
+}
+
+#[repr(C)]
+struct Display {
+    _private: [u8; 0],
+}
+
+#[link(name = "X11")]
+extern "C" {
+    fn XFree(data: *mut c_void) -> c_int;
+    fn XOpenDisplay(name: *const c_char) -> *mut Display;
+    fn XCloseDisplay(display: *mut Display) -> c_int;
+    fn XDefaultRootWindow(display: *mut Display) -> c_ulong;
+    fn XInternAtom(display: *mut Display, name: *const c_char, only_if_exists: c_int) -> c_ulong;
+    fn XGetWindowProperty(
+        display: *mut Display,
+        window: c_ulong,
+        property: c_ulong,
+        long_offset: c_long,
+        long_length: c_long,
+        delete: c_int,
+        req_type: c_ulong,
+        actual_type_return: *mut c_ulong,
+        actual_format_return: *mut c_int,
+        nitems_return: *mut c_ulong,
+        bytes_after_return: *mut c_ulong,
+        prop_return: *mut *mut u8,
+    ) -> c_int;
+}
+
+/// Xlib's XA_WINDOW, the type of a property that holds window ids.
+const XA_WINDOW: c_ulong = 33;
+
+/// At most this many windows are listed.
+const MAX_WINDOWS: c_long = 1024;
+
+/// How long a window that was asked to take the focus gets to have it.
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the focus is looked at meanwhile.
+const ACTIVATION_CHECK: Duration = Duration::from_millis(50);
+
+fn is_wayland_session() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|t| t.eq_ignore_ascii_case("wayland"))
+}
+
+pub fn probe() -> Availability {
+    if std::env::var_os("DISPLAY").is_none() {
+        return Availability::unavailable("no X11 display");
+    }
+    if is_wayland_session() {
+        return Availability::unavailable("Wayland session; xdo would only reach XWayland windows");
+    }
+    match XdoKeyboard::open() {
+        Ok(_) => Availability::available("X11 session"),
+        Err(e) => Availability::unavailable(e.to_string()),
+    }
+}
+
+pub struct XdoKeyboard {
+    xdo: *mut XdoHandle,
+}
+
+impl XdoKeyboard {
+    pub fn open() -> Result<Self> {
+        // A null display name makes libxdo use $DISPLAY.
+        let xdo = unsafe { xdo_new(ptr::null()) };
+        if xdo.is_null() {
+            bail!("Failed to open the X display for xdo");
+        }
+        Ok(XdoKeyboard { xdo })
+    }
+}
+
+impl XdoKeyboard {
+    /// The window that has the focus.
+    fn active_window(&self) -> Result<c_ulong> {
+        let mut window: c_ulong = 0;
+        if unsafe { xdo_get_active_window(self.xdo, &mut window) } != 0 || window == 0 {
+            bail!("Cannot tell which window has the focus");
+        }
+        Ok(window)
+    }
+
+    /// `window`'s title, as the X server has it.
+    fn window_title(&self, window: c_ulong) -> Result<String> {
+        let mut name: *mut u8 = ptr::null_mut();
+        let (mut len, mut name_type): (c_int, c_int) = (0, 0);
+        if unsafe { xdo_get_window_name(self.xdo, window, &mut name, &mut len, &mut name_type) } != 0 {
+            bail!("Cannot read the window's title");
+        }
+        if name.is_null() {
+            return Ok(String::new());
+        }
+        let title = unsafe { std::slice::from_raw_parts(name, usize::try_from(len).unwrap_or(0)) };
+        let title = String::from_utf8_lossy(title).into_owned();
+        unsafe { XFree(name as *mut c_void) };
+        Ok(title)
+    }
+}
+
+/// Fails with `message` outside an X11 session.
+fn require_x11(message: &str) -> Result<()> {
+    if std::env::var_os("DISPLAY").is_none() || is_wayland_session() {
+        bail!("{}", message);
+    }
+    Ok(())
+}
+
+/// The focused window's title, as the X server has it.
+pub fn focused_window_title() -> Result<String> {
+    require_x11("The focused window's title can only be checked in an X11 session")?;
+    let keyboard = XdoKeyboard::open()?;
+    let window = keyboard.active_window()?;
+    keyboard.window_title(window).map_err(|_| anyhow::anyhow!("Cannot read the focused window's title"))
+}
+
+/// The window manager's list of windows, from the root window's
+/// `_NET_CLIENT_LIST_STACKING` (bottom to top) or, without one, its
+/// `_NET_CLIENT_LIST`.
+fn client_list() -> Result<Vec<c_ulong>> {
+    let display = unsafe { XOpenDisplay(ptr::null()) };
+    if display.is_null() {
+        bail!("Failed to open the X display");
+    }
+    let root = unsafe { XDefaultRootWindow(display) };
+    let read = |name: &CStr| -> Option<Vec<c_ulong>> {
+        let atom = unsafe { XInternAtom(display, name.as_ptr(), 1) };
+        if atom == 0 {
+            return None;
+        }
+        let (mut actual_type, mut format, mut count, mut after) = (0, 0, 0, 0);
+        let mut data: *mut u8 = ptr::null_mut();
+        let status = unsafe {
+            XGetWindowProperty(
+                display, root, atom, 0, MAX_WINDOWS, 0, XA_WINDOW, &mut actual_type, &mut format, &mut count, &mut after,
+                &mut data,
+            )
+        };
+        if status != 0 || data.is_null() {
+            return None;
+        }
+        // Xlib hands 32-bit items over as longs.
+        let ids = (actual_type == XA_WINDOW && format == 32)
+            .then(|| unsafe { std::slice::from_raw_parts(data as *const c_ulong, count as usize) }.to_vec());
+        unsafe { XFree(data as *mut c_void) };
+        ids
+    };
+    let ids = read(c"_NET_CLIENT_LIST_STACKING").or_else(|| read(c"_NET_CLIENT_LIST"));
+    unsafe { XCloseDisplay(display) };
+    ids.ok_or_else(|| anyhow::anyhow!("The window manager does not list its windows (_NET_CLIENT_LIST)"))
+}
+
+/// The other applications' windows that have a title, top-most first.
+pub fn top_level_windows() -> Result<Vec<TopLevelWindow>> {
+    require_x11("Windows can only be listed in an X11 session")?;
+    let keyboard = XdoKeyboard::open()?;
+    let own = std::process::id();
+    let mut windows = Vec::new();
+    for id in client_list()?.into_iter().rev() {
+        if u32::try_from(unsafe { xdo_get_pid_window(keyboard.xdo, id) }).is_ok_and(|pid| pid == own) {
+            continue;
+        }
+        match keyboard.window_title(id) {
+            Ok(title) if !title.trim().is_empty() => windows.push(TopLevelWindow { id, title }),
+            _ => {}
+        }
+    }
+    Ok(windows)
+}
+
+/// Asks the window manager to bring `window` to the front, switching to
+/// its desktop, and waits until it has the focus.
+pub fn activate_window(window: c_ulong) -> Result<()> {
+    require_x11("Windows can only be brought to the front in an X11 session")?;
+    let keyboard = XdoKeyboard::open()?;
+    if !client_list()?.contains(&window) {
+        bail!("the window is gone");
+    }
+    if unsafe { xdo_activate_window(keyboard.xdo, window) } != 0 {
+        bail!("the window manager did not take the request");
+    }
+    let deadline = Instant::now() + ACTIVATION_TIMEOUT;
+    loop {
+        if keyboard.active_window().is_ok_and(|active| active == window) {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!("it did not get the focus within {} s", ACTIVATION_TIMEOUT.as_secs());
+        }
+        thread::sleep(ACTIVATION_CHECK);
+    }
+}
+
+impl Drop for XdoKeyboard {
+    fn drop(&mut self) {
+        unsafe { xdo_free(self.xdo) };
+    }
+}
+
+impl Keyboard for XdoKeyboard {
+    fn type_key(&mut self, c: char, key: EV_KEY, shift: bool) -> io::Result<()> {
+        // xdo types characters, not keys; a line break on another key than
+        // plain Enter goes through its key name instead.
+        let keyseq = match (c, key, shift) {
+            ('\n', EV_KEY::KEY_KPENTER, _) => Some(c"KP_Enter"),
+            ('\n', _, true) => Some(c"shift+Return"),
+            _ => None,
+        };
+        if let Some(keyseq) = keyseq {
+            if unsafe { xdo_send_keysequence_window(self.xdo, CURRENT_WINDOW, keyseq.as_ptr(), 0) } != 0 {
+                return Err(io::Error::other(format!("xdo failed to press {:?}", keyseq)));
+            }
+            return Ok(());
+        }
+        // Every mapped character is ASCII, so this never contains a NUL.
+        let text = CString::new(c.to_string())?;
+        if unsafe { xdo_enter_text_window(self.xdo, CURRENT_WINDOW, text.as_ptr(), 0) } != 0 {
+            return Err(io::Error::other(format!("xdo failed to type {:?}", c)));
+        }
+        Ok(())
+    }
+
+    fn press_chord(&mut self, chord: &Chord) -> io::Result<()> {
+        // Chords are made of key names, which never contain a NUL.
+        let keyseq = CString::new(chord.keysequence())?;
+        if unsafe { xdo_send_keysequence_window(self.xdo, CURRENT_WINDOW, keyseq.as_ptr(), 0) } != 0 {
+            return Err(io::Error::other(format!("xdo failed to press {}", chord)));
+        }
+        Ok(())
+    }
+}
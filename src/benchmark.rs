@@ -0,0 +1,172 @@
+//! The benchmark: types a known pattern into a scratch window at shorter
+//! and shorter pauses on one device, and measures how fast the keys
+//! actually went out, to help pick a character delay before a long run.
+//! Nothing reads the keys back, so it reports emission rates; whether the
+//! target kept up shows in the lines it received.
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use evdev_rs::enums::EV_KEY;
+
+use crate::backend::Keyboard;
+use crate::readback::Chord;
+use crate::typing::{connect_configured, simulate_typing_with, RunStats, TypingError, TypingEvent, TypingOptions};
+
+/// The character delays tried, in milliseconds, slowest first.
+pub const BENCHMARK_DELAYS_MS: [u32; 6] = [20, 10, 5, 2, 1, 0];
+
+/// The line each step types, with upper case and symbols so Shift is
+/// exercised too.
+pub const BENCHMARK_LINE: &str = "The quick brown fox jumps over the lazy dog: 0123456789!";
+
+/// Lines typed per step; each ends with a line break.
+const LINES_PER_STEP: usize = 3;
+
+/// Rest between two steps, so the target can catch up.
+const STEP_PAUSE: Duration = Duration::from_millis(500);
+
+/// A step is on pace when its keys went out no more than this much slower
+/// than asked, relative and absolute.
+const PACE_TOLERANCE: f64 = 0.1;
+const PACE_SLACK: Duration = Duration::from_micros(500);
+
+/// One delay tried and what came of it.
+#[derive(Clone, Copy, Debug)]
+pub struct BenchmarkStep {
+    pub char_delay_ms: u32,
+    pub stats: RunStats,
+}
+
+impl BenchmarkStep {
+    /// Whether the keys went out about as fast as the delay asked for.
+    pub fn on_pace(&self) -> bool {
+        let target = Duration::from_millis(u64::from(self.char_delay_ms));
+        self.stats.average_interval <= target.mul_f64(1.0 + PACE_TOLERANCE) + PACE_SLACK
+    }
+
+    /// Keys per second, going by the time between them.
+    pub fn keys_per_second(&self) -> f64 {
+        let interval = self.stats.average_interval.as_secs_f64();
+        if interval > 0.0 { 1.0 / interval } else { 0.0 }
+    }
+}
+
+/// Events sent from the benchmark worker.
+#[derive(Debug)]
+pub enum BenchmarkEvent {
+    /// A step is about to type.
+    Starting { char_delay_ms: u32 },
+    Step(BenchmarkStep),
+    /// A retry or similar, plain English like the log.
+    Notice { message: String },
+    /// Every step ran.
+    Finished,
+    /// A step failed or was cancelled; the steps before it stand.
+    Failed { error: TypingError },
+}
+
+/// The fastest step whose keys went out on pace.
+pub fn fastest_on_pace(steps: &[BenchmarkStep]) -> Option<&BenchmarkStep> {
+    steps.iter().filter(|step| step.on_pace()).min_by_key(|step| step.char_delay_ms)
+}
+
+/// One line per step, for the terminal and the dialog.
+pub fn step_text(step: &BenchmarkStep) -> String {
+    format!(
+        "{:>2} ms: one key every {:.2} ms ({:.0} keys/s){}",
+        step.char_delay_ms,
+        step.stats.average_interval.as_secs_f64() * 1000.0,
+        step.keys_per_second(),
+        if step.on_pace() { "" } else { ", slower than asked" }
+    )
+}
+
+/// What the steps add up to.
+pub fn summary_text(steps: &[BenchmarkStep]) -> String {
+    let mut out = match fastest_on_pace(steps) {
+        Some(step) => format!("The keys kept pace down to {} ms ({:.0} keys/s).", step.char_delay_ms, step.keys_per_second()),
+        None => "No step kept the pace it asked for.".to_string(),
+    };
+    out.push_str(
+        " Compare the lines in the scratch window with the pattern: \
+         where they have gaps, the target could not keep up at that delay.",
+    );
+    out
+}
+
+/// Hands the benchmark's one device to each step's run.
+struct Shared(Rc<RefCell<Box<dyn Keyboard>>>);
+
+impl Keyboard for Shared {
+    fn type_key(&mut self, c: char, key: EV_KEY, shift: bool) -> io::Result<()> {
+        self.0.borrow_mut().type_key(c, key, shift)
+    }
+
+    fn press_chord(&mut self, chord: &Chord) -> io::Result<()> {
+        self.0.borrow_mut().press_chord(chord)
+    }
+}
+
+/// Runs every step in [`BENCHMARK_DELAYS_MS`] on the backend `options`
+/// ask for, with their Enter key. Setting `cancel` stops it.
+pub fn run_benchmark(options: &TypingOptions, cancel: &AtomicBool, events: &Sender<BenchmarkEvent>) {
+    let text = format!("{}\n", BENCHMARK_LINE).repeat(LINES_PER_STEP);
+    let mut device: Option<Rc<RefCell<Box<dyn Keyboard>>>> = None;
+    for (i, &char_delay_ms) in BENCHMARK_DELAYS_MS.iter().enumerate() {
+        if i > 0 {
+            thread::sleep(STEP_PAUSE);
+        }
+        if cancel.load(Ordering::Relaxed) {
+            let _ = events.send(BenchmarkEvent::Failed { error: TypingError::Cancelled { at_char: 0 } });
+            return;
+        }
+        let _ = events.send(BenchmarkEvent::Starting { char_delay_ms });
+        let step_options = TypingOptions {
+            char_delay: Duration::from_millis(u64::from(char_delay_ms)),
+            newline_delay: Duration::ZERO,
+            target_window: None,
+            readback: None,
+            ..options.clone()
+        };
+        // The first connect of a step reuses the device; one after the
+        // device went away makes a new one.
+        let mut reuse = true;
+        let mut connect = |log: &dyn Fn(String)| -> Result<Box<dyn Keyboard>, TypingError> {
+            if std::mem::replace(&mut reuse, false) {
+                if let Some(keyboard) = &device {
+                    return Ok(Box::new(Shared(keyboard.clone())));
+                }
+            }
+            let keyboard = Rc::new(RefCell::new(connect_configured(options, None, log)?));
+            device = Some(keyboard.clone());
+            Ok(Box::new(Shared(keyboard)))
+        };
+        let (sender, receiver) = flume::unbounded();
+        simulate_typing_with(text.as_str(), &step_options, Instant::now(), cancel, &sender, &mut connect);
+        drop(sender);
+        for event in receiver {
+            match event {
+                TypingEvent::Notice { message } => {
+                    let _ = events.send(BenchmarkEvent::Notice { message });
+                }
+                TypingEvent::Finished { stats } => {
+                    let step = BenchmarkStep { char_delay_ms, stats };
+                    log::info!("Benchmark at {}", step_text(&step));
+                    let _ = events.send(BenchmarkEvent::Step(step));
+                }
+                TypingEvent::Failed { error, .. } => {
+                    let _ = events.send(BenchmarkEvent::Failed { error });
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+    let _ = events.send(BenchmarkEvent::Finished);
+}
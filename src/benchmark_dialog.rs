@@ -0,0 +1,162 @@
+// "Benchmark…": after the countdown, types a test pattern into whichever
+// window has the focus at shorter and shorter pauses, and lists how fast
+// the keys went out at each, to help choose the character delay.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::SimpleAction,
+    glib::{timeout_add_local, ControlFlow},
+    Button, Label, Orientation, Window,
+};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use pasteclipboard::benchmark::{run_benchmark, step_text, summary_text, BenchmarkEvent, BENCHMARK_LINE};
+use pasteclipboard::settings::load_typing_options;
+use pasteclipboard::typing::TypingError;
+
+use crate::window::{failure_text, MainWindow};
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let action = SimpleAction::new("benchmark", None);
+    let weak = Rc::downgrade(ui);
+    action.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            show(&ui);
+        }
+    });
+    ui.window.add_action(&action);
+}
+
+fn show(ui: &Rc<MainWindow>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Benchmark")
+        .default_width(520)
+        .build();
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+    let intro = Label::new(Some(&format!(
+        "Types \"{}\" a few times at each of several character delays, from 20 ms down to none, and measures how \
+         fast the keys went out. Start it, then focus a window where typing is harmless, such as an empty text \
+         editor, before the countdown ends.",
+        BENCHMARK_LINE
+    )));
+    intro.set_xalign(0.0);
+    intro.set_wrap(true);
+    vbox.append(&intro);
+    let lbl_results = Label::new(None);
+    lbl_results.set_xalign(0.0);
+    lbl_results.set_wrap(true);
+    lbl_results.set_selectable(true);
+    lbl_results.add_css_class("monospace");
+    vbox.append(&lbl_results);
+    let lbl_status = Label::new(None);
+    lbl_status.set_xalign(0.0);
+    lbl_status.set_wrap(true);
+    vbox.append(&lbl_status);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_start = Button::with_label("Start");
+    btn_start.add_css_class("suggested-action");
+    let btn_close = Button::with_label("Close");
+    buttons.append(&btn_start);
+    buttons.append(&btn_close);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_start));
+
+    // Set when the dialog closes, so a running benchmark stops with it.
+    let cancel: Rc<Cell<Option<Arc<AtomicBool>>>> = Rc::default();
+    let cancel_clone = cancel.clone();
+    dialog.connect_close_request(move |_| {
+        if let Some(cancel) = cancel_clone.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        gtk4::glib::Propagation::Proceed
+    });
+    let dialog_clone = dialog.clone();
+    btn_close.connect_clicked(move |_| dialog_clone.close());
+
+    let weak = Rc::downgrade(ui);
+    btn_start.connect_clicked(move |btn_start| {
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        if ui.is_busy() {
+            lbl_status.set_text("Wait for the typing run to end first.");
+            return;
+        }
+        let options = match load_typing_options() {
+            Ok(options) => options,
+            Err(e) => {
+                lbl_status.set_text(&format!("{} in config.toml.", e));
+                return;
+            }
+        };
+        btn_start.set_sensitive(false);
+        lbl_results.set_text("");
+        let run_cancel = Arc::new(AtomicBool::new(false));
+        cancel.set(Some(run_cancel.clone()));
+        let start_at = Instant::now() + Duration::from_secs(ui.entry_delay.value() as u64);
+        let (sender, receiver) = mpsc::channel();
+        let mut sender = Some(sender);
+        let mut steps = Vec::new();
+        let lbl_status = lbl_status.clone();
+        let lbl_results = lbl_results.clone();
+        let btn_start = btn_start.clone();
+        timeout_add_local(Duration::from_millis(100), move || {
+            if let Some(sender) = sender.take_if(|_| Instant::now() >= start_at) {
+                let (options, cancel) = (options.clone(), run_cancel.clone());
+                thread::spawn(move || run_benchmark(&options, &cancel, &sender));
+            } else if sender.is_some() {
+                if run_cancel.load(Ordering::Relaxed) {
+                    return ControlFlow::Break;
+                }
+                let left = start_at.saturating_duration_since(Instant::now());
+                lbl_status.set_text(&format!("Focus the scratch window; starting in {} s…", left.as_secs() + 1));
+                return ControlFlow::Continue;
+            }
+            loop {
+                let event = match receiver.try_recv() {
+                    Ok(event) => event,
+                    Err(TryRecvError::Empty) => return ControlFlow::Continue,
+                    Err(TryRecvError::Disconnected) => BenchmarkEvent::Failed {
+                        error: TypingError::Backend("The benchmark stopped unexpectedly".to_string()),
+                    },
+                };
+                match event {
+                    BenchmarkEvent::Starting { char_delay_ms } => {
+                        lbl_status.set_text(&format!("Typing at {} ms…", char_delay_ms));
+                    }
+                    BenchmarkEvent::Step(step) => {
+                        steps.push(step);
+                        let lines: Vec<String> = steps.iter().map(step_text).collect();
+                        lbl_results.set_text(&lines.join("\n"));
+                    }
+                    BenchmarkEvent::Notice { message } => lbl_status.set_text(&message),
+                    BenchmarkEvent::Finished => {
+                        lbl_status.set_text(&summary_text(&steps));
+                        btn_start.set_sensitive(true);
+                        return ControlFlow::Break;
+                    }
+                    BenchmarkEvent::Failed { error } => {
+                        lbl_status.set_text(&format!("Stopped: {}", failure_text(&error)));
+                        btn_start.set_sensitive(true);
+                        return ControlFlow::Break;
+                    }
+                }
+            }
+        });
+    });
+    dialog.present();
+}
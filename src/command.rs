@@ -0,0 +1,229 @@
+//! Text from a command's stdout: argv parsing (no shell), running with a
+//! size cap and timeout, and the persisted command history.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+use crate::settings::state_path;
+use crate::typing::MAX_REMOTE_TEXT_BYTES;
+
+/// Largest output accepted from a command, in bytes.
+pub const MAX_COMMAND_OUTPUT_BYTES: usize = MAX_REMOTE_TEXT_BYTES;
+
+/// How long a command may run before it is killed.
+pub const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the output may take to arrive once the command has exited,
+/// at least.
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
+/// Number of commands kept in the history.
+pub const HISTORY_LENGTH: usize = 50;
+
+/// Splits a command line into arguments. Single and double quotes group
+/// words and a backslash escapes the next character; nothing else (globs,
+/// variables, pipes) is interpreted.
+pub fn split_argv(line: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.get_or_insert_with(String::new).push(c),
+            (_, '\\') => match chars.next() {
+                Some(escaped) => current.get_or_insert_with(String::new).push(escaped),
+                None => bail!("Trailing backslash in command"),
+            },
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        bail!("Unterminated quote in command");
+    }
+    args.extend(current);
+    if args.is_empty() {
+        bail!("Enter a command to run");
+    }
+    Ok(args)
+}
+
+fn read_capped(mut reader: impl Read, limit: usize) -> std::io::Result<(Vec<u8>, bool)> {
+    let mut data = Vec::new();
+    reader.by_ref().take(limit as u64 + 1).read_to_end(&mut data)?;
+    let truncated = data.len() > limit;
+    data.truncate(limit);
+    // Keep draining so the command never blocks on a full pipe.
+    std::io::copy(&mut reader, &mut std::io::sink())?;
+    Ok((data, truncated))
+}
+
+/// Reads `reader` with [`read_capped`] on a thread of its own, which sends
+/// what it read once the pipe closes.
+fn spawn_reader(reader: impl Read + Send + 'static, limit: usize) -> mpsc::Receiver<std::io::Result<(Vec<u8>, bool)>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(read_capped(reader, limit));
+    });
+    receiver
+}
+
+/// What a reader of `program`'s output read, once its pipe closed by
+/// `deadline`. A program the command started in the background may keep
+/// the pipe open long after it exited; the reader is then left to finish
+/// on its own.
+fn collect(
+    reader: &mpsc::Receiver<std::io::Result<(Vec<u8>, bool)>>,
+    program: &str,
+    deadline: Instant,
+) -> Result<(Vec<u8>, bool)> {
+    match reader.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(result) => Ok(result?),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            bail!("'{}' exited, but a program it started in the background kept its output open", program)
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => bail!("Reading the output of '{}' failed", program),
+    }
+}
+
+/// Runs `argv` without a shell and returns its stdout, minus one trailing
+/// newline. Fails with the command's stderr if it exits unsuccessfully,
+/// prints more than `MAX_COMMAND_OUTPUT_BYTES`, or outlives `timeout`.
+pub fn run_command(argv: &[String], timeout: Duration) -> Result<String> {
+    let program = argv.first().context("Enter a command to run")?;
+    let output = run_program(argv, None, timeout)?;
+    let mut text = String::from_utf8(output).with_context(|| format!("'{}' did not print UTF-8 text", program))?;
+    if text.ends_with('\n') {
+        text.pop();
+        if text.ends_with('\r') {
+            text.pop();
+        }
+    }
+    Ok(text)
+}
+
+/// [`run_command`] for any output, as bytes, with `input` written to the
+/// command's stdin (which is empty otherwise).
+pub fn run_program(argv: &[String], input: Option<&[u8]>, timeout: Duration) -> Result<Vec<u8>> {
+    let (program, args) = argv.split_first().context("Enter a command to run")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Cannot run '{}'", program))?;
+
+    let stdin = child.stdin.take();
+    let stdout = child.stdout.take().context("stdout was not captured")?;
+    let stderr = child.stderr.take().context("stderr was not captured")?;
+    let stdout = spawn_reader(stdout, MAX_COMMAND_OUTPUT_BYTES);
+    let stderr = spawn_reader(stderr, 64 * 1024);
+    let started = Instant::now();
+
+    let status = thread::scope(|scope| {
+        // Written from a thread of its own, so a command that prints before
+        // it has read all of its input cannot block on a full pipe. It ends
+        // with a broken pipe if the command exits or is stopped first.
+        if let (Some(mut stdin), Some(input)) = (stdin, input) {
+            scope.spawn(move || {
+                let _ = stdin.write_all(input);
+            });
+        }
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            if started.elapsed() >= timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("'{}' did not finish within {} seconds and was stopped", program, timeout.as_secs());
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    })?;
+
+    let deadline = (started + timeout).max(Instant::now() + OUTPUT_GRACE);
+    let (output, truncated) = collect(&stdout, program, deadline)?;
+    let (errors, _) = collect(&stderr, program, deadline)?;
+    let errors = String::from_utf8_lossy(&errors);
+    if !status.success() {
+        bail!("'{}' failed ({}):\n{}", program, status, errors.trim_end());
+    }
+    if truncated {
+        bail!("'{}' printed more than {} bytes", program, MAX_COMMAND_OUTPUT_BYTES);
+    }
+    Ok(output)
+}
+
+fn history_path() -> Option<PathBuf> {
+    state_path("command_history")
+}
+
+/// Previously run commands, most recent first.
+pub fn load_command_history() -> Vec<String> {
+    history_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|history| history.lines().filter(|l| !l.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Moves `line` to the front of the history.
+pub fn remember_command(line: &str) {
+    let Some(path) = history_path() else {
+        return;
+    };
+    let line = line.trim();
+    let mut history = load_command_history();
+    history.retain(|l| l != line);
+    history.insert(0, line.to_string());
+    history.truncate(HISTORY_LENGTH);
+
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::write(path, history.join("\n") + "\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(line: &str) -> Vec<String> {
+        split_argv(line).unwrap()
+    }
+
+    #[test]
+    fn splits_without_a_shell() {
+        assert_eq!(argv(r#"op read 'a b' "c\"d" e\ f"#), ["op", "read", "a b", "c\"d", "e f"]);
+        assert_eq!(argv("echo ''"), ["echo", ""]);
+        assert!(split_argv("echo 'open").is_err());
+    }
+
+    #[test]
+    fn returns_the_output() {
+        assert_eq!(run_command(&argv("printf 'hi\\n'"), COMMAND_TIMEOUT).unwrap(), "hi");
+    }
+
+    #[test]
+    fn does_not_wait_for_a_background_program() {
+        let started = Instant::now();
+        let result = run_command(&argv("sh -c 'sleep 30 & echo hi'"), Duration::from_secs(2));
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
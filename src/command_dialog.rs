@@ -0,0 +1,140 @@
+// The "Run command…" dialog: loads a command's stdout into the buffer, or
+// types it directly without it ever appearing in the window.
+
+use gtk4::prelude::*;
+use gtk4::{
+    glib::{timeout_add_local, ControlFlow},
+    Button, ButtonsType, CheckButton, ComboBoxText, Label, MessageDialog, MessageType, Orientation, Window,
+};
+use std::rc::Rc;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use pasteclipboard::command::{
+    load_command_history, remember_command, run_command, split_argv, COMMAND_TIMEOUT,
+};
+use pasteclipboard::settings::Settings;
+
+use crate::window::MainWindow;
+
+pub fn show(ui: &Rc<MainWindow>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Run command")
+        .default_width(460)
+        .build();
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let lbl_help = Label::new(Some(
+        "Command and arguments (run directly, without a shell; quote arguments containing spaces):",
+    ));
+    lbl_help.set_xalign(0.0);
+    lbl_help.set_wrap(true);
+    vbox.append(&lbl_help);
+
+    let combo = ComboBoxText::with_entry();
+    for line in load_command_history() {
+        combo.append_text(&line);
+    }
+    vbox.append(&combo);
+
+    let chk_secret = CheckButton::with_label("Type the output directly (secret: not shown or kept in history)");
+    vbox.append(&chk_secret);
+
+    let lbl_status = Label::new(None);
+    lbl_status.set_xalign(0.0);
+    lbl_status.set_wrap(true);
+    vbox.append(&lbl_status);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label("Cancel");
+    let btn_run = Button::with_label("Run");
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_run);
+    vbox.append(&buttons);
+
+    dialog.set_child(Some(&vbox));
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    let ui = ui.clone();
+    let dialog_clone = dialog.clone();
+    btn_run.connect_clicked(move |btn_run| {
+        // Checked again here in case config.toml changed while the app ran.
+        if !Settings::load().allow_commands {
+            lbl_status.set_text("Running commands is disabled in config.toml.");
+            return;
+        }
+        let line = combo.active_text().map(|t| t.to_string()).unwrap_or_default();
+        let argv = match split_argv(&line) {
+            Ok(argv) => argv,
+            Err(e) => {
+                lbl_status.set_text(&format!("{}.", e));
+                return;
+            }
+        };
+        let secret = chk_secret.is_active();
+        if secret && ui.is_busy() {
+            lbl_status.set_text("A typing run is already in progress.");
+            return;
+        }
+
+        btn_run.set_sensitive(false);
+        lbl_status.set_text("Running...");
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(run_command(&argv, COMMAND_TIMEOUT));
+        });
+
+        let ui = ui.clone();
+        let dialog = dialog_clone.clone();
+        let btn_run = btn_run.clone();
+        let lbl_status = lbl_status.clone();
+        timeout_add_local(Duration::from_millis(100), move || {
+            let result = match receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return ControlFlow::Continue,
+                Err(TryRecvError::Disconnected) => Err(anyhow::anyhow!("The command runner stopped unexpectedly")),
+            };
+            btn_run.set_sensitive(true);
+            lbl_status.set_text("");
+            match result {
+                Ok(output) if secret => {
+                    dialog.close();
+                    ui.start_text(output);
+                }
+                Ok(output) => {
+                    remember_command(&line);
+                    dialog.close();
+                    ui.replace_text(&output);
+                    ui.set_status("Loaded the command's output.");
+                }
+                Err(e) => show_error(&dialog, &format!("{:#}", e)),
+            }
+            ControlFlow::Break
+        });
+    });
+
+    dialog.present();
+}
+
+fn show_error(parent: &Window, message: &str) {
+    let alert = MessageDialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .message_type(MessageType::Error)
+        .buttons(ButtonsType::Close)
+        .text("The command failed")
+        .secondary_text(message)
+        .build();
+    alert.connect_response(|alert, _| alert.close());
+    alert.present();
+}
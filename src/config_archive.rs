@@ -0,0 +1,555 @@
+//! Moving the whole configuration to another machine: config.toml, the
+//! profiles and the snippets in one archive, and importing such an archive
+//! again. It is a plain tar file, so `tar tf` shows what is inside; the
+//! first entry is a manifest that marks it as ours. The HTTP, URL and remote
+//! typing tokens and the MQTT password, the window's drafts and the text of the snippets
+//! stored in the keyring are left out unless asked for; such a snippet's
+//! text goes back into the keyring when it is imported.
+//!
+//! An import checks every entry before anything is written: only the files
+//! an export contains are accepted, each under its own folder. It then
+//! writes them all next to their targets, stores the keyring snippets and
+//! only then renames the files into place, so a failure on the way leaves
+//! the old configuration as it was (see [`ImportPlan::apply`]).
+
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use configparser::ini::Ini;
+use zeroize::Zeroizing;
+
+use crate::config_toml;
+use crate::profiles::profiles_dir;
+use crate::settings::{config_path, parse_settings, state_dir, toml_text};
+use crate::secret_store;
+use crate::text_file::write_atomically_with_mode;
+use crate::snippets::{is_secure, keyring_label, list_snippets, load_snippet, snippets_dir, INDEX_FILE, SECRET_KEY, TRASH_DIR};
+
+pub const MANIFEST: &str = "pasteclipboard-export.json";
+const FORMAT: u64 = 1;
+
+/// Keys whose values let others into this machine or a server.
+pub const SECRET_KEYS: [&str; 4] = ["http_token", "mqtt_password", "remote_token", "url_token"];
+
+/// Far more than any configuration; a larger file is not one of ours.
+const MAX_ARCHIVE_BYTES: u64 = 64 * 1024 * 1024;
+
+const BLOCK: usize = 512;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExportOptions {
+    pub include_secrets: bool,
+    pub include_drafts: bool,
+    pub include_secure_snippets: bool,
+}
+
+/// One file of the archive, by its name there ("config/config.toml").
+struct Entry {
+    name: String,
+    data: Vec<u8>,
+}
+
+/// A file an import would write.
+#[derive(Clone, Debug)]
+pub struct PlannedFile {
+    /// The name in the archive, e.g. "config/snippets/ssh.txt".
+    pub name: String,
+    pub target: PathBuf,
+    /// Whether a file of that name exists and would be replaced.
+    pub replaces: bool,
+    data: Vec<u8>,
+    /// For a snippet the archive's index marks as stored securely, the
+    /// keyring id its text goes under instead of `target`.
+    secret_id: Option<String>,
+}
+
+/// A checked archive, ready to be applied.
+#[derive(Clone, Debug)]
+pub struct ImportPlan {
+    pub files: Vec<PlannedFile>,
+}
+
+/// `text`, the file `name`, without the secret keys in any section.
+fn strip_secrets(name: &str, text: &str) -> String {
+    if name.ends_with(".toml") {
+        return config_toml::without_keys(text, &SECRET_KEYS).unwrap_or_else(|| text.to_string());
+    }
+    let mut conf = Ini::new();
+    if conf.read(text.to_string()).is_err() {
+        return text.to_string();
+    }
+    for section in conf.sections() {
+        for key in SECRET_KEYS {
+            conf.remove_key(&section, key);
+        }
+    }
+    conf.writes()
+}
+
+/// The plain files directly in `dir` whose names pass `keep`, sorted.
+fn files_in(dir: &Path, keep: impl Fn(&str) -> bool) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|entry| Some((entry.file_name().into_string().ok()?, entry.path())))
+        .filter(|(name, _)| !name.starts_with('.') && keep(name))
+        .collect();
+    files.sort();
+    files
+}
+
+fn is_settings_file(name: &str) -> bool {
+    name.ends_with(".ini")
+}
+
+fn is_draft(name: &str) -> bool {
+    name == "draft.txt"
+        || name.strip_prefix("draft-").and_then(|n| n.strip_suffix(".txt")).is_some_and(|n| n.parse::<u32>().is_ok())
+}
+
+fn collect(options: &ExportOptions) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut add_settings = |name: String, path: &Path| -> Result<()> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let text = if options.include_secrets { text } else { strip_secrets(&name, &text) };
+        entries.push(Entry { name, data: text.into_bytes() });
+        Ok(())
+    };
+    if let Some(path) = config_path().filter(|path| path.exists()) {
+        add_settings("config/config.toml".to_string(), &path)?;
+    }
+    if let Some(dir) = profiles_dir() {
+        for (name, path) in files_in(&dir, is_settings_file) {
+            add_settings(format!("config/profiles/{}", name), &path)?;
+        }
+    }
+    if let Some(dir) = snippets_dir() {
+        // The trash is a folder, so it is never among the files.
+        for (name, path) in files_in(&dir, |_| true) {
+            let data = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            entries.push(Entry { name: format!("config/snippets/{}", name), data });
+        }
+    }
+    if options.include_secure_snippets {
+        for name in list_snippets()?.into_iter().filter(|name| is_secure(name)) {
+            let text = load_snippet(&name)?;
+            entries.push(Entry { name: format!("config/snippets/{}.txt", name), data: text.into_bytes() });
+        }
+    }
+    if options.include_drafts {
+        if let Some(dir) = state_dir() {
+            for (name, path) in files_in(&dir, is_draft) {
+                let data = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+                entries.push(Entry { name: format!("state/{}", name), data });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Writes the archive to `path` and returns how many files it holds,
+/// besides the manifest.
+pub fn export(path: &Path, options: &ExportOptions) -> Result<usize> {
+    let entries = collect(options)?;
+    if entries.is_empty() {
+        bail!("There are no settings or snippets to export yet");
+    }
+    let manifest = serde_json::json!({
+        "app": "PasteClipboard",
+        "format": FORMAT,
+        "version": env!("CARGO_PKG_VERSION"),
+        "secrets": options.include_secrets,
+        "drafts": options.include_drafts,
+        "secure_snippets": options.include_secure_snippets,
+    });
+    let mut archive = Vec::new();
+    append_entry(&mut archive, MANIFEST, manifest.to_string().as_bytes());
+    for entry in &entries {
+        append_entry(&mut archive, &entry.name, &entry.data);
+    }
+    archive.resize(archive.len() + 2 * BLOCK, 0);
+    // Only readable by this user: it may hold the tokens and secrets.
+    write_atomically_with_mode(path, &archive, 0o600)?;
+    Ok(entries.len())
+}
+
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+/// A pax record ("30 path=config/snippets/…\n"), whose length counts
+/// its own digits.
+fn pax_record(key: &str, value: &str) -> String {
+    let rest = format!(" {}={}\n", key, value);
+    let mut length = rest.len() + 1;
+    while length.to_string().len() + rest.len() != length {
+        length += 1;
+    }
+    format!("{}{}", length, rest)
+}
+
+/// Appends a ustar header for a plain file and its data, padded to blocks.
+/// A name too long for the header goes in a pax header before it.
+fn append_entry(archive: &mut Vec<u8>, name: &str, data: &[u8]) {
+    if name.len() <= 100 {
+        append_header(archive, b'0', name, data);
+        return;
+    }
+    append_header(archive, b'x', "pax_header", pax_record("path", name).as_bytes());
+    // Readers without pax support get the start of the file name.
+    let short = name.rsplit('/').next().unwrap_or(name);
+    let mut end = short.len().min(100);
+    while !short.is_char_boundary(end) {
+        end -= 1;
+    }
+    append_header(archive, b'0', &short[..end], data);
+}
+
+fn append_header(archive: &mut Vec<u8>, kind: u8, name: &str, data: &[u8]) {
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], data.len() as u64);
+    let mtime = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    octal(&mut header[136..148], mtime);
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[148..156].fill(b' ');
+    let checksum: u64 = header.iter().map(|b| u64::from(*b)).sum();
+    octal(&mut header[148..155], checksum);
+    header[155] = b' ';
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().div_ceil(BLOCK) * BLOCK, 0);
+}
+
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+fn field_text(field: &[u8]) -> Result<&str> {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[..end]).context("The archive has a file name that is not UTF-8")
+}
+
+/// The `path` of a pax header's records.
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(data).ok()?;
+    text.lines().find_map(|record| record.split_once(' ')?.1.strip_prefix("path=")).map(str::to_string)
+}
+
+/// The plain files in a tar archive, in order.
+fn read_entries(archive: &[u8]) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    // The name from a pax header, for the entry after it.
+    let mut long_name: Option<String> = None;
+    while offset + BLOCK <= archive.len() {
+        let header = &archive[offset..offset + BLOCK];
+        if header.iter().all(|b| *b == 0) {
+            return Ok(entries);
+        }
+        let stored = parse_octal(&header[148..156]).context("The archive is damaged (bad header)")?;
+        let sum: u64 = header.iter().enumerate().map(|(i, b)| if (148..156).contains(&i) { 32 } else { u64::from(*b) }).sum();
+        if stored != sum {
+            bail!("The archive is damaged (bad header checksum)");
+        }
+        let size = parse_octal(&header[124..136]).context("The archive is damaged (bad file size)")? as usize;
+        let prefix = field_text(&header[345..500])?;
+        let name = field_text(&header[..100])?;
+        let name = if prefix.is_empty() { name.to_string() } else { format!("{}/{}", prefix, name) };
+        let start = offset + BLOCK;
+        let Some(data) = archive.get(start..start + size) else {
+            bail!("The archive is cut off in {}", name);
+        };
+        match header[156] {
+            b'0' | 0 => entries.push(Entry { name: long_name.take().unwrap_or(name), data: data.to_vec() }),
+            b'x' => long_name = pax_path(data),
+            // Folders come with their files.
+            b'5' => {}
+            _ => bail!("{} in the archive is not a plain file", name),
+        }
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+    }
+    bail!("The archive is cut off")
+}
+
+/// Where an archive entry goes, or why it may not go anywhere.
+fn target_for(name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    let parts: Vec<&str> = path
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str().filter(|part| !part.starts_with('.')),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+        .with_context(|| format!("The archive has an unsafe path: {}", name))?;
+    let unexpected = || anyhow::anyhow!("The archive has a file this app does not export: {}", name);
+    let dir = |dir: Option<PathBuf>| dir.context("Cannot determine your configuration directory");
+    match parts.as_slice() {
+        // Exports of versions before config.toml have config.ini instead.
+        ["config", "config.toml" | "config.ini"] => config_path().context("Cannot determine your configuration directory"),
+        ["config", "profiles", file] if is_settings_file(file) => Ok(dir(profiles_dir())?.join(file)),
+        ["config", "snippets", file] if *file != TRASH_DIR => Ok(dir(snippets_dir())?.join(file)),
+        ["state", file] if is_draft(file) => Ok(dir(state_dir())?.join(file)),
+        _ => Err(unexpected()),
+    }
+}
+
+/// Reads and checks the archive at `path` without writing anything.
+pub fn read_archive(path: &Path) -> Result<ImportPlan> {
+    let size = fs::metadata(path).with_context(|| format!("Failed to open {}", path.display()))?.len();
+    if size > MAX_ARCHIVE_BYTES {
+        bail!("{} is too large to be an exported configuration", path.display());
+    }
+    let archive = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let not_ours = || anyhow::anyhow!("This is not a configuration exported by PasteClipboard");
+    if archive.get(..100).and_then(|name| field_text(name).ok()) != Some(MANIFEST) {
+        return Err(not_ours());
+    }
+    let mut entries = read_entries(&archive)?.into_iter();
+    let manifest = entries
+        .next()
+        .filter(|entry| entry.name == MANIFEST)
+        .and_then(|entry| serde_json::from_slice::<serde_json::Value>(&entry.data).ok())
+        .filter(|manifest| manifest["app"] == "PasteClipboard")
+        .ok_or_else(not_ours)?;
+    if manifest["format"].as_u64() != Some(FORMAT) {
+        bail!("The archive was exported by a newer version of PasteClipboard");
+    }
+    let mut files: Vec<PlannedFile> = Vec::new();
+    for entry in entries {
+        let target = target_for(&entry.name)?;
+        if files.iter().any(|file| file.target == target) {
+            bail!("The archive has {} twice", entry.name);
+        }
+        let mut data = entry.data;
+        if entry.name.ends_with(".ini") || entry.name.ends_with(".toml") {
+            let text = String::from_utf8(data.clone()).ok();
+            let conf = text.and_then(|text| parse_settings(Path::new(&entry.name), &text).ok());
+            let Some(conf) = conf else {
+                bail!("{} in the archive is not a valid settings file", entry.name);
+            };
+            if entry.name == "config/config.ini" {
+                data = toml_text(&conf, None).into_bytes();
+            }
+        }
+        files.push(PlannedFile { name: entry.name, replaces: target.exists(), target, data, secret_id: None });
+    }
+    if files.is_empty() {
+        bail!("The archive is empty");
+    }
+    mark_secure_snippets(&mut files);
+    Ok(ImportPlan { files })
+}
+
+/// Points the snippets that the archive's index stores securely at the
+/// keyring, under the ids the index gives them.
+fn mark_secure_snippets(files: &mut [PlannedFile]) {
+    let index_name = format!("config/snippets/{}", INDEX_FILE);
+    let Some(text) = files.iter().find(|file| file.name == index_name).and_then(|file| String::from_utf8(file.data.clone()).ok())
+    else {
+        return;
+    };
+    let mut index = Ini::new_cs();
+    if index.read(text).is_err() {
+        return;
+    }
+    for file in files.iter_mut() {
+        let Some(name) = file.name.strip_prefix("config/snippets/").and_then(|name| name.strip_suffix(".txt")) else {
+            continue;
+        };
+        if let Some(id) = index.get(name, SECRET_KEY).map(|id| id.trim().to_string()).filter(|id| !id.is_empty()) {
+            file.replaces = file.replaces || is_secure(name);
+            file.secret_id = Some(id);
+        }
+    }
+}
+
+impl ImportPlan {
+    /// Writes every file, all or none. Each is written to a temporary file
+    /// next to its target first; then the keyring snippets are stored, and
+    /// put back as they were if one fails; only then are the files renamed
+    /// into place, the files they replace being kept until every rename
+    /// has worked. Last, the plain copies of snippets now in the keyring
+    /// are removed. Only a crash during the renames can leave part of it.
+    pub fn apply(&self) -> Result<()> {
+        let mut staged: Vec<(PathBuf, &Path)> = Vec::new();
+        let remove_staged = |staged: &[(PathBuf, &Path)]| {
+            for (temp, _) in staged {
+                let _ = fs::remove_file(temp);
+            }
+        };
+        for file in self.files.iter().filter(|file| file.secret_id.is_none()) {
+            match stage(&file.target, ".import-tmp", Some(&file.data)) {
+                Ok(temp) => staged.push((temp, &file.target)),
+                Err(e) => {
+                    remove_staged(&staged);
+                    return Err(e);
+                }
+            }
+        }
+        if let Err(e) = self.store_secure_snippets() {
+            remove_staged(&staged);
+            return Err(e);
+        }
+        if let Err(e) = commit(&staged) {
+            remove_staged(&staged);
+            return Err(e);
+        }
+        for file in self.files.iter().filter(|file| file.secret_id.is_some()) {
+            // A plain copy from before would stay behind in the folder.
+            let _ = fs::remove_file(&file.target);
+        }
+        Ok(())
+    }
+
+    /// Stores the keyring snippets, or, when one fails, puts back what the
+    /// keyring held under each id before.
+    fn store_secure_snippets(&self) -> Result<()> {
+        let mut stored: Vec<(&str, String, Option<Zeroizing<String>>)> = Vec::new();
+        let result = (|| -> Result<()> {
+            for file in &self.files {
+                let Some(id) = &file.secret_id else {
+                    continue;
+                };
+                let name = file.target.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                let context = || format!("Cannot store snippet '{}' in the keyring", name);
+                let before = secret_store::lookup(id).with_context(context)?.map(Zeroizing::new);
+                let text = Zeroizing::new(String::from_utf8_lossy(&file.data).into_owned());
+                secret_store::store(id, &keyring_label(&name), &text).with_context(context)?;
+                stored.push((id, name, before));
+            }
+            Ok(())
+        })();
+        if result.is_err() {
+            for (id, name, before) in stored.iter().rev() {
+                let _ = match before {
+                    Some(text) => secret_store::store(id, &keyring_label(name), text),
+                    None => secret_store::delete(id),
+                };
+            }
+        }
+        result
+    }
+}
+
+/// Copies `data` to a new file next to `target`, named with `suffix`, or
+/// with `None` hard-links `target` there. The copy is private, since
+/// settings files hold tokens.
+fn stage(target: &Path, suffix: &str, data: Option<&[u8]>) -> Result<PathBuf> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut temp_name = target.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(suffix);
+    let temp = target.with_file_name(temp_name);
+    let _ = fs::remove_file(&temp);
+    let Some(data) = data else {
+        fs::hard_link(target, &temp).with_context(|| format!("Failed to keep a copy of {}", target.display()))?;
+        return Ok(temp);
+    };
+    let mut out = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temp)
+        .with_context(|| format!("Failed to create {}", temp.display()))?;
+    out.write_all(data).and_then(|()| out.sync_all()).with_context(|| format!("Failed to write {}", temp.display()))?;
+    Ok(temp)
+}
+
+/// Renames the staged files over their targets, keeping the replaced ones
+/// until all are in place; if a rename fails, the ones done are undone.
+fn commit(staged: &[(PathBuf, &Path)]) -> Result<()> {
+    let mut kept: Vec<(Option<PathBuf>, &Path)> = Vec::new();
+    let result = (|| -> Result<()> {
+        for (temp, target) in staged {
+            let old = if target.exists() { Some(stage(target, ".import-old", None)?) } else { None };
+            if let Err(e) = fs::rename(temp, target) {
+                if let Some(old) = old {
+                    let _ = fs::remove_file(old);
+                }
+                return Err(e).with_context(|| format!("Failed to replace {}", target.display()));
+            }
+            kept.push((old, target));
+        }
+        Ok(())
+    })();
+    for (old, target) in kept.iter().rev() {
+        let _ = match (old, result.is_ok()) {
+            (Some(old), true) => fs::remove_file(old),
+            (Some(old), false) => fs::rename(old, target),
+            (None, true) => Ok(()),
+            (None, false) => fs::remove_file(target),
+        };
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pasteclipboard-archive-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn a_failed_rename_puts_the_replaced_files_back() {
+        let dir = scratch("commit");
+        let (first, second) = (dir.join("config.toml"), dir.join("snippets"));
+        fs::write(&first, "old").unwrap();
+        // A file cannot be renamed over a folder.
+        fs::create_dir(&second).unwrap();
+        let staged = vec![
+            (stage(&first, ".import-tmp", Some(b"new")).unwrap(), first.as_path()),
+            (stage(&second, ".import-tmp", Some(b"new")).unwrap(), second.as_path()),
+        ];
+        assert_eq!(fs::metadata(&staged[0].0).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(commit(&staged).is_err());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old");
+        assert!(!dir.join("config.toml.import-old").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn commits_every_file() {
+        let dir = scratch("commit-all");
+        let (first, second) = (dir.join("config.toml"), dir.join("new.txt"));
+        fs::write(&first, "old").unwrap();
+        let staged = vec![
+            (stage(&first, ".import-tmp", Some(b"new")).unwrap(), first.as_path()),
+            (stage(&second, ".import-tmp", Some(b"text")).unwrap(), second.as_path()),
+        ];
+        commit(&staged).unwrap();
+        assert_eq!(fs::read_to_string(&first).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "text");
+        let mut names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["config.toml", "new.txt"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
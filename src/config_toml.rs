@@ -0,0 +1,218 @@
+//! config.toml, whose `[settings]` table is [`Settings`]: values are read
+//! and written with serde, so each has the type of its field rather than
+//! one guessed from its text (`delay_seconds = 2.5` stays a number, and a
+//! `pass_command` of "007" a string). The rest of the settings code sees
+//! each key with its value as text, as config.ini and the profiles have
+//! them. Saving edits the existing file rather than writing a new one, so
+//! its comments and order stay, and so does anything this version does
+//! not know, such as other tables and keys of newer versions.
+
+use anyhow::{anyhow, Result};
+use toml_edit::{Document, Item, Table, TableLike, Value};
+
+use crate::settings::Settings;
+
+/// The table the settings are in.
+const SECTION: &str = "settings";
+
+/// A single value as text, or `None` for arrays and inline tables.
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.value().clone()),
+        Value::Integer(number) => Some(number.value().to_string()),
+        Value::Float(number) => Some(number.value().to_string()),
+        Value::Boolean(flag) => Some(flag.value().to_string()),
+        Value::Datetime(time) => Some(time.value().to_string()),
+        Value::Array(_) | Value::InlineTable(_) => None,
+    }
+}
+
+/// `text` as a TOML document, with a one-line error.
+fn parse(text: &str) -> Result<Document> {
+    text.parse::<Document>().map_err(|e| {
+        // Without the excerpt of the file that is drawn with '|'.
+        let message = e.to_string();
+        let lines: Vec<&str> = message.lines().filter(|line| !line.contains('|') && !line.trim().is_empty()).collect();
+        anyhow!("{}", lines.join(": "))
+    })
+}
+
+/// The settings `table` holds, the keys it does not set at their
+/// defaults, as text.
+fn entries(table: Table) -> Result<Vec<(&'static str, String)>, toml_edit::de::Error> {
+    toml_edit::de::from_document::<Settings>(Document::from(table)).map(|settings| settings.entries())
+}
+
+/// config.toml's `[settings]` as each key with its value as text. A key
+/// of [`Settings`] is read as its field's type, and given as that field
+/// is written; one whose value does not have that type, like a key this
+/// version does not know, is given as written, for the settings code to
+/// report and replace with the default.
+pub fn read(text: &str) -> Result<Vec<(String, String)>> {
+    let doc = parse(text)?;
+    let Some(section) = doc.get(SECTION).and_then(Item::as_table_like) else {
+        return Ok(Vec::new());
+    };
+    let mut table = Table::new();
+    for (key, item) in section.iter().filter(|(_, item)| item.as_value().and_then(scalar_text).is_some()) {
+        table.insert(key, item.clone());
+    }
+    // Usually every value has its type, and one pass reads them all.
+    let all = entries(table.clone()).ok();
+    let mut read = Vec::new();
+    for (key, item) in table.iter() {
+        let Some(raw) = item.as_value().and_then(scalar_text) else {
+            continue;
+        };
+        let typed = match &all {
+            Some(all) => all.iter().find(|(known, _)| *known == key).map(|(_, text)| text.clone()),
+            None => {
+                let mut one = Table::new();
+                one.insert(key, item.clone());
+                entries(one).ok().and_then(|one| one.into_iter().find(|(known, _)| *known == key)).map(|(_, text)| text)
+            }
+        };
+        read.push((key.to_string(), typed.unwrap_or(raw)));
+    }
+    Ok(read)
+}
+
+/// The strings of config.toml's table `name`, such as `[keymap]`, in the
+/// order they are written; other values are left out.
+pub fn read_table(text: &str, name: &str) -> Result<Vec<(String, String)>> {
+    let doc = parse(text)?;
+    let Some(table) = doc.get(name).and_then(Item::as_table_like) else {
+        return Ok(Vec::new());
+    };
+    Ok(table
+        .iter()
+        .filter_map(|(key, item)| Some((key.to_string(), item.as_str()?.to_string())))
+        .collect())
+}
+
+/// `previous`, the file's current text, with `values` as its `[settings]`:
+/// keys it no longer has are removed, and values it has as they were are
+/// left alone. `settings` is what `values` read as; a known key whose text
+/// is the one `settings` has for it is written with its field's type, or
+/// left out while that field is unset, and anything else as a string.
+pub fn write(values: &[(String, String)], settings: &Settings, previous: Option<&str>) -> String {
+    let mut doc: Document = previous.and_then(|text| text.parse().ok()).unwrap_or_default();
+    let serialized = toml_edit::ser::to_document(settings).unwrap_or_default();
+    let entries = settings.entries();
+    let typed = |key: &str, text: &str| -> Option<Value> {
+        if entries.iter().any(|(known, value)| *known == key && value == text) {
+            serialized.get(key).and_then(Item::as_value).cloned()
+        } else {
+            Some(text.into())
+        }
+    };
+
+    let item = doc.as_table_mut().entry(SECTION).or_insert(Item::Table(Table::new()));
+    if !item.is_table_like() {
+        *item = Item::Table(Table::new());
+    }
+    let Some(table) = item.as_table_like_mut() else {
+        return doc.to_string();
+    };
+    let stale: Vec<String> = table
+        .iter()
+        .filter(|(key, item)| {
+            item.as_value().and_then(scalar_text).is_some() && !values.iter().any(|(k, _)| k.as_str() == *key)
+        })
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for key in stale {
+        table.remove(&key);
+    }
+    let mut values: Vec<&(String, String)> = values.iter().collect();
+    values.sort();
+    for (key, text) in values {
+        match table.get_mut(key).and_then(Item::as_value_mut) {
+            Some(old) if scalar_text(old).as_deref() == Some(text.as_str()) => {}
+            Some(old) => match typed(key, text) {
+                Some(value) => {
+                    let decor = old.decor().clone();
+                    *old = value;
+                    *old.decor_mut() = decor;
+                }
+                None => {
+                    table.remove(key);
+                }
+            },
+            None => {
+                if let Some(value) = typed(key, text) {
+                    table.insert(key, Item::Value(value));
+                }
+            }
+        }
+    }
+    doc.to_string()
+}
+
+fn remove_everywhere(table: &mut dyn TableLike, keys: &[&str]) {
+    for key in keys {
+        table.remove(key);
+    }
+    for (_, item) in table.iter_mut() {
+        if let Some(inner) = item.as_table_like_mut() {
+            remove_everywhere(inner, keys);
+        }
+    }
+}
+
+/// `text` without `keys` in any table, or `None` if it is not TOML.
+pub fn without_keys(text: &str, keys: &[&str]) -> Option<String> {
+    let mut doc: Document = text.parse().ok()?;
+    remove_everywhere(doc.as_table_mut(), keys);
+    Some(doc.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_read_as_their_fields() {
+        let text = "[settings]\ndelay_seconds = 2.5\nsounds = true\nchar_delay_ms = 40\npass_command = \"007\"\n";
+        let read = read(text).unwrap();
+        let expected = [("delay_seconds", "2.5"), ("sounds", "true"), ("char_delay_ms", "40"), ("pass_command", "007")];
+        assert_eq!(read, expected.map(|(key, value)| (key.to_string(), value.to_string())));
+    }
+
+    #[test]
+    fn values_of_another_type_are_kept_as_written() {
+        let text = "[settings]\nsounds = \"maybe\"\nchar_delay_ms = \"40\"\nnewer_key = 7\nlist = [1, 2]\n";
+        let read = read(text).unwrap();
+        let expected = [("sounds", "maybe"), ("char_delay_ms", "40"), ("newer_key", "7")];
+        assert_eq!(read, expected.map(|(key, value)| (key.to_string(), value.to_string())));
+    }
+
+    #[test]
+    fn writing_keeps_what_it_does_not_change() {
+        let previous = "# Mine\n[settings]\nlist = [1, 2]\nsounds = false # off\nstale = 1\n\n[other]\nx = 1\n";
+        let settings = Settings { sounds: true, pass_command: "007".to_string(), delay_seconds: 2.5, ..Settings::default() };
+        let values = [("sounds", "true"), ("pass_command", "007"), ("delay_seconds", "2.5"), ("newer_key", "7")]
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+        let text = write(&values, &settings, Some(previous));
+        for line in ["# Mine", "list = [1, 2]", "sounds = true # off", "pass_command = \"007\"", "delay_seconds = 2.5"] {
+            assert!(text.contains(line), "{} is not in\n{}", line, text);
+        }
+        assert!(text.contains("newer_key = \"7\"") && text.contains("[other]\nx = 1"), "{}", text);
+        assert!(!text.contains("stale"), "{}", text);
+    }
+
+    #[test]
+    fn a_table_is_read_as_its_strings() {
+        let text = "[settings]\nsounds = true\n\n[keymap]\nz = \"y\"\n\";\" = \"shift+KEY_COMMA\"\nn = 1\n";
+        let expected = [("z", "y"), (";", "shift+KEY_COMMA")].map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(read_table(text, "keymap").unwrap(), expected);
+        assert!(read_table(text, "other").unwrap().is_empty());
+    }
+
+    #[test]
+    fn unset_fields_are_left_out() {
+        let values = [("http_port", ""), ("delay_seconds", "3")].map(|(key, value)| (key.to_string(), value.to_string()));
+        let text = write(&values, &Settings::default(), Some("[settings]\nhttp_port = 8080\n"));
+        assert_eq!(text, "[settings]\ndelay_seconds = 3\n");
+    }
+}
@@ -0,0 +1,207 @@
+// "Export Configuration…" and "Import Configuration…": the settings,
+// profiles and snippets as one archive for another machine. Export asks
+// whether to include the secrets, drafts and secure snippets, which stay
+// out by default; import lists what the archive would add or replace
+// before it writes anything.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::SimpleAction, glib, Button, ButtonsType, CheckButton, FileChooserAction, FileChooserNative, FileFilter,
+    MessageDialog, MessageType, Orientation, ResponseType, Window,
+};
+use std::path::Path;
+use std::rc::Rc;
+use pasteclipboard::config_archive::{export, read_archive, ExportOptions, ImportPlan};
+use pasteclipboard::i18n::{args, gettext, ngettext};
+use pasteclipboard::settings::Settings;
+
+use crate::tabs;
+use crate::window::MainWindow;
+
+/// Files listed by name in the import question; the rest are counted.
+const LISTED_FILES: usize = 15;
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let add = |name: &str, handler: fn(&Rc<MainWindow>)| {
+        let action = SimpleAction::new(name, None);
+        let weak = Rc::downgrade(ui);
+        action.connect_activate(move |_, _| {
+            if let Some(ui) = weak.upgrade() {
+                handler(&ui);
+            }
+        });
+        ui.window.add_action(&action);
+    };
+    add("export-config", ask_export_options);
+    add("import-config", choose_import);
+}
+
+fn archive_filter() -> FileFilter {
+    let filter = FileFilter::new();
+    filter.set_name(Some(&gettext("Configuration archives")));
+    filter.add_pattern("*.tar");
+    filter
+}
+
+fn ask_export_options(ui: &Rc<MainWindow>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title(gettext("Export Configuration"))
+        .default_width(380)
+        .build();
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+    let note = gtk4::Label::new(Some(&gettext("Saves config.toml, the profiles and the snippets in one file to import on another machine.")));
+    note.set_xalign(0.0);
+    note.set_wrap(true);
+    vbox.append(&note);
+    let chk_secrets = CheckButton::with_label(&gettext("Include the HTTP, URL and remote typing tokens and the MQTT password"));
+    let chk_drafts = CheckButton::with_label(&gettext("Include the drafts of the tabs"));
+    let chk_secure = CheckButton::with_label(&gettext("Include the text of the snippets stored in the keyring"));
+    vbox.append(&chk_secrets);
+    vbox.append(&chk_drafts);
+    vbox.append(&chk_secure);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label(&gettext("Cancel"));
+    let btn_export = Button::with_label(&gettext("Export…"));
+    btn_export.add_css_class("suggested-action");
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_export);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_export));
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+    let ui = ui.clone();
+    let dialog_clone = dialog.clone();
+    btn_export.connect_clicked(move |_| {
+        let options = ExportOptions {
+            include_secrets: chk_secrets.is_active(),
+            include_drafts: chk_drafts.is_active(),
+            include_secure_snippets: chk_secure.is_active(),
+        };
+        dialog_clone.close();
+        choose_export_file(&ui, options);
+    });
+    dialog.present();
+}
+
+fn choose_export_file(ui: &Rc<MainWindow>, options: ExportOptions) {
+    let dialog = FileChooserNative::new(
+        Some(&gettext("Export Configuration")),
+        Some(&ui.window),
+        FileChooserAction::Save,
+        Some(&gettext("Export")),
+        Some(&gettext("Cancel")),
+    );
+    dialog.set_current_name("pasteclipboard-config.tar");
+    dialog.add_filter(&archive_filter());
+    let weak = Rc::downgrade(ui);
+    dialog.connect_response(glib::clone!(@strong dialog => move |_, response| {
+        let path = dialog.file().and_then(|f| f.path());
+        dialog.destroy();
+        if let (Some(ui), ResponseType::Accept, Some(path)) = (weak.upgrade(), response, path) {
+            match export(&path, &options) {
+                Ok(count) => {
+                    let text = ngettext("Exported {0} file to {1}.", "Exported {0} files to {1}.", count as u64);
+                    ui.set_status(&args(&text, &[&count, &path.display()]));
+                }
+                Err(e) => ui.set_error(&format!("{:#}.", e)),
+            }
+        }
+    }));
+    dialog.show();
+}
+
+fn choose_import(ui: &Rc<MainWindow>) {
+    let dialog = FileChooserNative::new(
+        Some(&gettext("Import Configuration")),
+        Some(&ui.window),
+        FileChooserAction::Open,
+        Some(&gettext("Open")),
+        Some(&gettext("Cancel")),
+    );
+    dialog.add_filter(&archive_filter());
+    let weak = Rc::downgrade(ui);
+    dialog.connect_response(glib::clone!(@strong dialog => move |_, response| {
+        let path = dialog.file().and_then(|f| f.path());
+        dialog.destroy();
+        if let (Some(ui), ResponseType::Accept, Some(path)) = (weak.upgrade(), response, path) {
+            match read_archive(&path) {
+                Ok(plan) => confirm_import(&ui, &path, plan),
+                Err(e) => ui.set_error(&format!("{:#}.", e)),
+            }
+        }
+    }));
+    dialog.show();
+}
+
+/// Lists what the import would add and replace, and applies it once the
+/// user agrees.
+fn confirm_import(ui: &Rc<MainWindow>, path: &Path, plan: ImportPlan) {
+    let mut lines: Vec<String> = plan
+        .files
+        .iter()
+        .take(LISTED_FILES)
+        .map(|file| {
+            let template = if file.replaces { gettext("Replaces {0}") } else { gettext("Adds {0}") };
+            args(&template, &[&file.target.display()])
+        })
+        .collect();
+    let more = plan.files.len().saturating_sub(LISTED_FILES) as u64;
+    if more > 0 {
+        lines.push(args(&ngettext("…and {0} more file", "…and {0} more files", more), &[&more]));
+    }
+    let replaced = plan.files.iter().filter(|file| file.replaces).count() as u64;
+    let text = if replaced > 0 {
+        args(&ngettext("Import and replace {0} file?", "Import and replace {0} files?", replaced), &[&replaced])
+    } else {
+        gettext("Import the configuration?")
+    };
+    let dialog = MessageDialog::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .message_type(if replaced > 0 { MessageType::Warning } else { MessageType::Question })
+        .buttons(ButtonsType::None)
+        .text(text)
+        .secondary_text(lines.join("\n"))
+        .build();
+    dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+    dialog.add_button(&gettext("Import"), ResponseType::Accept);
+    dialog.set_default_response(ResponseType::Cancel);
+    let weak = Rc::downgrade(ui);
+    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        let (Some(ui), ResponseType::Accept) = (weak.upgrade(), response) else {
+            return;
+        };
+        match plan.apply() {
+            Ok(()) => {
+                ui.settings_changed();
+                ui.show_config_problems(&Settings::load_with_warnings().1);
+                let _ = WidgetExt::activate_action(&ui.window, "win.refresh-snippets", None);
+                // Closing the window saves the open tabs over the drafts, so
+                // the imported ones are opened right away.
+                for file in plan.files.iter().filter(|file| file.name.starts_with("state/")) {
+                    if let Ok(text) = std::fs::read_to_string(&file.target) {
+                        tabs::open_draft(&ui, text);
+                    }
+                }
+                let count = plan.files.len() as u64;
+                let text = args(&ngettext("Imported {0} file from {1}.", "Imported {0} files from {1}.", count), &[&count, &name]);
+                ui.log.add(&text);
+                ui.set_status(&text);
+            }
+            Err(e) => ui.set_error(&format!("{:#}.", e)),
+        }
+    });
+    dialog.present();
+}
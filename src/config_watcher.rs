@@ -0,0 +1,103 @@
+// Reloads the settings when config.toml or a profile changes on disk, as
+// when it is edited by hand or checked out of a dotfiles repository. Saves
+// from this process are recognized by their content and skipped, so the
+// preferences dialog never causes a second reload. Runs read the typing
+// options as they start, so a new speed or line-break key is used from the
+// next run; the window shows the new values at once and says when a change
+// has to wait, like the backend or anything during a run.
+
+use gtk4::glib::{timeout_add_local, ControlFlow};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use pasteclipboard::i18n::{args, gettext};
+use pasteclipboard::profiles::profiles_dir;
+use pasteclipboard::settings::{config_path, is_own_write, Settings};
+
+use crate::window::MainWindow;
+
+/// How long the files must stay unchanged before they are read, so an
+/// editor's save (often several writes and a rename) is one reload.
+const SETTLE: Duration = Duration::from_millis(300);
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let Some(config) = config_path() else {
+        return;
+    };
+    if let Err(e) = watch(ui, config) {
+        eprintln!("{:#}.", e);
+    }
+}
+
+fn watch(ui: &Rc<MainWindow>, config: PathBuf) -> Result<()> {
+    let dir = config.parent().map(Path::to_path_buf).context("config.toml has no folder")?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("Failed to set up the config watcher")?;
+    // Editors that save by renaming a new file over the old one replace it,
+    // so the folder is watched rather than the file.
+    watcher
+        .watch(&dir, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", dir.display()))?;
+    let profiles = profiles_dir();
+    let is_settings_file = move |path: &Path| {
+        path == config
+            || profiles.as_deref().is_some_and(|dir| {
+                path.parent() == Some(dir) && path.extension().is_some_and(|ext| ext == "ini")
+            })
+    };
+
+    let weak = Rc::downgrade(ui);
+    let mut touched: Vec<PathBuf> = Vec::new();
+    let mut last_change = Instant::now();
+    timeout_add_local(Duration::from_millis(100), move || {
+        // The watcher stops when dropped, so this source keeps it.
+        let _watcher = &watcher;
+        let Some(ui) = weak.upgrade() else {
+            return ControlFlow::Break;
+        };
+        while let Ok(event) = receiver.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for path in event.paths.into_iter().filter(|path| is_settings_file(path)) {
+                last_change = Instant::now();
+                if !touched.contains(&path) {
+                    touched.push(path);
+                }
+            }
+        }
+        if !touched.is_empty() && last_change.elapsed() >= SETTLE {
+            let changed = std::mem::take(&mut touched);
+            if !changed.iter().all(|path| is_own_write(path)) {
+                reload(&ui);
+            }
+        }
+        ControlFlow::Continue
+    });
+    Ok(())
+}
+
+fn reload(ui: &MainWindow) {
+    let (settings, problems) = Settings::load_with_warnings();
+    ui.show_config_problems(&problems);
+    let changed = settings.changed_keys(&ui.applied_settings.borrow());
+    if changed.is_empty() {
+        return;
+    }
+    let keys = changed.join(", ");
+    ui.log.add(&format!("Settings changed on disk: {}.", keys));
+    ui.settings_changed();
+    let text = if ui.is_busy() || changed.contains(&"backend") {
+        args(&gettext("Reloaded the settings ({0}); the changes apply from the next run."), &[&keys])
+    } else {
+        args(&gettext("Reloaded the settings ({0})."), &[&keys])
+    };
+    ui.set_status(&text);
+}
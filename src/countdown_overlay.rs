@@ -0,0 +1,153 @@
+// The optional countdown overlay: a small undecorated window with the
+// remaining seconds and a Cancel button, kept above the target application
+// while the user looks at it. It must not take the keyboard focus away from
+// the target. On X11 it asks the window manager not to focus it and moves
+// it into the configured corner; Wayland has no such requests for normal
+// windows (that would need layer-shell), so there the compositor decides.
+
+use gtk4::prelude::*;
+use gtk4::{glib, Button, Label, Orientation, Window};
+use std::ffi::{c_int, c_ulong, c_void};
+use std::rc::Rc;
+use pasteclipboard::i18n::gettext;
+use pasteclipboard::settings::{load_overlay_corner_setting, OverlayCorner, Settings};
+
+use crate::keep_above;
+use crate::window::MainWindow;
+
+/// Distance from the screen edges, in logical pixels.
+const MARGIN: i32 = 24;
+
+extern "C" {
+    fn gdk_x11_surface_get_xid(surface: *mut c_void) -> c_ulong;
+    fn gdk_x11_display_get_xdisplay(display: *mut c_void) -> *mut c_void;
+    fn gdk_x11_surface_set_user_time(surface: *mut c_void, timestamp: u32);
+    fn gdk_x11_surface_set_skip_taskbar_hint(surface: *mut c_void, skip: glib::ffi::gboolean);
+    fn gdk_x11_surface_set_skip_pager_hint(surface: *mut c_void, skip: glib::ffi::gboolean);
+}
+
+#[link(name = "X11")]
+extern "C" {
+    fn XMoveWindow(display: *mut c_void, window: c_ulong, x: c_int, y: c_int) -> c_int;
+    fn XFlush(display: *mut c_void) -> c_int;
+}
+
+/// Before the window is mapped: a user time of 0 tells an EWMH window
+/// manager not to focus it, and it stays out of the taskbar.
+fn prepare_x11(window: &Window) {
+    let Some(surface) = window.surface().filter(|_| keep_above::supported(window)) else {
+        return;
+    };
+    unsafe {
+        let surface = surface.as_ptr() as *mut c_void;
+        gdk_x11_surface_set_user_time(surface, 0);
+        gdk_x11_surface_set_skip_taskbar_hint(surface, glib::ffi::GTRUE);
+        gdk_x11_surface_set_skip_pager_hint(surface, glib::ffi::GTRUE);
+    }
+}
+
+/// Moves the mapped window into `corner` of the monitor it is on.
+fn place_x11(window: &Window, corner: OverlayCorner) {
+    let Some(surface) = window.surface().filter(|_| keep_above::supported(window)) else {
+        return;
+    };
+    let display = WidgetExt::display(window);
+    let Some(monitor) = display.monitor_at_surface(&surface) else {
+        return;
+    };
+    let area = monitor.geometry();
+    let (width, height) = (window.width(), window.height());
+    let x = match corner {
+        OverlayCorner::TopLeft | OverlayCorner::BottomLeft => area.x() + MARGIN,
+        OverlayCorner::TopRight | OverlayCorner::BottomRight => area.x() + area.width() - width - MARGIN,
+    };
+    let y = match corner {
+        OverlayCorner::TopLeft | OverlayCorner::TopRight => area.y() + MARGIN,
+        OverlayCorner::BottomLeft | OverlayCorner::BottomRight => area.y() + area.height() - height - MARGIN,
+    };
+    // X11 positions are in device pixels.
+    let scale = surface.scale_factor();
+    unsafe {
+        let xdisplay = gdk_x11_display_get_xdisplay(display.as_ptr() as *mut c_void);
+        let xid = gdk_x11_surface_get_xid(surface.as_ptr() as *mut c_void);
+        if xdisplay.is_null() || xid == 0 {
+            return;
+        }
+        XMoveWindow(xdisplay, xid, x * scale, y * scale);
+        XFlush(xdisplay);
+    }
+}
+
+/// Assistive mode's seconds are twice the size.
+fn seconds_markup(seconds: u64, large: bool) -> String {
+    format!("<span size=\"{}\" weight=\"bold\">{}</span>", if large { "600%" } else { "300%" }, seconds)
+}
+
+/// Closes when dropped.
+pub struct CountdownOverlay {
+    window: Window,
+    lbl_seconds: Label,
+    large: bool,
+}
+
+impl CountdownOverlay {
+    /// Shows the overlay for a countdown of `seconds`, or returns `None`
+    /// when it is turned off. Assistive mode always shows it, larger.
+    pub fn show(ui: &Rc<MainWindow>, seconds: u64) -> Option<Self> {
+        let settings = Settings::load();
+        let large = settings.assistive_mode;
+        if !large && !settings.countdown_overlay {
+            return None;
+        }
+        let window = Window::builder()
+            .decorated(false)
+            .resizable(false)
+            .deletable(false)
+            .title("PasteClipboard countdown")
+            .build();
+        window.add_css_class("osd");
+
+        let vbox = gtk4::Box::new(Orientation::Vertical, 6);
+        vbox.set_margin_start(16);
+        vbox.set_margin_end(16);
+        vbox.set_margin_top(12);
+        vbox.set_margin_bottom(12);
+        let lbl_seconds = Label::new(None);
+        lbl_seconds.set_markup(&seconds_markup(seconds, large));
+        vbox.append(&lbl_seconds);
+        let btn_cancel = Button::with_label(&gettext("Cancel"));
+        // Not even a click should leave the focus here for long; the run is
+        // over anyway.
+        btn_cancel.set_focus_on_click(false);
+        let weak = Rc::downgrade(ui);
+        btn_cancel.connect_clicked(move |_| {
+            if let Some(ui) = weak.upgrade() {
+                ui.cancel_run();
+            }
+        });
+        vbox.append(&btn_cancel);
+        window.set_child(Some(&vbox));
+
+        window.connect_realize(prepare_x11);
+        let corner = load_overlay_corner_setting();
+        window.connect_map(move |window| {
+            keep_above::set(window, true);
+            let window = window.clone();
+            // The size is known once the first frame is laid out.
+            glib::idle_add_local_once(move || place_x11(&window, corner));
+        });
+        // Unlike present(), showing the window does not ask for the focus.
+        window.set_visible(true);
+        Some(CountdownOverlay { window, lbl_seconds, large })
+    }
+
+    pub fn set_remaining(&self, seconds: u64) {
+        self.lbl_seconds.set_markup(&seconds_markup(seconds, self.large));
+    }
+}
+
+impl Drop for CountdownOverlay {
+    fn drop(&mut self) {
+        self.window.destroy();
+    }
+}
@@ -0,0 +1,102 @@
+// The panic hook: every panic writes a crash report to the state directory
+// and, while GTK is running, says so in a dialog with the path and a button
+// to copy the report, rather than the app vanishing with the message on a
+// terminal nobody looks at. A panic on the main thread cannot be recovered
+// from, so the dialog runs its own main loop there before the app exits;
+// the typing worker's panics are caught and end the run instead.
+
+use gtk4::prelude::*;
+use gtk4::{glib, Button, Label, Orientation, Window};
+use std::backtrace::Backtrace;
+use std::panic;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use pasteclipboard::crash_report::{panic_message, report_text, write_report};
+use pasteclipboard::i18n::{args, gettext};
+
+/// Set while a main-thread crash dialog runs, so a panic inside it does not
+/// open another.
+static SHOWING: AtomicBool = AtomicBool::new(false);
+
+pub fn install() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let location = info.location().map(|location| location.to_string());
+        let text = report_text(&panic_message(info.payload()), location, &Backtrace::force_capture());
+        let path = match write_report(&text) {
+            Ok(path) => {
+                eprintln!("The crash report was saved to {}.", path.display());
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("{:#}.", e);
+                None
+            }
+        };
+        if gtk4::is_initialized_main_thread() {
+            if !SHOWING.swap(true, Ordering::SeqCst) {
+                let main_loop = glib::MainLoop::new(None, false);
+                let window = show(path.as_deref(), text);
+                window.connect_destroy(glib::clone!(@strong main_loop => move |_| main_loop.quit()));
+                main_loop.run();
+            }
+        } else if gtk4::is_initialized() {
+            glib::MainContext::default().invoke(move || {
+                show(path.as_deref(), text);
+            });
+        }
+    }));
+}
+
+fn show(path: Option<&Path>, text: String) -> Window {
+    let parent = gtk4::gio::Application::default()
+        .and_then(|app| app.downcast::<gtk4::Application>().ok())
+        .and_then(|app| app.active_window());
+    let dialog = Window::builder()
+        .modal(true)
+        .title(gettext("PasteClipboard Crashed"))
+        .default_width(440)
+        .build();
+    dialog.set_transient_for(parent.as_ref());
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+    let message = match path {
+        Some(path) => {
+            args(&gettext("Something went wrong inside PasteClipboard. The details were saved to {0}."), &[&path.display()])
+        }
+        None => gettext("Something went wrong inside PasteClipboard, and the details could not be saved."),
+    };
+    let label = Label::new(Some(&message));
+    label.set_xalign(0.0);
+    label.set_wrap(true);
+    label.set_selectable(true);
+    vbox.append(&label);
+    let hint = Label::new(Some(&gettext("Please include them when you report the problem.")));
+    hint.set_xalign(0.0);
+    hint.set_wrap(true);
+    vbox.append(&hint);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_copy = Button::with_label(&gettext("Copy Details"));
+    let btn_close = Button::with_label(&gettext("Close"));
+    btn_close.add_css_class("suggested-action");
+    buttons.append(&btn_copy);
+    buttons.append(&btn_close);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_close));
+
+    btn_copy.connect_clicked(move |button| {
+        button.clipboard().set_text(&text);
+        button.set_label(&gettext("Copied"));
+    });
+    let dialog_clone = dialog.clone();
+    btn_close.connect_clicked(move |_| dialog_clone.close());
+    dialog.present();
+    dialog
+}
@@ -0,0 +1,52 @@
+//! Crash reports: what a panic said, where and on which thread, with its
+//! backtrace, written to a file in the state directory so it can go into a
+//! bug report even when nobody saw the terminal.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use gio::glib;
+
+use crate::settings::state_path;
+
+/// The text a panic was raised with, for the usual `&str` and `String`
+/// payloads.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(text) = payload.downcast_ref::<&str>() {
+        text.to_string()
+    } else if let Some(text) = payload.downcast_ref::<String>() {
+        text.clone()
+    } else {
+        "(no message)".to_string()
+    }
+}
+
+/// The report for a panic with `message` at `location`.
+pub fn report_text(message: &str, location: Option<String>, backtrace: &Backtrace) -> String {
+    let thread = std::thread::current();
+    format!(
+        "PasteClipboard {} panicked on thread '{}'\nat {}:\n{}\n\nBacktrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        thread.name().unwrap_or("unnamed"),
+        location.as_deref().unwrap_or("an unknown location"),
+        message,
+        backtrace
+    )
+}
+
+/// Writes `text` to a new crash-<time>.txt in the state directory.
+pub fn write_report(text: &str) -> Result<PathBuf> {
+    let time = glib::DateTime::now_local()
+        .and_then(|now| now.format("%Y%m%d-%H%M%S"))
+        .map(|t| t.to_string())
+        .unwrap_or_default();
+    let path = state_path(&format!("crash-{}.txt", time)).context("No home directory to write the crash report in")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
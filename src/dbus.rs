@@ -0,0 +1,139 @@
+// D-Bus interface for starting runs from scripts and other applications.
+//
+//   gdbus call --session --dest com.example.PasteClipboard \
+//       --object-path /com/example/PasteClipboard \
+//       --method com.example.PasteClipboard.Type "hello" 3
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{DBusConnection, DBusMethodInvocation, DBusNodeInfo},
+    glib::{self, Variant},
+    Application,
+};
+use std::rc::Rc;
+use pasteclipboard::settings::load_typing_options;
+use pasteclipboard::typing::{TypingEvent, MAX_DELAY_SECONDS, MAX_REMOTE_TEXT_BYTES};
+
+use crate::window::{format_count, RunListener, WindowSlot};
+
+pub const OBJECT_PATH: &str = "/com/example/PasteClipboard";
+pub const INTERFACE: &str = "com.example.PasteClipboard";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="com.example.PasteClipboard">
+    <method name="Type">
+      <arg type="s" name="text" direction="in"/>
+      <arg type="u" name="delay_seconds" direction="in"/>
+    </method>
+    <method name="Show"/>
+    <method name="Confirm">
+      <arg type="b" name="confirmed" direction="out"/>
+    </method>
+    <signal name="Progress">
+      <arg type="u" name="typed"/>
+      <arg type="u" name="total"/>
+    </signal>
+    <signal name="Finished">
+      <arg type="b" name="success"/>
+      <arg type="s" name="message"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+/// Exports the interface on the application's own bus connection. Only the
+/// primary instance calls this, from `startup`.
+pub fn register(app: &Application, conn: &DBusConnection, slot: Rc<WindowSlot>) -> Result<(), glib::Error> {
+    let node = DBusNodeInfo::for_xml(INTERFACE_XML)?;
+    let Some(interface) = node.lookup_interface(INTERFACE) else {
+        return Err(glib::Error::new(gtk4::gio::IOErrorEnum::Failed, "interface missing from its own XML"));
+    };
+    let app = app.downgrade();
+    conn.register_object(
+        OBJECT_PATH,
+        &interface,
+        move |conn, _, _, _, method, params, invocation| {
+            let Some(app) = app.upgrade() else {
+                return;
+            };
+            match method {
+                "Type" => handle_type(&app, &slot, &conn, &params, invocation),
+                "Show" => {
+                    slot.get(&app).window.present();
+                    invocation.return_value(None);
+                }
+                // For a hotkey that confirms a waiting run without taking
+                // the focus from the target.
+                "Confirm" => {
+                    let confirmed = slot.peek().is_some_and(|ui| ui.assistive.confirm(&ui));
+                    invocation.return_value(Some(&(confirmed,).to_variant()));
+                }
+                _ => invocation.return_dbus_error("org.freedesktop.DBus.Error.UnknownMethod", method),
+            }
+        },
+        |_, _, _, _, _| ().to_variant(),
+        |_, _, _, _, _, _| false,
+    )?;
+    Ok(())
+}
+
+fn handle_type(
+    app: &Application,
+    slot: &WindowSlot,
+    conn: &DBusConnection,
+    params: &Variant,
+    invocation: DBusMethodInvocation,
+) {
+    let Some((text, delay)) = params.get::<(String, u32)>() else {
+        invocation.return_dbus_error("org.freedesktop.DBus.Error.InvalidArgs", "Expected (su)");
+        return;
+    };
+    if text.len() > MAX_REMOTE_TEXT_BYTES {
+        invocation.return_dbus_error(
+            "com.example.PasteClipboard.Error.TooLarge",
+            &format!("Text is larger than {} bytes", format_count(MAX_REMOTE_TEXT_BYTES)),
+        );
+        return;
+    }
+    if u64::from(delay) > MAX_DELAY_SECONDS {
+        invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.InvalidArgs",
+            "Invalid delay (must be a number from 0–86400).",
+        );
+        return;
+    }
+    let options = match load_typing_options() {
+        Ok(options) => options,
+        Err(e) => {
+            invocation.return_dbus_error("com.example.PasteClipboard.Error.Config", &format!("{} in config.toml", e));
+            return;
+        }
+    };
+
+    let ui = slot.get(app);
+    if ui.is_busy() {
+        invocation.return_dbus_error("com.example.PasteClipboard.Error.Busy", "A typing run is already in progress.");
+        return;
+    }
+    ui.run_listeners.borrow_mut().push(signal_listener(conn.clone()));
+    ui.start_run(text.into(), u64::from(delay), options);
+    invocation.return_value(None);
+}
+
+/// Mirrors a run started over D-Bus as Progress and Finished signals.
+fn signal_listener(conn: DBusConnection) -> RunListener {
+    let to_u32 = |n: usize| u32::try_from(n).unwrap_or(u32::MAX);
+    Box::new(move |event| {
+        let (name, args) = match event {
+            TypingEvent::Progress { typed, total } => ("Progress", (to_u32(*typed), to_u32(*total)).to_variant()),
+            TypingEvent::Finished { .. } => ("Finished", (true, "Done typing.").to_variant()),
+            TypingEvent::Failed { error, stats } => (
+                "Finished",
+                (false, format!("Typing failed after {} characters: {:#}", format_count(stats.typed), error)).to_variant(),
+            ),
+            _ => return,
+        };
+        let _ = conn.emit_signal(None, OBJECT_PATH, INTERFACE, name, Some(&args));
+    })
+}
@@ -0,0 +1,241 @@
+// `pasteclipboard install-desktop-entry`: a launcher for installs that come
+// without one, such as `cargo install`. It writes a .desktop file to
+// ~/.local/share/applications and the app's icon to the user's hicolor
+// theme, and lists what it wrote, with a hash of each file, in a manifest;
+// `--uninstall` removes those files and only while they are unchanged.
+
+use anyhow::{bail, Context, Result};
+use directories::BaseDirs;
+use ring::digest::{digest, SHA256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use pasteclipboard::settings::portable_base;
+
+use crate::{APP_ID, APP_NAME};
+
+const USAGE: &str = "Usage: pasteclipboard install-desktop-entry [--uninstall]
+
+Writes a launcher (a .desktop file) to ~/.local/share/applications and the
+app's icon to ~/.local/share/icons, for installs that did not come with
+them.
+
+  --uninstall   Remove the files an earlier install-desktop-entry wrote
+";
+
+const ICON_SVG: &str = include_str!("../data/com.example.PasteClipboard.svg");
+
+const MANIFEST_NAME: &str = "desktop-entry.manifest";
+
+/// Where the files go, and the manifest that lists them.
+struct Paths {
+    desktop: PathBuf,
+    icon: PathBuf,
+    manifest: PathBuf,
+}
+
+fn paths() -> Result<Paths> {
+    if portable_base().is_some() {
+        bail!("Portable mode does not write to the home directory, where the launcher would go");
+    }
+    let base = BaseDirs::new().context("Cannot determine your home directory")?;
+    let data = base.data_local_dir();
+    Ok(Paths {
+        desktop: data.join("applications").join(format!("{}.desktop", APP_ID)),
+        icon: data.join("icons/hicolor/scalable/apps").join(format!("{}.svg", APP_ID)),
+        manifest: data.join("PasteClipboard").join(MANIFEST_NAME),
+    })
+}
+
+/// Quotes a path for an Exec= key: quoting protects spaces, a backslash
+/// escapes `"`, `` ` ``, `$` and itself within the quotes, `%` starts a
+/// field code, and the value as a whole escapes backslashes once more.
+fn quote_exec_path(path: &Path) -> Result<String> {
+    let Some(path) = path.to_str().filter(|path| !path.contains(['\n', '\r'])) else {
+        bail!("The executable path {} cannot be written to a .desktop file", path.display());
+    };
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '"' | '`' | '$' => quoted.extend(['\\', '\\', c]),
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Ok(quoted)
+}
+
+fn desktop_contents(exe: &Path) -> Result<String> {
+    Ok(format!(
+        "[Desktop Entry]
+Type=Application
+Name={name}
+Comment=Type text into windows that do not accept pasting
+Exec={exec}
+Icon={id}
+Terminal=false
+Categories=Utility;
+Keywords=type;paste;keyboard;clipboard;
+StartupNotify=true
+StartupWMClass={id}
+",
+        name = APP_NAME,
+        exec = quote_exec_path(exe)?,
+        id = APP_ID,
+    ))
+}
+
+fn sha256(data: &[u8]) -> String {
+    digest(&SHA256, data).as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The files the manifest lists, with the hash of what was written to
+/// each, one `<hash>  <path>` line per file as sha256sum writes them.
+fn load_manifest(path: &Path) -> Result<BTreeMap<PathBuf, String>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    };
+    let mut files = BTreeMap::new();
+    for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let Some((hash, file)) = line.split_once("  ") else {
+            bail!("Line {} of {} is not `<hash>  <path>`", number + 1, path.display());
+        };
+        files.insert(PathBuf::from(file), hash.to_string());
+    }
+    Ok(files)
+}
+
+/// Writes the manifest, or removes it once it lists nothing.
+fn save_manifest(path: &Path, files: &BTreeMap<PathBuf, String>) -> Result<()> {
+    if files.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        };
+    }
+    let mut text = String::new();
+    for (file, hash) in files {
+        let Some(file) = file.to_str() else {
+            bail!("The path {} is not valid UTF-8", file.display());
+        };
+        text.push_str(&format!("{}  {}\n", hash, file));
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Writes `contents` to `path` unless a file is there that the manifest
+/// does not account for, and records it in the manifest. Returns what it
+/// did, for the report.
+fn install_file(path: &Path, contents: &str, manifest: &mut BTreeMap<PathBuf, String>) -> Result<&'static str> {
+    let verb = match fs::read(path) {
+        Ok(existing) if existing == contents.as_bytes() => "Kept",
+        Ok(existing) if manifest.get(path) == Some(&sha256(&existing)) => "Updated",
+        Ok(_) => bail!("{} already exists and was not written by install-desktop-entry; left as it is", path.display()),
+        Err(e) if e.kind() == ErrorKind::NotFound => "Wrote",
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    };
+    if verb != "Kept" {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    manifest.insert(path.to_path_buf(), sha256(contents.as_bytes()));
+    Ok(verb)
+}
+
+/// Installs each file on its own, so one that fails is reported without
+/// keeping the rest from being written. Returns whether all of them were.
+fn install(paths: &Paths) -> Result<bool> {
+    let exe = std::env::current_exe().context("Cannot determine the path of this executable")?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+    let files = [(&paths.desktop, desktop_contents(&exe)?), (&paths.icon, ICON_SVG.to_string())];
+
+    let mut manifest = load_manifest(&paths.manifest)?;
+    let mut all = true;
+    for (path, contents) in files {
+        match install_file(path, &contents, &mut manifest) {
+            Ok(verb) => println!("{} {}", verb, path.display()),
+            Err(e) => {
+                eprintln!("install-desktop-entry: {:#}", e);
+                all = false;
+            }
+        }
+    }
+    save_manifest(&paths.manifest, &manifest)?;
+    Ok(all)
+}
+
+/// Removes each file the manifest lists while it still has the contents
+/// that were written; one that has changed is left, and stays listed.
+fn uninstall(paths: &Paths) -> Result<bool> {
+    let mut manifest = load_manifest(&paths.manifest)?;
+    if manifest.is_empty() {
+        println!("Nothing to remove: install-desktop-entry has not written any files.");
+        return Ok(true);
+    }
+    let mut all = true;
+    manifest.retain(|path, hash| {
+        let result = match fs::read(path) {
+            Ok(existing) if sha256(&existing) == *hash => {
+                fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+            Ok(_) => Err(anyhow::anyhow!("{} has changed since it was written; left as it is", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                println!("Already gone: {}", path.display());
+                return false;
+            }
+            Err(e) => Err(e).with_context(|| format!("Cannot read {}", path.display())),
+        };
+        match result {
+            Ok(()) => {
+                println!("Removed {}", path.display());
+                false
+            }
+            Err(e) => {
+                eprintln!("install-desktop-entry: {:#}", e);
+                all = false;
+                true
+            }
+        }
+    });
+    save_manifest(&paths.manifest, &manifest)?;
+    Ok(all)
+}
+
+/// Runs the subcommand with the arguments that follow its name.
+pub fn run(args: &[String]) -> i32 {
+    let mut remove = false;
+    for arg in args {
+        match arg.as_str() {
+            "--uninstall" => remove = true,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return 0;
+            }
+            other => {
+                eprintln!("Unknown argument '{}'.\n\n{}", other, USAGE);
+                return 2;
+            }
+        }
+    }
+    let result = paths().and_then(|paths| if remove { uninstall(&paths) } else { install(&paths) });
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("install-desktop-entry: {:#}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! The setup checks behind the first-run assistant, "Run Diagnostics" and
+//! `--doctor`: the uinput device, its permissions, the session type and
+//! every backend's probe, each with a hint on how to fix a failure.
+
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::backend::{first_available, probe_all, Backend};
+use crate::uinput_access::{group_status, test_access, GroupStatus, UINPUT_GROUP, UINPUT_PATH};
+
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+    /// What to do about a failure; empty when there is nothing to do.
+    pub hint: String,
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check { name: name.into(), passed: true, detail: detail.into(), hint: String::new() }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check { name: name.into(), passed: false, detail: detail.into(), hint: hint.into() }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionType {
+    Wayland,
+    X11,
+    /// No graphical session in the environment (a console or SSH login).
+    None,
+}
+
+/// What kind of graphical session this process runs in, going by the same
+/// variables the backends' probes look at.
+pub fn session_type() -> SessionType {
+    let declared = std::env::var("XDG_SESSION_TYPE").unwrap_or_default().to_ascii_lowercase();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() || declared == "wayland" {
+        SessionType::Wayland
+    } else if std::env::var_os("DISPLAY").is_some() || declared == "x11" {
+        SessionType::X11
+    } else {
+        SessionType::None
+    }
+}
+
+fn device_check() -> Check {
+    if Path::new(UINPUT_PATH).exists() {
+        Check::pass("uinput device", format!("{} exists", UINPUT_PATH))
+    } else {
+        Check::fail(
+            "uinput device",
+            format!("{} is missing", UINPUT_PATH),
+            "Load the kernel module with 'sudo modprobe uinput'; to load it at every boot, put 'uinput' in \
+             /etc/modules-load.d/uinput.conf.",
+        )
+    }
+}
+
+fn access_check() -> Check {
+    const NAME: &str = "uinput permissions";
+    match test_access() {
+        Ok(()) => Check::pass(NAME, format!("{} is writable", UINPUT_PATH)),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            Check::fail(NAME, format!("{} cannot be opened because it is missing", UINPUT_PATH), "Load the module first.")
+        }
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            let hint = match group_status(UINPUT_GROUP) {
+                GroupStatus::MemberAfterLogin => {
+                    format!("You were added to the '{}' group after logging in; log out and back in.", UINPUT_GROUP)
+                }
+                _ => format!(
+                    "Install the udev rule (a failed run offers to do it for you, or run 'sudo pasteclipboard \
+                     --install-udev-rule'), or add yourself to the '{}' group and log in again.",
+                    UINPUT_GROUP
+                ),
+            };
+            Check::fail(NAME, format!("permission denied on {}", UINPUT_PATH), hint)
+        }
+        Err(e) => Check::fail(NAME, format!("cannot open {}: {}", UINPUT_PATH, e), ""),
+    }
+}
+
+fn session_check() -> Check {
+    const NAME: &str = "Session type";
+    match session_type() {
+        SessionType::Wayland => Check::pass(NAME, "Wayland; xdo cannot type into native Wayland windows"),
+        SessionType::X11 => Check::pass(NAME, "X11; every backend can work"),
+        SessionType::None => Check::fail(
+            NAME,
+            "no graphical session found (neither WAYLAND_DISPLAY nor DISPLAY is set)",
+            "Run PasteClipboard from your desktop session; only uinput can type from a console.",
+        ),
+    }
+}
+
+fn backend_hint(backend: Backend) -> &'static str {
+    match backend {
+        Backend::Uinput => "See the uinput checks above.",
+        #[cfg(feature = "x11-xdo")]
+        Backend::Xdo => "xdo needs an X11 session; on Wayland use uinput or portal.",
+        Backend::Portal => {
+            "Install xdg-desktop-portal and your desktop's portal backend (e.g. xdg-desktop-portal-gnome or -kde)."
+        }
+        Backend::Libvirt => "Only needed to type into a local VM: set libvirt_domain (and libvirt_uri) in config.toml.",
+    }
+}
+
+/// Runs every check, in the order the report shows them. The last one says
+/// whether any backend can type at all.
+pub fn run_checks() -> Vec<Check> {
+    let mut checks = vec![device_check(), access_check(), session_check()];
+    let statuses = probe_all();
+    for (backend, status) in &statuses {
+        let name = format!("Backend {}", backend);
+        checks.push(if status.available {
+            Check::pass(name, status.detail.clone())
+        } else {
+            Check::fail(name, status.detail.clone(), backend_hint(*backend))
+        });
+    }
+    checks.push(match first_available(&statuses) {
+        Some(backend) => Check::pass("Typing", format!("automatic selection would use {}", backend)),
+        None => Check::fail("Typing", "no backend is available", "Fix one of the backends above."),
+    });
+    checks
+}
+
+/// Whether text can be typed at all, according to `run_checks`' result.
+pub fn can_type(checks: &[Check]) -> bool {
+    checks.last().is_some_and(|check| check.passed)
+}
+
+/// The plain-text report `--doctor` prints.
+pub fn report(checks: &[Check]) -> String {
+    let mut out = String::new();
+    for check in checks {
+        let _ = writeln!(out, "[{}] {}: {}", if check.passed { "PASS" } else { "FAIL" }, check.name, check.detail);
+        if !check.passed && !check.hint.is_empty() {
+            let _ = writeln!(out, "       {}", check.hint);
+        }
+    }
+    out
+}
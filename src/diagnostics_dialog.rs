@@ -0,0 +1,171 @@
+// The setup assistant: shown once on the first launch and again from "Run
+// Diagnostics", it runs the checks from `diagnostics` and lists what passed,
+// what failed and how to fix it.
+
+use gtk4::prelude::*;
+use gtk4::{
+    glib::{timeout_add_local, ControlFlow},
+    Button, Grid, Image, Label, Orientation, PolicyType, ScrolledWindow, Window,
+};
+use std::rc::Rc;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use pasteclipboard::diagnostics::{can_type, run_checks, Check};
+use pasteclipboard::session::{load_window_flag, save_window_flag};
+
+use crate::test_target;
+use crate::window::MainWindow;
+
+const SHOWN_KEY: &str = "setup_assistant_shown";
+
+/// Shows the assistant if it has never been shown before.
+pub fn show_on_first_run(ui: &Rc<MainWindow>) {
+    if load_window_flag(SHOWN_KEY) {
+        return;
+    }
+    save_window_flag(SHOWN_KEY, true);
+    show(ui, true);
+}
+
+fn fill_results(grid: &Grid, checks: &[Check]) {
+    while let Some(child) = grid.first_child() {
+        grid.remove(&child);
+    }
+    for (row, check) in checks.iter().enumerate() {
+        let icon = Image::from_icon_name(if check.passed { "emblem-ok-symbolic" } else { "dialog-error-symbolic" });
+        icon.set_valign(gtk4::Align::Start);
+        icon.set_tooltip_text(Some(if check.passed { "Passed" } else { "Failed" }));
+        let name = Label::new(Some(&check.name));
+        name.set_xalign(0.0);
+        name.set_valign(gtk4::Align::Start);
+        name.add_css_class("heading");
+
+        let text = gtk4::Box::new(Orientation::Vertical, 2);
+        let detail = Label::new(Some(&check.detail));
+        detail.set_xalign(0.0);
+        detail.set_wrap(true);
+        detail.set_selectable(true);
+        text.append(&detail);
+        if !check.passed && !check.hint.is_empty() {
+            let hint = Label::new(Some(&check.hint));
+            hint.set_xalign(0.0);
+            hint.set_wrap(true);
+            hint.set_selectable(true);
+            hint.add_css_class("dim-label");
+            text.append(&hint);
+        }
+        text.set_hexpand(true);
+
+        grid.attach(&icon, 0, row as i32, 1, 1);
+        grid.attach(&name, 1, row as i32, 1, 1);
+        grid.attach(&text, 2, row as i32, 1, 1);
+    }
+}
+
+/// Runs the checks on a worker thread, since probing the portal talks to
+/// the session bus, and fills the results in once they are done.
+fn run(grid: &Grid, lbl_summary: &Label, btn_again: &Button) {
+    btn_again.set_sensitive(false);
+    lbl_summary.set_text("Checking…");
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(run_checks());
+    });
+    let grid = grid.clone();
+    let lbl_summary = lbl_summary.clone();
+    let btn_again = btn_again.clone();
+    timeout_add_local(Duration::from_millis(100), move || {
+        let checks = match receiver.try_recv() {
+            Ok(checks) => checks,
+            Err(TryRecvError::Empty) => return ControlFlow::Continue,
+            Err(TryRecvError::Disconnected) => {
+                lbl_summary.set_text("The checks stopped unexpectedly.");
+                btn_again.set_sensitive(true);
+                return ControlFlow::Break;
+            }
+        };
+        fill_results(&grid, &checks);
+        let failed = checks.iter().filter(|check| !check.passed).count();
+        lbl_summary.set_text(&match (can_type(&checks), failed) {
+            (true, 0) => "Everything is set up.".to_string(),
+            (true, _) => "PasteClipboard can type. The failed items only matter if you want to use those backends."
+                .to_string(),
+            (false, _) => "PasteClipboard cannot type yet. Fix one of the failed items and run the checks again."
+                .to_string(),
+        });
+        btn_again.set_sensitive(true);
+        ControlFlow::Break
+    });
+}
+
+/// `welcome` adds the first-run introduction above the results.
+pub fn show(ui: &Rc<MainWindow>, welcome: bool) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title(if welcome { "Welcome to PasteClipboard" } else { "Diagnostics" })
+        .default_width(560)
+        .default_height(460)
+        .build();
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    if welcome {
+        let intro = Label::new(Some(
+            "PasteClipboard types text as if it came from a keyboard. Depending on your system that needs the uinput \
+             kernel module, write access to /dev/uinput, an X11 session or the desktop's RemoteDesktop portal. \
+             These checks show what works here. You can run them again from the menu with Run Diagnostics.",
+        ));
+        intro.set_xalign(0.0);
+        intro.set_wrap(true);
+        vbox.append(&intro);
+    }
+
+    let lbl_summary = Label::new(None);
+    lbl_summary.set_xalign(0.0);
+    lbl_summary.set_wrap(true);
+    vbox.append(&lbl_summary);
+
+    let grid = Grid::builder().row_spacing(10).column_spacing(12).build();
+    let scrolled = ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Never)
+        .vexpand(true)
+        .child(&grid)
+        .build();
+    vbox.append(&scrolled);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_again = Button::with_label("Run Again");
+    let btn_test = Button::with_label("Test Target…");
+    btn_test.set_tooltip_text(Some("Type a test text into a window of this app and check what arrives"));
+    let btn_close = Button::with_label("Close");
+    btn_close.add_css_class("suggested-action");
+    buttons.append(&btn_again);
+    buttons.append(&btn_test);
+    buttons.append(&btn_close);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_close));
+
+    let dialog_clone = dialog.clone();
+    btn_close.connect_clicked(move |_| dialog_clone.close());
+    let (weak, dialog_clone) = (Rc::downgrade(ui), dialog.clone());
+    btn_test.connect_clicked(move |_| {
+        dialog_clone.close();
+        if let Some(ui) = weak.upgrade() {
+            test_target::show(&ui, None);
+        }
+    });
+    let grid_clone = grid.clone();
+    let lbl_clone = lbl_summary.clone();
+    btn_again.connect_clicked(move |btn_again| run(&grid_clone, &lbl_clone, btn_again));
+
+    run(&grid, &lbl_summary, &btn_again);
+    dialog.present();
+}
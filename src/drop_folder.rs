@@ -0,0 +1,86 @@
+//! Files dropped into the watched directory (`watch_directory`, ~/TypeMe by
+//! default): which ones count, reading them safely, and filing them under
+//! done/ once handled.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use directories::BaseDirs;
+
+use crate::settings::{portable_base, Settings};
+use crate::typing::MAX_REMOTE_TEXT_BYTES;
+
+/// Subfolder processed files are moved into.
+pub const DONE_DIR: &str = "done";
+
+/// The configured directory, or ~/TypeMe (TypeMe/ next to a portable
+/// executable).
+pub fn watch_directory() -> Option<PathBuf> {
+    match Settings::load().watch_directory {
+        dir if !dir.is_empty() => Some(match dir.strip_prefix("~/") {
+            Some(rest) => BaseDirs::new()?.home_dir().join(rest),
+            None => PathBuf::from(dir),
+        }),
+        _ => match portable_base() {
+            Some(base) => Some(base.join("TypeMe")),
+            None => BaseDirs::new().map(|base| base.home_dir().join("TypeMe")),
+        },
+    }
+}
+
+/// Whether `path` is something the user dropped, rather than an editor's
+/// temporary or backup file.
+pub fn is_candidate(path: &Path) -> bool {
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => !name.starts_with('.') && !name.ends_with('~') && !name.ends_with(".part"),
+        None => false,
+    }
+}
+
+/// Reads a dropped file. Symlinks are refused rather than followed, so a
+/// link cannot make the app type a file from elsewhere.
+pub fn read_dropped_file(path: &Path) -> Result<String> {
+    let metadata = fs::symlink_metadata(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    if metadata.file_type().is_symlink() {
+        bail!("{} is a symbolic link; copy the file itself into the folder", path.display());
+    }
+    if !metadata.is_file() {
+        bail!("{} is not a regular file", path.display());
+    }
+    if metadata.len() > MAX_REMOTE_TEXT_BYTES as u64 {
+        bail!("{} is larger than {} bytes", path.display(), MAX_REMOTE_TEXT_BYTES);
+    }
+    let bytes = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    String::from_utf8(bytes).map_err(|_| anyhow::anyhow!("{} is not UTF-8 text", path.display()))
+}
+
+/// Moves `path` into `dir`/done/, adding a number to the name if a file of
+/// that name was already processed.
+pub fn move_to_done(dir: &Path, path: &Path) -> Result<PathBuf> {
+    let done = dir.join(DONE_DIR);
+    fs::create_dir_all(&done).with_context(|| format!("Failed to create {}", done.display()))?;
+    let name = path.file_name().context("Dropped file has no name")?;
+    let mut target = done.join(name);
+    let stem = Path::new(name).file_stem().unwrap_or(name).to_string_lossy().into_owned();
+    let extension = Path::new(name).extension().map(|e| format!(".{}", e.to_string_lossy()));
+    let mut n = 1;
+    while target.exists() {
+        target = done.join(format!("{}-{}{}", stem, n, extension.as_deref().unwrap_or("")));
+        n += 1;
+    }
+    fs::rename(path, &target).with_context(|| format!("Failed to move {} to {}", path.display(), done.display()))?;
+    Ok(target)
+}
+
+/// Whether `path` names a file directly inside `dir` (not in done/ or
+/// elsewhere), compared without following a final symlink.
+pub fn is_inside(dir: &Path, path: &Path) -> bool {
+    let Ok(dir) = dir.canonicalize() else {
+        return false;
+    };
+    match (path.parent().and_then(|p| p.canonicalize().ok()), path.file_name()) {
+        (Some(parent), Some(_)) => parent == dir,
+        _ => false,
+    }
+}
@@ -0,0 +1,110 @@
+// How the text area shows its text: monospace or not, the font size and
+// word wrap, toggled from the View section of the menu and kept in
+// config.toml.
+
+use gtk4::prelude::*;
+use gtk4::{gio, pango, Application, CssProvider, TextView};
+use std::rc::Rc;
+use pasteclipboard::settings::{save_setting, Settings};
+
+use crate::window::MainWindow;
+
+const MIN_FONT_SIZE: i32 = 6;
+const MAX_FONT_SIZE: i32 = 48;
+const CSS_CLASS: &str = "editor";
+
+/// The saved size in points; 0 (or none) means the theme's.
+fn load_font_size() -> i32 {
+    i32::try_from(Settings::load().editor_font_size)
+        .ok()
+        .filter(|size| (MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(size))
+        .unwrap_or(0)
+}
+
+fn css(monospace: bool, font_size: i32) -> String {
+    let mut rules = String::new();
+    if monospace {
+        rules.push_str("font-family: monospace;");
+    }
+    if font_size > 0 {
+        rules.push_str(&format!("font-size: {}pt;", font_size));
+    }
+    format!("textview.{} {{ {} }}", CSS_CLASS, rules)
+}
+
+struct EditorStyle {
+    text_view: TextView,
+    provider: CssProvider,
+}
+
+impl EditorStyle {
+    fn apply(&self) {
+        self.provider.load_from_data(&css(Settings::load().monospace, load_font_size()));
+    }
+
+    /// The size in use: the saved one, or the theme's for the current font.
+    fn current_size(&self) -> i32 {
+        match load_font_size() {
+            0 => self.text_view.pango_context().font_description().map_or(11, |font| font.size() / pango::SCALE),
+            size => size,
+        }
+    }
+
+    fn zoom(&self, step: i32) {
+        let size = (self.current_size() + step).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        save_setting("editor_font_size", &size.to_string());
+        self.apply();
+    }
+}
+
+pub fn install(app: &Application, ui: &Rc<MainWindow>, text_view: &TextView) {
+    text_view.add_css_class(CSS_CLASS);
+    let provider = CssProvider::new();
+    gtk4::style_context_add_provider_for_display(
+        &text_view.display(),
+        &provider,
+        gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+    let style = Rc::new(EditorStyle { text_view: text_view.clone(), provider });
+    style.apply();
+
+    let monospace = gio::SimpleAction::new_stateful("monospace", None, &Settings::load().monospace.to_variant());
+    let style_clone = style.clone();
+    monospace.connect_change_state(move |action, state| {
+        let Some(on) = state.and_then(|s| s.get::<bool>()) else {
+            return;
+        };
+        action.set_state(&on.to_variant());
+        save_setting("monospace", &on.to_string());
+        style_clone.apply();
+    });
+    ui.window.add_action(&monospace);
+
+    let wrap = Settings::load().word_wrap;
+    text_view.set_wrap_mode(if wrap { gtk4::WrapMode::WordChar } else { gtk4::WrapMode::None });
+    let word_wrap = gio::SimpleAction::new_stateful("word-wrap", None, &wrap.to_variant());
+    let view = text_view.clone();
+    word_wrap.connect_change_state(move |action, state| {
+        let Some(on) = state.and_then(|s| s.get::<bool>()) else {
+            return;
+        };
+        action.set_state(&on.to_variant());
+        view.set_wrap_mode(if on { gtk4::WrapMode::WordChar } else { gtk4::WrapMode::None });
+        save_setting("word_wrap", &on.to_string());
+    });
+    ui.window.add_action(&word_wrap);
+
+    let add = |name: &str, accels: &[&str], handler: fn(&EditorStyle)| {
+        let action = gio::SimpleAction::new(name, None);
+        let style = style.clone();
+        action.connect_activate(move |_, _| handler(&style));
+        ui.window.add_action(&action);
+        app.set_accels_for_action(&format!("win.{}", name), accels);
+    };
+    add("zoom-in", &["<Control>equal", "<Control>plus", "<Control>KP_Add"], |style| style.zoom(1));
+    add("zoom-out", &["<Control>minus", "<Control>KP_Subtract"], |style| style.zoom(-1));
+    add("zoom-reset", &["<Control>0", "<Control>KP_0"], |style| {
+        save_setting("editor_font_size", "0");
+        style.apply();
+    });
+}
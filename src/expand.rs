@@ -0,0 +1,203 @@
+//! Opt-in `${NAME}` variable expansion applied to the text right before
+//! typing, and the `{DATE:…}`, `{TIME:…}` and `{COUNTER:…}` placeholders
+//! templates may use, with the counters' persisted values.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use configparser::ini::Ini;
+use gio::glib;
+
+use crate::settings::state_path;
+
+/// Replaces every `${NAME}` in `text` with the value `lookup` returns for it.
+///
+/// Only the braced form is recognised, so `$VAR` in ordinary shell snippets
+/// is left alone, and `$${NAME}` produces a literal `${NAME}`. Unknown
+/// variables stay literal unless `strict` is set, in which case they are
+/// all reported together as an error.
+pub fn expand_variables(text: &str, lookup: impl Fn(&str) -> Option<String>, strict: bool) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut unknown: Vec<&str> = Vec::new();
+    let mut rest = text;
+
+    while let Some(pos) = rest.find("${") {
+        let (before, from_brace) = rest.split_at(pos);
+        let name = from_brace[2..]
+            .find('}')
+            .map(|end| &from_brace[2..2 + end])
+            .filter(|name| is_variable_name(name));
+        let Some(name) = name else {
+            out.push_str(&rest[..pos + 2]);
+            rest = &rest[pos + 2..];
+            continue;
+        };
+        let token = &from_brace[..name.len() + 3];
+
+        if let Some(before) = before.strip_suffix('$') {
+            out.push_str(before);
+            out.push_str(token);
+        } else {
+            out.push_str(before);
+            match lookup(name) {
+                Some(value) => out.push_str(&value),
+                None => {
+                    if !unknown.contains(&name) {
+                        unknown.push(name);
+                    }
+                    out.push_str(token);
+                }
+            }
+        }
+        rest = &from_brace[token.len()..];
+    }
+    out.push_str(rest);
+
+    if strict && !unknown.is_empty() {
+        bail!("Unknown variable{}: {}", if unknown.len() == 1 { "" } else { "s" }, unknown.join(", "));
+    }
+    Ok(out)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Looks a variable up in the process environment, falling back to the
+/// kernel hostname for `HOSTNAME` and `LOGNAME` for `USER`, which shells
+/// commonly set without exporting.
+pub fn env_lookup(name: &str) -> Option<String> {
+    if let Ok(value) = std::env::var(name) {
+        return Some(value);
+    }
+    match name {
+        "HOSTNAME" => std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .ok()
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty()),
+        "USER" => std::env::var("LOGNAME").ok(),
+        _ => None,
+    }
+}
+
+/// The formats `{DATE}` and `{TIME}` use without one of their own.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+pub const DEFAULT_TIME_FORMAT: &str = "%H:%M";
+
+/// Text with its date, time and counter placeholders filled in, and the
+/// counter values it used, to be saved with [`commit_counters`] once the
+/// run completes.
+#[derive(Debug, Default)]
+pub struct Stamped {
+    pub text: String,
+    pub counters: Vec<(String, u64)>,
+}
+
+/// Replaces `{DATE:format}` and `{TIME:format}` with `now` in that
+/// strftime-style format (GLib's, `%Y-%m-%d %H:%M:%S` and the like; the
+/// format may be left out), and `{COUNTER:name}` with one more than the
+/// counter's value in `counters`. Each counter has the same value
+/// throughout the text. `\{` keeps a placeholder literal. An invalid format
+/// or counter name is an error, so the run is refused before it is planned.
+pub fn expand_stamps(text: &str, now: &glib::DateTime, counters: &HashMap<String, u64>) -> Result<Stamped> {
+    let mut stamped = Stamped { text: String::with_capacity(text.len()), counters: Vec::new() };
+    let mut rest = text;
+    while let Some(pos) = rest.find('{') {
+        let (before, from_brace) = rest.split_at(pos);
+        let stamp = ["DATE", "TIME", "COUNTER"]
+            .into_iter()
+            .find(|kind| from_brace[1..].starts_with(kind))
+            .and_then(|kind| Some((kind, &from_brace[1 + kind.len()..from_brace.find('}')?])))
+            .filter(|(_, argument)| argument.is_empty() || argument.starts_with(':'));
+        let Some((kind, argument)) = stamp else {
+            stamped.text.push_str(&rest[..pos + 1]);
+            rest = &rest[pos + 1..];
+            continue;
+        };
+        let token = &from_brace[..kind.len() + argument.len() + 2];
+        rest = &from_brace[token.len()..];
+        if let Some(before) = before.strip_suffix('\\') {
+            stamped.text.push_str(before);
+            stamped.text.push_str(token);
+            continue;
+        }
+        stamped.text.push_str(before);
+        let argument = argument.strip_prefix(':').unwrap_or(argument);
+        match kind {
+            "COUNTER" => {
+                if argument.is_empty() {
+                    bail!("{{COUNTER}} needs a name, like {{COUNTER:ticket}}");
+                }
+                if !is_counter_name(argument) {
+                    bail!("Invalid counter name in {} (use letters, digits, '_', '-' and '.')", token);
+                }
+                let value = match stamped.counters.iter().find(|(name, _)| name == argument) {
+                    Some((_, value)) => *value,
+                    None => {
+                        let value = counters.get(argument).copied().unwrap_or(0).saturating_add(1);
+                        stamped.counters.push((argument.to_string(), value));
+                        value
+                    }
+                };
+                stamped.text.push_str(&value.to_string());
+            }
+            _ => {
+                let format = match argument {
+                    "" if kind == "DATE" => DEFAULT_DATE_FORMAT,
+                    "" => DEFAULT_TIME_FORMAT,
+                    format => format,
+                };
+                match now.format(format) {
+                    Ok(formatted) => stamped.text.push_str(&formatted),
+                    Err(_) => bail!("Invalid date format in {}", token),
+                }
+            }
+        }
+    }
+    stamped.text.push_str(rest);
+    Ok(stamped)
+}
+
+fn is_counter_name(name: &str) -> bool {
+    name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn counters_path() -> Option<PathBuf> {
+    state_path("counters.ini")
+}
+
+/// Each counter's value as of the last run that completed with it.
+pub fn load_counters() -> HashMap<String, u64> {
+    let mut conf = Ini::new_cs();
+    counters_path()
+        .and_then(|path| conf.load(path).ok())
+        .and_then(|mut sections| sections.remove("counters"))
+        .map(|counters| {
+            counters.into_iter().filter_map(|(name, value)| Some((name, value?.trim().parse().ok()?))).collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Saves the values a completed run typed. A counter another run moved
+/// further meanwhile keeps its value.
+pub fn commit_counters(used: &[(String, u64)]) {
+    let Some(path) = counters_path().filter(|_| !used.is_empty()) else {
+        return;
+    };
+    let saved = load_counters();
+    let mut conf = Ini::new_cs();
+    let _ = conf.load(&path);
+    for (name, value) in used {
+        if saved.get(name).is_none_or(|saved| saved < value) {
+            conf.set("counters", name, Some(value.to_string()));
+        }
+    }
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = conf.write(path);
+}
@@ -0,0 +1,212 @@
+// Open, Save and Save As for the text buffer (Ctrl+O, Ctrl+S,
+// Shift+Ctrl+S), with the file name and an unsaved-changes marker in the
+// window title.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{self, SimpleAction},
+    glib,
+    Application, ButtonsType, FileChooserAction, FileChooserNative, MessageDialog, MessageType, ResponseType,
+};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use pasteclipboard::run_state::RunState;
+use pasteclipboard::session::{load_last_directory, save_last_directory};
+use pasteclipboard::text_file::{decode, normalize, write_atomically};
+
+use crate::tabs;
+use crate::window::MainWindow;
+use crate::APP_NAME;
+
+pub fn install(app: &Application, ui: &Rc<MainWindow>) {
+    let add = |name: &str, accels: &[&str], handler: fn(&Rc<MainWindow>)| {
+        let action = SimpleAction::new(name, None);
+        let weak = Rc::downgrade(ui);
+        action.connect_activate(move |_, _| {
+            if let Some(ui) = weak.upgrade() {
+                handler(&ui);
+            }
+        });
+        ui.window.add_action(&action);
+        app.set_accels_for_action(&format!("win.{}", name), accels);
+    };
+    add("open", &["<Control>o"], open);
+    add("save", &["<Control>s"], save);
+    add("save-as", &["<Control><Shift>s"], save_as);
+
+    let weak = Rc::downgrade(ui);
+    ui.buffer.connect_modified_changed(move |_| {
+        if let Some(ui) = weak.upgrade() {
+            update_title(&ui);
+        }
+    });
+}
+
+/// "*notes.txt — PasteClipboard" while an opened file has unsaved changes,
+/// prefixed with the countdown ("[7s] PasteClipboard") before a run.
+pub fn update_title(ui: &MainWindow) {
+    let mut title = match ui.file.borrow().as_ref().and_then(|p| p.file_name()) {
+        Some(name) => format!(
+            "{}{} — {}",
+            if ui.buffer.is_modified() { "*" } else { "" },
+            name.to_string_lossy(),
+            APP_NAME
+        ),
+        None => APP_NAME.to_string(),
+    };
+    if let RunState::CountingDown { remaining_seconds } = *ui.run_state.borrow() {
+        if remaining_seconds > 0 {
+            title = format!("[{}s] {}", remaining_seconds, title);
+        }
+    }
+    ui.window.set_title(Some(&title));
+    tabs::refresh_label(ui);
+}
+
+/// Whether closing or replacing the buffer would lose edits to an opened
+/// file. Text that was never saved to a file is not tracked.
+pub fn has_unsaved_changes(ui: &MainWindow) -> bool {
+    ui.file.borrow().is_some() && ui.buffer.is_modified()
+}
+
+/// Runs `then` once the user agrees to lose unsaved changes (or saved
+/// them), or straight away if there are none.
+pub fn confirm_discard(ui: &Rc<MainWindow>, then: impl Fn(&Rc<MainWindow>) + 'static) {
+    if !has_unsaved_changes(ui) {
+        then(ui);
+        return;
+    }
+    let name = ui.file.borrow().as_ref().and_then(|p| p.file_name()).map(|n| n.to_string_lossy().into_owned());
+    let dialog = MessageDialog::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .message_type(MessageType::Question)
+        .buttons(ButtonsType::None)
+        .text(format!("Save changes to {}?", name.unwrap_or_default()))
+        .secondary_text("Your changes will be lost if you don't save them.")
+        .build();
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    dialog.add_button("Discard", ResponseType::Reject);
+    dialog.add_button("Save", ResponseType::Accept);
+    dialog.set_default_response(ResponseType::Accept);
+
+    let weak = Rc::downgrade(ui);
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        match response {
+            ResponseType::Reject => then(&ui),
+            ResponseType::Accept if save_to_current(&ui) => then(&ui),
+            _ => {}
+        }
+    });
+    dialog.present();
+}
+
+fn chooser(ui: &MainWindow, title: &str, action: FileChooserAction, accept: &str) -> FileChooserNative {
+    let dialog = FileChooserNative::new(Some(title), Some(&ui.window), action, Some(accept), Some("Cancel"));
+    if let Some(dir) = load_last_directory() {
+        let _ = dialog.set_current_folder(Some(&gio::File::for_path(dir)));
+    }
+    dialog
+}
+
+fn remember_folder(path: &Path) {
+    if let Some(dir) = path.parent() {
+        save_last_directory(dir);
+    }
+}
+
+fn open(ui: &Rc<MainWindow>) {
+    confirm_discard(ui, |ui| {
+        let dialog = chooser(ui, "Open", FileChooserAction::Open, "Open");
+        let weak: Weak<MainWindow> = Rc::downgrade(ui);
+        dialog.connect_response(glib::clone!(@strong dialog => move |_, response| {
+            let path = dialog.file().and_then(|f| f.path());
+            dialog.destroy();
+            if let (Some(ui), ResponseType::Accept, Some(path)) = (weak.upgrade(), response, path) {
+                open_path(&ui, &path);
+            }
+        }));
+        dialog.show();
+    });
+}
+
+fn open_path(ui: &MainWindow, path: &Path) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            ui.set_error(&format!("Cannot read {}: {}", name, e));
+            return;
+        }
+    };
+    match decode(&bytes) {
+        Ok(decoded) => {
+            ui.replace_text(&normalize(&decoded.text));
+            ui.buffer.set_modified(false);
+            *ui.file.borrow_mut() = Some(path.to_path_buf());
+            remember_folder(path);
+            update_title(ui);
+            if decoded.replaced {
+                ui.set_warning(&format!("Opened {}; invalid UTF-8 was replaced with \u{fffd}.", name));
+            } else {
+                ui.set_status(&format!("Opened {}.", name));
+            }
+        }
+        Err(e) => ui.set_error(&format!("{} was not opened: {}.", name, e)),
+    }
+}
+
+fn write_to(ui: &MainWindow, path: &Path) -> bool {
+    // Saved exactly as shown; normalization only happens on the way in.
+    let text = ui.buffer.text(&ui.buffer.start_iter(), &ui.buffer.end_iter(), true);
+    match write_atomically(path, text.as_bytes()) {
+        Ok(()) => {
+            *ui.file.borrow_mut() = Some(path.to_path_buf());
+            ui.buffer.set_modified(false);
+            remember_folder(path);
+            update_title(ui);
+            ui.set_status(&format!("Saved {}.", path.display()));
+            true
+        }
+        Err(e) => {
+            ui.set_error(&format!("{:#}.", e));
+            false
+        }
+    }
+}
+
+/// Saves to the opened file; returns whether that happened. Without one,
+/// asks for a name, which finishes later.
+fn save_to_current(ui: &Rc<MainWindow>) -> bool {
+    let current: Option<PathBuf> = ui.file.borrow().clone();
+    match current {
+        Some(path) => write_to(ui, &path),
+        None => {
+            save_as(ui);
+            false
+        }
+    }
+}
+
+fn save(ui: &Rc<MainWindow>) {
+    save_to_current(ui);
+}
+
+fn save_as(ui: &Rc<MainWindow>) {
+    let dialog = chooser(ui, "Save As", FileChooserAction::Save, "Save");
+    let current_name = ui.file.borrow().as_ref().and_then(|p| p.file_name()).map(|n| n.to_string_lossy().into_owned());
+    dialog.set_current_name(current_name.as_deref().unwrap_or("untitled.txt"));
+    let weak: Weak<MainWindow> = Rc::downgrade(ui);
+    dialog.connect_response(glib::clone!(@strong dialog => move |_, response| {
+        let path = dialog.file().and_then(|f| f.path());
+        dialog.destroy();
+        if let (Some(ui), ResponseType::Accept, Some(path)) = (weak.upgrade(), response, path) {
+            write_to(&ui, &path);
+        }
+    }));
+    dialog.show();
+}
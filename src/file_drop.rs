@@ -0,0 +1,105 @@
+// Dropping a file (or plain text) onto the text area loads it. Holding
+// Shift while dropping appends instead of replacing.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gdk::{DragAction, ModifierType},
+    gio,
+    glib::Type,
+    ButtonsType, DropTarget, MessageDialog, MessageType, PropagationPhase, ResponseType, TextView,
+};
+use std::rc::{Rc, Weak};
+use pasteclipboard::text_file::{decode, normalize, LARGE_FILE_BYTES};
+
+use crate::window::{format_count, MainWindow};
+
+pub fn attach(ui: &Rc<MainWindow>, text_view: &TextView) {
+    let target = DropTarget::new(Type::INVALID, DragAction::COPY);
+    target.set_types(&[gio::File::static_type(), String::static_type()]);
+    // Run before the text view's own handler, which would insert a dropped
+    // file's URI as text.
+    target.set_propagation_phase(PropagationPhase::Capture);
+
+    let weak = Rc::downgrade(ui);
+    target.connect_drop(move |target, value, _, _| {
+        let Some(ui) = weak.upgrade() else {
+            return false;
+        };
+        let append = target.current_event_state().contains(ModifierType::SHIFT_MASK);
+        if let Ok(file) = value.get::<gio::File>() {
+            load_file(&ui, &file, append);
+            true
+        } else if let Ok(text) = value.get::<String>() {
+            insert_text(&ui, &normalize(&text), append);
+            ui.set_status("Loaded the dropped text.");
+            true
+        } else {
+            false
+        }
+    });
+    text_view.add_controller(target);
+}
+
+fn insert_text(ui: &MainWindow, text: &str, append: bool) {
+    if append {
+        ui.buffer.insert(&mut ui.buffer.end_iter(), text);
+    } else {
+        ui.replace_text(text);
+    }
+}
+
+fn load_file(ui: &Rc<MainWindow>, file: &gio::File, append: bool) {
+    let name = file.basename().map(|n| n.display().to_string()).unwrap_or_else(|| file.uri().to_string());
+    let size = file
+        .query_info("standard::size", gio::FileQueryInfoFlags::NONE, gio::Cancellable::NONE)
+        .map(|info| info.size().max(0) as u64)
+        .unwrap_or(0);
+    if size <= LARGE_FILE_BYTES {
+        read_and_insert(ui, file, &name, append);
+        return;
+    }
+
+    let confirm = MessageDialog::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .message_type(MessageType::Question)
+        .buttons(ButtonsType::YesNo)
+        .text(format!("Load {}?", name))
+        .secondary_text(format!(
+            "It is {} KiB, which will take a long time to type.",
+            format_count((size / 1024) as usize)
+        ))
+        .build();
+    let weak: Weak<MainWindow> = Rc::downgrade(ui);
+    let file = file.clone();
+    confirm.connect_response(move |confirm, response| {
+        confirm.close();
+        if response == ResponseType::Yes {
+            if let Some(ui) = weak.upgrade() {
+                read_and_insert(&ui, &file, &name, append);
+            }
+        }
+    });
+    confirm.present();
+}
+
+fn read_and_insert(ui: &MainWindow, file: &gio::File, name: &str, append: bool) {
+    let bytes = match file.load_contents(gio::Cancellable::NONE) {
+        Ok((bytes, _)) => bytes,
+        Err(e) => {
+            ui.set_error(&format!("Cannot read {}: {}", name, e));
+            return;
+        }
+    };
+    match decode(&bytes) {
+        Ok(decoded) => {
+            insert_text(ui, &normalize(&decoded.text), append);
+            if decoded.replaced {
+                ui.set_warning(&format!("Loaded {}; invalid UTF-8 was replaced with \u{fffd}.", name));
+            } else {
+                ui.set_status(&format!("Loaded {}.", name));
+            }
+        }
+        Err(e) => ui.set_error(&format!("{} was not loaded: {}.", name, e)),
+    }
+}
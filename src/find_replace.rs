@@ -0,0 +1,460 @@
+// The find and replace bar above the text area (Ctrl+F, Ctrl+H). Matches
+// are kept as buffer marks and highlighted with a tag, so moving between
+// them and editing around them never searches the text again; a new search
+// only runs once the pattern or the text stops changing.
+
+use gtk4::glib::translate::{from_glib_full, ToGlibPtr};
+use gtk4::glib::{self, Regex, RegexCompileFlags, RegexMatchFlags};
+use gtk4::prelude::*;
+use gtk4::{
+    Button, CheckButton, Entry, Label, Orientation, SearchBar, SearchEntry, TextBuffer, TextIter, TextMark, TextTag,
+    TextView,
+};
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::rc::Rc;
+use std::time::Duration;
+use pasteclipboard::i18n::{args, gettext, ngettext};
+
+use crate::status_area::StatusArea;
+use crate::window::format_count;
+
+/// A match as char offsets, with its replacement if one was asked for.
+struct Match {
+    start: i32,
+    end: i32,
+    replacement: Option<String>,
+}
+
+/// Every non-empty match of `regex` in `text`. In a regex search
+/// `replacement` may use \1 and \g<name> references.
+///
+/// glib 0.19's `Regex::match_full` and `MatchInfo::next` debug-assert on the
+/// ordinary "no more matches" result, so the loop talks to GRegex directly.
+fn find_all(regex: &Regex, text: &str, replacement: Option<&str>) -> Result<Vec<Match>, glib::Error> {
+    let replacement = replacement.map(|r| CString::new(r).unwrap_or_default());
+    let mut matches = Vec::new();
+    let (mut chars, mut last_byte) = (0i32, 0usize);
+    unsafe {
+        let mut info = ptr::null_mut();
+        let mut error = ptr::null_mut();
+        let mut found = glib::ffi::g_regex_match_full(
+            regex.to_glib_none().0,
+            text.as_ptr() as *const c_char,
+            text.len() as isize,
+            0,
+            0,
+            &mut info,
+            &mut error,
+        );
+        while found != 0 && error.is_null() {
+            let (mut start, mut end) = (0, 0);
+            glib::ffi::g_match_info_fetch_pos(info, 0, &mut start, &mut end);
+            let (start, end) = (start as usize, end as usize);
+            if end > start {
+                let expanded = match &replacement {
+                    Some(replacement) => {
+                        let out = glib::ffi::g_match_info_expand_references(info, replacement.as_ptr(), &mut error);
+                        if !error.is_null() {
+                            break;
+                        }
+                        Some(from_glib_full::<_, glib::GString>(out).to_string())
+                    }
+                    None => None,
+                };
+                chars += text[last_byte..start].chars().count() as i32;
+                let match_start = chars;
+                chars += text[start..end].chars().count() as i32;
+                last_byte = end;
+                matches.push(Match { start: match_start, end: chars, replacement: expanded });
+            }
+            found = glib::ffi::g_match_info_next(info, &mut error);
+        }
+        if !info.is_null() {
+            glib::ffi::g_match_info_free(info);
+        }
+        if !error.is_null() {
+            return Err(from_glib_full(error));
+        }
+    }
+    Ok(matches)
+}
+
+pub struct FindBar {
+    pub bar: SearchBar,
+    entry: SearchEntry,
+    chk_regex: CheckButton,
+    chk_case: CheckButton,
+    lbl_matches: Label,
+    replace_row: gtk4::Box,
+    replace_entry: Entry,
+    buffer: TextBuffer,
+    view: TextView,
+    /// Where replace-all reports how many it replaced.
+    status: Rc<StatusArea>,
+    tag: TextTag,
+    /// Start and end of every match, in buffer order.
+    matches: RefCell<Vec<(TextMark, TextMark)>>,
+    pending: RefCell<Option<glib::SourceId>>,
+}
+
+impl FindBar {
+    pub fn attach(text_view: &TextView, status: &Rc<StatusArea>) -> Rc<FindBar> {
+        let buffer = text_view.buffer();
+        let tag = TextTag::builder()
+            .name("found")
+            .background_rgba(&gtk4::gdk::RGBA::new(0.98, 0.82, 0.25, 0.5))
+            .build();
+        buffer.tag_table().add(&tag);
+
+        let entry = SearchEntry::builder().hexpand(true).placeholder_text(gettext("Find")).build();
+        let chk_regex = CheckButton::with_label(&gettext("Regular expression"));
+        let chk_case = CheckButton::with_label(&gettext("Match case"));
+        let btn_prev = Button::builder()
+            .icon_name("go-up-symbolic")
+            .tooltip_text(gettext("Previous match (Shift+Ctrl+G)"))
+            .build();
+        let btn_next = Button::builder()
+            .icon_name("go-down-symbolic")
+            .tooltip_text(gettext("Next match (Ctrl+G)"))
+            .build();
+        let lbl_matches = Label::new(None);
+        lbl_matches.add_css_class("dim-label");
+        let find_row = gtk4::Box::new(Orientation::Horizontal, 6);
+        find_row.append(&entry);
+        find_row.append(&btn_prev);
+        find_row.append(&btn_next);
+        find_row.append(&lbl_matches);
+        find_row.append(&chk_regex);
+        find_row.append(&chk_case);
+
+        let replace_entry = Entry::builder().hexpand(true).placeholder_text(gettext("Replace with")).build();
+        replace_entry.set_tooltip_text(Some(&gettext("With a regular expression, \\0 is the match and \\1 the first group.")));
+        let btn_replace = Button::with_label(&gettext("Replace"));
+        let btn_replace_all = Button::with_label(&gettext("Replace All"));
+        let replace_row = gtk4::Box::new(Orientation::Horizontal, 6);
+        replace_row.append(&replace_entry);
+        replace_row.append(&btn_replace);
+        replace_row.append(&btn_replace_all);
+
+        let rows = gtk4::Box::new(Orientation::Vertical, 6);
+        rows.set_hexpand(true);
+        rows.append(&find_row);
+        rows.append(&replace_row);
+        let bar = SearchBar::builder().child(&rows).show_close_button(true).build();
+        bar.connect_entry(&entry);
+
+        let find = Rc::new(FindBar {
+            bar,
+            entry,
+            chk_regex,
+            chk_case,
+            lbl_matches,
+            replace_row,
+            replace_entry,
+            buffer: buffer.clone(),
+            view: text_view.clone(),
+            status: status.clone(),
+            tag,
+            matches: RefCell::default(),
+            pending: RefCell::default(),
+        });
+
+        let weak = Rc::downgrade(&find);
+        find.entry.connect_search_changed(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.search();
+            }
+        });
+        for chk in [&find.chk_regex, &find.chk_case] {
+            let weak = Rc::downgrade(&find);
+            chk.connect_toggled(move |_| {
+                if let Some(find) = weak.upgrade() {
+                    find.search();
+                }
+            });
+        }
+        let weak = Rc::downgrade(&find);
+        find.entry.connect_activate(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.select_next(true);
+            }
+        });
+        let weak = Rc::downgrade(&find);
+        find.entry.connect_next_match(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.select_next(true);
+            }
+        });
+        let weak = Rc::downgrade(&find);
+        find.entry.connect_previous_match(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.select_next(false);
+            }
+        });
+        let weak = Rc::downgrade(&find);
+        btn_next.connect_clicked(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.select_next(true);
+            }
+        });
+        let weak = Rc::downgrade(&find);
+        btn_prev.connect_clicked(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.select_next(false);
+            }
+        });
+        let weak = Rc::downgrade(&find);
+        btn_replace.connect_clicked(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.replace_current();
+            }
+        });
+        let weak = Rc::downgrade(&find);
+        find.replace_entry.connect_activate(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.replace_current();
+            }
+        });
+        let weak = Rc::downgrade(&find);
+        btn_replace_all.connect_clicked(move |_| {
+            if let Some(find) = weak.upgrade() {
+                find.replace_all();
+            }
+        });
+
+        let weak = Rc::downgrade(&find);
+        find.bar.connect_search_mode_enabled_notify(move |bar| {
+            let Some(find) = weak.upgrade() else {
+                return;
+            };
+            if bar.is_search_mode() {
+                find.search();
+            } else {
+                find.clear();
+                find.view.grab_focus();
+            }
+        });
+
+        // The marks follow edits; only the set of matches needs redoing,
+        // once typing pauses.
+        let weak = Rc::downgrade(&find);
+        buffer.connect_changed(move |_| {
+            if let Some(find) = weak.upgrade().filter(|find| find.bar.is_search_mode()) {
+                find.schedule_search();
+            }
+        });
+
+        find
+    }
+
+    /// Opens the bar, with the replace row if `replace` is set, searching
+    /// for the selection if it is on one line.
+    pub fn show(&self, replace: bool) {
+        self.replace_row.set_visible(replace);
+        if let Some((start, end)) = self.buffer.selection_bounds() {
+            let selected = self.buffer.text(&start, &end, true);
+            if !selected.contains('\n') {
+                self.entry.set_text(&selected);
+            }
+        }
+        self.bar.set_search_mode(true);
+        self.entry.grab_focus();
+        self.entry.select_region(0, -1);
+    }
+
+    fn regex(&self) -> Result<Option<Regex>, glib::Error> {
+        let pattern = self.entry.text();
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        let pattern = if self.chk_regex.is_active() { pattern.to_string() } else { Regex::escape_string(&pattern).to_string() };
+        let mut flags = RegexCompileFlags::MULTILINE | RegexCompileFlags::OPTIMIZE;
+        if !self.chk_case.is_active() {
+            flags |= RegexCompileFlags::CASELESS;
+        }
+        Regex::new(&pattern, flags, RegexMatchFlags::empty())
+    }
+
+    fn matches_now(&self, replacement: Option<&str>) -> Result<Vec<Match>, glib::Error> {
+        let Some(regex) = self.regex()? else {
+            return Ok(Vec::new());
+        };
+        let text = self.buffer.text(&self.buffer.start_iter(), &self.buffer.end_iter(), true);
+        find_all(&regex, &text, replacement)
+    }
+
+    /// The replacement entry's text as `find_all` wants it: expanded per
+    /// match in a regex search, taken literally otherwise.
+    fn replacement(&self) -> Result<(Option<String>, String), glib::Error> {
+        let text = self.replace_entry.text().to_string();
+        if self.chk_regex.is_active() {
+            Regex::check_replacement(&text)?;
+            Ok((Some(text.clone()), text))
+        } else {
+            Ok((None, text))
+        }
+    }
+
+    fn schedule_search(self: &Rc<Self>) {
+        if let Some(source) = self.pending.borrow_mut().take() {
+            source.remove();
+        }
+        let weak = Rc::downgrade(self);
+        *self.pending.borrow_mut() = Some(glib::timeout_add_local_once(Duration::from_millis(250), move || {
+            if let Some(find) = weak.upgrade() {
+                find.pending.borrow_mut().take();
+                find.search();
+            }
+        }));
+    }
+
+    fn clear(&self) {
+        if let Some(source) = self.pending.borrow_mut().take() {
+            source.remove();
+        }
+        self.buffer.remove_tag(&self.tag, &self.buffer.start_iter(), &self.buffer.end_iter());
+        for (start, end) in self.matches.borrow_mut().drain(..) {
+            self.buffer.delete_mark(&start);
+            self.buffer.delete_mark(&end);
+        }
+        self.lbl_matches.set_text("");
+        self.entry.remove_css_class("error");
+        self.entry.set_tooltip_text(None);
+    }
+
+    /// Finds and highlights every match of the current pattern.
+    fn search(&self) {
+        self.clear();
+        let matches = match self.matches_now(None) {
+            Ok(matches) => matches,
+            Err(e) => {
+                self.entry.add_css_class("error");
+                self.entry.set_tooltip_text(Some(e.message()));
+                self.lbl_matches.set_text(&gettext("Invalid pattern"));
+                return;
+            }
+        };
+        let mut marks = self.matches.borrow_mut();
+        for m in &matches {
+            let start = self.buffer.iter_at_offset(m.start);
+            let end = self.buffer.iter_at_offset(m.end);
+            self.buffer.apply_tag(&self.tag, &start, &end);
+            marks.push((self.buffer.create_mark(None, &start, true), self.buffer.create_mark(None, &end, false)));
+        }
+        drop(marks);
+        self.update_count();
+    }
+
+    /// "3 of 12" while a match is selected, "12 matches" otherwise.
+    fn update_count(&self) {
+        let marks = self.matches.borrow();
+        if self.entry.text().is_empty() {
+            self.lbl_matches.set_text("");
+            return;
+        }
+        let text = match self.selected_index() {
+            Some(i) => args(&gettext("{0} of {1}"), &[&format_count(i + 1), &format_count(marks.len())]),
+            None if marks.is_empty() => gettext("No matches"),
+            None => args(&ngettext("{0} match", "{0} matches", marks.len() as u64), &[&format_count(marks.len())]),
+        };
+        self.lbl_matches.set_text(&text);
+    }
+
+    fn bounds(&self, (start, end): &(TextMark, TextMark)) -> (TextIter, TextIter) {
+        (self.buffer.iter_at_mark(start), self.buffer.iter_at_mark(end))
+    }
+
+    /// The match that is exactly the selection, if any.
+    fn selected_index(&self) -> Option<usize> {
+        let (sel_start, sel_end) = self.buffer.selection_bounds()?;
+        let marks = self.matches.borrow();
+        let i = marks.partition_point(|m| self.bounds(m).0.offset() < sel_start.offset());
+        let (start, end) = self.bounds(marks.get(i)?);
+        (start.offset() == sel_start.offset() && end.offset() == sel_end.offset()).then_some(i)
+    }
+
+    /// Selects the next (or previous) match after the cursor, wrapping around.
+    fn select_next(&self, forward: bool) {
+        // The text changed since the last search.
+        let stale = self.pending.borrow_mut().take();
+        if let Some(source) = stale {
+            source.remove();
+            self.search();
+        }
+        let marks = self.matches.borrow();
+        if marks.is_empty() {
+            return;
+        }
+        let (sel_start, sel_end) = self
+            .buffer
+            .selection_bounds()
+            .unwrap_or_else(|| {
+                let cursor = self.buffer.iter_at_mark(&self.buffer.get_insert());
+                (cursor, cursor)
+            });
+        let i = if forward {
+            let i = marks.partition_point(|m| self.bounds(m).0.offset() < sel_end.offset());
+            if i == marks.len() { 0 } else { i }
+        } else {
+            let i = marks.partition_point(|m| self.bounds(m).1.offset() <= sel_start.offset());
+            if i == 0 { marks.len() - 1 } else { i - 1 }
+        };
+        let (start, end) = self.bounds(&marks[i]);
+        drop(marks);
+        self.buffer.select_range(&start, &end);
+        self.view.scroll_to_mark(&self.buffer.get_insert(), 0.1, false, 0.0, 0.0);
+        self.update_count();
+    }
+
+    /// Replaces the selected match and moves on to the next one; selects the
+    /// next match first if none is selected.
+    fn replace_current(&self) {
+        let Some(i) = self.selected_index() else {
+            self.select_next(true);
+            return;
+        };
+        let (start, _) = self.bounds(&self.matches.borrow()[i]);
+        let result = self.replacement().and_then(|(expand, literal)| {
+            let matches = self.matches_now(expand.as_deref())?;
+            Ok(matches.into_iter().find(|m| m.start == start.offset()).map(|m| m.replacement.unwrap_or(literal)))
+        });
+        let replacement = match result {
+            Ok(Some(replacement)) => replacement,
+            Ok(None) => return self.search(),
+            Err(e) => return self.status.error(&args(&gettext("Cannot replace: {0}."), &[&e.message()]), None),
+        };
+        let Some((mut start, mut end)) = self.buffer.selection_bounds() else {
+            return;
+        };
+        self.buffer.begin_user_action();
+        self.buffer.delete(&mut start, &mut end);
+        self.buffer.insert(&mut start, &replacement);
+        self.buffer.end_user_action();
+        self.search();
+        self.select_next(true);
+    }
+
+    /// Replaces every match as one undoable step and reports the count in
+    /// the status line.
+    fn replace_all(&self) {
+        let result = self.replacement().and_then(|(expand, literal)| Ok((self.matches_now(expand.as_deref())?, literal)));
+        let (matches, literal) = match result {
+            Ok(found) => found,
+            Err(e) => return self.status.error(&args(&gettext("Cannot replace: {0}."), &[&e.message()]), None),
+        };
+        // Back to front, so the offsets of the matches still to do hold.
+        self.buffer.begin_user_action();
+        for m in matches.iter().rev() {
+            let mut start = self.buffer.iter_at_offset(m.start);
+            let mut end = self.buffer.iter_at_offset(m.end);
+            self.buffer.delete(&mut start, &mut end);
+            self.buffer.insert(&mut start, m.replacement.as_deref().unwrap_or(&literal));
+        }
+        self.buffer.end_user_action();
+        self.search();
+        let replaced = ngettext("Replaced {0} occurrence.", "Replaced {0} occurrences.", matches.len() as u64);
+        self.status.info(&args(&replaced, &[&format_count(matches.len())]));
+    }
+}
@@ -0,0 +1,327 @@
+//! Snippet shortcuts that work in any application, through the
+//! xdg-desktop-portal GlobalShortcuts interface. The app asks for a key
+//! combination per snippet; the desktop decides what it gets, may ask the
+//! user first, and says which snippet's shortcut was pressed. Unlike the
+//! RemoteDesktop portal, the session lives as long as the app, so the
+//! requests are awaited on the main loop instead of blocking a worker.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use anyhow::{anyhow, bail, Context, Result};
+use gio::glib::{Variant, VariantDict, VariantTy};
+use gio::prelude::*;
+use gio::{DBusCallFlags, DBusConnection, DBusSignalFlags, SignalSubscriptionId};
+
+const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const GLOBAL_SHORTCUTS: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+/// Modifiers a shortcut may hold, by the names it is written with, with the
+/// portal's name for each and how it is shown, in the order they are shown.
+const MODIFIERS: [(&[&str], &str, &str); 4] = [
+    (&["ctrl", "control"], "CTRL", "Ctrl"),
+    (&["alt"], "ALT", "Alt"),
+    (&["shift"], "SHIFT", "Shift"),
+    (&["super", "meta", "logo"], "LOGO", "Super"),
+];
+
+/// Keys with a name rather than a character, by the names they are written
+/// with, with their X keysyms.
+const NAMED_KEYS: [(&str, &str); 8] = [
+    ("space", "space"),
+    ("insert", "Insert"),
+    ("delete", "Delete"),
+    ("home", "Home"),
+    ("end", "End"),
+    ("page_up", "Page_Up"),
+    ("page_down", "Page_Down"),
+    ("pause", "Pause"),
+];
+
+/// A global shortcut such as Super+F1: modifiers held while one key is
+/// pressed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trigger {
+    /// Indexes into [`MODIFIERS`], in its order.
+    modifiers: Vec<usize>,
+    /// The key's X keysym, e.g. `a`, `F1` or `Page_Up`.
+    keysym: String,
+}
+
+impl Trigger {
+    /// Reads a shortcut written as modifiers and a key joined by `+`, e.g.
+    /// `super+f1` or `ctrl+alt+t`: ctrl, alt, shift and super, then a
+    /// letter, a digit, F1–F24 or one of insert, delete, home, end,
+    /// page_up, page_down, pause and space, in any case. Only a function
+    /// key may go without a modifier, since the others would stop typing
+    /// that character anywhere.
+    pub fn parse(text: &str) -> Result<Trigger, String> {
+        let parts: Vec<String> = text.split('+').map(|part| part.trim().to_ascii_lowercase()).collect();
+        let Some((key_name, modifier_names)) = parts.split_last() else {
+            return Err("it is empty".to_string());
+        };
+        let mut modifiers = Vec::new();
+        for name in modifier_names {
+            let Some(index) = MODIFIERS.iter().position(|(names, _, _)| names.contains(&name.as_str())) else {
+                return Err(format!("'{}' is not ctrl, alt, shift or super", name));
+            };
+            if !modifiers.contains(&index) {
+                modifiers.push(index);
+            }
+        }
+        modifiers.sort_unstable();
+        let mut chars = key_name.chars();
+        let function_key = key_name
+            .strip_prefix('f')
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|n| (1..=24).contains(n) && !key_name.starts_with("f0"));
+        let keysym = match (chars.next(), chars.next(), function_key) {
+            (Some(c), None, _) if c.is_ascii_alphanumeric() => c.to_string(),
+            (_, _, Some(n)) => format!("F{}", n),
+            _ => match NAMED_KEYS.iter().find(|(name, _)| name == key_name) {
+                Some((_, keysym)) => keysym.to_string(),
+                None => {
+                    return Err(format!(
+                        "'{}' is not a letter, a digit, F1–F24, insert, delete, home, end, page_up, page_down, \
+                         pause or space",
+                        key_name
+                    ))
+                }
+            },
+        };
+        if modifiers.is_empty() && function_key.is_none() {
+            return Err("only F1–F24 can be used without ctrl, alt, shift or super".to_string());
+        }
+        Ok(Trigger { modifiers, keysym })
+    }
+
+    /// How snippet settings keep it, e.g. `super+f1`; [`Trigger::parse`]
+    /// reads it back.
+    pub fn name(&self) -> String {
+        let mut names: Vec<String> = self.modifiers.iter().map(|&i| MODIFIERS[i].0[0].to_string()).collect();
+        names.push(self.keysym.to_ascii_lowercase());
+        names.join("+")
+    }
+
+    /// As the portal's `preferred_trigger` takes it (the XDG shortcuts
+    /// format), e.g. `LOGO+F1`.
+    pub fn portal_trigger(&self) -> String {
+        let mut names: Vec<&str> = self.modifiers.iter().map(|&i| MODIFIERS[i].1).collect();
+        names.push(&self.keysym);
+        names.join("+")
+    }
+}
+
+impl fmt::Display for Trigger {
+    /// E.g. "Super+F1", for the UI and the log.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<String> = self.modifiers.iter().map(|&i| MODIFIERS[i].2.to_string()).collect();
+        let mut chars = self.keysym.chars();
+        names.push(chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default());
+        f.write_str(&names.join("+"))
+    }
+}
+
+/// A shortcut to ask the desktop for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Binding {
+    /// What the portal calls it back by.
+    pub id: String,
+    /// Shown where the desktop lists the app's shortcuts.
+    pub description: String,
+    pub trigger: Trigger,
+}
+
+/// A shortcut the desktop bound, with the keys it assigned as it describes
+/// them; empty when it assigned none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bound {
+    pub id: String,
+    pub trigger_description: String,
+}
+
+/// Numbers each request's token, so a new session's requests never share
+/// a path with an earlier one's.
+static NEXT_TOKEN: AtomicU32 = AtomicU32::new(1);
+
+/// A GlobalShortcuts session; it is closed when dropped, which unbinds its
+/// shortcuts.
+pub struct ShortcutsSession {
+    conn: DBusConnection,
+    handle: String,
+    activated: Option<SignalSubscriptionId>,
+}
+
+impl ShortcutsSession {
+    /// Whether the session bus has a GlobalShortcuts portal.
+    pub async fn is_available(conn: &DBusConnection) -> bool {
+        conn.call_future(
+            Some(PORTAL_BUS_NAME),
+            PORTAL_PATH,
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            Some(&(GLOBAL_SHORTCUTS, "version").to_variant()),
+            None,
+            DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+        .is_ok()
+    }
+
+    pub async fn create(conn: &DBusConnection) -> Result<ShortcutsSession> {
+        let options = VariantDict::new(None);
+        let session_token = format!("pasteclipboard_shortcuts_{}", NEXT_TOKEN.fetch_add(1, Ordering::Relaxed));
+        options.insert("session_handle_token", session_token.as_str());
+        let results = request(conn, "CreateSession", Vec::new(), options).await?;
+        let handle = results
+            .get("session_handle")
+            .and_then(|v| v.get::<String>().or_else(|| v.str().map(str::to_string)))
+            .ok_or_else(|| anyhow!("The portal did not return a session handle"))?;
+        Ok(ShortcutsSession { conn: conn.clone(), handle, activated: None })
+    }
+
+    /// Asks for `bindings`, replacing what the session had, and returns
+    /// what the desktop made of each. It may ask the user first.
+    pub async fn bind(&self, bindings: &[Binding]) -> Result<Vec<Bound>> {
+        let shortcuts = bindings.iter().map(|binding| {
+            let options = VariantDict::new(None);
+            options.insert("description", binding.description.as_str());
+            options.insert("preferred_trigger", binding.trigger.portal_trigger().as_str());
+            Variant::tuple_from_iter([binding.id.to_variant(), options.end()])
+        });
+        let shortcut_type = VariantTy::new("(sa{sv})").expect("a valid type string");
+        let args = vec![
+            self.session_path()?,
+            Variant::array_from_iter_with_type(shortcut_type, shortcuts),
+            "".to_variant(),
+        ];
+        let results = request(&self.conn, "BindShortcuts", args, VariantDict::new(None)).await?;
+        let bound: Vec<(String, HashMap<String, Variant>)> =
+            results.get("shortcuts").and_then(|v| v.get()).unwrap_or_default();
+        Ok(bound
+            .into_iter()
+            .map(|(id, properties)| Bound {
+                id,
+                trigger_description: properties
+                    .get("trigger_description")
+                    .and_then(|v| v.get::<String>())
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Calls `f` with a shortcut's id each time it is pressed.
+    pub fn connect_activated(&mut self, f: impl Fn(&str) + 'static) {
+        if let Some(old) = self.activated.take() {
+            self.conn.signal_unsubscribe(old);
+        }
+        let handle = self.handle.clone();
+        self.activated = Some(self.conn.signal_subscribe(
+            Some(PORTAL_BUS_NAME),
+            Some(GLOBAL_SHORTCUTS),
+            Some("Activated"),
+            Some(PORTAL_PATH),
+            None,
+            DBusSignalFlags::NONE,
+            move |_, _, _, _, _, params| {
+                let session = params.child_value(0);
+                let session = session.str().unwrap_or_default();
+                if session == handle {
+                    if let Some(id) = params.child_value(1).str() {
+                        f(id);
+                    }
+                }
+            },
+        ));
+    }
+
+    fn session_path(&self) -> Result<Variant> {
+        gio::glib::variant::ObjectPath::try_from(self.handle.clone())
+            .map(|path| path.to_variant())
+            .map_err(|e| anyhow!("Invalid portal session handle: {}", e))
+    }
+}
+
+impl Drop for ShortcutsSession {
+    fn drop(&mut self) {
+        if let Some(activated) = self.activated.take() {
+            self.conn.signal_unsubscribe(activated);
+        }
+        self.conn.call(
+            Some(PORTAL_BUS_NAME),
+            &self.handle,
+            "org.freedesktop.portal.Session",
+            "Close",
+            None,
+            None,
+            DBusCallFlags::NONE,
+            -1,
+            gio::Cancellable::NONE,
+            |_| {},
+        );
+    }
+}
+
+/// Calls a GlobalShortcuts method that answers through a Request object
+/// and waits for its Response. `args` precede the options dictionary.
+async fn request(
+    conn: &DBusConnection,
+    method: &str,
+    mut args: Vec<Variant>,
+    options: VariantDict,
+) -> Result<HashMap<String, Variant>> {
+    let token = format!("pasteclipboard_{}_{}", method.to_ascii_lowercase(), NEXT_TOKEN.fetch_add(1, Ordering::Relaxed));
+    let sender = conn
+        .unique_name()
+        .ok_or_else(|| anyhow!("The session bus connection has no name"))?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let request_path = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+
+    // Subscribed before calling so a fast response cannot be missed.
+    let (response_sender, response) = flume::bounded(1);
+    let subscription = conn.signal_subscribe(
+        Some(PORTAL_BUS_NAME),
+        Some("org.freedesktop.portal.Request"),
+        Some("Response"),
+        Some(&request_path),
+        None,
+        DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            let _ = response_sender.try_send(params.clone());
+        },
+    );
+
+    options.insert("handle_token", token.as_str());
+    args.push(options.end());
+    let call = conn
+        .call_future(
+            Some(PORTAL_BUS_NAME),
+            PORTAL_PATH,
+            GLOBAL_SHORTCUTS,
+            method,
+            Some(&Variant::tuple_from_iter(args)),
+            None,
+            DBusCallFlags::NONE,
+            -1,
+        )
+        .await;
+    if let Err(e) = call {
+        conn.signal_unsubscribe(subscription);
+        return Err(anyhow!(e).context(format!("The portal's {} call failed", method)));
+    }
+    let params = response.recv_async().await;
+    conn.signal_unsubscribe(subscription);
+    let params = params.context("The portal's response went missing")?;
+
+    let (code, results): (u32, HashMap<String, Variant>) =
+        params.get().ok_or_else(|| anyhow!("Unexpected {} response from the portal", method))?;
+    match code {
+        0 => Ok(results),
+        1 => bail!("The portal request was cancelled"),
+        _ => bail!("The portal's {} request failed", method),
+    }
+}
@@ -0,0 +1,129 @@
+//! Snippets encrypted with GnuPG: `<name>.gpg` files in the snippets
+//! folder, as `gpg --encrypt` writes them, so they can be synced with the
+//! rest. `gpg` decrypts one into memory whenever it is loaded or typed,
+//! and nothing here writes the text to disk. The window keeps it off disk
+//! too: typing one is a secret run, so it is not kept in the recent texts
+//! or for resuming, and a tab it was loaded into is left out of the
+//! drafts. Exporting one copies the encrypted file. Saving one encrypts it
+//! again to the keys it was encrypted to, read from the file itself.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use zeroize::Zeroizing;
+
+use crate::command::run_program;
+
+/// How long gpg may take; gpg-agent may first ask for the key's
+/// passphrase.
+pub const GPG_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn gpg(args: &[&str]) -> Vec<String> {
+    ["gpg", "--batch", "--quiet", "--no-tty"].iter().chain(args).map(|arg| arg.to_string()).collect()
+}
+
+/// The file's text, decrypted. Fails with what gpg said, e.g. when there
+/// is no secret key for it or the agent could not ask for the passphrase.
+pub fn decrypt(path: &Path) -> Result<Zeroizing<String>> {
+    let file = path.to_string_lossy();
+    let output = Zeroizing::new(
+        run_program(&gpg(&["--decrypt", "--", &file]), None, GPG_TIMEOUT)
+            .with_context(|| format!("Cannot decrypt {}", path.display()))?,
+    );
+    match std::str::from_utf8(&output) {
+        Ok(text) => Ok(Zeroizing::new(text.to_string())),
+        Err(_) => bail!("{} does not decrypt to UTF-8 text", path.display()),
+    }
+}
+
+/// Replaces the encrypted file at `path` with `text`, encrypted to the same
+/// keys. The new file is written next to it and then renamed over it, so a
+/// failure leaves the old one.
+pub fn encrypt_again(path: &Path, text: &str) -> Result<()> {
+    let data = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let keys = recipients(&data).with_context(|| format!("Cannot tell who {} is encrypted to", path.display()))?;
+    let mut args = vec!["--encrypt"];
+    for key in &keys {
+        args.extend(["--recipient", key]);
+    }
+    let encrypted = run_program(&gpg(&args), Some(text.as_bytes()), GPG_TIMEOUT)
+        .with_context(|| format!("Cannot encrypt {}", path.display()))?;
+    let temporary = path.with_extension("gpg.tmp");
+    fs::write(&temporary, encrypted).with_context(|| format!("Failed to write {}", temporary.display()))?;
+    fs::rename(&temporary, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// The key ids a binary OpenPGP message is encrypted to, from the public
+/// key encrypted session key packets it starts with (RFC 4880 5.1).
+pub fn recipients(data: &[u8]) -> Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut rest = data;
+    while let Some(Packet { tag, body, after }) = packet(rest)? {
+        if tag != 1 {
+            break;
+        }
+        match body {
+            [3, id @ ..] if id.len() >= 8 => {
+                let id: String = id[..8].iter().map(|byte| format!("{:02X}", byte)).collect();
+                if id == "0000000000000000" {
+                    bail!("It is encrypted to a hidden recipient");
+                }
+                keys.push(id);
+            }
+            _ => bail!("It uses a session key packet this version cannot read"),
+        }
+        rest = after;
+    }
+    if keys.is_empty() {
+        bail!("It is not encrypted to a public key (ASCII-armored and symmetric files are not supported)");
+    }
+    Ok(keys)
+}
+
+/// An OpenPGP packet: its tag, its body and what follows it.
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8],
+    after: &'a [u8],
+}
+
+/// The first packet of `data`. Only the tag is read of one with a length
+/// that is not given up front.
+fn packet(data: &[u8]) -> Result<Option<Packet<'_>>> {
+    let Some(&header) = data.first() else {
+        return Ok(None);
+    };
+    if header & 0x80 == 0 {
+        bail!("It is not an OpenPGP message");
+    }
+    let (tag, len, skip) = if header & 0x40 != 0 {
+        // New format: the length takes one, two or five octets.
+        let tag = header & 0x3f;
+        match data.get(1..) {
+            Some([first @ 0..=191, ..]) => (tag, usize::from(*first), 2),
+            Some([first @ 192..=223, second, ..]) => {
+                (tag, ((usize::from(*first) - 192) << 8) + usize::from(*second) + 192, 3)
+            }
+            Some([255, a, b, c, d, ..]) => (tag, u32::from_be_bytes([*a, *b, *c, *d]) as usize, 6),
+            _ => return Ok(Some(Packet { tag, body: &[], after: &[] })),
+        }
+    } else {
+        let tag = (header >> 2) & 0x0f;
+        let octets = match header & 0x03 {
+            0 => 1,
+            1 => 2,
+            2 => 4,
+            _ => return Ok(Some(Packet { tag, body: &[], after: &[] })),
+        };
+        let Some(bytes) = data.get(1..1 + octets) else {
+            bail!("The file is cut short");
+        };
+        (tag, bytes.iter().fold(0usize, |len, byte| len << 8 | usize::from(*byte)), 1 + octets)
+    };
+    match data.get(skip..skip + len) {
+        Some(body) => Ok(Some(Packet { tag, body, after: &data[skip + len..] })),
+        None => bail!("The file is cut short"),
+    }
+}
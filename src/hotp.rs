@@ -0,0 +1,201 @@
+//! Counter-based one-time passwords (RFC 4226) for the `{HOTP:label}`
+//! auto-type placeholder. Each label names a token kept in the keyring
+//! (see `secret_store`): its seed, as for `{TOTP}` a base32 secret or an
+//! `otpauth://hotp/…` link that may also set the digits and algorithm, and
+//! the counter of the next code. The code is worked out from the counter
+//! right before the run; the counter only moves on once the run has typed
+//! to the end, so a cancelled or failed run does not use a code up.
+
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+use zeroize::Zeroizing;
+
+use crate::secret_store;
+use crate::totp::{decode_base32, hotp, Algorithm, DEFAULT_DIGITS};
+
+/// Longest label accepted, in characters.
+pub const MAX_LABEL_CHARS: usize = 64;
+
+/// An HOTP token: the shared key, wiped when dropped, how codes are made
+/// from it, and the counter of the next code.
+#[derive(Clone)]
+pub struct Hotp {
+    key: Zeroizing<Vec<u8>>,
+    pub digits: u32,
+    pub algorithm: Algorithm,
+    pub counter: u64,
+}
+
+impl fmt::Debug for Hotp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hotp")
+            .field("digits", &self.digits)
+            .field("algorithm", &self.algorithm)
+            .field("counter", &self.counter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hotp {
+    pub fn new(key: &[u8], digits: u32, algorithm: Algorithm, counter: u64) -> Self {
+        Hotp { key: Zeroizing::new(key.to_vec()), digits, algorithm, counter }
+    }
+
+    /// Reads a seed: a base32 secret, with the counter at 0, or an
+    /// `otpauth://hotp/` link with `secret` and optionally `counter`,
+    /// `digits` (6–10) and `algorithm`.
+    pub fn parse(seed: &str) -> Result<Self> {
+        let seed = seed.trim();
+        let Some(link) = seed.strip_prefix("otpauth://") else {
+            return Ok(Hotp { key: decode_base32(seed)?, digits: DEFAULT_DIGITS, algorithm: Algorithm::Sha1, counter: 0 });
+        };
+        let Some((kind, query)) = link.split_once('?') else {
+            bail!("The HOTP link has no secret");
+        };
+        if !kind.get(..5).is_some_and(|kind| kind.eq_ignore_ascii_case("hotp/")) {
+            bail!("The link is not an otpauth://hotp/ link");
+        }
+        let (mut key, mut digits, mut algorithm, mut counter) = (None, DEFAULT_DIGITS, Algorithm::Sha1, 0);
+        for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match name.to_ascii_lowercase().as_str() {
+                "secret" => key = Some(decode_base32(value)?),
+                "counter" => match value.parse() {
+                    Ok(n) => counter = n,
+                    _ => bail!("Invalid HOTP counter {} (must be a whole number)", value),
+                },
+                "digits" => match value.parse() {
+                    Ok(n @ 6..=10) => digits = n,
+                    _ => bail!("Invalid HOTP digits {} (must be from 6–10)", value),
+                },
+                "algorithm" => match Algorithm::parse(value) {
+                    Some(a) => algorithm = a,
+                    None => bail!("Unknown HOTP algorithm {} (expected SHA1, SHA256 or SHA512)", value),
+                },
+                _ => {}
+            }
+        }
+        match key {
+            Some(key) => Ok(Hotp { key, digits, algorithm, counter }),
+            None => bail!("The HOTP link has no secret"),
+        }
+    }
+
+    /// The code for `counter`.
+    pub fn code(&self, counter: u64) -> Zeroizing<String> {
+        let code = hotp(&self.key, counter, self.digits, self.algorithm);
+        Zeroizing::new(format!("{:0width$}", code, width = self.digits as usize))
+    }
+}
+
+/// Checks a label as `{HOTP:label}` gives it.
+pub fn validate_label(label: &str) -> Result<()> {
+    if label.is_empty() {
+        bail!("{{HOTP:}} needs a label, like {{HOTP:vpn}}");
+    }
+    if label.chars().count() > MAX_LABEL_CHARS || label.contains(['\n', '\r', '{', '}']) {
+        bail!("Invalid HOTP label '{}' (at most {} characters, without braces or line breaks)", label, MAX_LABEL_CHARS);
+    }
+    Ok(())
+}
+
+fn token_id(label: &str) -> String {
+    format!("hotp:{}", label)
+}
+
+/// The seed and the counter, a line each, as the keyring keeps them, or
+/// `None` when no token is saved. A keyring that cannot be read is an
+/// error: taking it for a missing token would start the counter over and
+/// reuse codes already typed.
+fn stored(label: &str) -> Result<Option<(Zeroizing<String>, u64)>> {
+    let text = secret_store::lookup(&token_id(label))
+        .with_context(|| format!("Cannot read HOTP token '{}' from the keyring", label))?;
+    let Some(text) = text.map(Zeroizing::new) else {
+        return Ok(None);
+    };
+    let Some((seed, counter)) = text.split_once('\n') else {
+        bail!("The keyring's entry for HOTP token '{}' has no counter", label);
+    };
+    let Ok(counter) = counter.trim().parse() else {
+        bail!("The keyring's entry for HOTP token '{}' has an invalid counter", label);
+    };
+    Ok(Some((Zeroizing::new(seed.to_string()), counter)))
+}
+
+fn store(label: &str, seed: &str, counter: u64) -> Result<()> {
+    let text = Zeroizing::new(format!("{}\n{}", seed, counter));
+    secret_store::store(&token_id(label), &format!("PasteClipboard HOTP token '{}'", label), &text)
+        .with_context(|| format!("Cannot store HOTP token '{}' in the keyring", label))
+}
+
+/// The token saved under `label`, with its counter. Reading it may first
+/// ask to unlock the keyring.
+pub fn load_token(label: &str) -> Result<Hotp> {
+    let Some((seed, counter)) = stored(label)? else {
+        bail!("No HOTP token '{}' is saved; add its seed in the snippet's properties", label);
+    };
+    let mut token = Hotp::parse(&seed).with_context(|| format!("HOTP token '{}'", label))?;
+    token.counter = counter;
+    Ok(token)
+}
+
+/// The counter of the next code saved for `label`, if a token is saved.
+pub fn saved_counter(label: &str) -> Option<u64> {
+    stored(label).ok().flatten().map(|(_, counter)| counter)
+}
+
+/// Saves `seed`, when given, and `counter`, when given, for `label`; what
+/// is not given stays as saved. A new token starts at the link's counter,
+/// or 0.
+pub fn save_token(label: &str, seed: Option<&str>, counter: Option<u64>) -> Result<()> {
+    validate_label(label)?;
+    let saved = stored(label)?;
+    let (seed, counter) = match (seed, saved) {
+        (Some(seed), saved) => {
+            let parsed = Hotp::parse(seed)?;
+            let kept = saved.map(|(_, counter)| counter).filter(|_| !seed.trim().starts_with("otpauth://"));
+            (Zeroizing::new(seed.trim().to_string()), counter.or(kept).unwrap_or(parsed.counter))
+        }
+        (None, Some((seed, saved))) => (seed, counter.unwrap_or(saved)),
+        (None, None) if counter.is_some() => bail!("No HOTP token '{}' is saved; enter its seed too", label),
+        (None, None) => return Ok(()),
+    };
+    store(label, &seed, counter)
+}
+
+/// Moves each label's counter on to the one given, after a run that typed
+/// its codes. A counter the keyring already has further on stays.
+pub fn commit_counters(next: &[(String, u64)]) -> Result<()> {
+    for (label, counter) in next {
+        match stored(label)? {
+            Some((seed, saved)) if saved < *counter => store(label, &seed, *counter)?,
+            Some(_) => {}
+            None => bail!("HOTP token '{}' is no longer in the keyring", label),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 4226 Appendix D: the key "12345678901234567890" and counters 0–9.
+    #[test]
+    fn rfc4226_test_vectors() {
+        let token = Hotp::new(b"12345678901234567890", 6, Algorithm::Sha1, 0);
+        let expected =
+            ["755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489"];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(token.code(counter as u64).as_str(), *code, "counter {}", counter);
+        }
+    }
+
+    #[test]
+    fn parses_a_link_with_a_counter() {
+        // The RFC's key in base32.
+        let token = Hotp::parse("otpauth://hotp/x?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=7").unwrap();
+        assert_eq!(token.counter, 7);
+        assert_eq!(token.code(token.counter).as_str(), "162583");
+    }
+}
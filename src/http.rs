@@ -0,0 +1,130 @@
+// Opt-in localhost HTTP endpoint (see pasteclipboard::http_api for the API).
+//
+// The server thread forwards each request to the main loop, which answers it
+// against the window's run state as soon as it arrives, the same way worker
+// events are handled.
+
+use gtk4::{glib, Application};
+use std::io::Read;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+use anyhow::{anyhow, bail, Result};
+use pasteclipboard::http_api::{self, HttpRequest, HttpResponse, RunControl, TypeRequest};
+use pasteclipboard::run_state::RunState;
+use pasteclipboard::settings::{load_delay_setting, load_typing_options, Settings};
+use pasteclipboard::typing::MAX_REMOTE_TEXT_BYTES;
+
+use crate::window::WindowSlot;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The `http_port` from config.toml, if the endpoint is enabled there. An
+/// invalid one is reported by the settings check and leaves it off.
+pub fn configured_port() -> Option<u16> {
+    Settings::load().http_port
+}
+
+struct WindowControl {
+    app: Application,
+    slot: Rc<WindowSlot>,
+}
+
+impl RunControl for WindowControl {
+    fn state(&self) -> RunState {
+        self.slot.peek().map(|ui| ui.run_state.borrow().clone()).unwrap_or_default()
+    }
+
+    fn start(&self, request: TypeRequest) -> Result<(), String> {
+        let mut options = load_typing_options().map_err(|e| format!("{} in config.toml", e))?;
+        if let Some(ms) = request.char_delay_ms {
+            options.char_delay = Duration::from_millis(u64::from(ms));
+        }
+        let delay = match request.delay_seconds {
+            Some(delay) => delay,
+            None => load_delay_setting().map_err(|e| format!("{} in config.toml", e))?,
+        };
+        self.slot.get(&self.app).start_run(request.text.into(), delay, options);
+        Ok(())
+    }
+}
+
+/// Starts the endpoint on 127.0.0.1:`port`; later calls do nothing. Requires
+/// `http_token` in config.toml.
+pub fn start(app: &Application, slot: Rc<WindowSlot>, port: u16) -> Result<()> {
+    if STARTED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let token = Settings::load().http_token;
+    if token.is_empty() {
+        bail!("Set http_token in config.toml before enabling the HTTP endpoint");
+    }
+    let server = tiny_http::Server::http(("127.0.0.1", port))
+        .map_err(|e| anyhow!("Cannot listen on 127.0.0.1:{}: {}", port, e))?;
+    STARTED.store(true, Ordering::SeqCst);
+
+    let (sender, receiver) = flume::unbounded::<(HttpRequest, Sender<HttpResponse>)>();
+    let server_token = token.clone();
+    thread::spawn(move || serve(server, &server_token, sender));
+
+    let control = WindowControl { app: app.clone(), slot };
+    glib::MainContext::default().spawn_local(async move {
+        while let Ok((request, reply)) = receiver.recv_async().await {
+            let _ = reply.send(http_api::handle(&request, &token, &control));
+        }
+    });
+    Ok(())
+}
+
+fn serve(server: tiny_http::Server, token: &str, main_loop: flume::Sender<(HttpRequest, Sender<HttpResponse>)>) {
+    let json_type = tiny_http::Header::from_bytes("Content-Type", "application/json").ok();
+    for mut request in server.incoming_requests() {
+        let authorization = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .map(|h| h.value.as_str().to_string());
+
+        // Without the token nothing of the body is read.
+        let body = match http_api::check_token(authorization.as_deref(), token) {
+            Some(refused) => Err(refused),
+            None => {
+                // Read one byte past the limit so oversized bodies are still rejected.
+                let mut body = Vec::new();
+                let limit = (MAX_REMOTE_TEXT_BYTES + 1025) as u64;
+                request.as_reader().take(limit).read_to_end(&mut body).map(|_| body).map_err(|e| HttpResponse {
+                    status: 400,
+                    body: serde_json::json!({ "error": e.to_string() }),
+                })
+            }
+        };
+
+        let response = match body {
+            Err(response) => response,
+            Ok(body) => {
+                let forwarded = HttpRequest {
+                    method: request.method().as_str().to_ascii_uppercase(),
+                    path: request.url().split('?').next().unwrap_or_default().to_string(),
+                    authorization,
+                    body,
+                };
+                let (reply, answer) = mpsc::channel();
+                if main_loop.send((forwarded, reply)).is_err() {
+                    return;
+                }
+                answer.recv_timeout(Duration::from_secs(5)).unwrap_or_else(|_| HttpResponse {
+                    status: 503,
+                    body: serde_json::json!({ "error": "The application did not answer" }),
+                })
+            }
+        };
+
+        let mut reply = tiny_http::Response::from_string(response.body.to_string()).with_status_code(response.status);
+        if let Some(header) = json_type.clone() {
+            reply.add_header(header);
+        }
+        let _ = request.respond(reply);
+    }
+}
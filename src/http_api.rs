@@ -0,0 +1,221 @@
+//! Request handling for the opt-in localhost HTTP endpoint, kept apart from
+//! the server so it can be driven directly.
+//!
+//!   POST /type    {"text": "...", "delay_seconds": 3, "char_delay_ms": 20}
+//!   GET  /status
+//!
+//! Every request needs `Authorization: Bearer <http_token>`.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::run_state::RunState;
+use crate::typing::{MAX_CHAR_DELAY_MS, MAX_DELAY_SECONDS, MAX_REMOTE_TEXT_BYTES};
+
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl HttpResponse {
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        HttpResponse { status, body: json!({ "error": message.into() }) }
+    }
+}
+
+/// Body of POST /type. Omitted fields fall back to the configured values.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TypeRequest {
+    pub text: String,
+    pub delay_seconds: Option<u64>,
+    pub char_delay_ms: Option<u32>,
+}
+
+/// The part of the app the endpoint drives.
+pub trait RunControl {
+    fn state(&self) -> RunState;
+    /// Starts a run; only called while `state()` is not busy.
+    fn start(&self, request: TypeRequest) -> Result<(), String>;
+}
+
+/// Compares tokens without stopping at the first mismatch, so response
+/// timing does not reveal how much of a guess was right.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// The 401 answer for an `Authorization` header without the bearer
+/// `token`; `None` when the request may go on. The server checks it before
+/// reading the body.
+pub fn check_token(authorization: Option<&str>, token: &str) -> Option<HttpResponse> {
+    let given = authorization.and_then(|h| h.strip_prefix("Bearer "));
+    if token.is_empty() || !given.is_some_and(|given| tokens_match(given.trim(), token)) {
+        return Some(HttpResponse::error(401, "Missing or wrong bearer token"));
+    }
+    None
+}
+
+pub fn handle(request: &HttpRequest, token: &str, control: &dyn RunControl) -> HttpResponse {
+    if let Some(refused) = check_token(request.authorization.as_deref(), token) {
+        return refused;
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => HttpResponse { status: 200, body: json!(control.state()) },
+        ("POST", "/type") => handle_type(&request.body, control),
+        (_, "/status") | (_, "/type") => HttpResponse::error(405, "Method not allowed"),
+        _ => HttpResponse::error(404, "Not found"),
+    }
+}
+
+fn handle_type(body: &[u8], control: &dyn RunControl) -> HttpResponse {
+    if body.len() > MAX_REMOTE_TEXT_BYTES + 1024 {
+        return HttpResponse::error(413, format!("Request is larger than {} bytes", MAX_REMOTE_TEXT_BYTES));
+    }
+    let request: TypeRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return HttpResponse::error(400, format!("Invalid JSON: {}", e)),
+    };
+    if request.text.len() > MAX_REMOTE_TEXT_BYTES {
+        return HttpResponse::error(413, format!("Text is larger than {} bytes", MAX_REMOTE_TEXT_BYTES));
+    }
+    if request.delay_seconds.is_some_and(|d| d > MAX_DELAY_SECONDS) {
+        return HttpResponse::error(400, format!("delay_seconds must be from 0–{}", MAX_DELAY_SECONDS));
+    }
+    if request.char_delay_ms.is_some_and(|d| d > MAX_CHAR_DELAY_MS) {
+        return HttpResponse::error(400, format!("char_delay_ms must be from 0–{}", MAX_CHAR_DELAY_MS));
+    }
+    if control.state().is_busy() {
+        return HttpResponse::error(409, "A typing run is already in progress");
+    }
+    match control.start(request) {
+        Ok(()) => HttpResponse { status: 202, body: json!(control.state()) },
+        Err(message) => HttpResponse::error(500, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use crate::typing::{RunStats, TypingError, TypingEvent};
+
+    const TOKEN: &str = "a token of some length";
+
+    /// The run state as the window keeps it, with the texts it was asked to
+    /// type.
+    #[derive(Default)]
+    struct Control {
+        state: RefCell<RunState>,
+        started: RefCell<Vec<TypeRequest>>,
+    }
+
+    impl RunControl for Control {
+        fn state(&self) -> RunState {
+            self.state.borrow().clone()
+        }
+
+        fn start(&self, request: TypeRequest) -> Result<(), String> {
+            self.state.borrow_mut().start(request.delay_seconds.unwrap_or(3));
+            self.started.borrow_mut().push(request);
+            Ok(())
+        }
+    }
+
+    impl Control {
+        fn apply(&self, event: TypingEvent) {
+            self.state.borrow_mut().apply(&event);
+        }
+    }
+
+    fn request(method: &str, path: &str, body: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            authorization: Some(format!("Bearer {}", TOKEN)),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn status(control: &Control) -> HttpResponse {
+        handle(&request("GET", "/status", ""), TOKEN, control)
+    }
+
+    fn type_text(control: &Control, body: &str) -> HttpResponse {
+        handle(&request("POST", "/type", body), TOKEN, control)
+    }
+
+    #[test]
+    fn every_request_needs_the_token() {
+        let control = Control::default();
+        let mut wrong = request("GET", "/status", "");
+        wrong.authorization = Some("Bearer a token of some lengtH".to_string());
+        assert_eq!(handle(&wrong, TOKEN, &control).status, 401);
+        wrong.authorization = None;
+        assert_eq!(handle(&wrong, TOKEN, &control).status, 401);
+        // No token configured lets nobody in.
+        assert_eq!(handle(&request("GET", "/status", ""), "", &control).status, 401);
+        assert_eq!(check_token(Some("Bearer  a token of some length "), TOKEN), None);
+        assert_eq!(check_token(Some(TOKEN), TOKEN).map(|refused| refused.status), Some(401));
+        assert_eq!(status(&control), HttpResponse { status: 200, body: json!({ "state": "idle" }) });
+    }
+
+    #[test]
+    fn a_run_is_refused_until_the_one_before_is_over() {
+        let control = Control::default();
+        let accepted = type_text(&control, r#"{"text": "hello", "delay_seconds": 2, "char_delay_ms": 5}"#);
+        assert_eq!(accepted, HttpResponse { status: 202, body: json!({ "state": "counting_down", "remaining_seconds": 2 }) });
+        assert_eq!(type_text(&control, r#"{"text": "again"}"#).status, 409);
+
+        control.apply(TypingEvent::Started { total_chars: 5 });
+        control.apply(TypingEvent::Progress { typed: 3, total: 5 });
+        assert_eq!(status(&control).body, json!({ "state": "typing", "typed": 3, "total": 5, "skipped": 0 }));
+        assert_eq!(type_text(&control, r#"{"text": "again"}"#).status, 409);
+
+        control.apply(TypingEvent::Finished { stats: RunStats { typed: 5, ..RunStats::default() } });
+        assert_eq!(status(&control).body, json!({ "state": "finished", "typed": 5, "skipped": 0 }));
+        assert_eq!(type_text(&control, r#"{"text": "again"}"#).status, 202);
+
+        control.apply(TypingEvent::Failed { error: TypingError::Cancelled { at_char: 0 }, stats: RunStats::default() });
+        assert_eq!(status(&control).body["state"], "failed");
+        assert_eq!(type_text(&control, r#"{"text": "once more"}"#).status, 202);
+
+        let started = control.started.borrow();
+        let texts: Vec<&str> = started.iter().map(|request| request.text.as_str()).collect();
+        assert_eq!(texts, ["hello", "again", "once more"]);
+        assert_eq!((started[0].delay_seconds, started[0].char_delay_ms), (Some(2), Some(5)));
+        assert_eq!((started[1].delay_seconds, started[1].char_delay_ms), (None, None));
+    }
+
+    #[test]
+    fn bad_requests_start_nothing() {
+        let control = Control::default();
+        for body in [
+            "not json",
+            r#"{"delay_seconds": 2}"#,
+            r#"{"text": "a", "speed": 2}"#,
+            &format!(r#"{{"text": "a", "delay_seconds": {}}}"#, MAX_DELAY_SECONDS + 1),
+            &format!(r#"{{"text": "a", "char_delay_ms": {}}}"#, MAX_CHAR_DELAY_MS + 1),
+        ] {
+            assert_eq!(type_text(&control, body).status, 400, "{}", body);
+        }
+        let long = json!({ "text": "a".repeat(MAX_REMOTE_TEXT_BYTES + 1) }).to_string();
+        assert_eq!(type_text(&control, &long).status, 413);
+        assert_eq!(handle(&request("GET", "/type", ""), TOKEN, &control).status, 405);
+        assert_eq!(handle(&request("POST", "/status", ""), TOKEN, &control).status, 405);
+        assert_eq!(handle(&request("GET", "/", ""), TOKEN, &control).status, 404);
+        assert!(control.started.borrow().is_empty());
+        assert_eq!(control.state(), RunState::Idle);
+    }
+}
@@ -0,0 +1,101 @@
+//! Translations through the C library's gettext, under the `pasteclipboard`
+//! domain. Messages with values are templates with numbered placeholders
+//! (`{0}`, `{1}`) so translations can reorder them; fill them in with
+//! `args`.
+
+use std::ffi::{c_char, c_int, c_ulong, CStr, CString};
+use std::fmt::Display;
+
+pub const DOMAIN: &str = "pasteclipboard";
+
+/// Where the compiled catalogs live, as `<dir>/<lang>/LC_MESSAGES/pasteclipboard.mo`.
+/// `PASTECLIPBOARD_LOCALEDIR` overrides it, e.g. for running from the source tree.
+const DEFAULT_LOCALE_DIR: &str = match option_env!("PASTECLIPBOARD_LOCALEDIR") {
+    Some(dir) => dir,
+    None => "/usr/share/locale",
+};
+
+/// glibc's value; GTK sets the same one again when it starts.
+const LC_ALL: c_int = 6;
+
+mod ffi {
+    use std::ffi::{c_char, c_int, c_ulong};
+
+    extern "C" {
+        pub fn setlocale(category: c_int, locale: *const c_char) -> *mut c_char;
+        pub fn bindtextdomain(domain: *const c_char, dir: *const c_char) -> *mut c_char;
+        pub fn bind_textdomain_codeset(domain: *const c_char, codeset: *const c_char) -> *mut c_char;
+        pub fn textdomain(domain: *const c_char) -> *mut c_char;
+        pub fn gettext(msgid: *const c_char) -> *mut c_char;
+        pub fn ngettext(msgid: *const c_char, plural: *const c_char, n: c_ulong) -> *mut c_char;
+    }
+}
+
+/// Loads the user's locale and binds the domain. Call it first thing in
+/// `main`, before any message is looked up.
+pub fn init() {
+    let dir = std::env::var("PASTECLIPBOARD_LOCALEDIR").unwrap_or_else(|_| DEFAULT_LOCALE_DIR.to_string());
+    let (Ok(domain), Ok(dir)) = (CString::new(DOMAIN), CString::new(dir)) else {
+        return;
+    };
+    unsafe {
+        ffi::setlocale(LC_ALL, c"".as_ptr());
+        ffi::bindtextdomain(domain.as_ptr(), dir.as_ptr());
+        ffi::bind_textdomain_codeset(domain.as_ptr(), c"UTF-8".as_ptr());
+        ffi::textdomain(domain.as_ptr());
+    }
+}
+
+/// Copies gettext's result, which points into the catalog or at the msgid.
+fn owned(ptr: *const c_char, fallback: &str) -> String {
+    if ptr.is_null() {
+        return fallback.to_string();
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+}
+
+/// The translation of `msgid`, or `msgid` itself.
+pub fn gettext(msgid: &str) -> String {
+    let Ok(c_msgid) = CString::new(msgid) else {
+        return msgid.to_string();
+    };
+    owned(unsafe { ffi::gettext(c_msgid.as_ptr()) }, msgid)
+}
+
+/// The singular or plural form for `n`, by the language's plural rules.
+pub fn ngettext(msgid: &str, plural: &str, n: u64) -> String {
+    let fallback = if n == 1 { msgid } else { plural };
+    let (Ok(c_msgid), Ok(c_plural)) = (CString::new(msgid), CString::new(plural)) else {
+        return fallback.to_string();
+    };
+    // Huge counts only need to land in the plural forms' range.
+    let n = c_ulong::try_from(n).unwrap_or(c_ulong::MAX);
+    owned(unsafe { ffi::ngettext(c_msgid.as_ptr(), c_plural.as_ptr(), n) }, fallback)
+}
+
+/// Replaces `{0}`, `{1}`, … in a translated template with `values`.
+/// Anything else in braces, or an index with no value, stays as it is.
+pub fn args(template: &str, values: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let value = after
+            .find('}')
+            .and_then(|close| Some((close, after[..close].parse::<usize>().ok()?)))
+            .and_then(|(close, index)| Some((close, values.get(index)?)));
+        match value {
+            Some((close, value)) => {
+                out.push_str(&value.to_string());
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
@@ -0,0 +1,97 @@
+// "Keep on top": GTK 4 has no API for it, so on X11 the window manager is
+// asked directly with an EWMH _NET_WM_STATE_ABOVE client message. Wayland
+// gives clients no such request; there it has to be a compositor rule.
+
+use gtk4::prelude::*;
+use std::ffi::{c_char, c_int, c_long, c_ulong, c_void};
+
+const CLIENT_MESSAGE: c_int = 33;
+const SUBSTRUCTURE_NOTIFY_MASK: c_long = 1 << 19;
+const SUBSTRUCTURE_REDIRECT_MASK: c_long = 1 << 20;
+const NET_WM_STATE_REMOVE: c_long = 0;
+const NET_WM_STATE_ADD: c_long = 1;
+/// EWMH source indication for a normal application.
+const SOURCE_APPLICATION: c_long = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct XClientMessageEvent {
+    type_: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut c_void,
+    window: c_ulong,
+    message_type: c_ulong,
+    format: c_int,
+    data: [c_long; 5],
+}
+
+/// Xlib's XEvent union is 24 longs; XSendEvent copies all of it.
+#[repr(C)]
+union XEvent {
+    client: XClientMessageEvent,
+    pad: [c_long; 24],
+}
+
+extern "C" {
+    fn gdk_x11_surface_get_xid(surface: *mut c_void) -> c_ulong;
+    fn gdk_x11_display_get_xdisplay(display: *mut c_void) -> *mut c_void;
+}
+
+#[link(name = "X11")]
+extern "C" {
+    fn XInternAtom(display: *mut c_void, name: *const c_char, only_if_exists: c_int) -> c_ulong;
+    fn XDefaultRootWindow(display: *mut c_void) -> c_ulong;
+    fn XSendEvent(display: *mut c_void, window: c_ulong, propagate: c_int, mask: c_long, event: *mut XEvent) -> c_int;
+    fn XFlush(display: *mut c_void) -> c_int;
+}
+
+/// Whether the window is on an X11 display, where this can work at all.
+pub fn supported(window: &impl IsA<gtk4::Window>) -> bool {
+    WidgetExt::display(window.as_ref()).type_().name() == "GdkX11Display"
+}
+
+/// Asks the window manager to keep `window` above others, or to stop.
+/// Does nothing before the window is mapped or off X11; the WM forgets the
+/// state when the window is unmapped, so callers re-apply it on map.
+pub fn set(window: &impl IsA<gtk4::Window>, above: bool) {
+    if !supported(window) {
+        return;
+    }
+    let Some(surface) = window.as_ref().surface() else {
+        return;
+    };
+    let display = WidgetExt::display(window.as_ref());
+    unsafe {
+        let xdisplay = gdk_x11_display_get_xdisplay(display.as_ptr() as *mut c_void);
+        let xid = gdk_x11_surface_get_xid(surface.as_ptr() as *mut c_void);
+        if xdisplay.is_null() || xid == 0 {
+            return;
+        }
+        let event = XClientMessageEvent {
+            type_: CLIENT_MESSAGE,
+            serial: 0,
+            send_event: 1,
+            display: xdisplay,
+            window: xid,
+            message_type: XInternAtom(xdisplay, c"_NET_WM_STATE".as_ptr(), 0),
+            format: 32,
+            data: [
+                if above { NET_WM_STATE_ADD } else { NET_WM_STATE_REMOVE },
+                XInternAtom(xdisplay, c"_NET_WM_STATE_ABOVE".as_ptr(), 0) as c_long,
+                0,
+                SOURCE_APPLICATION,
+                0,
+            ],
+        };
+        let mut event = XEvent { client: event };
+        XSendEvent(
+            xdisplay,
+            XDefaultRootWindow(xdisplay),
+            0,
+            SUBSTRUCTURE_REDIRECT_MASK | SUBSTRUCTURE_NOTIFY_MASK,
+            &mut event,
+        );
+        XFlush(xdisplay);
+    }
+}
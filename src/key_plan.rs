@@ -0,0 +1,144 @@
+//! The plan of a run: every key to press, in order, with the pause before
+//! it, worked out from the text and the options before the first key so the
+//! run itself only replays it. The time estimates read the same plan, so
+//! they agree with what a run does. Long texts are planned a chunk at a
+//! time rather than all at once.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use evdev_rs::enums::EV_KEY;
+use zeroize::Zeroizing;
+
+use crate::typing::TypingOptions;
+
+/// Most events a chunk of the plan holds.
+pub const PLAN_CHUNK: usize = 4096;
+
+/// One character to type: the key for it, whether Shift is held, and how
+/// long to wait before pressing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlannedEvent {
+    /// Position of the character in the text, in chars.
+    pub at: usize,
+    pub c: char,
+    pub key: EV_KEY,
+    pub shift: bool,
+    /// The character delay, plus the newline delay after a line break;
+    /// nothing before the first key.
+    pub pre_delay: Duration,
+}
+
+/// A text a plan borrows, or owns so that it goes with the plan.
+pub trait PlanText {
+    fn as_text(&self) -> &str;
+}
+
+impl PlanText for &str {
+    fn as_text(&self) -> &str {
+        self
+    }
+}
+
+/// The text of a run the window started, shared with the window.
+impl PlanText for Arc<Zeroizing<String>> {
+    fn as_text(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// The plan for a text, as chunks of at most [`PLAN_CHUNK`] events.
+/// Characters without a key are left out.
+pub struct Plan<'a, T: PlanText = &'a str> {
+    text: T,
+    /// Where the next character starts in the text, in bytes.
+    offset: usize,
+    options: &'a TypingOptions,
+    /// The pause before the next key; `None` before the first one.
+    next_delay: Option<Duration>,
+    /// Position of the next character in the text.
+    position: usize,
+    /// The first of the options' pauses not yet reached.
+    next_pause: usize,
+}
+
+impl<'a, T: PlanText> Plan<'a, T> {
+    pub fn new(text: T, options: &'a TypingOptions) -> Self {
+        Plan { text, offset: 0, options, next_delay: None, position: 0, next_pause: 0 }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.text.as_text()[self.offset..].chars().next()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+
+    /// The extra waits due at or before the next character.
+    fn take_pauses(&mut self) -> Duration {
+        let mut pause = Duration::ZERO;
+        while let Some(&(_, wait)) = self.options.pauses.get(self.next_pause).filter(|(at, _)| *at <= self.position) {
+            pause += wait;
+            self.next_pause += 1;
+        }
+        pause
+    }
+}
+
+impl<T: PlanText> Iterator for Plan<'_, T> {
+    type Item = Vec<PlannedEvent>;
+
+    fn next(&mut self) -> Option<Vec<PlannedEvent>> {
+        let mut chunk = Vec::new();
+        // A pause before a character without a key waits before the next
+        // one that has one.
+        let mut pause = Duration::ZERO;
+        while let Some(c) = self.next_char() {
+            pause += self.take_pauses();
+            let at = self.position;
+            self.position += 1;
+            if !self.options.keymap.is_typeable(c) {
+                continue;
+            }
+            let (key, shift) = if c == '\n' { self.options.enter_key.key() } else { self.options.keymap.get(c) };
+            let pre_delay = self.next_delay.unwrap_or_default() + std::mem::take(&mut pause);
+            let mut next_delay = self.options.char_delay;
+            if c == '\n' {
+                next_delay += self.options.newline_delay;
+            }
+            self.next_delay = Some(next_delay);
+            chunk.push(PlannedEvent { at, c, key, shift, pre_delay });
+            if chunk.len() == PLAN_CHUNK {
+                break;
+            }
+        }
+        (!chunk.is_empty()).then_some(chunk)
+    }
+}
+
+/// Time a run of `text` spends in its pauses; the keys themselves add a
+/// little.
+pub fn plan_duration(text: &str, options: &TypingOptions) -> Duration {
+    Plan::new(text, options).flatten().map(|event| event.pre_delay).sum()
+}
+
+/// Every key a run of `text` presses, Shift included when anything needs
+/// it, read from the same plan the run replays.
+pub fn plan_keys(text: &str, options: &TypingOptions) -> Vec<EV_KEY> {
+    let mut keys = BTreeSet::new();
+    for event in Plan::new(text, options).flatten() {
+        keys.insert(event.key);
+        if event.shift {
+            keys.insert(EV_KEY::KEY_LEFTSHIFT);
+        }
+    }
+    // A TOTP code is typed over the zeros standing in for it, so any digit
+    // may be pressed.
+    if !options.codes.is_empty() {
+        keys.extend(('0'..='9').map(|digit| options.keymap.get(digit).0));
+    }
+    if let Some(readback) = &options.readback {
+        keys.extend(readback.keys());
+    }
+    keys.into_iter().collect()
+}
@@ -0,0 +1,276 @@
+//! ASCII character to evdev key mapping for a US keyboard layout, with
+//! the user's overrides from config.toml's `[keymap]` on top.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use evdev_rs::enums::EV_KEY;
+
+/// The US layout for every ASCII character, indexed by code point and built
+/// at compile time, so a lookup is one bounds check and a load.
+static US_ASCII: [(EV_KEY, bool); 128] = us_ascii_table();
+
+const fn us_ascii_table() -> [(EV_KEY, bool); 128] {
+    let mut table = [(EV_KEY::KEY_RESERVED, false); 128];
+    let mut i = 0;
+    while i < table.len() {
+        table[i] = us_key(i as u8 as char);
+        i += 1;
+    }
+    table
+}
+
+/// Maps an ASCII character to its corresponding evdev::Key and whether Shift is needed.
+pub fn char_to_key_event(c: char) -> (EV_KEY, bool) {
+    US_ASCII.get(c as usize).copied().unwrap_or((EV_KEY::KEY_RESERVED, false))
+}
+
+/// The US layout's key for `c`; `KEY_RESERVED` for characters it has none for.
+const fn us_key(c: char) -> (EV_KEY, bool) {
+    // This exhaustive match is the correct and only reliable way to map chars to keycodes.
+    match c {
+        'a' => (EV_KEY::KEY_A, false), 'b' => (EV_KEY::KEY_B, false), 'c' => (EV_KEY::KEY_C, false),
+        'd' => (EV_KEY::KEY_D, false), 'e' => (EV_KEY::KEY_E, false), 'f' => (EV_KEY::KEY_F, false),
+        'g' => (EV_KEY::KEY_G, false), 'h' => (EV_KEY::KEY_H, false), 'i' => (EV_KEY::KEY_I, false),
+        'j' => (EV_KEY::KEY_J, false), 'k' => (EV_KEY::KEY_K, false), 'l' => (EV_KEY::KEY_L, false),
+        'm' => (EV_KEY::KEY_M, false), 'n' => (EV_KEY::KEY_N, false), 'o' => (EV_KEY::KEY_O, false),
+        'p' => (EV_KEY::KEY_P, false), 'q' => (EV_KEY::KEY_Q, false), 'r' => (EV_KEY::KEY_R, false),
+        's' => (EV_KEY::KEY_S, false), 't' => (EV_KEY::KEY_T, false), 'u' => (EV_KEY::KEY_U, false),
+        'v' => (EV_KEY::KEY_V, false), 'w' => (EV_KEY::KEY_W, false), 'x' => (EV_KEY::KEY_X, false),
+        'y' => (EV_KEY::KEY_Y, false), 'z' => (EV_KEY::KEY_Z, false),
+        'A' => (EV_KEY::KEY_A, true), 'B' => (EV_KEY::KEY_B, true), 'C' => (EV_KEY::KEY_C, true),
+        'D' => (EV_KEY::KEY_D, true), 'E' => (EV_KEY::KEY_E, true), 'F' => (EV_KEY::KEY_F, true),
+        'G' => (EV_KEY::KEY_G, true), 'H' => (EV_KEY::KEY_H, true), 'I' => (EV_KEY::KEY_I, true),
+        'J' => (EV_KEY::KEY_J, true), 'K' => (EV_KEY::KEY_K, true), 'L' => (EV_KEY::KEY_L, true),
+        'M' => (EV_KEY::KEY_M, true), 'N' => (EV_KEY::KEY_N, true), 'O' => (EV_KEY::KEY_O, true),
+        'P' => (EV_KEY::KEY_P, true), 'Q' => (EV_KEY::KEY_Q, true), 'R' => (EV_KEY::KEY_R, true),
+        'S' => (EV_KEY::KEY_S, true), 'T' => (EV_KEY::KEY_T, true), 'U' => (EV_KEY::KEY_U, true),
+        'V' => (EV_KEY::KEY_V, true), 'W' => (EV_KEY::KEY_W, true), 'X' => (EV_KEY::KEY_X, true),
+        'Y' => (EV_KEY::KEY_Y, true), 'Z' => (EV_KEY::KEY_Z, true),
+        '1' => (EV_KEY::KEY_1, false), '2' => (EV_KEY::KEY_2, false), '3' => (EV_KEY::KEY_3, false),
+        '4' => (EV_KEY::KEY_4, false), '5' => (EV_KEY::KEY_5, false), '6' => (EV_KEY::KEY_6, false),
+        '7' => (EV_KEY::KEY_7, false), '8' => (EV_KEY::KEY_8, false), '9' => (EV_KEY::KEY_9, false),
+        '0' => (EV_KEY::KEY_0, false),
+        '!' => (EV_KEY::KEY_1, true), '@' => (EV_KEY::KEY_2, true), '#' => (EV_KEY::KEY_3, true),
+        '$' => (EV_KEY::KEY_4, true), '%' => (EV_KEY::KEY_5, true), '^' => (EV_KEY::KEY_6, true),
+        '&' => (EV_KEY::KEY_7, true), '*' => (EV_KEY::KEY_8, true), '(' => (EV_KEY::KEY_9, true),
+        ')' => (EV_KEY::KEY_0, true),
+        '-' => (EV_KEY::KEY_MINUS, false), '_' => (EV_KEY::KEY_MINUS, true),
+        '=' => (EV_KEY::KEY_EQUAL, false), '+' => (EV_KEY::KEY_EQUAL, true),
+        '[' => (EV_KEY::KEY_LEFTBRACE, false), '{' => (EV_KEY::KEY_LEFTBRACE, true),
+        ']' => (EV_KEY::KEY_RIGHTBRACE, false), '}' => (EV_KEY::KEY_RIGHTBRACE, true),
+        '\\' => (EV_KEY::KEY_BACKSLASH, false), '|' => (EV_KEY::KEY_BACKSLASH, true),
+        ';' => (EV_KEY::KEY_SEMICOLON, false), ':' => (EV_KEY::KEY_SEMICOLON, true),
+        '\'' => (EV_KEY::KEY_APOSTROPHE, false), '"' => (EV_KEY::KEY_APOSTROPHE, true),
+        '`' => (EV_KEY::KEY_GRAVE, false), '~' => (EV_KEY::KEY_GRAVE, true),
+        ',' => (EV_KEY::KEY_COMMA, false), '<' => (EV_KEY::KEY_COMMA, true),
+        '.' => (EV_KEY::KEY_DOT, false), '>' => (EV_KEY::KEY_DOT, true),
+        '/' => (EV_KEY::KEY_SLASH, false), '?' => (EV_KEY::KEY_SLASH, true),
+        ' ' => (EV_KEY::KEY_SPACE, false),
+        '\n' => (EV_KEY::KEY_ENTER, false),
+        '\t' => (EV_KEY::KEY_TAB, false),
+        _ => (EV_KEY::KEY_RESERVED, false),
+    }
+}
+
+/// Whether `c` can be typed at all; everything else is skipped.
+pub fn is_typeable(c: char) -> bool {
+    char_to_key_event(c).0 != EV_KEY::KEY_RESERVED
+}
+
+/// The built-in layout with some characters mapped to other keys, e.g. the
+/// few that differ in another layout. The overlay is checked first.
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    overlay: HashMap<char, (EV_KEY, bool)>,
+}
+
+impl Keymap {
+    /// The overrides in `entries`, config.toml's `[keymap]`, and what is
+    /// wrong with those that are left out. Each maps a character, or its
+    /// code point as `U+00E9`, to a key: `KEY_Y` or a character of the US
+    /// layout, after `shift+` to hold Shift, so `"z" = "y"` and
+    /// `"Z" = "shift+KEY_Y"`.
+    pub fn from_entries(entries: &[(String, String)]) -> (Keymap, Vec<String>) {
+        let mut keymap = Keymap::default();
+        let mut problems = Vec::new();
+        for (name, value) in entries {
+            match (parse_char(name), parse_key(value)) {
+                (Some(c), Ok((key, shift))) => keymap.set(c, key, shift),
+                (None, _) => problems.push(format!("'{}' is not one character or U+ code point", name)),
+                (_, Err(e)) => problems.push(format!("'{}' for '{}' {}", value, name, e)),
+            }
+        }
+        (keymap, problems)
+    }
+
+    /// Types `c` with `key`, and Shift if `shift`.
+    pub fn set(&mut self, c: char, key: EV_KEY, shift: bool) {
+        self.overlay.insert(c, (key, shift));
+    }
+
+    pub fn get(&self, c: char) -> (EV_KEY, bool) {
+        if !self.overlay.is_empty() {
+            if let Some(&mapped) = self.overlay.get(&c) {
+                return mapped;
+            }
+        }
+        char_to_key_event(c)
+    }
+
+    /// [`is_typeable`] with the overrides.
+    pub fn is_typeable(&self, c: char) -> bool {
+        self.get(c).0 != EV_KEY::KEY_RESERVED
+    }
+}
+
+/// A `[keymap]` name: one character, or `U+` and its code point.
+fn parse_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => {
+            let hex = name.strip_prefix("U+").or_else(|| name.strip_prefix("u+"))?;
+            char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+        }
+    }
+}
+
+/// A `[keymap]` value: a key and whether Shift is held.
+fn parse_key(value: &str) -> Result<(EV_KEY, bool), String> {
+    let value = value.trim();
+    let (forced, name) = match value.get(..6) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("shift+") && value.len() > 6 => (true, &value[6..]),
+        _ => (false, value),
+    };
+    let mut chars = name.chars();
+    let (key, shift) = match (chars.next(), chars.next()) {
+        (Some(c), None) => char_to_key_event(c),
+        _ => (EV_KEY::from_str(&name.to_ascii_uppercase()).map_err(|_| "is not a key name such as KEY_Y".to_string())?, false),
+    };
+    if key == EV_KEY::KEY_RESERVED {
+        return Err("has no key on the US layout".to_string());
+    }
+    Ok((key, shift || forced))
+}
+
+/// Every character `char_to_key_event` maps to a real key, capitals aside
+/// (they are their letter's key with Shift).
+pub const SUPPORTED_CHARS: &str =
+    "abcdefghijklmnopqrstuvwxyz1234567890!@#$%^&*()-_=+[{]};:'\",<.>/?`~\\| \n\t";
+
+/// How a character is named in messages about skipped text, e.g.
+/// "U+2014 EM DASH". Only characters that commonly sneak in from word
+/// processors and web pages have a name; the rest get their code point.
+pub fn describe_char(c: char) -> String {
+    let name = match c {
+        '\r' => "CARRIAGE RETURN",
+        '\u{a0}' => "NO-BREAK SPACE",
+        '\u{ad}' => "SOFT HYPHEN",
+        '\u{b0}' => "DEGREE SIGN",
+        '\u{a9}' => "COPYRIGHT SIGN",
+        '\u{ae}' => "REGISTERED SIGN",
+        '\u{d7}' => "MULTIPLICATION SIGN",
+        '\u{2007}' => "FIGURE SPACE",
+        '\u{200b}' => "ZERO WIDTH SPACE",
+        '\u{2010}' => "HYPHEN",
+        '\u{2011}' => "NON-BREAKING HYPHEN",
+        '\u{2012}' => "FIGURE DASH",
+        '\u{2013}' => "EN DASH",
+        '\u{2014}' => "EM DASH",
+        '\u{2015}' => "HORIZONTAL BAR",
+        '\u{2018}' => "LEFT SINGLE QUOTATION MARK",
+        '\u{2019}' => "RIGHT SINGLE QUOTATION MARK",
+        '\u{201a}' => "SINGLE LOW-9 QUOTATION MARK",
+        '\u{201b}' => "SINGLE HIGH-REVERSED-9 QUOTATION MARK",
+        '\u{201c}' => "LEFT DOUBLE QUOTATION MARK",
+        '\u{201d}' => "RIGHT DOUBLE QUOTATION MARK",
+        '\u{201e}' => "DOUBLE LOW-9 QUOTATION MARK",
+        '\u{201f}' => "DOUBLE HIGH-REVERSED-9 QUOTATION MARK",
+        '\u{2022}' => "BULLET",
+        '\u{2026}' => "HORIZONTAL ELLIPSIS",
+        '\u{202f}' => "NARROW NO-BREAK SPACE",
+        '\u{2032}' => "PRIME",
+        '\u{2033}' => "DOUBLE PRIME",
+        '\u{20ac}' => "EURO SIGN",
+        '\u{2122}' => "TRADE MARK SIGN",
+        '\u{2212}' => "MINUS SIGN",
+        '\u{feff}' => "ZERO WIDTH NO-BREAK SPACE",
+        _ => return format!("U+{:04X}", c as u32),
+    };
+    format!("U+{:04X} {}", c as u32, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_table_has_the_us_layout() {
+        let expected = [
+            ('a', EV_KEY::KEY_A, false),
+            ('Z', EV_KEY::KEY_Z, true),
+            ('0', EV_KEY::KEY_0, false),
+            ('!', EV_KEY::KEY_1, true),
+            ('@', EV_KEY::KEY_2, true),
+            (')', EV_KEY::KEY_0, true),
+            ('_', EV_KEY::KEY_MINUS, true),
+            ('+', EV_KEY::KEY_EQUAL, true),
+            ('{', EV_KEY::KEY_LEFTBRACE, true),
+            ('|', EV_KEY::KEY_BACKSLASH, true),
+            (':', EV_KEY::KEY_SEMICOLON, true),
+            ('"', EV_KEY::KEY_APOSTROPHE, true),
+            ('~', EV_KEY::KEY_GRAVE, true),
+            ('<', EV_KEY::KEY_COMMA, true),
+            ('?', EV_KEY::KEY_SLASH, true),
+            ('/', EV_KEY::KEY_SLASH, false),
+            (' ', EV_KEY::KEY_SPACE, false),
+            ('\n', EV_KEY::KEY_ENTER, false),
+            ('\t', EV_KEY::KEY_TAB, false),
+        ];
+        for (c, key, shift) in expected {
+            assert_eq!(char_to_key_event(c), (key, shift), "{:?}", c);
+        }
+        for c in ['\0', '\r', '\u{1b}', '\u{7f}'] {
+            assert_eq!(char_to_key_event(c), (EV_KEY::KEY_RESERVED, false), "{:?}", c);
+        }
+        let typeable: String = (0..128u8).map(char::from).filter(|c| is_typeable(*c)).collect();
+        assert_eq!(typeable.len(), 26 + SUPPORTED_CHARS.len());
+        assert!(typeable.chars().all(|c| SUPPORTED_CHARS.contains(c.to_ascii_lowercase())), "{:?}", typeable);
+    }
+
+    #[test]
+    fn an_override_beats_the_layout() {
+        let entries = [("z", "y"), ("Z", "shift+KEY_Y"), ("U+00E9", "KEY_102ND"), ("\n", "KEY_KPENTER")];
+        let entries = entries.map(|(name, value)| (name.to_string(), value.to_string()));
+        let (keymap, problems) = Keymap::from_entries(&entries);
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(keymap.get('z'), (EV_KEY::KEY_Y, false));
+        assert_eq!(keymap.get('Z'), (EV_KEY::KEY_Y, true));
+        assert_eq!(keymap.get('\u{e9}'), (EV_KEY::KEY_102ND, false));
+        assert_eq!(keymap.get('\n'), (EV_KEY::KEY_KPENTER, false));
+        assert!(keymap.is_typeable('\u{e9}') && !is_typeable('\u{e9}'));
+        // Everything else is the layout's.
+        assert_eq!(keymap.get('y'), (EV_KEY::KEY_Y, false));
+        assert_eq!(keymap.get('?'), (EV_KEY::KEY_SLASH, true));
+        assert!(!keymap.is_typeable('\u{2014}'));
+    }
+
+    #[test]
+    fn bad_overrides_are_left_out() {
+        let entries = [("zz", "y"), ("U+D800", "y"), ("a", "KEY_NOPE"), ("b", "\u{e9}"), ("c", "shift+"), ("d", "b")];
+        let entries = entries.map(|(name, value)| (name.to_string(), value.to_string()));
+        let (keymap, problems) = Keymap::from_entries(&entries);
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+        assert_eq!(keymap.get('a'), (EV_KEY::KEY_A, false));
+        assert_eq!(keymap.get('c'), (EV_KEY::KEY_C, false));
+        assert_eq!(keymap.get('d'), (EV_KEY::KEY_B, false));
+    }
+
+    #[test]
+    fn other_characters_have_no_key() {
+        for c in ['\u{80}', '\u{a0}', '\u{e9}', '\u{2014}', '\u{1f600}'] {
+            assert_eq!(char_to_key_event(c), (EV_KEY::KEY_RESERVED, false), "{:?}", c);
+        }
+    }
+}
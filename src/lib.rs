@@ -0,0 +1,51 @@
+//! PasteClipboard core: everything that does not need GTK, shared by the
+//! window and the command line.
+
+pub mod activity_log;
+pub mod assistive;
+pub mod auto_type;
+pub mod backend;
+pub mod benchmark;
+pub mod command;
+pub mod config_archive;
+pub mod config_toml;
+pub mod crash_report;
+pub mod diagnostics;
+pub mod drop_folder;
+pub mod expand;
+pub mod global_shortcuts;
+pub mod gpg;
+pub mod hotp;
+pub mod http_api;
+pub mod i18n;
+pub mod key_plan;
+pub mod keymap;
+pub mod line_filter;
+pub mod logging;
+pub mod mqtt_config;
+pub mod native_messaging;
+pub mod pass;
+pub mod profiles;
+pub mod readback;
+pub mod recent_texts;
+pub mod remote;
+pub mod resume;
+pub mod run_state;
+pub mod secret_scan;
+pub mod secret_store;
+pub mod self_test;
+pub mod session;
+pub mod session_lock;
+pub mod settings;
+pub mod smart_punctuation;
+pub mod snippets;
+pub mod stats;
+pub mod stream;
+pub mod template;
+pub mod text_file;
+pub mod text_stats;
+pub mod totp;
+pub mod transform;
+pub mod typing;
+pub mod uinput_access;
+pub mod url_source;
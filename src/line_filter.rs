@@ -0,0 +1,56 @@
+//! Line filters for typing code into a console: leaving out blank lines,
+//! which would run the previous command again, and comment lines, which
+//! only take time. They apply to the text as typed, after `${VARIABLE}`
+//! expansion, so the preview and the counts show their effect.
+
+/// The comment prefixes when config.toml names none.
+pub const DEFAULT_COMMENT_PREFIXES: &str = "#, //";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineFilter {
+    /// Leave out empty and whitespace-only lines.
+    pub skip_blank_lines: bool,
+    /// Leave out lines that start with one of these after any indentation;
+    /// empty to keep every line.
+    pub comment_prefixes: Vec<String>,
+}
+
+/// Splits a `comment_prefixes` value such as "#, //, --" into its prefixes.
+pub fn parse_prefixes(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|prefix| !prefix.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl LineFilter {
+    pub fn is_active(&self) -> bool {
+        self.skip_blank_lines || !self.comment_prefixes.is_empty()
+    }
+
+    fn keeps(&self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        if self.skip_blank_lines && trimmed.trim_end().is_empty() {
+            return false;
+        }
+        !self.comment_prefixes.iter().any(|prefix| trimmed.starts_with(prefix.as_str()))
+    }
+
+    /// `text` without the filtered lines. A final line break stays, so the
+    /// last command is still entered.
+    pub fn apply(&self, text: &str) -> String {
+        if !self.is_active() {
+            return text.to_string();
+        }
+        let (body, ends_with_newline) = match text.strip_suffix('\n') {
+            Some(body) => (body, true),
+            None => (text, false),
+        };
+        let mut out: Vec<&str> = body.split('\n').filter(|line| self.keeps(line)).collect();
+        if ends_with_newline && !out.is_empty() {
+            out.push("");
+        }
+        out.join("\n")
+    }
+}
@@ -0,0 +1,82 @@
+// The collapsible "Activity log" under the status line: every run's start,
+// progress, skipped characters and outcome, with the full error chain, so a
+// failure is still there after the status line has moved on. It shows the
+// info messages of the process log (see `pasteclipboard::logging`), so what
+// the library logs from the typing worker shows up here too.
+
+use gtk4::prelude::*;
+use gtk4::{
+    glib::{self, timeout_add_local, ControlFlow},
+    Button, Expander, Orientation, ScrolledWindow, TextBuffer, TextView,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{self, TryRecvError};
+use std::time::Duration;
+use pasteclipboard::activity_log::ActivityLog;
+use pasteclipboard::logging;
+
+pub struct LogPane {
+    pub expander: Expander,
+}
+
+impl LogPane {
+    pub fn build() -> LogPane {
+        let view = TextView::builder()
+            .editable(false)
+            .cursor_visible(false)
+            .monospace(true)
+            .wrap_mode(gtk4::WrapMode::WordChar)
+            .build();
+        let scrolled = ScrolledWindow::builder().min_content_height(120).child(&view).build();
+        let btn_copy = Button::with_label("Copy log");
+        btn_copy.set_halign(gtk4::Align::End);
+        btn_copy.set_tooltip_text(Some("Copy the log to the clipboard, e.g. for a bug report."));
+        let content = gtk4::Box::new(Orientation::Vertical, 6);
+        content.append(&scrolled);
+        content.append(&btn_copy);
+        let expander = Expander::builder().label("Activity log").child(&content).build();
+
+        let buffer = view.buffer();
+        let copy_buffer = buffer.clone();
+        btn_copy.connect_clicked(move |btn| {
+            let text = copy_buffer.text(&copy_buffer.start_iter(), &copy_buffer.end_iter(), false);
+            btn.clipboard().set_text(&text);
+        });
+
+        let (sender, receiver) = mpsc::channel();
+        logging::subscribe(sender);
+        let log: Rc<RefCell<ActivityLog>> = Rc::default();
+        timeout_add_local(Duration::from_millis(100), move || loop {
+            match receiver.try_recv() {
+                Ok(message) => append(&buffer, &view, &log, &message),
+                Err(TryRecvError::Empty) => return ControlFlow::Continue,
+                Err(TryRecvError::Disconnected) => return ControlFlow::Break,
+            }
+        });
+
+        LogPane { expander }
+    }
+
+    /// Logs an entry at info level, which brings it here. Callers never pass
+    /// typed text itself, only what is safe to keep on screen and paste into
+    /// a bug report.
+    pub fn add(&self, message: &str) {
+        log::info!("{}", message);
+    }
+}
+
+/// Adds a timestamped entry to the pane.
+fn append(buffer: &TextBuffer, view: &TextView, log: &RefCell<ActivityLog>, message: &str) {
+    let time = glib::DateTime::now_local()
+        .and_then(|now| now.format("%H:%M:%S"))
+        .map(|t| t.to_string())
+        .unwrap_or_default();
+    let dropped = log.borrow_mut().push(&time, message);
+    if dropped {
+        buffer.set_text(&log.borrow().to_text());
+    } else {
+        buffer.insert(&mut buffer.end_iter(), &format!("{}  {}\n", time, message));
+    }
+    view.scroll_to_iter(&mut buffer.end_iter(), 0.0, false, 0.0, 0.0);
+}
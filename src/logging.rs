@@ -0,0 +1,205 @@
+//! Diagnostic logging through `tracing`: to stderr, optionally to
+//! pasteclipboard.log in the state directory, and to subscribers in the
+//! process, such as the window's activity log, so every message has one
+//! source. Messages of the `log` facade are passed on to it. Runs and the
+//! making of keyboards are spans whose fields (counts, backends and delays,
+//! never the typed text) go with each message inside them.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+
+use anyhow::{bail, Result};
+use gio::glib;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_log::AsLog;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+use crate::settings::state_path;
+
+/// Sets the log level like `--log-level`, e.g. `debug` or `debug,file`.
+pub const LOG_ENV: &str = "PASTECLIPBOARD_LOG";
+
+/// The log file in the state directory, with `,file`.
+pub const LOG_FILE: &str = "pasteclipboard.log";
+
+/// Size at which the log file is renamed to pasteclipboard.log.1, replacing
+/// the one before.
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Where messages go and from which level on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LogOptions {
+    pub level: LevelFilter,
+    pub to_file: bool,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions { level: LevelFilter::WARN, to_file: false }
+    }
+}
+
+impl FromStr for LogOptions {
+    type Err = anyhow::Error;
+
+    /// A level (off, error, warn, info, debug, trace), optionally followed
+    /// by `,file`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut options = LogOptions::default();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            if part.eq_ignore_ascii_case("file") {
+                options.to_file = true;
+            } else {
+                match part.to_ascii_lowercase().as_str() {
+                    "off" => options.level = LevelFilter::OFF,
+                    "error" => options.level = LevelFilter::ERROR,
+                    "warn" => options.level = LevelFilter::WARN,
+                    "info" => options.level = LevelFilter::INFO,
+                    "debug" => options.level = LevelFilter::DEBUG,
+                    "trace" => options.level = LevelFilter::TRACE,
+                    _ => bail!("Unknown log level '{}' (expected off, error, warn, info, debug or trace)", part),
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The log file, if `,file` opened one.
+static FILE: Mutex<Option<(PathBuf, File)>> = Mutex::new(None);
+
+/// Receive the message of every event at info level and above, whatever
+/// the options say.
+static SUBSCRIBERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+
+fn open_log_file() -> Option<(PathBuf, File)> {
+    let path = state_path(LOG_FILE)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok()?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(&path).ok()?;
+    Some((path, file))
+}
+
+/// Appends to the log file, starting a new one when it grows too large.
+struct LogFile;
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Ok(mut file) = FILE.lock() else {
+            return Ok(buf.len());
+        };
+        let Some((path, handle)) = file.as_mut() else {
+            return Ok(buf.len());
+        };
+        handle.write_all(buf)?;
+        if handle.metadata().is_ok_and(|meta| meta.len() > MAX_LOG_BYTES) {
+            let mut rotated = path.clone().into_os_string();
+            rotated.push(".1");
+            let _ = fs::rename(&*path, rotated);
+            *file = open_log_file();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match FILE.lock().as_mut().map(|file| file.as_mut()) {
+            Ok(Some((_, handle))) => handle.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Local time, as the activity log shows it.
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        let time = glib::DateTime::now_local().and_then(|now| now.format("%Y-%m-%d %H:%M:%S"));
+        write!(w, "{}", time.map(|t| t.to_string()).unwrap_or_default())
+    }
+}
+
+/// The message of an event, without its other fields.
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_string();
+        }
+    }
+}
+
+/// Passes each event's message on to [`SUBSCRIBERS`].
+struct Subscribers;
+
+impl<S: Subscriber> Layer<S> for Subscribers {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if *event.metadata().level() > Level::INFO {
+            return;
+        }
+        let mut message = Message::default();
+        event.record(&mut message);
+        if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+            subscribers.retain(|subscriber| subscriber.send(message.0.clone()).is_ok());
+        }
+    }
+}
+
+/// Starts logging as `options` say, once per process.
+pub fn init(options: LogOptions) {
+    if options.to_file {
+        if let Ok(mut file) = FILE.lock() {
+            *file = open_log_file();
+        }
+    }
+    let stderr = tracing_subscriber::fmt::layer().with_timer(LocalTime).with_writer(io::stderr).with_filter(options.level);
+    let file = options
+        .to_file
+        .then(|| tracing_subscriber::fmt::layer().with_timer(LocalTime).with_writer(|| LogFile).with_filter(options.level));
+    let subscribers = Subscribers.with_filter(LevelFilter::INFO);
+    if tracing_subscriber::registry().with(stderr).with(file).with(subscribers).try_init().is_ok() {
+        // Subscribers get info messages even when stderr does not.
+        let _ = tracing_log::LogTracer::init_with_filter(options.level.max(LevelFilter::INFO).as_log());
+    }
+}
+
+/// Sends the message of every info, warning and error event to `sender`
+/// from now on, from whichever thread logs it.
+pub fn subscribe(sender: Sender<String>) {
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.push(sender);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_read_a_level_and_file() {
+        let options: LogOptions = "Debug, file".parse().unwrap();
+        assert_eq!(options, LogOptions { level: LevelFilter::DEBUG, to_file: true });
+        assert_eq!("".parse::<LogOptions>().unwrap(), LogOptions::default());
+        assert!("loud".parse::<LogOptions>().is_err());
+    }
+}
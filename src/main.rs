@@ -1,298 +1,991 @@
 // PasteClipboard – Rust/GTK port
 // GUI: GTK4
 // Typing: evdev-rs crate for Wayland-compatible uinput (ASCII ONLY)
-// Settings: ~/.config/PasteClipboard/config.ini (compatible path)
+// Settings: ~/.config/PasteClipboard/config.toml (copied once from the compatible config.ini)
+
+mod appearance;
+mod assistive_mode;
+mod auto_type_dialog;
+mod benchmark_dialog;
+mod command_dialog;
+mod config_transfer;
+mod config_watcher;
+mod countdown_overlay;
+mod crash_dialog;
+mod dbus;
+mod desktop_entry;
+mod diagnostics_dialog;
+mod editor_style;
+mod file_actions;
+mod file_drop;
+mod find_replace;
+mod http;
+mod keep_above;
+mod log_pane;
+mod mqtt;
+mod native_host;
+mod permission_dialog;
+mod placeholder_dialog;
+mod preferences;
+mod profile_switcher;
+mod readback_check;
+mod readiness;
+mod recent_menu;
+mod resume_offer;
+mod search_provider;
+mod secret_dialog;
+mod secret_scan_dialog;
+mod service;
+mod shortcuts;
+mod skipped_marks;
+mod snippet_shortcuts;
+mod snippets_panel;
+mod sounds;
+mod status_area;
+mod tabs;
+mod test_target;
+mod tray;
+mod url_dialog;
+mod watcher;
+mod window;
+mod window_picker;
 
 use gtk4::prelude::*;
 use gtk4::{
-    glib::{self, source::timeout_add_local_once, ControlFlow, timeout_add_local},
-    Application, ApplicationWindow, Button, Entry, Label, Orientation, ScrolledWindow, TextView,
+    glib::{self, translate::ToGlibPtr},
+    gio::{self, ApplicationCommandLine, ApplicationFlags},
+    Application,
 };
-use std::sync::mpsc;
-use std::path::PathBuf;
-use configparser::ini::Ini;
-use directories::BaseDirs;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::cell::RefCell;
-use std::thread;
-use std::time::Duration;
-use anyhow::{Context, Result};
-use evdev_rs::{
-    enums::{EventCode, EV_KEY, EV_SYN},
-    InputEvent, TimeVal, UInputDevice, UninitDevice, DeviceWrapper
+use pasteclipboard::backend::{first_available, probe_all, EventRecorder, Keyboard};
+use pasteclipboard::benchmark::{run_benchmark, step_text, summary_text, BenchmarkEvent};
+use pasteclipboard::diagnostics;
+use pasteclipboard::i18n::{self, args, gettext, ngettext};
+use pasteclipboard::logging::{self, LogOptions, LOG_ENV};
+use pasteclipboard::profiles::set_active_profile;
+use pasteclipboard::remote::{self, load_token_file, server_tls};
+use pasteclipboard::settings::{
+    clear_overrides, config_dir, config_path, countdown_seconds, detect_portable_base, load_appearance_setting, load_backend_setting,
+    load_delay_setting, load_typing_options, load_url_token, migrate_config, migrate_state_files, parse_delay_lenient, parse_overrides, set_overrides, set_portable_base, state_dir,
+    Settings,
 };
-use std::io::ErrorKind;
-
-const APP_ID: &str = "com.example.PasteClipboard";
-const APP_NAME: &str = "PasteClipboard";
+use pasteclipboard::template::parse_vars;
+use pasteclipboard::text_file::normalize;
+use pasteclipboard::typing::{simulate_typing_with, TypingError, TypingEvent, MAX_DELAY_SECONDS};
+use pasteclipboard::uinput_access;
+use pasteclipboard::url_source::{fetch_text, remember_url};
+
+use window::{failure_text, format_count, progress_text, stats_text, MainWindow, RunListener, WindowSlot};
+
+pub const APP_ID: &str = "com.example.PasteClipboard";
+pub const APP_NAME: &str = "PasteClipboard";
+
+/// Prints to the stdout of the invoking terminal, which may belong to another process.
+fn cmdline_print(cmdline: &ApplicationCommandLine, message: &str) {
+    let message: glib::translate::Stash<*const std::ffi::c_char, str> = message.to_glib_none();
+    // g_application_command_line_print_literal needs GLib 2.80; pass the
+    // message through a "%s" format instead so it is never interpreted.
+    unsafe {
+        gio::ffi::g_application_command_line_print(
+            cmdline.to_glib_none().0,
+            c"%s".as_ptr(),
+            message.0,
+        );
+    }
+}
 
-fn config_path() -> Option<PathBuf> {
-    BaseDirs::new().map(|base| base.config_dir().join("PasteClipboard").join("config.ini"))
+/// Like `cmdline_print`, but for the invoking terminal's stderr.
+fn cmdline_printerr(cmdline: &ApplicationCommandLine, message: &str) {
+    let message: glib::translate::Stash<*const std::ffi::c_char, str> = message.to_glib_none();
+    unsafe {
+        gio::ffi::g_application_command_line_printerr(
+            cmdline.to_glib_none().0,
+            c"%s".as_ptr(),
+            message.0,
+        );
+    }
 }
 
-fn save_settings(delay: &str) {
-    if let Some(path) = config_path() {
-        let mut conf = Ini::new();
-        conf.set("settings", "delay_seconds", Some(delay.to_string()));
+/// Reports a run started from the command line back to the invoking
+/// terminal: as progress text, or with `json` as a single object once the
+/// run ends. Holding `cmdline` keeps a remote invocation waiting until the
+/// run ends, so its exit status reflects the outcome. A `stream` run does
+/// not know its length when it starts.
+fn command_line_listener(cmdline: &ApplicationCommandLine, json: bool, stream: bool) -> RunListener {
+    let cmdline = cmdline.clone();
+    Box::new(move |event| match event {
+        TypingEvent::Finished { stats } | TypingEvent::Failed { stats, .. } if json => {
+            let mut report = stats.to_json();
+            report["success"] = matches!(event, TypingEvent::Finished { .. }).into();
+            if let TypingEvent::Failed { error, .. } = event {
+                report["error"] = format!("{:#}", error).into();
+                cmdline.set_exit_status(1);
+            }
+            cmdline_print(&cmdline, &format!("{}\n", report));
+        }
+        _ if json => {}
+        TypingEvent::Started { .. } if stream => {
+            cmdline_print(&cmdline, &format!("{}\n", gettext("Typing standard input as it comes in...")));
+        }
+        TypingEvent::Started { total_chars } => {
+            let text = ngettext("Typing {0} character...", "Typing {0} characters...", *total_chars as u64);
+            cmdline_print(&cmdline, &format!("{}\n", args(&text, &[&format_count(*total_chars)])));
+        }
+        TypingEvent::Progress { typed, total } => {
+            cmdline_print(&cmdline, &format!("\r{}", progress_text(*typed, *total)));
+        }
+        TypingEvent::Skipped { count } => {
+            let text = ngettext(
+                "{0} unsupported character will be skipped.",
+                "{0} unsupported characters will be skipped.",
+                *count as u64,
+            );
+            cmdline_print(&cmdline, &format!("{}\n", args(&text, &[&format_count(*count)])));
+        }
+        TypingEvent::Notice { message } => {
+            cmdline_printerr(&cmdline, &format!("{}\n", message));
+        }
+        TypingEvent::AwaitingConfirmation { wait } => {
+            cmdline_printerr(&cmdline, &format!("\n{}.\n", wait));
+        }
+        TypingEvent::Finished { stats } => {
+            cmdline_print(&cmdline, &format!("\n{}\n", args(&gettext("Done typing: {0}."), &[&stats_text(stats)])));
+        }
+        TypingEvent::Failed { error, stats } => {
+            let text = args(&gettext("Typing failed after {0}: {1}"), &[&stats_text(stats), &failure_text(error)]);
+            cmdline_printerr(&cmdline, &format!("\n{}\n", text));
+            cmdline.set_exit_status(1);
+        }
+    })
+}
 
-        if let Some(parent) = path.parent() {
-            let _ = std::fs::create_dir_all(parent);
+/// Handles `--profile`, `--delay`, `--text`, `--now`, `--stream`, `--set`, `--background`,
+/// `--http-port`, `--mqtt-broker` and `--self-test-gui`, whether they were given to this process or forwarded from a second
+/// invocation to the running instance.
+fn handle_command_line(app: &Application, cmdline: &ApplicationCommandLine, slot: &Rc<WindowSlot>) -> i32 {
+    let options = cmdline.options_dict();
+
+    // Checked like config.toml's values, but only a --now run uses them.
+    let overrides = options.lookup::<Vec<String>>("set").ok().flatten().unwrap_or_default();
+    let overrides = match parse_overrides(&overrides) {
+        Ok(overrides) => overrides,
+        Err(errors) => {
+            for error in errors {
+                cmdline_printerr(cmdline, &format!("{}.\n", error));
+            }
+            return 2;
+        }
+    };
+    let vars = options.lookup::<Vec<String>>("var").ok().flatten().unwrap_or_default();
+    let vars = match parse_vars(&vars) {
+        Ok(vars) => vars,
+        Err(e) => {
+            cmdline_printerr(cmdline, &format!("{}.\n", e));
+            return 2;
+        }
+    };
+
+    if let Some(name) = options.lookup::<String>("profile").ok().flatten() {
+        // An empty name switches to config.toml's own settings.
+        let name = Some(name.trim()).filter(|name| !name.is_empty());
+        if let Err(e) = set_active_profile(name) {
+            cmdline_printerr(cmdline, &format!("{}.\n", e));
+            return 1;
+        }
+        if let Some(ui) = slot.peek() {
+            ui.settings_changed();
         }
-        let _ = conf.write(path);
     }
-}
 
-fn load_settings() -> String {
-    let mut delay = "3".to_string();
+    if let Some(port) = options.lookup::<i32>("http-port").ok().flatten() {
+        let Some(port) = u16::try_from(port).ok().filter(|p| *p > 0) else {
+            cmdline_printerr(cmdline, &format!("{}\n", gettext("Invalid HTTP port (must be a number from 1–65535).")));
+            return 1;
+        };
+        if let Err(e) = http::start(app, slot.clone(), port) {
+            cmdline_printerr(cmdline, &format!("{}.\n", e));
+            return 1;
+        }
+    }
+    if let Some(broker) = options.lookup::<String>("mqtt-broker").ok().flatten() {
+        if let Err(e) = mqtt::start(app, slot.clone(), &broker) {
+            cmdline_printerr(cmdline, &format!("{:#}.\n", e));
+            return 1;
+        }
+    }
 
-    if let Some(path) = config_path() {
-        let mut conf = Ini::new();
-        if conf.load(path).is_ok() {
-            if let Some(d) = conf.get("settings", "delay_seconds") {
-                delay = d;
+    if options.contains("background") {
+        // Stay resident without opening a window; `activate` (launching the
+        // app again, or D-Bus activation) or the Show method presents it. The
+        // GNOME Shell search provider is only exported then.
+        slot.keep_running(app);
+        if let Some(conn) = app.dbus_connection() {
+            if let Err(e) = search_provider::register(app, &conn, slot.clone()) {
+                cmdline_printerr(cmdline, &format!("{}\n", args(&gettext("Failed to export the search provider: {0}"), &[&e])));
             }
         }
+        return 0;
     }
-    delay
-}
 
-/// Maps an ASCII character to its corresponding evdev::Key and whether Shift is needed.
-fn char_to_key_event(c: char) -> (EV_KEY, bool) {
-    // This exhaustive match is the correct and only reliable way to map chars to keycodes.
-    match c {
-        'a' => (EV_KEY::KEY_A, false), 'b' => (EV_KEY::KEY_B, false), 'c' => (EV_KEY::KEY_C, false),
-        'd' => (EV_KEY::KEY_D, false), 'e' => (EV_KEY::KEY_E, false), 'f' => (EV_KEY::KEY_F, false),
-        'g' => (EV_KEY::KEY_G, false), 'h' => (EV_KEY::KEY_H, false), 'i' => (EV_KEY::KEY_I, false),
-        'j' => (EV_KEY::KEY_J, false), 'k' => (EV_KEY::KEY_K, false), 'l' => (EV_KEY::KEY_L, false),
-        'm' => (EV_KEY::KEY_M, false), 'n' => (EV_KEY::KEY_N, false), 'o' => (EV_KEY::KEY_O, false),
-        'p' => (EV_KEY::KEY_P, false), 'q' => (EV_KEY::KEY_Q, false), 'r' => (EV_KEY::KEY_R, false),
-        's' => (EV_KEY::KEY_S, false), 't' => (EV_KEY::KEY_T, false), 'u' => (EV_KEY::KEY_U, false),
-        'v' => (EV_KEY::KEY_V, false), 'w' => (EV_KEY::KEY_W, false), 'x' => (EV_KEY::KEY_X, false),
-        'y' => (EV_KEY::KEY_Y, false), 'z' => (EV_KEY::KEY_Z, false),
-        'A' => (EV_KEY::KEY_A, true), 'B' => (EV_KEY::KEY_B, true), 'C' => (EV_KEY::KEY_C, true),
-        'D' => (EV_KEY::KEY_D, true), 'E' => (EV_KEY::KEY_E, true), 'F' => (EV_KEY::KEY_F, true),
-        'G' => (EV_KEY::KEY_G, true), 'H' => (EV_KEY::KEY_H, true), 'I' => (EV_KEY::KEY_I, true),
-        'J' => (EV_KEY::KEY_J, true), 'K' => (EV_KEY::KEY_K, true), 'L' => (EV_KEY::KEY_L, true),
-        'M' => (EV_KEY::KEY_M, true), 'N' => (EV_KEY::KEY_N, true), 'O' => (EV_KEY::KEY_O, true),
-        'P' => (EV_KEY::KEY_P, true), 'Q' => (EV_KEY::KEY_Q, true), 'R' => (EV_KEY::KEY_R, true),
-        'S' => (EV_KEY::KEY_S, true), 'T' => (EV_KEY::KEY_T, true), 'U' => (EV_KEY::KEY_U, true),
-        'V' => (EV_KEY::KEY_V, true), 'W' => (EV_KEY::KEY_W, true), 'X' => (EV_KEY::KEY_X, true),
-        'Y' => (EV_KEY::KEY_Y, true), 'Z' => (EV_KEY::KEY_Z, true),
-        '1' => (EV_KEY::KEY_1, false), '2' => (EV_KEY::KEY_2, false), '3' => (EV_KEY::KEY_3, false),
-        '4' => (EV_KEY::KEY_4, false), '5' => (EV_KEY::KEY_5, false), '6' => (EV_KEY::KEY_6, false),
-        '7' => (EV_KEY::KEY_7, false), '8' => (EV_KEY::KEY_8, false), '9' => (EV_KEY::KEY_9, false),
-        '0' => (EV_KEY::KEY_0, false),
-        '!' => (EV_KEY::KEY_1, true), '@' => (EV_KEY::KEY_2, true), '#' => (EV_KEY::KEY_3, true),
-        '$' => (EV_KEY::KEY_4, true), '%' => (EV_KEY::KEY_5, true), '^' => (EV_KEY::KEY_6, true),
-        '&' => (EV_KEY::KEY_7, true), '*' => (EV_KEY::KEY_8, true), '(' => (EV_KEY::KEY_9, true),
-        ')' => (EV_KEY::KEY_0, true),
-        '-' => (EV_KEY::KEY_MINUS, false), '_' => (EV_KEY::KEY_MINUS, true),
-        '=' => (EV_KEY::KEY_EQUAL, false), '+' => (EV_KEY::KEY_EQUAL, true),
-        '[' => (EV_KEY::KEY_LEFTBRACE, false), '{' => (EV_KEY::KEY_LEFTBRACE, true),
-        ']' => (EV_KEY::KEY_RIGHTBRACE, false), '}' => (EV_KEY::KEY_RIGHTBRACE, true),
-        '\\' => (EV_KEY::KEY_BACKSLASH, false), '|' => (EV_KEY::KEY_BACKSLASH, true),
-        ';' => (EV_KEY::KEY_SEMICOLON, false), ':' => (EV_KEY::KEY_SEMICOLON, true),
-        '\'' => (EV_KEY::KEY_APOSTROPHE, false), '"' => (EV_KEY::KEY_APOSTROPHE, true),
-        '`' => (EV_KEY::KEY_GRAVE, false), '~' => (EV_KEY::KEY_GRAVE, true),
-        ',' => (EV_KEY::KEY_COMMA, false), '<' => (EV_KEY::KEY_COMMA, true),
-        '.' => (EV_KEY::KEY_DOT, false), '>' => (EV_KEY::KEY_DOT, true),
-        '/' => (EV_KEY::KEY_SLASH, false), '?' => (EV_KEY::KEY_SLASH, true),
-        ' ' => (EV_KEY::KEY_SPACE, false),
-        '\n' => (EV_KEY::KEY_ENTER, false),
-        '\t' => (EV_KEY::KEY_TAB, false),
-        _ => (EV_KEY::KEY_RESERVED, false),
+    let delay = options.lookup::<i32>("delay").ok().flatten();
+    let text = options.lookup::<String>("text").ok().flatten();
+    let url = options.lookup::<String>("url").ok().flatten();
+    // A stream starts at once, as --now does.
+    let stream = options.contains("stream");
+    let now = options.contains("now") || stream;
+
+    if let Some(d) = delay {
+        if u64::try_from(d).map_or(true, |d| d > MAX_DELAY_SECONDS) {
+            let invalid = args(&gettext("Invalid delay (must be a number from 0–{0})."), &[&MAX_DELAY_SECONDS]);
+            cmdline_printerr(cmdline, &format!("{}\n", invalid));
+            return 1;
+        }
     }
-}
 
+    let stdin = if stream { cmdline.stdin() } else { None };
+    if stream && (text.is_some() || url.is_some()) {
+        cmdline_printerr(cmdline, &format!("{}\n", gettext("--stream types standard input; use it without --text or --url.")));
+        return 1;
+    }
+    if stream && stdin.is_none() {
+        cmdline_printerr(cmdline, &format!("{}\n", gettext("There is no standard input to read for --stream.")));
+        return 1;
+    }
 
-/// Simulates typing the given text using the evdev-rs crate and uinput.
-fn simulate_typing_with_uinput(text: &str) -> Result<()> {
-    // Explicitly filter for ASCII characters
-    let ascii_text: String = text.chars().filter(|c| c.is_ascii()).collect();
+    let request = WindowRequest {
+        text,
+        delay,
+        now,
+        stdin,
+        overrides,
+        vars,
+        json: options.contains("json"),
+        self_test: options.contains("self-test-gui"),
+    };
+    let Some(url) = url else {
+        return open_window(app, cmdline, slot, request);
+    };
+    if request.text.is_some() {
+        cmdline_printerr(cmdline, &format!("{}\n", gettext("Use either --text or --url, not both.")));
+        return 1;
+    }
+    // Fetched on a worker, so a slow server holds up this invocation and
+    // not the main loop; the window only changes once the text is there,
+    // so a failure leaves it as it was.
+    let (sender, receiver) = flume::bounded(1);
+    let token = load_url_token();
+    let fetched_url = url.clone();
+    std::thread::spawn(move || {
+        let _ = sender.send(fetch_text(&fetched_url, token.as_deref()));
+    });
+    let (app, cmdline, slot) = (app.clone(), cmdline.clone(), slot.clone());
+    // Without a window yet, the app would otherwise quit while it waits.
+    let hold = app.hold();
+    glib::MainContext::default().spawn_local(async move {
+        let result = receiver
+            .recv_async()
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("The download stopped unexpectedly")));
+        let status = match result {
+            Ok(fetched) => {
+                remember_url(&url);
+                open_window(&app, &cmdline, &slot, WindowRequest { text: Some(normalize(&fetched)), ..request })
+            }
+            Err(e) => {
+                let failed = args(&gettext("Cannot load {0}: {1}."), &[&url.trim(), &format!("{:#}", e)]);
+                cmdline_printerr(&cmdline, &format!("{}\n", failed));
+                1
+            }
+        };
+        // A run started from here sets the status itself once it ends.
+        if status != 0 {
+            cmdline.set_exit_status(status);
+        }
+        drop(hold);
+    });
+    0
+}
 
-    let dev = UninitDevice::new().context("Failed to create uninit evdev device")?;
-    dev.set_name("PasteClipboard-Virtual-Keyboard");
+/// What an invocation asks of the window once its text is known.
+struct WindowRequest {
+    text: Option<String>,
+    delay: Option<i32>,
+    now: bool,
+    /// What a `--stream` run types.
+    stdin: Option<gio::InputStream>,
+    overrides: Vec<(String, String)>,
+    vars: HashMap<String, String>,
+    json: bool,
+    self_test: bool,
+}
 
-    // Define the set of ASCII keys we support
-    let supported_keys = "abcdefghijklmnopqrstuvwxyz1234567890!@#$%^&*()-_=+[{]};:'\",<.>/?`~\\| \n\t";
-    for char_code in supported_keys.chars() {
-        let (key, _) = char_to_key_event(char_code);
-        if key != EV_KEY::KEY_RESERVED {
-            dev.enable(EventCode::EV_KEY(key)).with_context(|| format!("Failed to enable key {:?}", key))?;
-        }
+/// The rest of [`handle_command_line`]: loads the text and delay into the
+/// window and presents it, then starts a `--now` run or the self-test.
+fn open_window(app: &Application, cmdline: &ApplicationCommandLine, slot: &Rc<WindowSlot>, request: WindowRequest) -> i32 {
+    let WindowRequest { text, delay, now, stdin, overrides, vars, json, self_test } = request;
+    let stream = stdin.is_some();
+    let ui = slot.get(app);
+    if let Some(text) = text {
+        ui.replace_text(&text);
+    }
+    if let Some(d) = delay {
+        ui.entry_delay.set_value(f64::from(d));
+    }
+    ui.window.present();
+    if self_test {
+        // The invocation waits for the outcome, as it does for a --now run.
+        let cmdline = cmdline.clone();
+        test_target::show(
+            &ui,
+            Some(Box::new(move |passed, report| {
+                cmdline_print(&cmdline, &format!("{}\n", report));
+                if !passed {
+                    cmdline.set_exit_status(1);
+                }
+            })),
+        );
+        return 0;
+    }
+    if !now {
+        diagnostics_dialog::show_on_first_run(&ui);
     }
-    dev.enable(EventCode::EV_KEY(EV_KEY::KEY_LEFTSHIFT)).context("Failed to enable Shift key")?;
 
-    let device = UInputDevice::create_from_device(&dev).map_err(|err| {
-        let context_msg = match err.kind() {
-            ErrorKind::NotFound => "Failed to create UInput device. Is the 'uinput' kernel module loaded?",
-            ErrorKind::PermissionDenied => "Failed to create UInput device. Do you have permissions for /dev/uinput?",
-            _ => "Failed to create UInput device.",
-        };
-        anyhow::Error::new(err).context(context_msg)
-    })?;
+    if !now && !overrides.is_empty() {
+        // E.g. a desktop file's Exec line: the window opens as usual.
+        cmdline_printerr(cmdline, &format!("{}\n", gettext("--set only applies to a --now run; ignoring it.")));
+    }
+    if !now && !vars.is_empty() {
+        cmdline_printerr(cmdline, &format!("{}\n", gettext("--var only applies to a --now run; ignoring it.")));
+    }
 
-    thread::sleep(Duration::from_millis(200));
+    if now {
+        if ui.is_busy() {
+            cmdline_printerr(cmdline, &format!("{}\n", gettext("A typing run is already in progress.")));
+            return 1;
+        }
+        // The countdown is the window's, so an overridden delay goes there
+        // and back afterwards; --delay wins over it.
+        let mut restore_delay = None;
+        let delay_override = overrides.iter().find(|(key, _)| key == "delay_seconds");
+        if let (None, Some(d)) = (delay, delay_override.and_then(|(_, d)| parse_delay_lenient(d))) {
+            restore_delay = Some((ui.entry_delay.clone(), ui.entry_delay.value()));
+            ui.entry_delay.set_value(countdown_seconds(d) as f64);
+        }
+        if !overrides.is_empty() {
+            let list: Vec<String> = overrides.iter().map(|(key, value)| format!("{} = {}", key, value)).collect();
+            ui.log.add(&args(&gettext("Settings for this run only: {0}"), &[&list.join(", ")]));
+        }
+        set_overrides(overrides);
+        *ui.template_vars.borrow_mut() = vars;
+        ui.run_listeners.borrow_mut().push(command_line_listener(cmdline, json, stream));
+        ui.run_listeners.borrow_mut().push(Box::new(move |event| {
+            if matches!(event, TypingEvent::Finished { .. } | TypingEvent::Failed { .. }) {
+                clear_overrides();
+                if let Some((entry, value)) = &restore_delay {
+                    entry.set_value(*value);
+                }
+            }
+        }));
+        match stdin {
+            Some(stdin) => {
+                let (lines, receiver) = flume::unbounded();
+                if ui.start_stream(receiver) {
+                    forward_lines(&ui, stdin, lines);
+                }
+            }
+            None => ui.btn_start.emit_clicked(),
+        }
+        if !ui.is_busy() {
+            // The click was rejected (e.g. an invalid saved delay); the
+            // window's status label says why.
+            ui.run_listeners.borrow_mut().clear();
+            ui.template_vars.take();
+            clear_overrides();
+            cmdline_printerr(cmdline, &format!("{}\n", gettext("Could not start typing; see the window for details.")));
+            return 1;
+        }
+    }
+    0
+}
 
-    let time = TimeVal::new(0, 0);
+/// Reads `stdin` a line at a time on the main loop and sends each line to
+/// the stream run, until it ends or the run does.
+fn forward_lines(ui: &Rc<MainWindow>, stdin: gio::InputStream, lines: flume::Sender<String>) {
+    let input = gio::DataInputStream::new(&stdin);
+    let weak = Rc::downgrade(ui);
+    glib::MainContext::default().spawn_local(async move {
+        loop {
+            match input.read_line_utf8_future(glib::Priority::DEFAULT).await {
+                Ok(Some(line)) => {
+                    if lines.send(line.trim_end_matches('\r').to_string()).is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => return,
+                Err(e) => {
+                    if let Some(ui) = weak.upgrade() {
+                        ui.log.add(&format!("Reading standard input failed: {}; the stream ends here.", e));
+                    }
+                    return;
+                }
+            }
+        }
+    });
+}
 
-    for c in ascii_text.chars() {
-        let (key, needs_shift) = char_to_key_event(c);
-        if key == EV_KEY::KEY_RESERVED {
-            continue;
+/// `--log-level`, or else `PASTECLIPBOARD_LOG`, read before GTK parses the
+/// options so that startup is logged too.
+fn log_options(args: &[String]) -> anyhow::Result<LogOptions> {
+    let mut value = None;
+    for (i, arg) in args.iter().enumerate() {
+        if let Some(level) = arg.strip_prefix("--log-level=") {
+            value = Some(level.to_string());
+        } else if arg == "--log-level" {
+            value = args.get(i + 1).cloned();
         }
+    }
+    match value.or_else(|| std::env::var(LOG_ENV).ok()) {
+        Some(value) => value.parse(),
+        None => Ok(LogOptions::default()),
+    }
+}
 
-        if needs_shift {
-            device.write_event(&InputEvent::new(&time, &EventCode::EV_KEY(EV_KEY::KEY_LEFTSHIFT), 1))?;
-            device.write_event(&InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0))?;
+/// Keeps everything in `base`, the executable's folder: the settings and
+/// state through the settings paths, and what GTK and GLib would put in
+/// ~/.local/share and ~/.cache in data/ and cache/. GSettings, such as the
+/// file chooser's, are kept in memory rather than in ~/.config/dconf.
+fn use_portable_base(base: &Path) {
+    set_portable_base(Some(base.to_path_buf()));
+    for dir in [config_dir(), state_dir()].into_iter().flatten() {
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Failed to create {}: {}", dir.display(), e);
         }
+    }
+    // Before GTK starts, while this is the only thread.
+    std::env::set_var("XDG_DATA_HOME", base.join("data"));
+    std::env::set_var("XDG_CACHE_HOME", base.join("cache"));
+    std::env::set_var("GSETTINGS_BACKEND", "memory");
+}
 
-        device.write_event(&InputEvent::new(&time, &EventCode::EV_KEY(key), 1))?;
-        device.write_event(&InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0))?;
-        
-        device.write_event(&InputEvent::new(&time, &EventCode::EV_KEY(key), 0))?;
-        device.write_event(&InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0))?;
+/// Prints every backend's availability for `--list-backends`, using the same
+/// probes automatic selection does. Runs in the invoking process, without a
+/// window.
+fn list_backends(json: bool) -> i32 {
+    let statuses = probe_all();
+    let selected = match load_backend_setting() {
+        Ok(Some(backend)) => Some((backend.name(), "configured")),
+        Ok(None) => first_available(&statuses).map(|b| (b.name(), "automatic")),
+        Err(e) => {
+            eprintln!("{} in config.toml.", e);
+            return 1;
+        }
+    };
+
+    if json {
+        let backends: Vec<_> = statuses
+            .iter()
+            .map(|(backend, status)| {
+                serde_json::json!({ "name": backend, "available": status.available, "detail": status.detail })
+            })
+            .collect();
+        let report = serde_json::json!({
+            "backends": backends,
+            "selected": selected.map(|(name, _)| name),
+        });
+        println!("{}", report);
+    } else {
+        for (backend, status) in &statuses {
+            println!("{}: {}", backend, status);
+        }
+        match selected {
+            Some((name, how)) => println!("Selected: {} ({})", name, how),
+            None => println!("Selected: none"),
+        }
+    }
+    0
+}
 
-        if needs_shift {
-            device.write_event(&InputEvent::new(&time, &EventCode::EV_KEY(EV_KEY::KEY_LEFTSHIFT), 0))?;
-            device.write_event(&InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0))?;
+/// Runs the benchmark for `--benchmark` after the countdown (`delay`, or
+/// the configured one), printing each step as it ends. Runs in the invoking
+/// process, without a window.
+fn benchmark(delay: Option<i32>) -> i32 {
+    let delay = match delay {
+        Some(d) => match u64::try_from(d).ok().filter(|d| *d <= MAX_DELAY_SECONDS) {
+            Some(d) => d,
+            None => {
+                eprintln!("Invalid delay (must be a number from 0–{}).", MAX_DELAY_SECONDS);
+                return 1;
+            }
+        },
+        None => load_delay_setting().unwrap_or(3),
+    };
+    let options = match load_typing_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{} in config.toml.", e);
+            return 1;
+        }
+    };
+    println!(
+        "Focus a window where typing is harmless, such as an empty text editor; the benchmark starts in {} s.",
+        delay
+    );
+    std::thread::sleep(std::time::Duration::from_secs(delay));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let cancel = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| run_benchmark(&options, &cancel, &sender));
+        let mut steps = Vec::new();
+        for event in receiver.iter() {
+            match event {
+                BenchmarkEvent::Starting { .. } => {}
+                BenchmarkEvent::Step(step) => {
+                    println!("{}", step_text(&step));
+                    steps.push(step);
+                }
+                BenchmarkEvent::Notice { message } => eprintln!("{}", message),
+                BenchmarkEvent::Finished => {
+                    println!("{}", summary_text(&steps));
+                    return 0;
+                }
+                BenchmarkEvent::Failed { error } => {
+                    eprintln!("The benchmark stopped: {}", failure_text(&error));
+                    return 1;
+                }
+            }
         }
+        1
+    })
+}
 
-        thread::sleep(Duration::from_millis(20));
+/// For `--dry-run`: runs `text` as a run would, delays included, on a
+/// keyboard that only records its uinput events, and writes them to `path`
+/// as an evemu trace. No window is focused or read back, so nothing is
+/// typed anywhere.
+fn dry_run(path: &Path, text: &str) -> i32 {
+    let mut options = match load_typing_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{} in config.toml.", e);
+            return 1;
+        }
+    };
+    options.focus_window = None;
+    options.target_window = None;
+    options.readback = None;
+    options.watch_lock = false;
+    let recorder = EventRecorder::new(options.event_timestamps);
+    let mut connect = |_: &dyn Fn(String)| -> Result<Box<dyn Keyboard>, TypingError> { Ok(Box::new(recorder.clone())) };
+    let (sender, receiver) = flume::unbounded();
+    let cancel = std::sync::atomic::AtomicBool::new(false);
+    simulate_typing_with(text, &options, std::time::Instant::now(), &cancel, &sender, &mut connect);
+    drop(sender);
+    for event in receiver.iter() {
+        match event {
+            TypingEvent::Notice { message } => eprintln!("{}", message),
+            TypingEvent::Failed { error, .. } => {
+                eprintln!("The dry run stopped: {}", failure_text(&error));
+                return 1;
+            }
+            _ => {}
+        }
     }
+    match std::fs::write(path, recorder.trace()) {
+        Ok(()) => {
+            println!("Wrote {} events to {}.", recorder.events().len(), path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("Cannot write {}: {}.", path.display(), e);
+            1
+        }
+    }
+}
 
-    Ok(())
+/// Types what other machines send for `--serve`, until interrupted. Runs in
+/// the invoking process, without a window, so it also works on a machine
+/// with no desktop session.
+fn serve(address: &str, token: Option<PathBuf>, cert: Option<PathBuf>, key: Option<PathBuf>) -> i32 {
+    let Some(token_path) = token else {
+        eprintln!("--serve needs --token FILE, a file holding the token clients must know.");
+        return 2;
+    };
+    let token = match load_token_file(&token_path) {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("{:#}.", e);
+            return 1;
+        }
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if std::fs::metadata(&token_path).is_ok_and(|meta| meta.permissions().mode() & 0o077 != 0) {
+            eprintln!("Warning: other users can read {}; chmod 600 it.", token_path.display());
+        }
+    }
+    let tls = match (cert, key) {
+        (Some(cert), Some(key)) => match server_tls(&cert, &key) {
+            Ok(tls) => Some(tls),
+            Err(e) => {
+                eprintln!("{:#}.", e);
+                return 1;
+            }
+        },
+        (None, None) => None,
+        _ => {
+            eprintln!("Give both --tls-cert and --tls-key, or neither.");
+            return 2;
+        }
+    };
+    match remote::serve(address, &token, tls, |message| eprintln!("{}", message)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{:#}.", e);
+            1
+        }
+    }
 }
 
+/// Prints the problems in config.toml that the window's startup banner
+/// lists, for `--check-config`. Fails when there are any.
+fn check_config() -> i32 {
+    let Some(path) = config_path() else {
+        eprintln!("{}", gettext("No home directory, so there is no config.toml."));
+        return 1;
+    };
+    if !path.exists() {
+        println!("{}", args(&gettext("{0} does not exist; the defaults are used."), &[&path.display()]));
+        return 0;
+    }
+    let problems = Settings::load_with_warnings().1;
+    if problems.is_empty() {
+        println!("{}", args(&gettext("{0}: no problems."), &[&path.display()]));
+        return 0;
+    }
+    println!("{}:", path.display());
+    for problem in &problems {
+        println!("  {}", problem);
+    }
+    1
+}
 
-fn build_ui(app: &Application) {
-    let window = ApplicationWindow::builder()
-        .application(app)
-        .title(APP_NAME)
-        .default_width(560)
-        .default_height(420)
-        .build();
+/// `--install-udev-rule`, which pkexec or sudo runs as root.
+fn install_udev_rule() -> i32 {
+    match uinput_access::install_rule() {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{:#}.", e);
+            1
+        }
+    }
+}
 
-    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
-    vbox.set_margin_start(12);
-    vbox.set_margin_end(12);
-    vbox.set_margin_top(12);
-    vbox.set_margin_bottom(12);
+fn main() -> glib::ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    // Root's process does only this: the rest of the startup would create
+    // and migrate settings and state in root's home folder.
+    if args.iter().skip(1).any(|arg| arg == "--install-udev-rule") {
+        return glib::ExitCode::from(install_udev_rule());
+    }
+    i18n::init();
+    crash_dialog::install();
+    if let Some(base) = detect_portable_base(args.iter().any(|arg| arg == "--portable")) {
+        use_portable_base(&base);
+    }
+    match log_options(&args) {
+        Ok(options) => logging::init(options),
+        Err(e) => {
+            eprintln!("{}.", e);
+            return glib::ExitCode::from(2);
+        }
+    }
+    for path in migrate_state_files() {
+        log::info!("Moved {} to the state directory.", path.display());
+    }
+    if let Some(path) = migrate_config() {
+        log::info!("Copied the settings from config.ini to {}; config.ini is kept as it was.", path.display());
+    }
 
-    let lbl_text = Label::new(Some("Input text (typed after delay):"));
-    lbl_text.set_xalign(0.0);
-    vbox.append(&lbl_text);
+    // Subcommands run on their own, before GTK is involved.
+    match args.get(1).map(String::as_str) {
+        Some("install-service") => return glib::ExitCode::from(service::run(&args[2..])),
+        Some("install-desktop-entry") => return glib::ExitCode::from(desktop_entry::run(&args[2..])),
+        Some("install-native-host") => return glib::ExitCode::from(native_host::run_install(&args[2..])),
+        // Browsers append their own arguments (origin, manifest path) after it.
+        Some("--native-messaging") => return glib::ExitCode::from(native_host::serve()),
+        _ => {}
+    }
 
-    let scrolled = ScrolledWindow::builder()
-        .hexpand(true)
-        .vexpand(true)
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(ApplicationFlags::HANDLES_COMMAND_LINE)
         .build();
-    let text_view = TextView::new();
-    text_view.set_wrap_mode(gtk4::WrapMode::WordChar);
-    scrolled.set_child(Some(&text_view));
-    vbox.append(&scrolled);
-    let buffer = text_view.buffer();
-
-    let row = gtk4::Box::new(Orientation::Horizontal, 6);
-    let lbl_delay = Label::new(Some("Delay (seconds):"));
-    let entry_delay = Entry::new();
-    entry_delay.set_max_length(6);
-    entry_delay.set_placeholder_text(Some("e.g., 3"));
-    row.append(&lbl_delay);
-    row.append(&entry_delay);
-    vbox.append(&row);
-
-    let btn_start = Button::with_label("Type After Delay");
-    vbox.append(&btn_start);
-
-    let lbl_status = Label::new(None);
-    lbl_status.set_xalign(0.0);
-    vbox.append(&lbl_status);
-
-    window.set_child(Some(&vbox));
-
-    let saved_delay = load_settings();
-    entry_delay.set_text(&saved_delay);
-
-    btn_start.connect_clicked(glib::clone!(
-        @weak buffer,
-        @weak entry_delay,
-        @weak lbl_status,
-        @weak btn_start,
-        => move |_| {
-            let start = buffer.start_iter();
-            let end = buffer.end_iter();
-            let text = buffer.text(&start, &end, true).to_string();
-            let delay_str = entry_delay.text().to_string();
-
-            save_settings(&delay_str);
-
-            let delay_sec = match delay_str.parse::<u64>() {
-                Ok(d) if d <= 86400 => d,
-                _ => {
-                    lbl_status.set_text("Invalid delay (must be a number from 0–86400).");
-                    return;
+
+    app.add_main_option(
+        "delay",
+        glib::Char::from(b'd'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Int,
+        "Delay in seconds before typing starts",
+        Some("SECONDS"),
+    );
+    app.add_main_option(
+        "text",
+        glib::Char::from(b't'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Text to load into the window",
+        Some("TEXT"),
+    );
+    app.add_main_option(
+        "url",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Load the text at this http:// or https:// URL into the window",
+        Some("URL"),
+    );
+    app.add_main_option(
+        "now",
+        glib::Char::from(b'n'),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Start the countdown immediately",
+        None,
+    );
+    app.add_main_option(
+        "stream",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Type standard input line by line as it comes in, following control lines such as %%PAUSE 2000%%",
+        None,
+    );
+
+    app.add_main_option(
+        "set",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::StringArray,
+        "Use VALUE for the setting KEY in this --now run only, without saving it (repeatable)",
+        Some("KEY=VALUE"),
+    );
+    app.add_main_option(
+        "var",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::StringArray,
+        "Give the template variable NAME the value VALUE in this --now run (repeatable)",
+        Some("NAME=VALUE"),
+    );
+    app.add_main_option(
+        "log-level",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Log from this level on (error, warn, info, debug, trace) to stderr, and with \",file\" to the state folder",
+        Some("LEVEL[,file]"),
+    );
+    app.add_main_option(
+        "portable",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Keep the settings and state next to the executable, as if portable.flag were there",
+        None,
+    );
+    app.add_main_option(
+        "profile",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Switch to this settings profile (\"\" for none)",
+        Some("NAME"),
+    );
+    app.add_main_option(
+        "background",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Run without a window, serving D-Bus requests until activated",
+        None,
+    );
+    app.add_main_option(
+        "list-backends",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Print each typing backend and whether it is available, then exit",
+        None,
+    );
+    app.add_main_option(
+        "check-config",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Print the problems in config.toml, then exit (non-zero if there are any)",
+        None,
+    );
+    app.add_main_option(
+        "json",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Print machine-readable JSON (with --list-backends or --now)",
+        None,
+    );
+    app.add_main_option(
+        "http-port",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Int,
+        "Serve the localhost HTTP API on this port (needs http_token in config.toml)",
+        Some("PORT"),
+    );
+    app.add_main_option(
+        "mqtt-broker",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Type messages from this MQTT broker, e.g. mqtts://host:8883 (needs allow_remote_text in config.toml)",
+        Some("URL"),
+    );
+
+    app.add_main_option(
+        "serve",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::String,
+        "Type the texts other machines send to this address, e.g. 0.0.0.0:7878, without opening a window",
+        Some("ADDRESS"),
+    );
+    app.add_main_option(
+        "token",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Filename,
+        "With --serve: the file holding the token clients must know (at least 16 characters)",
+        Some("FILE"),
+    );
+    app.add_main_option(
+        "tls-cert",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Filename,
+        "With --serve: serve over TLS with this PEM certificate chain",
+        Some("FILE"),
+    );
+    app.add_main_option(
+        "tls-key",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Filename,
+        "With --serve and --tls-cert: the certificate's PEM private key",
+        Some("FILE"),
+    );
+
+    app.add_main_option(
+        "benchmark",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "After the countdown, type a test pattern at shorter and shorter delays and print how fast the keys went out",
+        None,
+    );
+    app.add_main_option(
+        "dry-run",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::Filename,
+        "With --text: type nothing, and write the keyboard events the run would send to FILE as an evemu trace",
+        Some("FILE"),
+    );
+    app.add_main_option(
+        "self-test-gui",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Type a test text into the app's test target window and report what arrived; fails unless all of it did",
+        None,
+    );
+    app.add_main_option(
+        "doctor",
+        glib::Char::from(0),
+        glib::OptionFlags::NONE,
+        glib::OptionArg::None,
+        "Check the uinput device, permissions, session and backends, and print how to fix problems",
+        None,
+    );
+
+    // Informational options are answered locally and never reach a running instance.
+    app.connect_handle_local_options(|_, options| {
+        if options.contains("list-backends") {
+            return list_backends(options.contains("json"));
+        }
+        if options.contains("check-config") {
+            return check_config();
+        }
+        if options.contains("doctor") {
+            let checks = diagnostics::run_checks();
+            print!("{}", diagnostics::report(&checks));
+            return if diagnostics::can_type(&checks) { 0 } else { 1 };
+        }
+        if options.contains("benchmark") {
+            return benchmark(options.lookup::<i32>("delay").ok().flatten());
+        }
+        if let Some(path) = options.lookup::<PathBuf>("dry-run").ok().flatten() {
+            return match options.lookup::<String>("text").ok().flatten() {
+                Some(text) => dry_run(&path, &text),
+                None => {
+                    eprintln!("--dry-run needs --text TEXT, the text to run.");
+                    1
                 }
             };
-
-            btn_start.set_sensitive(false);
-            lbl_status.set_text(&format!("Typing in {} second{}... focus the target window.", delay_sec, if delay_sec == 1 { "" } else { "s" }));
-
-            let remaining_seconds = Rc::new(RefCell::new(delay_sec));
-
-            if delay_sec > 0 {
-                let lbl_status_clone = lbl_status.clone();
-                let remaining_seconds_clone = remaining_seconds.clone();
-                timeout_add_local(Duration::from_secs(1), move || {
-                    let mut current = remaining_seconds_clone.borrow_mut();
-                    *current -= 1;
-                    if *current > 0 {
-                        lbl_status_clone.set_text(&format!("Typing in {} second{}... focus the target window.", *current, if *current == 1 { "" } else { "s" }));
-                        ControlFlow::Continue
-                    } else {
-                        lbl_status_clone.set_text("Typing now...");
-                        ControlFlow::Break
-                    }
-                });
+        }
+        if let Some(address) = options.lookup::<String>("serve").ok().flatten() {
+            let path = |name: &str| options.lookup::<PathBuf>(name).ok().flatten();
+            return serve(&address, path("token"), path("tls-cert"), path("tls-key"));
+        }
+        -1
+    });
+
+    let slot: Rc<WindowSlot> = Rc::default();
+    app.connect_startup(glib::clone!(@strong slot => move |app| {
+        appearance::apply(load_appearance_setting());
+        if let Some(conn) = app.dbus_connection() {
+            if let Err(e) = dbus::register(app, &conn, slot.clone()) {
+                log::warn!("Failed to export the D-Bus interface: {}", e);
             }
-
-            let (sender, receiver) = mpsc::channel::<Result<()>>();
-            timeout_add_local(Duration::from_millis(100), glib::clone!(
-                @weak btn_start,
-                @weak lbl_status
-                => @default-return ControlFlow::Break,
-                move || {
-                    match receiver.try_recv() {
-                        Ok(result) => {
-                            match result {
-                                Ok(()) => lbl_status.set_text("✓ Done typing."),
-                                Err(e) => lbl_status.set_text(&format!("Typing failed: {:?}", e)),
-                            }
-                            btn_start.set_sensitive(true);
-                            ControlFlow::Break
-                        }
-                        Err(_) => ControlFlow::Continue,
-                    }
+            if tray::enabled() {
+                if let Err(e) = tray::start(app, &conn, slot.clone()) {
+                    log::warn!("Failed to export the tray icon: {}", e);
                 }
-            ));
-
-            timeout_add_local_once(Duration::from_secs(delay_sec), move || {
-                thread::spawn(move || {
-                    let res = simulate_typing_with_uinput(&text);
-                    let _ = sender.send(res);
-                });
-            });
+            }
         }
-    ));
-
-    window.present();
-}
-
-fn main() {
-    let app = Application::builder()
-        .application_id(APP_ID)
-        .build();
-
-    app.connect_activate(build_ui);
-    app.run();
+        if let Some(port) = http::configured_port() {
+            if let Err(e) = http::start(app, slot.clone(), port) {
+                log::warn!("{}.", e);
+            }
+        }
+        if let Some(broker) = mqtt::configured_broker() {
+            if let Err(e) = mqtt::start(app, slot.clone(), &broker) {
+                log::warn!("{:#}.", e);
+            }
+        }
+        if watcher::enabled() {
+            if let Err(e) = watcher::start(app, slot.clone()) {
+                log::warn!("{:#}.", e);
+            }
+        }
+        snippet_shortcuts::start(app, slot.clone());
+    }));
+    app.connect_activate(glib::clone!(@strong slot => move |app| {
+        slot.get(app).window.present();
+    }));
+    app.connect_command_line(move |app, cmdline| handle_command_line(app, cmdline, &slot));
+    app.run()
 }
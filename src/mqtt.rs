@@ -0,0 +1,144 @@
+// Opt-in MQTT trigger mode: every message on the configured topic is typed
+// after the configured delay, and run status is published to a companion
+// topic. Only starts with `allow_remote_text = true`, since it lets anyone
+// who can publish to the topic type into this machine.
+
+use gtk4::{glib, Application};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS, Transport};
+use serde_json::{json, Value};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
+use pasteclipboard::mqtt_config::{load_mqtt_settings, MqttSettings};
+use pasteclipboard::settings::{load_delay_setting, load_typing_options, remote_text_allowed, Settings};
+use pasteclipboard::typing::{TypingEvent, MAX_REMOTE_TEXT_BYTES};
+
+use crate::window::{RunListener, WindowSlot};
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The `mqtt_broker` from config.toml, if the mode is enabled there.
+pub fn configured_broker() -> Option<String> {
+    Some(Settings::load().mqtt_broker).filter(|b| !b.is_empty())
+}
+
+fn mqtt_options(settings: &MqttSettings) -> Result<MqttOptions> {
+    let broker = &settings.broker;
+    let mut options = MqttOptions::new(&settings.client_id, &broker.host, broker.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    // Larger payloads are dropped by the client before they reach us; the
+    // text limit itself is checked (and reported) per message.
+    options.set_max_packet_size(4 * MAX_REMOTE_TEXT_BYTES, 64 * 1024);
+    options.set_last_will(LastWill::new(&settings.status_topic, json!({ "state": "offline" }).to_string(), QoS::AtLeastOnce, true));
+    if let Some(username) = &settings.username {
+        options.set_credentials(username, settings.password.clone().unwrap_or_default());
+    }
+    if broker.tls {
+        let transport = match &settings.ca_file {
+            Some(path) => {
+                let ca = std::fs::read(path).with_context(|| format!("Cannot read mqtt_ca_file {}", path.display()))?;
+                Transport::tls(ca, None, None)
+            }
+            None => std::panic::catch_unwind(Transport::tls_with_default_config)
+                .map_err(|_| anyhow!("Cannot load the system's CA certificates; set mqtt_ca_file"))?,
+        };
+        options.set_transport(transport);
+    }
+    Ok(options)
+}
+
+/// Connects to `broker_url` and starts typing messages from it; later calls
+/// do nothing.
+pub fn start(app: &Application, slot: Rc<WindowSlot>, broker_url: &str) -> Result<()> {
+    if STARTED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    if !remote_text_allowed() {
+        bail!("Set allow_remote_text = true in config.toml before enabling MQTT; it lets the broker type on this machine");
+    }
+    let settings = load_mqtt_settings(broker_url)?;
+    let (client, mut connection) = Client::new(mqtt_options(&settings)?, 16);
+    STARTED.store(true, Ordering::SeqCst);
+
+    let (sender, receiver) = flume::unbounded::<Vec<u8>>();
+    let topic = settings.topic.clone();
+    let status_topic = settings.status_topic.clone();
+    let subscriber = client.clone();
+    thread::spawn(move || {
+        for notification in connection.iter() {
+            match notification {
+                // Subscriptions do not survive a reconnect with a clean session.
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    let _ = subscriber.try_subscribe(&topic, QoS::AtLeastOnce);
+                    let _ = subscriber.try_publish(&status_topic, QoS::AtLeastOnce, true, json!({ "state": "online" }).to_string());
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) if publish.topic == topic => {
+                    if sender.send(publish.payload.to_vec()).is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::warn!("MQTT: {}", e);
+                    thread::sleep(Duration::from_secs(5));
+                }
+            }
+        }
+    });
+
+    let app = app.clone();
+    glib::MainContext::default().spawn_local(async move {
+        while let Ok(payload) = receiver.recv_async().await {
+            if let Err(e) = start_run(&app, &slot, &client, &settings.status_topic, payload) {
+                publish(&client, &settings.status_topic, json!({ "state": "rejected", "error": e.to_string() }));
+            }
+        }
+    });
+    Ok(())
+}
+
+fn publish(client: &Client, topic: &str, status: Value) {
+    let _ = client.try_publish(topic, QoS::AtLeastOnce, false, status.to_string());
+}
+
+fn start_run(app: &Application, slot: &WindowSlot, client: &Client, status_topic: &str, payload: Vec<u8>) -> Result<()> {
+    if payload.len() > MAX_REMOTE_TEXT_BYTES {
+        bail!("Text is longer than {} bytes", MAX_REMOTE_TEXT_BYTES);
+    }
+    let text = String::from_utf8(payload).map_err(|_| anyhow!("Text is not valid UTF-8"))?;
+    let delay = load_delay_setting().map_err(|e| anyhow!("{} in config.toml", e))?;
+    let options = load_typing_options().map_err(|e| anyhow!("{} in config.toml", e))?;
+
+    let ui = slot.get(app);
+    if ui.is_busy() {
+        bail!("A typing run is already in progress");
+    }
+    ui.run_listeners.borrow_mut().push(status_listener(client.clone(), status_topic.to_string()));
+    ui.start_run(text.into(), delay, options);
+    publish(client, status_topic, json!({ "state": "counting_down", "remaining_seconds": delay }));
+    Ok(())
+}
+
+/// Publishes when typing starts and how the run ended.
+fn status_listener(client: Client, topic: String) -> RunListener {
+    Box::new(move |event| {
+        let status = match event {
+            TypingEvent::Started { total_chars } => json!({ "state": "typing", "total": total_chars }),
+            TypingEvent::Finished { stats } => {
+                let mut status = stats.to_json();
+                status["state"] = "finished".into();
+                status
+            }
+            TypingEvent::Failed { error, stats } => {
+                let mut status = stats.to_json();
+                status["state"] = "failed".into();
+                status["error"] = format!("{:#}", error).into();
+                status
+            }
+            _ => return,
+        };
+        publish(&client, &topic, status);
+    })
+}
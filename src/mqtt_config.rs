@@ -0,0 +1,137 @@
+//! Settings for the opt-in MQTT trigger mode, from the broker URL and the
+//! `mqtt_*` keys in config.toml.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::settings::Settings;
+
+pub const DEFAULT_TOPIC: &str = "pasteclipboard/type";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Broker {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+/// Parses `mqtt://host[:port]` or `mqtts://host[:port]` (TLS). A bare
+/// `host[:port]` means plain MQTT.
+pub fn parse_broker(url: &str) -> Result<Broker> {
+    let url = url.trim();
+    let (tls, rest) = match url.split_once("://") {
+        Some(("mqtt" | "tcp", rest)) => (false, rest),
+        Some(("mqtts" | "ssl", rest)) => (true, rest),
+        Some((scheme, _)) => bail!("Unsupported MQTT scheme '{}' (expected mqtt:// or mqtts://)", scheme),
+        None => (false, url),
+    };
+    let rest = rest.trim_end_matches('/');
+    let default_port = if tls { 8883 } else { 1883 };
+    let (host, port) = match rest.strip_prefix('[') {
+        // [IPv6]:port
+        Some(bracketed) => {
+            let (host, after) = bracketed.split_once(']').ok_or_else(|| anyhow!("Invalid MQTT broker '{}'", url))?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match rest.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => (host, Some(port)),
+            _ => (rest, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse::<u16>().ok().filter(|p| *p > 0).ok_or_else(|| anyhow!("Invalid MQTT port '{}'", port))?,
+        None => default_port,
+    };
+    if host.is_empty() || host.contains('/') {
+        bail!("Invalid MQTT broker '{}'", url);
+    }
+    Ok(Broker { host: host.to_string(), port, tls })
+}
+
+#[derive(Clone, Debug)]
+pub struct MqttSettings {
+    pub broker: Broker,
+    /// Messages on this topic are typed.
+    pub topic: String,
+    /// Run status is published here.
+    pub status_topic: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// CA certificate (PEM) for TLS; the system's certificates otherwise.
+    pub ca_file: Option<PathBuf>,
+    pub client_id: String,
+}
+
+pub fn load_mqtt_settings(broker_url: &str) -> Result<MqttSettings> {
+    let settings = Settings::load();
+    let set = |value: &String| Some(value.clone()).filter(|v| !v.is_empty());
+
+    let topic = set(&settings.mqtt_topic).unwrap_or_else(|| DEFAULT_TOPIC.to_string());
+    if topic.contains(['+', '#']) {
+        bail!("mqtt_topic must not contain wildcards");
+    }
+    let status_topic = set(&settings.mqtt_status_topic).unwrap_or_else(|| format!("{}/status", topic));
+    let username = set(&settings.mqtt_username);
+    let password = set(&settings.mqtt_password);
+    if password.is_some() && username.is_none() {
+        bail!("mqtt_password is set without mqtt_username");
+    }
+
+    Ok(MqttSettings {
+        broker: parse_broker(broker_url)?,
+        topic,
+        status_topic,
+        username,
+        password,
+        ca_file: set(&settings.mqtt_ca_file).map(PathBuf::from),
+        client_id: set(&settings.mqtt_client_id).unwrap_or_else(|| format!("pasteclipboard-{}", std::process::id())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn broker(host: &str, port: u16, tls: bool) -> Broker {
+        Broker { host: host.to_string(), port, tls }
+    }
+
+    #[test]
+    fn the_scheme_picks_tls_and_the_default_port() {
+        for (url, expected) in [
+            ("mqtt://broker.lan", broker("broker.lan", 1883, false)),
+            ("tcp://broker.lan:1884/", broker("broker.lan", 1884, false)),
+            ("mqtts://broker.lan", broker("broker.lan", 8883, true)),
+            ("ssl://broker.lan:9000", broker("broker.lan", 9000, true)),
+            ("  broker.lan ", broker("broker.lan", 1883, false)),
+            ("10.0.0.2:1885", broker("10.0.0.2", 1885, false)),
+        ] {
+            assert_eq!(parse_broker(url).unwrap(), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn ipv6_hosts_take_their_port_after_the_brackets() {
+        assert_eq!(parse_broker("mqtts://[::1]:8884").unwrap(), broker("::1", 8884, true));
+        assert_eq!(parse_broker("[fd00::5]").unwrap(), broker("fd00::5", 1883, false));
+        // Without brackets the colons are all part of the address.
+        assert_eq!(parse_broker("fd00::5").unwrap(), broker("fd00::5", 1883, false));
+    }
+
+    #[test]
+    fn bad_brokers_are_refused() {
+        for url in [
+            "http://broker.lan",
+            "mqtt://",
+            "mqtt://:1883",
+            "mqtt://broker.lan:0",
+            "mqtt://broker.lan:65536",
+            "mqtt://broker.lan:port",
+            "mqtt://broker.lan/topic",
+            "mqtt://[::1",
+        ] {
+            assert!(parse_broker(url).is_err(), "{}", url);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// `pasteclipboard --native-messaging`: a browser native messaging host that
+// forwards {"action": "type"} messages to the running instance over D-Bus,
+// starting it in background mode if needed, and `install-native-host`,
+// which registers the host with Firefox and Chrome/Chromium.
+
+use anyhow::{anyhow, bail, Context, Result};
+use directories::BaseDirs;
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{self, BusType, DBusCallFlags, DBusConnection, DBusSignalFlags},
+    glib::{self, MainContext, Variant},
+};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use pasteclipboard::native_messaging::{read_message, write_message, Incoming, NativeRequest, MAX_MESSAGE_BYTES};
+use pasteclipboard::settings::{load_delay_setting, portable_base};
+use pasteclipboard::typing::{MAX_DELAY_SECONDS, MAX_REMOTE_TEXT_BYTES};
+
+use crate::dbus::{INTERFACE, OBJECT_PATH};
+use crate::APP_ID;
+
+/// The host name browsers know us by.
+const HOST_NAME: &str = "com.example.pasteclipboard";
+
+const USAGE: &str = "Usage: pasteclipboard install-native-host [--firefox EXTENSION_ID] [--chrome EXTENSION_ID]
+
+Registers PasteClipboard as a native messaging host for the given browser
+extensions. --chrome covers both Google Chrome and Chromium.
+";
+
+/// Reads messages from the browser until it closes stdin, answering each on
+/// stdout. Nothing else may be written to stdout in this mode.
+pub fn serve() -> i32 {
+    let conn = match gio::bus_get_sync(BusType::Session, gio::Cancellable::NONE) {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("Failed to connect to the session bus: {}", e);
+            return 1;
+        }
+    };
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    loop {
+        let reply = match read_message(&mut stdin, MAX_MESSAGE_BYTES) {
+            Ok(None) => return 0,
+            Ok(Some(Incoming::TooLarge(length))) => {
+                failure(format!("Message of {} bytes is too large (limit {} bytes)", length, MAX_MESSAGE_BYTES))
+            }
+            Ok(Some(Incoming::Message(body))) => match serde_json::from_slice::<NativeRequest>(&body) {
+                Ok(request) => match handle(&conn, request) {
+                    Ok(message) => json!({ "success": true, "message": message }),
+                    Err(e) => failure(format!("{:#}", e)),
+                },
+                Err(e) => failure(format!("Invalid message: {}", e)),
+            },
+            Err(e) => {
+                eprintln!("Failed to read from the browser: {}", e);
+                return 1;
+            }
+        };
+        if let Err(e) = write_message(&mut stdout, &reply) {
+            eprintln!("Failed to answer the browser: {}", e);
+            return 1;
+        }
+    }
+}
+
+fn failure(error: String) -> Value {
+    json!({ "success": false, "error": error })
+}
+
+fn handle(conn: &DBusConnection, request: NativeRequest) -> Result<String> {
+    let NativeRequest::Type { text, delay } = request;
+    if text.len() > MAX_REMOTE_TEXT_BYTES {
+        bail!("Text is longer than {} bytes", MAX_REMOTE_TEXT_BYTES);
+    }
+    let delay = match delay {
+        Some(delay) => delay,
+        None => load_delay_setting().map_err(|e| anyhow!("{} in config.toml", e))?,
+    };
+    let delay = u32::try_from(delay)
+        .ok()
+        .filter(|d| u64::from(*d) <= MAX_DELAY_SECONDS)
+        .ok_or_else(|| anyhow!("Invalid delay (must be a number from 0–{})", MAX_DELAY_SECONDS))?;
+
+    ensure_running(conn)?;
+    type_and_wait(conn, &text, delay)
+}
+
+fn has_owner(conn: &DBusConnection) -> Result<bool> {
+    let reply = conn.call_sync(
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "NameHasOwner",
+        Some(&(APP_ID,).to_variant()),
+        None,
+        DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+    )?;
+    reply.get::<(bool,)>().map(|(owned,)| owned).ok_or_else(|| anyhow!("Unexpected reply from the bus"))
+}
+
+/// Starts `pasteclipboard --background` unless an instance already owns the
+/// application's bus name, and waits for it to appear.
+fn ensure_running(conn: &DBusConnection) -> Result<()> {
+    if has_owner(conn)? {
+        return Ok(());
+    }
+    let exe = std::env::current_exe().context("Cannot determine the path of this executable")?;
+    Command::new(exe)
+        .arg("--background")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to start PasteClipboard")?;
+
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(10) {
+        std::thread::sleep(Duration::from_millis(100));
+        if has_owner(conn)? {
+            return Ok(());
+        }
+    }
+    bail!("PasteClipboard did not start within 10 seconds")
+}
+
+/// Removes GDBus's "GDBus.Error:<name>: " prefix from a remote error.
+fn remote_message(error: &glib::Error) -> String {
+    let message = error.message();
+    match message.strip_prefix("GDBus.Error:").and_then(|rest| rest.split_once(": ")) {
+        Some((_, text)) => text.to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// Calls Type and waits for the run's Finished signal. The instance refuses
+/// a second concurrent run, so the next Finished after a successful call is
+/// this run's.
+fn type_and_wait(conn: &DBusConnection, text: &str, delay: u32) -> Result<String> {
+    let context = MainContext::new();
+    context
+        .with_thread_default(|| -> Result<String> {
+            let outcome: Rc<RefCell<Option<Result<String>>>> = Rc::default();
+            let finished = conn.signal_subscribe(
+                None,
+                Some(INTERFACE),
+                Some("Finished"),
+                Some(OBJECT_PATH),
+                None,
+                DBusSignalFlags::NONE,
+                glib::clone!(@strong outcome => move |_, _, _, _, _, params| {
+                    let result = match params.get::<(bool, String)>() {
+                        Some((true, message)) => Ok(message),
+                        Some((false, message)) => Err(anyhow!(message)),
+                        None => Err(anyhow!("Unexpected Finished signal")),
+                    };
+                    outcome.replace(Some(result));
+                }),
+            );
+            let vanished = conn.signal_subscribe(
+                Some("org.freedesktop.DBus"),
+                Some("org.freedesktop.DBus"),
+                Some("NameOwnerChanged"),
+                Some("/org/freedesktop/DBus"),
+                Some(APP_ID),
+                DBusSignalFlags::NONE,
+                glib::clone!(@strong outcome => move |_, _, _, _, _, params| {
+                    if params.get::<(String, String, String)>().is_some_and(|(_, _, owner)| owner.is_empty()) {
+                        outcome.replace(Some(Err(anyhow!("PasteClipboard exited before the run finished"))));
+                    }
+                }),
+            );
+
+            let call = conn.call_sync(
+                Some(APP_ID),
+                OBJECT_PATH,
+                INTERFACE,
+                "Type",
+                Some(&Variant::from((text, delay))),
+                None,
+                DBusCallFlags::NONE,
+                -1,
+                gio::Cancellable::NONE,
+            );
+            if let Err(e) = call {
+                outcome.replace(Some(Err(anyhow!(remote_message(&e)))));
+            }
+            while outcome.borrow().is_none() {
+                context.iteration(true);
+            }
+
+            conn.signal_unsubscribe(finished);
+            conn.signal_unsubscribe(vanished);
+            outcome.take().expect("loop ends with an outcome")
+        })
+        .map_err(|e| anyhow!("Failed to use a private main context: {}", e))?
+}
+
+fn data_dir() -> Result<(BaseDirs, PathBuf)> {
+    if portable_base().is_some() {
+        bail!("Portable mode does not write to the home directory, where browsers look for the host");
+    }
+    let base = BaseDirs::new().context("Cannot determine your home directory")?;
+    let data = base.data_local_dir().join("PasteClipboard");
+    Ok((base, data))
+}
+
+/// Browsers start the host without arguments of our choosing, so the
+/// manifest points at a script that adds `--native-messaging`.
+fn write_wrapper(dir: &Path) -> Result<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let exe = std::env::current_exe().context("Cannot determine the path of this executable")?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+    let Some(exe) = exe.to_str() else {
+        bail!("The executable path {} is not valid UTF-8", exe.display());
+    };
+    let path = dir.join("native-messaging-host");
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let script = format!("#!/bin/sh\nexec '{}' --native-messaging \"$@\"\n", exe.replace('\'', "'\\''"));
+    std::fs::write(&path, script).with_context(|| format!("Failed to write {}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make {} executable", path.display()))?;
+    Ok(path)
+}
+
+fn write_manifest(dir: &Path, manifest: &Value) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}.json", HOST_NAME));
+    let contents = serde_json::to_string_pretty(manifest).context("Failed to encode the manifest")?;
+    std::fs::write(&path, contents + "\n").with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn install(firefox: Option<&str>, chrome: Option<&str>) -> Result<()> {
+    let (base, data) = data_dir()?;
+    let wrapper = write_wrapper(&data)?;
+    let description = "PasteClipboard: types text into the focused window";
+
+    if let Some(id) = firefox {
+        let manifest = json!({
+            "name": HOST_NAME,
+            "description": description,
+            "path": wrapper,
+            "type": "stdio",
+            "allowed_extensions": [id],
+        });
+        write_manifest(&base.home_dir().join(".mozilla").join("native-messaging-hosts"), &manifest)?;
+    }
+    if let Some(id) = chrome {
+        let manifest = json!({
+            "name": HOST_NAME,
+            "description": description,
+            "path": wrapper,
+            "type": "stdio",
+            "allowed_origins": [format!("chrome-extension://{}/", id)],
+        });
+        for browser in ["google-chrome", "chromium"] {
+            write_manifest(&base.config_dir().join(browser).join("NativeMessagingHosts"), &manifest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `install-native-host` with the arguments that follow its name.
+pub fn run_install(args: &[String]) -> i32 {
+    let mut firefox = None;
+    let mut chrome = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--firefox" => &mut firefox,
+            "--chrome" => &mut chrome,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return 0;
+            }
+            other => {
+                eprintln!("Unknown argument '{}'.\n\n{}", other, USAGE);
+                return 2;
+            }
+        };
+        match args.next() {
+            Some(id) => *target = Some(id.as_str()),
+            None => {
+                eprintln!("{} needs an extension ID.\n\n{}", arg, USAGE);
+                return 2;
+            }
+        }
+    }
+    if firefox.is_none() && chrome.is_none() {
+        eprintln!("Give at least one of --firefox or --chrome.\n\n{}", USAGE);
+        return 2;
+    }
+    match install(firefox, chrome) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("install-native-host: {:#}", e);
+            1
+        }
+    }
+}
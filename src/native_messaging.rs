@@ -0,0 +1,55 @@
+//! The browser native messaging protocol: each message is a JSON document
+//! preceded by its length as a 32-bit native-endian integer.
+//!
+//!   {"action": "type", "text": "...", "delay": 3}
+
+use std::io::{self, Read, Write};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::typing::MAX_REMOTE_TEXT_BYTES;
+
+/// Largest message read from the browser. JSON escaping can make a text of
+/// `MAX_REMOTE_TEXT_BYTES` longer than that, so leave room; the text itself
+/// is checked again after parsing.
+pub const MAX_MESSAGE_BYTES: usize = 2 * MAX_REMOTE_TEXT_BYTES + 4096;
+
+pub enum Incoming {
+    Message(Vec<u8>),
+    /// A message longer than the limit; its bytes were read and dropped.
+    TooLarge(usize),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum NativeRequest {
+    /// `delay` falls back to the configured delay.
+    Type { text: String, delay: Option<u64> },
+}
+
+/// Reads the next message; `None` once the browser closes the pipe.
+pub fn read_message(reader: &mut impl Read, limit: usize) -> io::Result<Option<Incoming>> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_ne_bytes(header) as usize;
+    if length > limit {
+        io::copy(&mut reader.take(length as u64), &mut io::sink())?;
+        return Ok(Some(Incoming::TooLarge(length)));
+    }
+    let mut message = vec![0; length];
+    reader.read_exact(&mut message)?;
+    Ok(Some(Incoming::Message(message)))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    let length = u32::try_from(body.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too long"))?;
+    writer.write_all(&length.to_ne_bytes())?;
+    writer.write_all(body.as_bytes())?;
+    writer.flush()
+}
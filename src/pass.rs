@@ -0,0 +1,45 @@
+//! Secrets from the `pass` password store for the auto-type `{PASS:path}`
+//! placeholder. `pass show <path>` runs without a shell, like a command
+//! source, and only the first line of what it prints, the password by the
+//! store's convention, is used. gopass takes the same arguments, so
+//! `pass_command` can name it instead.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use zeroize::Zeroizing;
+
+use crate::command::run_command;
+
+pub const DEFAULT_PASS_COMMAND: &str = "pass";
+
+/// How long `pass show` may take; longer than a command source gets, since
+/// gpg-agent may first ask for the key's passphrase.
+pub const PASS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Checks an entry's path as a sequence names it. One starting with `-`
+/// would be read as an option.
+pub fn validate_path(path: &str) -> Result<()> {
+    if path.is_empty() {
+        bail!("{{PASS:}} needs the path of an entry, like {{PASS:work/vpn}}");
+    }
+    if path.starts_with('-') || path.chars().any(char::is_control) {
+        bail!("Invalid pass entry '{}'", path);
+    }
+    Ok(())
+}
+
+/// The password stored at `path`, from `command show`. Fails with what the
+/// command said if the entry is missing or cannot be decrypted, and if the
+/// entry's first line is empty.
+pub fn pass_show(command: &str, path: &str) -> Result<Zeroizing<String>> {
+    validate_path(path)?;
+    let argv = [command.to_string(), "show".to_string(), path.to_string()];
+    let output = Zeroizing::new(
+        run_command(&argv, PASS_TIMEOUT).with_context(|| format!("Cannot read '{}' from the password store", path))?,
+    );
+    match output.lines().next().filter(|line| !line.is_empty()) {
+        Some(password) => Ok(Zeroizing::new(password.to_string())),
+        None => bail!("The pass entry '{}' has no password on its first line", path),
+    }
+}
@@ -0,0 +1,120 @@
+// Shown when a run fails because /dev/uinput is not writable: explains the
+// fixes and can install the udev rule through pkexec, then checks again.
+
+use gtk4::prelude::*;
+use gtk4::{
+    glib::{timeout_add_local, ControlFlow},
+    Button, Label, Orientation, Window,
+};
+use std::rc::Rc;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use pasteclipboard::uinput_access::{
+    group_status, install_rule_with_pkexec, test_access, GroupStatus, UDEV_RULE_PATH, UINPUT_GROUP, UINPUT_PATH,
+};
+
+use crate::window::MainWindow;
+
+fn group_text() -> String {
+    match group_status(UINPUT_GROUP) {
+        GroupStatus::Missing => format!("This system has no '{}' group; the rule creates access through your login seat instead.", UINPUT_GROUP),
+        GroupStatus::NotMember => format!("You are not in the '{}' group, so access comes through your login seat.", UINPUT_GROUP),
+        GroupStatus::MemberAfterLogin => {
+            format!("You were added to the '{}' group after logging in; that takes effect at the next login.", UINPUT_GROUP)
+        }
+        GroupStatus::Member => format!("You are in the '{}' group.", UINPUT_GROUP),
+    }
+}
+
+/// What the retest after installing found, for the dialog's status line.
+fn outcome_text(result: anyhow::Result<()>) -> String {
+    if let Err(e) = result {
+        return format!("{:#}.", e);
+    }
+    match test_access() {
+        Ok(()) => format!("Fixed: {} is writable now. Start the run again.", UINPUT_PATH),
+        Err(e) if group_status(UINPUT_GROUP) == GroupStatus::MemberAfterLogin => {
+            format!("The rule is installed, but {} is still not writable ({}). Log out and back in.", UINPUT_PATH, e)
+        }
+        Err(e) => format!(
+            "The rule is installed, but {} is still not writable ({}). Adding yourself to the '{}' group and logging in \
+             again should fix it.",
+            UINPUT_PATH, e, UINPUT_GROUP
+        ),
+    }
+}
+
+pub fn show(ui: &Rc<MainWindow>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("No permission for /dev/uinput")
+        .default_width(480)
+        .build();
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let lbl_help = Label::new(Some(&format!(
+        "PasteClipboard types through a virtual keyboard, which needs write access to {}. There are two ways to get it:\n\n\
+         • Install a udev rule that gives the device to the '{}' group and to whoever is logged in at this computer. \
+         \"Fix it for me\" writes {} and reloads udev; it asks for an administrator password.\n\n\
+         • Or run PasteClipboard as root, e.g. with pkexec pasteclipboard. This is not recommended, and Wayland \
+         sessions usually refuse windows from root.\n\n{}",
+        UINPUT_PATH,
+        UINPUT_GROUP,
+        UDEV_RULE_PATH,
+        group_text()
+    )));
+    lbl_help.set_xalign(0.0);
+    lbl_help.set_wrap(true);
+    vbox.append(&lbl_help);
+
+    let lbl_status = Label::new(None);
+    lbl_status.set_xalign(0.0);
+    lbl_status.set_wrap(true);
+    vbox.append(&lbl_status);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_close = Button::with_label("Close");
+    let btn_fix = Button::with_label("Fix It for Me");
+    btn_fix.add_css_class("suggested-action");
+    buttons.append(&btn_close);
+    buttons.append(&btn_fix);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+
+    let dialog_clone = dialog.clone();
+    btn_close.connect_clicked(move |_| dialog_clone.close());
+
+    let readiness = ui.readiness.clone();
+    btn_fix.connect_clicked(move |btn_fix| {
+        btn_fix.set_sensitive(false);
+        lbl_status.set_text("Installing the udev rule…");
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(outcome_text(install_rule_with_pkexec()));
+        });
+        let btn_fix = btn_fix.clone();
+        let lbl_status = lbl_status.clone();
+        let readiness = readiness.clone();
+        timeout_add_local(Duration::from_millis(100), move || {
+            let text = match receiver.try_recv() {
+                Ok(text) => text,
+                Err(TryRecvError::Empty) => return ControlFlow::Continue,
+                Err(TryRecvError::Disconnected) => "The installer stopped unexpectedly.".to_string(),
+            };
+            lbl_status.set_text(&text);
+            btn_fix.set_sensitive(test_access().is_err());
+            readiness.check();
+            ControlFlow::Break
+        });
+    });
+
+    dialog.present();
+}
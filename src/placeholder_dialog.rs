@@ -0,0 +1,142 @@
+// Asks for the values of the text's {{placeholders}} before a run, offering
+// the values entered last time. A variable only tested by {{if}} is a
+// check box.
+
+use gtk4::prelude::*;
+use gtk4::{Button, CheckButton, Entry, Grid, Label, Orientation, Widget, Window};
+use std::collections::HashMap;
+use std::rc::Rc;
+use pasteclipboard::template::{fill, is_set, load_remembered_values, remember_values, Variable};
+
+use crate::window::MainWindow;
+
+/// The field a variable is entered in.
+enum Field {
+    Text(Entry),
+    Condition(CheckButton),
+}
+
+impl Field {
+    fn value(&self) -> String {
+        match self {
+            Field::Text(entry) => entry.text().to_string(),
+            Field::Condition(check) => check.is_active().to_string(),
+        }
+    }
+
+    fn widget(&self) -> Widget {
+        match self {
+            Field::Text(entry) => entry.clone().upcast(),
+            Field::Condition(check) => check.clone().upcast(),
+        }
+    }
+}
+
+/// Shows the dialog for `text`'s `variables` and calls `then` with the
+/// filled-in text once the user confirms. `given` values (from `--var`)
+/// come first, then the remembered ones. `remember` saves the values for
+/// next time; secret runs leave it off so nothing they were given ends up
+/// on disk.
+pub fn show(
+    ui: &Rc<MainWindow>,
+    text: String,
+    variables: Vec<Variable>,
+    given: HashMap<String, String>,
+    remember: bool,
+    then: impl Fn(&Rc<MainWindow>, String) + 'static,
+) {
+    let mut known = if remember { load_remembered_values() } else { HashMap::new() };
+    known.extend(given);
+
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Fill in placeholders")
+        .default_width(420)
+        .build();
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let grid = Grid::builder().row_spacing(6).column_spacing(12).build();
+    let entries: Vec<(String, Field)> = variables
+        .into_iter()
+        .enumerate()
+        .map(|(row, Variable { name, condition })| {
+            let label = Label::new(Some(&name));
+            label.set_xalign(0.0);
+            let value = known.get(&name).map(String::as_str);
+            let field = if condition {
+                Field::Condition(CheckButton::builder().active(is_set(value)).build())
+            } else {
+                let entry = Entry::builder().hexpand(true).activates_default(true).build();
+                entry.set_text(value.unwrap_or_default());
+                Field::Text(entry)
+            };
+            grid.attach(&label, 0, row as i32, 1, 1);
+            grid.attach(&field.widget(), 1, row as i32, 1, 1);
+            (name, field)
+        })
+        .collect();
+    vbox.append(&grid);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label("Cancel");
+    let btn_type = Button::with_label("Type");
+    btn_type.add_css_class("suggested-action");
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_type);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_type));
+
+    // Every placeholder that will be typed needs a value before the run may
+    // start; one in a branch that is not typed does not.
+    let entries = Rc::new(entries);
+    let values = {
+        let entries = entries.clone();
+        move || -> HashMap<String, String> { entries.iter().map(|(name, field)| (name.clone(), field.value())).collect() }
+    };
+    let text = Rc::new(text);
+    let update = {
+        let (text, values, btn_type) = (text.clone(), values.clone(), btn_type.clone());
+        move || btn_type.set_sensitive(fill(&text, &values()).is_ok())
+    };
+    update();
+    for (_, field) in entries.iter() {
+        let update = update.clone();
+        match field {
+            Field::Text(entry) => entry.connect_changed(move |_| update()),
+            Field::Condition(check) => check.connect_toggled(move |_| update()),
+        };
+    }
+
+    if let Some((_, first)) = entries.first() {
+        first.widget().grab_focus();
+    }
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    let ui = ui.clone();
+    let dialog_clone = dialog.clone();
+    btn_type.connect_clicked(move |_| {
+        let values = values();
+        match fill(&text, &values) {
+            Ok(filled) => {
+                if remember {
+                    remember_values(&values);
+                }
+                dialog_clone.close();
+                then(&ui, filled);
+            }
+            Err(e) => ui.set_error(&format!("{}.", e)),
+        }
+    });
+
+    dialog.present();
+}
@@ -0,0 +1,415 @@
+// The preferences dialog: every config.toml option, grouped into General,
+// Typing, Safety and Advanced pages. Changes are written as they are made
+// and the main window is told to pick them up.
+
+use gtk4::prelude::*;
+use gtk4::{
+    Button, CheckButton, DropDown, Entry, Grid, Label, Orientation, PasswordEntry, Stack, StackSidebar, Window,
+};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use pasteclipboard::backend::Backend;
+use pasteclipboard::line_filter::DEFAULT_COMMENT_PREFIXES;
+use pasteclipboard::session::discard_draft;
+use pasteclipboard::settings::{Appearance, OverlayCorner, Settings};
+use pasteclipboard::typing::EnterKey;
+
+use crate::appearance;
+use crate::sounds::{self, Sound};
+use crate::window::MainWindow;
+
+/// Shared by every widget's handler.
+struct Prefs {
+    settings: RefCell<Settings>,
+    ui: Weak<MainWindow>,
+}
+
+impl Prefs {
+    fn changed(&self) {
+        if let Some(ui) = self.ui.upgrade() {
+            ui.settings_changed();
+        }
+    }
+}
+
+fn page() -> Grid {
+    let grid = Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_margin_start(18);
+    grid.set_margin_end(18);
+    grid.set_margin_top(18);
+    grid.set_margin_bottom(18);
+    grid
+}
+
+/// Appends a labelled widget; returns the next free row.
+fn add_row(grid: &Grid, row: i32, label: &str, widget: &impl IsA<gtk4::Widget>) -> i32 {
+    let label = Label::new(Some(label));
+    label.set_xalign(0.0);
+    grid.attach(&label, 0, row, 1, 1);
+    widget.set_hexpand(true);
+    grid.attach(widget, 1, row, 1, 1);
+    row + 1
+}
+
+fn add_note(grid: &Grid, row: i32, text: &str) -> i32 {
+    let note = Label::new(Some(text));
+    note.set_xalign(0.0);
+    note.set_wrap(true);
+    note.add_css_class("dim-label");
+    grid.attach(&note, 0, row, 2, 1);
+    row + 1
+}
+
+/// An entry whose value is saved through `set` on every change. Rejected
+/// values are marked in place, with the reason underneath, and not saved.
+fn add_validated_entry(
+    grid: &Grid,
+    row: i32,
+    label: &str,
+    entry: Entry,
+    prefs: &Rc<Prefs>,
+    set: impl Fn(&mut Settings, &str) -> anyhow::Result<()> + 'static,
+) -> i32 {
+    let lbl_error = Label::new(None);
+    lbl_error.set_xalign(0.0);
+    lbl_error.add_css_class("error");
+    lbl_error.set_visible(false);
+
+    let prefs = prefs.clone();
+    let lbl_error_clone = lbl_error.clone();
+    entry.connect_changed(move |entry| {
+        let result = set(&mut prefs.settings.borrow_mut(), &entry.text());
+        match result {
+            Ok(()) => {
+                entry.remove_css_class("error");
+                lbl_error_clone.set_visible(false);
+                prefs.changed();
+            }
+            Err(e) => {
+                entry.add_css_class("error");
+                lbl_error_clone.set_text(&format!("{}.", e));
+                lbl_error_clone.set_visible(true);
+            }
+        }
+    });
+
+    let row = add_row(grid, row, label, &entry);
+    grid.attach(&lbl_error, 1, row, 1, 1);
+    row + 1
+}
+
+fn add_flag(grid: &Grid, row: i32, label: &str, key: &'static str, value: bool, prefs: &Rc<Prefs>) -> i32 {
+    let check = CheckButton::with_label(label);
+    check.set_active(value);
+    let prefs = prefs.clone();
+    check.connect_toggled(move |check| {
+        let result = prefs.settings.borrow_mut().set_flag(key, check.is_active());
+        if result.is_ok() {
+            prefs.changed();
+        }
+    });
+    grid.attach(&check, 0, row, 2, 1);
+    row + 1
+}
+
+fn entry_with(text: &str, placeholder: Option<&str>) -> Entry {
+    let entry = Entry::new();
+    entry.set_text(text);
+    entry.set_placeholder_text(placeholder);
+    entry
+}
+
+fn general_page(prefs: &Rc<Prefs>, settings: &Settings) -> Grid {
+    let grid = page();
+    let row = add_validated_entry(
+        &grid,
+        0,
+        "Delay (seconds):",
+        entry_with(&settings.delay_seconds.to_string(), Some("e.g., 3")),
+        prefs,
+        |s, v| s.set_delay_seconds(v),
+    );
+    let labels: Vec<&str> = Appearance::ALL.iter().map(|a| a.label()).collect();
+    let dd_appearance = DropDown::from_strings(&labels);
+    dd_appearance.set_selected(Appearance::ALL.iter().position(|a| *a == settings.appearance).unwrap_or(0) as u32);
+    let prefs_clone = prefs.clone();
+    dd_appearance.connect_selected_notify(move |dd| {
+        let appearance = Appearance::ALL.get(dd.selected() as usize).copied().unwrap_or_default();
+        prefs_clone.settings.borrow_mut().set_appearance(appearance);
+        appearance::apply(appearance);
+    });
+    let row = add_row(&grid, row, "Appearance:", &dd_appearance);
+    let row = add_flag(&grid, row, "Expand ${VARIABLES} before typing", "expand_variables", settings.expand_variables, prefs);
+    let row = add_flag(&grid, row, "Refuse to type unknown ${VARIABLES}", "strict_variables", settings.strict_variables, prefs);
+    let row = add_flag(&grid, row, "Skip blank lines by default", "skip_blank_lines", settings.skip_blank_lines, prefs);
+    let row = add_flag(&grid, row, "Strip comment lines by default", "strip_comments", settings.strip_comments, prefs);
+    let row = add_flag(
+        &grid,
+        row,
+        "Fix smart punctuation by default (curly quotes, dashes, ellipses and no-break spaces become ASCII)",
+        "fix_smart_punctuation",
+        settings.fix_smart_punctuation,
+        prefs,
+    );
+    let entry_prefixes = entry_with(&settings.comment_prefixes, Some(DEFAULT_COMMENT_PREFIXES));
+    let prefs_clone = prefs.clone();
+    entry_prefixes.connect_changed(move |entry| {
+        prefs_clone.settings.borrow_mut().set_comment_prefixes(&entry.text());
+        prefs_clone.changed();
+    });
+    let row = add_row(&grid, row, "Comment prefixes:", &entry_prefixes);
+    let row = add_flag(&grid, row, "Ask for {{placeholder}} values before typing", "templates", settings.templates, prefs);
+    let row = add_flag(&grid, row, "Keep the text as a draft between launches", "restore_draft", settings.restore_draft, prefs);
+    let row = add_flag(
+        &grid,
+        row,
+        "Remember recently typed texts (turn off on shared machines)",
+        "remember_recent",
+        settings.remember_recent,
+        prefs,
+    );
+    let row = add_flag(
+        &grid,
+        row,
+        "Ask before typing text that looks like it holds a password, key or token",
+        "secret_scan",
+        settings.secret_scan,
+        prefs,
+    );
+    let row = add_flag(&grid, row, "Show desktop notifications", "notifications", settings.notifications, prefs);
+    let row = add_validated_entry(
+        &grid,
+        row,
+        "Notify before typing (seconds):",
+        entry_with(&settings.notify_before_seconds.to_string(), Some("3")),
+        prefs,
+        |s, v| s.set_notify_before_seconds(v),
+    );
+    let row = add_flag(
+        &grid,
+        row,
+        "Update the countdown only every 5 seconds (fewer screen reader announcements)",
+        "quiet_countdown",
+        settings.quiet_countdown,
+        prefs,
+    );
+    let row = add_flag(
+        &grid,
+        row,
+        "Keep the screen from blanking during the countdown too, not only while typing",
+        "inhibit_idle_countdown",
+        settings.inhibit_idle_countdown,
+        prefs,
+    );
+    let row = add_flag(
+        &grid,
+        row,
+        "Assistive mode: at least 150 ms per character, a large countdown, a confirmation before each line, and a \
+         pause whenever a keyboard or mouse is used",
+        "assistive_mode",
+        settings.assistive_mode,
+        prefs,
+    );
+    let row = add_flag(
+        &grid,
+        row,
+        "Confirm before each Enter: type a line, then wait for a confirmation before the line break",
+        "confirm_enter",
+        settings.confirm_enter,
+        prefs,
+    );
+    let row = add_flag(
+        &grid,
+        row,
+        "Show the countdown in a small window on top of the others",
+        "countdown_overlay",
+        settings.countdown_overlay,
+        prefs,
+    );
+    let labels: Vec<&str> = OverlayCorner::ALL.iter().map(|c| c.label()).collect();
+    let dd_corner = DropDown::from_strings(&labels);
+    dd_corner.set_selected(OverlayCorner::ALL.iter().position(|c| *c == settings.overlay_corner).unwrap_or(0) as u32);
+    let prefs_clone = prefs.clone();
+    dd_corner.connect_selected_notify(move |dd| {
+        let corner = OverlayCorner::ALL.get(dd.selected() as usize).copied().unwrap_or_default();
+        prefs_clone.settings.borrow_mut().set_overlay_corner(corner);
+    });
+    let row = add_row(&grid, row, "Countdown window corner:", &dd_corner);
+    let row = add_flag(&grid, row, "Play a sound when a run finishes or fails", "sounds", settings.sounds, prefs);
+    let btn_test_sound = Button::with_label("Play test sound");
+    btn_test_sound.set_halign(gtk4::Align::Start);
+    btn_test_sound.connect_clicked(|_| sounds::play(Sound::Success));
+    grid.attach(&btn_test_sound, 0, row, 2, 1);
+    let row = row + 1;
+    let btn_discard = Button::with_label("Discard draft");
+    btn_discard.set_halign(gtk4::Align::Start);
+    btn_discard.connect_clicked(|_| discard_draft());
+    grid.attach(&btn_discard, 0, row, 2, 1);
+    grid
+}
+
+fn typing_page(prefs: &Rc<Prefs>, settings: &Settings) -> Grid {
+    let grid = page();
+    let labels: Vec<&str> = std::iter::once("Automatic").chain(Backend::ALL.iter().map(|b| b.name())).collect();
+    let dd_backend = DropDown::from_strings(&labels);
+    let selected = settings.backend.and_then(|b| Backend::ALL.iter().position(|x| *x == b)).map_or(0, |i| i + 1);
+    dd_backend.set_selected(selected as u32);
+    let prefs_clone = prefs.clone();
+    dd_backend.connect_selected_notify(move |dd| {
+        let backend = (dd.selected() as usize).checked_sub(1).and_then(|i| Backend::ALL.get(i).copied());
+        prefs_clone.settings.borrow_mut().set_backend(backend);
+        prefs_clone.changed();
+    });
+    let row = add_row(&grid, 0, "Backend:", &dd_backend);
+    let row = add_validated_entry(
+        &grid,
+        row,
+        "Pause between characters (ms):",
+        entry_with(&settings.char_delay_ms.to_string(), Some("20")),
+        prefs,
+        |s, v| s.set_char_delay_ms(v),
+    );
+    let row = add_validated_entry(
+        &grid,
+        row,
+        "Extra pause after Enter (ms):",
+        entry_with(&settings.newline_delay_ms.to_string(), Some("0")),
+        prefs,
+        |s, v| s.set_newline_delay_ms(v),
+    );
+    let labels: Vec<&str> = EnterKey::ALL.iter().map(|key| key.label()).collect();
+    let dd_enter = DropDown::from_strings(&labels);
+    dd_enter.set_selected(EnterKey::ALL.iter().position(|key| *key == settings.enter_key).unwrap_or(0) as u32);
+    let prefs_clone = prefs.clone();
+    dd_enter.connect_selected_notify(move |dd| {
+        let key = EnterKey::ALL.get(dd.selected() as usize).copied().unwrap_or_default();
+        prefs_clone.settings.borrow_mut().set_enter_key(key);
+        prefs_clone.changed();
+    });
+    let row = add_row(&grid, row, "Type line breaks with:", &dd_enter);
+    let row = add_flag(
+        &grid,
+        row,
+        "Read the text back afterwards: select all and copy in the target, and compare",
+        "verify_readback",
+        settings.verify_readback,
+        prefs,
+    );
+    let row = add_note(
+        &grid,
+        row,
+        "Only for targets that hold nothing but the text, such as an empty field: select-all takes in whatever \
+         else is there, and in a terminal Ctrl+C interrupts the program. Snippets can turn it on or off for \
+         themselves.",
+    );
+    let row = add_flag(
+        &grid,
+        row,
+        "Resume a stopped run from the start of the line it stopped in",
+        "resume_from_line_start",
+        settings.resume_from_line_start,
+        prefs,
+    );
+    let row = add_note(&grid, row, "Run pasteclipboard --list-backends to see which backends work in this session.");
+    let row = add_flag(
+        &grid,
+        row,
+        "Type on another machine instead (one running pasteclipboard --serve)",
+        "remote_typing",
+        settings.remote_typing,
+        prefs,
+    );
+    let row = add_validated_entry(
+        &grid,
+        row,
+        "Remote machine:",
+        entry_with(&settings.remote_host, Some("tls://host:7878")),
+        prefs,
+        |s, v| s.set_remote_host(v),
+    );
+    let entry_token = PasswordEntry::new();
+    entry_token.set_show_peek_icon(true);
+    entry_token.set_text(&settings.remote_token);
+    let prefs_clone = prefs.clone();
+    entry_token.connect_changed(move |entry| prefs_clone.settings.borrow_mut().set_remote_token(&entry.text()));
+    add_row(&grid, row, "Remote token:", &entry_token);
+    grid
+}
+
+fn safety_page(prefs: &Rc<Prefs>, settings: &Settings) -> Grid {
+    let grid = page();
+    let row = add_flag(&grid, 0, "Allow \"Run command…\"", "allow_commands", settings.allow_commands, prefs);
+    let row = add_flag(
+        &grid,
+        row,
+        "Allow text from other machines (MQTT)",
+        "allow_remote_text",
+        settings.allow_remote_text,
+        prefs,
+    );
+    add_note(&grid, row, "Anyone who can publish to the MQTT topic can type on this machine while remote text is allowed.");
+    grid
+}
+
+fn advanced_page(prefs: &Rc<Prefs>, settings: &Settings) -> Grid {
+    let grid = page();
+    let row = add_flag(&grid, 0, "Show an icon in the system tray", "tray_icon", settings.tray_icon, prefs);
+    let row = add_flag(&grid, row, "Watch the drop folder", "watch_enabled", settings.watch_enabled, prefs);
+
+    let entry_dir = entry_with(&settings.watch_directory, Some("~/TypeMe"));
+    let prefs_clone = prefs.clone();
+    entry_dir.connect_changed(move |entry| prefs_clone.settings.borrow_mut().set_watch_directory(&entry.text()));
+    let row = add_row(&grid, row, "Drop folder:", &entry_dir);
+
+    let port = settings.http_port.map(|p| p.to_string()).unwrap_or_default();
+    let row = add_validated_entry(&grid, row, "HTTP port:", entry_with(&port, Some("off")), prefs, |s, v| {
+        s.set_http_port(v)
+    });
+
+    let entry_token = PasswordEntry::new();
+    entry_token.set_show_peek_icon(true);
+    entry_token.set_text(&settings.http_token);
+    let prefs_clone = prefs.clone();
+    entry_token.connect_changed(move |entry| prefs_clone.settings.borrow_mut().set_http_token(&entry.text()));
+    let row = add_row(&grid, row, "HTTP token:", &entry_token);
+
+    let row = add_validated_entry(
+        &grid,
+        row,
+        "MQTT broker:",
+        entry_with(&settings.mqtt_broker, Some("mqtts://host:8883")),
+        prefs,
+        |s, v| s.set_mqtt_broker(v),
+    );
+    add_note(&grid, row, "Changes on this page take effect the next time PasteClipboard starts.");
+    grid
+}
+
+pub fn show(ui: &Rc<MainWindow>) {
+    let settings = Settings::load();
+    let prefs = Rc::new(Prefs { settings: RefCell::new(settings.clone()), ui: Rc::downgrade(ui) });
+
+    let stack = Stack::new();
+    stack.add_titled(&general_page(&prefs, &settings), Some("general"), "General");
+    stack.add_titled(&typing_page(&prefs, &settings), Some("typing"), "Typing");
+    stack.add_titled(&safety_page(&prefs, &settings), Some("safety"), "Safety");
+    stack.add_titled(&advanced_page(&prefs, &settings), Some("advanced"), "Advanced");
+
+    let sidebar = StackSidebar::new();
+    sidebar.set_stack(&stack);
+    let hbox = gtk4::Box::new(Orientation::Horizontal, 0);
+    hbox.append(&sidebar);
+    hbox.append(&stack);
+
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Preferences")
+        .default_width(560)
+        .default_height(360)
+        .child(&hbox)
+        .build();
+    dialog.present();
+}
@@ -0,0 +1,153 @@
+// The profile dropdown in the header bar ("No profile" and the saved
+// profiles) and "Save Settings as Profile…", which asks for a name and
+// saves the settings in effect under it.
+
+use gtk4::prelude::*;
+use gtk4::{gio::SimpleAction, Button, DropDown, Entry, Label, Orientation, StringList, Window};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use pasteclipboard::i18n::{args, gettext};
+use pasteclipboard::profiles::{active_profile, list_profiles, save_profile, set_active_profile};
+
+use crate::window::MainWindow;
+
+pub struct ProfileSwitcher {
+    pub dropdown: DropDown,
+    /// The profiles after "No profile", in dropdown order.
+    names: RefCell<Vec<String>>,
+    /// Set while `refresh` rebuilds the dropdown, whose selection then
+    /// changes without the user switching.
+    updating: Cell<bool>,
+}
+
+impl ProfileSwitcher {
+    pub fn new() -> Self {
+        let dropdown = DropDown::from_strings(&[]);
+        dropdown.set_tooltip_text(Some(&gettext("Settings profile")));
+        ProfileSwitcher { dropdown, names: RefCell::default(), updating: Cell::new(false) }
+    }
+}
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let weak = Rc::downgrade(ui);
+    ui.profiles.dropdown.connect_selected_notify(move |dropdown| {
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        if ui.profiles.updating.get() {
+            return;
+        }
+        let name = (dropdown.selected() as usize).checked_sub(1).and_then(|i| ui.profiles.names.borrow().get(i).cloned());
+        switch(&ui, name);
+    });
+
+    let action = SimpleAction::new("save-profile", None);
+    let weak = Rc::downgrade(ui);
+    action.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            ask_name(&ui);
+        }
+    });
+    ui.window.add_action(&action);
+    refresh(ui);
+}
+
+/// Rebuilds the dropdown from the profiles folder and selects the active
+/// profile.
+pub fn refresh(ui: &MainWindow) {
+    let names = list_profiles();
+    let active = active_profile();
+    let mut labels = vec![gettext("No profile")];
+    labels.extend(names.iter().cloned());
+    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+    let selected = active.and_then(|active| names.iter().position(|name| *name == active)).map_or(0, |i| i + 1);
+
+    let switcher = &ui.profiles;
+    switcher.updating.set(true);
+    switcher.dropdown.set_model(Some(&StringList::new(&labels)));
+    switcher.dropdown.set_selected(selected as u32);
+    switcher.updating.set(false);
+    // Without profiles there is nothing to switch to.
+    switcher.dropdown.set_visible(!names.is_empty());
+    *switcher.names.borrow_mut() = names;
+}
+
+fn switch(ui: &MainWindow, name: Option<String>) {
+    if let Err(e) = set_active_profile(name.as_deref()) {
+        ui.set_error(&format!("{}.", e));
+        refresh(ui);
+        return;
+    }
+    let text = match &name {
+        Some(name) => args(&gettext("Using profile {0}."), &[name]),
+        None => gettext("Using the settings without a profile."),
+    };
+    ui.log.add(&text);
+    ui.settings_changed();
+    ui.set_status(&text);
+}
+
+fn ask_name(ui: &Rc<MainWindow>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title(gettext("Save Settings as Profile"))
+        .default_width(360)
+        .build();
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+    let label = Label::new(Some(&gettext("Profile name:")));
+    label.set_xalign(0.0);
+    vbox.append(&label);
+    let entry = Entry::builder().hexpand(true).activates_default(true).build();
+    entry.set_text(&active_profile().unwrap_or_default());
+    vbox.append(&entry);
+    let note = Label::new(Some(&gettext("A profile of that name is replaced. While it is active, changes to the settings are saved to it.")));
+    note.set_xalign(0.0);
+    note.set_wrap(true);
+    note.add_css_class("dim-label");
+    vbox.append(&note);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label(&gettext("Cancel"));
+    let btn_save = Button::with_label(&gettext("Save"));
+    btn_save.add_css_class("suggested-action");
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_save);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_save));
+
+    let update = {
+        let btn_save = btn_save.clone();
+        move |entry: &Entry| btn_save.set_sensitive(!entry.text().trim().is_empty())
+    };
+    update(&entry);
+    entry.connect_changed(update);
+    entry.grab_focus();
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    let ui = ui.clone();
+    let dialog_clone = dialog.clone();
+    btn_save.connect_clicked(move |_| {
+        let name = entry.text().trim().to_string();
+        dialog_clone.close();
+        match save_profile(&name) {
+            Ok(()) => {
+                refresh(&ui);
+                ui.log.add(&args(&gettext("Saved the settings as profile {0}."), &[&name]));
+                ui.set_status(&args(&gettext("Saved the settings as profile {0}."), &[&name]));
+            }
+            Err(e) => ui.set_error(&format!("{:#}.", e)),
+        }
+    });
+
+    dialog.present();
+}
@@ -0,0 +1,153 @@
+//! Profiles: named sets of settings, such as "Remote desktop" with a slow
+//! speed, keypad Enter and a long delay. Each is an ini file in
+//! ~/.config/PasteClipboard/profiles/ with a `[settings]` section like
+//! config.toml's. While one is active (`active_profile` in config.toml), its
+//! keys take the place of config.toml's and changes are saved to it, so
+//! switching back to no profile restores config.toml's own settings.
+//! Built-in profiles are listed before they are saved; the first time one
+//! is chosen its file is written with the tuned values, to edit from there.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use configparser::ini::Ini;
+
+use crate::settings::{config_dir, load_config, load_ini, load_main_config, write_ini, write_main_config, Settings};
+
+const ACTIVE_KEY: &str = "active_profile";
+
+/// Profiles that come with the application, with their settings. "VM
+/// console" is for virt-manager and GNOME Boxes windows, which drop keys
+/// typed quickly and can turn keypad Enter into something else; characters
+/// that have no key are skipped and marked rather than typed by other means.
+const BUILTIN_PROFILES: [(&str, &[(&str, &str)]); 1] = [(
+    "VM console",
+    &[
+        ("char_delay_ms", "60"),
+        ("newline_delay_ms", "250"),
+        ("enter_key", "enter"),
+        ("delay_seconds", "5"),
+        ("mark_skipped", "true"),
+    ],
+)];
+
+pub fn profiles_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("profiles"))
+}
+
+/// A name that can be a file name: not empty, without slashes, and not
+/// starting with a dot.
+pub fn validate_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("A profile needs a name");
+    }
+    if name.starts_with('.') || name.contains(['/', '\\']) || name.chars().any(char::is_control) {
+        bail!("A profile name cannot start with a dot or contain slashes");
+    }
+    Ok(name)
+}
+
+pub fn profile_path(name: &str) -> Option<PathBuf> {
+    let name = validate_name(name).ok()?;
+    profiles_dir().map(|dir| dir.join(format!("{}.ini", name)))
+}
+
+/// The saved and built-in profiles' names, sorted.
+pub fn list_profiles() -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_PROFILES.iter().map(|(name, _)| name.to_string()).collect();
+    if let Some(entries) = profiles_dir().and_then(|dir| std::fs::read_dir(dir).ok()) {
+        names.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "ini"))
+                .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+                .filter(|name| validate_name(name).is_ok()),
+        );
+    }
+    names.sort_by_key(|name| name.to_lowercase());
+    names.dedup();
+    names
+}
+
+/// Writes built-in profile `name`'s file if it has none yet.
+fn install_builtin(name: &str) -> Result<()> {
+    let Some((_, settings)) = BUILTIN_PROFILES.iter().find(|(builtin, _)| *builtin == name) else {
+        return Ok(());
+    };
+    let Some(path) = profile_path(name).filter(|path| !path.exists()) else {
+        return Ok(());
+    };
+    let mut profile = Ini::new();
+    for (key, value) in settings.iter() {
+        profile.set("settings", key, Some(value.to_string()));
+    }
+    write_ini(&path, &profile)?;
+    Ok(())
+}
+
+/// The active profile, if config.toml names one that exists.
+pub fn active_profile() -> Option<String> {
+    let name = load_main_config().get("settings", ACTIVE_KEY)?.trim().to_string();
+    profile_path(&name).filter(|path| path.exists()).map(|_| name)
+}
+
+/// Makes `name` the active profile, or `None` config.toml's own settings.
+pub fn set_active_profile(name: Option<&str>) -> Result<()> {
+    let mut conf = load_main_config();
+    match name {
+        Some(name) => {
+            install_builtin(name.trim())?;
+            if !profile_path(name).is_some_and(|path| path.exists()) {
+                bail!("There is no profile '{}'", name.trim());
+            }
+            conf.set("settings", ACTIVE_KEY, Some(name.trim().to_string()));
+        }
+        None => {
+            conf.remove_key("settings", ACTIVE_KEY);
+        }
+    }
+    write_main_config(&conf);
+    Ok(())
+}
+
+/// Saves the settings in effect as profile `name`, replacing one of that
+/// name, and makes it the active profile.
+pub fn save_profile(name: &str) -> Result<()> {
+    let name = validate_name(name)?;
+    let Some(path) = profile_path(name) else {
+        bail!("No home directory to save profiles in");
+    };
+    let current = load_config();
+    // Known keys are saved as they are read; others, kept for a newer
+    // version, as written.
+    let typed = Settings::from_config(&current).entries();
+    let mut profile = Ini::new();
+    for (key, value) in current.get_map_ref().get("settings").into_iter().flatten() {
+        if key != ACTIVE_KEY {
+            match typed.iter().find(|(known, _)| known == key) {
+                Some((_, typed)) => profile.set("settings", key, Some(typed.clone())),
+                None => profile.set("settings", key, value.clone()),
+            };
+        }
+    }
+    write_ini(&path, &profile)?;
+    set_active_profile(Some(name))
+}
+
+/// Puts the active profile's keys in `conf`'s `[settings]`.
+pub(crate) fn apply_active(conf: &mut Ini) {
+    let Some(name) = conf.get("settings", ACTIVE_KEY) else {
+        return;
+    };
+    let Some(path) = profile_path(&name).filter(|path| path.exists()) else {
+        return;
+    };
+    let profile = load_ini(&path);
+    for (key, value) in profile.get_map_ref().get("settings").into_iter().flatten() {
+        if key != ACTIVE_KEY {
+            conf.set("settings", key, value.clone());
+        }
+    }
+}
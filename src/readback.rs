@@ -0,0 +1,213 @@
+//! Reading a run back: once the last key is typed, the worker presses
+//! select-all and copy in the target (Ctrl+A and Ctrl+C unless
+//! `verify_select_chord` and `verify_copy_chord` say otherwise), and the
+//! window compares what the copy put on the clipboard with the text sent.
+//! It only makes sense for a target that holds nothing but what was typed,
+//! such as an empty text field, so it is off unless `verify_readback` or a
+//! snippet turns it on.
+
+use std::fmt;
+
+use evdev_rs::enums::EV_KEY;
+
+use crate::keymap::char_to_key_event;
+use crate::self_test::{compare, difference_text, Difference};
+
+pub const DEFAULT_SELECT_CHORD: &str = "ctrl+a";
+pub const DEFAULT_COPY_CHORD: &str = "ctrl+c";
+
+/// Modifiers a chord may hold, by the names it is written with, with the
+/// name xdo knows each by.
+const MODIFIERS: [(&str, EV_KEY, &str); 6] = [
+    ("ctrl", EV_KEY::KEY_LEFTCTRL, "ctrl"),
+    ("control", EV_KEY::KEY_LEFTCTRL, "ctrl"),
+    ("shift", EV_KEY::KEY_LEFTSHIFT, "shift"),
+    ("alt", EV_KEY::KEY_LEFTALT, "alt"),
+    ("super", EV_KEY::KEY_LEFTMETA, "super"),
+    ("meta", EV_KEY::KEY_LEFTMETA, "super"),
+];
+
+/// Keys with a name rather than a character, such as the Insert of the
+/// Ctrl+Insert some terminals copy with, and their X keysyms.
+const NAMED_KEYS: [(&str, EV_KEY, &str); 3] =
+    [("insert", EV_KEY::KEY_INSERT, "Insert"), ("tab", EV_KEY::KEY_TAB, "Tab"), ("space", EV_KEY::KEY_SPACE, "space")];
+
+/// A shortcut such as Ctrl+A: modifiers held while one key is pressed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chord {
+    pub modifiers: Vec<EV_KEY>,
+    pub key: EV_KEY,
+    /// How xdo names it, e.g. `ctrl+a`.
+    keysequence: String,
+}
+
+impl Chord {
+    /// Reads a chord written as modifiers and a key joined by `+`, e.g.
+    /// `ctrl+shift+c`: ctrl, shift, alt and super, then a letter, a digit,
+    /// insert, tab or space, in any case.
+    pub fn parse(text: &str) -> Result<Chord, String> {
+        let parts: Vec<String> = text.split('+').map(|part| part.trim().to_ascii_lowercase()).collect();
+        let Some((key_name, modifier_names)) = parts.split_last() else {
+            return Err("it is empty".to_string());
+        };
+        let mut modifiers = Vec::new();
+        let mut names = Vec::new();
+        for name in modifier_names {
+            let Some((_, key, xdo_name)) = MODIFIERS.iter().find(|(known, _, _)| known == name) else {
+                return Err(format!("'{}' is not ctrl, shift, alt or super", name));
+            };
+            if !modifiers.contains(key) {
+                modifiers.push(*key);
+                names.push(xdo_name.to_string());
+            }
+        }
+        let mut chars = key_name.chars();
+        let (key, keysym) = match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphanumeric() => (char_to_key_event(c).0, c.to_string()),
+            _ => match NAMED_KEYS.iter().find(|(name, _, _)| name == key_name) {
+                Some((_, key, keysym)) => (*key, keysym.to_string()),
+                None => return Err(format!("'{}' is not a letter, a digit, insert, tab or space", key_name)),
+            },
+        };
+        names.push(keysym);
+        Ok(Chord { modifiers, key, keysequence: names.join("+") })
+    }
+
+    /// How xdo's key sequences name the chord.
+    pub fn keysequence(&self) -> &str {
+        &self.keysequence
+    }
+
+    /// Every key the chord presses.
+    pub fn keys(&self) -> impl Iterator<Item = EV_KEY> + '_ {
+        self.modifiers.iter().copied().chain([self.key])
+    }
+}
+
+impl fmt::Display for Chord {
+    /// E.g. "Ctrl+A", for the log.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self
+            .keysequence
+            .split('+')
+            .map(|name| {
+                let mut chars = name.chars();
+                chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+            })
+            .collect();
+        f.write_str(&names.join("+"))
+    }
+}
+
+/// The chords a run that is read back ends with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Readback {
+    pub select_all: Chord,
+    pub copy: Chord,
+}
+
+impl Default for Readback {
+    fn default() -> Self {
+        Readback {
+            select_all: Chord::parse(DEFAULT_SELECT_CHORD).expect("the default chord is valid"),
+            copy: Chord::parse(DEFAULT_COPY_CHORD).expect("the default chord is valid"),
+        }
+    }
+}
+
+impl Readback {
+    /// Every key the two chords press.
+    pub fn keys(&self) -> impl Iterator<Item = EV_KEY> + '_ {
+        self.select_all.keys().chain(self.copy.keys())
+    }
+}
+
+/// `text` with its CRLF and CR line endings made LF, as they are typed.
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Past this many characters between the first and the last difference,
+/// a mismatch only says where it begins; lining up more would take too
+/// long and too much memory.
+const MAX_COMPARED_CHARS: usize = 2000;
+
+/// At most this many differences are listed.
+const LISTED: usize = 5;
+
+/// How a text read back differs from the text sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub sent: usize,
+    pub received: usize,
+    /// Line and column, from 1, in the text sent, of the first difference.
+    pub first: (usize, usize),
+    /// The differences, when the part that differs was short enough to
+    /// line up.
+    pub differences: Option<Vec<Difference>>,
+}
+
+/// Compares what was sent with what a run read back, both with their line
+/// endings normalized; `None` if they are the same.
+pub fn check(sent: &str, copied: &str) -> Option<Mismatch> {
+    let (sent, copied) = (normalize_line_endings(sent), normalize_line_endings(copied));
+    if sent == copied {
+        return None;
+    }
+    let a: Vec<char> = sent.chars().collect();
+    let b: Vec<char> = copied.chars().collect();
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (middle_sent, middle_copied) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let differences = (middle_sent.len().max(middle_copied.len()) <= MAX_COMPARED_CHARS).then(|| {
+        let middle_sent: String = middle_sent.iter().collect();
+        let middle_copied: String = middle_copied.iter().collect();
+        compare(&middle_sent, &middle_copied).differences.into_iter().map(|d| shifted(d, prefix)).collect()
+    });
+    let before = &a[..prefix];
+    let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+    let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+    Some(Mismatch { sent: a.len(), received: b.len(), first: (line, column), differences })
+}
+
+/// `difference` with its offset moved on by `by` characters.
+fn shifted(difference: Difference, by: usize) -> Difference {
+    match difference {
+        Difference::Missing { at, expected } => Difference::Missing { at: at + by, expected },
+        Difference::Wrong { at, expected, got } => Difference::Wrong { at: at + by, expected, got },
+        Difference::Transposed { at, first, second } => Difference::Transposed { at: at + by, first, second },
+        Difference::Extra { at, got } => Difference::Extra { at: at + by, got },
+    }
+}
+
+/// The log's account of `mismatch`, a line for the counts and where the
+/// text first differs, then one for each of the first differences. A
+/// secret run's only gets the first line, which names no characters.
+pub fn mismatch_text(sent: &str, mismatch: &Mismatch, secret: bool) -> String {
+    let (line, column) = mismatch.first;
+    let mut out = format!(
+        "The text read back differs from the text sent: {} characters for the {} sent, first at line {}, column {}",
+        mismatch.received, mismatch.sent, line, column
+    );
+    match &mismatch.differences {
+        Some(differences) => out.push_str(&format!(
+            "; {} difference{}.",
+            differences.len(),
+            if differences.len() == 1 { "" } else { "s" }
+        )),
+        None => out.push('.'),
+    }
+    if secret {
+        return out;
+    }
+    let sent = normalize_line_endings(sent);
+    let differences = mismatch.differences.as_deref().unwrap_or_default();
+    for difference in differences.iter().take(LISTED) {
+        out.push('\n');
+        out.push_str(&difference_text(&sent, difference));
+    }
+    if differences.len() > LISTED {
+        out.push_str(&format!("\n…and {} more.", differences.len() - LISTED));
+    }
+    out
+}
@@ -0,0 +1,88 @@
+// The window's half of reading a run back (see `readback`): it keeps the
+// text the clipboard held when the run started, reads what the run's copy
+// chord put there once it is over, compares that with the text sent and
+// puts the earlier text back. The log gets how they differ, and for a
+// secret run only the counts.
+
+use gtk4::prelude::*;
+use gtk4::glib;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use pasteclipboard::i18n::gettext;
+use pasteclipboard::readback::{check, mismatch_text};
+use zeroize::Zeroizing;
+
+use crate::window::MainWindow;
+
+/// Time for the window to take the focus back before reading: Wayland only
+/// tells the focused window what the clipboard holds.
+const FOCUS_WAIT: Duration = Duration::from_millis(300);
+
+/// The clipboard's text from before a run, once it has been read; `None`
+/// inside when it held none.
+pub struct ReadbackCheck {
+    before: Rc<RefCell<Option<Option<Zeroizing<String>>>>>,
+}
+
+impl ReadbackCheck {
+    /// Reads the clipboard as it is now, before the run's copy replaces it.
+    pub fn start(ui: &MainWindow) -> ReadbackCheck {
+        let before: Rc<RefCell<Option<Option<Zeroizing<String>>>>> = Rc::default();
+        let before_clone = before.clone();
+        let clipboard = ui.window.clipboard();
+        glib::MainContext::default().spawn_local(async move {
+            let text = clipboard.read_text_future().await.ok().flatten().map(|text| Zeroizing::new(text.to_string()));
+            before_clone.replace(Some(text));
+        });
+        ReadbackCheck { before }
+    }
+
+    /// Compares what the run copied with `sent`, which it typed, and puts
+    /// the clipboard back.
+    pub fn finish(self, ui: &Rc<MainWindow>, sent: Arc<Zeroizing<String>>, secret: bool) {
+        let ui = ui.clone();
+        glib::MainContext::default().spawn_local(async move {
+            // A window that is hidden (background mode) stays hidden.
+            if ui.window.is_visible() {
+                ui.window.present();
+            }
+            glib::timeout_future(FOCUS_WAIT).await;
+            let clipboard = ui.window.clipboard();
+            let copied = clipboard.read_text_future().await.ok().flatten().map(|text| Zeroizing::new(text.to_string()));
+            let before = self.before.borrow_mut().take().flatten();
+            match &before {
+                Some(text) => clipboard.set_text(text),
+                None => {
+                    // What the copy left there may be a secret.
+                    clipboard.set_text("");
+                    ui.log.add("The clipboard held no text before the run; it is left empty rather than with the copy.");
+                }
+            }
+
+            let copied = match copied {
+                Some(copied) if before.as_ref() != Some(&copied) || *copied == **sent => copied,
+                _ => {
+                    ui.set_warning(&gettext("Not checked: nothing was copied back from the target."));
+                    ui.log.add(
+                        "Reading the text back failed: the clipboard did not change, so the target did not take \
+                         select-all or copy, or they are other shortcuts there (verify_select_chord, \
+                         verify_copy_chord).",
+                    );
+                    return;
+                }
+            };
+            match check(&sent, &copied) {
+                None => {
+                    ui.set_status(&gettext("✓ Done typing, and the text read back matches."));
+                    ui.log.add("Read back: the text arrived as it was sent.");
+                }
+                Some(mismatch) => {
+                    ui.set_warning(&gettext("The text read back differs from what was typed; see the log."));
+                    ui.log.add(&mismatch_text(&sent, &mismatch, secret));
+                }
+            }
+        });
+    }
+}
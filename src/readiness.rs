@@ -0,0 +1,111 @@
+// The readiness indicator next to the delay: whether /dev/uinput can be
+// written, checked at startup, after the udev rule is installed and when
+// clicked, so a permission problem shows before the text is typed rather
+// than after the countdown. It stays hidden when another backend is set.
+
+use gtk4::prelude::*;
+use gtk4::{
+    glib::{timeout_add_local, ControlFlow},
+    Button,
+};
+use std::cell::Cell;
+use std::io::ErrorKind;
+use std::rc::Rc;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use pasteclipboard::backend::Backend;
+use pasteclipboard::i18n::{args, gettext};
+use pasteclipboard::settings::load_backend_setting;
+use pasteclipboard::uinput_access::{test_access, UINPUT_PATH};
+
+use crate::diagnostics_dialog;
+use crate::permission_dialog;
+use crate::window::MainWindow;
+
+#[derive(Clone)]
+pub struct Readiness {
+    pub button: Button,
+    /// The kind of error the last check found, or `None` once it passed.
+    problem: Rc<Cell<Option<ErrorKind>>>,
+    checking: Rc<Cell<bool>>,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        let button = Button::builder().label(gettext("Checking uinput…")).visible(false).build();
+        button.add_css_class("flat");
+        button.add_css_class("readiness");
+        Readiness { button, problem: Rc::default(), checking: Rc::default() }
+    }
+
+    /// Opens /dev/uinput on a worker thread and shows the outcome. Does
+    /// nothing while a check is running.
+    pub fn check(&self) {
+        let uses_uinput = !matches!(load_backend_setting(), Ok(Some(backend)) if backend != Backend::Uinput);
+        self.button.set_visible(uses_uinput);
+        if !uses_uinput || self.checking.replace(true) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(test_access());
+        });
+        let readiness = self.clone();
+        timeout_add_local(Duration::from_millis(50), move || {
+            let result = match receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return ControlFlow::Continue,
+                Err(TryRecvError::Disconnected) => Err(std::io::Error::other(gettext("the check stopped unexpectedly"))),
+            };
+            readiness.show(result);
+            ControlFlow::Break
+        });
+    }
+
+    fn show(&self, result: std::io::Result<()>) {
+        self.checking.set(false);
+        let button = &self.button;
+        match result {
+            Ok(()) => {
+                self.problem.set(None);
+                button.set_label(&gettext("uinput ready"));
+                button.set_tooltip_text(Some(&args(&gettext("{0} is writable. Click to check again."), &[&UINPUT_PATH])));
+                button.remove_css_class("not-ready");
+                button.add_css_class("ready");
+            }
+            Err(e) => {
+                self.problem.set(Some(e.kind()));
+                let (label, reason) = match e.kind() {
+                    ErrorKind::PermissionDenied => {
+                        (gettext("No uinput permission"), args(&gettext("This user cannot write to {0}."), &[&UINPUT_PATH]))
+                    }
+                    ErrorKind::NotFound => (
+                        gettext("uinput missing"),
+                        args(&gettext("{0} does not exist; the 'uinput' kernel module is probably not loaded."), &[&UINPUT_PATH]),
+                    ),
+                    _ => (gettext("uinput not ready"), args(&gettext("Cannot open {0}: {1}."), &[&UINPUT_PATH, &e])),
+                };
+                button.set_label(&label);
+                button.set_tooltip_text(Some(&format!("{} {}", reason, gettext("Click for how to fix it."))));
+                button.remove_css_class("ready");
+                button.add_css_class("not-ready");
+            }
+        }
+    }
+}
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let weak = Rc::downgrade(ui);
+    ui.readiness.button.connect_clicked(move |_| {
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        match ui.readiness.problem.get() {
+            Some(ErrorKind::PermissionDenied) => permission_dialog::show(&ui),
+            Some(_) => diagnostics_dialog::show(&ui, false),
+            None => ui.readiness.check(),
+        }
+    });
+    ui.readiness.check();
+}
@@ -0,0 +1,63 @@
+// The "Recent" submenu: the last texts typed to the end, loaded back into
+// the text area when picked, and "Clear Recent".
+
+use gtk4::prelude::*;
+use gtk4::{gio, glib};
+use std::rc::Rc;
+use pasteclipboard::i18n::gettext;
+use pasteclipboard::recent_texts::{clear_recent_texts, enabled, load_recent_texts, preview};
+
+use crate::window::MainWindow;
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let load = gio::SimpleAction::new("load-recent", Some(glib::VariantTy::UINT32));
+    let weak = Rc::downgrade(ui);
+    load.connect_activate(move |_, index| {
+        let (Some(ui), Some(index)) = (weak.upgrade(), index.and_then(|i| i.get::<u32>())) else {
+            return;
+        };
+        if let Some(text) = load_recent_texts().get(index as usize) {
+            ui.replace_text(text);
+        }
+    });
+    ui.window.add_action(&load);
+
+    let clear = gio::SimpleAction::new("clear-recent", None);
+    let weak = Rc::downgrade(ui);
+    clear.connect_activate(move |_, _| {
+        clear_recent_texts();
+        if let Some(ui) = weak.upgrade() {
+            refresh(&ui);
+        }
+    });
+    ui.window.add_action(&clear);
+    refresh(ui);
+}
+
+/// Rebuilds the submenu after a run or a change of settings.
+pub fn refresh(ui: &MainWindow) {
+    let section = &ui.recent_section;
+    section.remove_all();
+    if !enabled() {
+        // Switching the feature off also forgets what it kept.
+        clear_recent_texts();
+        return;
+    }
+    let recent = load_recent_texts();
+    let entries = gio::Menu::new();
+    for (index, text) in recent.iter().enumerate() {
+        // Menu labels treat "_" as a mnemonic.
+        let item = gio::MenuItem::new(Some(&preview(text).replace('_', "__")), None);
+        item.set_action_and_target_value(Some("win.load-recent"), Some(&(index as u32).to_variant()));
+        entries.append_item(&item);
+    }
+    let commands = gio::Menu::new();
+    commands.append(Some(&gettext("Clear Recent")), Some("win.clear-recent"));
+    let submenu = gio::Menu::new();
+    submenu.append_section(None, &entries);
+    submenu.append_section(None, &commands);
+    section.append_submenu(Some(&gettext("Recent")), &submenu);
+    if let Some(action) = ui.window.lookup_action("clear-recent").and_downcast::<gio::SimpleAction>() {
+        action.set_enabled(!recent.is_empty());
+    }
+}
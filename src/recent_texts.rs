@@ -0,0 +1,70 @@
+//! The last texts that were typed to the end, for the window's "Recent"
+//! menu. They are kept newest first in `recent_texts.json` in the XDG state
+//! directory; secret runs never get here, and `remember_recent = false`
+//! turns the whole feature off.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::settings::{state_path, Settings};
+
+pub const RECENT_LENGTH: usize = 10;
+
+/// Longer texts are not remembered, which keeps the file small; a snippet
+/// suits them better anyway.
+pub const MAX_RECENT_TEXT_BYTES: usize = 64 * 1024;
+
+const PREVIEW_CHARS: usize = 40;
+
+fn recent_path() -> Option<PathBuf> {
+    state_path("recent_texts.json")
+}
+
+pub fn enabled() -> bool {
+    Settings::load().remember_recent
+}
+
+/// The remembered texts, newest first.
+pub fn load_recent_texts() -> Vec<String> {
+    recent_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Puts `text` in front of the recent texts, unless it is already the
+/// newest one, is empty or too long, or the feature is off.
+pub fn remember_text(text: &str) {
+    if !enabled() || text.trim().is_empty() || text.len() > MAX_RECENT_TEXT_BYTES {
+        return;
+    }
+    let Some(path) = recent_path() else {
+        return;
+    };
+    let mut recent = load_recent_texts();
+    if recent.first().map(String::as_str) == Some(text) {
+        return;
+    }
+    recent.insert(0, text.to_string());
+    recent.truncate(RECENT_LENGTH);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string(&recent) {
+        let _ = fs::write(path, json);
+    }
+}
+
+pub fn clear_recent_texts() {
+    if let Some(path) = recent_path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// The start of the first non-blank line, with "…" when there is more.
+pub fn preview(text: &str) -> String {
+    let first = text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    let preview: String = first.chars().take(PREVIEW_CHARS).collect();
+    let more = preview.len() < first.len() || text.trim().contains('\n');
+    format!("{}{}", preview, if more { "…" } else { "" })
+}
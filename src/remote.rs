@@ -0,0 +1,1089 @@
+//! Remote typing, for when the text is on one machine and the keyboard that
+//! can type into the target on another. `pasteclipboard --serve ADDR --token
+//! FILE` types what other instances send it; with `remote_typing` on, an
+//! instance sends its runs to `remote_host` instead of typing them itself
+//! and reports the remote run's events as its own.
+//!
+//! Each message is JSON led by its length in four bytes (big-endian), over
+//! TLS with `--tls-cert` and `--tls-key`, or plain TCP on a loopback
+//! address, as at the end of an SSH tunnel:
+//!
+//!   server  {"type": "hello", "version": 1, "nonce": "<64 hex digits>"}
+//!   client  {"type": "auth", "mac": "<HMAC-SHA256 of the nonce keyed with the token, in hex>"}
+//!   server  {"type": "ready"}
+//!   client  {"type": "run", "text": "...", "delay_ms": 3000, "char_delay_ms": 20, ...}
+//!   server  {"type": "accepted"}, then the run's events ("started", "progress",
+//!           "skipped", "notice") up to "finished" or "failed"
+//!   client  {"type": "cancel"}, at any time during the run
+//!
+//! The token itself never crosses the network, and the server checks the
+//! MAC in constant time. Only the challenge is signed, so the messages
+//! after it are protected by TLS or not at all: neither side uses plain TCP
+//! beyond this machine. The handshake, up to the run, must be over within
+//! [`REMOTE_TIMEOUT`], and at most [`MAX_PENDING_SESSIONS`] connections may
+//! be in it at once; more are closed straight away. Instead of "ready" or "accepted" the server may
+//! answer {"type": "refused", "message": "..."} and close the connection:
+//! for a wrong token, a run larger than [`MAX_REMOTE_TEXT_BYTES`] or while
+//! it is typing another run. A client that goes away cancels its run.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use flume::Sender;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::settings::{load_typing_options, Settings};
+use crate::typing::{
+    simulate_typing, EnterKey, RunStats, TypingError, TypingEvent, TypingOptions, MAX_CHAR_DELAY_MS, MAX_DELAY_SECONDS,
+    MAX_REMOTE_TEXT_BYTES,
+};
+
+/// The port `--serve` and `remote_host` use when none is given.
+pub const DEFAULT_REMOTE_PORT: u16 = 7878;
+
+/// How long connecting, the handshake and sending a run may take.
+pub const REMOTE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How many connections `--serve` lets prove they know the token at once.
+pub const MAX_PENDING_SESSIONS: usize = 8;
+
+/// Shortest token `--serve` accepts.
+pub const MIN_TOKEN_LENGTH: usize = 16;
+
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Largest message other than a run.
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Largest run message: a text at the limit with every character escaped
+/// in two bytes, as line breaks and quotes are, and the options.
+const MAX_RUN_BYTES: usize = 2 * MAX_REMOTE_TEXT_BYTES + MAX_MESSAGE_BYTES;
+
+/// How long either side waits for a message before looking at the run
+/// again.
+const POLL: Duration = Duration::from_millis(50);
+
+/// A `remote_host`: `host`, `host:port` or `[v6]:port`, led by `tls://`
+/// for TLS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteAddress {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl RemoteAddress {
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (tls, rest) = match text.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("tls") => (true, rest),
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("tcp") => (false, rest),
+            Some((scheme, _)) => bail!("Unsupported scheme '{}' (use tls:// or none)", scheme),
+            None => (false, text),
+        };
+        let (host, port) = match rest.strip_prefix('[') {
+            Some(v6) => match v6.split_once(']') {
+                Some((host, "")) => (host, None),
+                Some((host, port)) => (host, Some(port.strip_prefix(':').unwrap_or(port))),
+                None => bail!("Missing ] after the IPv6 address"),
+            },
+            None if rest.matches(':').count() > 1 => bail!("Put an IPv6 address in brackets, like [::1]:7878"),
+            None => match rest.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (rest, None),
+            },
+        };
+        if host.is_empty() || host.contains(['/', '@']) || host.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            bail!("Expected a host name or address, optionally with :port");
+        }
+        let port = match port {
+            None => DEFAULT_REMOTE_PORT,
+            Some(port) => match port.parse() {
+                Ok(port) if port > 0 => port,
+                _ => bail!("Invalid port '{}'", port),
+            },
+        };
+        Ok(RemoteAddress { host: host.to_string(), port, tls })
+    }
+}
+
+impl fmt::Display for RemoteAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls { "tls://" } else { "" };
+        if self.host.contains(':') {
+            write!(f, "{}[{}]:{}", scheme, self.host, self.port)
+        } else {
+            write!(f, "{}{}:{}", scheme, self.host, self.port)
+        }
+    }
+}
+
+/// A run's counts as they cross the network, named as in `--json` output.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+struct WireStats {
+    typed: usize,
+    skipped: usize,
+    duration_seconds: f64,
+    average_interval_ms: f64,
+}
+
+impl From<&RunStats> for WireStats {
+    fn from(stats: &RunStats) -> Self {
+        WireStats {
+            typed: stats.typed,
+            skipped: stats.skipped,
+            duration_seconds: stats.duration.as_secs_f64(),
+            average_interval_ms: stats.average_interval.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+impl From<WireStats> for RunStats {
+    fn from(stats: WireStats) -> Self {
+        // The other side's numbers are not trusted to be valid durations.
+        let seconds = |seconds: f64| Duration::try_from_secs_f64(seconds).unwrap_or_default();
+        RunStats {
+            typed: stats.typed,
+            skipped: stats.skipped,
+            duration: seconds(stats.duration_seconds),
+            average_interval: seconds(stats.average_interval_ms / 1000.0),
+        }
+    }
+}
+
+/// What to type and how; omitted options are the server's own.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunOrder {
+    text: String,
+    /// The countdown left on the client, so both end together.
+    delay_ms: u64,
+    char_delay_ms: Option<u32>,
+    newline_delay_ms: Option<u32>,
+    enter_key: Option<String>,
+    /// Auto-type `{DELAY}` waits, as (position in chars, milliseconds).
+    #[serde(default)]
+    pauses: Vec<(usize, u64)>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Auth { mac: String },
+    Run(RunOrder),
+    Cancel,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Hello { version: u32, nonce: String },
+    Ready,
+    Accepted,
+    Refused { message: String },
+    Started { total_chars: usize },
+    Progress { typed: usize, total: usize },
+    Skipped { count: usize },
+    Notice { message: String },
+    Finished { stats: WireStats },
+    Failed { message: String, cancelled: bool, stats: WireStats },
+}
+
+impl From<&TypingEvent> for ServerMessage {
+    fn from(event: &TypingEvent) -> Self {
+        match event {
+            TypingEvent::Started { total_chars } => ServerMessage::Started { total_chars: *total_chars },
+            TypingEvent::Progress { typed, total } => ServerMessage::Progress { typed: *typed, total: *total },
+            TypingEvent::Skipped { count } => ServerMessage::Skipped { count: *count },
+            TypingEvent::Notice { message } => ServerMessage::Notice { message: message.clone() },
+            TypingEvent::AwaitingConfirmation { wait } => ServerMessage::Notice { message: wait.to_string() },
+            TypingEvent::Finished { stats } => ServerMessage::Finished { stats: stats.into() },
+            TypingEvent::Failed { error, stats } => ServerMessage::Failed {
+                message: error.to_string(),
+                cancelled: error.is_cancelled(),
+                stats: stats.into(),
+            },
+        }
+    }
+}
+
+/// A connection, plain or over TLS as either side.
+enum Link {
+    Plain(TcpStream),
+    Client(Box<StreamOwned<ClientConnection, TcpStream>>),
+    Server(Box<StreamOwned<ServerConnection, TcpStream>>),
+}
+
+fn timed_out(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+/// Makes reads give up after [`POLL`], so the caller can look at the run
+/// between messages, and writes after [`REMOTE_TIMEOUT`].
+fn configure(socket: TcpStream) -> io::Result<TcpStream> {
+    socket.set_read_timeout(Some(POLL))?;
+    socket.set_write_timeout(Some(REMOTE_TIMEOUT))?;
+    socket.set_nodelay(true)?;
+    Ok(socket)
+}
+
+impl Link {
+    /// Completes the TLS handshake, if there is one, by `deadline`.
+    fn handshake(&mut self, deadline: Instant) -> io::Result<()> {
+        loop {
+            let result = match self {
+                Link::Plain(_) => return Ok(()),
+                Link::Client(stream) if stream.conn.is_handshaking() => stream.conn.complete_io(&mut stream.sock),
+                Link::Server(stream) if stream.conn.is_handshaking() => stream.conn.complete_io(&mut stream.sock),
+                _ => return Ok(()),
+            };
+            match result {
+                Err(e) if timed_out(&e) && Instant::now() < deadline => {}
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            }
+        }
+    }
+
+    fn send(&mut self, message: &impl Serialize) -> io::Result<()> {
+        let body = Zeroizing::new(serde_json::to_vec(message).map_err(io::Error::other)?);
+        self.send_bytes(&body)
+    }
+
+    /// Sends a message body, led by its length in one write.
+    fn send_bytes(&mut self, body: &[u8]) -> io::Result<()> {
+        let mut frame = Zeroizing::new(Vec::with_capacity(4 + body.len()));
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(body);
+        self.write_all(&frame)?;
+        self.flush()
+    }
+}
+
+impl Read for Link {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Link::Plain(socket) => socket.read(buf),
+            Link::Client(stream) => stream.read(buf),
+            Link::Server(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Link {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Link::Plain(socket) => socket.write(buf),
+            Link::Client(stream) => stream.write(buf),
+            Link::Server(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Link::Plain(socket) => socket.flush(),
+            Link::Client(stream) => stream.flush(),
+            Link::Server(stream) => stream.flush(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum LinkError {
+    Closed,
+    /// A message announced as this many bytes, more than allowed.
+    TooLarge(usize),
+    TimedOut,
+    Io(io::Error),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Closed => f.write_str("the connection was closed"),
+            LinkError::TooLarge(len) => write!(f, "a message of {} bytes is larger than allowed", len),
+            LinkError::TimedOut => write!(f, "no answer within {} seconds", REMOTE_TIMEOUT.as_secs()),
+            LinkError::Io(e) => match e.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+                Some(tls) => write!(f, "TLS error: {}", tls),
+                None => e.fmt(f),
+            },
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Collects messages from a link whose reads time out.
+#[derive(Default)]
+struct Inbox {
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl Inbox {
+    /// The next message if it has arrived by the time a read times out.
+    /// It reads once at most, so a peer sending a byte at a time cannot
+    /// keep it from returning.
+    fn poll(&mut self, link: &mut Link, max: usize) -> Result<Option<Zeroizing<Vec<u8>>>, LinkError> {
+        let mut read = false;
+        loop {
+            if let Some(header) = self.buffer.get(..4) {
+                let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+                if len > max {
+                    return Err(LinkError::TooLarge(len));
+                }
+                if self.buffer.len() >= 4 + len {
+                    let message = Zeroizing::new(self.buffer[4..4 + len].to_vec());
+                    self.buffer.drain(..4 + len);
+                    return Ok(Some(message));
+                }
+                // Grown once, so no copy of a text is left behind in
+                // memory that was given back.
+                let missing = 4 + len - self.buffer.len();
+                self.buffer.reserve(missing);
+            }
+            if read {
+                return Ok(None);
+            }
+            read = true;
+            let mut chunk = Zeroizing::new([0u8; 16 * 1024]);
+            match link.read(&mut chunk[..]) {
+                Ok(0) => return Err(LinkError::Closed),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if timed_out(&e) => return Ok(None),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(LinkError::Closed),
+                Err(e) => return Err(LinkError::Io(e)),
+            }
+        }
+    }
+
+    /// The next message, waiting for it until `deadline`.
+    fn receive(&mut self, link: &mut Link, max: usize, deadline: Instant) -> Result<Zeroizing<Vec<u8>>, LinkError> {
+        loop {
+            if let Some(message) = self.poll(link, max)? {
+                return Ok(message);
+            }
+            if Instant::now() >= deadline {
+                return Err(LinkError::TimedOut);
+            }
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|at| u8::from_str_radix(&text[at..at + 2], 16).ok()).collect()
+}
+
+fn token_key(token: &str) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes())
+}
+
+/// Reads `--token`'s file: its whole content, without surrounding
+/// whitespace, of at least [`MIN_TOKEN_LENGTH`] characters.
+pub fn load_token_file(path: &Path) -> Result<Zeroizing<String>> {
+    let token = Zeroizing::new(
+        std::fs::read_to_string(path).with_context(|| format!("Cannot read the token file {}", path.display()))?,
+    );
+    let token = Zeroizing::new(token.trim().to_string());
+    if token.chars().count() < MIN_TOKEN_LENGTH {
+        bail!("The token in {} is shorter than {} characters", path.display(), MIN_TOKEN_LENGTH);
+    }
+    Ok(token)
+}
+
+/// TLS for `--serve` with a PEM certificate chain and private key.
+pub fn server_tls(cert: &Path, key: &Path) -> Result<Arc<ServerConfig>> {
+    let open = |path: &Path| {
+        File::open(path).map(BufReader::new).with_context(|| format!("Cannot read {}", path.display()))
+    };
+    let certs = rustls_pemfile::certs(&mut open(cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Cannot read the certificates in {}", cert.display()))?;
+    if certs.is_empty() {
+        bail!("{} has no PEM certificate", cert.display());
+    }
+    let private_key = rustls_pemfile::private_key(&mut open(key)?)
+        .with_context(|| format!("Cannot read the private key in {}", key.display()))?
+        .ok_or_else(|| anyhow!("{} has no PEM private key", key.display()))?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .map_err(|e| anyhow!("Cannot use {} with {}: {}", cert.display(), key.display(), e))?;
+    Ok(Arc::new(config))
+}
+
+/// What every connection to a server shares.
+struct Served {
+    key: hmac::Key,
+    tls: Option<Arc<ServerConfig>>,
+    /// Set while a run is typing; there is one at a time.
+    busy: AtomicBool,
+    /// The connections that have not proved they know the token yet.
+    pending: AtomicUsize,
+    report: Box<dyn Fn(String) + Send + Sync>,
+}
+
+/// Clears [`Served::busy`] when the run it was set for ends.
+struct BusyGuard<'a>(&'a AtomicBool);
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Counts a connection in [`Served::pending`] until it is released or
+/// dropped.
+struct PendingGuard<'a>(Option<&'a AtomicUsize>);
+
+impl PendingGuard<'_> {
+    fn release(&mut self) {
+        if let Some(pending) = self.0.take() {
+            pending.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Whether runs may reach `address` over plain TCP: only on this machine.
+fn plain_allowed(address: &SocketAddr) -> bool {
+    address.ip().is_loopback()
+}
+
+/// Listens on `address` and types the runs authorized with `token`, one
+/// at a time, until the process ends. Without `tls` it only listens on a
+/// loopback address. Typing uses this machine's settings
+/// for everything a run does not give, such as the backend. `report` hears
+/// about connections and runs, never their text.
+pub fn serve(
+    address: &str,
+    token: &str,
+    tls: Option<Arc<ServerConfig>>,
+    report: impl Fn(String) + Send + Sync + 'static,
+) -> Result<()> {
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_REMOTE_PORT)
+    };
+    let listener = TcpListener::bind(&address).with_context(|| format!("Cannot listen on {}", address))?;
+    let bound = listener.local_addr().with_context(|| format!("Cannot listen on {}", address))?;
+    if tls.is_none() && !plain_allowed(&bound) {
+        bail!(
+            "Serving on {} needs --tls-cert and --tls-key; without TLS only a loopback address such as 127.0.0.1 \
+             is served, for an SSH tunnel",
+            bound
+        );
+    }
+    report(match tls {
+        Some(_) => format!("Listening on {} with TLS.", bound),
+        None => format!("Listening on {} without TLS, for this machine only.", bound),
+    });
+    let served = Arc::new(Served {
+        key: token_key(token),
+        tls,
+        busy: AtomicBool::new(false),
+        pending: AtomicUsize::new(0),
+        report: Box::new(report),
+    });
+    for socket in listener.incoming() {
+        let socket = match socket {
+            Ok(socket) => socket,
+            Err(e) => {
+                (served.report)(format!("Failed to accept a connection: {}.", e));
+                continue;
+            }
+        };
+        let peer = socket.peer_addr().map(|a| a.to_string()).unwrap_or_else(|_| "?".to_string());
+        // Released by the session once the client is authorized.
+        if served.pending.fetch_add(1, Ordering::SeqCst) >= MAX_PENDING_SESSIONS {
+            served.pending.fetch_sub(1, Ordering::SeqCst);
+            (served.report)(format!("{}: closed, {} connections are already logging in.", peer, MAX_PENDING_SESSIONS));
+            continue;
+        }
+        let served = served.clone();
+        thread::spawn(move || {
+            if let Err(e) = session(socket, &served, &peer) {
+                (served.report)(format!("{}: {:#}.", peer, e));
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Refuses the client's request, telling it why.
+fn refuse(link: &mut Link, message: &str) -> anyhow::Error {
+    let _ = link.send(&ServerMessage::Refused { message: message.to_string() });
+    anyhow!("Refused: {}", message)
+}
+
+/// The options for a run, from this machine's settings and the order's.
+fn run_options(order: &RunOrder) -> Result<(TypingOptions, Duration)> {
+    if order.text.len() > MAX_REMOTE_TEXT_BYTES {
+        bail!("The text is larger than {} bytes", MAX_REMOTE_TEXT_BYTES);
+    }
+    if order.delay_ms > MAX_DELAY_SECONDS * 1000 {
+        bail!("delay_ms must be from 0–{}", MAX_DELAY_SECONDS * 1000);
+    }
+    let mut options = load_typing_options().map_err(|e| anyhow!("{} in this machine's config.toml", e))?;
+    let millis = |key: &str, ms: Option<u32>| match ms {
+        Some(ms) if ms > MAX_CHAR_DELAY_MS => bail!("{} must be from 0–{}", key, MAX_CHAR_DELAY_MS),
+        Some(ms) => Ok(Some(Duration::from_millis(u64::from(ms)))),
+        None => Ok(None),
+    };
+    if let Some(delay) = millis("char_delay_ms", order.char_delay_ms)? {
+        options.char_delay = delay;
+    }
+    if let Some(delay) = millis("newline_delay_ms", order.newline_delay_ms)? {
+        options.newline_delay = delay;
+    }
+    if let Some(name) = &order.enter_key {
+        options.enter_key = EnterKey::parse(name).ok_or_else(|| anyhow!("Unknown enter_key '{}'", name))?;
+    }
+    let mut pauses = order.pauses.clone();
+    pauses.sort_by_key(|(at, _)| *at);
+    if pauses.iter().any(|(_, ms)| *ms > MAX_DELAY_SECONDS * 1000) {
+        bail!("A pause is longer than {} seconds", MAX_DELAY_SECONDS);
+    }
+    options.pauses = pauses.into_iter().map(|(at, ms)| (at, Duration::from_millis(ms))).collect();
+    // Typed here, whatever this machine would do with its own runs.
+    options.remote = None;
+    // Only the machine that sent the run could read it back.
+    options.readback = None;
+    Ok((options, Duration::from_millis(order.delay_ms)))
+}
+
+fn session(socket: TcpStream, served: &Served, peer: &str) -> Result<()> {
+    let mut pending = PendingGuard(Some(&served.pending));
+    // The whole handshake, up to the run, has this long.
+    let deadline = Instant::now() + REMOTE_TIMEOUT;
+    let socket = configure(socket)?;
+    let mut link = match &served.tls {
+        Some(config) => {
+            let connection = ServerConnection::new(config.clone()).context("TLS error")?;
+            Link::Server(Box::new(StreamOwned::new(connection, socket)))
+        }
+        None => Link::Plain(socket),
+    };
+    link.handshake(deadline).context("TLS handshake failed")?;
+
+    let mut nonce = [0u8; 32];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| anyhow!("No random numbers for the handshake"))?;
+    link.send(&ServerMessage::Hello { version: PROTOCOL_VERSION, nonce: hex(&nonce) })?;
+    let mut inbox = Inbox::default();
+    let auth = inbox.receive(&mut link, MAX_MESSAGE_BYTES, deadline)?;
+    let authorized = match serde_json::from_slice(&auth) {
+        Ok(ClientMessage::Auth { mac }) => {
+            unhex(&mac).is_some_and(|mac| hmac::verify(&served.key, &nonce, &mac).is_ok())
+        }
+        _ => false,
+    };
+    if !authorized {
+        return Err(refuse(&mut link, "Wrong token"));
+    }
+    pending.release();
+    link.send(&ServerMessage::Ready)?;
+
+    let order = match inbox.receive(&mut link, MAX_RUN_BYTES, deadline) {
+        Ok(message) => message,
+        Err(LinkError::TooLarge(_)) => {
+            return Err(refuse(&mut link, &format!("The text is larger than {} bytes", MAX_REMOTE_TEXT_BYTES)));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut order = match serde_json::from_slice(&order) {
+        Ok(ClientMessage::Run(order)) => order,
+        Ok(_) => return Err(refuse(&mut link, "Expected a run")),
+        Err(e) => return Err(refuse(&mut link, &format!("Invalid run: {}", e))),
+    };
+    let (options, delay) = match run_options(&order) {
+        Ok(options) => options,
+        Err(e) => {
+            order.text.zeroize();
+            return Err(refuse(&mut link, &format!("{:#}", e)));
+        }
+    };
+    let text = Zeroizing::new(std::mem::take(&mut order.text));
+    if served.busy.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+        return Err(refuse(&mut link, "A typing run is already in progress"));
+    }
+    let _busy = BusyGuard(&served.busy);
+    link.send(&ServerMessage::Accepted)?;
+    (served.report)(format!("{}: typing {} characters in {:.1} s.", peer, text.chars().count(), delay.as_secs_f64()));
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = flume::unbounded();
+    let worker = {
+        let cancel = cancel.clone();
+        let start_at = Instant::now() + delay;
+        thread::spawn(move || simulate_typing(text.as_str(), &options, start_at, &cancel, &sender))
+    };
+    let mut connected = true;
+    let outcome = loop {
+        match receiver.try_recv() {
+            Ok(event) => {
+                if connected && link.send(&ServerMessage::from(&event)).is_err() {
+                    connected = false;
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                if event.is_terminal() {
+                    break event;
+                }
+                continue;
+            }
+            Err(flume::TryRecvError::Disconnected) => bail!("The typing worker stopped unexpectedly"),
+            Err(flume::TryRecvError::Empty) => {}
+        }
+        if !connected {
+            thread::sleep(POLL);
+            continue;
+        }
+        match inbox.poll(&mut link, MAX_MESSAGE_BYTES) {
+            Ok(None) => {}
+            Ok(Some(message)) => {
+                if matches!(serde_json::from_slice(&message), Ok(ClientMessage::Cancel)) {
+                    (served.report)(format!("{}: cancelled by the client.", peer));
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            }
+            Err(e) => {
+                (served.report)(format!("{}: {}; cancelling the run.", peer, e));
+                connected = false;
+                cancel.store(true, Ordering::Relaxed);
+            }
+        }
+    };
+    let _ = worker.join();
+    (served.report)(match outcome {
+        TypingEvent::Finished { stats } => format!("{}: done, {} characters typed.", peer, stats.typed),
+        TypingEvent::Failed { error, stats } => {
+            format!("{}: failed after {} characters: {}.", peer, stats.typed, error)
+        }
+        _ => unreachable!("only the last event ends the loop"),
+    });
+    Ok(())
+}
+
+/// The token `remote_typing` authorizes runs with, if `remote_token` sets
+/// one.
+pub fn load_remote_token() -> Option<Zeroizing<String>> {
+    Some(Zeroizing::new(Settings::load().remote_token)).filter(|token| !token.is_empty())
+}
+
+/// The certificates a TLS `remote_host` is checked against: those in
+/// `remote_ca_file` if it is set, or the system's.
+fn client_tls() -> Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    let path = PathBuf::from(Settings::load().remote_ca_file);
+    if !path.as_os_str().is_empty() {
+        let mut reader = File::open(&path)
+            .map(BufReader::new)
+            .with_context(|| format!("Cannot read remote_ca_file {}", path.display()))?;
+        for certificate in rustls_pemfile::certs(&mut reader) {
+            let certificate = certificate.with_context(|| format!("Cannot read remote_ca_file {}", path.display()))?;
+            roots.add(certificate).map_err(|e| anyhow!("Cannot use remote_ca_file {}: {}", path.display(), e))?;
+        }
+    } else {
+        let certificates = rustls_native_certs::load_native_certs().context("Cannot read the system's certificates")?;
+        for certificate in certificates {
+            let _ = roots.add(certificate);
+        }
+    }
+    if roots.is_empty() {
+        bail!("No certificates were found to check the remote machine's against; set remote_ca_file");
+    }
+    Ok(Arc::new(ClientConfig::builder().with_root_certificates(roots).with_no_client_auth()))
+}
+
+fn connect(address: &RemoteAddress) -> Result<Link> {
+    let deadline = Instant::now() + REMOTE_TIMEOUT;
+    let addresses: Vec<_> = (address.host.as_str(), address.port)
+        .to_socket_addrs()
+        .with_context(|| format!("Cannot find the host {}", address.host))?
+        .collect();
+    let mut last_error = io::Error::from(io::ErrorKind::TimedOut);
+    let mut socket = None;
+    for candidate in addresses {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        match TcpStream::connect_timeout(&candidate, left) {
+            Ok(connected) => {
+                socket = Some(connected);
+                break;
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    let socket = socket.ok_or(last_error).context("Cannot connect")?;
+    let socket = configure(socket)?;
+    if !address.tls {
+        // The run would cross the network without integrity protection.
+        if !socket.peer_addr().is_ok_and(|peer| plain_allowed(&peer)) {
+            bail!("{} is not on this machine, so it needs tls://", address.host);
+        }
+        return Ok(Link::Plain(socket));
+    }
+    let name = ServerName::try_from(address.host.clone()).map_err(|_| anyhow!("Invalid host name {}", address.host))?;
+    let connection = ClientConnection::new(client_tls()?, name).context("TLS error")?;
+    let mut link = Link::Client(Box::new(StreamOwned::new(connection, socket)));
+    link.handshake(deadline).map_err(LinkError::Io).context("TLS handshake failed")?;
+    Ok(link)
+}
+
+/// Waits for the server's answer to a step of the handshake.
+fn answer(link: &mut Link, inbox: &mut Inbox) -> Result<ServerMessage> {
+    let message = inbox.receive(link, MAX_MESSAGE_BYTES, Instant::now() + REMOTE_TIMEOUT)?;
+    match serde_json::from_slice(&message).context("Unexpected answer")? {
+        ServerMessage::Refused { message } => bail!("It refused the run: {}", message),
+        message => Ok(message),
+    }
+}
+
+/// Authorizes and hands over the run, up to the server's "accepted".
+fn start_remote(link: &mut Link, inbox: &mut Inbox, order: RunOrder, token: &str) -> Result<()> {
+    let hello = answer(link, inbox).map_err(|e| match e.downcast_ref::<LinkError>() {
+        // A TLS server waits for the client to start, so neither says anything.
+        Some(LinkError::TimedOut | LinkError::Closed) if matches!(link, Link::Plain(_)) => {
+            anyhow!("{} (if it serves with TLS, use tls://)", e)
+        }
+        _ => e,
+    })?;
+    let (version, nonce) = match hello {
+        ServerMessage::Hello { version, nonce } => (version, nonce),
+        message => bail!("Unexpected answer {:?}", message),
+    };
+    if version != PROTOCOL_VERSION {
+        bail!("It speaks version {} of the protocol; this one speaks {}", version, PROTOCOL_VERSION);
+    }
+    let nonce = unhex(&nonce).ok_or_else(|| anyhow!("Invalid nonce"))?;
+    let mac = hmac::sign(&token_key(token), &nonce);
+    link.send(&ClientMessage::Auth { mac: hex(mac.as_ref()) })?;
+    match answer(link, inbox)? {
+        ServerMessage::Ready => {}
+        message => bail!("Unexpected answer {:?}", message),
+    }
+    let mut run = ClientMessage::Run(order);
+    let body = Zeroizing::new(serde_json::to_vec(&run)?);
+    if let ClientMessage::Run(order) = &mut run {
+        order.text.zeroize();
+    }
+    if body.len() > MAX_RUN_BYTES {
+        bail!("The text takes more than the {} bytes a remote run can take", MAX_RUN_BYTES);
+    }
+    link.send_bytes(&body)?;
+    match answer(link, inbox)? {
+        ServerMessage::Accepted => Ok(()),
+        message => bail!("Unexpected answer {:?}", message),
+    }
+}
+
+/// Types `text` on the machine at `address`, as [`simulate_typing`] does
+/// here: the remote machine's events, ending with the outcome, are sent
+/// through `events`, and setting `cancel` cancels the remote run. The
+/// countdown is the remote machine's, ending at `start_at`.
+pub fn type_remote(
+    text: &str,
+    options: &TypingOptions,
+    address: &RemoteAddress,
+    start_at: Instant,
+    cancel: &AtomicBool,
+    events: &Sender<TypingEvent>,
+) {
+    let mut typed = 0;
+    if let Err(message) = drive(text, options, address, start_at, cancel, events, &mut typed) {
+        let error = if cancel.load(Ordering::Relaxed) {
+            TypingError::Cancelled { at_char: typed }
+        } else {
+            log::debug!("Remote run failed: {:#}", message);
+            TypingError::Backend(format!("{}: {:#}", address, message))
+        };
+        let stats = RunStats { typed, ..RunStats::default() };
+        let _ = events.send(TypingEvent::Failed { error, stats });
+    }
+}
+
+/// [`type_remote`] up to the remote outcome, which it sends; failing before
+/// then, `typed` says how far the remote run got.
+fn drive(
+    text: &str,
+    options: &TypingOptions,
+    address: &RemoteAddress,
+    start_at: Instant,
+    cancel: &AtomicBool,
+    events: &Sender<TypingEvent>,
+    typed: &mut usize,
+) -> Result<()> {
+    if !options.codes.is_empty() {
+        bail!("auto-type {{TOTP}} codes cannot be typed on another machine");
+    }
+    if options.assistive {
+        bail!("Assistive mode only types on this machine; turn off remote_typing or assistive_mode");
+    }
+    if options.confirm_enter {
+        bail!("Confirm-before-Enter mode only types on this machine; turn off remote_typing or confirm_enter");
+    }
+    if text.len() > MAX_REMOTE_TEXT_BYTES {
+        bail!("The text is larger than the {} bytes a remote run can take", MAX_REMOTE_TEXT_BYTES);
+    }
+    let Some(token) = load_remote_token() else {
+        bail!("Set remote_token in config.toml to the token the remote machine serves with");
+    };
+    let order = RunOrder {
+        text: text.to_string(),
+        delay_ms: start_at.saturating_duration_since(Instant::now()).as_millis() as u64,
+        char_delay_ms: Some(options.char_delay.as_millis() as u32),
+        newline_delay_ms: Some(options.newline_delay.as_millis() as u32),
+        enter_key: Some(options.enter_key.name().to_string()),
+        pauses: options.pauses.iter().map(|(at, wait)| (*at, wait.as_millis() as u64)).collect(),
+    };
+    log::debug!("Sending the run to {}", address);
+    let mut link = connect(address)?;
+    let mut inbox = Inbox::default();
+    start_remote(&mut link, &mut inbox, order, &token)?;
+
+    let mut cancel_sent = false;
+    loop {
+        if cancel.load(Ordering::Relaxed) && !cancel_sent {
+            cancel_sent = true;
+            link.send(&ClientMessage::Cancel).context("The connection was lost")?;
+        }
+        let Some(message) = inbox.poll(&mut link, MAX_MESSAGE_BYTES).context("The connection was lost")? else {
+            continue;
+        };
+        let event = match serde_json::from_slice(&message).context("Unexpected message")? {
+            ServerMessage::Started { total_chars } => TypingEvent::Started { total_chars },
+            ServerMessage::Progress { typed: now, total } => {
+                *typed = now;
+                TypingEvent::Progress { typed: now, total }
+            }
+            ServerMessage::Skipped { count } => TypingEvent::Skipped { count },
+            ServerMessage::Notice { message } => {
+                // As the local worker's notices are, for the activity log.
+                log::warn!("{}: {}", address, message);
+                TypingEvent::Notice { message }
+            }
+            ServerMessage::Finished { stats } => TypingEvent::Finished { stats: stats.into() },
+            ServerMessage::Failed { message, cancelled, stats } => {
+                let stats = RunStats::from(stats);
+                let error = if cancelled && cancel_sent {
+                    TypingError::Cancelled { at_char: stats.typed }
+                } else {
+                    TypingError::Backend(format!("{}: {}", address, message))
+                };
+                TypingEvent::Failed { error, stats }
+            }
+            message => bail!("Unexpected message {:?}", message),
+        };
+        let terminal = event.is_terminal();
+        let _ = events.send(event);
+        if terminal {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_tcp_is_only_for_this_machine() {
+        assert!(plain_allowed(&"127.0.0.1:7878".parse().unwrap()));
+        assert!(plain_allowed(&"[::1]:7878".parse().unwrap()));
+        assert!(!plain_allowed(&"0.0.0.0:7878".parse().unwrap()));
+        assert!(!plain_allowed(&"192.168.1.5:7878".parse().unwrap()));
+    }
+
+    #[test]
+    fn refuses_to_serve_the_network_without_tls() {
+        let error = serve("0.0.0.0:0", "a token of sixteen chars", None, |_| {}).unwrap_err();
+        assert!(error.to_string().contains("needs --tls-cert"), "{}", error);
+    }
+
+    #[test]
+    fn addresses_are_read_with_their_port_and_scheme() {
+        let address = |host: &str, port, tls| RemoteAddress { host: host.to_string(), port, tls };
+        for (text, expected) in [
+            ("typist.lan", address("typist.lan", DEFAULT_REMOTE_PORT, false)),
+            (" typist.lan:9000 ", address("typist.lan", 9000, false)),
+            ("tls://typist.lan", address("typist.lan", DEFAULT_REMOTE_PORT, true)),
+            ("TLS://10.0.0.3:7000", address("10.0.0.3", 7000, true)),
+            ("tcp://127.0.0.1:7001", address("127.0.0.1", 7001, false)),
+            ("[::1]", address("::1", DEFAULT_REMOTE_PORT, false)),
+            ("tls://[fd00::7]:7002", address("fd00::7", 7002, true)),
+        ] {
+            let parsed = RemoteAddress::parse(text).unwrap();
+            assert_eq!(parsed, expected, "{}", text);
+            assert_eq!(RemoteAddress::parse(&parsed.to_string()).unwrap(), expected, "{}", parsed);
+        }
+    }
+
+    #[test]
+    fn bad_addresses_are_refused() {
+        for (text, problem) in [
+            ("ssh://typist.lan", "Unsupported scheme"),
+            ("::1", "in brackets"),
+            ("[::1:7878", "Missing ]"),
+            ("", "Expected a host"),
+            (":7878", "Expected a host"),
+            ("user@typist.lan", "Expected a host"),
+            ("typist.lan/path", "Expected a host"),
+            ("typist.lan:0", "Invalid port '0'"),
+            ("typist.lan:65536", "Invalid port '65536'"),
+            ("typist.lan:", "Invalid port ''"),
+            ("[::1]:port", "Invalid port 'port'"),
+        ] {
+            let error = RemoteAddress::parse(text).unwrap_err().to_string();
+            assert!(error.contains(problem), "{:?}: {}", text, error);
+        }
+    }
+
+    /// Both ends of a loopback connection, the first configured as either
+    /// side configures its own.
+    fn linked() -> (Link, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let near = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (far, _) = listener.accept().unwrap();
+        (Link::Plain(configure(near).unwrap()), far)
+    }
+
+    #[test]
+    fn the_inbox_refuses_a_message_announced_as_too_large() {
+        let (mut link, mut far) = linked();
+        far.write_all(&101u32.to_be_bytes()).unwrap();
+        let mut inbox = Inbox::default();
+        let deadline = Instant::now() + REMOTE_TIMEOUT;
+        assert!(matches!(inbox.receive(&mut link, 100, deadline), Err(LinkError::TooLarge(101))));
+    }
+
+    #[test]
+    fn the_inbox_puts_a_message_together_across_reads() {
+        let (mut link, mut far) = linked();
+        let mut inbox = Inbox::default();
+        assert!(matches!(inbox.poll(&mut link, 100), Ok(None)));
+
+        far.write_all(&[0, 0, 0, 11, b'h', b'e', b'l']).unwrap();
+        let deadline = Instant::now() + REMOTE_TIMEOUT;
+        while inbox.buffer.len() < 7 && Instant::now() < deadline {
+            assert!(matches!(inbox.poll(&mut link, 100), Ok(None)));
+        }
+        far.write_all(b"lo world").unwrap();
+        assert_eq!(inbox.receive(&mut link, 100, deadline).unwrap().as_slice(), b"hello world");
+        assert!(inbox.buffer.is_empty());
+
+        drop(far);
+        assert!(matches!(inbox.receive(&mut link, 100, deadline), Err(LinkError::Closed)));
+    }
+
+    /// What a server with `served_token` answers a client that signs its
+    /// challenge with `token`.
+    fn answer_to(served_token: &str, token: &str) -> ServerMessage {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let served = Served {
+            key: token_key(served_token),
+            tls: None,
+            busy: AtomicBool::new(false),
+            // As `serve` counts the connection before its session.
+            pending: AtomicUsize::new(1),
+            report: Box::new(|_| {}),
+        };
+        let server = thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            // The client leaves after the answer, so the session ends with an error.
+            let _ = session(socket, &served, "client");
+            served.pending.load(Ordering::SeqCst)
+        });
+
+        let mut link = Link::Plain(configure(TcpStream::connect(address).unwrap()).unwrap());
+        let mut inbox = Inbox::default();
+        let deadline = Instant::now() + REMOTE_TIMEOUT;
+        let hello = inbox.receive(&mut link, MAX_MESSAGE_BYTES, deadline).unwrap();
+        let Ok(ServerMessage::Hello { version: PROTOCOL_VERSION, nonce }) = serde_json::from_slice(&hello) else {
+            panic!("no hello");
+        };
+        let mac = hmac::sign(&token_key(token), &unhex(&nonce).unwrap());
+        link.send(&ClientMessage::Auth { mac: hex(mac.as_ref()) }).unwrap();
+        let answer = inbox.receive(&mut link, MAX_MESSAGE_BYTES, deadline).unwrap();
+        drop(link);
+        assert_eq!(server.join().unwrap(), 0, "the connection is still counted as logging in");
+        serde_json::from_slice(&answer).unwrap()
+    }
+
+    #[test]
+    fn only_the_right_token_gets_past_the_handshake() {
+        let token = "a token of sixteen chars";
+        assert!(matches!(answer_to(token, token), ServerMessage::Ready));
+        match answer_to(token, "a token of sixteen charS") {
+            ServerMessage::Refused { message } => assert_eq!(message, "Wrong token"),
+            message => panic!("{:?}", message),
+        }
+    }
+
+    fn order() -> RunOrder {
+        RunOrder {
+            text: "hello".to_string(),
+            delay_ms: 0,
+            char_delay_ms: None,
+            newline_delay_ms: None,
+            enter_key: None,
+            pauses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn run_options_keep_orders_within_the_limits() {
+        let at_limits = RunOrder {
+            text: "a".repeat(MAX_REMOTE_TEXT_BYTES),
+            delay_ms: MAX_DELAY_SECONDS * 1000,
+            char_delay_ms: Some(MAX_CHAR_DELAY_MS),
+            newline_delay_ms: Some(0),
+            pauses: vec![(3, 10), (1, MAX_DELAY_SECONDS * 1000)],
+            ..order()
+        };
+        let (options, delay) = run_options(&at_limits).unwrap();
+        assert_eq!(delay, Duration::from_secs(MAX_DELAY_SECONDS));
+        assert_eq!(options.char_delay, Duration::from_millis(u64::from(MAX_CHAR_DELAY_MS)));
+        assert_eq!(options.newline_delay, Duration::ZERO);
+        assert_eq!(options.pauses, [(1, Duration::from_secs(MAX_DELAY_SECONDS)), (3, Duration::from_millis(10))]);
+        assert!(options.remote.is_none() && options.readback.is_none());
+
+        for (bad, problem) in [
+            (RunOrder { text: "a".repeat(MAX_REMOTE_TEXT_BYTES + 1), ..order() }, "larger than"),
+            (RunOrder { delay_ms: MAX_DELAY_SECONDS * 1000 + 1, ..order() }, "delay_ms"),
+            (RunOrder { char_delay_ms: Some(MAX_CHAR_DELAY_MS + 1), ..order() }, "char_delay_ms"),
+            (RunOrder { newline_delay_ms: Some(MAX_CHAR_DELAY_MS + 1), ..order() }, "newline_delay_ms"),
+            (RunOrder { enter_key: Some("Return".to_string()), ..order() }, "Unknown enter_key"),
+            (RunOrder { pauses: vec![(0, MAX_DELAY_SECONDS * 1000 + 1)], ..order() }, "pause"),
+        ] {
+            let error = run_options(&bad).unwrap_err().to_string();
+            assert!(error.contains(problem), "{}: {}", problem, error);
+        }
+    }
+}
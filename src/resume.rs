@@ -0,0 +1,133 @@
+//! Resuming a run that stopped part way, after a device error, a cancel or
+//! a failed focus check: the rest of its text is typed as a run of its
+//! own. It starts at the character after the last one the worker counted
+//! as typed, or at the start of that character's line when
+//! `resume_from_line_start` is on, as a line cut short may have arrived
+//! garbled. Positions are in chars into the whole text.
+
+use crate::keymap::Keymap;
+use crate::typing::TypingOptions;
+
+/// Where the character after the first `typed` typeable ones is in `text`;
+/// `None` when those were all of them.
+pub fn next_position(text: &str, typed: usize, keymap: &Keymap) -> Option<usize> {
+    text.chars().enumerate().filter(|(_, c)| keymap.is_typeable(*c)).nth(typed).map(|(at, _)| at)
+}
+
+/// `at`, moved back to the start of its line with `line_start`, then to the
+/// start of a TOTP code it falls in, so the code is typed whole.
+pub fn snap(text: &str, at: usize, options: &TypingOptions, line_start: bool) -> usize {
+    let mut at = at;
+    if line_start {
+        let before: Vec<char> = text.chars().take(at).collect();
+        at = before.iter().rposition(|&c| c == '\n').map_or(0, |newline| newline + 1);
+    }
+    match options.codes.iter().find(|code| code.covers(at)) {
+        Some(code) => code.at,
+        None => at,
+    }
+}
+
+/// How many characters with a key come before `at`; a run resumed there
+/// goes on from the one after them.
+pub fn typeable_before(text: &str, at: usize, keymap: &Keymap) -> usize {
+    text.chars().take(at).filter(|c| keymap.is_typeable(*c)).count()
+}
+
+/// `text` from `at` on.
+pub fn rest(text: &str, at: usize) -> &str {
+    text.char_indices().nth(at).map_or("", |(index, _)| &text[index..])
+}
+
+/// `options` for typing [`rest`]: the pauses and TOTP codes move with the
+/// text, and those before `at` are left out.
+pub fn rest_options(options: &TypingOptions, at: usize) -> TypingOptions {
+    let mut rest = options.clone();
+    rest.pauses = options
+        .pauses
+        .iter()
+        .filter(|(position, _)| *position >= at)
+        .map(|(position, wait)| (position - at, *wait))
+        .collect();
+    rest.codes = options
+        .codes
+        .iter()
+        .filter(|code| code.at >= at)
+        .map(|code| {
+            let mut code = code.clone();
+            code.at -= at;
+            code
+        })
+        .collect();
+    rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use evdev_rs::enums::EV_KEY;
+
+    use crate::totp::{Algorithm, CodeSlot, Totp};
+
+    fn code_at(at: usize) -> CodeSlot {
+        CodeSlot { at, totp: Totp::new(b"12345678901234567890", 6, 30, Algorithm::Sha1) }
+    }
+
+    #[test]
+    fn the_next_position_skips_characters_without_a_key() {
+        let text = "a\u{e9}b\nc";
+        let keymap = Keymap::default();
+        assert_eq!(next_position(text, 0, &keymap), Some(0));
+        // 'é' has no key, so the one after 'a' is 'b'.
+        assert_eq!(next_position(text, 1, &keymap), Some(2));
+        assert_eq!(next_position(text, 2, &keymap), Some(3));
+        assert_eq!(next_position(text, 3, &keymap), Some(4));
+        assert_eq!(next_position(text, 4, &keymap), None);
+        assert_eq!(next_position("", 0, &keymap), None);
+        assert_eq!(typeable_before(text, 3, &keymap), 2);
+        assert_eq!(rest(text, 2), "b\nc");
+        assert_eq!(rest(text, 5), "");
+    }
+
+    #[test]
+    fn an_override_gives_a_character_a_key_to_resume_at() {
+        let mut keymap = Keymap::default();
+        keymap.set('\u{e9}', EV_KEY::KEY_102ND, false);
+        assert_eq!(next_position("a\u{e9}b", 1, &keymap), Some(1));
+        assert_eq!(typeable_before("a\u{e9}b", 2, &keymap), 2);
+    }
+
+    #[test]
+    fn snapping_goes_back_to_the_line_or_the_code() {
+        let text = "first\nsecond\n000000 code";
+        let options = TypingOptions::default();
+        assert_eq!(snap(text, 9, &options, false), 9);
+        assert_eq!(snap(text, 9, &options, true), 6);
+        assert_eq!(snap(text, 3, &options, true), 0);
+        // At a line's start it stays there.
+        assert_eq!(snap(text, 6, &options, true), 6);
+
+        let options = TypingOptions { codes: vec![code_at(13)], ..TypingOptions::default() };
+        assert_eq!(snap(text, 16, &options, false), 13);
+        assert_eq!(snap(text, 18, &options, false), 13);
+        assert_eq!(snap(text, 19, &options, false), 19, "one past the code");
+        assert_eq!(snap(text, 16, &options, true), 13);
+    }
+
+    #[test]
+    fn the_rest_keeps_the_pauses_and_codes_after_it() {
+        let wait = Duration::from_millis(500);
+        let options = TypingOptions {
+            pauses: vec![(2, wait), (10, wait * 2), (12, wait * 3)],
+            codes: vec![code_at(1), code_at(12)],
+            ..TypingOptions::default()
+        };
+        let rest = rest_options(&options, 10);
+        assert_eq!(rest.pauses, [(0, wait * 2), (2, wait * 3)]);
+        assert_eq!(rest.codes.iter().map(|code| code.at).collect::<Vec<_>>(), [2]);
+        assert_eq!((rest.char_delay, rest.enter_key), (options.char_delay, options.enter_key));
+        assert_eq!(rest_options(&options, 0).pauses, options.pauses);
+    }
+}
@@ -0,0 +1,86 @@
+// "Resume from character …": once a run stops part way, a button next to
+// Cancel offers to type the rest of its text after a fresh countdown (see
+// `resume`). The offer goes when a run starts or the text area changes, as
+// the text kept would no longer be the one there. Secret runs and streams
+// are never kept for it.
+
+use gtk4::prelude::*;
+use gtk4::{gio::SimpleAction, Button};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use pasteclipboard::i18n::{args, gettext};
+use pasteclipboard::resume::typeable_before;
+use pasteclipboard::typing::TypingOptions;
+use zeroize::Zeroizing;
+
+use crate::window::{format_count, MainWindow};
+
+/// A run that stopped part way, as it is needed to type the rest.
+#[derive(Clone)]
+pub struct Interrupted {
+    /// The whole text, not only the part the run that stopped typed.
+    pub text: Arc<Zeroizing<String>>,
+    /// Where the rest begins, in chars.
+    pub at: usize,
+    /// The options for the whole text.
+    pub options: TypingOptions,
+    /// The counters to save once the rest is typed.
+    pub counters: Vec<(String, u64)>,
+}
+
+#[derive(Clone)]
+pub struct ResumeOffer {
+    pub button: Button,
+    interrupted: Rc<RefCell<Option<Interrupted>>>,
+}
+
+impl ResumeOffer {
+    pub fn new() -> Self {
+        let button = Button::builder()
+            .tooltip_text(gettext("Type the rest of the last run after the delay"))
+            .action_name("win.resume")
+            .visible(false)
+            .build();
+        ResumeOffer { button, interrupted: Rc::default() }
+    }
+
+    /// Offers to type the rest of `interrupted`.
+    pub fn offer(&self, interrupted: Interrupted) {
+        let next = typeable_before(&interrupted.text, interrupted.at, &interrupted.options.keymap) + 1;
+        self.button.set_label(&args(&gettext("Resume from character {0}"), &[&format_count(next)]));
+        self.button.set_visible(true);
+        self.interrupted.replace(Some(interrupted));
+    }
+
+    /// Withdraws the offer; returns whether there was one.
+    pub fn withdraw(&self) -> bool {
+        self.button.set_visible(false);
+        self.interrupted.take().is_some()
+    }
+}
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let action = SimpleAction::new("resume", None);
+    let weak = Rc::downgrade(ui);
+    action.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            let interrupted = ui.resume.interrupted.take();
+            ui.resume.button.set_visible(false);
+            match interrupted {
+                Some(interrupted) => ui.start_resumed(interrupted),
+                None => ui.set_warning(&gettext("There is no stopped run to resume.")),
+            }
+        }
+    });
+    ui.window.add_action(&action);
+
+    let weak = Rc::downgrade(ui);
+    ui.buffer.connect_changed(move |_| {
+        if let Some(ui) = weak.upgrade() {
+            if ui.resume.withdraw() {
+                ui.log.add("The text changed, so the stopped run can no longer be resumed.");
+            }
+        }
+    });
+}
@@ -0,0 +1,167 @@
+//! What the app is doing right now, as seen by every surface that can start
+//! or watch a run (window, command line, D-Bus, HTTP).
+
+use serde::Serialize;
+
+use crate::typing::TypingEvent;
+
+/// Tells runs apart: each run that starts takes the next id, so a timer or
+/// handler made for one run can see that a newer one has taken over and
+/// leave it alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunId(u64);
+
+impl RunId {
+    /// The id of the run after this one.
+    pub fn next(self) -> RunId {
+        RunId(self.0 + 1)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RunState {
+    #[default]
+    Idle,
+    CountingDown { remaining_seconds: u64 },
+    Typing { typed: usize, total: usize, skipped: usize },
+    Finished { typed: usize, skipped: usize },
+    Failed { typed: usize, error: String },
+}
+
+impl RunState {
+    /// Whether a run is in progress, so a new one must be refused.
+    pub fn is_busy(&self) -> bool {
+        matches!(self, RunState::CountingDown { .. } | RunState::Typing { .. })
+    }
+
+    /// Enters the countdown of a new run; returns false (and changes
+    /// nothing) if a run is already in progress.
+    pub fn start(&mut self, delay_seconds: u64) -> bool {
+        if self.is_busy() {
+            return false;
+        }
+        *self = RunState::CountingDown { remaining_seconds: delay_seconds };
+        true
+    }
+
+    /// One second of the countdown has passed.
+    pub fn tick(&mut self) {
+        if let RunState::CountingDown { remaining_seconds } = self {
+            *remaining_seconds = remaining_seconds.saturating_sub(1);
+        }
+    }
+
+    /// Follows the worker's events. Events arriving while no run is in
+    /// progress are ignored.
+    pub fn apply(&mut self, event: &TypingEvent) {
+        let skipped = match *self {
+            RunState::Typing { skipped, .. } => skipped,
+            RunState::CountingDown { .. } => 0,
+            _ => return,
+        };
+        *self = match *event {
+            TypingEvent::Started { total_chars } => RunState::Typing { typed: 0, total: total_chars, skipped },
+            TypingEvent::Progress { typed, total } => RunState::Typing { typed, total, skipped },
+            TypingEvent::Skipped { count } => match *self {
+                RunState::Typing { typed, total, .. } => RunState::Typing { typed, total, skipped: count },
+                _ => RunState::Typing { typed: 0, total: 0, skipped: count },
+            },
+            TypingEvent::Notice { .. } | TypingEvent::AwaitingConfirmation { .. } => return,
+            TypingEvent::Finished { stats } => RunState::Finished { typed: stats.typed, skipped: stats.skipped },
+            TypingEvent::Failed { ref error, stats } => RunState::Failed { typed: stats.typed, error: format!("{:#}", error) },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typing::{RunStats, TypingError};
+
+    fn stats(typed: usize, skipped: usize) -> RunStats {
+        RunStats { typed, skipped, ..RunStats::default() }
+    }
+
+    #[test]
+    fn a_run_counts_down_types_and_ends() {
+        let mut state = RunState::default();
+        assert!(!state.is_busy());
+        assert!(state.start(2));
+        assert_eq!(state, RunState::CountingDown { remaining_seconds: 2 });
+        state.tick();
+        state.tick();
+        state.tick();
+        assert_eq!(state, RunState::CountingDown { remaining_seconds: 0 });
+
+        state.apply(&TypingEvent::Started { total_chars: 10 });
+        state.apply(&TypingEvent::Skipped { count: 2 });
+        assert_eq!(state, RunState::Typing { typed: 0, total: 10, skipped: 2 });
+        state.apply(&TypingEvent::Progress { typed: 4, total: 10 });
+        state.apply(&TypingEvent::Notice { message: "note".to_string() });
+        assert_eq!(state, RunState::Typing { typed: 4, total: 10, skipped: 2 });
+        // Ticks only count the countdown down.
+        state.tick();
+        assert!(state.is_busy());
+
+        state.apply(&TypingEvent::Finished { stats: stats(10, 2) });
+        assert_eq!(state, RunState::Finished { typed: 10, skipped: 2 });
+        assert!(!state.is_busy());
+    }
+
+    #[test]
+    fn a_new_run_only_starts_once_the_last_is_over() {
+        let mut state = RunState::default();
+        assert!(state.start(3));
+        assert!(!state.start(5));
+        assert_eq!(state, RunState::CountingDown { remaining_seconds: 3 });
+        state.apply(&TypingEvent::Started { total_chars: 1 });
+        assert!(!state.start(5));
+
+        state.apply(&TypingEvent::Failed { error: TypingError::Cancelled { at_char: 0 }, stats: stats(0, 0) });
+        assert_eq!(state, RunState::Failed { typed: 0, error: "Cancelled".to_string() });
+        assert!(state.start(0));
+        assert_eq!(state, RunState::CountingDown { remaining_seconds: 0 });
+    }
+
+    #[test]
+    fn events_without_a_run_change_nothing() {
+        for mut state in [
+            RunState::Idle,
+            RunState::Finished { typed: 1, skipped: 0 },
+            RunState::Failed { typed: 0, error: "x".to_string() },
+        ] {
+            let before = state.clone();
+            state.apply(&TypingEvent::Started { total_chars: 3 });
+            state.apply(&TypingEvent::Progress { typed: 1, total: 3 });
+            state.apply(&TypingEvent::Finished { stats: stats(3, 0) });
+            state.tick();
+            assert_eq!(state, before);
+        }
+    }
+
+    #[test]
+    fn a_run_failing_in_its_countdown_fails() {
+        let mut state = RunState::default();
+        state.start(5);
+        state.apply(&TypingEvent::Failed { error: TypingError::UinputMissing, stats: stats(0, 0) });
+        assert!(matches!(state, RunState::Failed { typed: 0, .. }));
+    }
+
+    #[test]
+    fn run_ids_go_up() {
+        let first = RunId::default();
+        let second = first.next();
+        assert_ne!(first, second);
+        assert_eq!(second.next(), first.next().next());
+        assert_ne!(second.next(), second);
+    }
+
+    #[test]
+    fn the_state_is_reported_by_name() {
+        let typing = RunState::Typing { typed: 1, total: 2, skipped: 0 };
+        let json = serde_json::to_value(&typing).unwrap();
+        assert_eq!(json, serde_json::json!({ "state": "typing", "typed": 1, "total": 2, "skipped": 0 }));
+        assert_eq!(serde_json::to_value(RunState::Idle).unwrap(), serde_json::json!({ "state": "idle" }));
+    }
+}
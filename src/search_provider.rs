@@ -0,0 +1,153 @@
+// GNOME Shell search provider (org.gnome.Shell.SearchProvider2) for the
+// snippets, exported in background mode: searching in the overview lists
+// the snippets whose names contain every search term, and choosing one
+// types it after its countdown, into the window that gets the focus back.
+// Snippets kept in the keyring or encrypted with gpg never show up.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{self, DBusConnection, DBusMethodInvocation, DBusNodeInfo},
+    glib::{self, Variant, VariantDict, VariantTy},
+    Application,
+};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use pasteclipboard::i18n::gettext;
+use pasteclipboard::settings::load_delay_setting;
+use pasteclipboard::snippets::{is_encrypted, is_secure, list_snippets};
+
+use crate::window::WindowSlot;
+
+pub const OBJECT_PATH: &str = "/com/example/PasteClipboard/SearchProvider";
+const INTERFACE: &str = "org.gnome.Shell.SearchProvider2";
+
+/// How many results the overview is given; it shows only a few anyway.
+const MAX_RESULTS: usize = 20;
+
+static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.gnome.Shell.SearchProvider2">
+    <method name="GetInitialResultSet">
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="as" name="results" direction="out"/>
+    </method>
+    <method name="GetSubsearchResultSet">
+      <arg type="as" name="previous_results" direction="in"/>
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="as" name="results" direction="out"/>
+    </method>
+    <method name="GetResultMetas">
+      <arg type="as" name="identifiers" direction="in"/>
+      <arg type="aa{sv}" name="metas" direction="out"/>
+    </method>
+    <method name="ActivateResult">
+      <arg type="s" name="identifier" direction="in"/>
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="u" name="timestamp" direction="in"/>
+    </method>
+    <method name="LaunchSearch">
+      <arg type="as" name="terms" direction="in"/>
+      <arg type="u" name="timestamp" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Whether the snippet may be offered: its text is in a plain file.
+fn searchable(name: &str) -> bool {
+    !is_secure(name) && !is_encrypted(name)
+}
+
+fn matches(name: &str, terms: &[String]) -> bool {
+    let name = name.to_lowercase();
+    terms.iter().all(|term| name.contains(&term.to_lowercase()))
+}
+
+fn search(candidates: Vec<String>, terms: &[String]) -> Vec<String> {
+    candidates.into_iter().filter(|name| matches(name, terms) && searchable(name)).take(MAX_RESULTS).collect()
+}
+
+fn result_metas(names: &[String]) -> Variant {
+    let description = gettext("Type this snippet after the countdown");
+    let metas = names.iter().map(|name| {
+        let meta = VariantDict::new(None);
+        meta.insert_value("id", &name.to_variant());
+        meta.insert_value("name", &name.to_variant());
+        meta.insert_value("description", &description.to_variant());
+        meta.end()
+    });
+    Variant::tuple_from_iter([Variant::array_from_iter_with_type(VariantTy::VARDICT, metas)])
+}
+
+/// Exports the provider on the application's own bus connection. Only the
+/// first call has an effect, so `--background` can be passed again.
+pub fn register(app: &Application, conn: &DBusConnection, slot: Rc<WindowSlot>) -> Result<(), glib::Error> {
+    if REGISTERED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let node = DBusNodeInfo::for_xml(INTERFACE_XML)?;
+    let Some(interface) = node.lookup_interface(INTERFACE) else {
+        return Err(glib::Error::new(gio::IOErrorEnum::Failed, "interface missing from its own XML"));
+    };
+    let app = app.downgrade();
+    conn.register_object(
+        OBJECT_PATH,
+        &interface,
+        move |_, _, _, _, method, params, invocation| {
+            let Some(app) = app.upgrade() else {
+                return;
+            };
+            handle(&app, &slot, method, &params, invocation);
+        },
+        |_, _, _, _, _| ().to_variant(),
+        |_, _, _, _, _, _| false,
+    )?;
+    Ok(())
+}
+
+fn handle(app: &Application, slot: &WindowSlot, method: &str, params: &Variant, invocation: DBusMethodInvocation) {
+    let strings = |i: usize| params.try_child_value(i).and_then(|v| v.get::<Vec<String>>());
+    match method {
+        "GetInitialResultSet" => {
+            let terms = strings(0).unwrap_or_default();
+            let names = list_snippets().unwrap_or_default();
+            invocation.return_value(Some(&(search(names, &terms),).to_variant()));
+        }
+        "GetSubsearchResultSet" => {
+            let previous = strings(0).unwrap_or_default();
+            let terms = strings(1).unwrap_or_default();
+            invocation.return_value(Some(&(search(previous, &terms),).to_variant()));
+        }
+        "GetResultMetas" => {
+            let names: Vec<String> = strings(0).unwrap_or_default().into_iter().filter(|n| searchable(n)).collect();
+            invocation.return_value(Some(&result_metas(&names)));
+        }
+        "ActivateResult" => {
+            let name = params.try_child_value(0).and_then(|v| v.get::<String>()).unwrap_or_default();
+            invocation.return_value(None);
+            // Only what a search could have offered is typed.
+            if list_snippets().unwrap_or_default().contains(&name) && searchable(&name) {
+                type_snippet(app, slot, &name);
+            }
+        }
+        "LaunchSearch" => {
+            slot.get(app).window.present();
+            invocation.return_value(None);
+        }
+        _ => invocation.return_dbus_error("org.freedesktop.DBus.Error.UnknownMethod", method),
+    }
+}
+
+fn type_snippet(app: &Application, slot: &WindowSlot, name: &str) {
+    let ui = slot.get(app);
+    if ui.is_busy() {
+        ui.set_warning(&gettext("A typing run is already in progress."));
+        return;
+    }
+    match load_delay_setting() {
+        Ok(delay) => ui.start_snippet(name, delay),
+        Err(e) => ui.set_error(&format!("{} in config.toml.", e)),
+    }
+}
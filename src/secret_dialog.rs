@@ -0,0 +1,153 @@
+// "Type Secret…": searches the keyring's items and types the chosen one's
+// secret after the delay, as a secret run. The list shows labels and
+// attributes only; the secret is read when the run starts, unlocking the
+// item's collection first if it is locked.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::SimpleAction, Button, Image, Label, ListBox, Orientation, ScrolledWindow, SearchEntry, SelectionMode, Window,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use pasteclipboard::secret_store::{item_secret, search, ItemInfo, ItemQuery};
+
+use crate::window::MainWindow;
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let action = SimpleAction::new("type-secret", None);
+    let weak = Rc::downgrade(ui);
+    action.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            show(&ui);
+        }
+    });
+    ui.window.add_action(&action);
+}
+
+/// One item in the list: a lock for a locked one, its label, and its
+/// attributes below it.
+fn item_row(item: &ItemInfo) -> gtk4::Box {
+    let row = gtk4::Box::new(Orientation::Horizontal, 8);
+    row.set_margin_top(4);
+    row.set_margin_bottom(4);
+    let icon = Image::from_icon_name(if item.locked { "changes-prevent-symbolic" } else { "dialog-password-symbolic" });
+    icon.set_tooltip_text(Some(if item.locked { "Locked; typing it asks to unlock the keyring" } else { "Unlocked" }));
+    row.append(&icon);
+    let text = gtk4::Box::new(Orientation::Vertical, 2);
+    let label = Label::new(Some(if item.label.is_empty() { "(no label)" } else { &item.label }));
+    label.set_xalign(0.0);
+    text.append(&label);
+    let attributes: Vec<String> = item.attributes.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    let lbl_attributes = Label::new(Some(&attributes.join("  ")));
+    lbl_attributes.set_xalign(0.0);
+    lbl_attributes.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    lbl_attributes.add_css_class("dim-label");
+    text.append(&lbl_attributes);
+    row.append(&text);
+    row
+}
+
+fn show(ui: &Rc<MainWindow>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Type secret")
+        .default_width(520)
+        .default_height(440)
+        .build();
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+    let intro = Label::new(Some(
+        "Search by label, or by attribute as name=value (such as service=vpn). The chosen item's secret is typed \
+         after the delay and the log only gets its label and length.",
+    ));
+    intro.set_xalign(0.0);
+    intro.set_wrap(true);
+    vbox.append(&intro);
+    let entry_search = SearchEntry::new();
+    vbox.append(&entry_search);
+    let list = ListBox::new();
+    list.set_selection_mode(SelectionMode::Single);
+    let scroller = ScrolledWindow::builder().child(&list).vexpand(true).min_content_height(240).build();
+    vbox.append(&scroller);
+    let lbl_status = Label::new(None);
+    lbl_status.set_xalign(0.0);
+    lbl_status.set_wrap(true);
+    vbox.append(&lbl_status);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label("Cancel");
+    let btn_type = Button::with_label("Type");
+    btn_type.add_css_class("suggested-action");
+    btn_type.set_sensitive(false);
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_type);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+
+    let items: Rc<RefCell<Vec<ItemInfo>>> = Rc::default();
+    let refresh = {
+        let (items, list, lbl_status) = (items.clone(), list.clone(), lbl_status.clone());
+        move |query: &str| {
+            while let Some(row) = list.row_at_index(0) {
+                list.remove(&row);
+            }
+            let found = match search(&ItemQuery::parse(query)) {
+                Ok(found) => found,
+                Err(e) => {
+                    lbl_status.set_text(&format!("{:#}.", e));
+                    items.borrow_mut().clear();
+                    return;
+                }
+            };
+            for item in &found {
+                list.append(&item_row(item));
+            }
+            lbl_status.set_text(&match found.len() {
+                0 => "No item matches.".to_string(),
+                1 => "1 item.".to_string(),
+                count => format!("{} items.", count),
+            });
+            *items.borrow_mut() = found;
+        }
+    };
+    refresh("");
+    entry_search.connect_search_changed(move |entry| refresh(&entry.text()));
+
+    let btn_type_clone = btn_type.clone();
+    list.connect_row_selected(move |_, row| btn_type_clone.set_sensitive(row.is_some()));
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    let type_selected = {
+        let (ui, dialog, list) = (ui.clone(), dialog.clone(), list.clone());
+        move || {
+            let Some(item) = list.selected_row().and_then(|row| items.borrow().get(row.index() as usize).cloned()) else {
+                return;
+            };
+            if ui.is_busy() {
+                lbl_status.set_text("A typing run is already in progress.");
+                return;
+            }
+            match item_secret(&item.path) {
+                Ok(secret) => {
+                    dialog.close();
+                    ui.type_secret(&item.label, secret);
+                }
+                Err(e) => lbl_status.set_text(&format!("{:#}.", e)),
+            }
+        }
+    };
+    let type_selected = Rc::new(type_selected);
+    let type_clone = type_selected.clone();
+    list.connect_row_activated(move |_, _| type_clone());
+    btn_type.connect_clicked(move |_| type_selected());
+
+    entry_search.grab_focus();
+    dialog.present();
+}
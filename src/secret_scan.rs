@@ -0,0 +1,133 @@
+//! A look at the text before a run for what might be a secret: words that
+//! start like well-known credentials (`AKIA…`, `ghp_…`, `-----BEGIN …`),
+//! the prefixes in `secret_patterns`, and long words whose characters look
+//! random. It is a heuristic: it can miss a secret and flag a harmless
+//! word, such as a commit hash, so the window only asks before typing what
+//! it finds (`secret_scan` turns it off).
+
+use std::collections::HashMap;
+
+/// A well-known prefix, the length a credential that starts with it has at
+/// least, and what kind of credential it is.
+const KNOWN_PREFIXES: [(&str, usize, &str); 14] = [
+    ("AKIA", 20, "AWS access key ID"),
+    ("ASIA", 20, "AWS temporary access key ID"),
+    ("ghp_", 40, "GitHub token"),
+    ("gho_", 40, "GitHub token"),
+    ("ghu_", 40, "GitHub token"),
+    ("ghs_", 40, "GitHub token"),
+    ("ghr_", 40, "GitHub token"),
+    ("github_pat_", 40, "GitHub token"),
+    ("glpat-", 26, "GitLab token"),
+    ("xoxb-", 15, "Slack token"),
+    ("xoxp-", 15, "Slack token"),
+    ("sk_live_", 24, "Stripe secret key"),
+    ("AIza", 39, "Google API key"),
+    ("-----BEGIN", 10, "PEM block (a key or certificate)"),
+];
+
+/// A word shorter than this is never flagged for looking random.
+pub const MIN_RANDOM_CHARS: usize = 20;
+
+/// A hexadecimal word shorter than this is not either; shorter ones are
+/// mostly colours, ids and abbreviated hashes.
+const MIN_RANDOM_HEX_CHARS: usize = 32;
+
+/// How close to the most a word's characters could vary (see [`entropy`])
+/// they must come to look random.
+const RANDOM_ENTROPY_RATIO: f64 = 0.85;
+
+/// At most this many characters of a flagged word show before the mask.
+const SHOWN_CHARS: usize = 3;
+
+/// What the scan found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// From 1.
+    pub line: usize,
+    pub kind: String,
+    /// The word masked: its start, or the prefix it was flagged for, then
+    /// one dot for each hidden character, at most 12.
+    pub preview: String,
+    pub chars: usize,
+}
+
+/// Finds what looks like a secret in `text`, in order, with `patterns`
+/// (from `secret_patterns`) as prefixes beyond the well-known ones.
+pub fn scan(text: &str, patterns: &[String]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        for word in words(line) {
+            if let Some((shown, kind)) = classify(word, patterns) {
+                findings.push(Finding { line: index + 1, kind, preview: mask(word, shown), chars: word.chars().count() });
+            }
+        }
+    }
+    findings
+}
+
+/// The line's words: what lies between spaces, quotes, brackets and the
+/// `=`, `:`, `,` and `;` of assignments, so `KEY="value"` yields `value`.
+/// A `-----BEGIN` is a word of its own.
+fn words(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| c.is_whitespace() || "\"'`=:,;()[]{}<>".contains(c))
+        .filter(|word| !word.is_empty())
+        .map(|word| if word.contains("-----BEGIN") { "-----BEGIN" } else { word })
+}
+
+/// Whether `word` looks like a secret: how many of its characters its
+/// preview may show, and what it looks like.
+fn classify(word: &str, patterns: &[String]) -> Option<(usize, String)> {
+    let chars = word.chars().count();
+    if let Some((prefix, _, kind)) = KNOWN_PREFIXES.iter().find(|(prefix, min, _)| word.starts_with(prefix) && chars >= *min) {
+        return Some((prefix.chars().count(), kind.to_string()));
+    }
+    if let Some(pattern) = patterns.iter().find(|pattern| word.starts_with(pattern.as_str()) && word.len() > pattern.len()) {
+        return Some((pattern.chars().count(), format!("starts with {}, from secret_patterns", pattern)));
+    }
+    looks_random(word).then(|| (SHOWN_CHARS, "random-looking text".to_string()))
+}
+
+/// Whether `word` is long, made of the characters keys and tokens are
+/// written with, and varies nearly as much as random characters would.
+fn looks_random(word: &str) -> bool {
+    let chars = word.chars().count();
+    if chars < MIN_RANDOM_CHARS || !word.chars().all(|c| c.is_ascii_alphanumeric() || "+/=_-.".contains(c)) {
+        return false;
+    }
+    let has_digit = word.chars().any(|c| c.is_ascii_digit());
+    let hex = word.chars().all(|c| c.is_ascii_hexdigit());
+    let alphabet = if hex {
+        if chars < MIN_RANDOM_HEX_CHARS || !word.chars().any(|c| c.is_ascii_alphabetic()) {
+            return false;
+        }
+        16.0
+    } else {
+        // Words, paths and names in camel case rarely mix all three.
+        let mixed = word.chars().any(|c| c.is_ascii_lowercase()) && word.chars().any(|c| c.is_ascii_uppercase());
+        if !has_digit || !mixed {
+            return false;
+        }
+        64.0
+    };
+    let most = f64::min(alphabet, chars as f64).log2();
+    entropy(word) >= RANDOM_ENTROPY_RATIO * most
+}
+
+/// The Shannon entropy of `word`'s characters, in bits per character.
+pub fn entropy(word: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in word.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let total = word.chars().count() as f64;
+    counts.values().map(|&n| n as f64 / total).map(|p| -p * p.log2()).sum()
+}
+
+/// `word` with all but its first `shown` characters hidden.
+fn mask(word: &str, shown: usize) -> String {
+    let hidden = word.chars().count().saturating_sub(shown);
+    let mut preview: String = word.chars().take(shown).collect();
+    preview.extend(std::iter::repeat_n('•', hidden.min(12)));
+    preview
+}
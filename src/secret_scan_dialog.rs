@@ -0,0 +1,135 @@
+// Asks before a run whose text looks like it holds a secret (see
+// `secret_scan`): it shows what was found, masked, and offers to type the
+// run as a secret one and only into a window with the expected title, both
+// checked to begin with. Closing it without typing cancels the run.
+
+use gtk4::prelude::*;
+use gtk4::{glib, Button, CheckButton, Entry, Label, Orientation, Window};
+use std::cell::Cell;
+use std::rc::Rc;
+use pasteclipboard::diagnostics::{session_type, SessionType};
+use pasteclipboard::i18n::{args, gettext, ngettext};
+use pasteclipboard::secret_scan::Finding;
+
+use crate::window::{format_count, MainWindow};
+
+/// Findings listed before the rest are only counted.
+const LISTED: usize = 5;
+
+/// What the run is to do when the user goes ahead.
+pub struct Choice {
+    pub secret: bool,
+    pub target_window: Option<String>,
+}
+
+fn finding_text(finding: &Finding) -> String {
+    let text = ngettext("Line {0}: {1}, {2} ({3} character)", "Line {0}: {1}, {2} ({3} characters)", finding.chars as u64);
+    args(&text, &[&format_count(finding.line), &finding.kind, &finding.preview, &format_count(finding.chars)])
+}
+
+/// Shows the dialog for `findings` and calls `then` when the user types
+/// anyway. `secret` and `target_window` are what the run was to have.
+pub fn show(
+    ui: &Rc<MainWindow>,
+    findings: &[Finding],
+    secret: bool,
+    target_window: Option<String>,
+    then: impl Fn(&Rc<MainWindow>, Choice) + 'static,
+) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Text looks like a secret")
+        .default_width(480)
+        .build();
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let intro = Label::new(Some(
+        "The text looks like it holds a password, key or token. Typed into the wrong window, such as a chat, it \
+         is out of your hands. Check which window will have the focus, and keep the options below on.",
+    ));
+    intro.set_xalign(0.0);
+    intro.set_wrap(true);
+    vbox.append(&intro);
+    let mut lines: Vec<String> = findings.iter().take(LISTED).map(finding_text).collect();
+    if findings.len() > LISTED {
+        lines.push(format!("…and {} more.", format_count(findings.len() - LISTED)));
+    }
+    let lbl_findings = Label::new(Some(&lines.join("\n")));
+    lbl_findings.set_xalign(0.0);
+    lbl_findings.set_wrap(true);
+    lbl_findings.add_css_class("monospace");
+    vbox.append(&lbl_findings);
+
+    let chk_secret = CheckButton::with_label("Type it as a secret run: the log only gets its length, and it is not remembered");
+    chk_secret.set_active(true);
+    chk_secret.set_sensitive(!secret);
+
+    // Only X11 tells which window has the focus.
+    let gate_works = cfg!(feature = "x11-xdo") && session_type() == SessionType::X11;
+    let gate = gtk4::Box::new(Orientation::Horizontal, 6);
+    let chk_gate = CheckButton::with_label("Only type into a window titled:");
+    chk_gate.set_active(gate_works);
+    let entry_gate = Entry::builder().hexpand(true).placeholder_text("e.g. *Terminal*; * stands for anything").build();
+    entry_gate.set_text(target_window.as_deref().unwrap_or_default());
+    gate.append(&chk_gate);
+    gate.append(&entry_gate);
+    if !gate_works {
+        gate.set_sensitive(false);
+        gate.set_tooltip_text(Some("The focused window's title can only be checked in an X11 session."));
+    }
+    vbox.append(&chk_secret);
+    vbox.append(&gate);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label("Cancel");
+    let btn_type = Button::with_label("Type anyway");
+    btn_type.add_css_class("destructive-action");
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_type);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_cancel));
+
+    let update = {
+        let (chk_gate, entry_gate, btn_type) = (chk_gate.clone(), entry_gate.clone(), btn_type.clone());
+        move || btn_type.set_sensitive(!chk_gate.is_active() || !entry_gate.text().trim().is_empty())
+    };
+    update();
+    let update_clone = update.clone();
+    chk_gate.connect_toggled(move |_| update_clone());
+    entry_gate.connect_changed(move |_| update());
+
+    let typed = Rc::new(Cell::new(false));
+    let (weak, typed_clone) = (Rc::downgrade(ui), typed.clone());
+    dialog.connect_close_request(move |_| {
+        if let Some(ui) = weak.upgrade().filter(|_| !typed_clone.get()) {
+            ui.set_warning(&gettext("Not typed: the text looks like it holds a secret."));
+        }
+        glib::Propagation::Proceed
+    });
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    entry_gate.grab_focus();
+    let ui = ui.clone();
+    let dialog_clone = dialog.clone();
+    btn_type.connect_clicked(move |_| {
+        let pattern = entry_gate.text().trim().to_string();
+        let choice = Choice {
+            secret: chk_secret.is_active(),
+            target_window: if chk_gate.is_active() { Some(pattern) } else { target_window.clone() },
+        };
+        typed.set(true);
+        dialog_clone.close();
+        then(&ui, choice);
+    });
+
+    dialog.present();
+}
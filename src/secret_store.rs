@@ -0,0 +1,293 @@
+//! Secrets in the freedesktop Secret Service (GNOME Keyring, KWallet),
+//! for snippets that must not be stored as plain files. Each is an item in
+//! the default collection, found by its attributes; a locked collection is
+//! unlocked through the keyring's own prompt, which this waits for.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use gio::glib::{self, variant::ObjectPath, MainContext, Variant};
+use gio::prelude::*;
+use gio::{BusType, DBusCallFlags, DBusConnection, DBusSignalFlags};
+use zeroize::Zeroizing;
+
+const BUS_NAME: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const SERVICE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION: &str = "org.freedesktop.Secret.Collection";
+const ITEM: &str = "org.freedesktop.Secret.Item";
+/// The object path the service returns for "none", e.g. when no prompt is
+/// needed.
+const NO_OBJECT: &str = "/";
+
+/// The attributes that find a snippet's item.
+fn attributes(id: &str) -> HashMap<String, String> {
+    HashMap::from([
+        ("application".to_string(), "pasteclipboard".to_string()),
+        ("snippet".to_string(), id.to_string()),
+    ])
+}
+
+/// A connection with an open session, closed when dropped. Secrets travel
+/// unencrypted over the session bus, which only this user can read.
+struct Keyring {
+    conn: DBusConnection,
+    session: ObjectPath,
+    context: MainContext,
+}
+
+impl Keyring {
+    fn open() -> Result<Keyring> {
+        let conn = gio::bus_get_sync(BusType::Session, gio::Cancellable::NONE)
+            .context("Failed to connect to the session bus")?;
+        let reply = conn
+            .call_sync(
+                Some(BUS_NAME),
+                SERVICE_PATH,
+                SERVICE,
+                "OpenSession",
+                Some(&("plain", "".to_variant()).to_variant()),
+                None,
+                DBusCallFlags::NONE,
+                -1,
+                gio::Cancellable::NONE,
+            )
+            .context("No keyring (Secret Service) is running")?;
+        let (_, session): (Variant, ObjectPath) =
+            reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+        Ok(Keyring { conn, session, context: MainContext::new() })
+    }
+
+    fn call(&self, path: &str, interface: &str, method: &str, args: Option<Variant>) -> Result<Variant> {
+        self.conn
+            .call_sync(
+                Some(BUS_NAME),
+                path,
+                interface,
+                method,
+                args.as_ref(),
+                None,
+                DBusCallFlags::NONE,
+                -1,
+                gio::Cancellable::NONE,
+            )
+            .map_err(|e| anyhow!(e).context(format!("The keyring's {} call failed", method)))
+    }
+
+    /// Runs `prompt` unless it is "/", and waits until the user has
+    /// answered the keyring's dialog.
+    fn prompt(&self, prompt: &ObjectPath) -> Result<()> {
+        if prompt.as_str() == NO_OBJECT {
+            return Ok(());
+        }
+        self.context
+            .with_thread_default(|| {
+                // Subscribe before calling so a fast answer cannot be missed.
+                let completed: Rc<RefCell<Option<Variant>>> = Rc::default();
+                let subscription = self.conn.signal_subscribe(
+                    Some(BUS_NAME),
+                    Some("org.freedesktop.Secret.Prompt"),
+                    Some("Completed"),
+                    Some(prompt.as_str()),
+                    None,
+                    DBusSignalFlags::NONE,
+                    glib::clone!(@strong completed => move |_, _, _, _, _, params| {
+                        completed.replace(Some(params.clone()));
+                    }),
+                );
+                if let Err(e) = self.call(prompt.as_str(), "org.freedesktop.Secret.Prompt", "Prompt", Some(("",).to_variant())) {
+                    self.conn.signal_unsubscribe(subscription);
+                    return Err(e);
+                }
+                let params = loop {
+                    if let Some(params) = completed.take() {
+                        break params;
+                    }
+                    self.context.iteration(true);
+                };
+                self.conn.signal_unsubscribe(subscription);
+                let (dismissed, _): (bool, Variant) =
+                    params.get().ok_or_else(|| anyhow!("Unexpected answer from the keyring"))?;
+                if dismissed {
+                    bail!("The keyring was not unlocked");
+                }
+                Ok(())
+            })
+            .map_err(|e| anyhow!("Failed to use a private main context: {}", e))?
+    }
+
+    fn unlock(&self, objects: Vec<ObjectPath>) -> Result<()> {
+        let reply = self.call(SERVICE_PATH, SERVICE, "Unlock", Some((objects,).to_variant()))?;
+        let (_, prompt): (Vec<ObjectPath>, ObjectPath) =
+            reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+        self.prompt(&prompt)
+    }
+
+    /// The default collection, unlocked.
+    fn collection(&self) -> Result<ObjectPath> {
+        let reply = self.call(SERVICE_PATH, SERVICE, "ReadAlias", Some(("default",).to_variant()))?;
+        let (collection,): (ObjectPath,) = reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+        if collection.as_str() == NO_OBJECT {
+            bail!("The keyring has no default collection to store snippets in");
+        }
+        self.unlock(vec![collection.clone()])?;
+        Ok(collection)
+    }
+
+    /// The items with `id`'s attributes, unlocked.
+    fn find(&self, id: &str) -> Result<Vec<ObjectPath>> {
+        let reply = self.call(SERVICE_PATH, SERVICE, "SearchItems", Some((attributes(id),).to_variant()))?;
+        let (mut unlocked, locked): (Vec<ObjectPath>, Vec<ObjectPath>) =
+            reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+        if !locked.is_empty() {
+            self.unlock(locked.clone())?;
+            unlocked.extend(locked);
+        }
+        Ok(unlocked)
+    }
+}
+
+impl Drop for Keyring {
+    fn drop(&mut self) {
+        let _ = self.call(self.session.as_str(), "org.freedesktop.Secret.Session", "Close", None);
+    }
+}
+
+/// Stores `secret` under `id`, replacing what was stored under it.
+pub fn store(id: &str, label: &str, secret: &str) -> Result<()> {
+    let keyring = Keyring::open()?;
+    let collection = keyring.collection()?;
+    let properties: HashMap<String, Variant> = HashMap::from([
+        (format!("{}.Label", ITEM), label.to_variant()),
+        (format!("{}.Attributes", ITEM), attributes(id).to_variant()),
+    ]);
+    let secret = (keyring.session.clone(), Vec::<u8>::new(), secret.as_bytes().to_vec(), "text/plain; charset=utf8");
+    let reply = keyring.call(collection.as_str(), COLLECTION, "CreateItem", Some((properties, secret, true).to_variant()))?;
+    let (_, prompt): (ObjectPath, ObjectPath) = reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+    keyring.prompt(&prompt)
+}
+
+/// The secret stored under `id`, or `None` when the keyring has no entry
+/// for it. A keyring that is missing, stays locked or fails is an error,
+/// never `None`.
+pub fn lookup(id: &str) -> Result<Option<String>> {
+    let keyring = Keyring::open()?;
+    let Some(item) = keyring.find(id)?.into_iter().next() else {
+        return Ok(None);
+    };
+    let reply = keyring.call(item.as_str(), ITEM, "GetSecret", Some((keyring.session.clone(),).to_variant()))?;
+    let ((_, _, value, _),): ((ObjectPath, Vec<u8>, Vec<u8>, String),) =
+        reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+    String::from_utf8(value).map(Some).map_err(|_| anyhow!("The keyring's entry is not text"))
+}
+
+/// Removes what is stored under `id`, if anything.
+pub fn delete(id: &str) -> Result<()> {
+    let keyring = Keyring::open()?;
+    for item in keyring.find(id)? {
+        let reply = keyring.call(item.as_str(), ITEM, "Delete", None)?;
+        let (prompt,): (ObjectPath,) = reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+        keyring.prompt(&prompt)?;
+    }
+    Ok(())
+}
+
+/// What a search for keyring items asks for: `name=value` words must be
+/// attributes of the item, and the service finds those; other words must
+/// appear in its label or attributes, any case, and are checked here.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItemQuery {
+    pub attributes: HashMap<String, String>,
+    pub words: Vec<String>,
+}
+
+impl ItemQuery {
+    pub fn parse(query: &str) -> ItemQuery {
+        let mut parsed = ItemQuery::default();
+        for word in query.split_whitespace() {
+            match word.split_once('=') {
+                Some((name, value)) if !name.is_empty() => {
+                    parsed.attributes.insert(name.to_string(), value.to_string());
+                }
+                _ => parsed.words.push(word.to_lowercase()),
+            }
+        }
+        parsed
+    }
+
+    pub fn matches(&self, item: &ItemInfo) -> bool {
+        let text = std::iter::once(item.label.as_str())
+            .chain(item.attributes.iter().flat_map(|(name, value)| [name.as_str(), value.as_str()]))
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        self.words.iter().all(|word| text.contains(word))
+    }
+}
+
+/// A keyring item as a search lists it: what identifies it, never its
+/// secret.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemInfo {
+    /// The item's object path, to read its secret with [`item_secret`].
+    pub path: String,
+    pub label: String,
+    /// Sorted by name.
+    pub attributes: Vec<(String, String)>,
+    pub locked: bool,
+}
+
+/// The items of every collection that match `query`, by label. Only their
+/// label and attributes are read, and locked ones are listed without being
+/// unlocked.
+pub fn search(query: &ItemQuery) -> Result<Vec<ItemInfo>> {
+    let keyring = Keyring::open()?;
+    let reply = keyring.call(SERVICE_PATH, SERVICE, "SearchItems", Some((query.attributes.clone(),).to_variant()))?;
+    let (unlocked, locked): (Vec<ObjectPath>, Vec<ObjectPath>) =
+        reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+    let paths = unlocked.into_iter().map(|path| (path, false)).chain(locked.into_iter().map(|path| (path, true)));
+    let mut items = Vec::new();
+    for (path, is_locked) in paths {
+        let reply = keyring.call(path.as_str(), "org.freedesktop.DBus.Properties", "GetAll", Some((ITEM,).to_variant()))?;
+        let (properties,): (HashMap<String, Variant>,) =
+            reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+        let label = properties.get("Label").and_then(|label| label.get::<String>()).unwrap_or_default();
+        let attributes: HashMap<String, String> =
+            properties.get("Attributes").and_then(|attributes| attributes.get()).unwrap_or_default();
+        let mut attributes: Vec<(String, String)> = attributes.into_iter().collect();
+        attributes.sort();
+        let item = ItemInfo { path: path.as_str().to_string(), label, attributes, locked: is_locked };
+        if query.matches(&item) {
+            items.push(item);
+        }
+    }
+    items.sort_by_key(|item| item.label.to_lowercase());
+    Ok(items)
+}
+
+/// The secret of the item at `path`, as text. A locked item is unlocked
+/// first, through the keyring's prompt.
+pub fn item_secret(path: &str) -> Result<Zeroizing<String>> {
+    let keyring = Keyring::open()?;
+    let item = ObjectPath::try_from(path).map_err(|_| anyhow!("Invalid keyring item {}", path))?;
+    keyring.unlock(vec![item.clone()])?;
+    let reply = keyring.call(item.as_str(), ITEM, "GetSecret", Some((keyring.session.clone(),).to_variant()))?;
+    let ((_, _, value, _),): ((ObjectPath, Vec<u8>, Vec<u8>, String),) =
+        reply.get().ok_or_else(|| anyhow!("Unexpected reply from the keyring"))?;
+    let value = Zeroizing::new(value);
+    match std::str::from_utf8(&value) {
+        Ok(text) => Ok(Zeroizing::new(text.to_string())),
+        Err(_) => bail!("The keyring item is not text"),
+    }
+}
+
+/// A new id for a secret, unique enough for one user's snippets.
+pub fn new_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}-{:x}", nanos, std::process::id())
+}
@@ -0,0 +1,192 @@
+//! The test target's self-test: a short text typed into a window of the
+//! app's own, then compared with what arrived there. Unlike the benchmark,
+//! which only sees the keys go out, this sees them come back through the
+//! keymap, the device, the compositor and GTK, so it tells which
+//! characters went missing, arrived as others, came in the wrong order or
+//! turned up uninvited; a new backend should pass it cleanly.
+
+use std::time::{Duration, Instant};
+
+/// What the self-test types: lower and upper case, digits and every US
+/// symbol, over a few lines.
+pub const SELF_TEST_TEXT: &str = "The quick brown fox jumps over the lazy dog.
+PACK MY BOX WITH FIVE DOZEN LIQUOR JUGS!
+0123456789 ~`!@#$%^&*()-_=+[]{}\\|;:'\",.<>/?
+";
+
+/// At most this many times the text sent is compared; the rest of what
+/// arrived counts as extra.
+const MAX_RECEIVED_FACTOR: usize = 4;
+
+/// How what arrived differs from what was sent, at `at`, a character
+/// offset into the text sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difference {
+    Missing { at: usize, expected: char },
+    /// `got` arrived instead of `expected`, as with a wrong keymap.
+    Wrong { at: usize, expected: char, got: char },
+    /// `first` and `second` arrived the other way round.
+    Transposed { at: usize, first: char, second: char },
+    /// `got` arrived before the character at `at`, which was not sent.
+    Extra { at: usize, got: char },
+}
+
+/// What a self-test found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Comparison {
+    pub sent: usize,
+    pub received: usize,
+    pub differences: Vec<Difference>,
+}
+
+impl Comparison {
+    /// Whether everything arrived as it was sent.
+    pub fn is_exact(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// Lines up `received` with `sent`, using as few differences as it can
+/// (an edit distance that counts a swap of two neighbours as one).
+pub fn compare(sent: &str, received: &str) -> Comparison {
+    let a: Vec<char> = sent.chars().collect();
+    let b: Vec<char> = received.chars().take(a.len().max(1) * MAX_RECEIVED_FACTOR).collect();
+    let (n, m) = (a.len(), b.len());
+    let width = m + 1;
+    let mut cost = vec![0usize; (n + 1) * width];
+    for i in 0..=n {
+        for j in 0..=m {
+            cost[i * width + j] = if i == 0 || j == 0 {
+                i + j
+            } else {
+                let mut best = (cost[(i - 1) * width + j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                    .min(cost[(i - 1) * width + j] + 1)
+                    .min(cost[i * width + j - 1] + 1);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] && a[i - 1] != a[i - 2] {
+                    best = best.min(cost[(i - 2) * width + j - 2] + 1);
+                }
+                best
+            };
+        }
+    }
+
+    // Back from the end, preferring a match, then a swap, then a wrong
+    // character, so a difference is reported the simplest way.
+    let mut differences = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let here = cost[i * width + j];
+        if i > 0 && j > 0 && a[i - 1] == b[j - 1] && here == cost[(i - 1) * width + j - 1] {
+            i -= 1;
+            j -= 1;
+        } else if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] && a[i - 1] != a[i - 2]
+            && here == cost[(i - 2) * width + j - 2] + 1
+        {
+            differences.push(Difference::Transposed { at: i - 2, first: a[i - 2], second: a[i - 1] });
+            i -= 2;
+            j -= 2;
+        } else if i > 0 && j > 0 && here == cost[(i - 1) * width + j - 1] + 1 {
+            differences.push(Difference::Wrong { at: i - 1, expected: a[i - 1], got: b[j - 1] });
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && here == cost[(i - 1) * width + j] + 1 {
+            differences.push(Difference::Missing { at: i - 1, expected: a[i - 1] });
+            i -= 1;
+        } else {
+            differences.push(Difference::Extra { at: i, got: b[j - 1] });
+            j -= 1;
+        }
+    }
+    differences.reverse();
+    let received_chars = received.chars().count();
+    let mut comparison = Comparison { sent: n, received: received_chars, differences };
+    for got in received.chars().skip(m) {
+        comparison.differences.push(Difference::Extra { at: n, got });
+    }
+    comparison
+}
+
+/// How a character shows in a report: in quotes, with the invisible ones
+/// named.
+fn shown(c: char) -> String {
+    match c {
+        '\n' => "Enter".to_string(),
+        '\t' => "Tab".to_string(),
+        ' ' => "space".to_string(),
+        c if c.is_control() => format!("U+{:04X}", u32::from(c)),
+        c => format!("'{}'", c),
+    }
+}
+
+/// Line and column, from 1, of the character at `at` in `text`.
+fn position(text: &str, at: usize) -> (usize, usize) {
+    let before: Vec<char> = text.chars().take(at).collect();
+    let line = before.iter().filter(|&&c| c == '\n').count() + 1;
+    let column = before.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+    (line, column)
+}
+
+/// One line for `difference`, with where it is in `sent`.
+pub fn difference_text(sent: &str, difference: &Difference) -> String {
+    let at = match difference {
+        Difference::Missing { at, .. }
+        | Difference::Wrong { at, .. }
+        | Difference::Transposed { at, .. }
+        | Difference::Extra { at, .. } => *at,
+    };
+    let (line, column) = position(sent, at);
+    let what = match difference {
+        Difference::Missing { expected, .. } => format!("{} is missing", shown(*expected)),
+        Difference::Wrong { expected, got, .. } => format!("{} arrived instead of {}", shown(*got), shown(*expected)),
+        Difference::Transposed { first, second, .. } => {
+            format!("{} and {} arrived the other way round", shown(*first), shown(*second))
+        }
+        Difference::Extra { got, .. } => format!("{} arrived but was not sent", shown(*got)),
+    };
+    format!("Line {}, column {}: {}", line, column, what)
+}
+
+/// Characters per second that arrived between the first and the last of
+/// `chars` arrivals.
+pub fn arrival_rate(chars: usize, first: Instant, last: Instant) -> Option<f64> {
+    let seconds = last.saturating_duration_since(first).as_secs_f64();
+    (chars > 1 && seconds > 0.0).then(|| (chars - 1) as f64 / seconds)
+}
+
+/// At most this many differences are listed.
+const LISTED: usize = 10;
+
+/// The report: a verdict, the rates, and the first differences.
+pub fn report(sent: &str, comparison: &Comparison, rate: Option<f64>, sent_interval: Duration) -> String {
+    let mut out = if comparison.is_exact() {
+        format!("Passed: all {} characters arrived as they were sent.", comparison.sent)
+    } else {
+        let count = |f: fn(&Difference) -> bool| comparison.differences.iter().filter(|d| f(d)).count();
+        format!(
+            "Failed: {} characters arrived for the {} sent; {} missing, {} wrong, {} transposed, {} extra.",
+            comparison.received,
+            comparison.sent,
+            count(|d| matches!(d, Difference::Missing { .. })),
+            count(|d| matches!(d, Difference::Wrong { .. })),
+            count(|d| matches!(d, Difference::Transposed { .. })),
+            count(|d| matches!(d, Difference::Extra { .. })),
+        )
+    };
+    match rate {
+        Some(rate) => out.push_str(&format!(" They arrived at {:.0} characters/s", rate)),
+        None => out.push_str(" Too little arrived to tell the rate"),
+    }
+    if sent_interval > Duration::ZERO {
+        out.push_str(&format!("; the keys went out one every {:.2} ms.", sent_interval.as_secs_f64() * 1000.0));
+    } else {
+        out.push('.');
+    }
+    for difference in comparison.differences.iter().take(LISTED) {
+        out.push('\n');
+        out.push_str(&difference_text(sent, difference));
+    }
+    if comparison.differences.len() > LISTED {
+        out.push_str(&format!("\n…and {} more.", comparison.differences.len() - LISTED));
+    }
+    out
+}
@@ -0,0 +1,118 @@
+// `pasteclipboard install-service`: a systemd user unit running `--background`.
+
+use anyhow::{bail, Context, Result};
+use directories::BaseDirs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use pasteclipboard::settings::portable_base;
+
+const UNIT_NAME: &str = "pasteclipboard.service";
+
+const USAGE: &str = "Usage: pasteclipboard install-service [--enable]
+
+Writes a systemd user unit that starts PasteClipboard in background mode
+with the graphical session.
+
+  --enable   Also reload systemd and enable and start the unit now
+";
+
+fn unit_path() -> Result<PathBuf> {
+    if portable_base().is_some() {
+        bail!("Portable mode does not write to the home directory, where the unit would go");
+    }
+    let base = BaseDirs::new().context("Cannot determine your home directory")?;
+    Ok(base.config_dir().join("systemd").join("user").join(UNIT_NAME))
+}
+
+/// Quotes a path for an ExecStart= line: systemd splits on whitespace and
+/// expands `%` specifiers.
+fn quote_exec_path(path: &Path) -> Result<String> {
+    let Some(path) = path.to_str() else {
+        bail!("The executable path {} is not valid UTF-8", path.display());
+    };
+    let escaped = path.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%");
+    Ok(format!("\"{}\"", escaped))
+}
+
+fn unit_contents(exe: &Path) -> Result<String> {
+    Ok(format!(
+        "[Unit]
+Description=PasteClipboard background agent
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+ExecStart={} --background
+Restart=on-failure
+
+[Install]
+WantedBy=graphical-session.target
+",
+        quote_exec_path(exe)?
+    ))
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .context("Failed to run systemctl")?;
+    if !output.status.success() {
+        bail!(
+            "systemctl --user {} failed ({}): {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn install(enable: bool) -> Result<()> {
+    let exe = std::env::current_exe().context("Cannot determine the path of this executable")?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+    let path = unit_path()?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let existed = path.exists();
+    std::fs::write(&path, unit_contents(&exe)?).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("{} {}", if existed { "Updated" } else { "Wrote" }, path.display());
+
+    if enable {
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", "--now", UNIT_NAME])?;
+        println!("Enabled and started {}", UNIT_NAME);
+    } else {
+        println!("Enable it with: systemctl --user enable --now {}", UNIT_NAME);
+    }
+    Ok(())
+}
+
+/// Runs the subcommand with the arguments that follow its name.
+pub fn run(args: &[String]) -> i32 {
+    let mut enable = false;
+    for arg in args {
+        match arg.as_str() {
+            "--enable" => enable = true,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return 0;
+            }
+            other => {
+                eprintln!("Unknown argument '{}'.\n\n{}", other, USAGE);
+                return 2;
+            }
+        }
+    }
+    match install(enable) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("install-service: {:#}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Window geometry and modes, the last file folder and the text drafts, kept in the XDG state directory
+//! between launches.
+
+use std::fs;
+use std::path::PathBuf;
+
+use configparser::ini::Ini;
+
+use crate::settings::{state_dir, state_path};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Geometry {
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+}
+
+fn geometry_path() -> Option<PathBuf> {
+    state_path("window.ini")
+}
+
+/// The first tab's draft keeps the name it had before there were tabs.
+fn draft_path(index: usize) -> Option<PathBuf> {
+    let name = match index {
+        0 => "draft.txt".to_string(),
+        n => format!("draft-{}.txt", n + 1),
+    };
+    state_path(&name)
+}
+
+/// The geometry saved when the window was last closed, if any.
+pub fn load_geometry() -> Option<Geometry> {
+    let mut conf = Ini::new();
+    conf.load(geometry_path()?).ok()?;
+    let size = |key| conf.getint("window", key).ok().flatten().and_then(|v| i32::try_from(v).ok()).filter(|v| *v > 0);
+    Some(Geometry {
+        width: size("width")?,
+        height: size("height")?,
+        maximized: conf.getbool("window", "maximized").ok().flatten().unwrap_or(false),
+    })
+}
+
+pub fn save_geometry(geometry: &Geometry) {
+    let Some(path) = geometry_path() else {
+        return;
+    };
+    let mut conf = Ini::new();
+    let _ = conf.load(&path);
+    conf.set("window", "width", Some(geometry.width.to_string()));
+    conf.set("window", "height", Some(geometry.height.to_string()));
+    conf.set("window", "maximized", Some(geometry.maximized.to_string()));
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = conf.write(path);
+}
+
+/// An on/off window option such as compact mode, kept next to the geometry.
+pub fn load_window_flag(key: &str) -> bool {
+    let mut conf = Ini::new();
+    geometry_path()
+        .and_then(|path| conf.load(path).ok())
+        .and_then(|_| conf.getbool("window", key).ok().flatten())
+        .unwrap_or(false)
+}
+
+pub fn save_window_flag(key: &str, value: bool) {
+    let Some(path) = geometry_path() else {
+        return;
+    };
+    let mut conf = Ini::new();
+    let _ = conf.load(&path);
+    conf.set("window", key, Some(value.to_string()));
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = conf.write(path);
+}
+
+/// The folder the last file was opened from or saved to.
+pub fn load_last_directory() -> Option<PathBuf> {
+    let mut conf = Ini::new();
+    conf.load(geometry_path()?).ok()?;
+    conf.get("files", "last_directory").map(PathBuf::from).filter(|dir| dir.is_dir())
+}
+
+pub fn save_last_directory(dir: &std::path::Path) {
+    let Some(path) = geometry_path() else {
+        return;
+    };
+    let mut conf = Ini::new();
+    let _ = conf.load(&path);
+    conf.set("files", "last_directory", Some(dir.to_string_lossy().into_owned()));
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = conf.write(path);
+}
+
+/// The drafts saved when the window last closed, one per tab in tab order.
+pub fn load_drafts() -> Vec<String> {
+    (0..).map_while(|index| fs::read_to_string(draft_path(index)?).ok()).collect()
+}
+
+/// Saves the tabs' texts as drafts, leaving out empty ones, and removes
+/// drafts of tabs that no longer exist.
+pub fn save_drafts(texts: &[String]) {
+    discard_draft();
+    let Some(dir) = state_dir() else {
+        return;
+    };
+    let _ = fs::create_dir_all(dir);
+    for (index, text) in texts.iter().filter(|text| !text.is_empty()).enumerate() {
+        if let Some(path) = draft_path(index) {
+            let _ = fs::write(path, text);
+        }
+    }
+}
+
+pub fn discard_draft() {
+    for index in 0.. {
+        let Some(path) = draft_path(index).filter(|path| path.exists()) else {
+            break;
+        };
+        let _ = fs::remove_file(path);
+    }
+}
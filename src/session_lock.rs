@@ -0,0 +1,99 @@
+//! Whether the desktop session is locked, so a run can pause rather than
+//! type into the lock screen. The screensaver interfaces GNOME Shell and
+//! KDE (and several others) export on the session bus are asked first, then
+//! logind's `LockedHint` on the system bus; a session that none of them
+//! answers for counts as unlocked.
+
+use std::time::{Duration, Instant};
+
+use gio::glib::Variant;
+use gio::prelude::*;
+use gio::{BusType, DBusCallFlags, DBusConnection};
+
+/// How often a run asks while typing.
+pub const LOCK_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long one question may take, in milliseconds.
+const CALL_TIMEOUT_MS: i32 = 500;
+
+const SCREENSAVERS: [(&str, &str, &str); 2] = [
+    ("org.gnome.ScreenSaver", "/org/gnome/ScreenSaver", "org.gnome.ScreenSaver"),
+    ("org.freedesktop.ScreenSaver", "/org/freedesktop/ScreenSaver", "org.freedesktop.ScreenSaver"),
+];
+
+const LOGIND: &str = "org.freedesktop.login1";
+const LOGIND_SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+const LOGIND_SESSION: &str = "org.freedesktop.login1.Session";
+
+fn call(
+    conn: &DBusConnection,
+    (name, path, interface): (&str, &str, &str),
+    method: &str,
+    args: Option<&Variant>,
+) -> Option<Variant> {
+    conn.call_sync(
+        Some(name),
+        path,
+        interface,
+        method,
+        args,
+        None,
+        DBusCallFlags::NO_AUTO_START,
+        CALL_TIMEOUT_MS,
+        gio::Cancellable::NONE,
+    )
+    .ok()
+}
+
+/// Asks the session's screensaver and logind, caching the answer for
+/// [`LOCK_CHECK_INTERVAL`].
+pub struct LockWatch {
+    session: Option<DBusConnection>,
+    system: Option<DBusConnection>,
+    checked: Option<(Instant, bool)>,
+}
+
+impl LockWatch {
+    pub fn new() -> Self {
+        LockWatch {
+            session: gio::bus_get_sync(BusType::Session, gio::Cancellable::NONE).ok(),
+            system: gio::bus_get_sync(BusType::System, gio::Cancellable::NONE).ok(),
+            checked: None,
+        }
+    }
+
+    pub fn is_locked(&mut self) -> bool {
+        match self.checked {
+            Some((at, locked)) if at.elapsed() < LOCK_CHECK_INTERVAL => locked,
+            _ => {
+                let locked = self.ask();
+                self.checked = Some((Instant::now(), locked));
+                locked
+            }
+        }
+    }
+
+    fn ask(&self) -> bool {
+        if let Some(conn) = &self.session {
+            for screensaver in SCREENSAVERS {
+                if call(conn, screensaver, "GetActive", None).and_then(|v| v.get::<(bool,)>()) == Some((true,)) {
+                    return true;
+                }
+            }
+        }
+        let Some(conn) = &self.system else {
+            return false;
+        };
+        let args = (LOGIND_SESSION, "LockedHint").to_variant();
+        call(conn, (LOGIND, LOGIND_SESSION_PATH, "org.freedesktop.DBus.Properties"), "Get", Some(&args))
+            .and_then(|reply| reply.get::<(Variant,)>())
+            .and_then(|(value,)| value.get::<bool>())
+            .unwrap_or(false)
+    }
+}
+
+impl Default for LockWatch {
+    fn default() -> Self {
+        LockWatch::new()
+    }
+}
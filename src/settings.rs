@@ -0,0 +1,1740 @@
+//! Settings stored in ~/.config/PasteClipboard/config.toml, or in the
+//! config.ini of older versions until it is copied over.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use configparser::ini::Ini;
+use directories::BaseDirs;
+
+use std::time::Duration;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::backend::{Backend, DEFAULT_LIBVIRT_HOLD_MS, MAX_LIBVIRT_HOLD_MS};
+use crate::config_toml;
+use crate::keymap::Keymap;
+use crate::line_filter::{parse_prefixes, DEFAULT_COMMENT_PREFIXES};
+use crate::pass::DEFAULT_PASS_COMMAND;
+use crate::profiles;
+use crate::text_file::write_atomically_with_mode;
+use crate::readback::{Chord, Readback, DEFAULT_COPY_CHORD, DEFAULT_SELECT_CHORD};
+use crate::remote::RemoteAddress;
+use crate::stream::DEFAULT_SENTINEL;
+use crate::totp::MAX_MARGIN_SECONDS;
+use crate::typing::{EnterKey, TypingOptions, MAX_CHAR_DELAY_MS, MAX_DELAY_SECONDS};
+
+/// Replaces ~/.config/PasteClipboard, e.g. to test with a clean setup.
+pub const CONFIG_DIR_ENV: &str = "PASTECLIPBOARD_CONFIG_DIR";
+/// Replaces ~/.local/state/PasteClipboard.
+pub const STATE_DIR_ENV: &str = "PASTECLIPBOARD_STATE_DIR";
+
+/// Next to the executable, switches to portable mode like `--portable`.
+pub const PORTABLE_FLAG: &str = "portable.flag";
+
+/// The folder portable mode keeps everything in; see [`set_portable_base`].
+static PORTABLE_BASE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// The executable's folder if this run is portable: `forced` by
+/// `--portable`, or because portable.flag is next to the executable.
+pub fn detect_portable_base(forced: bool) -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    portable_dir(&exe.canonicalize().unwrap_or(exe), forced)
+}
+
+/// The folder of the executable `exe` if it runs portable.
+fn portable_dir(exe: &Path, forced: bool) -> Option<PathBuf> {
+    let dir = exe.parent()?;
+    (forced || dir.join(PORTABLE_FLAG).is_file()).then(|| dir.to_path_buf())
+}
+
+/// Keeps the settings in `base`/config and the state in `base`/state
+/// instead of the home directory, or stops doing so with `None`. Set before
+/// anything is read; the environment variables still win.
+pub fn set_portable_base(base: Option<PathBuf>) {
+    if let Ok(mut current) = PORTABLE_BASE.lock() {
+        *current = base;
+    }
+}
+
+/// The folder set by [`set_portable_base`], if this run is portable.
+pub fn portable_base() -> Option<PathBuf> {
+    PORTABLE_BASE.lock().ok().and_then(|base| base.clone())
+}
+
+/// What [`config_dir`] and [`state_dir`] are found from: the running
+/// program's environment, portable folder and home directory, or in tests
+/// others.
+trait BasePaths {
+    /// The folder an environment variable such as [`CONFIG_DIR_ENV`] sets.
+    fn env_dir(&self, var: &str) -> Option<PathBuf>;
+    /// The folder set by [`set_portable_base`].
+    fn portable(&self) -> Option<PathBuf>;
+    /// The home directory's config folder, ~/.config.
+    fn config_home(&self) -> Option<PathBuf>;
+    /// The home directory's state folder, ~/.local/state, or the local
+    /// data folder on platforms without one.
+    fn state_home(&self) -> Option<PathBuf>;
+}
+
+struct RunningBasePaths;
+
+impl BasePaths for RunningBasePaths {
+    fn env_dir(&self, var: &str) -> Option<PathBuf> {
+        std::env::var_os(var).filter(|value| !value.is_empty()).map(PathBuf::from)
+    }
+
+    fn portable(&self) -> Option<PathBuf> {
+        portable_base()
+    }
+
+    fn config_home(&self) -> Option<PathBuf> {
+        BaseDirs::new().map(|base| base.config_dir().to_path_buf())
+    }
+
+    fn state_home(&self) -> Option<PathBuf> {
+        BaseDirs::new().map(|base| base.state_dir().unwrap_or_else(|| base.data_local_dir()).to_path_buf())
+    }
+}
+
+fn config_dir_in(bases: &dyn BasePaths) -> Option<PathBuf> {
+    if let Some(dir) = bases.env_dir(CONFIG_DIR_ENV) {
+        return Some(dir);
+    }
+    if let Some(base) = bases.portable() {
+        return Some(base.join("config"));
+    }
+    bases.config_home().map(|dir| dir.join("PasteClipboard"))
+}
+
+fn state_dir_in(bases: &dyn BasePaths) -> Option<PathBuf> {
+    if let Some(dir) = bases.env_dir(STATE_DIR_ENV) {
+        return Some(dir);
+    }
+    if let Some(base) = bases.portable() {
+        return Some(base.join("state"));
+    }
+    bases.state_home().map(|dir| dir.join("PasteClipboard"))
+}
+
+/// Where config.toml, the profiles and the snippets live
+/// (~/.config/PasteClipboard, or config/ next to a portable executable).
+pub fn config_dir() -> Option<PathBuf> {
+    config_dir_in(&RunningBasePaths)
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// The settings file of versions before config.toml.
+pub fn legacy_config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.ini"))
+}
+
+/// Copies config.ini's settings to a new config.toml when there is only
+/// the former, as on the first run after an update, and returns the new
+/// file. config.ini is left as it was, as a backup.
+pub fn migrate_config() -> Option<PathBuf> {
+    let path = config_path().filter(|path| !path.exists())?;
+    let legacy = legacy_config_path().filter(|legacy| legacy.exists())?;
+    let conf = read_ini(&legacy).or_else(|| read_ini(&backup_path(&legacy)))?;
+    write_ini(&path, &conf).ok()?;
+    Some(path)
+}
+
+/// Where run statistics, history and drafts live
+/// (~/.local/state/PasteClipboard), kept apart from the configuration so
+/// syncing dotfiles does not pick them up. Platforms without a state
+/// directory use the local data directory; a portable executable uses
+/// state/ next to it.
+pub fn state_dir() -> Option<PathBuf> {
+    state_dir_in(&RunningBasePaths)
+}
+
+/// The state file `name`, such as "stats.ini".
+pub fn state_path(name: &str) -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join(name))
+}
+
+/// State files that versions before the state directory kept next to
+/// config.ini. Drafts are matched by name, see `is_state_file`.
+const STATE_FILES: [&str; 6] =
+    ["stats.ini", "window.ini", "command_history", "recent_snippets", "recent_texts.json", "placeholders.ini"];
+
+fn is_state_file(name: &str) -> bool {
+    let is_draft = name == "draft.txt"
+        || name
+            .strip_prefix("draft-")
+            .and_then(|rest| rest.strip_suffix(".txt"))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+    is_draft || STATE_FILES.contains(&name)
+}
+
+/// Moves state files found in the configuration directory to the state
+/// directory, and returns where each one went. A file the state directory
+/// already has is left where it is rather than overwritten.
+pub fn migrate_state_files() -> Vec<PathBuf> {
+    let (Some(config), Some(state)) = (config_dir(), state_dir()) else {
+        return Vec::new();
+    };
+    if config == state {
+        return Vec::new();
+    }
+    let Ok(entries) = fs::read_dir(&config) else {
+        return Vec::new();
+    };
+    let mut moved = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name();
+        if !name.to_str().is_some_and(is_state_file) || !entry.path().is_file() {
+            continue;
+        }
+        let target = state.join(&name);
+        if target.exists() || fs::create_dir_all(&state).is_err() {
+            continue;
+        }
+        // Across file systems a rename fails; copy and remove instead.
+        let done = fs::rename(entry.path(), &target).is_ok()
+            || (fs::copy(entry.path(), &target).is_ok() && fs::remove_file(entry.path()).is_ok());
+        if done {
+            moved.push(target);
+        }
+    }
+    moved.sort();
+    moved
+}
+
+/// `path` with `suffix` added to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// The copy of a settings file written after each save, for when the file
+/// itself is lost (config.toml.bak).
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+/// A settings file's text as ini sections, whichever format `path` is in.
+pub(crate) fn parse_settings(path: &Path, text: &str) -> anyhow::Result<Ini> {
+    let mut conf = Ini::new();
+    if is_toml(path) {
+        for (key, value) in config_toml::read(text)? {
+            conf.set("settings", &key, Some(value));
+        }
+        return Ok(conf);
+    }
+    // Values such as comment_prefixes' "#, //" hold the comment characters,
+    // so only whole lines are comments.
+    conf.set_inline_comment_symbols(Some(&[]));
+    conf.read(text.to_string()).map_err(|e| anyhow::anyhow!(e))?;
+    Ok(conf)
+}
+
+/// `conf`'s `[settings]` as config.toml, in place of its settings in
+/// `previous` (see [`config_toml::write`]).
+pub(crate) fn toml_text(conf: &Ini, previous: Option<&str>) -> String {
+    let values: Vec<(String, String)> = conf
+        .get_map_ref()
+        .get("settings")
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| Some((key.clone(), value.clone()?)))
+        .collect();
+    config_toml::write(&values, &Settings::from_config(conf), previous)
+}
+
+/// `path` as an ini file (or config.toml), or `None` if it cannot be read,
+/// is empty, or is not one.
+fn read_ini(path: &Path) -> Option<Ini> {
+    let text = fs::read_to_string(path).ok().filter(|text| !text.trim().is_empty())?;
+    parse_settings(path, &text).ok()
+}
+
+/// The settings file in use: config.toml, or config.ini while it has not
+/// been copied over.
+fn main_config_file() -> Option<PathBuf> {
+    let path = config_path()?;
+    if path.exists() {
+        return Some(path);
+    }
+    legacy_config_path().filter(|legacy| legacy.exists())
+}
+
+/// The settings file as written, and whether it came from the backup
+/// because the file itself was unreadable or empty (as after a crash in
+/// older versions' saves, or a typo in an edit). A missing file is not
+/// recovered: that is how the settings are reset.
+fn load_config_with_source() -> (Ini, bool) {
+    match main_config_file() {
+        Some(path) => load_with_backup(&path),
+        None => (Ini::new(), false),
+    }
+}
+
+/// `path`, or its backup when it is unreadable or empty, and whether it
+/// was the backup.
+fn load_with_backup(path: &Path) -> (Ini, bool) {
+    if let Some(conf) = read_ini(path) {
+        return (conf, false);
+    }
+    match read_ini(&backup_path(path)) {
+        Some(conf) => (conf, true),
+        None => (Ini::new(), false),
+    }
+}
+
+/// The settings file as written, without the active profile.
+pub fn load_main_config() -> Ini {
+    load_config_with_source().0
+}
+
+/// The settings in effect: config.toml with the active profile's keys in
+/// place of its own, and the `--set` overrides of a run in progress on top.
+pub fn load_config() -> Ini {
+    let mut conf = load_main_config();
+    profiles::apply_active(&mut conf);
+    if let Ok(overrides) = OVERRIDES.lock() {
+        for (key, value) in overrides.iter() {
+            conf.set("settings", key, Some(value.clone()));
+        }
+    }
+    conf
+}
+
+/// `path` as an ini file, or an empty one if it cannot be read.
+pub fn load_ini(path: &Path) -> Ini {
+    read_ini(path).unwrap_or_default()
+}
+
+/// Settings files hold tokens and passwords, so only their owner may read
+/// them, whatever mode an older version left them with.
+const SETTINGS_MODE: u32 = 0o600;
+
+/// Writes `text` to `path` without ever leaving it half written (see
+/// [`write_atomically_with_mode`]).
+fn write_atomically(path: &Path, text: &str) -> std::io::Result<()> {
+    write_atomically_with_mode(path, text.as_bytes(), SETTINGS_MODE).map_err(std::io::Error::other)
+}
+
+/// The text each settings file was last given by [`write_ini`].
+static WRITTEN: Mutex<Vec<(PathBuf, String)>> = Mutex::new(Vec::new());
+
+/// Writes `conf` to `path` atomically, then to its backup. A .toml file
+/// keeps its comments and whatever else ini sections cannot hold.
+pub fn write_ini(path: &Path, conf: &Ini) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = if is_toml(path) {
+        toml_text(conf, fs::read_to_string(path).ok().as_deref())
+    } else {
+        conf.writes()
+    };
+    write_atomically(path, &text)?;
+    if let Ok(mut written) = WRITTEN.lock() {
+        written.retain(|(written_path, _)| written_path != path);
+        written.push((path.to_path_buf(), text.clone()));
+    }
+    write_atomically(&backup_path(path), &text)
+}
+
+/// Whether `path` holds what this process last wrote to it, so a change
+/// seen on disk is its own save rather than an edit elsewhere.
+pub fn is_own_write(path: &Path) -> bool {
+    let Ok(text) = fs::read_to_string(path) else {
+        return false;
+    };
+    WRITTEN.lock().is_ok_and(|written| written.iter().any(|(p, t)| p == path && *t == text))
+}
+
+pub fn write_main_config(conf: &Ini) {
+    if let Some(path) = config_path() {
+        let _ = write_ini(&path, conf);
+    }
+}
+
+/// Where changes go, with its current contents: the active profile's file,
+/// or config.toml.
+fn save_target() -> Option<(PathBuf, Ini)> {
+    match profiles::active_profile().and_then(|name| profiles::profile_path(&name)) {
+        Some(path) => Some((path.clone(), load_ini(&path))),
+        None => config_path().map(|path| (path, load_main_config())),
+    }
+}
+
+/// Updates a single key in the `[settings]` section, keeping the others,
+/// of the active profile if there is one.
+pub fn save_setting(key: &str, value: &str) {
+    if let Some((path, mut conf)) = save_target() {
+        conf.set("settings", key, Some(value.to_string()));
+        let _ = write_ini(&path, &conf);
+    }
+}
+
+/// The default of the on/off option `key` (see [`FLAG_DEFAULTS`]).
+fn flag_default(key: &str) -> bool {
+    FLAG_DEFAULTS.iter().find(|(k, _)| *k == key).is_some_and(|(_, default)| *default)
+}
+
+/// `key`, one of the on/off options, as `conf` has it or its default.
+fn flag_in(conf: &Ini, key: &str) -> bool {
+    conf.getbool("settings", key).ok().flatten().unwrap_or_else(|| flag_default(key))
+}
+
+/// Whether text may arrive from other machines (the MQTT trigger mode).
+/// Off unless `allow_remote_text = true` is set explicitly.
+pub fn remote_text_allowed() -> bool {
+    flag_in(&load_config(), "allow_remote_text")
+}
+
+/// `comment_prefixes` as written in the settings, or the default.
+pub fn load_comment_prefixes_setting() -> String {
+    comment_prefixes_in(&load_config())
+}
+
+fn comment_prefixes_in(conf: &Ini) -> String {
+    conf.get("settings", "comment_prefixes").unwrap_or_else(|| DEFAULT_COMMENT_PREFIXES.to_string())
+}
+
+/// The program `{PASS:…}` runs, `pass` unless `pass_command` names
+/// another, such as gopass or a full path.
+pub fn load_pass_command() -> String {
+    pass_command_in(&load_config())
+}
+
+fn pass_command_in(conf: &Ini) -> String {
+    conf.get("settings", "pass_command")
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty())
+        .unwrap_or_else(|| DEFAULT_PASS_COMMAND.to_string())
+}
+
+/// What marks a `--stream` control line: `stream_sentinel`, without
+/// spaces, or [`DEFAULT_SENTINEL`].
+pub fn load_stream_sentinel() -> String {
+    stream_sentinel_in(&load_config())
+}
+
+fn stream_sentinel_in(conf: &Ini) -> String {
+    conf.get("settings", "stream_sentinel")
+        .map(|sentinel| sentinel.trim().to_string())
+        .filter(|sentinel| !sentinel.is_empty() && !sentinel.contains(char::is_whitespace))
+        .unwrap_or_else(|| DEFAULT_SENTINEL.to_string())
+}
+
+/// The prefixes `secret_patterns` adds to those the secret scan knows (see
+/// `secret_scan`), separated by commas or spaces.
+pub fn load_secret_patterns() -> Vec<String> {
+    parse_prefixes(&load_config().get("settings", "secret_patterns").unwrap_or_default())
+}
+
+/// The chords a run read back ends with: `verify_select_chord` and
+/// `verify_copy_chord`, or Ctrl+A and Ctrl+C for one that is not set or not
+/// a shortcut ([`config_problems`] reports it).
+pub fn load_readback_chords() -> Readback {
+    readback_chords_in(&load_config())
+}
+
+fn readback_chords_in(conf: &Ini) -> Readback {
+    let chord = |key: &str, default: Chord| {
+        conf.get("settings", key).and_then(|text| Chord::parse(&text).ok()).unwrap_or(default)
+    };
+    let defaults = Readback::default();
+    Readback { select_all: chord("verify_select_chord", defaults.select_all), copy: chord("verify_copy_chord", defaults.copy) }
+}
+
+/// The bearer token "Load from URL…" and `--url` send, if `url_token` sets
+/// one.
+pub fn load_url_token() -> Option<String> {
+    Some(Settings::load().url_token).filter(|token| !token.is_empty())
+}
+
+/// The machine runs are sent to, when `remote_typing` is on. Having it on
+/// without a usable `remote_host` is an error, rather than typing here.
+fn load_remote_setting(conf: &Ini) -> anyhow::Result<Option<RemoteAddress>> {
+    if !conf.getbool("settings", "remote_typing").ok().flatten().unwrap_or(false) {
+        return Ok(None);
+    }
+    match conf.get("settings", "remote_host").map(|host| host.trim().to_string()).filter(|host| !host.is_empty()) {
+        Some(host) => {
+            RemoteAddress::parse(&host).map(Some).map_err(|e| anyhow::anyhow!("Invalid remote_host '{}': {}", host, e))
+        }
+        None => bail!("remote_typing is on but remote_host is not set"),
+    }
+}
+
+/// The configured backend; `None` means pick automatically.
+pub fn load_backend_setting() -> anyhow::Result<Option<Backend>> {
+    backend_in(&load_config())
+}
+
+fn backend_in(conf: &Ini) -> anyhow::Result<Option<Backend>> {
+    match conf.get("settings", "backend") {
+        None => Ok(None),
+        Some(name) if name.trim().eq_ignore_ascii_case("auto") => Ok(None),
+        Some(name) => name.parse().map(Some),
+    }
+}
+
+/// Backend, speed and line-break key for the next run, from the settings.
+/// Pauses above the maximum are clamped to it, and unreadable pauses and
+/// keys fall back to the defaults ([`config_problems`] reports both); only
+/// an unknown backend and remote typing without a remote are errors.
+pub fn load_typing_options() -> anyhow::Result<TypingOptions> {
+    Ok(TypingOptions { keymap: load_keymap(), ..typing_options_in(&load_config())? })
+}
+
+/// The key overrides in config.toml's `[keymap]`; those that cannot be
+/// read are logged and left out.
+pub fn load_keymap() -> Keymap {
+    let Some(text) = config_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Keymap::default();
+    };
+    let entries = match config_toml::read_table(&text, "keymap") {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("The [keymap] overrides were not read: {}", e);
+            return Keymap::default();
+        }
+    };
+    let (keymap, problems) = Keymap::from_entries(&entries);
+    for problem in problems {
+        log::warn!("Keymap override left out: {}", problem);
+    }
+    keymap
+}
+
+fn typing_options_in(conf: &Ini) -> anyhow::Result<TypingOptions> {
+    let settings = Settings::from_config(conf);
+    let defaults = TypingOptions::default();
+    Ok(TypingOptions {
+        backend: backend_in(conf)?,
+        char_delay: Duration::from_millis(u64::from(settings.char_delay_ms)),
+        newline_delay: Duration::from_millis(u64::from(settings.newline_delay_ms)),
+        enter_key: settings.enter_key,
+        event_timestamps: settings.event_timestamps,
+        code_margin: Duration::from_secs(settings.totp_margin_seconds),
+        remote: load_remote_setting(conf)?,
+        readback: settings
+            .verify_readback
+            .then_some(Readback { select_all: settings.verify_select_chord, copy: settings.verify_copy_chord }),
+        ..defaults
+    })
+}
+
+/// A number of milliseconds for `key`, capped at [`MAX_CHAR_DELAY_MS`].
+fn millis_in(conf: &Ini, key: &str, default: Duration) -> u32 {
+    match conf.get("settings", key).map(|v| v.trim().parse::<u32>()) {
+        Some(Ok(ms)) => ms.min(MAX_CHAR_DELAY_MS),
+        _ => default.as_millis() as u32,
+    }
+}
+
+/// Saves `delay` as `delay_seconds` if it is a valid countdown, so a bad
+/// value is never written; saving one replaces whatever was there, which
+/// repairs a value that [`load_delay_setting`] could not read.
+pub fn save_settings(delay: &str) -> anyhow::Result<f64> {
+    match save_target() {
+        Some((path, conf)) => save_delay_in(&path, conf, delay),
+        None => parse_delay(delay),
+    }
+}
+
+/// [`save_settings`] into the file at `path`, which holds `conf`.
+fn save_delay_in(path: &Path, mut conf: Ini, delay: &str) -> anyhow::Result<f64> {
+    let delay = parse_delay(delay)?;
+    conf.set("settings", "delay_seconds", Some(delay.to_string()));
+    let _ = write_ini(path, &conf);
+    Ok(delay)
+}
+
+/// A countdown as typed by the user: a number of seconds up to the
+/// maximum, such as 3 or 2.5. Unlike [`parse_delay_lenient`], nothing else
+/// is accepted.
+pub fn parse_delay(value: &str) -> anyhow::Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(delay) if (0.0..=MAX_DELAY_SECONDS as f64).contains(&delay) => Ok(delay),
+        _ => bail!("Invalid delay (must be a number from 0–{})", MAX_DELAY_SECONDS),
+    }
+}
+
+/// Reads a delay the way older versions may have saved it: the leading
+/// number counts, so "3s" is 3, and anything above the maximum is capped.
+/// `None` if the value does not start with a digit.
+pub fn parse_delay_lenient(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let whole = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    if whole == 0 {
+        return None;
+    }
+    let fraction = value[whole..]
+        .strip_prefix('.')
+        .map_or(0, |rest| 1 + rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len()));
+    let max = MAX_DELAY_SECONDS as f64;
+    Some(value[..whole + fraction].parse::<f64>().map_or(max, |delay| delay.min(max)))
+}
+
+/// The whole seconds the countdown for a delay of `seconds` counts: a
+/// fraction rounds up, so typing never starts early.
+pub fn countdown_seconds(seconds: f64) -> u64 {
+    seconds.max(0.0).ceil() as u64
+}
+
+/// The configured countdown in whole seconds (see [`countdown_seconds`]),
+/// read leniently (see [`parse_delay_lenient`]).
+pub fn load_delay_setting() -> anyhow::Result<u64> {
+    delay_in(&load_config()).map(countdown_seconds)
+}
+
+fn delay_in(conf: &Ini) -> anyhow::Result<f64> {
+    match parse_delay_lenient(&conf.get("settings", "delay_seconds").unwrap_or_else(|| "3".to_string())) {
+        Some(delay) => Ok(delay),
+        None => bail!("Invalid delay_seconds (must be a number from 0–{})", MAX_DELAY_SECONDS),
+    }
+}
+
+pub fn load_settings() -> String {
+    load_config().get("settings", "delay_seconds").unwrap_or_else(|| "3".to_string())
+}
+
+/// Whether the window follows the desktop's style or is forced light or dark.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Appearance {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    pub const ALL: [Appearance; 3] = [Appearance::System, Appearance::Light, Appearance::Dark];
+
+    /// The config.toml value.
+    pub fn name(self) -> &'static str {
+        match self {
+            Appearance::System => "system",
+            Appearance::Light => "light",
+            Appearance::Dark => "dark",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Appearance::System => "Follow the system",
+            Appearance::Light => "Light",
+            Appearance::Dark => "Dark",
+        }
+    }
+}
+
+/// The configured appearance; unknown values follow the system.
+pub fn load_appearance_setting() -> Appearance {
+    appearance_in(&load_config())
+}
+
+fn appearance_in(conf: &Ini) -> Appearance {
+    Appearance::from_name(&conf.get("settings", "appearance").unwrap_or_default()).unwrap_or_default()
+}
+
+/// The screen corner the countdown overlay sits in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlayCorner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayCorner {
+    pub const ALL: [OverlayCorner; 4] =
+        [OverlayCorner::TopLeft, OverlayCorner::TopRight, OverlayCorner::BottomLeft, OverlayCorner::BottomRight];
+
+    /// The config.toml value.
+    pub fn name(self) -> &'static str {
+        match self {
+            OverlayCorner::TopLeft => "top-left",
+            OverlayCorner::TopRight => "top-right",
+            OverlayCorner::BottomLeft => "bottom-left",
+            OverlayCorner::BottomRight => "bottom-right",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OverlayCorner::TopLeft => "Top left",
+            OverlayCorner::TopRight => "Top right",
+            OverlayCorner::BottomLeft => "Bottom left",
+            OverlayCorner::BottomRight => "Bottom right",
+        }
+    }
+}
+
+/// The configured overlay corner; unknown values mean top right.
+pub fn load_overlay_corner_setting() -> OverlayCorner {
+    overlay_corner_in(&load_config())
+}
+
+fn overlay_corner_in(conf: &Ini) -> OverlayCorner {
+    OverlayCorner::from_name(&conf.get("settings", "overlay_corner").unwrap_or_default()).unwrap_or_default()
+}
+
+/// How many seconds before typing starts the countdown notification is
+/// sent; 0 sends none.
+pub fn load_notify_before_setting() -> u64 {
+    notify_before_in(&load_config())
+}
+
+fn notify_before_in(conf: &Ini) -> u64 {
+    conf.get("settings", "notify_before_seconds")
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map_or(3, |seconds| seconds.min(MAX_DELAY_SECONDS))
+}
+
+fn save_millis(key: &str, value: &str) -> anyhow::Result<u32> {
+    match value.trim().parse::<u32>() {
+        Ok(ms) if ms <= MAX_CHAR_DELAY_MS => {
+            save_setting(key, &ms.to_string());
+            Ok(ms)
+        }
+        _ => bail!("Must be a number from 0–{}", MAX_CHAR_DELAY_MS),
+    }
+}
+
+/// The on/off options and their defaults, for [`config_problems`].
+const FLAG_DEFAULTS: [(&str, bool); 28] = [
+    ("allow_commands", false),
+    ("allow_remote_text", false),
+    ("assistive_mode", false),
+    ("confirm_enter", false),
+    ("countdown_overlay", false),
+    ("event_timestamps", false),
+    ("expand_variables", false),
+    ("fix_smart_punctuation", true),
+    ("inhibit_idle_countdown", false),
+    ("mark_skipped", true),
+    ("minimize_on_start", false),
+    ("monospace", false),
+    ("notifications", true),
+    ("quiet_countdown", false),
+    ("remember_recent", true),
+    ("remote_typing", false),
+    ("restore_draft", false),
+    ("resume_from_line_start", true),
+    ("secret_scan", true),
+    ("skip_blank_lines", false),
+    ("sounds", false),
+    ("strict_variables", false),
+    ("strip_comments", false),
+    ("templates", false),
+    ("tray_icon", false),
+    ("verify_readback", false),
+    ("watch_enabled", false),
+    ("word_wrap", true),
+];
+
+/// A line for each value in `conf`'s `[settings]` that is not used as
+/// written, saying what is used instead: numbers that do not parse or are
+/// above their maximum, unknown names, and on/off options that are neither
+/// `true` nor `false`. Keys this version does not know are left alone.
+pub fn config_problems(conf: &Ini) -> Vec<String> {
+    let mut problems = Vec::new();
+    let value = |key: &str| conf.get("settings", key).map(|v| v.trim().to_string());
+
+    let defaults = TypingOptions::default();
+    let numbers = [
+        ("delay_seconds", MAX_DELAY_SECONDS, 3),
+        ("notify_before_seconds", MAX_DELAY_SECONDS, 3),
+        ("char_delay_ms", u64::from(MAX_CHAR_DELAY_MS), defaults.char_delay.as_millis() as u64),
+        ("newline_delay_ms", u64::from(MAX_CHAR_DELAY_MS), defaults.newline_delay.as_millis() as u64),
+        ("totp_margin_seconds", MAX_MARGIN_SECONDS, defaults.code_margin.as_secs()),
+        ("libvirt_hold_ms", MAX_LIBVIRT_HOLD_MS, DEFAULT_LIBVIRT_HOLD_MS),
+    ];
+    for (key, max, default) in numbers {
+        let Some(text) = value(key) else {
+            continue;
+        };
+        match text.parse::<u64>() {
+            Ok(number) if number > max => {
+                problems.push(format!("{} = {} is above the maximum; using {}.", key, text, max));
+            }
+            Ok(_) => {}
+            // Older versions saved delays like "3s".
+            Err(_) if key == "delay_seconds" && parse_delay_lenient(&text).is_some() => {}
+            Err(_) => {
+                problems.push(format!("{} = {} is not a number from 0–{}; using {}.", key, text, max, default));
+            }
+        }
+    }
+
+    if let Some(text) = value("http_port").filter(|port| !port.is_empty() && port != "0") {
+        if text.parse::<u16>().is_err() {
+            problems.push(format!("http_port = {} is not a port number from 1–65535; the HTTP endpoint stays off.", text));
+        }
+    }
+
+    for (key, default) in FLAG_DEFAULTS {
+        if conf.getbool("settings", key).is_err() {
+            let text = value(key).unwrap_or_default();
+            problems.push(format!("{} = {} is neither true nor false; using {}.", key, text, default));
+        }
+    }
+
+    let mut name = |key: &str, names: &[&str], default: &str| {
+        let Some(text) = value(key) else {
+            return;
+        };
+        if !names.iter().any(|name| name.eq_ignore_ascii_case(&text)) {
+            problems.push(format!("{} = {} is not one of {}; using {}.", key, text, names.join(", "), default));
+        }
+    };
+    let enter_keys: Vec<&str> = EnterKey::ALL.iter().map(|key| key.name()).collect();
+    name("enter_key", &enter_keys, defaults.enter_key.name());
+    let appearances: Vec<&str> = Appearance::ALL.iter().map(|a| a.name()).collect();
+    name("appearance", &appearances, Appearance::default().name());
+    let corners: Vec<&str> = OverlayCorner::ALL.iter().map(|c| c.name()).collect();
+    name("overlay_corner", &corners, OverlayCorner::default().name());
+
+    for (key, default) in [("verify_select_chord", DEFAULT_SELECT_CHORD), ("verify_copy_chord", DEFAULT_COPY_CHORD)] {
+        if let Some(text) = value(key) {
+            if let Err(e) = Chord::parse(&text) {
+                problems.push(format!("{} = {} is not a shortcut ({}); using {}.", key, text, e, default));
+            }
+        }
+    }
+
+    if let Some(text) = value("stream_sentinel").filter(|s| !s.is_empty() && s.contains(char::is_whitespace)) {
+        problems.push(format!("stream_sentinel = {} contains spaces; using {}.", text, DEFAULT_SENTINEL));
+    }
+
+    if let Some(name) = value("active_profile").filter(|name| !name.is_empty()) {
+        if profiles::active_profile().is_none() {
+            problems.push(format!("active_profile = {} has no file in the profiles folder; using config.toml alone.", name));
+        }
+    }
+
+    // Runs refuse an unknown backend rather than pick another one.
+    if let Some(text) = value("backend") {
+        if !text.eq_ignore_ascii_case("auto") && text.parse::<Backend>().is_err() {
+            let names: Vec<&str> = Backend::ALL.iter().map(|b| b.name()).collect();
+            problems.push(format!("backend = {} is not auto or one of {}; typing fails until it is fixed.", text, names.join(", ")));
+        }
+    }
+    if let Some(text) = value("remote_host").filter(|host| !host.is_empty()) {
+        if let Err(e) = RemoteAddress::parse(&text) {
+            problems.push(format!("remote_host = {} is not usable ({}); remote typing fails until it is fixed.", text, e));
+        }
+    }
+    problems
+}
+
+/// Every `[settings]` key this version reads, except `active_profile`.
+pub const KNOWN_KEYS: [&str; 61] = [
+    "allow_commands",
+    "allow_remote_text",
+    "appearance",
+    "assistive_mode",
+    "backend",
+    "char_delay_ms",
+    "comment_prefixes",
+    "confirm_enter",
+    "countdown_overlay",
+    "delay_seconds",
+    "editor_font_size",
+    "enter_key",
+    "event_timestamps",
+    "expand_variables",
+    "fix_smart_punctuation",
+    "http_port",
+    "http_token",
+    "inhibit_idle_countdown",
+    "libvirt_domain",
+    "libvirt_hold_ms",
+    "libvirt_uri",
+    "mark_skipped",
+    "minimize_on_start",
+    "monospace",
+    "mqtt_broker",
+    "mqtt_ca_file",
+    "mqtt_client_id",
+    "mqtt_password",
+    "mqtt_status_topic",
+    "mqtt_topic",
+    "mqtt_username",
+    "newline_delay_ms",
+    "notifications",
+    "notify_before_seconds",
+    "overlay_corner",
+    "pass_command",
+    "quiet_countdown",
+    "remember_recent",
+    "remote_ca_file",
+    "remote_host",
+    "remote_token",
+    "remote_typing",
+    "restore_draft",
+    "resume_from_line_start",
+    "secret_patterns",
+    "secret_scan",
+    "skip_blank_lines",
+    "sounds",
+    "strict_variables",
+    "stream_sentinel",
+    "strip_comments",
+    "templates",
+    "totp_margin_seconds",
+    "tray_icon",
+    "url_token",
+    "verify_copy_chord",
+    "verify_readback",
+    "verify_select_chord",
+    "watch_directory",
+    "watch_enabled",
+    "word_wrap",
+];
+
+/// `--set` overrides in effect for the run in progress; see
+/// [`set_overrides`].
+static OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Parses `--set KEY=VALUE` arguments, checking each as config.toml's
+/// values are checked, and returns a message for every one that is not
+/// used as written.
+pub fn parse_overrides(args: &[String]) -> Result<Vec<(String, String)>, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut overrides: Vec<(String, String)> = Vec::new();
+    for arg in args {
+        let Some((key, value)) = arg.split_once('=') else {
+            errors.push(format!("--set {}: expected KEY=VALUE", arg));
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        if key == "active_profile" {
+            errors.push("--set active_profile: use --profile to switch profiles".to_string());
+        } else if !KNOWN_KEYS.contains(&key.as_str()) {
+            errors.push(format!("--set {}: there is no such setting", key));
+        } else {
+            overrides.retain(|(k, _)| *k != key);
+            overrides.push((key, value.trim().to_string()));
+        }
+    }
+    let mut conf = Ini::new();
+    for (key, value) in &overrides {
+        conf.set("settings", key, Some(value.clone()));
+    }
+    // The loader's messages end with the fallback it uses ("; using 3."),
+    // which an override does not get.
+    for problem in config_problems(&conf) {
+        let problem = problem.split_once("; ").map_or(problem.as_str(), |(problem, _)| problem);
+        errors.push(format!("--set {}", problem.trim_end_matches('.')));
+    }
+    if errors.is_empty() {
+        Ok(overrides)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Puts `overrides` on top of the settings until [`clear_overrides`], for
+/// the one run they were given for. Nothing is written to config.toml.
+pub fn set_overrides(overrides: Vec<(String, String)>) {
+    if let Ok(mut current) = OVERRIDES.lock() {
+        *current = overrides;
+    }
+}
+
+pub fn clear_overrides() {
+    set_overrides(Vec::new());
+}
+
+/// Whether `--set` gave `key` for the run in progress.
+pub fn has_override(key: &str) -> bool {
+    OVERRIDES.lock().is_ok_and(|overrides| overrides.iter().any(|(k, _)| k == key))
+}
+
+/// Every `[settings]` key, typed, so the window, the command line and the
+/// D-Bus interface read the same values with the same defaults. `load`
+/// reads the settings once and tolerates bad values (falling back to
+/// defaults, and clamping numbers that are too large); each setter
+/// validates its input and writes config.toml immediately, so the next run
+/// picks the change up. `save` writes them all at once. Text settings are
+/// empty when they are not set. With serde, it is config.toml's
+/// `[settings]` table (see [`config_toml`]).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// In seconds, a fraction allowed (see [`countdown_seconds`]).
+    #[serde(serialize_with = "whole_as_integer")]
+    pub delay_seconds: f64,
+    pub expand_variables: bool,
+    pub strict_variables: bool,
+    pub skip_blank_lines: bool,
+    pub strip_comments: bool,
+    pub fix_smart_punctuation: bool,
+    pub comment_prefixes: String,
+    pub templates: bool,
+    pub restore_draft: bool,
+    pub remember_recent: bool,
+    pub secret_scan: bool,
+    pub secret_patterns: String,
+    #[serde(with = "by_name")]
+    pub backend: Option<Backend>,
+    pub char_delay_ms: u32,
+    pub newline_delay_ms: u32,
+    #[serde(with = "by_name")]
+    pub enter_key: EnterKey,
+    pub event_timestamps: bool,
+    pub totp_margin_seconds: u64,
+    pub pass_command: String,
+    pub stream_sentinel: String,
+    pub verify_readback: bool,
+    #[serde(with = "by_name")]
+    pub verify_select_chord: Chord,
+    #[serde(with = "by_name")]
+    pub verify_copy_chord: Chord,
+    pub resume_from_line_start: bool,
+    pub libvirt_domain: String,
+    pub libvirt_uri: String,
+    pub libvirt_hold_ms: u64,
+    pub allow_commands: bool,
+    pub allow_remote_text: bool,
+    pub watch_enabled: bool,
+    pub watch_directory: String,
+    pub http_port: Option<u16>,
+    pub http_token: String,
+    pub url_token: String,
+    pub mqtt_broker: String,
+    pub mqtt_topic: String,
+    pub mqtt_status_topic: String,
+    pub mqtt_username: String,
+    pub mqtt_password: String,
+    pub mqtt_ca_file: String,
+    pub mqtt_client_id: String,
+    pub remote_typing: bool,
+    pub remote_host: String,
+    pub remote_token: String,
+    pub remote_ca_file: String,
+    pub notifications: bool,
+    pub notify_before_seconds: u64,
+    pub quiet_countdown: bool,
+    pub inhibit_idle_countdown: bool,
+    pub assistive_mode: bool,
+    pub confirm_enter: bool,
+    pub countdown_overlay: bool,
+    #[serde(with = "by_name")]
+    pub overlay_corner: OverlayCorner,
+    pub sounds: bool,
+    pub tray_icon: bool,
+    pub minimize_on_start: bool,
+    pub mark_skipped: bool,
+    pub monospace: bool,
+    pub word_wrap: bool,
+    /// In points; 0 is the theme's size.
+    pub editor_font_size: u32,
+    #[serde(with = "by_name")]
+    pub appearance: Appearance,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings::from_config(&Ini::new())
+    }
+}
+
+/// A whole number of seconds as an integer, so config.toml says
+/// `delay_seconds = 3` rather than `3.0`.
+fn whole_as_integer<S: Serializer>(seconds: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if seconds.fract() == 0.0 && (0.0..=MAX_DELAY_SECONDS as f64).contains(seconds) {
+        serializer.serialize_u64(*seconds as u64)
+    } else {
+        serializer.serialize_f64(*seconds)
+    }
+}
+
+/// The settings written as a name, as [`Settings::entries`] writes them.
+trait Named: Sized {
+    fn to_name(&self) -> String;
+    fn from_name(name: &str) -> Option<Self>;
+}
+
+impl Named for Option<Backend> {
+    fn to_name(&self) -> String {
+        self.map_or("auto", Backend::name).to_string()
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            auto if auto.eq_ignore_ascii_case("auto") => Some(None),
+            name => name.parse().ok().map(Some),
+        }
+    }
+}
+
+impl Named for EnterKey {
+    fn to_name(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        EnterKey::parse(name)
+    }
+}
+
+impl Named for Chord {
+    fn to_name(&self) -> String {
+        self.keysequence().to_string()
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Chord::parse(name).ok()
+    }
+}
+
+impl Named for Appearance {
+    fn to_name(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Appearance::ALL.into_iter().find(|a| a.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl Named for OverlayCorner {
+    fn to_name(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        OverlayCorner::ALL.into_iter().find(|c| c.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Serde for the [`Named`] settings; an unknown name is an error.
+mod by_name {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::Named;
+
+    pub fn serialize<T: Named, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_name())
+    }
+
+    pub fn deserialize<'de, T: Named, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        T::from_name(&name).ok_or_else(|| D::Error::custom(format!("'{}' is not a name it can have", name)))
+    }
+}
+
+impl Settings {
+    /// The settings in effect (see [`load_config`]).
+    pub fn load() -> Settings {
+        Settings::from_config(&load_config())
+    }
+
+    /// The settings in `conf`'s `[settings]`.
+    pub fn from_config(conf: &Ini) -> Settings {
+        let text = |key: &str| conf.get("settings", key).map(|v| v.trim().to_string()).unwrap_or_default();
+        let flag = |key: &str| flag_in(conf, key);
+        let defaults = TypingOptions::default();
+        let readback = readback_chords_in(conf);
+        Settings {
+            delay_seconds: delay_in(conf).unwrap_or(3.0),
+            expand_variables: flag("expand_variables"),
+            strict_variables: flag("strict_variables"),
+            skip_blank_lines: flag("skip_blank_lines"),
+            strip_comments: flag("strip_comments"),
+            fix_smart_punctuation: flag("fix_smart_punctuation"),
+            comment_prefixes: comment_prefixes_in(conf),
+            templates: flag("templates"),
+            restore_draft: flag("restore_draft"),
+            remember_recent: flag("remember_recent"),
+            secret_scan: flag("secret_scan"),
+            secret_patterns: text("secret_patterns"),
+            backend: backend_in(conf).unwrap_or(None),
+            char_delay_ms: millis_in(conf, "char_delay_ms", defaults.char_delay),
+            newline_delay_ms: millis_in(conf, "newline_delay_ms", defaults.newline_delay),
+            enter_key: EnterKey::parse(&text("enter_key")).unwrap_or(defaults.enter_key),
+            event_timestamps: flag("event_timestamps"),
+            totp_margin_seconds: text("totp_margin_seconds")
+                .parse::<u64>()
+                .map_or(defaults.code_margin.as_secs(), |seconds| seconds.min(MAX_MARGIN_SECONDS)),
+            pass_command: pass_command_in(conf),
+            stream_sentinel: stream_sentinel_in(conf),
+            verify_readback: flag("verify_readback"),
+            verify_select_chord: readback.select_all,
+            verify_copy_chord: readback.copy,
+            resume_from_line_start: flag("resume_from_line_start"),
+            libvirt_domain: text("libvirt_domain"),
+            libvirt_uri: text("libvirt_uri"),
+            libvirt_hold_ms: text("libvirt_hold_ms")
+                .parse::<u64>()
+                .map_or(DEFAULT_LIBVIRT_HOLD_MS, |ms| ms.min(MAX_LIBVIRT_HOLD_MS)),
+            allow_commands: flag("allow_commands"),
+            allow_remote_text: flag("allow_remote_text"),
+            watch_enabled: flag("watch_enabled"),
+            watch_directory: text("watch_directory"),
+            http_port: text("http_port").parse().ok().filter(|p| *p > 0),
+            http_token: text("http_token"),
+            url_token: text("url_token"),
+            mqtt_broker: text("mqtt_broker"),
+            mqtt_topic: text("mqtt_topic"),
+            mqtt_status_topic: text("mqtt_status_topic"),
+            mqtt_username: text("mqtt_username"),
+            mqtt_password: text("mqtt_password"),
+            mqtt_ca_file: text("mqtt_ca_file"),
+            mqtt_client_id: text("mqtt_client_id"),
+            remote_typing: flag("remote_typing"),
+            remote_host: text("remote_host"),
+            remote_token: text("remote_token"),
+            remote_ca_file: text("remote_ca_file"),
+            notifications: flag("notifications"),
+            notify_before_seconds: notify_before_in(conf),
+            quiet_countdown: flag("quiet_countdown"),
+            inhibit_idle_countdown: flag("inhibit_idle_countdown"),
+            assistive_mode: flag("assistive_mode"),
+            confirm_enter: flag("confirm_enter"),
+            countdown_overlay: flag("countdown_overlay"),
+            overlay_corner: overlay_corner_in(conf),
+            sounds: flag("sounds"),
+            tray_icon: flag("tray_icon"),
+            minimize_on_start: flag("minimize_on_start"),
+            mark_skipped: flag("mark_skipped"),
+            monospace: flag("monospace"),
+            word_wrap: flag("word_wrap"),
+            editor_font_size: text("editor_font_size").parse().unwrap_or(0),
+            appearance: appearance_in(conf),
+        }
+    }
+
+    /// Like [`Settings::load`], with a message for each value in
+    /// the settings that is not used as written (see [`config_problems`]),
+    /// led by one if the file was unreadable and its backup was used.
+    pub fn load_with_warnings() -> (Settings, Vec<String>) {
+        let (mut conf, from_backup) = load_config_with_source();
+        profiles::apply_active(&mut conf);
+        let mut problems = config_problems(&conf);
+        if let Some(path) = main_config_file().filter(|_| from_backup) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            // A syntax error is worth showing where it is.
+            let reason = match fs::read_to_string(&path).map(|text| parse_settings(&path, &text)) {
+                Ok(Err(e)) => format!("could not be read ({:#})", e),
+                _ => "was empty or unreadable".to_string(),
+            };
+            problems.insert(
+                0,
+                format!("{} {}; using its backup {}. The next change saves it again.", name, reason, backup_path(&path).display()),
+            );
+        }
+        // The same overrides as `load_config`'s.
+        if let Ok(overrides) = OVERRIDES.lock() {
+            for (key, value) in overrides.iter() {
+                conf.set("settings", key, Some(value.clone()));
+            }
+        }
+        (Settings::from_config(&conf), problems)
+    }
+
+    /// The settings keys whose values differ between `self` and `other`.
+    pub fn changed_keys(&self, other: &Settings) -> Vec<&'static str> {
+        self.entries().into_iter().zip(other.entries()).filter(|(a, b)| a.1 != b.1).map(|(a, _)| a.0).collect()
+    }
+
+    /// Every field as its settings key and value, in file order.
+    pub(crate) fn entries(&self) -> Vec<(&'static str, String)> {
+        let flag = |value: bool| value.to_string();
+        vec![
+            ("delay_seconds", self.delay_seconds.to_string()),
+            ("expand_variables", flag(self.expand_variables)),
+            ("strict_variables", flag(self.strict_variables)),
+            ("skip_blank_lines", flag(self.skip_blank_lines)),
+            ("strip_comments", flag(self.strip_comments)),
+            ("fix_smart_punctuation", flag(self.fix_smart_punctuation)),
+            ("comment_prefixes", self.comment_prefixes.clone()),
+            ("templates", flag(self.templates)),
+            ("restore_draft", flag(self.restore_draft)),
+            ("remember_recent", flag(self.remember_recent)),
+            ("secret_scan", flag(self.secret_scan)),
+            ("secret_patterns", self.secret_patterns.clone()),
+            ("backend", self.backend.map_or("auto", Backend::name).to_string()),
+            ("char_delay_ms", self.char_delay_ms.to_string()),
+            ("newline_delay_ms", self.newline_delay_ms.to_string()),
+            ("enter_key", self.enter_key.name().to_string()),
+            ("event_timestamps", flag(self.event_timestamps)),
+            ("totp_margin_seconds", self.totp_margin_seconds.to_string()),
+            ("pass_command", self.pass_command.clone()),
+            ("stream_sentinel", self.stream_sentinel.clone()),
+            ("verify_readback", flag(self.verify_readback)),
+            ("verify_select_chord", self.verify_select_chord.keysequence().to_string()),
+            ("verify_copy_chord", self.verify_copy_chord.keysequence().to_string()),
+            ("resume_from_line_start", flag(self.resume_from_line_start)),
+            ("libvirt_domain", self.libvirt_domain.clone()),
+            ("libvirt_uri", self.libvirt_uri.clone()),
+            ("libvirt_hold_ms", self.libvirt_hold_ms.to_string()),
+            ("allow_commands", flag(self.allow_commands)),
+            ("allow_remote_text", flag(self.allow_remote_text)),
+            ("watch_enabled", flag(self.watch_enabled)),
+            ("watch_directory", self.watch_directory.clone()),
+            ("http_port", self.http_port.map(|port| port.to_string()).unwrap_or_default()),
+            ("http_token", self.http_token.clone()),
+            ("url_token", self.url_token.clone()),
+            ("mqtt_broker", self.mqtt_broker.clone()),
+            ("mqtt_topic", self.mqtt_topic.clone()),
+            ("mqtt_status_topic", self.mqtt_status_topic.clone()),
+            ("mqtt_username", self.mqtt_username.clone()),
+            ("mqtt_password", self.mqtt_password.clone()),
+            ("mqtt_ca_file", self.mqtt_ca_file.clone()),
+            ("mqtt_client_id", self.mqtt_client_id.clone()),
+            ("remote_typing", flag(self.remote_typing)),
+            ("remote_host", self.remote_host.clone()),
+            ("remote_token", self.remote_token.clone()),
+            ("remote_ca_file", self.remote_ca_file.clone()),
+            ("notifications", flag(self.notifications)),
+            ("notify_before_seconds", self.notify_before_seconds.to_string()),
+            ("quiet_countdown", flag(self.quiet_countdown)),
+            ("inhibit_idle_countdown", flag(self.inhibit_idle_countdown)),
+            ("assistive_mode", flag(self.assistive_mode)),
+            ("confirm_enter", flag(self.confirm_enter)),
+            ("countdown_overlay", flag(self.countdown_overlay)),
+            ("overlay_corner", self.overlay_corner.name().to_string()),
+            ("sounds", flag(self.sounds)),
+            ("tray_icon", flag(self.tray_icon)),
+            ("minimize_on_start", flag(self.minimize_on_start)),
+            ("mark_skipped", flag(self.mark_skipped)),
+            ("monospace", flag(self.monospace)),
+            ("word_wrap", flag(self.word_wrap)),
+            ("editor_font_size", self.editor_font_size.to_string()),
+            ("appearance", self.appearance.name().to_string()),
+        ]
+    }
+
+    /// Writes the settings that differ from those in effect to config.toml
+    /// (or the active profile), keeping
+    /// every other key and section: unknown ones from newer versions, and
+    /// values this version cannot read, which `load` replaced by defaults.
+    /// Defaults that were never changed are not written, so they can still
+    /// change with new versions.
+    pub fn save(&self) {
+        let Some((path, mut conf)) = save_target() else {
+            return;
+        };
+        let stored = Settings::load().entries();
+        for ((key, value), (_, old)) in self.entries().into_iter().zip(stored) {
+            if value != old {
+                conf.set("settings", key, Some(value));
+            }
+        }
+        let _ = write_ini(&path, &conf);
+    }
+
+    /// Writes just `key`, so a setter cannot put back what another window
+    /// changed since this copy was loaded.
+    fn save_key(&self, key: &str) {
+        if let Some((_, value)) = self.entries().into_iter().find(|(k, _)| *k == key) {
+            save_setting(key, &value);
+        }
+    }
+
+    pub fn set_delay_seconds(&mut self, value: &str) -> anyhow::Result<()> {
+        self.delay_seconds = parse_delay(value).map_err(|_| anyhow::anyhow!("Must be a number from 0–{}", MAX_DELAY_SECONDS))?;
+        self.save_key("delay_seconds");
+        Ok(())
+    }
+
+    pub fn set_notify_before_seconds(&mut self, value: &str) -> anyhow::Result<()> {
+        match value.trim().parse::<u64>() {
+            Ok(seconds) if seconds <= MAX_DELAY_SECONDS => {
+                self.notify_before_seconds = seconds;
+                self.save_key("notify_before_seconds");
+                Ok(())
+            }
+            _ => bail!("Must be a number from 0–{}", MAX_DELAY_SECONDS),
+        }
+    }
+
+    pub fn set_char_delay_ms(&mut self, value: &str) -> anyhow::Result<()> {
+        self.char_delay_ms = save_millis("char_delay_ms", value)?;
+        Ok(())
+    }
+
+    pub fn set_newline_delay_ms(&mut self, value: &str) -> anyhow::Result<()> {
+        self.newline_delay_ms = save_millis("newline_delay_ms", value)?;
+        Ok(())
+    }
+
+    pub fn set_appearance(&mut self, appearance: Appearance) {
+        self.appearance = appearance;
+        self.save_key("appearance");
+    }
+
+    pub fn set_overlay_corner(&mut self, corner: OverlayCorner) {
+        self.overlay_corner = corner;
+        self.save_key("overlay_corner");
+    }
+
+    pub fn set_enter_key(&mut self, key: EnterKey) {
+        self.enter_key = key;
+        self.save_key("enter_key");
+    }
+
+    pub fn set_backend(&mut self, backend: Option<Backend>) {
+        self.backend = backend;
+        self.save_key("backend");
+    }
+
+    /// Sets one of the on/off options by its settings key.
+    pub fn set_flag(&mut self, key: &str, value: bool) -> anyhow::Result<()> {
+        let field = match key {
+            "expand_variables" => &mut self.expand_variables,
+            "strict_variables" => &mut self.strict_variables,
+            "skip_blank_lines" => &mut self.skip_blank_lines,
+            "strip_comments" => &mut self.strip_comments,
+            "fix_smart_punctuation" => &mut self.fix_smart_punctuation,
+            "templates" => &mut self.templates,
+            "restore_draft" => &mut self.restore_draft,
+            "remember_recent" => &mut self.remember_recent,
+            "secret_scan" => &mut self.secret_scan,
+            "verify_readback" => &mut self.verify_readback,
+            "resume_from_line_start" => &mut self.resume_from_line_start,
+            "allow_commands" => &mut self.allow_commands,
+            "allow_remote_text" => &mut self.allow_remote_text,
+            "remote_typing" => &mut self.remote_typing,
+            "watch_enabled" => &mut self.watch_enabled,
+            "notifications" => &mut self.notifications,
+            "quiet_countdown" => &mut self.quiet_countdown,
+            "inhibit_idle_countdown" => &mut self.inhibit_idle_countdown,
+            "assistive_mode" => &mut self.assistive_mode,
+            "confirm_enter" => &mut self.confirm_enter,
+            "countdown_overlay" => &mut self.countdown_overlay,
+            "sounds" => &mut self.sounds,
+            "tray_icon" => &mut self.tray_icon,
+            "event_timestamps" => &mut self.event_timestamps,
+            "minimize_on_start" => &mut self.minimize_on_start,
+            "mark_skipped" => &mut self.mark_skipped,
+            "monospace" => &mut self.monospace,
+            "word_wrap" => &mut self.word_wrap,
+            _ => bail!("Unknown setting '{}'", key),
+        };
+        *field = value;
+        self.save_key(key);
+        Ok(())
+    }
+
+    pub fn set_comment_prefixes(&mut self, value: &str) {
+        self.comment_prefixes = value.trim().to_string();
+        self.save_key("comment_prefixes");
+    }
+
+    pub fn set_watch_directory(&mut self, value: &str) {
+        self.watch_directory = value.trim().to_string();
+        self.save_key("watch_directory");
+    }
+
+    /// An empty value turns the HTTP endpoint off.
+    pub fn set_http_port(&mut self, value: &str) -> anyhow::Result<()> {
+        self.http_port = match value.trim() {
+            "" | "0" => None,
+            value => match value.parse::<u16>() {
+                Ok(port) => Some(port),
+                Err(_) => bail!("Must be a port number from 1–65535, or empty"),
+            },
+        };
+        self.save_key("http_port");
+        Ok(())
+    }
+
+    pub fn set_http_token(&mut self, value: &str) {
+        self.http_token = value.trim().to_string();
+        self.save_key("http_token");
+    }
+
+    /// An empty value means no MQTT at startup.
+    pub fn set_mqtt_broker(&mut self, value: &str) -> anyhow::Result<()> {
+        let value = value.trim();
+        if !value.is_empty() {
+            crate::mqtt_config::parse_broker(value)?;
+        }
+        self.mqtt_broker = value.to_string();
+        self.save_key("mqtt_broker");
+        Ok(())
+    }
+
+    /// An empty value means no remote machine.
+    pub fn set_remote_host(&mut self, value: &str) -> anyhow::Result<()> {
+        let value = value.trim();
+        if !value.is_empty() {
+            RemoteAddress::parse(value)?;
+        }
+        self.remote_host = value.to_string();
+        self.save_key("remote_host");
+        Ok(())
+    }
+
+    pub fn set_remote_token(&mut self, value: &str) {
+        self.remote_token = value.trim().to_string();
+        self.save_key("remote_token");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// An empty folder of its own for each test.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pasteclipboard-settings-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn conf_with(key: &str, value: &str) -> Ini {
+        let mut conf = Ini::new();
+        conf.set("settings", key, Some(value.to_string()));
+        conf
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn every_known_key_is_a_field() {
+        let keys: Vec<_> = Settings::from_config(&Ini::new()).entries().into_iter().map(|(key, _)| key).collect();
+        for key in KNOWN_KEYS {
+            assert!(keys.contains(&key), "{} is not in Settings", key);
+        }
+        assert_eq!(keys.len(), KNOWN_KEYS.len());
+    }
+
+    #[test]
+    fn flags_start_at_their_defaults() {
+        let entries = Settings::from_config(&Ini::new()).entries();
+        for (key, default) in FLAG_DEFAULTS {
+            let value = entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+            assert_eq!(value, Some(if default { "true" } else { "false" }), "{}", key);
+        }
+    }
+
+    #[test]
+    fn values_are_read_once_and_checked() {
+        let mut conf = conf_with("delay_seconds", " 5 ");
+        conf.set("settings", "libvirt_hold_ms", Some("999999".to_string()));
+        conf.set("settings", "http_port", Some("port".to_string()));
+        conf.set("settings", "enter_key", Some("nonsense".to_string()));
+        conf.set("settings", "sounds", Some("TRUE".to_string()));
+        conf.set("settings", "mqtt_topic", Some(" typing ".to_string()));
+        let settings = Settings::from_config(&conf);
+        assert_eq!(settings.delay_seconds, 5.0);
+        assert_eq!(settings.libvirt_hold_ms, MAX_LIBVIRT_HOLD_MS);
+        assert_eq!(settings.http_port, None);
+        assert_eq!(settings.enter_key, TypingOptions::default().enter_key);
+        assert!(settings.sounds);
+        assert_eq!(settings.mqtt_topic, "typing");
+    }
+
+    /// Settings away from their defaults, with values of every kind.
+    fn changed() -> Settings {
+        Settings {
+            delay_seconds: 2.5,
+            backend: Some(Backend::Portal),
+            enter_key: EnterKey::ALL[1],
+            char_delay_ms: 40,
+            sounds: true,
+            word_wrap: false,
+            pass_command: "007".to_string(),
+            verify_copy_chord: Chord::parse("ctrl+shift+c").unwrap(),
+            http_port: Some(8080),
+            overlay_corner: OverlayCorner::BottomLeft,
+            appearance: Appearance::Dark,
+            editor_font_size: 14,
+            ..Settings::default()
+        }
+    }
+
+    fn round_trip(file_name: &str) -> (Settings, String) {
+        let dir = scratch(file_name);
+        let path = dir.join(file_name);
+        let mut conf = Ini::new();
+        for (key, value) in changed().entries() {
+            conf.set("settings", key, Some(value));
+        }
+        write_ini(&path, &conf).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let settings = Settings::from_config(&load_ini(&path));
+        fs::remove_dir_all(dir).unwrap();
+        (settings, text)
+    }
+
+    #[test]
+    fn settings_round_trip_through_config_ini() {
+        let (settings, text) = round_trip("config.ini");
+        assert_eq!(settings, changed());
+        assert!(text.contains("delay_seconds=2.5"), "{}", text);
+    }
+
+    #[test]
+    fn settings_round_trip_through_config_toml() {
+        let (settings, text) = round_trip("config.toml");
+        assert_eq!(settings, changed());
+        for line in ["delay_seconds = 2.5", "sounds = true", "http_port = 8080", "pass_command = \"007\"", "backend = \"portal\""] {
+            assert!(text.contains(line), "{} is not in\n{}", line, text);
+        }
+    }
+
+    #[test]
+    fn delays_may_have_a_fraction() {
+        assert_eq!(parse_delay(" 2.5 ").unwrap(), 2.5);
+        assert!(parse_delay("NaN").is_err() && parse_delay("-1").is_err() && parse_delay("86401").is_err());
+        assert_eq!(parse_delay_lenient("2.5s"), Some(2.5));
+        assert_eq!(parse_delay_lenient("3s"), Some(3.0));
+        assert_eq!(parse_delay_lenient("s"), None);
+        assert_eq!((countdown_seconds(2.5), countdown_seconds(3.0)), (3, 3));
+    }
+
+    #[test]
+    fn settings_files_are_private() {
+        let dir = scratch("private");
+        let path = dir.join("config.toml");
+        fs::write(&path, "[settings]\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_ini(&path, &conf_with("http_token", "secret")).unwrap();
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(&backup_path(&path)), 0o600);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn an_interrupted_save_loses_nothing() {
+        let dir = scratch("interrupted");
+        let path = dir.join("config.toml");
+        write_ini(&path, &conf_with("delay_seconds", "7")).unwrap();
+        // A save that died before its rename leaves only its temporary file.
+        fs::write(dir.join(".config.toml.999.tmp"), "[settings]\ndelay_seconds = 9").unwrap();
+        let (conf, from_backup) = load_with_backup(&path);
+        assert_eq!((conf.get("settings", "delay_seconds").as_deref(), from_backup), (Some("7"), false));
+        // A file truncated by a crash is read from its backup.
+        fs::write(&path, "").unwrap();
+        let (conf, from_backup) = load_with_backup(&path);
+        assert_eq!((conf.get("settings", "delay_seconds").as_deref(), from_backup), (Some("7"), true));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_save_leaves_no_temporary_file() {
+        let dir = scratch("failed");
+        // Renaming a file over a folder fails after the data is written.
+        let path = dir.join("config.toml");
+        fs::create_dir(&path).unwrap();
+        assert!(write_atomically(&path, "[settings]\n").is_err());
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, ["config.toml"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Base paths of a test: a home directory that counts its lookups.
+    struct TestBasePaths {
+        env: Vec<(&'static str, PathBuf)>,
+        portable: Option<PathBuf>,
+        home_lookups: std::cell::Cell<usize>,
+    }
+
+    impl TestBasePaths {
+        fn new(portable: Option<&str>) -> Self {
+            TestBasePaths { env: Vec::new(), portable: portable.map(PathBuf::from), home_lookups: Default::default() }
+        }
+    }
+
+    impl BasePaths for TestBasePaths {
+        fn env_dir(&self, var: &str) -> Option<PathBuf> {
+            self.env.iter().find(|(name, _)| *name == var).map(|(_, dir)| dir.clone())
+        }
+
+        fn portable(&self) -> Option<PathBuf> {
+            self.portable.clone()
+        }
+
+        fn config_home(&self) -> Option<PathBuf> {
+            self.home_lookups.set(self.home_lookups.get() + 1);
+            Some(PathBuf::from("/home/u/.config"))
+        }
+
+        fn state_home(&self) -> Option<PathBuf> {
+            self.home_lookups.set(self.home_lookups.get() + 1);
+            Some(PathBuf::from("/home/u/.local/state"))
+        }
+    }
+
+    #[test]
+    fn folders_are_in_the_home_directory_unless_portable() {
+        let home = TestBasePaths::new(None);
+        assert_eq!(config_dir_in(&home), Some(PathBuf::from("/home/u/.config/PasteClipboard")));
+        assert_eq!(state_dir_in(&home), Some(PathBuf::from("/home/u/.local/state/PasteClipboard")));
+
+        let portable = TestBasePaths::new(Some("/media/stick/pasteclipboard"));
+        assert_eq!(config_dir_in(&portable), Some(PathBuf::from("/media/stick/pasteclipboard/config")));
+        assert_eq!(state_dir_in(&portable), Some(PathBuf::from("/media/stick/pasteclipboard/state")));
+        assert_eq!(portable.home_lookups.get(), 0, "portable mode looked at the home directory");
+    }
+
+    #[test]
+    fn the_environment_wins_in_both_modes() {
+        for portable in [None, Some("/media/stick")] {
+            let mut bases = TestBasePaths::new(portable);
+            bases.env = vec![(CONFIG_DIR_ENV, PathBuf::from("/tmp/c")), (STATE_DIR_ENV, PathBuf::from("/tmp/s"))];
+            assert_eq!(config_dir_in(&bases), Some(PathBuf::from("/tmp/c")));
+            assert_eq!(state_dir_in(&bases), Some(PathBuf::from("/tmp/s")));
+            assert_eq!(bases.home_lookups.get(), 0);
+        }
+    }
+
+    #[test]
+    fn the_flag_next_to_the_executable_makes_it_portable() {
+        let dir = scratch("portable");
+        let exe = dir.join("pasteclipboard");
+        assert_eq!(portable_dir(&exe, false), None);
+        assert_eq!(portable_dir(&exe, true), Some(dir.clone()));
+        fs::write(dir.join(PORTABLE_FLAG), "").unwrap();
+        assert_eq!(portable_dir(&exe, false), Some(dir.clone()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn an_invalid_delay_is_not_saved_and_a_valid_one_repairs_the_file() {
+        let dir = scratch("delay");
+        let path = dir.join("config.toml");
+        let broken = "[settings]\ndelay_seconds = \"abc\"\nsounds = true\n";
+        fs::write(&path, broken).unwrap();
+
+        // What was saved before cannot be read, so the default is used.
+        let conf = read_ini(&path).unwrap();
+        assert!(delay_in(&conf).is_err());
+        assert_eq!(Settings::from_config(&conf).delay_seconds, 3.0);
+
+        for invalid in ["abc", "-1", "", &format!("{}", MAX_DELAY_SECONDS + 1)] {
+            assert!(save_delay_in(&path, read_ini(&path).unwrap(), invalid).is_err(), "{:?}", invalid);
+            assert_eq!(fs::read_to_string(&path).unwrap(), broken, "{:?} was saved", invalid);
+        }
+
+        assert_eq!(save_delay_in(&path, read_ini(&path).unwrap(), " 5 ").unwrap(), 5.0);
+        let conf = read_ini(&path).unwrap();
+        assert_eq!(delay_in(&conf).unwrap(), 5.0);
+        let settings = Settings::from_config(&conf);
+        assert!(settings.sounds);
+        assert_eq!(settings.delay_seconds, 5.0);
+        assert!(fs::read_to_string(&path).unwrap().contains("delay_seconds = 5\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+// Window-level keyboard shortcuts and the Keyboard Shortcuts window
+// (Ctrl+?), which lists them together with the file actions.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::SimpleAction, Application, Builder, EventControllerKey, PropagationPhase, ShortcutsWindow, TextView,
+};
+use std::rc::Rc;
+
+use crate::preferences;
+use crate::window::MainWindow;
+
+const SHORTCUTS_UI: &str = r#"
+<interface>
+  <object class="GtkShortcutsWindow" id="shortcuts">
+    <property name="modal">1</property>
+    <child>
+      <object class="GtkShortcutsSection">
+        <property name="section-name">shortcuts</property>
+        <child>
+          <object class="GtkShortcutsGroup">
+            <property name="title">Typing</property>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Type after delay</property>
+                <property name="accelerator">&lt;Control&gt;Return</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Type all text, ignoring the selection</property>
+                <property name="accelerator">&lt;Control&gt;&lt;Shift&gt;Return</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Cancel the countdown or run</property>
+                <property name="accelerator">Escape</property>
+              </object>
+            </child>
+          </object>
+        </child>
+        <child>
+          <object class="GtkShortcutsGroup">
+            <property name="title">Editing</property>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Undo</property>
+                <property name="accelerator">&lt;Control&gt;z</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Redo</property>
+                <property name="accelerator">&lt;Control&gt;&lt;Shift&gt;z</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Clear the text</property>
+                <property name="accelerator">&lt;Control&gt;l</property>
+              </object>
+            </child>
+          </object>
+        </child>
+        <child>
+          <object class="GtkShortcutsGroup">
+            <property name="title">Find</property>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Find</property>
+                <property name="accelerator">&lt;Control&gt;f</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Find and replace</property>
+                <property name="accelerator">&lt;Control&gt;h</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Next match</property>
+                <property name="accelerator">&lt;Control&gt;g Return</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Previous match</property>
+                <property name="accelerator">&lt;Control&gt;&lt;Shift&gt;g</property>
+              </object>
+            </child>
+          </object>
+        </child>
+        <child>
+          <object class="GtkShortcutsGroup">
+            <property name="title">View</property>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Larger text</property>
+                <property name="accelerator">&lt;Control&gt;equal</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Smaller text</property>
+                <property name="accelerator">&lt;Control&gt;minus</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Normal text size</property>
+                <property name="accelerator">&lt;Control&gt;0</property>
+              </object>
+            </child>
+          </object>
+        </child>
+        <child>
+          <object class="GtkShortcutsGroup">
+            <property name="title">Files</property>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Open</property>
+                <property name="accelerator">&lt;Control&gt;o</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Save</property>
+                <property name="accelerator">&lt;Control&gt;s</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Save As</property>
+                <property name="accelerator">&lt;Control&gt;&lt;Shift&gt;s</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">New tab</property>
+                <property name="accelerator">&lt;Control&gt;t</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Close tab</property>
+                <property name="accelerator">&lt;Control&gt;w</property>
+              </object>
+            </child>
+          </object>
+        </child>
+        <child>
+          <object class="GtkShortcutsGroup">
+            <property name="title">General</property>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Preferences</property>
+                <property name="accelerator">&lt;Control&gt;comma</property>
+              </object>
+            </child>
+            <child>
+              <object class="GtkShortcutsShortcut">
+                <property name="title">Keyboard shortcuts</property>
+                <property name="accelerator">&lt;Control&gt;question</property>
+              </object>
+            </child>
+          </object>
+        </child>
+      </object>
+    </child>
+  </object>
+</interface>
+"#;
+
+/// Undoes or redoes in the text area, unless another text field (the find
+/// bar, say) has the focus, which then gets the key as it would without
+/// the accelerator.
+fn edit_history(ui: &MainWindow, what: &str) {
+    if let Some(focus) = GtkWindowExt::focus(&ui.window).filter(|w| w.is::<gtk4::Text>()) {
+        let _ = focus.activate_action(&format!("text.{}", what), None);
+    } else if what == "undo" {
+        ui.buffer.undo();
+    } else {
+        ui.buffer.redo();
+    }
+}
+
+pub fn install(app: &Application, ui: &Rc<MainWindow>, text_view: &TextView) {
+    let add = |name: &str, accels: &[&str], handler: fn(&Rc<MainWindow>)| -> SimpleAction {
+        let action = SimpleAction::new(name, None);
+        let weak = Rc::downgrade(ui);
+        action.connect_activate(move |_, _| {
+            if let Some(ui) = weak.upgrade() {
+                handler(&ui);
+            }
+        });
+        ui.window.add_action(&action);
+        app.set_accels_for_action(&format!("win.{}", name), accels);
+        action
+    };
+    add("start", &["<Control>Return", "<Control>KP_Enter"], |ui| {
+        if ui.btn_start.is_sensitive() {
+            ui.start_from_buffer(false);
+        }
+    });
+    add("start-all", &["<Control><Shift>Return", "<Control><Shift>KP_Enter"], |ui| {
+        if ui.btn_start.is_sensitive() {
+            ui.start_from_buffer(true);
+        }
+    });
+    // Only enabled while a run is active, so Escape does nothing otherwise.
+    add("cancel", &["Escape"], |ui| ui.cancel_run()).set_enabled(false);
+    add("clear", &["<Control>l"], |ui| ui.replace_text(""));
+    let undo = add("undo", &["<Control>z"], |ui| edit_history(ui, "undo"));
+    let redo = add("redo", &["<Control><Shift>z"], |ui| edit_history(ui, "redo"));
+    undo.set_enabled(ui.buffer.can_undo());
+    redo.set_enabled(ui.buffer.can_redo());
+    ui.buffer.connect_can_undo_notify(move |buffer| undo.set_enabled(buffer.can_undo()));
+    ui.buffer.connect_can_redo_notify(move |buffer| redo.set_enabled(buffer.can_redo()));
+    add("find", &["<Control>f"], |ui| ui.find_bar.show(false));
+    add("replace", &["<Control>h"], |ui| ui.find_bar.show(true));
+    add("preferences", &["<Control>comma"], preferences::show);
+
+    // The text view would otherwise insert a newline for Ctrl+Enter before
+    // the shortcut sees it.
+    let keys = EventControllerKey::new();
+    keys.set_propagation_phase(PropagationPhase::Capture);
+    let weak = Rc::downgrade(ui);
+    keys.connect_key_pressed(move |_, key, _, state| {
+        let is_enter = matches!(key, gtk4::gdk::Key::Return | gtk4::gdk::Key::KP_Enter);
+        if is_enter && state.contains(gtk4::gdk::ModifierType::CONTROL_MASK) {
+            if let Some(ui) = weak.upgrade() {
+                let _ = WidgetExt::activate_action(&ui.window, "win.start", None);
+            }
+            return gtk4::glib::Propagation::Stop;
+        }
+        gtk4::glib::Propagation::Proceed
+    });
+    text_view.add_controller(keys);
+
+    let builder = Builder::from_string(SHORTCUTS_UI);
+    if let Some(shortcuts) = builder.object::<ShortcutsWindow>("shortcuts") {
+        // Adds the "win.show-help-overlay" action.
+        ui.window.set_help_overlay(Some(&shortcuts));
+        app.set_accels_for_action("win.show-help-overlay", &["<Control>question"]);
+    }
+}
@@ -0,0 +1,185 @@
+// Marks characters in the text area that typing will skip, with a red
+// underline and a tooltip naming the character. While smart punctuation is
+// fixed (see `smart_punctuation`), the characters that fixes get a faint
+// dotted underline and a tooltip saying what they are typed as instead.
+// Large texts are scanned a slice at a time from idle callbacks so a big
+// paste never blocks the UI.
+
+use gtk4::prelude::*;
+use gtk4::{glib, TextBuffer, TextTag, TextView};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use pasteclipboard::keymap::{describe_char, is_typeable};
+use pasteclipboard::settings::Settings;
+use pasteclipboard::smart_punctuation::ascii_for;
+
+/// Characters looked at per idle callback.
+const SLICE_CHARS: i32 = 16 * 1024;
+
+pub struct SkippedMarks {
+    buffer: TextBuffer,
+    tag: TextTag,
+    replaced_tag: TextTag,
+    enabled: Cell<bool>,
+    /// Smart punctuation is typed as ASCII, so it is not skipped.
+    fixing: Cell<bool>,
+    /// The full rescan in progress, if any.
+    scan: RefCell<Option<glib::SourceId>>,
+}
+
+impl SkippedMarks {
+    pub fn attach(text_view: &TextView) -> Rc<SkippedMarks> {
+        let buffer = text_view.buffer();
+        let tag = TextTag::builder()
+            .name("skipped")
+            .underline(gtk4::pango::Underline::Error)
+            .underline_rgba(&gtk4::gdk::RGBA::new(0.88, 0.11, 0.14, 1.0))
+            .build();
+        buffer.tag_table().add(&tag);
+        let replaced_tag = TextTag::builder()
+            .name("replaced")
+            .underline(gtk4::pango::Underline::Single)
+            .underline_rgba(&gtk4::gdk::RGBA::new(0.5, 0.5, 0.5, 0.6))
+            .build();
+        buffer.tag_table().add(&replaced_tag);
+
+        let settings = Settings::load();
+        let marks = Rc::new(SkippedMarks {
+            buffer: buffer.clone(),
+            tag,
+            replaced_tag,
+            enabled: Cell::new(settings.mark_skipped),
+            fixing: Cell::new(settings.fix_smart_punctuation),
+            scan: RefCell::default(),
+        });
+
+        // Runs after the default handler, when `end` sits after the new text.
+        let weak = Rc::downgrade(&marks);
+        buffer.connect_closure(
+            "insert-text",
+            true,
+            glib::closure_local!(move |_: TextBuffer, end: gtk4::TextIter, text: &str, _: i32| {
+                if let Some(marks) = weak.upgrade() {
+                    marks.inserted(end.offset(), text.chars().count() as i32);
+                }
+            }),
+        );
+
+        text_view.set_has_tooltip(true);
+        let weak = Rc::downgrade(&marks);
+        text_view.connect_query_tooltip(move |view, x, y, keyboard, tooltip| {
+            let Some(marks) = weak.upgrade() else {
+                return false;
+            };
+            let iter = if keyboard {
+                view.buffer().iter_at_mark(&view.buffer().get_insert())
+            } else {
+                let (bx, by) = view.window_to_buffer_coords(gtk4::TextWindowType::Widget, x, y);
+                match view.iter_at_location(bx, by) {
+                    Some(iter) => iter,
+                    None => return false,
+                }
+            };
+            let c = iter.char();
+            if iter.has_tag(&marks.tag) {
+                tooltip.set_text(Some(&format!("will be skipped: {}", describe_char(c))));
+            } else if let Some(ascii) = ascii_for(c).filter(|_| iter.has_tag(&marks.replaced_tag)) {
+                tooltip.set_text(Some(&format!("will be typed as \"{}\": {}", ascii, describe_char(c))));
+            } else {
+                return false;
+            }
+            true
+        });
+
+        marks.rescan();
+        marks
+    }
+
+    pub fn set_enabled(self: &Rc<Self>, enabled: bool) {
+        if self.enabled.replace(enabled) != enabled {
+            self.rescan();
+        }
+    }
+
+    /// Follows the "Fix smart punctuation" check box.
+    pub fn set_fixing(self: &Rc<Self>, fixing: bool) {
+        if self.fixing.replace(fixing) != fixing {
+            self.rescan();
+        }
+    }
+
+    /// Starts over, e.g. after the setting or the text view's keymap changed.
+    pub fn rescan(self: &Rc<Self>) {
+        if let Some(source) = self.scan.borrow_mut().take() {
+            source.remove();
+        }
+        self.buffer.remove_tag(&self.tag, &self.buffer.start_iter(), &self.buffer.end_iter());
+        self.buffer.remove_tag(&self.replaced_tag, &self.buffer.start_iter(), &self.buffer.end_iter());
+        if !self.enabled.get() {
+            return;
+        }
+        let weak = Rc::downgrade(self);
+        let next = Cell::new(0);
+        let source = glib::idle_add_local(move || {
+            let Some(marks) = weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            let start = next.get();
+            let end = (start + SLICE_CHARS).min(marks.buffer.char_count());
+            marks.mark(start, end);
+            next.set(end);
+            if end < marks.buffer.char_count() {
+                glib::ControlFlow::Continue
+            } else {
+                marks.scan.borrow_mut().take();
+                glib::ControlFlow::Break
+            }
+        });
+        *self.scan.borrow_mut() = Some(source);
+    }
+
+    fn inserted(self: &Rc<Self>, end: i32, len: i32) {
+        if !self.enabled.get() {
+            return;
+        }
+        // A scan in progress has stale offsets, and a big paste is the
+        // scan's job anyway.
+        if self.scan.borrow().is_some() || len > SLICE_CHARS {
+            self.rescan();
+        } else {
+            self.mark(end - len, end);
+        }
+    }
+
+    /// Tags every run of skipped characters, and of those typed as ASCII,
+    /// between two char offsets.
+    fn mark(&self, start: i32, end: i32) {
+        let mut iter = self.buffer.iter_at_offset(start);
+        let mut run: Option<(gtk4::TextIter, &TextTag)> = None;
+        while iter.offset() < end {
+            let tag = self.tag_for(iter.char());
+            if run.map(|(_, current)| current) != tag {
+                if let Some((from, current)) = run {
+                    self.buffer.apply_tag(current, &from, &iter);
+                }
+                run = tag.map(|tag| (iter, tag));
+            }
+            if !iter.forward_char() {
+                break;
+            }
+        }
+        if let Some((from, tag)) = run {
+            self.buffer.apply_tag(tag, &from, &iter);
+        }
+    }
+
+    fn tag_for(&self, c: char) -> Option<&TextTag> {
+        if is_typeable(c) {
+            None
+        } else if self.fixing.get() && ascii_for(c).is_some() {
+            Some(&self.replaced_tag)
+        } else {
+            Some(&self.tag)
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! "Fix smart punctuation" (`fix_smart_punctuation`): the curly quotes,
+//! dashes, ellipses and no-break spaces that word processors and web pages
+//! put in text have no key on a US layout, so they would be left out, which
+//! garbles commands (`—force` loses its dash). This replaces them with the
+//! ASCII they stand for before typing, after `${VARIABLE}` expansion and
+//! before the line filters, so the preview and the counts show the result.
+
+/// Each typographic character and the ASCII it is typed as.
+pub const REPLACEMENTS: [(char, &str); 21] = [
+    ('\u{a0}', " "), // NO-BREAK SPACE
+    ('\u{2007}', " "), // FIGURE SPACE
+    ('\u{202f}', " "), // NARROW NO-BREAK SPACE
+    ('\u{2010}', "-"), // HYPHEN
+    ('\u{2011}', "-"), // NON-BREAKING HYPHEN
+    ('\u{2012}', "-"), // FIGURE DASH
+    ('\u{2013}', "-"), // EN DASH
+    ('\u{2014}', "-"), // EM DASH
+    ('\u{2015}', "-"), // HORIZONTAL BAR
+    ('\u{2212}', "-"), // MINUS SIGN
+    ('\u{2018}', "'"), // LEFT SINGLE QUOTATION MARK
+    ('\u{2019}', "'"), // RIGHT SINGLE QUOTATION MARK
+    ('\u{201a}', "'"), // SINGLE LOW-9 QUOTATION MARK
+    ('\u{201b}', "'"), // SINGLE HIGH-REVERSED-9 QUOTATION MARK
+    ('\u{2032}', "'"), // PRIME
+    ('\u{201c}', "\""), // LEFT DOUBLE QUOTATION MARK
+    ('\u{201d}', "\""), // RIGHT DOUBLE QUOTATION MARK
+    ('\u{201e}', "\""), // DOUBLE LOW-9 QUOTATION MARK
+    ('\u{201f}', "\""), // DOUBLE HIGH-REVERSED-9 QUOTATION MARK
+    ('\u{2033}', "\""), // DOUBLE PRIME
+    ('\u{2026}', "..."), // HORIZONTAL ELLIPSIS
+];
+
+/// What `c` is typed as, if it is one of [`REPLACEMENTS`].
+pub fn ascii_for(c: char) -> Option<&'static str> {
+    REPLACEMENTS.iter().find(|(from, _)| *from == c).map(|(_, to)| *to)
+}
+
+/// `text` with each of [`REPLACEMENTS`] replaced.
+pub fn fix(text: &str) -> String {
+    let mut fixed = String::with_capacity(text.len());
+    for c in text.chars() {
+        match ascii_for(c) {
+            Some(ascii) => fixed.push_str(ascii),
+            None => fixed.push(c),
+        }
+    }
+    fixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::is_typeable;
+
+    #[test]
+    fn each_replacement_is_typeable_ascii() {
+        for (from, to) in REPLACEMENTS {
+            assert!(!is_typeable(from), "{:?} has a key of its own", from);
+            assert!(!to.is_empty() && to.chars().all(|c| c.is_ascii() && is_typeable(c)), "{:?} -> {:?}", from, to);
+            assert_eq!(ascii_for(from), Some(to));
+            assert_eq!(fix(&from.to_string()), to);
+        }
+        let mut from: Vec<char> = REPLACEMENTS.iter().map(|(from, _)| *from).collect();
+        from.sort();
+        from.dedup();
+        assert_eq!(from.len(), REPLACEMENTS.len());
+    }
+
+    #[test]
+    fn fix_leaves_everything_else_alone() {
+        let text = "\u{201c}Don\u{2019}t\u{201d} \u{2014}force\u{a0}it\u{2026} caf\u{e9} \u{2192} 5\u{2032}10\u{2033}\n\t\u{2212}1";
+        assert_eq!(fix(text), "\"Don't\" -force it... caf\u{e9} \u{2192} 5'10\"\n\t-1");
+        assert_eq!(fix("plain ASCII, \"quotes\" -- and ..."), "plain ASCII, \"quotes\" -- and ...");
+        assert_eq!(fix(""), "");
+        for c in ['a', '\u{e9}', '\u{2192}', '\u{1f600}'] {
+            assert_eq!(ascii_for(c), None);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// Global shortcuts for snippets (see `global_shortcuts`): at startup, and
+// whenever a snippet's shortcut changes, the snippets that have one are
+// bound through the portal, in a fresh session each time. A shortcut
+// pressed while a run is in progress is not queued; a notification says
+// so. A secret snippet, kept in the keyring, encrypted or an auto-type
+// one, is only typed once the Type button of a notification is clicked.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{DBusConnection, Notification, SimpleAction},
+    glib::{self, VariantTy},
+    Application,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use pasteclipboard::global_shortcuts::{Binding, Bound, ShortcutsSession};
+use pasteclipboard::i18n::{args, gettext};
+use pasteclipboard::settings::load_delay_setting;
+use pasteclipboard::snippets::{is_encrypted, is_secure, load_snippet_settings, snippet_shortcuts};
+
+use crate::tray;
+use crate::window::WindowSlot;
+
+/// Shortcut ids are the snippet's name after this.
+const ID_PREFIX: &str = "snippet:";
+
+const NOTIFICATION_ID: &str = "snippet-shortcut";
+
+struct Shortcuts {
+    app: glib::WeakRef<Application>,
+    slot: Rc<WindowSlot>,
+    session: Option<ShortcutsSession>,
+    /// Counts the binds, so one that a later bind overtook is dropped.
+    generation: u32,
+}
+
+thread_local! {
+    static SHORTCUTS: RefCell<Option<Shortcuts>> = const { RefCell::new(None) };
+}
+
+/// Binds the snippets' shortcuts and handles them from now on; later calls
+/// do nothing.
+pub fn start(app: &Application, slot: Rc<WindowSlot>) {
+    if SHORTCUTS.with(|shortcuts| shortcuts.borrow().is_some()) {
+        return;
+    }
+    add_actions(app, slot.clone());
+    SHORTCUTS.with(|shortcuts| {
+        shortcuts.replace(Some(Shortcuts { app: app.downgrade(), slot, session: None, generation: 0 }));
+    });
+    rebind();
+}
+
+/// Binds the shortcuts the snippets have now, replacing the ones bound
+/// before.
+pub fn rebind() {
+    let started = SHORTCUTS.with(|shortcuts| {
+        let mut shortcuts = shortcuts.borrow_mut();
+        let shortcuts = shortcuts.as_mut()?;
+        shortcuts.generation += 1;
+        // Closing the old session unbinds its shortcuts.
+        shortcuts.session.take();
+        Some((shortcuts.app.upgrade()?, shortcuts.slot.clone(), shortcuts.generation))
+    });
+    let Some((app, slot, generation)) = started else {
+        return;
+    };
+    let wanted = snippet_shortcuts();
+    if wanted.is_empty() {
+        return;
+    }
+    let bindings: Vec<Binding> = wanted
+        .into_iter()
+        .map(|(name, trigger)| Binding {
+            id: format!("{}{}", ID_PREFIX, name),
+            description: format!("Type the snippet '{}'", name),
+            trigger,
+        })
+        .collect();
+    let Some(conn) = app.dbus_connection() else {
+        return;
+    };
+    glib::MainContext::default().spawn_local(async move {
+        let result = if ShortcutsSession::is_available(&conn).await {
+            bind(&conn, &bindings).await
+        } else {
+            Err(anyhow::anyhow!("Snippet shortcuts need the desktop's GlobalShortcuts portal, which is missing"))
+        };
+        let current = |shortcuts: &Shortcuts| shortcuts.generation == generation;
+        if !SHORTCUTS.with(|shortcuts| shortcuts.borrow().as_ref().is_some_and(current)) {
+            return;
+        }
+        match result {
+            Ok((mut session, bound)) => {
+                report_bound(&slot, &bindings, &bound);
+                session.connect_activated(activated);
+                SHORTCUTS.with(|shortcuts| {
+                    if let Some(shortcuts) = shortcuts.borrow_mut().as_mut() {
+                        shortcuts.session = Some(session);
+                    }
+                });
+            }
+            Err(e) => report(&slot, &format!("The snippet shortcuts were not bound: {:#}.", e), true),
+        }
+    });
+}
+
+async fn bind(conn: &DBusConnection, bindings: &[Binding]) -> anyhow::Result<(ShortcutsSession, Vec<Bound>)> {
+    let session = ShortcutsSession::create(conn).await?;
+    let bound = session.bind(bindings).await?;
+    Ok((session, bound))
+}
+
+/// Logs what each shortcut was bound to, and warns about those the desktop
+/// gave no keys, as when another application already has them.
+fn report_bound(slot: &WindowSlot, bindings: &[Binding], bound: &[Bound]) {
+    let mut unbound = Vec::new();
+    for binding in bindings {
+        let name = binding.id.trim_start_matches(ID_PREFIX);
+        match bound.iter().find(|b| b.id == binding.id).filter(|b| !b.trigger_description.is_empty()) {
+            Some(b) => report(slot, &format!("Snippet '{}' is typed with {}.", name, b.trigger_description), false),
+            None => unbound.push(format!("{} for '{}'", binding.trigger, name)),
+        }
+    }
+    if !unbound.is_empty() {
+        report(
+            slot,
+            &format!(
+                "The desktop did not bind {}; another application may already use the keys, or they were declined.",
+                unbound.join(", ")
+            ),
+            true,
+        );
+    }
+}
+
+/// Logs `message`, in the window's log too if it is open, and with
+/// `warning` shows it there.
+fn report(slot: &WindowSlot, message: &str, warning: bool) {
+    if warning {
+        log::warn!("{}", message);
+    } else {
+        log::info!("{}", message);
+    }
+    if let Some(ui) = slot.peek() {
+        ui.log.add(message);
+        if warning {
+            ui.set_warning(message);
+        }
+    }
+}
+
+fn activated(id: &str) {
+    let Some(name) = id.strip_prefix(ID_PREFIX) else {
+        return;
+    };
+    let found = SHORTCUTS.with(|shortcuts| {
+        shortcuts.borrow().as_ref().and_then(|shortcuts| Some((shortcuts.app.upgrade()?, shortcuts.slot.clone())))
+    });
+    let Some((app, slot)) = found else {
+        return;
+    };
+    if is_secret(name) {
+        confirm(&app, name);
+    } else {
+        type_now(&app, &slot, name);
+    }
+}
+
+/// Whether typing the snippet types a secret.
+fn is_secret(name: &str) -> bool {
+    is_secure(name) || is_encrypted(name) || load_snippet_settings(name).auto_type
+}
+
+/// Types the snippet after its delay, unless a run is in progress.
+fn type_now(app: &Application, slot: &WindowSlot, name: &str) {
+    let ui = slot.get(app);
+    if ui.is_busy() {
+        let notification = Notification::new(&args(&gettext("'{0}' was not typed"), &[&name]));
+        notification.set_body(Some(&gettext(
+            "A typing run is already in progress. Press the shortcut again once it is over.",
+        )));
+        app.send_notification(Some(NOTIFICATION_ID), &notification);
+        ui.log.add(&format!("The shortcut for snippet '{}' was pressed during a run, so it was not typed.", name));
+        return;
+    }
+    tray::type_snippet(app, slot, name);
+}
+
+/// Asks whether to type a secret snippet.
+fn confirm(app: &Application, name: &str) {
+    let notification = Notification::new(&args(&gettext("Type the secret snippet '{0}'?"), &[&name]));
+    let delay = load_snippet_settings(name).delay_seconds.map_or_else(|| load_delay_setting().ok(), Some);
+    notification.set_body(Some(&match delay {
+        Some(delay) => {
+            args(&gettext("Typing starts {0} seconds after you click Type; focus the target window."), &[&delay])
+        }
+        None => gettext("Focus the target window after clicking Type."),
+    }));
+    notification.add_button_with_target_value(&gettext("Type"), "app.type-secret-snippet", Some(&name.to_variant()));
+    app.send_notification(Some(NOTIFICATION_ID), &notification);
+}
+
+/// The notification's button. It can be activated by anything on the
+/// session bus, so only a snippet with a shortcut counts.
+fn add_actions(app: &Application, slot: Rc<WindowSlot>) {
+    let action = SimpleAction::new("type-secret-snippet", Some(VariantTy::STRING));
+    let app_weak = app.downgrade();
+    action.connect_activate(move |_, target| {
+        let (Some(app), Some(name)) = (app_weak.upgrade(), target.and_then(|t| t.get::<String>())) else {
+            return;
+        };
+        app.withdraw_notification(NOTIFICATION_ID);
+        if snippet_shortcuts().iter().any(|(other, _)| *other == name) {
+            type_now(&app, &slot, &name);
+        }
+    });
+    app.add_action(&action);
+}
@@ -0,0 +1,526 @@
+//! Named snippets in ~/.config/PasteClipboard/snippets/: one `<name>.txt`
+//! per snippet, so the folder can be synced with git, plus `index.ini` for
+//! per-snippet settings. Deleted snippets go to its trash/ subfolder.
+//!
+//! A snippet stored securely has no file: its text is in the keyring (see
+//! `secret_store`), and its `index.ini` section holds the `secret_id` it is
+//! stored under. A login saved for an auto-type snippet, with its TOTP
+//! seed, is in the keyring too, under the section's `credentials_id`.
+//!
+//! An encrypted snippet is a `<name>.gpg` file instead of the `.txt` (see
+//! `gpg`), decrypted whenever it is read.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use configparser::ini::Ini;
+
+use zeroize::Zeroizing;
+
+use crate::auto_type::Credentials;
+use crate::global_shortcuts::Trigger;
+use crate::gpg;
+use crate::secret_store;
+use crate::settings::{config_dir, load_readback_chords, state_path};
+use crate::typing::{EnterKey, TypingOptions, MAX_CHAR_DELAY_MS, MAX_DELAY_SECONDS};
+
+const EXTENSION: &str = "txt";
+const GPG_EXTENSION: &str = "gpg";
+pub(crate) const INDEX_FILE: &str = "index.ini";
+pub(crate) const SECRET_KEY: &str = "secret_id";
+const CREDENTIALS_KEY: &str = "credentials_id";
+pub const TRASH_DIR: &str = "trash";
+const RECENT_LENGTH: usize = 10;
+
+/// What happens to line breaks at the end of a snippet when it is typed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NewlineMode {
+    /// Type the text exactly as stored.
+    #[default]
+    Keep,
+    /// Drop trailing line breaks, so typing never presses Enter at the end.
+    Strip,
+    /// End with exactly one line break, pressing Enter after the text.
+    Enter,
+}
+
+impl NewlineMode {
+    pub const ALL: [NewlineMode; 3] = [NewlineMode::Keep, NewlineMode::Strip, NewlineMode::Enter];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NewlineMode::Keep => "keep",
+            NewlineMode::Strip => "strip",
+            NewlineMode::Enter => "enter",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        NewlineMode::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            NewlineMode::Keep => text.to_string(),
+            NewlineMode::Strip => text.trim_end_matches(['\n', '\r']).to_string(),
+            NewlineMode::Enter => format!("{}\n", text.trim_end_matches(['\n', '\r'])),
+        }
+    }
+}
+
+/// A snippet's own settings; every `None` falls back to the global one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnippetSettings {
+    /// `None` uses the window's delay.
+    pub delay_seconds: Option<u64>,
+    pub newline: NewlineMode,
+    pub char_delay_ms: Option<u32>,
+    pub enter_key: Option<EnterKey>,
+    /// Only type into a window whose title matches; see `title_matches`.
+    pub target_window: Option<String>,
+    /// The title of a window to bring to the front before typing; see
+    /// `TypingOptions::focus_window`.
+    pub focus_window: Option<String>,
+    /// The text is an auto-type sequence (see `auto_type`).
+    pub auto_type: bool,
+    /// Whether the run is read back from the target to check it, which
+    /// selects all and copies there (see `readback`).
+    pub verify_readback: Option<bool>,
+    /// A global shortcut that types the snippet (see `global_shortcuts`).
+    pub shortcut: Option<Trigger>,
+}
+
+impl SnippetSettings {
+    /// Overrides the global typing options for one run of this snippet.
+    pub fn apply(&self, options: &mut TypingOptions) {
+        if let Some(ms) = self.char_delay_ms {
+            options.char_delay = Duration::from_millis(u64::from(ms));
+        }
+        if let Some(key) = self.enter_key {
+            options.enter_key = key;
+        }
+        if let Some(pattern) = &self.target_window {
+            options.target_window = Some(pattern.clone());
+        }
+        if let Some(title) = &self.focus_window {
+            options.focus_window = Some(title.clone());
+        }
+        if let Some(verify) = self.verify_readback {
+            options.readback = verify.then(load_readback_chords);
+        }
+    }
+}
+
+pub fn snippets_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("snippets"))
+}
+
+fn dir() -> Result<PathBuf> {
+    let dir = snippets_dir().context("Cannot determine your configuration directory")?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(dir)
+}
+
+/// Names become file names and index sections, so keep them to one plain
+/// path component.
+pub fn validate_name(name: &str) -> Result<()> {
+    let name_trimmed = name.trim();
+    if name_trimmed.is_empty() {
+        bail!("Enter a snippet name");
+    }
+    if name_trimmed != name {
+        bail!("Snippet names cannot start or end with spaces");
+    }
+    if name.starts_with('.') || name.contains(['/', '\\', '[', ']', '\n', '\0']) {
+        bail!("Snippet names cannot start with '.' or contain / \\ [ ]");
+    }
+    Ok(())
+}
+
+fn snippet_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name, EXTENSION))
+}
+
+fn encrypted_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}", name, GPG_EXTENSION))
+}
+
+fn load_index(dir: &Path) -> Ini {
+    let mut index = Ini::new_cs();
+    let _ = index.load(dir.join(INDEX_FILE));
+    index
+}
+
+fn write_index(dir: &Path, index: &Ini) -> Result<()> {
+    let path = dir.join(INDEX_FILE);
+    index.write(&path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The keyring id a securely stored snippet's text is under.
+fn secret_id(index: &Ini, name: &str) -> Option<String> {
+    index.get(name, SECRET_KEY).map(|id| id.trim().to_string()).filter(|id| !id.is_empty())
+}
+
+fn snippet_exists(dir: &Path, index: &Ini, name: &str) -> bool {
+    snippet_path(dir, name).exists() || encrypted_path(dir, name).exists() || secret_id(index, name).is_some()
+}
+
+pub(crate) fn keyring_label(name: &str) -> String {
+    format!("PasteClipboard snippet '{}'", name)
+}
+
+/// Whether the snippet's text is kept in the keyring.
+pub fn is_secure(name: &str) -> bool {
+    snippets_dir().is_some_and(|dir| secret_id(&load_index(&dir), name).is_some())
+}
+
+/// Whether the snippet is a file encrypted with gpg.
+pub fn is_encrypted(name: &str) -> bool {
+    snippets_dir().is_some_and(|dir| encrypted_path(&dir, name).is_file())
+}
+
+/// Snippet names, sorted case-insensitively.
+pub fn list_snippets() -> Result<Vec<String>> {
+    let Some(dir) = snippets_dir() else {
+        return Ok(Vec::new());
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", dir.display())),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == EXTENSION || e == GPG_EXTENSION))
+        .filter_map(|path| path.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+        .filter(|name| validate_name(name).is_ok())
+        .collect();
+    let index = load_index(&dir);
+    for name in index.sections() {
+        if secret_id(&index, &name).is_some() && validate_name(&name).is_ok() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.sort_by_key(|name| name.to_lowercase());
+    names.dedup();
+    Ok(names)
+}
+
+/// The snippets that have a global shortcut, with it.
+pub fn snippet_shortcuts() -> Vec<(String, Trigger)> {
+    let names = list_snippets().unwrap_or_default();
+    names.into_iter().filter_map(|name| load_snippet_settings(&name).shortcut.map(|trigger| (name, trigger))).collect()
+}
+
+/// The snippet's text. One stored securely is read from the keyring,
+/// which may first ask to be unlocked, and an encrypted one is decrypted,
+/// which may ask for the key's passphrase. The text of those two is a
+/// secret (see [`is_secure`] and [`is_encrypted`]): callers keep it out of
+/// files such as the drafts and the recent texts.
+pub fn load_snippet(name: &str) -> Result<String> {
+    let dir = dir()?;
+    if let Some(id) = secret_id(&load_index(&dir), name) {
+        return secret_store::lookup(&id)
+            .and_then(|text| text.ok_or_else(|| anyhow!("The keyring has no entry for it")))
+            .with_context(|| format!("Cannot read snippet '{}' from the keyring", name));
+    }
+    let encrypted = encrypted_path(&dir, name);
+    if encrypted.is_file() {
+        let text = gpg::decrypt(&encrypted).with_context(|| format!("Cannot read snippet '{}'", name))?;
+        return Ok(text.to_string());
+    }
+    let path = snippet_path(&dir, name);
+    fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))
+}
+
+pub fn load_snippet_settings(name: &str) -> SnippetSettings {
+    let Some(dir) = snippets_dir() else {
+        return SnippetSettings::default();
+    };
+    let index = load_index(&dir);
+    SnippetSettings {
+        delay_seconds: index
+            .get(name, "delay_seconds")
+            .and_then(|d| d.trim().parse().ok())
+            .filter(|d| *d <= MAX_DELAY_SECONDS),
+        newline: index.get(name, "newline").and_then(|m| NewlineMode::parse(&m)).unwrap_or_default(),
+        char_delay_ms: index
+            .get(name, "char_delay_ms")
+            .and_then(|ms| ms.trim().parse().ok())
+            .filter(|ms| *ms <= MAX_CHAR_DELAY_MS),
+        enter_key: index.get(name, "enter_key").and_then(|key| EnterKey::parse(&key)),
+        target_window: index.get(name, "target_window").map(|p| p.trim().to_string()).filter(|p| !p.is_empty()),
+        focus_window: index.get(name, "focus_window").map(|t| t.trim().to_string()).filter(|t| !t.is_empty()),
+        auto_type: index.getbool(name, "auto_type").ok().flatten().unwrap_or(false),
+        verify_readback: index.getbool(name, "verify_readback").ok().flatten(),
+        shortcut: index.get(name, "shortcut").and_then(|t| Trigger::parse(&t).ok()),
+    }
+}
+
+/// The snippet's text as it should be typed.
+pub fn snippet_text_for_typing(name: &str) -> Result<String> {
+    Ok(load_snippet_settings(name).newline.apply(&load_snippet(name)?))
+}
+
+/// Creates or overwrites a snippet, in the keyring if it is stored
+/// securely and encrypted to the same keys if it is encrypted.
+pub fn save_snippet(name: &str, text: &str) -> Result<()> {
+    validate_name(name)?;
+    let dir = dir()?;
+    if secret_id(&load_index(&dir), name).is_some() {
+        return save_snippet_securely(name, text);
+    }
+    let encrypted = encrypted_path(&dir, name);
+    if encrypted.is_file() {
+        return gpg::encrypt_again(&encrypted, text).with_context(|| format!("Cannot save snippet '{}'", name));
+    }
+    let path = snippet_path(&dir, name);
+    fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Creates or overwrites a snippet in the keyring, removing its file if it
+/// had one. The text is stored before the index points to it, so a keyring
+/// that fails leaves the snippet as it was.
+pub fn save_snippet_securely(name: &str, text: &str) -> Result<()> {
+    validate_name(name)?;
+    let dir = dir()?;
+    let mut index = load_index(&dir);
+    let id = secret_id(&index, name).unwrap_or_else(secret_store::new_id);
+    secret_store::store(&id, &keyring_label(name), text)
+        .with_context(|| format!("Cannot store snippet '{}' in the keyring", name))?;
+    index.set(name, SECRET_KEY, Some(id));
+    write_index(&dir, &index)?;
+    let path = snippet_path(&dir, name);
+    if path.exists() {
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Moves an existing snippet's text into the keyring or back to its file.
+pub fn set_snippet_secure(name: &str, secure: bool) -> Result<()> {
+    let dir = dir()?;
+    if secure && encrypted_path(&dir, name).is_file() {
+        bail!("Snippet '{}' is encrypted with gpg; it cannot also be stored in the keyring", name);
+    }
+    let mut index = load_index(&dir);
+    match (secret_id(&index, name), secure) {
+        (None, true) => save_snippet_securely(name, &load_snippet(name)?),
+        (Some(id), false) => {
+            let text = load_snippet(name)?;
+            let path = snippet_path(&dir, name);
+            fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+            index.remove_key(name, SECRET_KEY);
+            write_index(&dir, &index)?;
+            // The text is safe in its file by now; an entry left behind in
+            // the keyring holds nothing the file does not.
+            let _ = secret_store::delete(&id);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn save_snippet_settings(name: &str, settings: &SnippetSettings) -> Result<()> {
+    let dir = dir()?;
+    let mut index = load_index(&dir);
+    index.set(name, "delay_seconds", settings.delay_seconds.map(|d| d.to_string()));
+    index.set(name, "newline", Some(settings.newline.name().to_string()));
+    index.set(name, "char_delay_ms", settings.char_delay_ms.map(|ms| ms.to_string()));
+    index.set(name, "enter_key", settings.enter_key.map(|key| key.name().to_string()));
+    index.set(name, "target_window", settings.target_window.clone());
+    index.set(name, "focus_window", settings.focus_window.clone());
+    index.set(name, "auto_type", settings.auto_type.then(|| "true".to_string()));
+    index.set(name, "verify_readback", settings.verify_readback.map(|verify| verify.to_string()));
+    index.set(name, "shortcut", settings.shortcut.as_ref().map(Trigger::name));
+    write_index(&dir, &index)
+}
+
+/// The keyring id the snippet's login is under.
+fn credentials_id(index: &Ini, name: &str) -> Option<String> {
+    index.get(name, CREDENTIALS_KEY).map(|id| id.trim().to_string()).filter(|id| !id.is_empty())
+}
+
+/// Whether a login is saved for the snippet.
+pub fn has_credentials(name: &str) -> bool {
+    snippets_dir().is_some_and(|dir| credentials_id(&load_index(&dir), name).is_some())
+}
+
+/// The login saved for the snippet, empty if there is none. Reading it may
+/// first ask to unlock the keyring.
+pub fn load_credentials(name: &str) -> Result<Credentials> {
+    let dir = dir()?;
+    let Some(id) = credentials_id(&load_index(&dir), name) else {
+        return Ok(Credentials::default());
+    };
+    let stored = secret_store::lookup(&id)
+        .and_then(|stored| stored.ok_or_else(|| anyhow!("The keyring has no entry for it")))
+        .with_context(|| format!("Cannot read the login for snippet '{}' from the keyring", name))?;
+    let stored = Zeroizing::new(stored);
+    // The user name, the password and the TOTP seed, a line each; any may
+    // be empty, and logins saved before the seed have only two lines.
+    let mut lines = stored.splitn(3, '\n');
+    let mut field = || lines.next().filter(|value| !value.is_empty()).map(|value| Zeroizing::new(value.to_string()));
+    Ok(Credentials { username: field(), password: field(), totp_seed: field(), pass: Vec::new(), hotp: Vec::new() })
+}
+
+/// Saves `credentials` as the snippet's login, replacing the one before.
+pub fn save_credentials(name: &str, credentials: &Credentials) -> Result<()> {
+    let dir = dir()?;
+    let mut index = load_index(&dir);
+    let id = credentials_id(&index, name).unwrap_or_else(secret_store::new_id);
+    let username = credentials.username.as_deref().map_or("", String::as_str);
+    let password = credentials.password.as_deref().map_or("", String::as_str);
+    let seed = credentials.totp_seed.as_deref().map_or("", String::as_str);
+    let stored = Zeroizing::new(format!("{}\n{}\n{}", username, password, seed));
+    secret_store::store(&id, &format!("PasteClipboard login for snippet '{}'", name), &stored)
+        .with_context(|| format!("Cannot store the login for snippet '{}' in the keyring", name))?;
+    index.set(name, CREDENTIALS_KEY, Some(id));
+    write_index(&dir, &index)
+}
+
+/// Removes the snippet's saved login, if it has one.
+pub fn forget_credentials(name: &str) -> Result<()> {
+    let dir = dir()?;
+    let mut index = load_index(&dir);
+    let Some(id) = credentials_id(&index, name) else {
+        return Ok(());
+    };
+    secret_store::delete(&id)
+        .with_context(|| format!("Cannot remove the login for snippet '{}' from the keyring", name))?;
+    index.remove_key(name, CREDENTIALS_KEY);
+    write_index(&dir, &index)
+}
+
+pub fn rename_snippet(old: &str, new: &str) -> Result<()> {
+    validate_name(new)?;
+    if old == new {
+        return Ok(());
+    }
+    let dir = dir()?;
+    let mut index = load_index(&dir);
+    if snippet_exists(&dir, &index, new) {
+        bail!("A snippet called '{}' already exists", new);
+    }
+    // A secure snippet's keyring entry is found by its id, which the
+    // section keeps.
+    if secret_id(&index, old).is_none() {
+        let (from, to) = if encrypted_path(&dir, old).is_file() {
+            (encrypted_path(&dir, old), encrypted_path(&dir, new))
+        } else {
+            (snippet_path(&dir, old), snippet_path(&dir, new))
+        };
+        fs::rename(from, to).with_context(|| format!("Failed to rename '{}'", old))?;
+    }
+    if let Some(section) = index.remove_section(old) {
+        for (key, value) in section {
+            index.set(new, &key, value);
+        }
+        write_index(&dir, &index)?;
+    }
+    Ok(())
+}
+
+/// Moves a snippet to trash/ (numbering it if the trash already holds one
+/// of that name) and forgets its settings, returning where it went. A
+/// secure snippet is removed from the keyring instead, since the trash is a
+/// plain folder.
+pub fn delete_snippet(name: &str) -> Result<Option<PathBuf>> {
+    forget_credentials(name)?;
+    let dir = dir()?;
+    let mut index = load_index(&dir);
+    if let Some(id) = secret_id(&index, name) {
+        secret_store::delete(&id).with_context(|| format!("Cannot remove snippet '{}' from the keyring", name))?;
+        index.remove_section(name);
+        write_index(&dir, &index)?;
+        return Ok(None);
+    }
+    let trash = dir.join(TRASH_DIR);
+    fs::create_dir_all(&trash).with_context(|| format!("Failed to create {}", trash.display()))?;
+    // An encrypted snippet goes to the trash as it is.
+    let (source, path): (PathBuf, fn(&Path, &str) -> PathBuf) = match encrypted_path(&dir, name) {
+        encrypted if encrypted.is_file() => (encrypted, encrypted_path),
+        _ => (snippet_path(&dir, name), snippet_path),
+    };
+    let mut target = path(&trash, name);
+    let mut n = 1;
+    while target.exists() {
+        target = path(&trash, &format!("{}-{}", name, n));
+        n += 1;
+    }
+    fs::rename(source, &target).with_context(|| format!("Failed to delete '{}'", name))?;
+
+    if index.remove_section(name).is_some() {
+        write_index(&dir, &index)?;
+    }
+    Ok(Some(target))
+}
+
+/// Copies a plain text file in as a snippet named after the file; returns
+/// the name.
+pub fn import_snippet(path: &Path) -> Result<String> {
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .with_context(|| format!("{} has no usable file name", path.display()))?
+        .to_string();
+    let dir = dir()?;
+    if snippet_exists(&dir, &load_index(&dir), &name) {
+        bail!("A snippet called '{}' already exists", name);
+    }
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read {} as UTF-8 text", path.display()))?;
+    save_snippet(&name, &text)?;
+    Ok(name)
+}
+
+/// Writes the snippet's text to `path`; an encrypted snippet is copied still
+/// encrypted, as its text is never written to disk.
+pub fn export_snippet(name: &str, path: &Path) -> Result<()> {
+    let encrypted = encrypted_path(&dir()?, name);
+    if encrypted.is_file() {
+        return fs::copy(&encrypted, path).map(|_| ()).with_context(|| format!("Failed to write {}", path.display()));
+    }
+    fs::write(path, load_snippet(name)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Kept in the state directory rather than next to the snippets, so usage
+/// never shows up as changes in a synced snippets folder.
+fn recent_path() -> Option<PathBuf> {
+    state_path("recent_snippets")
+}
+
+/// The most recently typed snippets that still exist, newest first.
+pub fn recent_snippets(limit: usize) -> Vec<String> {
+    let existing = list_snippets().unwrap_or_default();
+    recent_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .unwrap_or_default()
+        .lines()
+        .filter(|name| existing.iter().any(|e| e == name))
+        .take(limit)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Moves `name` to the front of the recently typed snippets.
+pub fn remember_snippet_used(name: &str) {
+    let Some(path) = recent_path() else {
+        return;
+    };
+    let mut recent: Vec<String> = fs::read_to_string(&path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| !l.is_empty() && *l != name)
+        .map(str::to_string)
+        .collect();
+    recent.insert(0, name.to_string());
+    recent.truncate(RECENT_LENGTH);
+
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::write(path, recent.join("\n") + "\n");
+}
@@ -0,0 +1,685 @@
+// The snippet sidebar: a list of named snippets with add/edit/delete,
+// import/export, double-click to load and a context menu to type one.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{Menu, SimpleAction, SimpleActionGroup},
+    glib,
+    Button, CheckButton, DropDown, Entry, FileChooserAction, FileChooserNative, GestureClick, Image, Label, ListBox,
+    Orientation, PasswordEntry, PopoverMenu, ResponseType, ScrolledWindow, SelectionMode, Window,
+};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use pasteclipboard::auto_type::{hotp_labels, parse_sequence, Credentials, Field};
+use pasteclipboard::global_shortcuts::Trigger;
+use pasteclipboard::hotp;
+use pasteclipboard::snippets::{
+    delete_snippet, export_snippet, import_snippet, forget_credentials, has_credentials, is_encrypted, is_secure,
+    list_snippets, load_credentials, load_snippet, load_snippet_settings, rename_snippet, save_credentials,
+    save_snippet, save_snippet_securely, save_snippet_settings, set_snippet_secure, snippet_shortcuts, NewlineMode,
+    SnippetSettings,
+};
+use pasteclipboard::totp::Totp;
+use zeroize::Zeroizing;
+use pasteclipboard::typing::{EnterKey, MAX_CHAR_DELAY_MS, MAX_DELAY_SECONDS};
+
+use crate::snippet_shortcuts;
+use crate::window::MainWindow;
+use crate::window_picker;
+
+const NEWLINE_LABELS: [&str; 3] = ["Type as stored", "Strip trailing line breaks", "End with Enter"];
+
+/// The snippet's read-back choices: the global setting, on, off.
+const READBACK_LABELS: [&str; 3] = ["Default", "Yes, select all and copy afterwards", "No"];
+
+struct SnippetsPanel {
+    ui: Weak<MainWindow>,
+    list: ListBox,
+    names: RefCell<Vec<String>>,
+}
+
+pub fn build(ui: &Rc<MainWindow>) -> gtk4::Box {
+    let sidebar = gtk4::Box::new(Orientation::Vertical, 6);
+    sidebar.set_margin_start(12);
+    sidebar.set_margin_top(12);
+    sidebar.set_margin_bottom(12);
+    sidebar.set_size_request(180, -1);
+
+    let lbl_title = Label::new(Some("Snippets:"));
+    lbl_title.set_xalign(0.0);
+    sidebar.append(&lbl_title);
+
+    let list = ListBox::new();
+    list.set_selection_mode(SelectionMode::Single);
+    list.set_activate_on_single_click(false);
+    let scrolled = ScrolledWindow::builder().vexpand(true).child(&list).build();
+    sidebar.append(&scrolled);
+
+    let panel = Rc::new(SnippetsPanel { ui: Rc::downgrade(ui), list: list.clone(), names: RefCell::default() });
+    panel.refresh(None);
+
+    let row1 = gtk4::Box::new(Orientation::Horizontal, 4);
+    let btn_add = Button::with_label("Add…");
+    btn_add.set_tooltip_text(Some("Save the text area as a new snippet"));
+    let btn_edit = Button::with_label("Edit…");
+    let btn_delete = Button::with_label("Delete");
+    btn_delete.set_tooltip_text(Some("Move the snippet to the snippets/trash folder"));
+    row1.append(&btn_add);
+    row1.append(&btn_edit);
+    row1.append(&btn_delete);
+    sidebar.append(&row1);
+
+    let row2 = gtk4::Box::new(Orientation::Horizontal, 4);
+    let btn_import = Button::with_label("Import…");
+    let btn_export = Button::with_label("Export…");
+    row2.append(&btn_import);
+    row2.append(&btn_export);
+    sidebar.append(&row2);
+
+    let btn_type = Button::with_label("Type snippet");
+    sidebar.append(&btn_type);
+
+    // The same operations back the buttons and the context menu.
+    let actions = SimpleActionGroup::new();
+    let add_action = |name: &str, handler: fn(&Rc<SnippetsPanel>)| {
+        let action = SimpleAction::new(name, None);
+        let panel = panel.clone();
+        action.connect_activate(move |_, _| handler(&panel));
+        actions.add_action(&action);
+    };
+    add_action("load", SnippetsPanel::load_selected);
+    add_action("type", SnippetsPanel::type_selected);
+    add_action("add", SnippetsPanel::add);
+    add_action("edit", SnippetsPanel::edit_selected);
+    add_action("update", SnippetsPanel::update_selected);
+    add_action("delete", SnippetsPanel::delete_selected);
+    add_action("import", SnippetsPanel::import);
+    add_action("export", SnippetsPanel::export_selected);
+    sidebar.insert_action_group("snippet", Some(&actions));
+
+    // For whatever else changes the snippets folder, like importing a
+    // configuration.
+    let refresh = SimpleAction::new("refresh-snippets", None);
+    let panel_clone = panel.clone();
+    refresh.connect_activate(move |_, _| panel_clone.refresh(None));
+    ui.window.add_action(&refresh);
+
+    btn_add.set_action_name(Some("snippet.add"));
+    btn_edit.set_action_name(Some("snippet.edit"));
+    btn_delete.set_action_name(Some("snippet.delete"));
+    btn_import.set_action_name(Some("snippet.import"));
+    btn_export.set_action_name(Some("snippet.export"));
+    btn_type.set_action_name(Some("snippet.type"));
+
+    let panel_clone = panel.clone();
+    list.connect_row_activated(move |_, _| panel_clone.load_selected());
+
+    let menu = Menu::new();
+    menu.append(Some("Load into text area"), Some("snippet.load"));
+    menu.append(Some("Type snippet"), Some("snippet.type"));
+    menu.append(Some("Edit…"), Some("snippet.edit"));
+    menu.append(Some("Save text area into snippet"), Some("snippet.update"));
+    menu.append(Some("Export…"), Some("snippet.export"));
+    menu.append(Some("Delete"), Some("snippet.delete"));
+    let popover = PopoverMenu::from_model(Some(&menu));
+    popover.set_parent(&list);
+    popover.set_has_arrow(false);
+
+    let right_click = GestureClick::new();
+    right_click.set_button(gtk4::gdk::BUTTON_SECONDARY);
+    let list_clone = list.clone();
+    right_click.connect_pressed(move |_, _, x, y| {
+        let Some(row) = list_clone.row_at_y(y as i32) else {
+            return;
+        };
+        list_clone.select_row(Some(&row));
+        popover.set_pointing_to(Some(&gtk4::gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+        popover.popup();
+    });
+    list.add_controller(right_click);
+
+    sidebar
+}
+
+impl SnippetsPanel {
+    /// Reloads the list from disk, selecting `select` if given.
+    fn refresh(&self, select: Option<&str>) {
+        while let Some(row) = self.list.row_at_index(0) {
+            self.list.remove(&row);
+        }
+        let names = match list_snippets() {
+            Ok(names) => names,
+            Err(e) => {
+                self.error(&format!("{:#}.", e));
+                Vec::new()
+            }
+        };
+        for name in &names {
+            let row = gtk4::Box::new(Orientation::Horizontal, 6);
+            let label = Label::new(Some(name));
+            label.set_xalign(0.0);
+            label.set_hexpand(true);
+            row.append(&label);
+            if is_encrypted(name) {
+                let lock = Image::from_icon_name("changes-prevent-symbolic");
+                lock.set_tooltip_text(Some("Encrypted with gpg"));
+                row.append(&lock);
+            }
+            self.list.append(&row);
+        }
+        if let Some(index) = select.and_then(|s| names.iter().position(|n| n == s)) {
+            self.list.select_row(self.list.row_at_index(index as i32).as_ref());
+        }
+        *self.names.borrow_mut() = names;
+    }
+
+    fn selected(&self) -> Option<String> {
+        let index = self.list.selected_row()?.index();
+        self.names.borrow().get(usize::try_from(index).ok()?).cloned()
+    }
+
+    fn status(&self, text: &str) {
+        if let Some(ui) = self.ui.upgrade() {
+            ui.set_status(text);
+        }
+    }
+
+    fn error(&self, text: &str) {
+        if let Some(ui) = self.ui.upgrade() {
+            ui.set_error(text);
+        }
+    }
+
+    fn parent_window(&self) -> Option<Window> {
+        self.list.root().and_then(|root| root.downcast::<Window>().ok())
+    }
+
+    fn load_selected(self: &Rc<Self>) {
+        let (Some(ui), Some(name)) = (self.ui.upgrade(), self.selected()) else {
+            return;
+        };
+        match load_snippet(&name) {
+            Ok(text) => {
+                ui.replace_text(&text);
+                // Kept out of the drafts, and runs from it are secret.
+                ui.tabs.set_secret(is_secure(&name) || is_encrypted(&name));
+                ui.set_status(&format!("Loaded snippet '{}'.", name));
+            }
+            Err(e) => ui.set_error(&format!("{:#}.", e)),
+        }
+    }
+
+    fn type_selected(self: &Rc<Self>) {
+        let Some(ui) = self.ui.upgrade() else {
+            return;
+        };
+        let Some(name) = self.selected() else {
+            ui.set_status("Select a snippet to type.");
+            return;
+        };
+        ui.start_snippet(&name, ui.delay_seconds());
+    }
+
+    fn add(self: &Rc<Self>) {
+        self.show_properties(None);
+    }
+
+    fn edit_selected(self: &Rc<Self>) {
+        match self.selected() {
+            Some(name) => self.show_properties(Some(name)),
+            None => self.status("Select a snippet to edit."),
+        }
+    }
+
+    /// Replaces the selected snippet's text with the text area's, encrypted
+    /// again if the snippet is encrypted.
+    fn update_selected(self: &Rc<Self>) {
+        let (Some(ui), Some(name)) = (self.ui.upgrade(), self.selected()) else {
+            self.status("Select a snippet to update.");
+            return;
+        };
+        let text = ui.buffer.text(&ui.buffer.start_iter(), &ui.buffer.end_iter(), true);
+        match save_snippet(&name, &text) {
+            Ok(()) => self.status(&format!("Saved the text area into snippet '{}'.", name)),
+            Err(e) => self.error(&format!("{:#}.", e)),
+        }
+    }
+
+    fn delete_selected(self: &Rc<Self>) {
+        let Some(name) = self.selected() else {
+            self.status("Select a snippet to delete.");
+            return;
+        };
+        let had_shortcut = load_snippet_settings(&name).shortcut.is_some();
+        match delete_snippet(&name) {
+            Ok(moved) => {
+                match moved {
+                    Some(path) => self.status(&format!("Moved snippet '{}' to {}.", name, path.display())),
+                    None => self.status(&format!("Deleted snippet '{}' from the keyring.", name)),
+                }
+                if had_shortcut {
+                    snippet_shortcuts::rebind();
+                }
+            }
+            Err(e) => self.error(&format!("{:#}.", e)),
+        }
+        self.refresh(None);
+    }
+
+    fn import(self: &Rc<Self>) {
+        let dialog = FileChooserNative::new(
+            Some("Import snippet"),
+            self.parent_window().as_ref(),
+            FileChooserAction::Open,
+            Some("Import"),
+            Some("Cancel"),
+        );
+        let panel = self.clone();
+        dialog.connect_response(glib::clone!(@strong dialog => move |_, response| {
+            if response == ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|f| f.path()) {
+                    match import_snippet(&path) {
+                        Ok(name) => {
+                            panel.refresh(Some(&name));
+                            panel.status(&format!("Imported snippet '{}'.", name));
+                        }
+                        Err(e) => panel.status(&format!("{:#}.", e)),
+                    }
+                }
+            }
+            dialog.destroy();
+        }));
+        dialog.show();
+    }
+
+    fn export_selected(self: &Rc<Self>) {
+        let Some(name) = self.selected() else {
+            self.status("Select a snippet to export.");
+            return;
+        };
+        let dialog = FileChooserNative::new(
+            Some("Export snippet"),
+            self.parent_window().as_ref(),
+            FileChooserAction::Save,
+            Some("Export"),
+            Some("Cancel"),
+        );
+        dialog.set_current_name(&format!("{}.txt", name));
+        let panel = self.clone();
+        dialog.connect_response(glib::clone!(@strong dialog => move |_, response| {
+            if response == ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|f| f.path()) {
+                    match export_snippet(&name, &path) {
+                        Ok(()) => panel.status(&format!("Exported snippet '{}' to {}.", name, path.display())),
+                        Err(e) => panel.status(&format!("{:#}.", e)),
+                    }
+                }
+            }
+            dialog.destroy();
+        }));
+        dialog.show();
+    }
+
+    /// Name and settings of a snippet. With `existing` unset, the
+    /// text area is saved as a new snippet.
+    fn show_properties(self: &Rc<Self>, existing: Option<String>) {
+        let dialog = Window::builder()
+            .modal(true)
+            .title(if existing.is_some() { "Edit snippet" } else { "Add snippet" })
+            .default_width(360)
+            .build();
+        dialog.set_transient_for(self.parent_window().as_ref());
+
+        let grid = gtk4::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(8);
+        grid.set_margin_start(12);
+        grid.set_margin_end(12);
+        grid.set_margin_top(12);
+        grid.set_margin_bottom(12);
+
+        let settings = existing.as_deref().map(load_snippet_settings).unwrap_or_default();
+        let shortcut_before = settings.shortcut.clone();
+        let entry_name = Entry::new();
+        entry_name.set_text(existing.as_deref().unwrap_or_default());
+        entry_name.set_hexpand(true);
+        let entry_delay = Entry::new();
+        entry_delay.set_placeholder_text(Some("window's delay"));
+        entry_delay.set_text(&settings.delay_seconds.map(|d| d.to_string()).unwrap_or_default());
+        let dd_newline = DropDown::from_strings(&NEWLINE_LABELS);
+        let mode_index = NewlineMode::ALL.iter().position(|m| *m == settings.newline).unwrap_or(0);
+        dd_newline.set_selected(mode_index as u32);
+        let entry_char_delay = Entry::new();
+        entry_char_delay.set_placeholder_text(Some("global setting"));
+        entry_char_delay.set_text(&settings.char_delay_ms.map(|ms| ms.to_string()).unwrap_or_default());
+        // Index 0 is "no override".
+        let enter_labels: Vec<&str> =
+            std::iter::once("Default").chain(EnterKey::ALL.iter().map(|key| key.label())).collect();
+        let dd_enter = DropDown::from_strings(&enter_labels);
+        let enter_index = settings.enter_key.and_then(|k| EnterKey::ALL.iter().position(|x| *x == k)).map_or(0, |i| i + 1);
+        dd_enter.set_selected(enter_index as u32);
+        let entry_target = Entry::new();
+        entry_target.set_placeholder_text(Some("any window, e.g. *VirtualBox*"));
+        entry_target.set_tooltip_text(Some(
+            "Typing fails unless the focused window's title matches: * stands for anything, and a pattern without * \
+             may appear anywhere in the title. Needs an X11 session.",
+        ));
+        entry_target.set_text(settings.target_window.as_deref().unwrap_or_default());
+        let entry_focus = Entry::builder().hexpand(true).placeholder_text("none").build();
+        entry_focus.set_tooltip_text(Some(
+            "The window with this exact title, or else the first whose title matches it as above, is brought to the \
+             front right before typing; if none is open, nothing is typed. Needs an X11 session.",
+        ));
+        entry_focus.set_text(settings.focus_window.as_deref().unwrap_or_default());
+        let btn_pick = Button::with_label("Pick…");
+        let focus = gtk4::Box::new(Orientation::Horizontal, 6);
+        focus.append(&entry_focus);
+        focus.append(&btn_pick);
+        // Index 0 is "no override", as for the Enter key.
+        let dd_readback = DropDown::from_strings(&READBACK_LABELS);
+        dd_readback.set_selected(match settings.verify_readback {
+            None => 0,
+            Some(true) => 1,
+            Some(false) => 2,
+        });
+        dd_readback.set_tooltip_text(Some(
+            "Once the text is typed, presses select-all and copy in the target and compares what was copied with \
+             the text, then puts your clipboard back. Only use it where the text is all the target holds, such as \
+             an empty text field: select-all takes in whatever else is there, and in a terminal Ctrl+C interrupts \
+             the program that runs in it.",
+        ));
+        let entry_shortcut = Entry::new();
+        entry_shortcut.set_placeholder_text(Some("none, e.g. super+f1"));
+        entry_shortcut.set_tooltip_text(Some(
+            "Types the snippet from any application, after its delay: ctrl, alt, shift or super joined by + to a \
+             letter, a digit or F1–F24. The desktop may ask you to confirm it, or give it other keys. Needs the \
+             GlobalShortcuts portal.",
+        ));
+        entry_shortcut.set_text(&settings.shortcut.as_ref().map(Trigger::name).unwrap_or_default());
+        // A saved login is never shown; what is typed here replaces it.
+        let has_login = existing.as_deref().is_some_and(has_credentials);
+        let login_hint = if has_login { "saved in the keyring" } else { "asked for when typed" };
+        let entry_username = Entry::new();
+        entry_username.set_placeholder_text(Some(login_hint));
+        let entry_password = PasswordEntry::new();
+        entry_password.set_show_peek_icon(true);
+        entry_password.set_property("placeholder-text", login_hint);
+        let entry_totp = PasswordEntry::new();
+        entry_totp.set_show_peek_icon(true);
+        let totp_hint = if has_login { "saved in the keyring, if given" } else { "base32 or otpauth:// link" };
+        entry_totp.set_property("placeholder-text", totp_hint);
+        entry_totp.set_tooltip_text(Some(
+            "The secret for {TOTP}: the base32 key shown next to the site's QR code, or the otpauth://totp/ link it \
+             holds, which may also set the digits, period and algorithm.",
+        ));
+
+        let fields = [
+            ("Name:", entry_name.upcast_ref::<gtk4::Widget>()),
+            ("Delay (seconds):", entry_delay.upcast_ref()),
+            ("Line breaks:", dd_newline.upcast_ref()),
+            ("Pause between characters (ms):", entry_char_delay.upcast_ref()),
+            ("Type line breaks with:", dd_enter.upcast_ref()),
+            ("Only type into windows titled:", entry_target.upcast_ref()),
+            ("Bring to the front first:", focus.upcast_ref()),
+            ("Read back and compare:", dd_readback.upcast_ref()),
+            ("Global shortcut:", entry_shortcut.upcast_ref()),
+            ("Auto-type user name:", entry_username.upcast_ref()),
+            ("Auto-type password:", entry_password.upcast_ref()),
+            ("Auto-type TOTP seed:", entry_totp.upcast_ref()),
+        ];
+        let mut rows = fields.len() as i32;
+        for (row, (text, widget)) in fields
+        .into_iter()
+        .enumerate()
+        {
+            let label = Label::new(Some(text));
+            label.set_xalign(0.0);
+            grid.attach(&label, 0, row as i32, 1, 1);
+            grid.attach(widget, 1, row as i32, 1, 1);
+        }
+        // Each {HOTP:…} token the text uses gets its seed and, to resync it
+        // with the server, its next counter. An encrypted text is not
+        // decrypted just for this.
+        let sequence = match (&existing, self.ui.upgrade()) {
+            (Some(name), _) if is_encrypted(name) => String::new(),
+            (Some(name), _) => load_snippet(name).unwrap_or_default(),
+            (None, Some(ui)) => ui.buffer.text(&ui.buffer.start_iter(), &ui.buffer.end_iter(), true).to_string(),
+            (None, None) => String::new(),
+        };
+        let mut hotp_rows = Vec::new();
+        for label in parse_sequence(&sequence).map(|tokens| hotp_labels(&tokens)).unwrap_or_default() {
+            let saved = hotp::saved_counter(&label);
+            let entry_seed = PasswordEntry::new();
+            entry_seed.set_show_peek_icon(true);
+            let seed_hint = if saved.is_some() { "saved in the keyring, if given" } else { "base32 or otpauth:// link" };
+            entry_seed.set_property("placeholder-text", seed_hint);
+            entry_seed.set_tooltip_text(Some(
+                "The secret for this {HOTP:…} token: a base32 key, or an otpauth://hotp/ link, which may also set \
+                 the counter, digits and algorithm.",
+            ));
+            let entry_counter = Entry::new();
+            entry_counter.set_placeholder_text(Some(&match saved {
+                Some(counter) => format!("{}, as saved", counter),
+                None => "0, or the link's".to_string(),
+            }));
+            entry_counter.set_tooltip_text(Some(
+                "The counter of the code typed next. When the server no longer takes the codes, for one because \
+                 codes were used elsewhere, set it to the counter the server expects.",
+            ));
+            for (text, widget) in [
+                (format!("HOTP '{}' seed:", label), entry_seed.upcast_ref::<gtk4::Widget>()),
+                (format!("HOTP '{}' next counter:", label), entry_counter.upcast_ref()),
+            ] {
+                let text = Label::new(Some(&text));
+                text.set_xalign(0.0);
+                grid.attach(&text, 0, rows, 1, 1);
+                grid.attach(widget, 1, rows, 1, 1);
+                rows += 1;
+            }
+            hotp_rows.push((label, entry_seed, entry_counter));
+        }
+
+        let was_secure = existing.as_deref().is_some_and(is_secure);
+        let chk_secure = CheckButton::with_label("Store securely in the keyring");
+        chk_secure.set_tooltip_text(Some(
+            "Keeps the text in the desktop keyring instead of the snippets folder; only the settings stay in \
+             index.ini. Loading the snippet may ask to unlock the keyring.",
+        ));
+        chk_secure.set_active(was_secure);
+        // An encrypted snippet stays in its .gpg file.
+        chk_secure.set_sensitive(!existing.as_deref().is_some_and(is_encrypted));
+        grid.attach(&chk_secure, 0, rows, 2, 1);
+        let chk_auto_type = CheckButton::with_label("Auto-type sequence");
+        chk_auto_type.set_tooltip_text(Some(
+            "Reads {USERNAME}, {PASSWORD}, {TOTP}, {TAB}, {ENTER} and {DELAY 500} in the text, as password managers \
+             do. The user name and password come from the fields above, kept in the keyring, or are asked for when \
+             the snippet is typed; {TOTP} types the current code of the seed above, {HOTP:vpn} the next code of the \
+             HOTP token saved as vpn, and {PASS:work/vpn} the password pass has for that entry. {{} and {}} type a \
+             literal brace.",
+        ));
+        chk_auto_type.set_active(settings.auto_type);
+        grid.attach(&chk_auto_type, 0, rows + 1, 2, 1);
+        let chk_forget = CheckButton::with_label("Forget the saved login");
+        chk_forget.set_visible(has_login);
+        grid.attach(&chk_forget, 0, rows + 2, 2, 1);
+
+        let lbl_error = Label::new(None);
+        lbl_error.set_xalign(0.0);
+        lbl_error.set_wrap(true);
+        grid.attach(&lbl_error, 0, rows + 3, 2, 1);
+
+        let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+        buttons.set_halign(gtk4::Align::End);
+        let btn_cancel = Button::with_label("Cancel");
+        let btn_save = Button::with_label("Save");
+        buttons.append(&btn_cancel);
+        buttons.append(&btn_save);
+        grid.attach(&buttons, 0, rows + 4, 2, 1);
+        dialog.set_child(Some(&grid));
+
+        let dialog_clone = dialog.clone();
+        btn_cancel.connect_clicked(move |_| dialog_clone.close());
+        let (dialog_clone, entry_focus_clone) = (dialog.clone(), entry_focus.clone());
+        btn_pick.connect_clicked(move |_| {
+            let entry_focus = entry_focus_clone.clone();
+            window_picker::show(&dialog_clone, "Choose", move |title| entry_focus.set_text(&title));
+        });
+
+        let panel = self.clone();
+        let dialog_clone = dialog.clone();
+        btn_save.connect_clicked(move |_| {
+            let name = entry_name.text().to_string();
+            let delay_text = entry_delay.text();
+            let delay_seconds = match delay_text.trim() {
+                "" => None,
+                text => match text.parse::<u64>() {
+                    Ok(d) if d <= MAX_DELAY_SECONDS => Some(d),
+                    _ => {
+                        lbl_error.set_text("Invalid delay (must be a number from 0–86400, or empty).");
+                        return;
+                    }
+                },
+            };
+            let char_delay_text = entry_char_delay.text();
+            let char_delay_ms = match char_delay_text.trim() {
+                "" => None,
+                text => match text.parse::<u32>() {
+                    Ok(ms) if ms <= MAX_CHAR_DELAY_MS => Some(ms),
+                    _ => {
+                        lbl_error.set_text(&format!(
+                            "Invalid pause (must be a number from 0–{}, or empty).",
+                            MAX_CHAR_DELAY_MS
+                        ));
+                        return;
+                    }
+                },
+            };
+            let target = entry_target.text().trim().to_string();
+            let focus = entry_focus.text().trim().to_string();
+            let shortcut = match entry_shortcut.text().trim() {
+                "" => None,
+                text => match Trigger::parse(text) {
+                    Ok(trigger) => Some(trigger),
+                    Err(e) => {
+                        lbl_error.set_text(&format!("Invalid shortcut: {}.", e));
+                        return;
+                    }
+                },
+            };
+            if let Some(trigger) = &shortcut {
+                let taken = snippet_shortcuts().into_iter().find(|(other, bound)| {
+                    bound == trigger && *other != name && Some(other) != existing.as_ref()
+                });
+                if let Some((other, _)) = taken {
+                    lbl_error.set_text(&format!("{} is already the shortcut of snippet '{}'.", trigger, other));
+                    return;
+                }
+            }
+            let rebind = shortcut_before != shortcut
+                || (shortcut.is_some() && existing.as_deref().is_some_and(|old| old != name));
+            let settings = SnippetSettings {
+                delay_seconds,
+                newline: NewlineMode::ALL[(dd_newline.selected() as usize).min(NewlineMode::ALL.len() - 1)],
+                char_delay_ms,
+                enter_key: (dd_enter.selected() as usize).checked_sub(1).and_then(|i| EnterKey::ALL.get(i).copied()),
+                target_window: (!target.is_empty()).then_some(target),
+                focus_window: (!focus.is_empty()).then_some(focus),
+                auto_type: chk_auto_type.is_active(),
+                verify_readback: [None, Some(true), Some(false)][(dd_readback.selected() as usize).min(2)],
+                shortcut,
+            };
+            let secure = chk_secure.is_active();
+            let result = match &existing {
+                Some(old) => rename_snippet(old, &name)
+                    .and_then(|()| if secure != was_secure { set_snippet_secure(&name, secure) } else { Ok(()) }),
+                None => match panel.ui.upgrade() {
+                    Some(ui) if !panel.names.borrow().contains(&name) => {
+                        let text = ui.buffer.text(&ui.buffer.start_iter(), &ui.buffer.end_iter(), true);
+                        if secure {
+                            save_snippet_securely(&name, &text)
+                        } else {
+                            save_snippet(&name, &text)
+                        }
+                    }
+                    Some(_) => Err(anyhow::anyhow!("A snippet called '{}' already exists", name)),
+                    None => return,
+                },
+            }
+            .and_then(|()| save_snippet_settings(&name, &settings))
+            .and_then(|()| save_login(&name, &entry_username, &entry_password, &entry_totp, chk_forget.is_active()))
+            .and_then(|()| save_hotp_tokens(&hotp_rows));
+            match result {
+                Ok(()) => {
+                    panel.refresh(Some(&name));
+                    dialog_clone.close();
+                    if rebind {
+                        snippet_shortcuts::rebind();
+                    }
+                }
+                Err(e) => lbl_error.set_text(&format!("{:#}.", e)),
+            }
+        });
+
+        dialog.present();
+    }
+}
+
+/// Saves what the login fields were given: a typed value replaces the
+/// saved one, and an empty field keeps it. A TOTP seed must be readable.
+/// `forget` drops the saved login first.
+fn save_login(
+    name: &str,
+    username: &Entry,
+    password: &PasswordEntry,
+    totp: &PasswordEntry,
+    forget: bool,
+) -> anyhow::Result<()> {
+    let typed = [
+        (Field::Username, Zeroizing::new(username.text().to_string())),
+        (Field::Password, Zeroizing::new(password.text().to_string())),
+    ];
+    let seed = Zeroizing::new(totp.text().trim().to_string());
+    if !seed.is_empty() {
+        Totp::parse(&seed)?;
+    }
+    if forget {
+        forget_credentials(name)?;
+    }
+    if typed.iter().all(|(_, value)| value.is_empty()) && seed.is_empty() {
+        return Ok(());
+    }
+    let mut credentials = if has_credentials(name) { load_credentials(name)? } else { Credentials::default() };
+    for (field, value) in typed {
+        if !value.is_empty() {
+            credentials.set(field, value);
+        }
+    }
+    if !seed.is_empty() {
+        credentials.totp_seed = Some(seed);
+    }
+    save_credentials(name, &credentials)
+}
+
+/// Saves what the HOTP fields were given, as for the login: an empty field
+/// keeps what is saved. Every field is checked before any is saved.
+fn save_hotp_tokens(rows: &[(String, PasswordEntry, Entry)]) -> anyhow::Result<()> {
+    let mut tokens = Vec::new();
+    for (label, seed, counter) in rows {
+        let seed = Zeroizing::new(seed.text().trim().to_string());
+        if !seed.is_empty() {
+            hotp::Hotp::parse(&seed)?;
+        }
+        let counter = match counter.text().trim() {
+            "" => None,
+            text => match text.parse::<u64>() {
+                Ok(counter) => Some(counter),
+                Err(_) => anyhow::bail!("Invalid next counter for HOTP token '{}' (must be a whole number, or empty)", label),
+            },
+        };
+        tokens.push((label, seed, counter));
+    }
+    for (label, seed, counter) in tokens {
+        hotp::save_token(label, Some(seed.as_str()).filter(|seed| !seed.is_empty()), counter)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Optional sounds when a run finishes or fails. They come from the
+//! freedesktop sound theme, played through GTK's media support; without
+//! either, the display beeps instead.
+
+use gtk4::prelude::*;
+use gtk4::{gdk::Display, MediaFile};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use pasteclipboard::settings::Settings;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Sound {
+    Success,
+    Failure,
+}
+
+impl Sound {
+    fn theme_name(self) -> &'static str {
+        match self {
+            Sound::Success => "complete",
+            Sound::Failure => "dialog-error",
+        }
+    }
+}
+
+thread_local! {
+    // A MediaFile stops when dropped, so the current one is kept until the next.
+    static PLAYING: RefCell<Option<MediaFile>> = RefCell::default();
+}
+
+/// Looks the sound up in `$XDG_DATA_DIRS`, like a sound-theme loader would.
+fn theme_file(sound: Sound) -> Option<PathBuf> {
+    let dirs = std::env::var("XDG_DATA_DIRS").unwrap_or_default();
+    let dirs = if dirs.is_empty() { "/usr/local/share:/usr/share".to_string() } else { dirs };
+    dirs.split(':')
+        .filter(|dir| !dir.is_empty())
+        .flat_map(|dir| {
+            ["oga", "ogg", "wav"]
+                .map(|ext| PathBuf::from(dir).join("sounds/freedesktop/stereo").join(format!("{}.{}", sound.theme_name(), ext)))
+        })
+        .find(|path| path.is_file())
+}
+
+fn beep() {
+    if let Some(display) = Display::default() {
+        display.beep();
+    }
+}
+
+/// Plays `sound` regardless of the setting, at full stream volume. Must be
+/// called on the main thread.
+pub fn play(sound: Sound) {
+    let Some(path) = theme_file(sound) else {
+        beep();
+        return;
+    };
+    let media = MediaFile::for_filename(path);
+    // GTK builds without a media backend report that as an error.
+    media.connect_error_notify(|media| {
+        if media.error().is_some() {
+            beep();
+        }
+    });
+    media.set_volume(1.0);
+    media.play();
+    PLAYING.with(|playing| *playing.borrow_mut() = Some(media));
+}
+
+/// Plays `sound` if sounds are turned on in `settings`.
+pub fn play_if_enabled(sound: Sound, settings: &Settings) {
+    if settings.sounds {
+        play(sound);
+    }
+}
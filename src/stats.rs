@@ -0,0 +1,49 @@
+//! Cumulative typing statistics in ~/.local/state/PasteClipboard/stats.ini.
+
+use std::path::PathBuf;
+
+use configparser::ini::Ini;
+
+use crate::settings::state_path;
+use crate::typing::RunStats;
+
+/// Totals over every run, finished or failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub sessions: u64,
+    pub characters: u64,
+}
+
+pub fn stats_path() -> Option<PathBuf> {
+    state_path("stats.ini")
+}
+
+fn load_stats() -> Ini {
+    let mut stats = Ini::new();
+    if let Some(path) = stats_path() {
+        let _ = stats.load(path);
+    }
+    stats
+}
+
+pub fn load_totals() -> Totals {
+    let stats = load_stats();
+    let get = |key| stats.getuint("totals", key).ok().flatten().unwrap_or(0);
+    Totals { sessions: get("sessions"), characters: get("characters") }
+}
+
+/// Adds one run to the totals.
+pub fn record_run(run: &RunStats) {
+    let Some(path) = stats_path() else {
+        return;
+    };
+    let totals = load_totals();
+    let mut stats = load_stats();
+    stats.set("totals", "sessions", Some((totals.sessions + 1).to_string()));
+    stats.set("totals", "characters", Some((totals.characters + run.typed as u64).to_string()));
+
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = stats.write(path);
+}
@@ -0,0 +1,193 @@
+// The status area under the text. Routine messages (the countdown, "Opened
+// notes.txt.") replace each other; warnings and errors are styled by
+// severity, have a close button and, for a failed run, a "Details"
+// expander with the full error chain. A warning or error stays until it is
+// closed or the next run starts (a banner, such as the config.toml problems
+// found at startup, stays until it is closed): messages arriving meanwhile
+// queue behind it, and the latest routine one is shown once it goes.
+
+use gtk4::prelude::*;
+use gtk4::{AccessibleRole, Button, Expander, Image, Label, Orientation};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use pasteclipboard::i18n::{args, gettext, ngettext};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Error];
+
+    /// The style class, also used by the window's CSS.
+    fn css_class(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    fn icon_name(self) -> &'static str {
+        match self {
+            Severity::Info => "dialog-information-symbolic",
+            Severity::Warning => "dialog-warning-symbolic",
+            Severity::Error => "dialog-error-symbolic",
+        }
+    }
+}
+
+struct Notice {
+    severity: Severity,
+    text: String,
+    details: Option<String>,
+    /// Only the close button removes it.
+    sticky: bool,
+}
+
+pub struct StatusArea {
+    pub container: gtk4::Box,
+    /// Has the status role, so screen readers announce every change.
+    pub label: Label,
+    icon: Image,
+    details: Expander,
+    lbl_details: Label,
+    btn_close: Button,
+    /// Warnings and errors, oldest first; the first one is shown.
+    notices: RefCell<VecDeque<Notice>>,
+    /// The latest routine message, shown while there is no notice.
+    info: RefCell<String>,
+}
+
+impl StatusArea {
+    pub fn build() -> Rc<StatusArea> {
+        let label = Label::builder()
+            .accessible_role(AccessibleRole::Status)
+            .xalign(0.0)
+            .wrap(true)
+            .hexpand(true)
+            .build();
+        let icon = Image::new();
+        icon.set_visible(false);
+        let btn_close = Button::builder()
+            .icon_name("window-close-symbolic")
+            .valign(gtk4::Align::Start)
+            .visible(false)
+            .build();
+        btn_close.add_css_class("flat");
+        let row = gtk4::Box::new(Orientation::Horizontal, 6);
+        row.append(&icon);
+        row.append(&label);
+        row.append(&btn_close);
+
+        let lbl_details = Label::builder().xalign(0.0).wrap(true).selectable(true).build();
+        lbl_details.add_css_class("monospace");
+        let details = Expander::builder().label(gettext("Details")).child(&lbl_details).visible(false).build();
+
+        let container = gtk4::Box::new(Orientation::Vertical, 4);
+        container.add_css_class("status-area");
+        container.append(&row);
+        container.append(&details);
+
+        let status = Rc::new(StatusArea {
+            container,
+            label,
+            icon,
+            details,
+            lbl_details,
+            btn_close,
+            notices: RefCell::default(),
+            info: RefCell::default(),
+        });
+        let weak = Rc::downgrade(&status);
+        status.btn_close.connect_clicked(move |_| {
+            if let Some(status) = weak.upgrade() {
+                status.notices.borrow_mut().pop_front();
+                status.update();
+            }
+        });
+        status
+    }
+
+    /// A routine message; it waits behind a warning or error that is shown.
+    pub fn info(&self, text: &str) {
+        *self.info.borrow_mut() = text.to_string();
+        if self.notices.borrow().is_empty() {
+            self.update();
+        }
+    }
+
+    pub fn warning(&self, text: &str) {
+        self.push(Notice { severity: Severity::Warning, text: text.to_string(), details: None, sticky: false });
+    }
+
+    /// A warning that stays until it is closed, with `details` under
+    /// "Details". It replaces the banner shown before, if any.
+    pub fn banner(&self, text: &str, details: String) {
+        self.notices.borrow_mut().retain(|notice| !notice.sticky);
+        self.push(Notice { severity: Severity::Warning, text: text.to_string(), details: Some(details), sticky: true });
+    }
+
+    pub fn clear_banner(&self) {
+        self.notices.borrow_mut().retain(|notice| !notice.sticky);
+        self.update();
+    }
+
+    /// `details` is shown under "Details", e.g. an error's whole chain.
+    pub fn error(&self, text: &str, details: Option<String>) {
+        self.push(Notice { severity: Severity::Error, text: text.to_string(), details, sticky: false });
+    }
+
+    fn push(&self, notice: Notice) {
+        self.notices.borrow_mut().push_back(notice);
+        self.update();
+    }
+
+    /// Drops the warnings, which the outcome of a run sums up, and keeps
+    /// the errors.
+    pub fn clear_warnings(&self) {
+        self.notices.borrow_mut().retain(|notice| notice.sticky || notice.severity != Severity::Warning);
+        self.update();
+    }
+
+    /// Drops every warning and error but the banners, as a new run starts.
+    pub fn clear(&self) {
+        self.notices.borrow_mut().retain(|notice| notice.sticky);
+        self.update();
+    }
+
+    fn update(&self) {
+        let notices = self.notices.borrow();
+        let info = self.info.borrow();
+        let (severity, text, details) = match notices.front() {
+            Some(notice) => (notice.severity, notice.text.as_str(), notice.details.as_deref()),
+            None => (Severity::Info, info.as_str(), None),
+        };
+        for other in Severity::ALL {
+            self.container.remove_css_class(other.css_class());
+        }
+        if !notices.is_empty() {
+            self.container.add_css_class(severity.css_class());
+        }
+        self.icon.set_icon_name(Some(severity.icon_name()));
+        self.icon.set_visible(!notices.is_empty());
+        self.label.set_text(text);
+        if self.lbl_details.text() != details.unwrap_or_default() {
+            self.lbl_details.set_text(details.unwrap_or_default());
+            self.details.set_expanded(false);
+        }
+        self.details.set_visible(details.is_some());
+        self.btn_close.set_visible(!notices.is_empty());
+        let waiting = notices.len().saturating_sub(1) as u64;
+        let tooltip = if waiting > 0 {
+            args(&ngettext("Dismiss ({0} more message)", "Dismiss ({0} more messages)", waiting), &[&waiting])
+        } else {
+            gettext("Dismiss")
+        };
+        self.btn_close.set_tooltip_text(Some(&tooltip));
+    }
+}
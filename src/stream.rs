@@ -0,0 +1,255 @@
+//! Stream runs (`--stream`): the lines of standard input are typed as they
+//! come in, each with Enter after it, so a script can feed a run as it
+//! goes. A line that holds nothing but a directive between two sentinels
+//! (`stream_sentinel`, [`DEFAULT_SENTINEL`] unless set) is not typed but
+//! controls the run:
+//!
+//! - `%%PAUSE 2000%%` waits 2000 milliseconds before going on;
+//! - `%%WAIT-ENTER%%` waits until the operator confirms in the window.
+//!
+//! Directives are matched regardless of case, and spaces inside the
+//! sentinels are allowed. A line that starts and ends with the sentinel but
+//! holds no directive, such as `%%PAUSE soon%%`, is typed as it is, with a
+//! warning; the sentinel can be changed when the text itself has such
+//! lines.
+
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use flume::{Receiver, Sender, TryRecvError};
+
+use crate::assistive::Wait;
+use crate::backend::Backend;
+use crate::key_plan::{Plan, PlannedEvent};
+use crate::typing::{
+    connect_configured, run_steps, Connect, Progress, Step, TypingEvent, TypingOptions, MAX_DELAY_SECONDS,
+};
+
+/// What marks a control line unless `stream_sentinel` says otherwise.
+pub const DEFAULT_SENTINEL: &str = "%%";
+
+/// Longest pause a `PAUSE` line may ask for, in milliseconds.
+pub const MAX_PAUSE_MS: u64 = MAX_DELAY_SECONDS * 1000;
+
+/// A line of a stream, as [`parse_line`] reads it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StreamLine<'a> {
+    /// To be typed.
+    Text(&'a str),
+    Pause(Duration),
+    WaitEnter,
+    /// A control line that holds no directive: it is typed as text, and
+    /// `problem` says what is wrong with it.
+    Malformed { text: &'a str, problem: String },
+}
+
+/// Reads `line`, without its line break, with `sentinel` marking control
+/// lines.
+pub fn parse_line<'a>(line: &'a str, sentinel: &str) -> StreamLine<'a> {
+    let inner = line
+        .strip_prefix(sentinel)
+        .and_then(|rest| rest.strip_suffix(sentinel))
+        .filter(|_| !sentinel.is_empty() && line.len() >= 2 * sentinel.len());
+    let Some(inner) = inner else {
+        return StreamLine::Text(line);
+    };
+    let mut words = inner.split_whitespace();
+    let directive = words.next().unwrap_or_default();
+    let argument = words.next();
+    let extra = words.next().is_some();
+    let problem = if directive.eq_ignore_ascii_case("PAUSE") {
+        match argument.map(str::parse::<u64>) {
+            Some(Ok(ms)) if ms <= MAX_PAUSE_MS && !extra => return StreamLine::Pause(Duration::from_millis(ms)),
+            Some(Ok(_)) if !extra => format!("PAUSE takes at most {} milliseconds", MAX_PAUSE_MS),
+            _ => "PAUSE needs a number of milliseconds and nothing else".to_string(),
+        }
+    } else if directive.eq_ignore_ascii_case("WAIT-ENTER") {
+        if argument.is_none() {
+            return StreamLine::WaitEnter;
+        }
+        "WAIT-ENTER takes nothing after it".to_string()
+    } else if directive.is_empty() {
+        "it holds no directive".to_string()
+    } else {
+        format!("'{}' is neither PAUSE nor WAIT-ENTER", directive)
+    };
+    StreamLine::Malformed { text: line, problem }
+}
+
+/// A stream's lines as the steps of a run, planned as they come in.
+struct StreamSteps<'a> {
+    lines: Receiver<String>,
+    sentinel: &'a str,
+    options: &'a TypingOptions,
+    events: &'a Sender<TypingEvent>,
+    /// The keys of the line being typed.
+    pending: VecDeque<PlannedEvent>,
+    /// Lines read so far.
+    line: usize,
+    /// Characters read so far, line breaks included.
+    position: usize,
+}
+
+impl StreamSteps<'_> {
+    /// Plans `text` and a line break, with the pause after the line before
+    /// ahead of its first key.
+    fn queue(&mut self, text: &str) -> Step {
+        let text = format!("{}\n", text);
+        let mut keys: Vec<PlannedEvent> = Plan::new(text.as_str(), self.options).flatten().collect();
+        for key in &mut keys {
+            key.at += self.position;
+        }
+        if let Some(first) = keys.first_mut().filter(|_| self.position > 0) {
+            first.pre_delay += self.options.char_delay + self.options.newline_delay;
+        }
+        let chars = text.chars().count();
+        self.position += chars;
+        let typeable = keys.len();
+        self.pending.extend(keys);
+        Step::Line { typeable, skipped: chars - typeable }
+    }
+}
+
+impl Iterator for StreamSteps<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        if let Some(key) = self.pending.pop_front() {
+            return Some(Step::Key(key));
+        }
+        let line = match self.lines.try_recv() {
+            Ok(line) => line,
+            Err(TryRecvError::Empty) => return Some(Step::Idle),
+            Err(TryRecvError::Disconnected) => return None,
+        };
+        self.line += 1;
+        let step = match parse_line(&line, self.sentinel) {
+            StreamLine::Text(text) => self.queue(text),
+            StreamLine::Pause(wait) => Step::Pause(wait),
+            StreamLine::WaitEnter => Step::Confirm(Wait::Requested),
+            StreamLine::Malformed { text, problem } => {
+                let message = format!(
+                    "Line {} of the stream is marked as a control line, but {}; typing it as text.",
+                    self.line, problem
+                );
+                log::warn!("{}", message);
+                let _ = self.events.send(TypingEvent::Notice { message });
+                self.queue(text)
+            }
+        };
+        Some(step)
+    }
+}
+
+/// Types the lines `lines` brings, as `simulate_typing` types a text, until
+/// it is closed and every line is typed. Lines are read from `start_at` on;
+/// those that came earlier wait for it.
+pub fn stream_typing(
+    lines: Receiver<String>,
+    sentinel: &str,
+    options: &TypingOptions,
+    start_at: Instant,
+    cancel: &AtomicBool,
+    events: &Sender<TypingEvent>,
+) {
+    // Which keys the lines will need is not known, so the device gets them
+    // all.
+    let mut connect = |log: &dyn Fn(String)| connect_configured(options, None, log);
+    stream_typing_with(lines, sentinel, options, start_at, cancel, events, &mut connect);
+}
+
+/// [`stream_typing`] on the keyboards `connect` makes instead of the
+/// configured backend's.
+pub fn stream_typing_with(
+    lines: Receiver<String>,
+    sentinel: &str,
+    options: &TypingOptions,
+    start_at: Instant,
+    cancel: &AtomicBool,
+    events: &Sender<TypingEvent>,
+    connect: &mut Connect,
+) {
+    log::debug!(
+        "Stream started: backend={} char_delay_ms={} newline_delay_ms={}",
+        options.backend.map_or("auto", Backend::name),
+        options.char_delay.as_millis(),
+        options.newline_delay.as_millis()
+    );
+    let steps = StreamSteps {
+        lines,
+        sentinel,
+        options,
+        events,
+        pending: VecDeque::new(),
+        line: 0,
+        position: 0,
+    };
+    run_steps(steps, Progress::default(), options, start_at, cancel, events, connect);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn malformed<'a>(text: &'a str, problem: &str) -> StreamLine<'a> {
+        StreamLine::Malformed { text, problem: problem.to_string() }
+    }
+
+    #[test]
+    fn lines_are_read_as_text_or_directives() {
+        let pause_problem = "PAUSE needs a number of milliseconds and nothing else";
+        let over = format!("%%PAUSE {}%%", MAX_PAUSE_MS + 1);
+        let too_long = format!("PAUSE takes at most {} milliseconds", MAX_PAUSE_MS);
+        for (line, sentinel, expected) in [
+            ("hello world", "%%", StreamLine::Text("hello world")),
+            ("%%PAUSE 2000%%", "%%", StreamLine::Pause(Duration::from_millis(2000))),
+            ("%% pause 0 %%", "%%", StreamLine::Pause(Duration::ZERO)),
+            (over.as_str(), "%%", malformed(&over, &too_long)),
+            ("%%PAUSE soon%%", "%%", malformed("%%PAUSE soon%%", pause_problem)),
+            ("%%PAUSE 5 10%%", "%%", malformed("%%PAUSE 5 10%%", pause_problem)),
+            ("%%WAIT-ENTER%%", "%%", StreamLine::WaitEnter),
+            ("%%wait-enter%% extra", "%%", StreamLine::Text("%%wait-enter%% extra")),
+            ("%%WAIT-ENTER extra%%", "%%", malformed("%%WAIT-ENTER extra%%", "WAIT-ENTER takes nothing after it")),
+            ("%%SLEEP 5%%", "%%", malformed("%%SLEEP 5%%", "'SLEEP' is neither PAUSE nor WAIT-ENTER")),
+            ("%%%%", "%%", malformed("%%%%", "it holds no directive")),
+            // The sentinel on both sides needs twice its length.
+            ("%%", "%%", StreamLine::Text("%%")),
+            ("%%%", "%%", StreamLine::Text("%%%")),
+            ("##PAUSE 10##", "##", StreamLine::Pause(Duration::from_millis(10))),
+            ("%%PAUSE 10%%", "##", StreamLine::Text("%%PAUSE 10%%")),
+            // An empty sentinel marks nothing.
+            ("PAUSE 10", "", StreamLine::Text("PAUSE 10")),
+            ("", "", StreamLine::Text("")),
+        ] {
+            assert_eq!(parse_line(line, sentinel), expected, "{:?} with {:?}", line, sentinel);
+        }
+    }
+
+    #[test]
+    fn a_malformed_control_line_is_typed_with_a_warning() {
+        let (lines, incoming) = flume::unbounded();
+        let (events, notices) = flume::unbounded();
+        let options = TypingOptions::default();
+        let mut steps = StreamSteps {
+            lines: incoming,
+            sentinel: DEFAULT_SENTINEL,
+            options: &options,
+            events: &events,
+            pending: VecDeque::new(),
+            line: 0,
+            position: 0,
+        };
+        lines.send("%%PAUSE soon%%".to_string()).unwrap();
+        drop(lines);
+
+        assert!(matches!(steps.next(), Some(Step::Line { typeable: 15, skipped: 0 })));
+        // The line is typed as it is, and the stream ends after it.
+        assert!(steps.by_ref().all(|step| matches!(step, Step::Key(_))));
+        assert_eq!(steps.position, "%%PAUSE soon%%\n".len());
+        match notices.try_recv() {
+            Ok(TypingEvent::Notice { message }) => assert!(message.starts_with("Line 1 of the stream"), "{}", message),
+            _ => panic!("no warning for the line"),
+        }
+    }
+}
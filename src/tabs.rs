@@ -0,0 +1,318 @@
+// Tabs over the one text area (Ctrl+T, Ctrl+W, middle-click to close).
+// Each tab keeps its own text, file, cursor and unsaved-changes state; the
+// visible tab's is swapped into the shared buffer and `MainWindow::file`
+// when it is shown, so everything that works on the buffer — Start, the
+// counts, find, the preview — works on the visible tab.
+
+use gtk4::prelude::*;
+use gtk4::{gio::SimpleAction, Application, ButtonsType, GestureClick, Label, MessageDialog, MessageType, Notebook, ResponseType};
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
+use std::rc::Rc;
+use pasteclipboard::i18n::{args, gettext};
+use pasteclipboard::session::load_drafts;
+use pasteclipboard::settings::Settings;
+
+use crate::file_actions;
+use crate::window::MainWindow;
+
+/// What a hidden tab keeps; the visible tab's lives in the buffer.
+#[derive(Default)]
+struct TabState {
+    text: String,
+    file: Option<PathBuf>,
+    modified: bool,
+    cursor: i32,
+    /// The text came from the keyring or an encrypted snippet.
+    secret: bool,
+}
+
+struct Tab {
+    /// The notebook page. It is empty: the text view sits below the tabs.
+    page: gtk4::Box,
+    label: Label,
+    /// "Text 3" for a tab without a file.
+    number: u32,
+    state: TabState,
+}
+
+pub struct Tabs {
+    pub notebook: Notebook,
+    tabs: RefCell<Vec<Tab>>,
+    /// The page whose state is in the buffer.
+    shown: RefCell<Option<gtk4::Box>>,
+    /// Whether the visible tab's text is secret (see [`Tabs::set_secret`]).
+    shown_secret: Cell<bool>,
+    next_number: Cell<u32>,
+}
+
+impl Tabs {
+    pub fn new() -> Self {
+        let notebook = Notebook::builder().scrollable(true).show_border(false).build();
+        Tabs {
+            notebook,
+            tabs: RefCell::default(),
+            shown: RefCell::default(),
+            shown_secret: Cell::new(false),
+            next_number: Cell::new(1),
+        }
+    }
+
+    /// Marks the visible tab's text as secret, as when a snippet from the
+    /// keyring or an encrypted one was loaded into it: runs from it are
+    /// secret runs, and it is left out of the drafts, until its text is
+    /// replaced.
+    pub fn set_secret(&self, secret: bool) {
+        self.shown_secret.set(secret);
+    }
+
+    pub fn is_secret(&self) -> bool {
+        self.shown_secret.get()
+    }
+
+    fn is_shown(&self, page: &gtk4::Box) -> bool {
+        self.shown.borrow().as_ref() == Some(page)
+    }
+}
+
+pub fn install(app: &Application, ui: &Rc<MainWindow>) {
+    let add = |name: &str, accel: &str, handler: fn(&Rc<MainWindow>)| {
+        let action = SimpleAction::new(name, None);
+        let weak = Rc::downgrade(ui);
+        action.connect_activate(move |_, _| {
+            if let Some(ui) = weak.upgrade() {
+                handler(&ui);
+            }
+        });
+        ui.window.add_action(&action);
+        app.set_accels_for_action(&format!("win.{}", name), &[accel]);
+    };
+    add("new-tab", "<Control>t", new_tab);
+    add("close-tab", "<Control>w", |ui| {
+        if let Some(page) = ui.tabs.shown.borrow().clone() {
+            close_tab(ui, page);
+        }
+    });
+
+    let weak = Rc::downgrade(ui);
+    ui.tabs.notebook.connect_switch_page(move |_, page, _| {
+        let (Some(ui), Some(page)) = (weak.upgrade(), page.downcast_ref::<gtk4::Box>()) else {
+            return;
+        };
+        if !ui.tabs.is_shown(page) {
+            stash(&ui);
+            show(&ui, page);
+        }
+    });
+
+    let drafts = if Settings::load().restore_draft { load_drafts() } else { Vec::new() };
+    if drafts.is_empty() {
+        add_tab(ui, TabState::default());
+    }
+    for text in drafts {
+        // Never saved to a file, so closing the tab asks first.
+        add_tab(ui, TabState { text, modified: true, ..TabState::default() });
+    }
+    if let Some(page) = ui.tabs.tabs.borrow().first().map(|tab| tab.page.clone()) {
+        ui.tabs.notebook.set_current_page(Some(0));
+        if !ui.tabs.is_shown(&page) {
+            show(ui, &page);
+        }
+    }
+}
+
+fn add_tab(ui: &Rc<MainWindow>, state: TabState) -> u32 {
+    let tabs = &ui.tabs;
+    let number = tabs.next_number.get();
+    tabs.next_number.set(number + 1);
+    let page = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    let label = Label::new(None);
+    let middle_click = GestureClick::builder().button(2).build();
+    let weak = Rc::downgrade(ui);
+    let clicked = page.clone();
+    middle_click.connect_released(move |_, _, _, _| {
+        if let Some(ui) = weak.upgrade() {
+            close_tab(&ui, clicked.clone());
+        }
+    });
+    label.add_controller(middle_click);
+    tabs.tabs.borrow_mut().push(Tab { page: page.clone(), label: label.clone(), number, state });
+    let index = tabs.notebook.append_page(&page, Some(&label));
+    tabs.notebook.set_tab_reorderable(&page, true);
+    set_label(ui, &page);
+    index
+}
+
+/// Opens `text` in a new tab as never saved, like a restored draft.
+pub fn open_draft(ui: &Rc<MainWindow>, text: String) {
+    let index = add_tab(ui, TabState { text, modified: true, ..TabState::default() });
+    ui.tabs.notebook.set_current_page(Some(index));
+}
+
+fn new_tab(ui: &Rc<MainWindow>) {
+    let index = add_tab(ui, TabState::default());
+    ui.tabs.notebook.set_current_page(Some(index));
+}
+
+/// Saves the visible tab's state out of the buffer.
+fn stash(ui: &MainWindow) {
+    let Some(page) = ui.tabs.shown.borrow_mut().take() else {
+        return;
+    };
+    let buffer = &ui.buffer;
+    let mut tabs = ui.tabs.tabs.borrow_mut();
+    if let Some(tab) = tabs.iter_mut().find(|tab| tab.page == page) {
+        tab.state = TabState {
+            text: buffer.text(&buffer.start_iter(), &buffer.end_iter(), true).to_string(),
+            file: ui.file.borrow_mut().take(),
+            modified: buffer.is_modified(),
+            cursor: buffer.cursor_position(),
+            secret: ui.tabs.shown_secret.get(),
+        };
+    }
+}
+
+/// Loads `page`'s state into the buffer. The undo history belongs to the
+/// buffer, so it starts over with each switch.
+fn show(ui: &MainWindow, page: &gtk4::Box) {
+    let state = {
+        let mut tabs = ui.tabs.tabs.borrow_mut();
+        let Some(tab) = tabs.iter_mut().find(|tab| &tab.page == page) else {
+            return;
+        };
+        std::mem::take(&mut tab.state)
+    };
+    *ui.tabs.shown.borrow_mut() = Some(page.clone());
+    *ui.file.borrow_mut() = state.file;
+    ui.tabs.shown_secret.set(state.secret);
+    let buffer = &ui.buffer;
+    buffer.begin_irreversible_action();
+    buffer.set_text(&state.text);
+    buffer.end_irreversible_action();
+    buffer.place_cursor(&buffer.iter_at_offset(state.cursor));
+    buffer.set_modified(state.modified);
+    file_actions::update_title(ui);
+}
+
+/// "• notes.txt" or "• Text 2" while the tab has unsaved edits.
+fn set_label(ui: &MainWindow, page: &gtk4::Box) {
+    let shown = ui.tabs.is_shown(page);
+    let tabs = ui.tabs.tabs.borrow();
+    let Some(tab) = tabs.iter().find(|tab| &tab.page == page) else {
+        return;
+    };
+    let file = if shown { ui.file.borrow().clone() } else { tab.state.file.clone() };
+    let modified = if shown { ui.buffer.is_modified() } else { tab.state.modified };
+    let name = match file.as_ref().and_then(|path| path.file_name()) {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => args(&gettext("Text {0}"), &[&tab.number]),
+    };
+    tab.label.set_text(&format!("{}{}", if modified { "• " } else { "" }, name));
+    tab.label.set_tooltip_text(file.as_ref().map(|path| path.to_string_lossy()).as_deref());
+}
+
+/// Updates the visible tab's label after its file or unsaved state changed;
+/// the window title does this whenever it changes.
+pub fn refresh_label(ui: &MainWindow) {
+    let page = ui.tabs.shown.borrow().clone();
+    if let Some(page) = page {
+        set_label(ui, &page);
+    }
+}
+
+/// Closes `page` once the user agrees to lose its unsaved text. The last
+/// tab is replaced by an empty one.
+fn close_tab(ui: &Rc<MainWindow>, page: gtk4::Box) {
+    let Some(index) = ui.tabs.notebook.page_num(&page) else {
+        return;
+    };
+    // The questions and Save work on the visible tab.
+    ui.tabs.notebook.set_current_page(Some(index));
+    confirm_close(ui, move |ui| remove(ui, &page));
+}
+
+fn confirm_close(ui: &Rc<MainWindow>, then: impl Fn(&Rc<MainWindow>) + 'static) {
+    // Edits to a file get the usual Save question, which goes straight on
+    // when there is nothing to lose.
+    if file_actions::has_unsaved_changes(ui) || ui.buffer.char_count() == 0 || !ui.buffer.is_modified() {
+        file_actions::confirm_discard(ui, then);
+        return;
+    }
+    let dialog = MessageDialog::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .message_type(MessageType::Question)
+        .buttons(ButtonsType::None)
+        .text(gettext("Close this tab?"))
+        .secondary_text(gettext("Its text was never saved and will be lost."))
+        .build();
+    dialog.add_button(&gettext("Cancel"), ResponseType::Cancel);
+    dialog.add_button(&gettext("Close Tab"), ResponseType::Accept);
+    dialog.set_default_response(ResponseType::Cancel);
+    let weak = Rc::downgrade(ui);
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        if let (Some(ui), ResponseType::Accept) = (weak.upgrade(), response) {
+            then(&ui);
+        }
+    });
+    dialog.present();
+}
+
+fn remove(ui: &Rc<MainWindow>, page: &gtk4::Box) {
+    if ui.tabs.tabs.borrow().len() == 1 {
+        add_tab(ui, TabState::default());
+    }
+    if ui.tabs.is_shown(page) {
+        // Its state is dropped; the tab shown next overwrites the buffer.
+        ui.tabs.shown.borrow_mut().take();
+    }
+    let Some(index) = ui.tabs.notebook.page_num(page) else {
+        return;
+    };
+    ui.tabs.tabs.borrow_mut().retain(|tab| &tab.page != page);
+    ui.tabs.notebook.remove_page(Some(index));
+    if ui.tabs.shown.borrow().is_none() {
+        let current = ui.tabs.notebook.current_page().and_then(|index| ui.tabs.notebook.nth_page(Some(index)));
+        if let Some(page) = current.and_then(|page| page.downcast::<gtk4::Box>().ok()) {
+            show(ui, &page);
+        }
+    }
+}
+
+/// Every tab's text in tab order, for the drafts, leaving out secret ones.
+pub fn texts(ui: &MainWindow) -> Vec<String> {
+    let notebook = &ui.tabs.notebook;
+    let tabs = ui.tabs.tabs.borrow();
+    (0..notebook.n_pages())
+        .filter_map(|index| notebook.nth_page(Some(index)))
+        .filter_map(|page| tabs.iter().find(|tab| tab.page.upcast_ref::<gtk4::Widget>() == &page))
+        .filter_map(|tab| {
+            let shown = ui.tabs.is_shown(&tab.page);
+            if (shown && ui.tabs.is_secret()) || (!shown && tab.state.secret) {
+                return None;
+            }
+            Some(if shown {
+                ui.buffer.text(&ui.buffer.start_iter(), &ui.buffer.end_iter(), true).to_string()
+            } else {
+                tab.state.text.clone()
+            })
+        })
+        .collect()
+}
+
+/// Shows the first tab with unsaved changes to its file, if any, and
+/// returns whether there was one.
+pub fn show_unsaved(ui: &MainWindow) -> bool {
+    if file_actions::has_unsaved_changes(ui) {
+        return true;
+    }
+    let unsaved = ui.tabs.tabs.borrow().iter().find(|tab| tab.state.file.is_some() && tab.state.modified).map(|tab| tab.page.clone());
+    match unsaved.and_then(|page| ui.tabs.notebook.page_num(&page)) {
+        Some(index) => {
+            ui.tabs.notebook.set_current_page(Some(index));
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,384 @@
+//! Opt-in `{{name}}` placeholders and `{{if name}}…{{else}}…{{end}}`
+//! blocks, filled in from a dialog or `--var` right before typing, and the
+//! values last entered for them.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+use configparser::ini::Ini;
+
+use crate::settings::state_path;
+
+/// One piece of a template: literal text, a placeholder, or a block typed
+/// only if its variable is set.
+enum Node<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+    If { name: &'a str, then: Vec<Node<'a>>, otherwise: Vec<Node<'a>> },
+}
+
+/// What a `{{…}}` holds.
+enum Tag<'a> {
+    Placeholder(&'a str),
+    If(&'a str),
+    Else,
+    End,
+}
+
+/// An `{{if}}` whose `{{end}}` has not been reached yet.
+struct Block<'a> {
+    name: &'a str,
+    /// Where the `{{if}}` starts, for errors.
+    at: usize,
+    then: Vec<Node<'a>>,
+    otherwise: Option<Vec<Node<'a>>>,
+}
+
+/// Reads what is between `{{` and `}}`: `None` for something that is not a
+/// tag at all, which stays literal.
+fn tag(inner: &str) -> Result<Option<Tag<'_>>> {
+    let inner = inner.trim();
+    match inner {
+        "else" => return Ok(Some(Tag::Else)),
+        "end" => return Ok(Some(Tag::End)),
+        "if" => bail!("{{{{if}}}} needs the name of a variable, like {{{{if windows}}}}"),
+        _ => {}
+    }
+    if let Some(name) = inner.strip_prefix("if").filter(|rest| rest.starts_with(char::is_whitespace)) {
+        let name = name.trim();
+        if !is_placeholder_name(name) {
+            bail!("invalid variable name '{}' in {{{{if}}}}", name);
+        }
+        return Ok(Some(Tag::If(name)));
+    }
+    Ok(is_placeholder_name(inner).then_some(Tag::Placeholder(inner)))
+}
+
+/// The line and column (both from 1, the column in characters) of byte
+/// `offset` in `text`.
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+fn error_at(text: &str, offset: usize, message: impl std::fmt::Display) -> anyhow::Error {
+    let (line, column) = position(text, offset);
+    anyhow!("Line {}, column {}: {}", line, column, message)
+}
+
+/// Adds `node` to the innermost open block, or to `root` outside of one.
+fn push<'a>(root: &mut Vec<Node<'a>>, open: &mut [Block<'a>], node: Node<'a>) {
+    match open.last_mut() {
+        Some(Block { otherwise: Some(nodes), .. }) | Some(Block { then: nodes, .. }) => nodes.push(node),
+        None => root.push(node),
+    }
+}
+
+/// Parses `text`. `{{ name }}` may have spaces around the name; `\{{`
+/// stands for a literal `{{`, and anything that is not a tag stays literal
+/// too. An `{{if}}`, `{{else}}` or `{{end}}` alone on its line takes the
+/// whole line with it, so blocks can be written one tag per line without
+/// leaving empty lines behind. Mismatched tags are errors that say where.
+fn parse(text: &str) -> Result<Vec<Node<'_>>> {
+    let mut root = Vec::new();
+    let mut open: Vec<Block> = Vec::new();
+    // The literal text not yet added starts at `start`; the next tag is
+    // searched for from `from`.
+    let (mut start, mut from) = (0, 0);
+    while let Some(found) = text[from..].find("{{") {
+        let pos = from + found;
+        if text[..pos].ends_with('\\') {
+            push(&mut root, &mut open, Node::Text(&text[start..pos - 1]));
+            push(&mut root, &mut open, Node::Text("{{"));
+            (start, from) = (pos + 2, pos + 2);
+            continue;
+        }
+        let Some(len) = text[pos + 2..].find("}}") else {
+            break;
+        };
+        let end = pos + len + 4;
+        let Some(tag) = tag(&text[pos + 2..pos + 2 + len]).map_err(|e| error_at(text, pos, e))? else {
+            from = pos + 2;
+            continue;
+        };
+        // A block tag alone on its line goes with the line.
+        let (mut before, mut after) = (pos, end);
+        if !matches!(tag, Tag::Placeholder(_)) {
+            let line_start = text[..pos].rfind('\n').map_or(0, |at| at + 1);
+            let line_end = text[end..].find('\n').map_or(text.len(), |at| end + at + 1);
+            if line_start >= start && text[line_start..pos].trim().is_empty() && text[end..line_end].trim().is_empty() {
+                (before, after) = (line_start, line_end);
+            }
+        }
+        push(&mut root, &mut open, Node::Text(&text[start..before]));
+        (start, from) = (after, after);
+        match tag {
+            Tag::Placeholder(name) => push(&mut root, &mut open, Node::Placeholder(name)),
+            Tag::If(name) => open.push(Block { name, at: pos, then: Vec::new(), otherwise: None }),
+            Tag::Else => match open.last_mut() {
+                None => return Err(error_at(text, pos, "{{else}} without {{if}}")),
+                Some(Block { otherwise: Some(_), name, at, .. }) => {
+                    let (line, column) = position(text, *at);
+                    return Err(error_at(
+                        text,
+                        pos,
+                        format!("a second {{{{else}}}} for the {{{{if {}}}}} at line {}, column {}", name, line, column),
+                    ));
+                }
+                Some(block) => block.otherwise = Some(Vec::new()),
+            },
+            Tag::End => match open.pop() {
+                None => return Err(error_at(text, pos, "{{end}} without {{if}}")),
+                Some(Block { name, then, otherwise, .. }) => {
+                    push(&mut root, &mut open, Node::If { name, then, otherwise: otherwise.unwrap_or_default() })
+                }
+            },
+        }
+    }
+    if let Some(block) = open.last() {
+        return Err(error_at(text, block.at, format!("{{{{if {}}}}} has no {{{{end}}}}", block.name)));
+    }
+    push(&mut root, &mut open, Node::Text(&text[start..]));
+    Ok(root)
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// A variable a template uses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// Whether it is only ever tested by `{{if}}`, so it is a yes or no
+    /// rather than text.
+    pub condition: bool,
+}
+
+/// The distinct variables in `text`, in order of first appearance, in
+/// every branch. Fails if the text does not parse.
+pub fn variables(text: &str) -> Result<Vec<Variable>> {
+    fn walk(nodes: &[Node], found: &mut Vec<Variable>) {
+        for node in nodes {
+            let (name, condition) = match node {
+                Node::Text(_) => continue,
+                Node::Placeholder(name) => (*name, false),
+                Node::If { name, .. } => (*name, true),
+            };
+            match found.iter_mut().find(|variable| variable.name == name) {
+                Some(variable) => variable.condition &= condition,
+                None => found.push(Variable { name: name.to_string(), condition }),
+            }
+            if let Node::If { then, otherwise, .. } = node {
+                walk(then, found);
+                walk(otherwise, found);
+            }
+        }
+    }
+    let mut found = Vec::new();
+    walk(&parse(text)?, &mut found);
+    Ok(found)
+}
+
+/// Whether an `{{if}}` takes its first branch for `value`: anything but
+/// nothing, `0`, `false`, `no` and `off`.
+pub fn is_set(value: Option<&str>) -> bool {
+    value.map(str::trim).is_some_and(|value| {
+        !value.is_empty() && !["0", "false", "no", "off"].iter().any(|no| value.eq_ignore_ascii_case(no))
+    })
+}
+
+/// Substitutes every placeholder, picks each `{{if}}`'s branch, and
+/// unescapes `\{{`. Placeholders in a branch that is typed without a
+/// value, or with an empty one, are reported together.
+pub fn fill(text: &str, values: &HashMap<String, String>) -> Result<String> {
+    fn walk<'a>(nodes: &[Node<'a>], values: &HashMap<String, String>, out: &mut String, unfilled: &mut Vec<&'a str>) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Placeholder(name) => match values.get(*name).filter(|v| !v.is_empty()) {
+                    Some(value) => out.push_str(value),
+                    None if !unfilled.contains(name) => unfilled.push(name),
+                    None => {}
+                },
+                Node::If { name, then, otherwise } => {
+                    let branch = if is_set(values.get(*name).map(String::as_str)) { then } else { otherwise };
+                    walk(branch, values, out, unfilled);
+                }
+            }
+        }
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut unfilled: Vec<&str> = Vec::new();
+    walk(&parse(text)?, values, &mut out, &mut unfilled);
+    if !unfilled.is_empty() {
+        bail!("Fill in {}", unfilled.iter().map(|n| format!("{{{{{}}}}}", n)).collect::<Vec<_>>().join(", "));
+    }
+    Ok(out)
+}
+
+/// Reads `--var NAME=VALUE` arguments; a later one for the same name wins.
+pub fn parse_vars(args: &[String]) -> Result<HashMap<String, String>> {
+    let mut values = HashMap::new();
+    for arg in args {
+        let Some((name, value)) = arg.split_once('=') else {
+            bail!("--var {}: expected NAME=VALUE", arg);
+        };
+        if !is_placeholder_name(name.trim()) {
+            bail!("--var {}: invalid variable name '{}'", arg, name.trim());
+        }
+        values.insert(name.trim().to_string(), value.to_string());
+    }
+    Ok(values)
+}
+
+fn values_path() -> Option<PathBuf> {
+    state_path("placeholders.ini")
+}
+
+/// The value last entered for each placeholder name.
+pub fn load_remembered_values() -> HashMap<String, String> {
+    // Names are case-sensitive, like in the text.
+    let mut conf = Ini::new_cs();
+    values_path()
+        .and_then(|path| conf.load(path).ok())
+        .and_then(|mut sections| sections.remove("values"))
+        .map(|values| values.into_iter().filter_map(|(name, value)| Some((name, value?))).collect())
+        .unwrap_or_default()
+}
+
+pub fn remember_values(values: &HashMap<String, String>) {
+    let Some(path) = values_path() else {
+        return;
+    };
+    let mut conf = Ini::new_cs();
+    let _ = conf.load(&path);
+    for (name, value) in values {
+        conf.set("values", name, Some(value.clone()));
+    }
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = conf.write(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    fn error(text: &str) -> String {
+        fill(text, &HashMap::new()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn placeholders_are_replaced_by_their_values() {
+        let text = "Hello {{name}}, {{ name }} from {{team.name}}{{_x-1}}!";
+        let filled = fill(text, &values(&[("name", "Ann"), ("team.name", "ops"), ("_x-1", "?")])).unwrap();
+        assert_eq!(filled, "Hello Ann, Ann from ops?!");
+        // A value is typed as it is, even when it looks like a tag.
+        assert_eq!(fill("{{a}}", &values(&[("a", "{{b}}")])).unwrap(), "{{b}}");
+    }
+
+    #[test]
+    fn escapes_and_other_braces_stay_literal() {
+        let none = HashMap::new();
+        assert_eq!(fill(r"\{{name}} and \{{if x}}", &none).unwrap(), "{{name}} and {{if x}}");
+        assert_eq!(fill(r"a\\{{name}}", &values(&[("name", "b")])).unwrap(), r"a\{{name}}");
+        for literal in ["{{ not a tag }}", "{{}}", "{{9lives}}", "{{a\nb}}", "{{ unclosed", "}} {", "{ {name} }"] {
+            assert_eq!(fill(literal, &none).unwrap(), literal);
+        }
+        // Only what is not a tag stays literal; a tag after it still counts.
+        assert_eq!(fill("{{ x y }} {{x}}", &values(&[("x", "1")])).unwrap(), "{{ x y }} 1");
+        assert_eq!(fill("{{ifdef}}", &values(&[("ifdef", "1")])).unwrap(), "1");
+    }
+
+    #[test]
+    fn blocks_pick_a_branch_and_nest() {
+        let text = "{{if linux}}{{if admin}}sudo {{end}}ls{{else}}{{if admin}}runas {{else}}user {{end}}dir{{end}}";
+        let cases = [
+            (&[("linux", "yes"), ("admin", "1")][..], "sudo ls"),
+            (&[("linux", "yes")][..], "ls"),
+            (&[("admin", "on")][..], "runas dir"),
+            (&[][..], "user dir"),
+        ];
+        for (set, expected) in cases {
+            assert_eq!(fill(text, &values(set)).unwrap(), expected, "{:?}", set);
+        }
+        let deeper = "{{if a}}{{if b}}{{if c}}3{{end}}2{{end}}1{{end}}0";
+        assert_eq!(fill(deeper, &values(&[("a", "1"), ("b", "1"), ("c", "1")])).unwrap(), "3210");
+        assert_eq!(fill(deeper, &values(&[("a", "1"), ("c", "1")])).unwrap(), "10");
+    }
+
+    #[test]
+    fn unset_conditions_take_the_else_branch_or_nothing() {
+        for no in ["", " ", "0", "false", "No", "OFF"] {
+            assert!(!is_set(Some(no)), "{:?}", no);
+            assert_eq!(fill("{{if x}}a{{else}}b{{end}}", &values(&[("x", no)])).unwrap(), "b");
+        }
+        assert!(!is_set(None));
+        for yes in ["1", "yes", "true", "windows", " on"] {
+            assert!(is_set(Some(yes)), "{:?}", yes);
+        }
+        assert_eq!(fill("a{{if x}}b{{end}}c", &HashMap::new()).unwrap(), "ac");
+    }
+
+    #[test]
+    fn block_tags_alone_on_their_line_take_the_line() {
+        let text = "start\n  {{if x}}\nyes\n{{else}}  \nno\n{{end}}\nend";
+        assert_eq!(fill(text, &values(&[("x", "1")])).unwrap(), "start\nyes\nend");
+        assert_eq!(fill(text, &HashMap::new()).unwrap(), "start\nno\nend");
+        // Not alone: the line stays, and so do placeholders' lines.
+        assert_eq!(fill("a {{if x}}b{{end}}\n{{y}}\n", &values(&[("y", "")])).unwrap_err().to_string(), "Fill in {{y}}");
+        assert_eq!(fill("a {{if x}}b{{end}}\n{{y}}\n", &values(&[("y", "c")])).unwrap(), "a \nc\n");
+    }
+
+    #[test]
+    fn variables_without_a_value_are_reported_together() {
+        assert_eq!(error("{{b}} {{a}} {{b}}"), "Fill in {{b}}, {{a}}");
+        // Only the branch that is typed needs its values; values no
+        // placeholder uses are ignored.
+        let text = "{{if x}}{{secret}}{{else}}{{name}}{{end}}";
+        assert_eq!(fill(text, &values(&[("name", "n"), ("unused", "u")])).unwrap(), "n");
+        assert_eq!(fill(text, &values(&[("x", "1"), ("name", "n")])).unwrap_err().to_string(), "Fill in {{secret}}");
+    }
+
+    #[test]
+    fn mismatched_tags_say_where() {
+        assert_eq!(error("one\n  {{else}}"), "Line 2, column 3: {{else}} without {{if}}");
+        assert_eq!(error("é {{end}}"), "Line 1, column 3: {{end}} without {{if}}");
+        assert_eq!(error("a\n{{if x}}{{if y}}{{end}}"), "Line 2, column 1: {{if x}} has no {{end}}");
+        assert_eq!(
+            error("{{if x}}a{{else}}b{{else}}c{{end}}"),
+            "Line 1, column 19: a second {{else}} for the {{if x}} at line 1, column 1"
+        );
+        assert_eq!(error("x {{if}}"), "Line 1, column 3: {{if}} needs the name of a variable, like {{if windows}}");
+        assert_eq!(error("{{if 9x}}{{end}}"), "Line 1, column 1: invalid variable name '9x' in {{if}}");
+        assert!(variables("{{end}}").is_err());
+    }
+
+    #[test]
+    fn variables_are_listed_once_in_order() {
+        let found = variables("{{if os}}{{user}}{{else}}{{if admin}}{{end}}{{end}}{{user}} {{if user}}{{end}}").unwrap();
+        let expected = [("os", true), ("user", false), ("admin", true)];
+        assert_eq!(found, expected.map(|(name, condition)| Variable { name: name.to_string(), condition }));
+        assert!(variables(r"\{{user}} {{ no tag }}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn vars_are_read_from_the_command_line() {
+        let args = ["os=linux", " user = Ann ", "empty=", "os=windows", "eq=a=b"].map(String::from);
+        let vars = parse_vars(&args).unwrap();
+        assert_eq!(vars, values(&[("os", "windows"), ("user", " Ann "), ("empty", ""), ("eq", "a=b")]));
+        assert!(parse_vars(&["novalue".to_string()]).is_err());
+        assert!(parse_vars(&["9x=1".to_string()]).is_err());
+    }
+}
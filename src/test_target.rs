@@ -0,0 +1,250 @@
+// "Test Target…" and `--self-test-gui`: a window of the app's own that a
+// short test text is typed into, through the configured backend as any
+// run would be, then compared with what arrived (see `self_test`). The
+// text view does not take input from the user; the window records the
+// keys that reach it while a test is on.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gdk::Key,
+    gio::SimpleAction,
+    glib, Button, EventControllerKey, Label, Orientation, PolicyType, PropagationPhase, ScrolledWindow, TextView,
+    Window, WrapMode,
+};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use pasteclipboard::self_test::{arrival_rate, compare, report, SELF_TEST_TEXT};
+use pasteclipboard::settings::load_typing_options;
+use pasteclipboard::typing::{simulate_typing, TypingError, TypingEvent};
+
+use crate::window::{failure_text, MainWindow};
+
+/// Time for the window to take the focus before the first key.
+const START_DELAY: Duration = Duration::from_secs(1);
+
+/// How long keys may still come in after the run has sent the last one.
+const ARRIVAL_GRACE: Duration = Duration::from_millis(500);
+
+/// Called once a test started by `--self-test-gui` is over, with whether it
+/// passed and the report.
+pub type Done = Box<dyn FnOnce(bool, &str)>;
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let action = SimpleAction::new("test-target", None);
+    let weak = Rc::downgrade(ui);
+    action.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            show(&ui, None);
+        }
+    });
+    ui.window.add_action(&action);
+}
+
+/// What the keys that reached the window add up to while a test runs.
+#[derive(Default)]
+struct Capture {
+    recording: Cell<bool>,
+    text: RefCell<String>,
+    first: Cell<Option<Instant>>,
+    last: Cell<Option<Instant>>,
+}
+
+impl Capture {
+    fn start(&self) {
+        self.text.borrow_mut().clear();
+        self.first.set(None);
+        self.last.set(None);
+        self.recording.set(true);
+    }
+
+    fn record(&self, c: char) {
+        let now = Instant::now();
+        self.first.set(self.first.get().or(Some(now)));
+        self.last.set(Some(now));
+        self.text.borrow_mut().push(c);
+    }
+}
+
+/// Opens the test target; with `done` the test starts at once and `done`
+/// gets its outcome.
+pub fn show(ui: &Rc<MainWindow>, done: Option<Done>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Test target")
+        .default_width(560)
+        .default_height(440)
+        .build();
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+    let intro = Label::new(Some(
+        "Start types a short test text into the box below, through the configured backend, and compares what \
+         arrives with what was sent. Nothing reaches another application, so it is a safe way to try a backend \
+         or a key delay. Keep your hands off the keyboard while it types; Escape stops it.",
+    ));
+    intro.set_xalign(0.0);
+    intro.set_wrap(true);
+    vbox.append(&intro);
+
+    let view = TextView::builder()
+        .editable(false)
+        .cursor_visible(false)
+        .monospace(true)
+        .wrap_mode(WrapMode::Char)
+        .focusable(true)
+        .build();
+    let scrolled = ScrolledWindow::builder()
+        .hscrollbar_policy(PolicyType::Never)
+        .vexpand(true)
+        .min_content_height(120)
+        .child(&view)
+        .build();
+    vbox.append(&scrolled);
+    let lbl_report = Label::new(None);
+    lbl_report.set_xalign(0.0);
+    lbl_report.set_wrap(true);
+    lbl_report.set_selectable(true);
+    vbox.append(&lbl_report);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_start = Button::with_label("Start");
+    btn_start.add_css_class("suggested-action");
+    let btn_close = Button::with_label("Close");
+    buttons.append(&btn_start);
+    buttons.append(&btn_close);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+    dialog.set_default_widget(Some(&btn_start));
+
+    // Set while a test runs, so Escape or closing the window stops it.
+    let cancel: Rc<RefCell<Option<Arc<AtomicBool>>>> = Rc::default();
+    let capture = Rc::new(Capture::default());
+
+    // Ahead of the text view and the window's own shortcuts, so Tab and
+    // Enter are recorded rather than move the focus or press a button.
+    let keys = EventControllerKey::new();
+    keys.set_propagation_phase(PropagationPhase::Capture);
+    let (capture_clone, cancel_clone, buffer) = (capture.clone(), cancel.clone(), view.buffer());
+    keys.connect_key_pressed(move |_, key, _, _| {
+        if !capture_clone.recording.get() {
+            return glib::Propagation::Proceed;
+        }
+        let c = match key {
+            Key::Escape => {
+                if let Some(cancel) = cancel_clone.borrow().as_ref() {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                return glib::Propagation::Stop;
+            }
+            Key::Return | Key::KP_Enter => Some('\n'),
+            Key::Tab | Key::ISO_Left_Tab => Some('\t'),
+            key => key.to_unicode().filter(|c| !c.is_control()),
+        };
+        // Shift and the other modifiers come as keys of their own.
+        let Some(c) = c else {
+            return glib::Propagation::Proceed;
+        };
+        capture_clone.record(c);
+        buffer.insert(&mut buffer.end_iter(), c.encode_utf8(&mut [0; 4]));
+        glib::Propagation::Stop
+    });
+    dialog.add_controller(keys);
+
+    let cancel_clone = cancel.clone();
+    dialog.connect_close_request(move |_| {
+        if let Some(cancel) = cancel_clone.borrow_mut().take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        glib::Propagation::Proceed
+    });
+    let dialog_clone = dialog.clone();
+    btn_close.connect_clicked(move |_| dialog_clone.close());
+
+    let start_now = done.is_some();
+    let done = Rc::new(Cell::new(done));
+    let weak = Rc::downgrade(ui);
+    let dialog_clone = dialog.clone();
+    let start = move |btn_start: &Button| {
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        let finish = {
+            let (done, lbl_report, btn_start) = (done.clone(), lbl_report.clone(), btn_start.clone());
+            move |passed: bool, text: &str| {
+                lbl_report.set_text(text);
+                btn_start.set_sensitive(true);
+                if let Some(done) = done.take() {
+                    done(passed, text);
+                }
+            }
+        };
+        if ui.is_busy() {
+            return finish(false, "Wait for the typing run to end first.");
+        }
+        let mut options = match load_typing_options() {
+            Ok(options) => options,
+            Err(e) => return finish(false, &format!("{} in config.toml.", e)),
+        };
+        options.target_window = None;
+        options.readback = None;
+        btn_start.set_sensitive(false);
+        lbl_report.set_text("Typing the test text…");
+        view.buffer().set_text("");
+        capture.start();
+        dialog_clone.present();
+        view.grab_focus();
+
+        let run_cancel = Arc::new(AtomicBool::new(false));
+        *cancel.borrow_mut() = Some(run_cancel.clone());
+        let (sender, receiver) = flume::unbounded();
+        let start_at = Instant::now() + START_DELAY;
+        thread::spawn(move || simulate_typing(SELF_TEST_TEXT, &options, start_at, &run_cancel, &sender));
+        let (capture, cancel) = (capture.clone(), cancel.clone());
+        glib::MainContext::default().spawn_local(async move {
+            let outcome = loop {
+                match receiver.recv_async().await {
+                    Ok(TypingEvent::Finished { stats }) => break Ok(stats),
+                    Ok(TypingEvent::Failed { error, .. }) => break Err(error),
+                    Ok(_) => {}
+                    Err(_) => break Err(TypingError::Backend("The test stopped unexpectedly".to_string())),
+                }
+            };
+            glib::timeout_future(ARRIVAL_GRACE).await;
+            capture.recording.set(false);
+            cancel.borrow_mut().take();
+            let stats = match outcome {
+                Ok(stats) => stats,
+                Err(error) if error.is_cancelled() => return finish(false, "Stopped."),
+                Err(error) => return finish(false, &format!("Typing failed: {}", failure_text(&error))),
+            };
+            let received = capture.text.borrow().clone();
+            let comparison = compare(SELF_TEST_TEXT, &received);
+            let rate = match (capture.first.get(), capture.last.get()) {
+                (Some(first), Some(last)) => arrival_rate(comparison.received, first, last),
+                _ => None,
+            };
+            let mut text = report(SELF_TEST_TEXT, &comparison, rate, stats.average_interval);
+            if stats.skipped > 0 {
+                text.push_str(&format!(
+                    "\n{} characters have no key in the keymap and were not typed.",
+                    stats.skipped
+                ));
+            }
+            ui.log.add(&format!("Test target: {}", text.lines().next().unwrap_or_default()));
+            finish(comparison.is_exact(), &text);
+        });
+    };
+    btn_start.connect_clicked(start.clone());
+    dialog.present();
+    if start_now {
+        start(&btn_start);
+    }
+}
@@ -0,0 +1,81 @@
+//! Turning loaded bytes (a dropped or opened file) into buffer text.
+
+use anyhow::{bail, Result};
+
+/// Files larger than this need confirmation before they are loaded.
+pub const LARGE_FILE_BYTES: u64 = 1024 * 1024;
+
+pub struct Decoded {
+    pub text: String,
+    /// Whether invalid UTF-8 was replaced with U+FFFD.
+    pub replaced: bool,
+}
+
+/// Decodes text as UTF-8, replacing invalid sequences. Data with NUL bytes
+/// near the start is taken for a binary file and refused.
+pub fn decode(bytes: &[u8]) -> Result<Decoded> {
+    if bytes[..bytes.len().min(8192)].contains(&0) {
+        bail!("it looks like a binary file");
+    }
+    Ok(match String::from_utf8_lossy(bytes) {
+        std::borrow::Cow::Borrowed(text) => Decoded { text: text.to_string(), replaced: false },
+        std::borrow::Cow::Owned(text) => Decoded { text, replaced: true },
+    })
+}
+
+/// Cleans up text from outside the app before it reaches the buffer: drops
+/// a byte order mark and turns CRLF and lone CR line endings into LF.
+pub fn normalize(text: &str) -> String {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it over
+/// `path`, so a crash never leaves a half-written file behind.
+/// A file being replaced keeps its permissions.
+pub fn write_atomically(path: &std::path::Path, contents: &[u8]) -> Result<()> {
+    replace_file(path, contents, 0o666, true)
+}
+
+/// [`write_atomically`] for a private file: it gets `mode`, less the
+/// umask, whatever it had before. The temporary file has `mode` from the
+/// start, so the contents are never readable by others.
+pub fn write_atomically_with_mode(path: &std::path::Path, contents: &[u8], mode: u32) -> Result<()> {
+    replace_file(path, contents, mode, false)
+}
+
+/// Either way the temporary file is removed when the write fails.
+fn replace_file(path: &std::path::Path, contents: &[u8], mode: u32, keep_permissions: bool) -> Result<()> {
+    use anyhow::Context;
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    let name = path.file_name().context("The file has no name")?.to_string_lossy();
+    let temp = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+    let result = (|| -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(mode)
+            .open(&temp)
+            .with_context(|| format!("Failed to create {}", temp.display()))?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        // Keep the permissions of the file being replaced.
+        if let Some(metadata) = std::fs::metadata(path).ok().filter(|_| keep_permissions) {
+            std::fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        std::fs::rename(&temp, path).with_context(|| format!("Failed to replace {}", path.display()))
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+        return result;
+    }
+    // The rename itself is only durable once the directory is synced.
+    if let Ok(dir) = std::fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    result
+}
@@ -0,0 +1,57 @@
+//! Counts for the status row under the text area, how long typing the
+//! text would take, and the preview's view of skipped characters.
+
+use std::time::Duration;
+
+use crate::key_plan::plan_duration;
+use crate::keymap::is_typeable;
+use crate::typing::TypingOptions;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextSummary {
+    pub chars: usize,
+    pub words: usize,
+    pub lines: usize,
+    /// Characters `simulate_typing` will skip.
+    pub skipped: usize,
+}
+
+/// Stands in for each skipped character in the preview.
+pub const SKIPPED_MARKER: char = '⍰';
+
+/// `text` with every character typing would skip replaced by
+/// [`SKIPPED_MARKER`].
+pub fn mark_skipped(text: &str) -> String {
+    text.chars().map(|c| if is_typeable(c) { c } else { SKIPPED_MARKER }).collect()
+}
+
+pub fn summarize(text: &str) -> TextSummary {
+    let mut summary = TextSummary {
+        words: text.split_whitespace().count(),
+        lines: text.lines().count(),
+        ..TextSummary::default()
+    };
+    for c in text.chars() {
+        summary.chars += 1;
+        if !is_typeable(c) {
+            summary.skipped += 1;
+        }
+    }
+    summary
+}
+
+/// Time spent in the configured pauses, from the plan a run would replay;
+/// the keys themselves add a little.
+pub fn estimate(text: &str, options: &TypingOptions) -> Duration {
+    plan_duration(text, options)
+}
+
+/// "43s", "3m 12s" or "1h 05m".
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64().round() as u64;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
@@ -0,0 +1,222 @@
+//! Time-based one-time passwords (RFC 6238) for the `{TOTP}` auto-type
+//! placeholder. The seed is the base32 secret a site shows next to its QR
+//! code, or the whole `otpauth://totp/…` link the QR code holds, which may
+//! also set the digits, period and algorithm. The code is worked out when
+//! typing reaches it, so a long text before it does not leave it expired.
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use ring::hmac;
+use zeroize::Zeroizing;
+
+/// The digits, period and algorithm nearly every site uses.
+pub const DEFAULT_DIGITS: u32 = 6;
+pub const DEFAULT_PERIOD: u64 = 30;
+
+/// Longest accepted `totp_margin_seconds`.
+pub const MAX_MARGIN_SECONDS: u64 = 60;
+
+/// The HMAC a code is made with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// As an `otpauth://` link names it: `SHA1`, `SHA256` or `SHA512`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_uppercase().replace('-', "").as_str() {
+            "SHA1" => Some(Algorithm::Sha1),
+            "SHA256" => Some(Algorithm::Sha256),
+            "SHA512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn hmac(self) -> hmac::Algorithm {
+        match self {
+            Algorithm::Sha1 => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            Algorithm::Sha256 => hmac::HMAC_SHA256,
+            Algorithm::Sha512 => hmac::HMAC_SHA512,
+        }
+    }
+}
+
+/// A TOTP generator: the shared key, wiped when dropped, and how codes are
+/// made from it.
+#[derive(Clone)]
+pub struct Totp {
+    key: Zeroizing<Vec<u8>>,
+    pub digits: u32,
+    pub period: u64,
+    pub algorithm: Algorithm,
+}
+
+impl fmt::Debug for Totp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Totp")
+            .field("digits", &self.digits)
+            .field("period", &self.period)
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Totp {
+    pub fn new(key: &[u8], digits: u32, period: u64, algorithm: Algorithm) -> Self {
+        Totp { key: Zeroizing::new(key.to_vec()), digits, period, algorithm }
+    }
+
+    /// Reads a seed as saved with a snippet: a base32 secret (spaces and
+    /// case do not matter), or an `otpauth://totp/` link with `secret` and
+    /// optionally `digits` (6–10), `period` (1–300 s) and `algorithm`.
+    pub fn parse(seed: &str) -> Result<Self> {
+        let seed = seed.trim();
+        let Some(link) = seed.strip_prefix("otpauth://") else {
+            return Ok(Totp { key: decode_base32(seed)?, digits: DEFAULT_DIGITS, period: DEFAULT_PERIOD, algorithm: Algorithm::Sha1 });
+        };
+        let Some((kind, query)) = link.split_once('?') else {
+            bail!("The TOTP link has no secret");
+        };
+        if !kind.get(..5).is_some_and(|kind| kind.eq_ignore_ascii_case("totp/")) {
+            bail!("The link is not an otpauth://totp/ link");
+        }
+        let (mut key, mut digits, mut period, mut algorithm) = (None, DEFAULT_DIGITS, DEFAULT_PERIOD, Algorithm::Sha1);
+        for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            match name.to_ascii_lowercase().as_str() {
+                "secret" => key = Some(decode_base32(value)?),
+                "digits" => match value.parse() {
+                    Ok(n @ 6..=10) => digits = n,
+                    _ => bail!("Invalid TOTP digits {} (must be from 6–10)", value),
+                },
+                "period" => match value.parse() {
+                    Ok(n @ 1..=300) => period = n,
+                    _ => bail!("Invalid TOTP period {} (must be from 1–300 seconds)", value),
+                },
+                "algorithm" => match Algorithm::parse(value) {
+                    Some(a) => algorithm = a,
+                    None => bail!("Unknown TOTP algorithm {} (expected SHA1, SHA256 or SHA512)", value),
+                },
+                _ => {}
+            }
+        }
+        match key {
+            Some(key) => Ok(Totp { key, digits, period, algorithm }),
+            None => bail!("The TOTP link has no secret"),
+        }
+    }
+
+    /// The time step `time` falls in, and how long until the next one.
+    fn step(&self, time: SystemTime) -> (u64, Duration) {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let step = since_epoch.as_secs() / self.period;
+        let next = Duration::from_secs((step + 1) * self.period);
+        (step, next.saturating_sub(since_epoch))
+    }
+
+    /// The code valid at `time`.
+    pub fn code_at(&self, time: SystemTime) -> Zeroizing<String> {
+        let (step, _) = self.step(time);
+        let code = hotp(&self.key, step, self.digits, self.algorithm);
+        Zeroizing::new(format!("{:0width$}", code, width = self.digits as usize))
+    }
+
+    /// How much longer the code valid at `time` stays valid.
+    pub fn time_left(&self, time: SystemTime) -> Duration {
+        self.step(time).1
+    }
+}
+
+/// An HOTP value (RFC 4226): the HMAC of `counter` under `key`, truncated
+/// to `digits` decimal digits.
+pub fn hotp(key: &[u8], counter: u64, digits: u32, algorithm: Algorithm) -> u64 {
+    let tag = hmac::sign(&hmac::Key::new(algorithm.hmac(), key), &counter.to_be_bytes());
+    let mac = tag.as_ref();
+    let offset = usize::from(mac[mac.len() - 1] & 0x0f);
+    let truncated = u32::from_be_bytes([mac[offset], mac[offset + 1], mac[offset + 2], mac[offset + 3]]) & 0x7fff_ffff;
+    u64::from(truncated) % 10u64.pow(digits)
+}
+
+/// Decodes RFC 4648 base32, as TOTP seeds are written: any case, with
+/// spaces, dashes and `=` padding ignored.
+pub fn decode_base32(text: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let mut bytes = Zeroizing::new(Vec::with_capacity(text.len() * 5 / 8));
+    let (mut bits, mut count) = (0u64, 0u32);
+    for c in text.chars().filter(|c| !c.is_whitespace() && !matches!(c, '-' | '=')) {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u64 - 'A' as u64,
+            c @ '2'..='7' => c as u64 - '2' as u64 + 26,
+            _ => bail!("The TOTP seed is not base32 (it may only have the letters A–Z and the digits 2–7)"),
+        };
+        bits = (bits << 5 | value) & 0xffff;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    if bytes.is_empty() {
+        bail!("The TOTP seed is empty");
+    }
+    Ok(bytes)
+}
+
+/// A TOTP code within a run's text: it is typed over the `digits` zeros
+/// that stand in for it from position `at` (in chars) on.
+#[derive(Clone, Debug)]
+pub struct CodeSlot {
+    pub at: usize,
+    pub totp: Totp,
+}
+
+impl CodeSlot {
+    /// Whether the character at `position` is one of the code's digits.
+    pub fn covers(&self, position: usize) -> bool {
+        (self.at..self.at + self.totp.digits as usize).contains(&position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 Appendix B: eight digits every 30 seconds, with the ASCII
+    /// key "1234567890" repeated to the length of each hash.
+    #[test]
+    fn rfc6238_test_vectors() {
+        let key = "1234567890".repeat(7);
+        let generators = [
+            Totp::new(&key.as_bytes()[..20], 8, 30, Algorithm::Sha1),
+            Totp::new(&key.as_bytes()[..32], 8, 30, Algorithm::Sha256),
+            Totp::new(&key.as_bytes()[..64], 8, 30, Algorithm::Sha512),
+        ];
+        let expected: [(u64, [&str; 3]); 6] = [
+            (59, ["94287082", "46119246", "90693936"]),
+            (1111111109, ["07081804", "68084774", "25091201"]),
+            (1111111111, ["14050471", "67062674", "99943326"]),
+            (1234567890, ["89005924", "91819424", "93441116"]),
+            (2000000000, ["69279037", "90698825", "38618901"]),
+            (20000000000, ["65353130", "77737706", "47863826"]),
+        ];
+        for (seconds, codes) in expected {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+            for (totp, code) in generators.iter().zip(codes) {
+                assert_eq!(totp.code_at(time).as_str(), code, "{:?} at {}", totp.algorithm, seconds);
+            }
+        }
+    }
+
+    #[test]
+    fn parses_a_link_with_its_settings() {
+        // The RFC's SHA1 key in base32.
+        let totp = Totp::parse("otpauth://totp/x?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8&algorithm=SHA1").unwrap();
+        assert_eq!((totp.digits, totp.period, totp.algorithm), (8, DEFAULT_PERIOD, Algorithm::Sha1));
+        assert_eq!(totp.code_at(UNIX_EPOCH + Duration::from_secs(59)).as_str(), "94287082");
+        assert_eq!(totp.time_left(UNIX_EPOCH + Duration::from_secs(59)), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,188 @@
+//! The Edit menu's text transforms. Each takes the selection (or the whole
+//! text) and returns its replacement; lines are split on `\n` only, as the
+//! buffer holds normalized text.
+
+/// The transforms, in menu order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    Uppercase,
+    Lowercase,
+    TrimTrailingWhitespace,
+    Dedent,
+    JoinLines,
+    CollapseBlankLines,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 6] = [
+        Transform::Uppercase,
+        Transform::Lowercase,
+        Transform::TrimTrailingWhitespace,
+        Transform::Dedent,
+        Transform::JoinLines,
+        Transform::CollapseBlankLines,
+    ];
+
+    /// The action name, e.g. "trim-trailing-whitespace".
+    pub fn name(self) -> &'static str {
+        match self {
+            Transform::Uppercase => "uppercase",
+            Transform::Lowercase => "lowercase",
+            Transform::TrimTrailingWhitespace => "trim-trailing-whitespace",
+            Transform::Dedent => "dedent",
+            Transform::JoinLines => "join-lines",
+            Transform::CollapseBlankLines => "collapse-blank-lines",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transform::Uppercase => "UPPERCASE",
+            Transform::Lowercase => "lowercase",
+            Transform::TrimTrailingWhitespace => "Trim Trailing Whitespace",
+            Transform::Dedent => "Remove Common Indentation",
+            Transform::JoinLines => "Join Lines",
+            Transform::CollapseBlankLines => "Collapse Blank Lines",
+        }
+    }
+
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Transform::Uppercase => text.to_uppercase(),
+            Transform::Lowercase => text.to_lowercase(),
+            Transform::TrimTrailingWhitespace => trim_trailing_whitespace(text),
+            Transform::Dedent => dedent(text),
+            Transform::JoinLines => join_lines(text),
+            Transform::CollapseBlankLines => collapse_blank_lines(text),
+        }
+    }
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Strips whitespace (any Unicode whitespace) from the end of every line.
+pub fn trim_trailing_whitespace(text: &str) -> String {
+    text.split('\n').map(str::trim_end).collect::<Vec<_>>().join("\n")
+}
+
+/// Removes the leading whitespace all non-blank lines share, like Python's
+/// `textwrap.dedent`: a tab and a space count as different, and blank
+/// lines become empty.
+pub fn dedent(text: &str) -> String {
+    let mut common: Option<&str> = None;
+    for line in text.split('\n').filter(|line| !is_blank(line)) {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        common = Some(match common {
+            None => indent,
+            Some(common) => {
+                let shared: usize =
+                    common.chars().zip(indent.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a.len_utf8()).sum();
+                &common[..shared]
+            }
+        });
+    }
+    let common = common.unwrap_or("");
+    text.split('\n')
+        .map(|line| if is_blank(line) { "" } else { &line[common.len()..] })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Joins the lines of each paragraph into one, with a single space between
+/// them. Paragraphs stay separated by a blank line.
+pub fn join_lines(text: &str) -> String {
+    let mut out = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    for line in text.split('\n') {
+        if is_blank(line) {
+            if !paragraph.is_empty() {
+                out.push(paragraph.join(" "));
+                paragraph.clear();
+            }
+            out.push(String::new());
+        } else {
+            paragraph.push(line.trim());
+        }
+    }
+    if !paragraph.is_empty() {
+        out.push(paragraph.join(" "));
+    }
+    out.join("\n")
+}
+
+/// Replaces every run of blank lines with a single empty line.
+pub fn collapse_blank_lines(text: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    let mut previous_blank = false;
+    for line in text.split('\n') {
+        let blank = is_blank(line);
+        if !(blank && previous_blank) {
+            out.push(if blank { "" } else { line });
+        }
+        previous_blank = blank;
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_changes_follow_unicode() {
+        assert_eq!(Transform::Uppercase.apply("stra\u{df}e caf\u{e9} \u{3c3}"), "STRASSE CAF\u{c9} \u{3a3}");
+        assert_eq!(Transform::Lowercase.apply("\u{130}STANBUL \u{c9}T\u{c9}"), "i\u{307}stanbul \u{e9}t\u{e9}");
+        assert_eq!(Transform::Uppercase.apply("already 123\n"), "ALREADY 123\n");
+    }
+
+    #[test]
+    fn trimming_takes_any_whitespace_off_the_line_ends() {
+        let text = "a \t\nb\u{a0}\u{3000}\n  c\n\u{2003}\n";
+        assert_eq!(trim_trailing_whitespace(text), "a\nb\n  c\n\n");
+        assert_eq!(trim_trailing_whitespace("caf\u{e9}  "), "caf\u{e9}");
+        assert_eq!(trim_trailing_whitespace(""), "");
+    }
+
+    #[test]
+    fn dedent_removes_only_the_shared_indentation() {
+        assert_eq!(dedent("    a\n      b\n\n    c\n"), "a\n  b\n\nc\n");
+        // Blank lines do not count, whatever their indentation.
+        assert_eq!(dedent("  a\n \n\t\n  b"), "a\n\n\nb");
+        // A tab and spaces are not the same indentation.
+        assert_eq!(dedent("\ta\n    b"), "\ta\n    b");
+        // Multi-byte whitespace is cut at character boundaries.
+        assert_eq!(dedent("\u{3000}\u{3000}a\n\u{3000}b"), "\u{3000}a\nb");
+        assert_eq!(dedent(" \u{3000}a\n \u{2003}b"), "\u{3000}a\n\u{2003}b");
+        assert_eq!(dedent("\u{e9}\n  x"), "\u{e9}\n  x");
+        assert_eq!(dedent(""), "");
+    }
+
+    #[test]
+    fn joining_makes_each_paragraph_one_line() {
+        assert_eq!(join_lines("one\n  two \nthree"), "one two three");
+        assert_eq!(join_lines("caf\u{e9}\u{a0}\n\u{3000}na\u{ef}ve\n\nb\nc\n"), "caf\u{e9} na\u{ef}ve\n\nb c\n");
+        assert_eq!(join_lines("\n\na"), "\n\na");
+        assert_eq!(join_lines(""), "");
+    }
+
+    #[test]
+    fn runs_of_blank_lines_become_one_empty_line() {
+        assert_eq!(collapse_blank_lines("a\n\n\n\nb\n \n\u{a0}\nc"), "a\n\nb\n\nc");
+        assert_eq!(collapse_blank_lines("\n\n\u{3000}\na\n\n"), "\na\n");
+        assert_eq!(collapse_blank_lines("a\nb"), "a\nb");
+    }
+
+    #[test]
+    fn every_transform_has_its_own_action() {
+        let mut names: Vec<&str> = Transform::ALL.iter().map(|t| t.name()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), Transform::ALL.len());
+        for transform in Transform::ALL {
+            assert!(transform.name().chars().all(|c| c.is_ascii_lowercase() || c == '-'), "{}", transform.name());
+            assert_eq!(transform.apply(""), "");
+        }
+    }
+}
@@ -0,0 +1,520 @@
+// Optional tray icon (`tray_icon = true`). GTK 4 has no tray API, so this
+// exports a StatusNotifierItem and its com.canonical.dbusmenu menu on the
+// app's own bus connection and registers it with the desktop's
+// StatusNotifierWatcher. Desktops without one simply get no icon.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{self, BusNameWatcherFlags, DBusCallFlags, DBusConnection, DBusMethodInvocation, DBusNodeInfo},
+    glib::{self, ControlFlow, Variant},
+    Application,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use pasteclipboard::run_state::RunState;
+use pasteclipboard::settings::{load_delay_setting, Settings};
+use pasteclipboard::smart_punctuation;
+use pasteclipboard::snippets::{load_snippet_settings, recent_snippets};
+
+use crate::window::{format_count, WindowSlot};
+use crate::APP_NAME;
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+
+/// How many recently typed snippets the menu offers.
+const RECENT_SNIPPETS: usize = 3;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.kde.StatusNotifierItem">
+    <property name="Category" type="s" access="read"/>
+    <property name="Id" type="s" access="read"/>
+    <property name="Title" type="s" access="read"/>
+    <property name="Status" type="s" access="read"/>
+    <property name="IconName" type="s" access="read"/>
+    <property name="OverlayIconName" type="s" access="read"/>
+    <property name="AttentionIconName" type="s" access="read"/>
+    <property name="ToolTip" type="(sa(iiay)ss)" access="read"/>
+    <property name="ItemIsMenu" type="b" access="read"/>
+    <property name="Menu" type="o" access="read"/>
+    <method name="Activate">
+      <arg type="i" name="x" direction="in"/>
+      <arg type="i" name="y" direction="in"/>
+    </method>
+    <method name="SecondaryActivate">
+      <arg type="i" name="x" direction="in"/>
+      <arg type="i" name="y" direction="in"/>
+    </method>
+    <method name="ContextMenu">
+      <arg type="i" name="x" direction="in"/>
+      <arg type="i" name="y" direction="in"/>
+    </method>
+    <method name="Scroll">
+      <arg type="i" name="delta" direction="in"/>
+      <arg type="s" name="orientation" direction="in"/>
+    </method>
+    <signal name="NewTitle"/>
+    <signal name="NewIcon"/>
+    <signal name="NewAttentionIcon"/>
+    <signal name="NewOverlayIcon"/>
+    <signal name="NewToolTip"/>
+    <signal name="NewStatus">
+      <arg type="s" name="status"/>
+    </signal>
+  </interface>
+  <interface name="com.canonical.dbusmenu">
+    <property name="Version" type="u" access="read"/>
+    <property name="TextDirection" type="s" access="read"/>
+    <property name="Status" type="s" access="read"/>
+    <property name="IconThemePath" type="as" access="read"/>
+    <method name="GetLayout">
+      <arg type="i" name="parentId" direction="in"/>
+      <arg type="i" name="recursionDepth" direction="in"/>
+      <arg type="as" name="propertyNames" direction="in"/>
+      <arg type="u" name="revision" direction="out"/>
+      <arg type="(ia{sv}av)" name="layout" direction="out"/>
+    </method>
+    <method name="GetGroupProperties">
+      <arg type="ai" name="ids" direction="in"/>
+      <arg type="as" name="propertyNames" direction="in"/>
+      <arg type="a(ia{sv})" name="properties" direction="out"/>
+    </method>
+    <method name="GetProperty">
+      <arg type="i" name="id" direction="in"/>
+      <arg type="s" name="name" direction="in"/>
+      <arg type="v" name="value" direction="out"/>
+    </method>
+    <method name="Event">
+      <arg type="i" name="id" direction="in"/>
+      <arg type="s" name="eventId" direction="in"/>
+      <arg type="v" name="data" direction="in"/>
+      <arg type="u" name="timestamp" direction="in"/>
+    </method>
+    <method name="EventGroup">
+      <arg type="a(isvu)" name="events" direction="in"/>
+      <arg type="ai" name="idErrors" direction="out"/>
+    </method>
+    <method name="AboutToShow">
+      <arg type="i" name="id" direction="in"/>
+      <arg type="b" name="needUpdate" direction="out"/>
+    </method>
+    <method name="AboutToShowGroup">
+      <arg type="ai" name="ids" direction="in"/>
+      <arg type="ai" name="updatesNeeded" direction="out"/>
+      <arg type="ai" name="idErrors" direction="out"/>
+    </method>
+    <signal name="ItemsPropertiesUpdated">
+      <arg type="a(ia{sv})" name="updatedProps"/>
+      <arg type="a(ias)" name="removedProps"/>
+    </signal>
+    <signal name="LayoutUpdated">
+      <arg type="u" name="revision"/>
+      <arg type="i" name="parent"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+/// Whether the tray icon is turned on in config.toml.
+pub fn enabled() -> bool {
+    Settings::load().tray_icon
+}
+
+/// One menu entry; its dbusmenu id is its index in the menu plus one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    TypeClipboard { delay: u64 },
+    Snippet(String),
+    ToggleWindow,
+    Separator,
+    Quit,
+}
+
+impl Entry {
+    fn properties(&self) -> HashMap<String, Variant> {
+        let mut props = HashMap::new();
+        let label = match self {
+            Entry::TypeClipboard { delay } => format!("Type clipboard after {}s", delay),
+            // A single underscore would be taken as a mnemonic.
+            Entry::Snippet(name) => format!("Type “{}”", name.replace('_', "__")),
+            Entry::ToggleWindow => format!("Show or Hide {}", APP_NAME),
+            Entry::Separator => {
+                props.insert("type".to_string(), "separator".to_variant());
+                return props;
+            }
+            Entry::Quit => "Quit".to_string(),
+        };
+        props.insert("label".to_string(), label.to_variant());
+        props
+    }
+}
+
+fn menu_entries() -> Vec<Entry> {
+    let mut entries = vec![Entry::TypeClipboard { delay: load_delay_setting().unwrap_or(3) }];
+    let recent = recent_snippets(RECENT_SNIPPETS);
+    if !recent.is_empty() {
+        entries.push(Entry::Separator);
+        entries.extend(recent.into_iter().map(Entry::Snippet));
+    }
+    entries.extend([Entry::Separator, Entry::ToggleWindow, Entry::Separator, Entry::Quit]);
+    entries
+}
+
+/// What the icon shows; signals go out only when this changes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Appearance {
+    title: String,
+    status: &'static str,
+    icon: &'static str,
+    overlay: &'static str,
+    tooltip: String,
+}
+
+impl Appearance {
+    fn of(state: &RunState) -> Appearance {
+        match *state {
+            RunState::CountingDown { remaining_seconds } => Appearance {
+                title: format!("[{}s] {}", remaining_seconds, APP_NAME),
+                status: "NeedsAttention",
+                icon: "edit-paste",
+                overlay: "appointment-soon",
+                tooltip: format!("Typing starts in {}s", remaining_seconds),
+            },
+            RunState::Typing { typed, total, .. } => Appearance {
+                title: APP_NAME.to_string(),
+                status: "Active",
+                icon: "input-keyboard",
+                overlay: "",
+                tooltip: format!("Typing… {} / {}", format_count(typed), format_count(total)),
+            },
+            _ => Appearance {
+                title: APP_NAME.to_string(),
+                status: "Active",
+                icon: "edit-paste",
+                overlay: "",
+                tooltip: "Idle".to_string(),
+            },
+        }
+    }
+}
+
+struct Tray {
+    app: glib::WeakRef<Application>,
+    slot: Rc<WindowSlot>,
+    /// The menu as the host last saw it, so clicks find the right entry.
+    entries: RefCell<Vec<Entry>>,
+    revision: Cell<u32>,
+    shown: RefCell<Appearance>,
+}
+
+impl Tray {
+    fn appearance(&self) -> Appearance {
+        match self.slot.peek() {
+            Some(ui) => Appearance::of(&ui.run_state.borrow()),
+            None => Appearance::of(&RunState::Idle),
+        }
+    }
+
+    fn item_property(&self, name: &str) -> Variant {
+        let shown = self.shown.borrow();
+        match name {
+            "Category" => "ApplicationStatus".to_variant(),
+            "Id" => "pasteclipboard".to_variant(),
+            "Title" => shown.title.to_variant(),
+            "Status" => shown.status.to_variant(),
+            "IconName" => shown.icon.to_variant(),
+            "OverlayIconName" => shown.overlay.to_variant(),
+            "AttentionIconName" => "appointment-soon".to_variant(),
+            "ToolTip" => ("", Vec::<(i32, i32, Vec<u8>)>::new(), APP_NAME, shown.tooltip.as_str()).to_variant(),
+            "ItemIsMenu" => false.to_variant(),
+            "Menu" => glib::variant::ObjectPath::try_from(MENU_PATH.to_string())
+                .map(|path| path.to_variant())
+                .unwrap_or_else(|_| "/".to_variant()),
+            _ => ().to_variant(),
+        }
+    }
+
+    fn menu_property(&self, name: &str) -> Variant {
+        match name {
+            "Version" => 3u32.to_variant(),
+            "TextDirection" => "ltr".to_variant(),
+            "Status" => "normal".to_variant(),
+            "IconThemePath" => Vec::<String>::new().to_variant(),
+            _ => ().to_variant(),
+        }
+    }
+
+    /// Rebuilds the menu; returns whether it changed since the host last
+    /// fetched it.
+    fn refresh_menu(&self, conn: &DBusConnection) -> bool {
+        let entries = menu_entries();
+        if *self.entries.borrow() == entries {
+            return false;
+        }
+        *self.entries.borrow_mut() = entries;
+        self.revision.set(self.revision.get() + 1);
+        let _ = conn.emit_signal(
+            None,
+            MENU_PATH,
+            MENU_INTERFACE,
+            "LayoutUpdated",
+            Some(&(self.revision.get(), 0i32).to_variant()),
+        );
+        true
+    }
+
+    fn layout(&self) -> Variant {
+        let children: Vec<Variant> = self
+            .entries
+            .borrow()
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (i as i32 + 1, entry.properties(), Vec::<Variant>::new()).to_variant())
+            .collect();
+        let mut root = HashMap::new();
+        root.insert("children-display".to_string(), "submenu".to_variant());
+        (0i32, root, children).to_variant()
+    }
+
+    fn entry_properties(&self, id: i32) -> HashMap<String, Variant> {
+        let entries = self.entries.borrow();
+        match usize::try_from(id - 1).ok().and_then(|i| entries.get(i)) {
+            Some(entry) => entry.properties(),
+            None => HashMap::new(),
+        }
+    }
+
+    fn toggle_window(&self, app: &Application) {
+        let ui = self.slot.get(app);
+        if ui.window.is_visible() && ui.window.is_active() {
+            ui.window.set_visible(false);
+        } else {
+            ui.window.present();
+        }
+    }
+
+    fn clicked(&self, id: i32) {
+        let Some(app) = self.app.upgrade() else {
+            return;
+        };
+        let entry = usize::try_from(id - 1).ok().and_then(|i| self.entries.borrow().get(i).cloned());
+        match entry {
+            Some(Entry::TypeClipboard { delay }) => type_clipboard(&app, &self.slot, delay),
+            Some(Entry::Snippet(name)) => type_snippet(&app, &self.slot, &name),
+            Some(Entry::ToggleWindow) => self.toggle_window(&app),
+            Some(Entry::Quit) => app.quit(),
+            Some(Entry::Separator) | None => {}
+        }
+    }
+
+    fn handle_item_method(&self, method: &str, invocation: DBusMethodInvocation) {
+        if method == "Activate" {
+            if let Some(app) = self.app.upgrade() {
+                self.toggle_window(&app);
+            }
+        }
+        // Menus are shown by the host; scrolling means nothing here.
+        invocation.return_value(None);
+    }
+
+    fn handle_menu_method(&self, conn: &DBusConnection, method: &str, params: &Variant, invocation: DBusMethodInvocation) {
+        match method {
+            "GetLayout" => {
+                self.refresh_menu(conn);
+                invocation.return_value(Some(&Variant::tuple_from_iter([
+                    self.revision.get().to_variant(),
+                    self.layout(),
+                ])));
+            }
+            "GetGroupProperties" => {
+                let ids = params.child_value(0).get::<Vec<i32>>().unwrap_or_default();
+                let props: Vec<(i32, HashMap<String, Variant>)> =
+                    ids.into_iter().map(|id| (id, self.entry_properties(id))).collect();
+                invocation.return_value(Some(&(props,).to_variant()));
+            }
+            "GetProperty" => {
+                let (id, name) = params.get::<(i32, String)>().unwrap_or_default();
+                let value = self.entry_properties(id).remove(&name).unwrap_or_else(|| "".to_variant());
+                invocation.return_value(Some(&Variant::tuple_from_iter([Variant::from_variant(&value)])));
+            }
+            "Event" => {
+                let id = params.child_value(0).get::<i32>().unwrap_or(-1);
+                let event = params.child_value(1).get::<String>().unwrap_or_default();
+                invocation.return_value(None);
+                if event == "clicked" {
+                    self.clicked(id);
+                }
+            }
+            "EventGroup" => {
+                let events = params.child_value(0);
+                invocation.return_value(Some(&(Vec::<i32>::new(),).to_variant()));
+                for event in events.iter() {
+                    let id = event.child_value(0).get::<i32>().unwrap_or(-1);
+                    if event.child_value(1).get::<String>().as_deref() == Some("clicked") {
+                        self.clicked(id);
+                    }
+                }
+            }
+            "AboutToShow" => {
+                let changed = self.refresh_menu(conn);
+                invocation.return_value(Some(&(changed,).to_variant()));
+            }
+            "AboutToShowGroup" => {
+                self.refresh_menu(conn);
+                invocation.return_value(Some(&(Vec::<i32>::new(), Vec::<i32>::new()).to_variant()));
+            }
+            _ => invocation.return_dbus_error("org.freedesktop.DBus.Error.UnknownMethod", method),
+        }
+    }
+
+    /// Follows the run state, telling the host when the icon should change.
+    fn update(&self, conn: &DBusConnection) {
+        let appearance = self.appearance();
+        let changed = *self.shown.borrow() != appearance;
+        if !changed {
+            return;
+        }
+        let status = appearance.status;
+        *self.shown.borrow_mut() = appearance;
+        for signal in ["NewTitle", "NewIcon", "NewOverlayIcon", "NewToolTip"] {
+            let _ = conn.emit_signal(None, ITEM_PATH, ITEM_INTERFACE, signal, None);
+        }
+        let _ = conn.emit_signal(None, ITEM_PATH, ITEM_INTERFACE, "NewStatus", Some(&(status,).to_variant()));
+    }
+}
+
+fn type_clipboard(app: &Application, slot: &WindowSlot, delay: u64) {
+    let ui = slot.get(app);
+    if ui.is_busy() {
+        ui.set_warning("A typing run is already in progress.");
+        return;
+    }
+    let weak = Rc::downgrade(&ui);
+    ui.window.clipboard().read_text_async(gio::Cancellable::NONE, move |result| {
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        match result {
+            // Text copied from documents and web pages is where smart
+            // punctuation comes from.
+            Ok(Some(text)) if !text.is_empty() => {
+                let text = if Settings::load().fix_smart_punctuation {
+                    smart_punctuation::fix(&text)
+                } else {
+                    text.to_string()
+                };
+                ui.start_configured(text, delay, None)
+            }
+            Ok(_) => ui.set_warning("The clipboard holds no text."),
+            Err(e) => ui.set_error(&format!("Cannot read the clipboard: {}.", e)),
+        }
+    });
+}
+
+/// Types a snippet after its own delay, or the configured one.
+pub fn type_snippet(app: &Application, slot: &WindowSlot, name: &str) {
+    let ui = slot.get(app);
+    if ui.is_busy() {
+        ui.set_warning("A typing run is already in progress.");
+        return;
+    }
+    let delay = match load_snippet_settings(name).delay_seconds {
+        Some(delay) => delay,
+        None => match load_delay_setting() {
+            Ok(delay) => delay,
+            Err(e) => {
+                ui.set_error(&format!("{} in config.toml.", e));
+                return;
+            }
+        },
+    };
+    ui.start_snippet(name, delay);
+}
+
+/// Exports the icon and registers it whenever a tray host is (or later
+/// becomes) available. Keeps the app running with the window closed, as in
+/// background mode. Only the first call has an effect.
+pub fn start(app: &Application, conn: &DBusConnection, slot: Rc<WindowSlot>) -> Result<(), glib::Error> {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let node = DBusNodeInfo::for_xml(INTERFACE_XML)?;
+    let missing = || glib::Error::new(gio::IOErrorEnum::Failed, "interface missing from its own XML");
+    let item_interface = node.lookup_interface(ITEM_INTERFACE).ok_or_else(missing)?;
+    let menu_interface = node.lookup_interface(MENU_INTERFACE).ok_or_else(missing)?;
+
+    slot.keep_running(app);
+    let tray = Rc::new(Tray {
+        app: app.downgrade(),
+        slot,
+        entries: RefCell::new(menu_entries()),
+        revision: Cell::new(1),
+        shown: RefCell::default(),
+    });
+    *tray.shown.borrow_mut() = tray.appearance();
+
+    let item = tray.clone();
+    let item_props = tray.clone();
+    conn.register_object(
+        ITEM_PATH,
+        &item_interface,
+        move |_, _, _, _, method, _, invocation| item.handle_item_method(method, invocation),
+        move |_, _, _, _, name| item_props.item_property(name),
+        |_, _, _, _, _, _| false,
+    )?;
+    let menu = tray.clone();
+    let menu_props = tray.clone();
+    conn.register_object(
+        MENU_PATH,
+        &menu_interface,
+        move |conn, _, _, _, method, params, invocation| menu.handle_menu_method(&conn, method, &params, invocation),
+        move |_, _, _, _, name| menu_props.menu_property(name),
+        |_, _, _, _, _, _| false,
+    )?;
+
+    // Hosts restart (or appear after login); register again each time. The
+    // watch lasts as long as the app.
+    let Some(service) = conn.unique_name() else {
+        return Err(glib::Error::new(gio::IOErrorEnum::Failed, "the bus connection has no name"));
+    };
+    let _watch = gio::bus_watch_name_on_connection(
+        conn,
+        WATCHER_NAME,
+        BusNameWatcherFlags::NONE,
+        move |conn, _, _| {
+            conn.call(
+                Some(WATCHER_NAME),
+                "/StatusNotifierWatcher",
+                WATCHER_NAME,
+                "RegisterStatusNotifierItem",
+                Some(&(service.as_str(),).to_variant()),
+                None,
+                DBusCallFlags::NONE,
+                -1,
+                gio::Cancellable::NONE,
+                |result| {
+                    if let Err(e) = result {
+                        eprintln!("The tray refused the status icon: {}", e);
+                    }
+                },
+            );
+        },
+        |_, _| {},
+    );
+
+    let conn = conn.clone();
+    glib::timeout_add_local(Duration::from_millis(500), move || {
+        tray.update(&conn);
+        ControlFlow::Continue
+    });
+    Ok(())
+}
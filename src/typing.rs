@@ -0,0 +1,973 @@
+//! The typing run: maps text to keys and feeds them to a backend (ASCII only).
+
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use evdev_rs::enums::EV_KEY;
+use flume::Sender;
+use zeroize::Zeroizing;
+
+use crate::assistive::{line_preview, Confirmations, InputWatch, Wait, SETTLE_TIME};
+use crate::backend::{
+    activate_window, focused_window_title, is_device_lost, select_backend, top_level_windows, Backend, Keyboard,
+};
+use crate::crash_report::panic_message;
+use crate::key_plan::{plan_keys, Plan, PlanText, PlannedEvent};
+use crate::keymap::Keymap;
+use crate::readback::Readback;
+use crate::remote::RemoteAddress;
+use crate::session_lock::{LockWatch, LOCK_CHECK_INTERVAL};
+use crate::totp::CodeSlot;
+use crate::uinput_access::UINPUT_PATH;
+
+/// Number of characters typed between two `Progress` events.
+pub const PROGRESS_INTERVAL: usize = 25;
+
+/// Longest accepted countdown, in seconds.
+pub const MAX_DELAY_SECONDS: u64 = 86400;
+
+/// Longest accepted pause between two characters, in milliseconds.
+pub const MAX_CHAR_DELAY_MS: u32 = 10_000;
+
+/// Largest text accepted from another process (D-Bus, HTTP), in bytes.
+pub const MAX_REMOTE_TEXT_BYTES: usize = 1024 * 1024;
+
+/// The key a line break is typed with. Some targets want the keypad's
+/// Enter, and chat boxes take Shift+Enter as a line break instead of send.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnterKey {
+    #[default]
+    Enter,
+    KeypadEnter,
+    ShiftEnter,
+}
+
+impl EnterKey {
+    pub const ALL: [EnterKey; 3] = [EnterKey::Enter, EnterKey::KeypadEnter, EnterKey::ShiftEnter];
+
+    /// The snippet index value.
+    pub fn name(self) -> &'static str {
+        match self {
+            EnterKey::Enter => "enter",
+            EnterKey::KeypadEnter => "keypad-enter",
+            EnterKey::ShiftEnter => "shift-enter",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EnterKey::Enter => "Enter",
+            EnterKey::KeypadEnter => "Keypad Enter",
+            EnterKey::ShiftEnter => "Shift+Enter",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        EnterKey::ALL.into_iter().find(|key| key.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The key and whether Shift is held with it.
+    pub fn key(self) -> (EV_KEY, bool) {
+        match self {
+            EnterKey::Enter => (EV_KEY::KEY_ENTER, false),
+            EnterKey::KeypadEnter => (EV_KEY::KEY_KPENTER, false),
+            EnterKey::ShiftEnter => (EV_KEY::KEY_ENTER, true),
+        }
+    }
+}
+
+/// Per-run settings for the typing worker.
+#[derive(Clone, Debug)]
+pub struct TypingOptions {
+    /// `None` picks the first available backend.
+    pub backend: Option<Backend>,
+    /// Pause after each character.
+    pub char_delay: Duration,
+    /// Extra pause after each Enter, for targets that process lines slowly.
+    pub newline_delay: Duration,
+    pub enter_key: EnterKey,
+    /// The keys characters are typed with: the US layout with the user's
+    /// overrides.
+    pub keymap: Keymap,
+    /// Checked right before the first key: the focused window's title must
+    /// match this pattern (see [`title_matches`]), or the run fails.
+    pub target_window: Option<String>,
+    /// Brought to the front right before the first key: the open window
+    /// with this title, or else the top-most one whose title matches it as
+    /// a pattern. The run fails if there is none or it does not get the
+    /// focus. X11 only.
+    pub focus_window: Option<String>,
+    /// Virtual keyboard events carry the time they were written instead of
+    /// zero, as some input tooling expects.
+    pub event_timestamps: bool,
+    /// Typing waits while the session is locked, so the keys do not go to
+    /// the lock screen. Off for runs that type nowhere, such as dry runs.
+    pub watch_lock: bool,
+    /// Extra waits before characters of the text, as (position in chars,
+    /// wait) in order of position; an auto-type `{DELAY}` makes one.
+    pub pauses: Vec<(usize, Duration)>,
+    /// TOTP codes to type over the zeros standing in for them, in order of
+    /// position; an auto-type `{TOTP}` makes one.
+    pub codes: Vec<CodeSlot>,
+    /// A code that would expire within this long after it is typed is not
+    /// typed; the run waits for the next one instead.
+    pub code_margin: Duration,
+    /// Another machine to send the run to instead of typing it here
+    /// (`remote_typing`); see [`crate::remote::type_remote`].
+    pub remote: Option<RemoteAddress>,
+    /// Assistive mode: the run waits for a confirmation before each line
+    /// after the first and when a physical keyboard or mouse is used; see
+    /// [`crate::assistive`].
+    pub assistive: bool,
+    /// Confirm-before-Enter mode: each line is typed straight away, and the
+    /// Enter after it waits for a confirmation.
+    pub confirm_enter: bool,
+    /// How whoever started the run answers [`TypingEvent::AwaitingConfirmation`],
+    /// for assistive and confirm-before-Enter mode and a stream's `WAIT-ENTER`
+    /// lines.
+    pub confirmations: Arc<Confirmations>,
+    /// The chords the run ends with when whoever started it reads the text
+    /// back from the target to check it (see [`crate::readback`]).
+    pub readback: Option<Readback>,
+}
+
+impl Default for TypingOptions {
+    fn default() -> Self {
+        TypingOptions {
+            backend: None,
+            char_delay: Duration::from_millis(20),
+            newline_delay: Duration::ZERO,
+            enter_key: EnterKey::Enter,
+            keymap: Keymap::default(),
+            target_window: None,
+            focus_window: None,
+            event_timestamps: false,
+            watch_lock: true,
+            pauses: Vec::new(),
+            codes: Vec::new(),
+            code_margin: Duration::from_secs(3),
+            remote: None,
+            assistive: false,
+            confirm_enter: false,
+            confirmations: Arc::default(),
+            readback: None,
+        }
+    }
+}
+
+/// What a run actually did, counted by the worker as it typed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RunStats {
+    pub typed: usize,
+    /// Characters in the input that have no key and were not typed.
+    pub skipped: usize,
+    /// Wall-clock time from the start of the worker to the end of the run,
+    /// leaving out the chords of a read-back.
+    pub duration: Duration,
+    /// Average time from one key press to the next, to compare with the
+    /// configured pauses; zero with fewer than two keys.
+    pub average_interval: Duration,
+}
+
+impl RunStats {
+    /// Effective typing speed over the whole run.
+    pub fn chars_per_second(&self) -> f64 {
+        let seconds = self.duration.as_secs_f64();
+        if seconds > 0.0 { self.typed as f64 / seconds } else { 0.0 }
+    }
+
+    /// The shape `--json` runs print on stdout.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "typed": self.typed,
+            "skipped": self.skipped,
+            "duration_seconds": self.duration.as_secs_f64(),
+            "chars_per_second": self.chars_per_second(),
+            "average_interval_ms": self.average_interval.as_secs_f64() * 1000.0,
+        })
+    }
+}
+
+/// Events sent from the typing worker to whoever started the run.
+#[derive(Debug)]
+pub enum TypingEvent {
+    /// Typing is about to begin; `total_chars` is how many characters will be typed.
+    Started { total_chars: usize },
+    Progress { typed: usize, total: usize },
+    /// Characters in the input that have no key and will not be typed.
+    Skipped { count: usize },
+    /// Something worth logging that does not change the run, such as a
+    /// retry. Plain English, like the log.
+    Notice { message: String },
+    /// Typing waits until the window confirms, in assistive or
+    /// confirm-before-Enter mode or at a stream's `WAIT-ENTER` line.
+    AwaitingConfirmation { wait: Wait },
+    Finished { stats: RunStats },
+    Failed { error: TypingError, stats: RunStats },
+}
+
+/// Why a run failed, so callers can tell the user what to do about it.
+#[derive(Debug)]
+pub enum TypingError {
+    /// /dev/uinput exists but this user cannot write to it.
+    UinputPermissionDenied,
+    /// There is no /dev/uinput: the kernel module is not loaded.
+    UinputMissing,
+    /// The virtual keyboard could not be created for another reason.
+    DeviceCreation(io::Error),
+    /// A key press did not get through; `at_char` characters were typed.
+    WriteFailed { at_char: usize, source: io::Error },
+    /// Nothing in this session can do what the run needs, such as a
+    /// working backend or reading the focused window's title.
+    Unsupported(String),
+    /// The focused window is not the target window.
+    WrongWindow { title: String, pattern: String },
+    /// The window to bring to the front is not open or did not get the
+    /// focus, for this reason.
+    FocusFailed(String),
+    /// The xdo or portal backend could not be set up, or the worker stopped.
+    Backend(String),
+    /// Stopped by the user after `at_char` characters.
+    Cancelled { at_char: usize },
+    /// The worker panicked with this message; the panic hook wrote a crash
+    /// report.
+    Crashed(String),
+}
+
+/// What the window offers for a failed run besides the message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Remedy {
+    /// The dialog that installs the udev rule.
+    FixPermissions,
+    /// The diagnostics, which say how to load the module or set up a backend.
+    RunDiagnostics,
+}
+
+impl TypingError {
+    /// Sorts out why creating the uinput device failed.
+    pub fn from_device_error(error: io::Error) -> TypingError {
+        match error.kind() {
+            io::ErrorKind::NotFound => TypingError::UinputMissing,
+            io::ErrorKind::PermissionDenied => TypingError::UinputPermissionDenied,
+            _ => TypingError::DeviceCreation(error),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, TypingError::Cancelled { .. })
+    }
+
+    pub fn remedy(&self) -> Option<Remedy> {
+        match self {
+            TypingError::UinputPermissionDenied => Some(Remedy::FixPermissions),
+            TypingError::UinputMissing | TypingError::Unsupported(_) => Some(Remedy::RunDiagnostics),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TypingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypingError::UinputPermissionDenied => {
+                write!(f, "Failed to create UInput device. Do you have permissions for {}?", UINPUT_PATH)
+            }
+            TypingError::UinputMissing => f.write_str("Failed to create UInput device. Is the 'uinput' kernel module loaded?"),
+            TypingError::DeviceCreation(e) => write!(f, "Failed to create UInput device: {}", e),
+            TypingError::WriteFailed { at_char, source } => {
+                write!(f, "Failed to send character {}: {}", at_char + 1, source)
+            }
+            TypingError::Unsupported(reason) | TypingError::Backend(reason) => f.write_str(reason),
+            TypingError::WrongWindow { title, pattern } => {
+                write!(f, "The focused window '{}' does not match the target window '{}'", title, pattern)
+            }
+            TypingError::FocusFailed(reason) => write!(f, "Could not bring the target window to the front: {}", reason),
+            TypingError::Cancelled { .. } => f.write_str("Cancelled"),
+            TypingError::Crashed(message) => write!(f, "The typing worker crashed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for TypingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TypingError::DeviceCreation(e) | TypingError::WriteFailed { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl TypingEvent {
+    /// Whether this is the last event of a run.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, TypingEvent::Finished { .. } | TypingEvent::Failed { .. })
+    }
+}
+
+/// Whether a window title matches a target window pattern: case-insensitive,
+/// with `*` standing for any run of characters. A pattern without `*`
+/// matches anywhere in the title.
+pub fn title_matches(pattern: &str, title: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    let title = title.to_lowercase();
+    if !pattern.contains('*') {
+        return title.contains(&pattern);
+    }
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !title.starts_with(first) || title.len() < first.len() + last.len() || !title.ends_with(last) {
+        return false;
+    }
+    let mut rest = &title[first.len()..title.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// What makes a keyboard for a run, and again if the device goes away.
+/// It is given where to log retries.
+pub type Connect<'a> = dyn FnMut(&dyn Fn(String)) -> Result<Box<dyn Keyboard>, TypingError> + 'a;
+
+/// Connects the backend `options` ask for, or the first available one,
+/// with only `keys` when given (see [`Backend::connect`]).
+pub fn connect_configured(
+    options: &TypingOptions,
+    keys: Option<&[EV_KEY]>,
+    log: &dyn Fn(String),
+) -> Result<Box<dyn Keyboard>, TypingError> {
+    let backend = select_backend(options.backend).map_err(|e| TypingError::Unsupported(format!("{:#}", e)))?;
+    let _device = tracing::info_span!("device", backend = backend.name()).entered();
+    tracing::debug!("Connecting the backend");
+    backend.connect(keys, options.event_timestamps, log)
+}
+
+/// Types the given text as `options` say from `start_at` on, reporting
+/// progress and the final outcome through `events`. The keyboard is made
+/// straight away, so a countdown hides the time that takes and a failure
+/// ends the run before the countdown does. The debug log gets the counts,
+/// never the text. Setting `cancel` stops the run before the next
+/// character; it then fails with [`TypingError::Cancelled`]. A text the
+/// run owns, such as the window's shared copy, is let go before the last
+/// event is sent.
+pub fn simulate_typing(
+    text: impl PlanText,
+    options: &TypingOptions,
+    start_at: Instant,
+    cancel: &AtomicBool,
+    events: &Sender<TypingEvent>,
+) {
+    // The device only needs the keys this text presses; with none, it gets
+    // them all rather than have none.
+    let keys = Some(plan_keys(text.as_text(), options)).filter(|keys| !keys.is_empty());
+    let mut connect = |log: &dyn Fn(String)| connect_configured(options, keys.as_deref(), log);
+    simulate_typing_with(text, options, start_at, cancel, events, &mut connect);
+}
+
+/// [`simulate_typing`] on the keyboards `connect` makes instead of the
+/// configured backend's.
+pub fn simulate_typing_with(
+    text: impl PlanText,
+    options: &TypingOptions,
+    start_at: Instant,
+    cancel: &AtomicBool,
+    events: &Sender<TypingEvent>,
+    connect: &mut Connect,
+) {
+    // Only characters with a key, ASCII on a US layout and the overrides,
+    // can be typed.
+    let chars = text.as_text().chars();
+    let total = chars.clone().filter(|c| options.keymap.is_typeable(*c)).count();
+    let skipped = chars.count() - total;
+    let lines = text.as_text().lines().count().max(1);
+
+    let progress = Progress { total, skipped, lines: Some(lines), ..Progress::default() };
+    let steps = Plan::new(text, options).flatten().map(Step::Key);
+    run_steps(steps, progress, options, start_at, cancel, events, connect);
+}
+
+/// Types `steps` on a keyboard from `connect` once `start_at` has come,
+/// and sends the run's events, its last one included; `steps` go before
+/// it, with the text they hold. `progress` starts with what is known of
+/// the run beforehand.
+pub(crate) fn run_steps(
+    mut steps: impl Iterator<Item = Step>,
+    mut progress: Progress,
+    options: &TypingOptions,
+    start_at: Instant,
+    cancel: &AtomicBool,
+    events: &Sender<TypingEvent>,
+    connect: &mut Connect,
+) {
+    let _run = tracing::info_span!(
+        "run",
+        chars = progress.total,
+        skipped = progress.skipped,
+        backend = options.backend.map_or("auto", Backend::name),
+        char_delay_ms = options.char_delay.as_millis() as u64,
+        newline_delay_ms = options.newline_delay.as_millis() as u64,
+    )
+    .entered();
+    tracing::debug!("Run started");
+    let mut start = Instant::now();
+    // Between making the keyboard and its first key: the countdown, then
+    // the run counts as started.
+    let (total, skipped) = (progress.total, progress.skipped);
+    let mut ready = || {
+        wait_until(start_at, cancel, 0)?;
+        start = Instant::now();
+        let _ = events.send(TypingEvent::Started { total_chars: total });
+        if skipped > 0 {
+            let _ = events.send(TypingEvent::Skipped { count: skipped });
+        }
+        if let Some(title) = &options.focus_window {
+            bring_to_front(title)?;
+        }
+        if let Some(pattern) = &options.target_window {
+            let title = focused_window_title().map_err(|e| TypingError::Unsupported(format!("{:#}", e)))?;
+            if !title_matches(pattern, &title) {
+                return Err(TypingError::WrongWindow { title, pattern: pattern.clone() });
+            }
+        }
+        Ok(())
+    };
+    // A panic still ends the run with an event, so whoever started it is
+    // not left waiting.
+    let run = AssertUnwindSafe(|| type_keys(&mut steps, options, cancel, &mut progress, events, connect, &mut ready));
+    let result = panic::catch_unwind(run)
+        .unwrap_or_else(|payload| Err(TypingError::Crashed(panic_message(payload.as_ref()))));
+    drop(steps);
+    let typed = progress.typed;
+    let average_interval = match progress.key_times {
+        Some((first, last)) if typed > 1 => (last - first) / (typed - 1) as u32,
+        _ => Duration::ZERO,
+    };
+    let stats = RunStats { typed, skipped: progress.skipped, duration: progress.ended.unwrap_or_else(Instant::now) - start, average_interval };
+    let event = match result {
+        Ok(()) => {
+            tracing::debug!(
+                typed = stats.typed,
+                seconds = stats.duration.as_secs_f64(),
+                average_interval_ms = stats.average_interval.as_secs_f64() * 1000.0,
+                "Run finished"
+            );
+            TypingEvent::Finished { stats }
+        }
+        Err(error) => {
+            tracing::debug!(typed = stats.typed, seconds = stats.duration.as_secs_f64(), %error, "Run failed");
+            TypingEvent::Failed { error, stats }
+        }
+    };
+    let _ = events.send(event);
+}
+
+/// Brings the window `TypingOptions::focus_window` names to the front.
+fn bring_to_front(title: &str) -> Result<(), TypingError> {
+    let windows = top_level_windows().map_err(|e| TypingError::Unsupported(format!("{:#}", e)))?;
+    let window = windows
+        .iter()
+        .find(|window| window.title.eq_ignore_ascii_case(title.trim()))
+        .or_else(|| windows.iter().find(|window| title_matches(title, &window.title)))
+        .ok_or_else(|| TypingError::FocusFailed(format!("no window titled '{}' is open", title)))?;
+    activate_window(window.id).map_err(|e| TypingError::FocusFailed(format!("'{}': {:#}", window.title, e)))?;
+    log::debug!("Brought window {:#x} to the front", window.id);
+    Ok(())
+}
+
+/// Sleeping ends this long before a key is due and the rest is waited out
+/// awake, since the scheduler can wake a sleeping thread a millisecond or
+/// more late.
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+
+/// Waits until `deadline`, which may already have passed.
+fn sleep_until(deadline: Instant) {
+    if let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        if left > SPIN_MARGIN {
+            thread::sleep(left - SPIN_MARGIN);
+        }
+    }
+    while Instant::now() < deadline {
+        thread::yield_now();
+    }
+}
+
+/// How often a long wait looks at the cancel flag.
+const CANCEL_CHECK: Duration = Duration::from_millis(20);
+
+/// Waits until `until`, failing as cancelled after `typed` characters when
+/// `cancel` is set.
+fn wait_until(until: Instant, cancel: &AtomicBool, typed: usize) -> Result<(), TypingError> {
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(TypingError::Cancelled { at_char: typed });
+        }
+        match until.checked_duration_since(Instant::now()) {
+            Some(left) if !left.is_zero() => thread::sleep(left.min(CANCEL_CHECK)),
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Waits while the session is locked, failing as cancelled after `typed`
+/// characters when `cancel` is set.
+fn wait_for_unlock(
+    lock: &mut LockWatch,
+    cancel: &AtomicBool,
+    typed: usize,
+    log: &dyn Fn(String),
+) -> Result<(), TypingError> {
+    log(format!("The session is locked; typing paused after {} characters until it is unlocked.", typed));
+    while lock.is_locked() {
+        wait_until(Instant::now() + LOCK_CHECK_INTERVAL, cancel, typed)?;
+    }
+    log(format!("The session is unlocked; typing on from character {}.", typed + 1));
+    Ok(())
+}
+
+/// Tells the window what the run waits for and waits for its
+/// confirmation, then for the focus to go back to the target window.
+fn await_confirmation(
+    confirmations: &Confirmations,
+    wait: Wait,
+    input: Option<&mut InputWatch>,
+    cancel: &AtomicBool,
+    typed: usize,
+    events: &Sender<TypingEvent>,
+) -> Result<(), TypingError> {
+    confirmations.reset();
+    log::debug!("{} after {} characters", wait, typed);
+    let _ = events.send(TypingEvent::AwaitingConfirmation { wait });
+    confirmations.wait(cancel, typed)?;
+    wait_until(Instant::now() + SETTLE_TIME, cancel, typed)?;
+    // Confirming was input too.
+    if let Some(input) = input {
+        input.touched();
+    }
+    Ok(())
+}
+
+/// One thing for `type_keys` to do.
+pub(crate) enum Step {
+    Key(PlannedEvent),
+    /// A stream's line came in, with this many characters to type and to
+    /// skip; its keys follow.
+    Line { typeable: usize, skipped: usize },
+    /// A stream has no line waiting yet.
+    Idle,
+    /// Waits this long, as a stream's `PAUSE` line asks.
+    Pause(Duration),
+    /// Waits for a confirmation, as a stream's `WAIT-ENTER` line asks.
+    Confirm(Wait),
+}
+
+/// How far `type_keys` got.
+#[derive(Default)]
+pub(crate) struct Progress {
+    pub(crate) typed: usize,
+    /// Characters the run types in all; in a stream, those that came in
+    /// so far.
+    pub(crate) total: usize,
+    /// Characters without a key, which are not typed.
+    pub(crate) skipped: usize,
+    /// Lines the text has, which assistive mode asks for one by one;
+    /// unknown in a stream.
+    pub(crate) lines: Option<usize>,
+    /// When the first and the latest key were pressed.
+    key_times: Option<(Instant, Instant)>,
+    /// When the last key was typed, before a read-back's chords.
+    ended: Option<Instant>,
+}
+
+/// How often a stream with no line waiting looks again.
+const IDLE_CHECK: Duration = Duration::from_millis(20);
+
+fn type_keys(
+    steps: &mut dyn Iterator<Item = Step>,
+    options: &TypingOptions,
+    cancel: &AtomicBool,
+    progress: &mut Progress,
+    events: &Sender<TypingEvent>,
+    connect: &mut Connect,
+    ready: &mut dyn FnMut() -> Result<(), TypingError>,
+) -> Result<(), TypingError> {
+    let log = |message: String| {
+        log::warn!("{}", message);
+        let _ = events.send(TypingEvent::Notice { message });
+    };
+    let mut keyboard = connect(&log)?;
+    ready()?;
+    // The device is rebuilt at most once per run.
+    let mut recovered = false;
+    // Each key is due a fixed time after the one before was due, not after
+    // it was pressed, so a late wake-up is made up for instead of adding to
+    // the run.
+    let mut deadline = Instant::now();
+    let mut code = None;
+    // Keys sent into a virtual machine do not reach this session's lock
+    // screen.
+    let mut lock = (options.watch_lock && options.backend != Some(Backend::Libvirt)).then(LockWatch::new);
+    // Opened once the countdown is over, so the clicks that put the focus
+    // in the target window do not count.
+    let mut input = options.assistive.then(InputWatch::open);
+    if input.as_ref().is_some_and(|input| !input.is_watching()) {
+        log("No keyboard or mouse can be watched (reading /dev/input needs the input group); typing will not pause on \
+             its own when they are used."
+            .to_string());
+    }
+    let mut line = 1;
+    let mut line_ended = false;
+    // The line typed so far, for the preview of its Enter.
+    let mut line_typed = Zeroizing::new(String::new());
+
+    for step in steps {
+        let PlannedEvent { at, c, key, shift, pre_delay } = match step {
+            Step::Key(event) => event,
+            Step::Line { typeable, skipped } => {
+                progress.total += typeable;
+                progress.skipped += skipped;
+                continue;
+            }
+            Step::Idle => {
+                wait_until(Instant::now() + IDLE_CHECK, cancel, progress.typed)?;
+                deadline = Instant::now();
+                continue;
+            }
+            Step::Pause(wait) => {
+                wait_until(Instant::now() + wait, cancel, progress.typed)?;
+                deadline = Instant::now();
+                continue;
+            }
+            Step::Confirm(wait) => {
+                await_confirmation(&options.confirmations, wait, input.as_mut(), cancel, progress.typed, events)?;
+                deadline = Instant::now();
+                continue;
+            }
+        };
+        deadline += pre_delay;
+        sleep_until(deadline);
+        if cancel.load(Ordering::Relaxed) {
+            return Err(TypingError::Cancelled { at_char: progress.typed });
+        }
+        if let Some(lock) = &mut lock {
+            if lock.is_locked() {
+                wait_for_unlock(lock, cancel, progress.typed, &log)?;
+                deadline = Instant::now();
+            }
+        }
+        let new_line = std::mem::take(&mut line_ended);
+        if new_line {
+            line += 1;
+        }
+        if options.assistive {
+            // Confirming the Enter before it confirmed this line too.
+            let wait = if new_line && !options.confirm_enter {
+                Some(Wait::NextLine { line, lines: progress.lines })
+            } else if input.as_mut().is_some_and(InputWatch::touched) {
+                Some(Wait::Input)
+            } else {
+                None
+            };
+            if let Some(wait) = wait {
+                await_confirmation(&options.confirmations, wait, input.as_mut(), cancel, progress.typed, events)?;
+                deadline = Instant::now();
+            }
+        }
+        if options.confirm_enter && c == '\n' {
+            // Cancelling while it waits fails the run before the Enter.
+            let wait = Wait::Enter { line, preview: line_preview(&line_typed) };
+            await_confirmation(&options.confirmations, wait, input.as_mut(), cancel, progress.typed, events)?;
+            deadline = Instant::now();
+        }
+        let (c, key, shift) = match code_digit(options, at, &mut code, cancel, progress.typed, &log)? {
+            Some(digit) => {
+                // A wait for a fresh code pushes the rest of the run back.
+                deadline = deadline.max(Instant::now());
+                let (key, shift) = options.keymap.get(digit);
+                (digit, key, shift)
+            }
+            None => (c, key, shift),
+        };
+        match keyboard.type_key(c, key, shift) {
+            Ok(()) => {}
+            Err(e) if is_device_lost(&e) && !recovered => {
+                recovered = true;
+                let at = progress.typed + 1;
+                log(format!("The virtual keyboard went away at character {} ({}); creating it again.", at, e));
+                // The run still failed at this character, not in its setup.
+                keyboard = connect(&log).map_err(|e| TypingError::WriteFailed {
+                    at_char: progress.typed,
+                    source: io::Error::other(format!("the keyboard could not be created again: {}", e)),
+                })?;
+                keyboard.type_key(c, key, shift).map_err(|source| TypingError::WriteFailed { at_char: progress.typed, source })?;
+                log(format!("Recovered; typing on from character {}.", progress.typed + 1));
+                // Typing on at the usual pace, rather than racing to catch up
+                // the time the new device took.
+                deadline = Instant::now();
+            }
+            Err(source) => return Err(TypingError::WriteFailed { at_char: progress.typed, source }),
+        }
+
+        let now = Instant::now();
+        progress.key_times = Some((progress.key_times.map_or(now, |(first, _)| first), now));
+        progress.typed += 1;
+        line_ended = c == '\n';
+        if options.confirm_enter {
+            if line_ended {
+                line_typed.clear();
+            } else {
+                line_typed.push(c);
+            }
+        }
+        let (typed, total) = (progress.typed, progress.total);
+        if typed.is_multiple_of(PROGRESS_INTERVAL) || typed == total {
+            tracing::debug!(typed, total, "Progress");
+            let _ = events.send(TypingEvent::Progress { typed, total });
+        }
+    }
+    progress.ended = Some(Instant::now());
+
+    if let Some(readback) = &options.readback {
+        read_back(keyboard.as_mut(), readback, cancel, progress.typed, &log)?;
+    }
+    Ok(())
+}
+
+/// How long the target gets to take in the last keys, and each chord,
+/// before the next chord.
+const READBACK_SETTLE: Duration = Duration::from_millis(300);
+
+/// Selects everything in the target and copies it, for the window to
+/// compare with the text. A chord that does not get through is only
+/// logged: the text was typed, and the window finds nothing was copied.
+fn read_back(
+    keyboard: &mut dyn Keyboard,
+    readback: &Readback,
+    cancel: &AtomicBool,
+    typed: usize,
+    log: &dyn Fn(String),
+) -> Result<(), TypingError> {
+    for chord in [&readback.select_all, &readback.copy] {
+        wait_until(Instant::now() + READBACK_SETTLE, cancel, typed)?;
+        if let Err(e) = keyboard.press_chord(chord) {
+            log(format!("Could not press {} to read the text back: {}", chord, e));
+            return Ok(());
+        }
+    }
+    wait_until(Instant::now() + READBACK_SETTLE, cancel, typed)
+}
+
+/// The digit to type at position `at` if it is one of a TOTP code's, with
+/// `code` the code being typed and which slot it is for. A code is worked
+/// out at its first digit; if it would expire before it is typed and the
+/// margin has passed, the run waits for the next one, which it then types.
+fn code_digit(
+    options: &TypingOptions,
+    at: usize,
+    code: &mut Option<(usize, Zeroizing<String>)>,
+    cancel: &AtomicBool,
+    typed: usize,
+    log: &dyn Fn(String),
+) -> Result<Option<char>, TypingError> {
+    let Some(index) = options.codes.iter().position(|slot| slot.covers(at)) else {
+        return Ok(None);
+    };
+    let slot = &options.codes[index];
+    if code.as_ref().is_none_or(|(current, _)| *current != index) {
+        let mut now = SystemTime::now();
+        let left = slot.totp.time_left(now);
+        if left < options.char_delay * slot.totp.digits + options.code_margin {
+            log(format!("The TOTP code expires in {:.1} s; waiting for the next one.", left.as_secs_f64()));
+            wait_until(Instant::now() + left, cancel, typed)?;
+            now += left;
+        }
+        *code = Some((index, slot.totp.code_at(now)));
+    }
+    Ok(code.as_ref().and_then(|(_, code)| code.chars().nth(at - slot.at)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::readback::Chord;
+
+    /// A keyboard that types nowhere.
+    struct Nowhere;
+
+    impl Keyboard for Nowhere {
+        fn type_key(&mut self, _c: char, _key: EV_KEY, _shift: bool) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn press_chord(&mut self, _chord: &Chord) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A keyboard that records what it types into `sent`, until it has
+    /// typed `lost_after` characters: then the device is gone.
+    struct Flaky {
+        sent: Rc<RefCell<String>>,
+        lost_after: Option<usize>,
+        typed: usize,
+    }
+
+    impl Keyboard for Flaky {
+        fn type_key(&mut self, c: char, _key: EV_KEY, _shift: bool) -> io::Result<()> {
+            if self.lost_after.is_some_and(|after| self.typed == after) {
+                return Err(io::Error::from_raw_os_error(libc::ENODEV));
+            }
+            self.typed += 1;
+            self.sent.borrow_mut().push(c);
+            Ok(())
+        }
+
+        fn press_chord(&mut self, _chord: &Chord) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Types `text` on keyboards that go away after the characters in
+    /// `lost_after`, one keyboard each, and then on one that does not; a
+    /// `None` of them cannot be created. What was sent, how many keyboards
+    /// were asked for, and the last event.
+    fn run_on_flaky(text: &str, lost_after: &[Option<Option<usize>>]) -> (String, usize, Option<TypingEvent>) {
+        let sent = Rc::new(RefCell::new(String::new()));
+        let mut made = 0;
+        let mut connect = |_: &dyn Fn(String)| -> Result<Box<dyn Keyboard>, TypingError> {
+            let lost_after = lost_after.get(made).copied().unwrap_or(Some(None));
+            made += 1;
+            let lost_after = lost_after.ok_or_else(|| TypingError::DeviceCreation(io::Error::other("busy")))?;
+            Ok(Box::new(Flaky { sent: sent.clone(), lost_after, typed: 0 }))
+        };
+        let options = TypingOptions { watch_lock: false, char_delay: Duration::ZERO, ..TypingOptions::default() };
+        let (sender, receiver) = flume::unbounded();
+        simulate_typing_with(text, &options, Instant::now(), &AtomicBool::new(false), &sender, &mut connect);
+        let last = receiver.try_iter().last();
+        let sent = sent.borrow().clone();
+        (sent, made, last)
+    }
+
+    #[test]
+    fn a_lost_keyboard_is_made_again_and_the_run_goes_on() {
+        let (sent, made, last) = run_on_flaky("hello", &[Some(Some(2))]);
+        assert_eq!((sent.as_str(), made), ("hello", 2));
+        assert!(matches!(last, Some(TypingEvent::Finished { stats }) if stats.typed == 5), "{:?}", last);
+    }
+
+    #[test]
+    fn a_keyboard_lost_again_ends_the_run_where_it_stopped() {
+        let (sent, made, last) = run_on_flaky("hello", &[Some(Some(2)), Some(Some(0))]);
+        assert_eq!((sent.as_str(), made), ("he", 2));
+        assert!(
+            matches!(&last, Some(TypingEvent::Failed { error: TypingError::WriteFailed { at_char: 2, .. }, .. })),
+            "{:?}",
+            last
+        );
+        // A keyboard that cannot be made again ends it the same way, not as
+        // a setup problem.
+        let (sent, made, last) = run_on_flaky("hello", &[Some(Some(2)), None]);
+        assert_eq!((sent.as_str(), made), ("he", 2));
+        match last {
+            Some(TypingEvent::Failed { error: error @ TypingError::WriteFailed { at_char: 2, .. }, .. }) => {
+                assert_eq!(error.to_string(), "Failed to send character 3: the keyboard could not be created again: \
+                                               Failed to create UInput device: busy");
+            }
+            last => panic!("{:?}", last),
+        }
+    }
+
+    fn every_error() -> Vec<TypingError> {
+        vec![
+            TypingError::UinputPermissionDenied,
+            TypingError::UinputMissing,
+            TypingError::DeviceCreation(io::Error::other("busy")),
+            TypingError::WriteFailed { at_char: 4, source: io::Error::other("gone") },
+            TypingError::Unsupported("No backend can type here".to_string()),
+            TypingError::WrongWindow { title: "Terminal".to_string(), pattern: "*Editor".to_string() },
+            TypingError::FocusFailed("no window titled 'Editor' is open".to_string()),
+            TypingError::Backend("The portal refused".to_string()),
+            TypingError::Cancelled { at_char: 2 },
+            TypingError::Crashed("index out of bounds".to_string()),
+        ]
+    }
+
+    #[test]
+    fn only_setup_problems_have_a_remedy() {
+        let remedies: Vec<Option<Remedy>> = every_error().iter().map(TypingError::remedy).collect();
+        let expected = [
+            Some(Remedy::FixPermissions),
+            Some(Remedy::RunDiagnostics),
+            None,
+            None,
+            Some(Remedy::RunDiagnostics),
+            None,
+            None,
+            None,
+            None,
+            None,
+        ];
+        assert_eq!(remedies, expected);
+    }
+
+    #[test]
+    fn errors_say_what_went_wrong() {
+        let messages: Vec<String> = every_error().iter().map(ToString::to_string).collect();
+        let expected = [
+            format!("Failed to create UInput device. Do you have permissions for {}?", UINPUT_PATH),
+            "Failed to create UInput device. Is the 'uinput' kernel module loaded?".to_string(),
+            "Failed to create UInput device: busy".to_string(),
+            // Counted from 1, as the window shows characters.
+            "Failed to send character 5: gone".to_string(),
+            "No backend can type here".to_string(),
+            "The focused window 'Terminal' does not match the target window '*Editor'".to_string(),
+            "Could not bring the target window to the front: no window titled 'Editor' is open".to_string(),
+            "The portal refused".to_string(),
+            "Cancelled".to_string(),
+            "The typing worker crashed: index out of bounds".to_string(),
+        ];
+        assert_eq!(messages, expected);
+    }
+
+    #[test]
+    fn device_errors_are_sorted_out() {
+        let error = |kind| TypingError::from_device_error(io::Error::from(kind));
+        assert!(matches!(error(io::ErrorKind::NotFound), TypingError::UinputMissing));
+        assert!(matches!(error(io::ErrorKind::PermissionDenied), TypingError::UinputPermissionDenied));
+        assert!(matches!(error(io::ErrorKind::ResourceBusy), TypingError::DeviceCreation(_)));
+        let cancelled: Vec<bool> = every_error().iter().map(TypingError::is_cancelled).collect();
+        assert_eq!(cancelled.iter().filter(|c| **c).count(), 1);
+        assert!(std::error::Error::source(&every_error()[3]).is_some());
+    }
+
+    #[test]
+    fn a_cancelled_run_lets_go_of_its_text_before_its_last_event() {
+        let text = Arc::new(Zeroizing::new("a long text\n".repeat(1000)));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = flume::unbounded();
+        let worker = {
+            let (text, cancel) = (text.clone(), cancel.clone());
+            // The countdown outlasts the test unless cancelling ends it.
+            let start_at = Instant::now() + Duration::from_secs(60);
+            thread::spawn(move || {
+                let mut connect = |_: &dyn Fn(String)| -> Result<Box<dyn Keyboard>, TypingError> { Ok(Box::new(Nowhere)) };
+                simulate_typing_with(text, &TypingOptions::default(), start_at, &cancel, &sender, &mut connect);
+            })
+        };
+        cancel.store(true, Ordering::Relaxed);
+        let last = receiver.iter().find(TypingEvent::is_terminal);
+        assert!(matches!(last, Some(TypingEvent::Failed { error: TypingError::Cancelled { at_char: 0 }, .. })));
+        assert_eq!(Arc::strong_count(&text), 1);
+        worker.join().unwrap();
+    }
+}
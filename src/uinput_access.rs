@@ -0,0 +1,166 @@
+//! Getting write access to /dev/uinput: checking the `input` group and
+//! installing the udev rule that fixes a permission problem (run as root
+//! through pkexec). Runs report that problem as
+//! [`TypingError::UinputPermissionDenied`](crate::typing::TypingError).
+
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::text_file::write_atomically;
+
+pub const UINPUT_PATH: &str = "/dev/uinput";
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/99-pasteclipboard-uinput.rules";
+/// The group the rule gives the device to.
+pub const UINPUT_GROUP: &str = "input";
+
+/// Opens the device for writing, which is all creating a keyboard needs.
+pub fn test_access() -> std::io::Result<()> {
+    OpenOptions::new().write(true).open(UINPUT_PATH).map(drop)
+}
+
+/// The rule: the device belongs to the `input` group, and `uaccess` also
+/// gives whoever is logged in at the seat access straight away, without
+/// logging out and back in.
+pub fn rule_content() -> String {
+    format!(
+        "# Installed by PasteClipboard: lets its virtual keyboard use {}.\n\
+         KERNEL==\"uinput\", SUBSYSTEM==\"misc\", GROUP=\"{}\", MODE=\"0660\", TAG+=\"uaccess\", OPTIONS+=\"static_node=uinput\"\n",
+        UINPUT_PATH, UINPUT_GROUP
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupStatus {
+    /// The group does not exist on this system.
+    Missing,
+    NotMember,
+    /// Listed in /etc/group, but this process started before that, so it
+    /// needs a new login to pick it up.
+    MemberAfterLogin,
+    Member,
+}
+
+/// Looks `group` up in /etc/group and in this process's groups.
+pub fn group_status(group: &str) -> GroupStatus {
+    let etc_group = fs::read_to_string("/etc/group").unwrap_or_default();
+    let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let user = std::env::var("USER").or_else(|_| std::env::var("LOGNAME")).unwrap_or_default();
+    group_status_from(&etc_group, &status, &user, group)
+}
+
+fn group_status_from(etc_group: &str, proc_status: &str, user: &str, group: &str) -> GroupStatus {
+    // name:password:gid:member,member
+    let Some((gid, members)) = etc_group.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        (fields.next() == Some(group)).then(|| {
+            fields.next();
+            (fields.next().unwrap_or("").to_string(), fields.next().unwrap_or("").to_string())
+        })
+    }) else {
+        return GroupStatus::Missing;
+    };
+    let active = proc_status
+        .lines()
+        .find_map(|line| line.strip_prefix("Groups:"))
+        .is_some_and(|groups| groups.split_whitespace().any(|g| g == gid));
+    if active {
+        GroupStatus::Member
+    } else if !user.is_empty() && members.split(',').any(|m| m.trim() == user) {
+        GroupStatus::MemberAfterLogin
+    } else {
+        GroupStatus::NotMember
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output =
+        Command::new(program).args(args).output().with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// `--install-udev-rule`, run as root: writes the rule, loads the module if
+/// the device is missing, and has udev apply the rule to the existing node.
+pub fn install_rule() -> Result<()> {
+    let path = Path::new(UDEV_RULE_PATH);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    write_atomically(path, rule_content().as_bytes())?;
+    fs::set_permissions(path, std::os::unix::fs::PermissionsExt::from_mode(0o644))
+        .with_context(|| format!("Failed to set the permissions of {}", UDEV_RULE_PATH))?;
+    if !Path::new(UINPUT_PATH).exists() {
+        run("modprobe", &["uinput"])?;
+    }
+    run("udevadm", &["control", "--reload-rules"])?;
+    run("udevadm", &["trigger", "--action=change", "--subsystem-match=misc", "--sysname-match=uinput"])?;
+    run("udevadm", &["settle", "--timeout=5"])?;
+    Ok(())
+}
+
+/// Runs this program's `--install-udev-rule` through pkexec, which asks for
+/// an administrator password. Blocks until it is done.
+pub fn install_rule_with_pkexec() -> Result<()> {
+    let exe = std::env::current_exe().context("Cannot find this program's path")?;
+    let output = Command::new("pkexec")
+        .arg(&exe)
+        .arg("--install-udev-rule")
+        .output()
+        .context("Failed to run pkexec; is polkit installed?")?;
+    match output.status.code() {
+        Some(0) => Ok(()),
+        // pkexec's own codes for a dismissed or refused authentication.
+        Some(126) => bail!("Authentication was cancelled"),
+        Some(127) => bail!("Not authorized to install the udev rule"),
+        _ => bail!("Installing the udev rule failed: {}", String::from_utf8_lossy(&output.stderr).trim()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETC_GROUP: &str = "root:x:0:\ninput:x:104:alice, bob\nuinput:x:990:carol\nempty:x:991:\n";
+
+    fn status(groups: &str) -> String {
+        format!("Name:\tpasteclipboard\nUid:\t1000\t1000\t1000\t1000\nGroups:\t{}\nNSpgid:\t1\n", groups)
+    }
+
+    #[test]
+    fn the_group_is_looked_up_in_both_files() {
+        let cases = [
+            ("input", "4 24 104", "alice", GroupStatus::Member),
+            // Added after this process started: only /etc/group knows.
+            ("input", "4 24", "bob", GroupStatus::MemberAfterLogin),
+            ("uinput", "4 24", "alice", GroupStatus::NotMember),
+            // The process's groups count whoever the user is.
+            ("uinput", "990", "", GroupStatus::Member),
+            ("empty", "24", "alice", GroupStatus::NotMember),
+            ("plugdev", "104 990", "alice", GroupStatus::Missing),
+        ];
+        for (group, groups, user, expected) in cases {
+            assert_eq!(group_status_from(ETC_GROUP, &status(groups), user, group), expected, "{} for {}", group, user);
+        }
+    }
+
+    #[test]
+    fn a_gid_or_name_only_matches_whole() {
+        // 99 is not 990, and "carol" is not "caro".
+        assert_eq!(group_status_from(ETC_GROUP, &status("99 9900"), "caro", "uinput"), GroupStatus::NotMember);
+        // A group name that only starts like another is not it.
+        assert_eq!(group_status_from(ETC_GROUP, &status("104"), "alice", "inp"), GroupStatus::Missing);
+        // Without a Groups: line the process has none.
+        assert_eq!(group_status_from(ETC_GROUP, "Name:\tx\n", "alice", "input"), GroupStatus::MemberAfterLogin);
+        assert_eq!(group_status_from("", &status("104"), "alice", "input"), GroupStatus::Missing);
+    }
+}
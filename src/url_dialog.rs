@@ -0,0 +1,142 @@
+// "Load from URL…": fills the text area with what a URL serves, or types it
+// directly without it ever appearing in the window, like "Run command…".
+// The download runs off the main thread.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::SimpleAction, glib, Button, ButtonsType, CheckButton, ComboBoxText, Label, MessageDialog, MessageType,
+    Orientation, Window,
+};
+use std::rc::Rc;
+use std::thread;
+use pasteclipboard::settings::load_url_token;
+use pasteclipboard::text_file::normalize;
+use pasteclipboard::url_source::{fetch_text, load_url_history, remember_url, Url};
+
+use crate::window::MainWindow;
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let action = SimpleAction::new("load-url", None);
+    let weak = Rc::downgrade(ui);
+    action.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            show(&ui);
+        }
+    });
+    ui.window.add_action(&action);
+}
+
+fn show(ui: &Rc<MainWindow>) {
+    let dialog = Window::builder()
+        .transient_for(&ui.window)
+        .modal(true)
+        .title("Load from URL")
+        .default_width(460)
+        .build();
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let lbl_help = Label::new(Some(
+        "An http:// or https:// URL serving UTF-8 text (at most 1 MiB; url_token in config.toml is sent to HTTPS \
+         servers as a bearer token):",
+    ));
+    lbl_help.set_xalign(0.0);
+    lbl_help.set_wrap(true);
+    vbox.append(&lbl_help);
+
+    let combo = ComboBoxText::with_entry();
+    for url in load_url_history() {
+        combo.append_text(&url);
+    }
+    vbox.append(&combo);
+
+    let chk_secret = CheckButton::with_label("Type the text directly (secret: not shown or kept in history)");
+    vbox.append(&chk_secret);
+
+    let lbl_status = Label::new(None);
+    lbl_status.set_xalign(0.0);
+    lbl_status.set_wrap(true);
+    vbox.append(&lbl_status);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_cancel = Button::with_label("Cancel");
+    let btn_load = Button::with_label("Load");
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_load);
+    vbox.append(&buttons);
+
+    dialog.set_child(Some(&vbox));
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    let ui = ui.clone();
+    let dialog_clone = dialog.clone();
+    btn_load.connect_clicked(move |btn_load| {
+        let url = combo.active_text().map(|t| t.trim().to_string()).unwrap_or_default();
+        if let Err(e) = Url::parse(&url) {
+            lbl_status.set_text(&format!("{}.", e));
+            return;
+        }
+        let secret = chk_secret.is_active();
+        if secret && ui.is_busy() {
+            lbl_status.set_text("A typing run is already in progress.");
+            return;
+        }
+
+        btn_load.set_sensitive(false);
+        lbl_status.set_text("Downloading…");
+        let (sender, receiver) = flume::bounded(1);
+        let token = load_url_token();
+        let request = url.clone();
+        thread::spawn(move || {
+            let _ = sender.send(fetch_text(&request, token.as_deref()));
+        });
+
+        let ui = ui.clone();
+        let dialog = dialog_clone.clone();
+        let btn_load = btn_load.clone();
+        let lbl_status = lbl_status.clone();
+        glib::MainContext::default().spawn_local(async move {
+            let result = receiver
+                .recv_async()
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("The download stopped unexpectedly")));
+            btn_load.set_sensitive(true);
+            lbl_status.set_text("");
+            match result {
+                Ok(text) if secret => {
+                    dialog.close();
+                    ui.start_text(normalize(&text));
+                }
+                Ok(text) => {
+                    remember_url(&url);
+                    dialog.close();
+                    ui.replace_text(&normalize(&text));
+                    ui.set_status("Loaded the URL's text.");
+                }
+                Err(e) => show_error(&dialog, &format!("{:#}.", e)),
+            }
+        });
+    });
+
+    dialog.present();
+}
+
+fn show_error(parent: &Window, message: &str) {
+    let alert = MessageDialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .message_type(MessageType::Error)
+        .buttons(ButtonsType::Close)
+        .text("The URL could not be loaded")
+        .secondary_text(message)
+        .build();
+    alert.connect_response(|alert, _| alert.close());
+    alert.present();
+}
@@ -0,0 +1,457 @@
+//! Text from a URL for "Load from URL…" and `--url`: a plain HTTP/1.1 GET,
+//! over TLS with the system's certificates for `https://`, with a size cap
+//! and timeout, and the persisted URL history. Only text comes back; a
+//! response that is not text or not UTF-8 is refused.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::settings::state_path;
+use crate::typing::MAX_REMOTE_TEXT_BYTES;
+
+/// Largest response body accepted, in bytes.
+pub const MAX_URL_BYTES: usize = MAX_REMOTE_TEXT_BYTES;
+
+/// How long connecting and reading the whole response may take.
+pub const URL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Number of URLs kept in the history.
+pub const HISTORY_LENGTH: usize = 50;
+
+/// Redirects followed before giving up.
+const MAX_REDIRECTS: usize = 5;
+
+/// Longest status line plus headers accepted.
+const MAX_HEADER_BYTES: u64 = 64 * 1024;
+
+/// An `http://` or `https://` URL, split the way a request needs it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Url {
+    pub secure: bool,
+    /// The host name or address, without the brackets of an IPv6 one.
+    pub host: String,
+    pub port: u16,
+    /// The path and query, starting with `/`; the fragment is dropped.
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        if text.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            bail!("The URL may not contain spaces");
+        }
+        let (secure, rest) = match text.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => (true, rest),
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => (false, rest),
+            Some((scheme, _)) => bail!("Unsupported URL scheme '{}' (use http:// or https://)", scheme),
+            None => bail!("The URL must start with http:// or https://"),
+        };
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(at) if rest[at..].starts_with('?') => (&rest[..at], format!("/{}", &rest[at..])),
+            Some(at) => (&rest[..at], rest[at..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        if authority.contains('@') {
+            bail!("User names and passwords in the URL are not supported; set url_token in config.toml instead");
+        }
+        let (host, port) = match authority.strip_prefix('[') {
+            // An IPv6 address: [::1] or [::1]:8080.
+            Some(v6) => match v6.split_once(']') {
+                Some((host, "")) => (host, None),
+                Some((host, port)) => (host, Some(port.strip_prefix(':').unwrap_or(port))),
+                None => bail!("Invalid host in the URL"),
+            },
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        if host.is_empty() {
+            bail!("The URL has no host");
+        }
+        let port = match port {
+            None if secure => 443,
+            None => 80,
+            Some(port) => match port.parse() {
+                Ok(port) if port > 0 => port,
+                _ => bail!("Invalid port '{}' in the URL", port),
+            },
+        };
+        Ok(Url { secure, host: host.to_string(), port, path })
+    }
+
+    /// The value of the `Host` header: the port only if it is not the
+    /// scheme's default.
+    fn authority(&self) -> String {
+        let host = if self.host.contains(':') { format!("[{}]", self.host) } else { self.host.clone() };
+        if self.port == if self.secure { 443 } else { 80 } {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    /// Where a `Location` header sends a request for this URL.
+    fn join(&self, location: &str) -> Result<Url> {
+        let scheme = if self.secure { "https" } else { "http" };
+        if location.contains("://") {
+            Url::parse(location)
+        } else if let Some(rest) = location.strip_prefix("//") {
+            Url::parse(&format!("{}://{}", scheme, rest))
+        } else if location.starts_with('/') {
+            Ok(Url { path: location.to_string(), ..self.clone() })
+        } else {
+            let dir = self.path.split('?').next().unwrap_or("/");
+            let dir = &dir[..dir.rfind('/').map_or(0, |at| at + 1)];
+            Ok(Url { path: format!("{}{}", dir, location), ..self.clone() })
+        }
+    }
+}
+
+/// Fetches `url` and returns its text, following redirects. `token` is
+/// sent as a bearer token, only over HTTPS and only to the host it was
+/// asked of. Each kind of failure has its own message: the connection, the
+/// TLS handshake (e.g. an unknown certificate), an HTTP error status, a
+/// response over [`MAX_URL_BYTES`], and one that is not UTF-8 text.
+pub fn fetch_text(url: &str, token: Option<&str>) -> Result<String> {
+    let deadline = Instant::now() + URL_TIMEOUT;
+    let first = Url::parse(url)?;
+    let mut url = first.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        let same_origin = url.secure && url.host == first.host && url.port == first.port;
+        let token = token.filter(|_| same_origin);
+        match get(&url, token, deadline)? {
+            Fetched::Text(text) => return Ok(text),
+            Fetched::Redirect(location) => url = url.join(&location)?,
+        }
+    }
+    bail!("The URL redirects more than {} times", MAX_REDIRECTS)
+}
+
+enum Fetched {
+    Text(String),
+    Redirect(String),
+}
+
+fn get(url: &Url, token: Option<&str>, deadline: Instant) -> Result<Fetched> {
+    let mut connection = connect(url, deadline)?;
+    let mut request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: PasteClipboard/{}\r\n\
+         Accept: text/plain, text/*;q=0.9, */*;q=0.1\r\nConnection: close\r\n",
+        url.path,
+        url.authority(),
+        env!("CARGO_PKG_VERSION"),
+    );
+    if let Some(token) = token {
+        request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    connection.write_all(request.as_bytes()).and_then(|_| connection.flush()).map_err(|e| io_error(url, e))?;
+
+    let mut reader = BufReader::new(connection);
+    let head = read_head(&mut reader).map_err(|e| io_error(url, e))?;
+    let (status, reason) = (head.status, &head.reason);
+    let header = |name: &str| head.header(name);
+    match status {
+        200..=299 => {}
+        301 | 302 | 303 | 307 | 308 => match header("Location") {
+            Some(location) => return Ok(Fetched::Redirect(location.to_string())),
+            None => bail!("The server answered {} {} without saying where to", status, reason),
+        },
+        401 | 403 => bail!("The server refused access ({} {}); check url_token in config.toml", status, reason),
+        400..=499 => bail!("The server could not serve the URL ({} {})", status, reason),
+        500..=599 => bail!("The server failed ({} {}); try again later", status, reason),
+        _ => bail!("Unexpected answer from the server ({} {})", status, reason),
+    }
+    let content_type = header("Content-Type").map(str::to_string);
+    check_content_type(content_type.as_deref())?;
+    if header("Content-Encoding").is_some_and(|encoding| !encoding.eq_ignore_ascii_case("identity")) {
+        bail!("The server sent the response compressed, which is not supported");
+    }
+
+    let chunked = header("Transfer-Encoding").is_some_and(|encoding| encoding.to_ascii_lowercase().contains("chunked"));
+    let length = header("Content-Length").map(|length| length.trim().parse::<u64>());
+    let body = if chunked {
+        read_chunked(&mut reader)
+    } else if let Some(length) = length {
+        let length = length.map_err(|_| anyhow!("The server sent an invalid Content-Length"))?;
+        if length > MAX_URL_BYTES as u64 {
+            bail!("The response is larger than {} MiB", MAX_URL_BYTES / (1024 * 1024));
+        }
+        let mut body = Vec::new();
+        match reader.take(length).read_to_end(&mut body) {
+            Ok(_) if body.len() as u64 == length => Ok(body),
+            Ok(_) => bail!("The response was cut short"),
+            Err(e) => Err(e.into()),
+        }
+    } else {
+        read_to_close(&mut reader.take(MAX_URL_BYTES as u64 + 1))
+    };
+    let body = body.map_err(|e| match e.downcast::<io::Error>() {
+        Ok(e) => io_error(url, e),
+        Err(e) => e,
+    })?;
+    if body.len() > MAX_URL_BYTES {
+        bail!("The response is larger than {} MiB", MAX_URL_BYTES / (1024 * 1024));
+    }
+    if body[..body.len().min(8192)].contains(&0) {
+        bail!("The response looks like a binary file, not text");
+    }
+    match String::from_utf8(body) {
+        Ok(text) => Ok(Fetched::Text(text)),
+        Err(_) => bail!("The response is not UTF-8 text"),
+    }
+}
+
+/// Refuses a response whose type is not a kind of text, or whose charset
+/// is not UTF-8 (or ASCII, a part of it). Without a type the body decides.
+fn check_content_type(content_type: Option<&str>) -> Result<()> {
+    let Some(content_type) = content_type else {
+        return Ok(());
+    };
+    let mut parts = content_type.split(';');
+    let mime = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let textual = mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json" | "application/xml" | "application/javascript" | "application/x-sh" | "application/yaml"
+        );
+    if !textual {
+        bail!("The response is {}, not text", mime);
+    }
+    for parameter in parts {
+        if let Some((name, value)) = parameter.split_once('=') {
+            let charset = value.trim().trim_matches('"').to_ascii_lowercase();
+            if name.trim().eq_ignore_ascii_case("charset") && !matches!(charset.as_str(), "utf-8" | "utf8" | "us-ascii") {
+                bail!("The response is in the {} character set; only UTF-8 is supported", charset);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A response's status line and headers.
+struct Head {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Head {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_head(reader: &mut impl BufRead) -> io::Result<Head> {
+    let mut limited = reader.by_ref().take(MAX_HEADER_BYTES);
+    let mut line = String::new();
+    limited.read_line(&mut line)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "the server's answer is not HTTP");
+    let mut parts = line.trim_end().splitn(3, ' ');
+    if !parts.next().is_some_and(|version| version.starts_with("HTTP/1.")) {
+        return Err(invalid());
+    }
+    let status = parts.next().and_then(|status| status.parse().ok()).ok_or_else(invalid)?;
+    let reason = parts.next().unwrap_or_default().to_string();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if limited.read_line(&mut line)? == 0 {
+            return Err(invalid());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(Head { status, reason, headers });
+        }
+        let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+}
+
+/// Reads a chunked body, stopping past [`MAX_URL_BYTES`].
+fn read_chunked(reader: &mut impl BufRead) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.by_ref().take(1024).read_line(&mut line)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size, 16).map_err(|_| anyhow!("The server sent an invalid chunk"))?;
+        if size == 0 {
+            return Ok(body);
+        }
+        if body.len() as u64 + size > MAX_URL_BYTES as u64 {
+            bail!("The response is larger than {} MiB", MAX_URL_BYTES / (1024 * 1024));
+        }
+        let before = body.len();
+        reader.by_ref().take(size).read_to_end(&mut body)?;
+        if (body.len() - before) as u64 != size {
+            bail!("The response was cut short");
+        }
+        line.clear();
+        reader.by_ref().take(2).read_line(&mut line)?;
+    }
+}
+
+/// Reads a body that ends when the server closes the connection. Many
+/// servers close without TLS's close_notify, which is taken for the end
+/// too: the response is checked as text afterwards either way.
+fn read_to_close(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    match reader.read_to_end(&mut body) {
+        Ok(_) => Ok(body),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(body),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Describes a failed read or write: a TLS error (such as an untrusted
+/// certificate), the timeout, or the connection itself.
+fn io_error(url: &Url, error: io::Error) -> anyhow::Error {
+    if let Some(tls) = error.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+        return anyhow!("TLS error with {}: {}", url.host, tls);
+    }
+    match error.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+            anyhow!("{} did not answer within {} seconds", url.host, URL_TIMEOUT.as_secs())
+        }
+        _ => anyhow!("Connection to {} failed: {}", url.host, error),
+    }
+}
+
+/// A connection to the server, plain or over TLS, whose reads and writes
+/// fail once `deadline` has passed.
+enum Connection {
+    Plain(TcpStream, Instant),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>, Instant),
+}
+
+impl Connection {
+    /// Applies what is left of the deadline to the socket.
+    fn arm(&mut self) -> io::Result<()> {
+        let (socket, deadline) = match self {
+            Connection::Plain(socket, deadline) => (&*socket, *deadline),
+            Connection::Tls(stream, deadline) => (&stream.sock, *deadline),
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        socket.set_read_timeout(Some(left))?;
+        socket.set_write_timeout(Some(left))
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.arm()?;
+        match self {
+            Connection::Plain(socket, _) => socket.read(buf),
+            Connection::Tls(stream, _) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.arm()?;
+        match self {
+            Connection::Plain(socket, _) => socket.write(buf),
+            Connection::Tls(stream, _) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(socket, _) => socket.flush(),
+            Connection::Tls(stream, _) => stream.flush(),
+        }
+    }
+}
+
+fn connect(url: &Url, deadline: Instant) -> Result<Connection> {
+    let addresses: Vec<_> = (url.host.as_str(), url.port)
+        .to_socket_addrs()
+        .with_context(|| format!("Cannot find the host {}", url.host))?
+        .collect();
+    let mut last_error = None;
+    let mut socket = None;
+    for address in addresses {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        match TcpStream::connect_timeout(&address, left) {
+            Ok(connected) => {
+                socket = Some(connected);
+                break;
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    let socket = match (socket, last_error) {
+        (Some(socket), _) => socket,
+        (None, Some(e)) => return Err(io_error(url, e)),
+        (None, None) => return Err(io_error(url, io::ErrorKind::TimedOut.into())),
+    };
+    if !url.secure {
+        return Ok(Connection::Plain(socket, deadline));
+    }
+    let name = ServerName::try_from(url.host.clone()).map_err(|_| anyhow!("Invalid host name {}", url.host))?;
+    let tls = ClientConnection::new(tls_config()?, name).map_err(|e| anyhow!("TLS error with {}: {}", url.host, e))?;
+    Ok(Connection::Tls(Box::new(StreamOwned::new(tls, socket)), deadline))
+}
+
+/// A TLS client trusting the system's certificates.
+fn tls_config() -> Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    for certificate in rustls_native_certs::load_native_certs().context("Cannot read the system's certificates")? {
+        // One unreadable certificate should not stop the others.
+        let _ = roots.add(certificate);
+    }
+    if roots.is_empty() {
+        bail!("No system certificates were found to check the server's against");
+    }
+    Ok(Arc::new(ClientConfig::builder().with_root_certificates(roots).with_no_client_auth()))
+}
+
+fn history_path() -> Option<PathBuf> {
+    state_path("url_history")
+}
+
+/// The URLs loaded before, most recent first.
+pub fn load_url_history() -> Vec<String> {
+    history_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|history| history.lines().filter(|l| !l.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Moves `url` to the front of the history.
+pub fn remember_url(url: &str) {
+    let Some(path) = history_path() else {
+        return;
+    };
+    let url = url.trim();
+    let mut history = load_url_history();
+    history.retain(|u| u != url);
+    history.insert(0, url.to_string());
+    history.truncate(HISTORY_LENGTH);
+
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::write(path, history.join("\n") + "\n");
+}
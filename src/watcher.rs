@@ -0,0 +1,163 @@
+// Optional drop directory: each text file saved into it is offered in a
+// notification ("Type contents of foo.txt?") and, once typed or dismissed,
+// moved to its done/ subfolder.
+
+use gtk4::prelude::*;
+use gtk4::{
+    gio::{Notification, SimpleAction},
+    glib::{timeout_add_local, ControlFlow, VariantTy},
+    Application,
+};
+use notify::event::{AccessKind, AccessMode, CreateKind, ModifyKind, RenameMode};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
+use pasteclipboard::drop_folder::{is_candidate, is_inside, move_to_done, read_dropped_file, watch_directory};
+use pasteclipboard::settings::{load_delay_setting, load_typing_options, Settings};
+
+use crate::window::WindowSlot;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn enabled() -> bool {
+    Settings::load().watch_enabled
+}
+
+fn notification_id(path: &Path) -> String {
+    format!("drop-{}", path.file_name().unwrap_or_default().to_string_lossy())
+}
+
+fn notify_problem(app: &Application, path: &Path, error: &anyhow::Error) {
+    let notification = Notification::new(&format!(
+        "Cannot type {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    notification.set_body(Some(&format!("{:#}", error)));
+    app.send_notification(Some(&notification_id(path)), &notification);
+}
+
+/// Asks whether to type a newly dropped file. For a file that cannot be
+/// typed, says why instead.
+fn offer(app: &Application, path: &Path) {
+    if let Err(e) = read_dropped_file(path) {
+        notify_problem(app, path, &e);
+        return;
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let notification = Notification::new(&format!("Type contents of {}?", name));
+    notification.set_body(Some(&match load_delay_setting() {
+        Ok(delay) => format!("Typing starts {} seconds after you click Type; focus the target window.", delay),
+        Err(_) => "Focus the target window after clicking Type.".to_string(),
+    }));
+    let target = path.to_string_lossy().to_variant();
+    notification.add_button_with_target_value("Type", "app.type-dropped-file", Some(&target));
+    notification.add_button_with_target_value("Dismiss", "app.dismiss-dropped-file", Some(&target));
+    app.send_notification(Some(&notification_id(path)), &notification);
+}
+
+fn type_dropped_file(app: &Application, slot: &WindowSlot, dir: &Path, path: &Path) -> Result<()> {
+    let text = read_dropped_file(path)?;
+    let delay = load_delay_setting().map_err(|e| anyhow!("{} in config.toml", e))?;
+    let options = load_typing_options().map_err(|e| anyhow!("{} in config.toml", e))?;
+    let ui = slot.get(app);
+    if ui.is_busy() {
+        bail!("A typing run is already in progress");
+    }
+    move_to_done(dir, path)?;
+    ui.start_run(text.into(), delay, options);
+    Ok(())
+}
+
+/// The notification buttons. They can be activated by anything on the
+/// session bus, so only files directly inside the watched directory count.
+fn add_actions(app: &Application, slot: Rc<WindowSlot>, dir: PathBuf) {
+    let type_action = SimpleAction::new("type-dropped-file", Some(VariantTy::STRING));
+    let dismiss_action = SimpleAction::new("dismiss-dropped-file", Some(VariantTy::STRING));
+
+    let app_weak = app.downgrade();
+    let type_dir = dir.clone();
+    type_action.connect_activate(move |_, target| {
+        let (Some(app), Some(path)) = (app_weak.upgrade(), target.and_then(|t| t.get::<String>())) else {
+            return;
+        };
+        let path = PathBuf::from(path);
+        if !is_inside(&type_dir, &path) {
+            return;
+        }
+        app.withdraw_notification(&notification_id(&path));
+        if let Err(e) = type_dropped_file(&app, &slot, &type_dir, &path) {
+            notify_problem(&app, &path, &e);
+        }
+    });
+
+    let app_weak = app.downgrade();
+    dismiss_action.connect_activate(move |_, target| {
+        let (Some(app), Some(path)) = (app_weak.upgrade(), target.and_then(|t| t.get::<String>())) else {
+            return;
+        };
+        let path = PathBuf::from(path);
+        if !is_inside(&dir, &path) {
+            return;
+        }
+        app.withdraw_notification(&notification_id(&path));
+        if let Err(e) = move_to_done(&dir, &path) {
+            notify_problem(&app, &path, &e);
+        }
+    });
+
+    app.add_action(&type_action);
+    app.add_action(&dismiss_action);
+}
+
+/// Starts watching the drop directory, creating it if needed; later calls
+/// do nothing.
+pub fn start(app: &Application, slot: Rc<WindowSlot>) -> Result<()> {
+    if STARTED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let dir = watch_directory().context("Cannot determine your home directory")?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("Failed to set up the directory watcher")?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", dir.display()))?;
+    STARTED.store(true, Ordering::SeqCst);
+    add_actions(app, slot, dir);
+
+    let app = app.downgrade();
+    timeout_add_local(Duration::from_millis(100), move || {
+        // The watcher stops when dropped, so this source keeps it.
+        let _watcher = &watcher;
+        let Some(app) = app.upgrade() else {
+            return ControlFlow::Break;
+        };
+        while let Ok(event) = receiver.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            // Regular files are offered once fully written (or moved in);
+            // symlinks never get a close event, so they are caught at
+            // creation to report that they are refused.
+            let complete = match event.kind {
+                EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)) => true,
+                EventKind::Create(CreateKind::File | CreateKind::Any | CreateKind::Other) => false,
+                _ => continue,
+            };
+            for path in event.paths.iter().filter(|p| p.parent().is_some() && is_candidate(p)) {
+                let is_symlink = std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+                if complete || is_symlink {
+                    offer(&app, path);
+                }
+            }
+        }
+        ControlFlow::Continue
+    });
+    Ok(())
+}
@@ -0,0 +1,2021 @@
+// The main window and the typing-run machinery behind its Start button.
+
+use gtk4::prelude::*;
+use gtk4::{
+    accessible, gdk, gio,
+    glib::{self, source::timeout_add_local_once, ControlFlow, timeout_add_local},
+    AboutDialog, Application, ApplicationInhibitFlags, ApplicationWindow, Button, ButtonsType, CheckButton, HeaderBar, Label, MenuButton,
+    MessageDialog, MessageType, Orientation, ProgressBar, ScrolledWindow, SpinButton, TextBuffer, TextView,
+    ToggleButton,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use pasteclipboard::activity_log::milestone;
+use pasteclipboard::assistive::ASSISTIVE_MIN_CHAR_DELAY_MS;
+use pasteclipboard::auto_type::{
+    fetch_pass, fields, hotp_labels, parse_sequence, pass_paths, resolve, Credentials, Token,
+};
+use pasteclipboard::backend::{first_available, probe_all, Backend};
+use pasteclipboard::expand::{commit_counters, env_lookup, expand_stamps, expand_variables, load_counters, Stamped};
+use pasteclipboard::hotp;
+use pasteclipboard::i18n::{args, gettext, ngettext};
+use pasteclipboard::line_filter::{parse_prefixes, LineFilter};
+use pasteclipboard::profiles::active_profile;
+use pasteclipboard::recent_texts::{preview, remember_text};
+use pasteclipboard::remote::type_remote;
+use pasteclipboard::resume::{next_position, rest, rest_options, snap, typeable_before};
+use pasteclipboard::run_state::{RunId, RunState};
+use pasteclipboard::secret_scan::scan;
+use pasteclipboard::session::{
+    discard_draft, load_geometry, load_window_flag, save_drafts, save_geometry, save_window_flag, Geometry,
+};
+use pasteclipboard::settings::{
+    config_path, countdown_seconds, has_override, load_backend_setting, load_comment_prefixes_setting, load_notify_before_setting, load_pass_command, load_secret_patterns, load_settings, load_stream_sentinel, load_typing_options, parse_delay_lenient, save_setting, save_settings,
+    Settings,
+};
+use pasteclipboard::smart_punctuation;
+use pasteclipboard::snippets::{
+    is_encrypted, is_secure, load_credentials, load_snippet_settings, remember_snippet_used, snippet_text_for_typing,
+    SnippetSettings,
+};
+use pasteclipboard::stats::{load_totals, record_run};
+use pasteclipboard::stream::stream_typing;
+use pasteclipboard::transform::Transform;
+use pasteclipboard::template::{fill, variables};
+use pasteclipboard::text_stats::{estimate, format_duration, mark_skipped, summarize};
+use pasteclipboard::typing::{
+    simulate_typing, Remedy, RunStats, TypingError, TypingEvent, TypingOptions, MAX_DELAY_SECONDS,
+};
+use pasteclipboard::uinput_access::UINPUT_PATH;
+use zeroize::Zeroizing;
+
+use crate::auto_type_dialog;
+use crate::benchmark_dialog;
+use crate::command_dialog;
+use crate::config_transfer;
+use crate::assistive_mode::{self, AssistiveMode};
+use crate::config_watcher;
+use crate::countdown_overlay::CountdownOverlay;
+use crate::diagnostics_dialog;
+use crate::editor_style;
+use crate::file_actions;
+use crate::file_drop;
+use crate::find_replace::FindBar;
+use crate::keep_above;
+use crate::log_pane::LogPane;
+use crate::permission_dialog;
+use crate::placeholder_dialog;
+use crate::profile_switcher::{self, ProfileSwitcher};
+use crate::readback_check::ReadbackCheck;
+use crate::readiness::{self, Readiness};
+use crate::recent_menu;
+use crate::resume_offer::{self, Interrupted, ResumeOffer};
+use crate::secret_dialog;
+use crate::secret_scan_dialog;
+use crate::shortcuts;
+use crate::skipped_marks::SkippedMarks;
+use crate::snippets_panel;
+use crate::sounds::{self, Sound};
+use crate::status_area::StatusArea;
+use crate::tabs::{self, Tabs};
+use crate::test_target;
+use crate::url_dialog;
+use crate::window_picker;
+use crate::APP_NAME;
+
+/// Countdown and completion notifications replace each other.
+const RUN_NOTIFICATION_ID: &str = "run";
+
+const COMPACT_WIDTH: i32 = 360;
+
+/// How long a failed run's progress bar stays up.
+const FAILED_PROGRESS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Formats a count with thousands separators, e.g. 8977 -> "8,977".
+pub fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub fn progress_text(typed: usize, total: usize) -> String {
+    args(&gettext("typed {0} / {1}"), &[&format_count(typed), &format_count(total)])
+}
+
+/// "1:12" or "1:02:05", for the progress bar's remaining time.
+fn format_clock(duration: Duration) -> String {
+    let secs = duration.as_secs_f64().ceil() as u64;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Estimates the time left from the speed so far, or from the run's
+/// `planned` duration before the first second of typing has been measured.
+fn remaining_time(typed: usize, total: usize, elapsed: Duration, planned: Duration) -> Duration {
+    let left = total.saturating_sub(typed);
+    if typed > 0 && elapsed >= Duration::from_secs(1) {
+        elapsed.mul_f64(left as f64 / typed as f64)
+    } else {
+        planned.mul_f64(left as f64 / total.max(1) as f64)
+    }
+}
+
+/// The progress bar's text, e.g. "Typing… 4,112 / 9,800 (ETA 1:12)".
+fn progress_bar_text(typed: usize, total: usize, eta: Duration) -> String {
+    args(
+        &gettext("Typing… {0} / {1} (ETA {2})"),
+        &[&format_count(typed), &format_count(total), &format_clock(eta)],
+    )
+}
+
+/// The progress bar's text in a stream run, e.g. "Typing… 180 of 240
+/// received".
+fn stream_bar_text(typed: usize, received: usize) -> String {
+    args(&gettext("Typing… {0} of {1} received"), &[&format_count(typed), &format_count(received)])
+}
+
+/// Summarises a run, e.g. "1,234 characters in 25.3s (48.8 chars/s)".
+pub fn stats_text(stats: &RunStats) -> String {
+    let mut text = args(
+        &ngettext("{0} character in {1}s ({2} chars/s)", "{0} characters in {1}s ({2} chars/s)", stats.typed as u64),
+        &[
+            &format_count(stats.typed),
+            &format!("{:.1}", stats.duration.as_secs_f64()),
+            &format!("{:.1}", stats.chars_per_second()),
+        ],
+    );
+    if stats.skipped > 0 {
+        text.push_str(&args(
+            &ngettext(", {0} unsupported character skipped", ", {0} unsupported characters skipped", stats.skipped as u64),
+            &[&format_count(stats.skipped)],
+        ));
+    }
+    if stats.typed > 1 {
+        let interval = format!("{:.1}", stats.average_interval.as_secs_f64() * 1000.0);
+        text.push_str(&args(&gettext(", one key every {0} ms"), &[&interval]));
+    }
+    text
+}
+
+/// What went wrong in a run, in the words shown to the user.
+pub fn failure_text(error: &TypingError) -> String {
+    match error {
+        TypingError::UinputPermissionDenied => args(&gettext("No permission to use {0}."), &[&UINPUT_PATH]),
+        TypingError::UinputMissing => {
+            args(&gettext("{0} is missing; the 'uinput' kernel module is probably not loaded."), &[&UINPUT_PATH])
+        }
+        TypingError::DeviceCreation(e) => args(&gettext("Could not create the virtual keyboard: {0}."), &[e]),
+        TypingError::WriteFailed { at_char, source } => args(
+            &gettext("The key press for character {0} did not get through: {1}."),
+            &[&format_count(at_char + 1), source],
+        ),
+        TypingError::Unsupported(reason) => args(&gettext("This session cannot type: {0}."), &[reason]),
+        TypingError::WrongWindow { title, pattern } => args(
+            &gettext("The focused window “{0}” is not the target window “{1}”."),
+            &[title, pattern],
+        ),
+        TypingError::FocusFailed(reason) => {
+            args(&gettext("Could not bring the target window to the front: {0}."), &[reason])
+        }
+        TypingError::Backend(reason) => format!("{}.", reason.trim_end_matches('.')),
+        TypingError::Cancelled { .. } => gettext("Cancelled."),
+        TypingError::Crashed(message) => args(&gettext("The typing worker crashed: {0}."), &[message]),
+    }
+}
+
+/// The Details of a failed run: the error, the backend the run used, how
+/// far it got and the OS error behind it, for bug reports.
+fn failure_details(error: &TypingError, backend: &str) -> String {
+    let mut details = format!("{}\nBackend: {}", error, backend);
+    if let TypingError::WriteFailed { at_char, .. } = error {
+        details.push_str(&format!("\nCharacters typed: {}", at_char));
+    }
+    let os_error = match error {
+        TypingError::DeviceCreation(e) | TypingError::WriteFailed { source: e, .. } => e.raw_os_error(),
+        _ => None,
+    };
+    if let Some(errno) = os_error {
+        // As strerror says it, without the "(os error N)" Rust adds.
+        let text = std::io::Error::from_raw_os_error(errno).to_string();
+        let text = text.trim_end_matches(&format!(" (os error {})", errno));
+        details.push_str(&format!("\nOS error: {} (errno {})", text, errno));
+    }
+    details
+}
+
+/// Sends (or replaces) the run's desktop notification unless notifications
+/// are turned off. Only counts and errors go into it, never the text.
+fn notify_run(ui: &MainWindow, title: &str, body: &str) {
+    if !Settings::load().notifications {
+        return;
+    }
+    let Some(app) = ui.window.application() else {
+        return;
+    };
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(body));
+    app.send_notification(Some(RUN_NOTIFICATION_ID), &notification);
+}
+
+fn notify_countdown(ui: &MainWindow, remaining_seconds: u64) {
+    notify_run(
+        ui,
+        &args(
+            &ngettext("Typing starts in {0} second", "Typing starts in {0} seconds", remaining_seconds),
+            &[&remaining_seconds],
+        ),
+        &gettext("Focus the window you want the text typed into."),
+    );
+}
+
+/// The status line during the countdown; `selection` is the length of the
+/// selected text when only that is typed.
+fn countdown_text(remaining_seconds: u64, selection: Option<usize>) -> String {
+    let countdown = args(
+        &ngettext(
+            "Typing in {0} second... focus the target window.",
+            "Typing in {0} seconds... focus the target window.",
+            remaining_seconds,
+        ),
+        &[&remaining_seconds],
+    );
+    match selection {
+        Some(chars) => args(&gettext("Typing selection, {0} chars. {1}"), &[&format_count(chars), &countdown]),
+        None => countdown,
+    }
+}
+
+/// What the log says about a run's text: the start of its first line and
+/// its length, or only the length for a secret run.
+fn describe_text(text: &str, secret: bool) -> String {
+    let chars = format!("{} chars", format_count(text.chars().count()));
+    if secret {
+        return format!("{} (secret)", chars);
+    }
+    format!("“{}” ({})", preview(text), chars)
+}
+
+/// Fills in `{DATE:…}`, `{TIME:…}` and `{COUNTER:…}` as of now, when
+/// templates are turned on.
+fn stamp(text: String) -> anyhow::Result<Stamped> {
+    if !Settings::load().templates {
+        return Ok(Stamped { text, counters: Vec::new() });
+    }
+    let now = glib::DateTime::now_local()?;
+    expand_stamps(&text, &now, &load_counters())
+}
+
+/// Receives every event of the current run after the window has handled it.
+/// Listeners are dropped once the run finishes or fails.
+pub type RunListener = Box<dyn FnMut(&TypingEvent)>;
+
+/// Widgets of the main window that command-line invocations need to reach.
+pub struct MainWindow {
+    pub window: ApplicationWindow,
+    pub buffer: TextBuffer,
+    pub entry_delay: SpinButton,
+    pub btn_start: Button,
+    btn_cancel: Button,
+    chk_expand: CheckButton,
+    chk_skip_blank: CheckButton,
+    chk_strip_comments: CheckButton,
+    chk_smart_punctuation: CheckButton,
+    chk_minimize: CheckButton,
+    btn_command: Button,
+    pub status: Rc<StatusArea>,
+    lbl_counts: Label,
+    progress: ProgressBar,
+    pub run_listeners: RefCell<Vec<RunListener>>,
+    pub run_state: RefCell<RunState>,
+    /// The run in progress, or the last one; callbacks of older runs do
+    /// nothing.
+    run_id: Cell<RunId>,
+    /// The file the buffer was opened from or saved to.
+    pub file: RefCell<Option<PathBuf>>,
+    pub tabs: Tabs,
+    pub profiles: ProfileSwitcher,
+    pub readiness: Readiness,
+    pub assistive: AssistiveMode,
+    pub resume: ResumeOffer,
+    /// What `settings_changed` last applied, so the config watcher can tell
+    /// the window's own saves from edits elsewhere.
+    pub applied_settings: RefCell<Settings>,
+    /// Holds the "Recent" submenu, rebuilt as it changes.
+    pub recent_section: gio::Menu,
+    /// Set while the window is minimized for the current run, so it is
+    /// presented again when the run ends.
+    minimized_for_run: Cell<bool>,
+    /// The cookie of the idle inhibitor the current run holds, or 0.
+    idle_inhibit: Cell<u32>,
+    paned: gtk4::Paned,
+    /// Everything compact mode hides, besides the snippets sidebar.
+    full_layout: Vec<gtk4::Widget>,
+    /// Compact mode's one-line stand-in for the text area.
+    lbl_summary: Label,
+    /// The size to return to when leaving compact mode.
+    normal_size: Cell<Option<(i32, i32)>>,
+    /// Set to cancel the current run.
+    cancel: RefCell<Arc<AtomicBool>>,
+    skipped_marks: Rc<SkippedMarks>,
+    preview_box: gtk4::Box,
+    preview_buffer: TextBuffer,
+    chk_preview_auto: CheckButton,
+    pub find_bar: Rc<FindBar>,
+    pub log: LogPane,
+    /// Set for the next run when its text must not be described in the
+    /// log, as for a secret command's output.
+    secret_run: Cell<bool>,
+    /// Set for the next run when it types the selection rather than the
+    /// whole buffer.
+    selection_run: Cell<bool>,
+    /// The stopped run the next run types the rest of.
+    resume_run: RefCell<Option<Interrupted>>,
+    /// The lines the next run types instead of a text, for `--stream`.
+    stream_run: RefCell<Option<flume::Receiver<String>>>,
+    /// The counter values the next run's text uses, saved if it completes.
+    counter_run: RefCell<Vec<(String, u64)>>,
+    /// The counters the next run's HOTP tokens move on to if it completes.
+    hotp_run: RefCell<Vec<(String, u64)>>,
+    /// Template variables given with `--var` for the next run.
+    pub template_vars: RefCell<HashMap<String, String>>,
+    /// The countdown overlay, while a countdown shows one.
+    overlay: RefCell<Option<CountdownOverlay>>,
+}
+
+pub fn build_ui(app: &Application) -> Rc<MainWindow> {
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title(APP_NAME)
+        .default_width(760)
+        .default_height(420)
+        .build();
+
+    let file_section = gio::Menu::new();
+    file_section.append(Some(&gettext("New Tab")), Some("win.new-tab"));
+    file_section.append(Some(&gettext("Close Tab")), Some("win.close-tab"));
+    file_section.append(Some(&gettext("Open…")), Some("win.open"));
+    file_section.append(Some(&gettext("Load from URL…")), Some("win.load-url"));
+    file_section.append(Some(&gettext("Save")), Some("win.save"));
+    file_section.append(Some(&gettext("Save As…")), Some("win.save-as"));
+    file_section.append(Some(&gettext("Type Secret…")), Some("win.type-secret"));
+    file_section.append(Some(&gettext("Type Into Window…")), Some("win.type-into-window"));
+    let recent_section = gio::Menu::new();
+    file_section.append_section(None, &recent_section);
+    let edit_section = gio::Menu::new();
+    edit_section.append(Some(&gettext("Undo")), Some("win.undo"));
+    edit_section.append(Some(&gettext("Redo")), Some("win.redo"));
+    edit_section.append(Some(&gettext("Clear")), Some("win.clear"));
+    let transform_menu = gio::Menu::new();
+    for transform in Transform::ALL {
+        transform_menu.append(Some(transform.label()), Some(&format!("win.{}", transform.name())));
+    }
+    edit_section.append_submenu(Some(&gettext("Transform")), &transform_menu);
+    edit_section.append(Some(&gettext("Find…")), Some("win.find"));
+    edit_section.append(Some(&gettext("Find and Replace…")), Some("win.replace"));
+    let view_section = gio::Menu::new();
+    view_section.append(Some(&gettext("Monospace Font")), Some("win.monospace"));
+    view_section.append(Some(&gettext("Word Wrap")), Some("win.word-wrap"));
+    view_section.append(Some(&gettext("Larger Text")), Some("win.zoom-in"));
+    view_section.append(Some(&gettext("Smaller Text")), Some("win.zoom-out"));
+    view_section.append(Some(&gettext("Normal Text Size")), Some("win.zoom-reset"));
+    view_section.append(Some(&gettext("Keep on Top")), Some("win.keep-on-top"));
+    let app_section = gio::Menu::new();
+    app_section.append(Some(&gettext("Preferences")), Some("win.preferences"));
+    app_section.append(Some(&gettext("Save Settings as Profile…")), Some("win.save-profile"));
+    app_section.append(Some(&gettext("Export Configuration…")), Some("win.export-config"));
+    app_section.append(Some(&gettext("Import Configuration…")), Some("win.import-config"));
+    app_section.append(Some(&gettext("Keyboard Shortcuts")), Some("win.show-help-overlay"));
+    app_section.append(Some(&gettext("Statistics")), Some("win.statistics"));
+    app_section.append(Some(&gettext("Benchmark…")), Some("win.benchmark"));
+    app_section.append(Some(&gettext("Run Diagnostics")), Some("win.diagnostics"));
+    app_section.append(Some(&gettext("Test Target…")), Some("win.test-target"));
+    app_section.append(Some(&gettext("About PasteClipboard")), Some("win.about"));
+    let menu = gio::Menu::new();
+    menu.append_section(None, &file_section);
+    menu.append_section(None, &edit_section);
+    menu.append_section(None, &view_section);
+    menu.append_section(None, &app_section);
+    let btn_menu = MenuButton::builder()
+        .icon_name("open-menu-symbolic")
+        .tooltip_text(gettext("Main menu"))
+        .menu_model(&menu)
+        .build();
+    let btn_compact = ToggleButton::builder()
+        .icon_name("view-restore-symbolic")
+        .tooltip_text(gettext("Compact mode"))
+        .action_name("win.compact")
+        .build();
+
+    let btn_start = Button::with_label(&gettext("Type After Delay"));
+    btn_start.add_css_class("suggested-action");
+    btn_start.set_tooltip_text(Some(&gettext("Type the selection, or all text without one, after the delay (Ctrl+Enter; Shift+click types all)")));
+    let btn_cancel = Button::builder()
+        .label(gettext("Cancel"))
+        .tooltip_text(gettext("Cancel the countdown or run (Escape)"))
+        .action_name("win.cancel")
+        .visible(false)
+        .build();
+    let entry_delay = SpinButton::with_range(0.0, MAX_DELAY_SECONDS as f64, 1.0);
+    entry_delay.set_numeric(true);
+    entry_delay.set_tooltip_text(Some(&gettext("Delay in seconds before typing starts")));
+
+    let btn_preview = ToggleButton::builder()
+        .icon_name("view-dual-symbolic")
+        .tooltip_text(gettext("Preview the text as it will be typed"))
+        .action_name("win.preview")
+        .build();
+
+    let header = HeaderBar::new();
+    header.pack_start(&btn_start);
+    header.pack_start(&btn_cancel);
+    let resume = ResumeOffer::new();
+    header.pack_start(&resume.button);
+    header.pack_start(&entry_delay);
+    let readiness = Readiness::new();
+    header.pack_start(&readiness.button);
+    let profiles = ProfileSwitcher::new();
+    header.pack_end(&profiles.dropdown);
+    let assistive = AssistiveMode::new();
+    header.pack_end(&assistive.toggle);
+    header.pack_end(&btn_menu);
+    header.pack_end(&btn_compact);
+    header.pack_end(&btn_preview);
+    window.set_titlebar(Some(&header));
+
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let lbl_text = Label::new(Some(&gettext("Input text (typed after delay):")));
+    lbl_text.set_xalign(0.0);
+    vbox.append(&lbl_text);
+
+    let scrolled = ScrolledWindow::builder()
+        .hexpand(true)
+        .vexpand(true)
+        .build();
+    let text_view = TextView::new();
+    scrolled.set_child(Some(&text_view));
+
+    let preview_view = TextView::builder()
+        .editable(false)
+        .cursor_visible(false)
+        .wrap_mode(gtk4::WrapMode::WordChar)
+        .build();
+    let preview_buffer = preview_view.buffer();
+    let preview_scrolled = ScrolledWindow::builder().hexpand(true).vexpand(true).child(&preview_view).build();
+    let preview_header = gtk4::Box::new(Orientation::Horizontal, 6);
+    let lbl_preview = Label::new(Some(&gettext("As typed (⍰ = skipped):")));
+    lbl_preview.set_xalign(0.0);
+    lbl_preview.set_hexpand(true);
+    preview_header.append(&lbl_preview);
+    let chk_preview_auto = CheckButton::with_label(&gettext("Automatic"));
+    chk_preview_auto.set_active(true);
+    preview_header.append(&chk_preview_auto);
+    let btn_refresh_preview = Button::with_label(&gettext("Refresh preview"));
+    preview_header.append(&btn_refresh_preview);
+    let preview_box = gtk4::Box::new(Orientation::Vertical, 6);
+    preview_box.append(&preview_header);
+    preview_box.append(&preview_scrolled);
+    preview_box.set_visible(false);
+
+    let status = StatusArea::build();
+    let tabs = Tabs::new();
+    vbox.append(&tabs.notebook);
+    let find_bar = FindBar::attach(&text_view, &status);
+    vbox.append(&find_bar.bar);
+
+    let text_paned = gtk4::Paned::new(Orientation::Horizontal);
+    text_paned.set_start_child(Some(&scrolled));
+    text_paned.set_end_child(Some(&preview_box));
+    text_paned.set_shrink_end_child(false);
+    vbox.append(&text_paned);
+    let buffer = text_view.buffer();
+    let skipped_marks = SkippedMarks::attach(&text_view);
+
+    let lbl_counts = Label::new(None);
+    lbl_counts.set_xalign(0.0);
+    lbl_counts.add_css_class("dim-label");
+    vbox.append(&lbl_counts);
+
+    let settings = Settings::load();
+    let row = gtk4::Box::new(Orientation::Horizontal, 6);
+    let chk_expand = CheckButton::with_label(&gettext("Expand ${VARIABLES}"));
+    chk_expand.set_tooltip_text(Some(&gettext(
+        "Replace ${USER}, ${HOSTNAME} and other ${ENV_VAR} references with their values before typing. \
+         Write $${NAME} to type ${NAME} literally.",
+    )));
+    chk_expand.set_active(settings.expand_variables);
+    chk_expand.connect_toggled(|chk| save_setting("expand_variables", &chk.is_active().to_string()));
+    row.append(&chk_expand);
+    // Per run: the checkboxes start from the defaults in Preferences.
+    let chk_skip_blank = CheckButton::with_label(&gettext("Skip blank lines"));
+    chk_skip_blank.set_tooltip_text(Some(&gettext("Leave out empty and whitespace-only lines.")));
+    chk_skip_blank.set_active(settings.skip_blank_lines);
+    row.append(&chk_skip_blank);
+    let chk_strip_comments = CheckButton::with_label(&gettext("Strip comments"));
+    chk_strip_comments.set_tooltip_text(Some(&gettext(
+        "Leave out lines that start with a comment prefix from Preferences (# and // unless changed).",
+    )));
+    chk_strip_comments.set_active(settings.strip_comments);
+    row.append(&chk_strip_comments);
+    let chk_smart_punctuation = CheckButton::with_label(&gettext("Fix smart punctuation"));
+    chk_smart_punctuation.set_tooltip_text(Some(&gettext(
+        "Type curly quotes, dashes, ellipses and no-break spaces as plain ASCII instead of leaving them out.",
+    )));
+    chk_smart_punctuation.set_active(settings.fix_smart_punctuation);
+    row.append(&chk_smart_punctuation);
+    let chk_minimize = CheckButton::with_label(&gettext("Minimize this window when countdown starts"));
+    chk_minimize.set_tooltip_text(Some(&gettext("The window comes back when the run finishes or fails.")));
+    chk_minimize.set_active(settings.minimize_on_start);
+    chk_minimize.connect_toggled(|chk| save_setting("minimize_on_start", &chk.is_active().to_string()));
+    row.append(&chk_minimize);
+    let chk_marks = CheckButton::with_label(&gettext("Mark skipped characters"));
+    chk_marks.set_tooltip_text(Some(&gettext("Underline characters that cannot be typed and will be left out.")));
+    chk_marks.set_active(settings.mark_skipped);
+    let marks = skipped_marks.clone();
+    chk_marks.connect_toggled(move |chk| {
+        save_setting("mark_skipped", &chk.is_active().to_string());
+        marks.set_enabled(chk.is_active());
+    });
+    row.append(&chk_marks);
+    let spacer = gtk4::Box::new(Orientation::Horizontal, 0);
+    spacer.set_hexpand(true);
+    row.append(&spacer);
+    let btn_command = Button::with_label(&gettext("Run command…"));
+    btn_command.set_tooltip_text(Some(&gettext("Load a command's output into the text area, or type it directly.")));
+    btn_command.set_visible(settings.allow_commands);
+    row.append(&btn_command);
+    vbox.append(&row);
+
+    let lbl_summary = Label::new(None);
+    lbl_summary.set_xalign(0.0);
+    lbl_summary.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    lbl_summary.set_visible(false);
+    vbox.append(&lbl_summary);
+
+    vbox.append(&status.container);
+    vbox.append(&assistive.btn_confirm);
+
+    let progress = ProgressBar::new();
+    progress.set_show_text(true);
+    progress.set_visible(false);
+    // GTK exposes the fraction; the text adds the counts and the ETA.
+    progress.connect_text_notify(|progress| {
+        progress.update_property(&[accessible::Property::ValueText(&progress.text().unwrap_or_default())]);
+    });
+    vbox.append(&progress);
+
+    text_view.update_relation(&[accessible::Relation::LabelledBy(&[lbl_text.upcast_ref()])]);
+    entry_delay.update_property(&[accessible::Property::Label(&gettext("Delay in seconds"))]);
+    btn_start.update_relation(&[accessible::Relation::DescribedBy(&[status.label.upcast_ref()])]);
+    let log = LogPane::build();
+    vbox.append(&log.expander);
+    let css = gtk4::CssProvider::new();
+    css.load_from_data(
+        "progressbar.failed > trough > progress { background-color: @error_color; }
+         .status-area.warning, .status-area.error { padding: 6px; border-radius: 6px; }
+         .status-area.warning { background-color: alpha(@warning_color, 0.15); }
+         .status-area.error { background-color: alpha(@error_color, 0.15); }
+         .readiness.ready { color: @success_color; }
+         .readiness.not-ready { color: @warning_color; }
+         .assistive-on { color: @accent_color; }
+         .assistive-confirm { padding: 24px; }",
+    );
+    gtk4::style_context_add_provider_for_display(
+        &progress.display(),
+        &css,
+        gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+
+    let paned = gtk4::Paned::new(Orientation::Horizontal);
+    paned.set_end_child(Some(&vbox));
+    paned.set_shrink_start_child(false);
+    window.set_child(Some(&paned));
+
+    // Values older versions saved, like "3s", are read leniently and written
+    // back cleaned up. An unreadable one falls back to the default but stays
+    // in config.toml, where the startup banner points to it, until the next
+    // run saves the delay it used.
+    let saved_delay = load_settings();
+    let delay = parse_delay_lenient(&saved_delay);
+    if let Some(delay) = delay.filter(|delay| saved_delay.trim() != delay.to_string()) {
+        let _ = save_settings(&delay.to_string());
+    }
+    entry_delay.set_value(delay.map_or(3, countdown_seconds) as f64);
+
+    // GTK 4 cannot place windows, so only the size and maximized state are restored.
+    if let Some(geometry) = load_geometry() {
+        window.set_default_size(geometry.width, geometry.height);
+        window.set_maximized(geometry.maximized);
+    }
+
+    let lbl_counts_widget = lbl_counts.clone().upcast();
+    let tabs_widget = tabs.notebook.clone().upcast();
+    let ui = Rc::new(MainWindow {
+        window,
+        buffer,
+        entry_delay,
+        btn_start,
+        btn_cancel,
+        chk_expand,
+        chk_skip_blank,
+        chk_strip_comments,
+        chk_smart_punctuation,
+        chk_minimize,
+        btn_command,
+        status,
+        lbl_counts,
+        progress,
+        run_listeners: RefCell::default(),
+        run_state: RefCell::default(),
+        run_id: Cell::default(),
+        file: RefCell::default(),
+        tabs,
+        profiles,
+        readiness,
+        assistive,
+        resume,
+        applied_settings: RefCell::new(settings),
+        recent_section,
+        minimized_for_run: Cell::default(),
+        idle_inhibit: Cell::default(),
+        paned: paned.clone(),
+        full_layout: vec![
+            lbl_text.upcast(),
+            tabs_widget,
+            text_paned.upcast(),
+            lbl_counts_widget,
+            row.upcast(),
+            log.expander.clone().upcast(),
+        ],
+        lbl_summary,
+        normal_size: Cell::default(),
+        cancel: RefCell::default(),
+        skipped_marks,
+        preview_box,
+        preview_buffer,
+        chk_preview_auto,
+        find_bar,
+        log,
+        secret_run: Cell::default(),
+        selection_run: Cell::default(),
+        resume_run: RefCell::default(),
+        stream_run: RefCell::default(),
+        counter_run: RefCell::default(),
+        hotp_run: RefCell::default(),
+        template_vars: RefCell::default(),
+        overlay: RefCell::default(),
+    });
+
+    paned.set_start_child(Some(&snippets_panel::build(&ui)));
+    file_drop::attach(&ui, &text_view);
+
+    file_actions::install(app, &ui);
+    tabs::install(app, &ui);
+    recent_menu::install(&ui);
+    profile_switcher::install(&ui);
+    assistive_mode::install(&ui);
+    config_watcher::install(&ui);
+    config_transfer::install(&ui);
+    benchmark_dialog::install(&ui);
+    test_target::install(&ui);
+    secret_dialog::install(&ui);
+    window_picker::install(&ui);
+    resume_offer::install(&ui);
+    url_dialog::install(&ui);
+    readiness::install(&ui);
+    shortcuts::install(app, &ui, &text_view);
+    editor_style::install(app, &ui, &text_view);
+    watch_text(&ui);
+
+    let weak = Rc::downgrade(&ui);
+    btn_refresh_preview.connect_clicked(move |_| {
+        if let Some(ui) = weak.upgrade() {
+            ui.refresh_pipeline(true);
+        }
+    });
+    let weak = Rc::downgrade(&ui);
+    ui.chk_smart_punctuation.connect_toggled(move |check| {
+        if let Some(ui) = weak.upgrade() {
+            ui.skipped_marks.set_fixing(check.is_active());
+        }
+    });
+    for check in [&ui.chk_expand, &ui.chk_skip_blank, &ui.chk_strip_comments, &ui.chk_smart_punctuation] {
+        let weak = Rc::downgrade(&ui);
+        check.connect_toggled(move |_| {
+            if let Some(ui) = weak.upgrade() {
+                ui.refresh_pipeline(false);
+            }
+        });
+    }
+
+    // Only the tabs' text is saved: text typed from a command in secret mode or
+    // sent by another process never enters it, and tabs holding a secret
+    // snippet are left out.
+    let weak = Rc::downgrade(&ui);
+    ui.window.connect_close_request(move |window| {
+        let Some(ui) = weak.upgrade() else {
+            return glib::Propagation::Proceed;
+        };
+        // A hidden window keeps its text, so only a real close can lose edits.
+        if !window.hides_on_close() && tabs::show_unsaved(&ui) {
+            // Asks about one tab at a time; closing again finds the next.
+            file_actions::confirm_discard(&ui, |ui| {
+                ui.buffer.set_modified(false);
+                ui.window.close();
+            });
+            return glib::Propagation::Stop;
+        }
+        let (width, height) = ui.normal_size.get().unwrap_or_else(|| window.default_size());
+        save_geometry(&Geometry { width, height, maximized: window.is_maximized() });
+        if Settings::load().restore_draft {
+            save_drafts(&tabs::texts(&ui));
+        }
+        glib::Propagation::Proceed
+    });
+
+    install_window_modes(&ui);
+    install_transforms(&ui);
+
+    let discard = gio::SimpleAction::new("discard-draft", None);
+    discard.connect_activate(|_, _| discard_draft());
+    ui.window.add_action(&discard);
+
+    let statistics = gio::SimpleAction::new("statistics", None);
+    let window = ui.window.clone();
+    statistics.connect_activate(move |_, _| show_statistics(&window));
+    ui.window.add_action(&statistics);
+    let diagnostics = gio::SimpleAction::new("diagnostics", None);
+    let weak = Rc::downgrade(&ui);
+    diagnostics.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            diagnostics_dialog::show(&ui, false);
+        }
+    });
+    ui.window.add_action(&diagnostics);
+    let about = gio::SimpleAction::new("about", None);
+    let window = ui.window.clone();
+    about.connect_activate(move |_, _| show_about(&window));
+    ui.window.add_action(&about);
+
+    let weak = Rc::downgrade(&ui);
+    ui.btn_command.connect_clicked(move |_| {
+        if let Some(ui) = weak.upgrade() {
+            command_dialog::show(&ui);
+        }
+    });
+
+    let weak = Rc::downgrade(&ui);
+    ui.btn_start.connect_clicked(move |button| {
+        let Some(ui) = weak.upgrade() else {
+            return;
+        };
+        // Shift+click types everything even with a selection.
+        let whole = WidgetExt::display(button)
+            .default_seat()
+            .and_then(|seat| seat.keyboard())
+            .is_some_and(|keyboard| keyboard.modifier_state().contains(gdk::ModifierType::SHIFT_MASK));
+        ui.start_from_buffer(whole);
+    });
+
+    ui.show_config_problems(&Settings::load_with_warnings().1);
+
+    ui
+}
+
+/// The stateful "win.compact", "win.preview" and "win.keep-on-top" actions
+/// behind the header bar toggles and menu item, restored from the last
+/// session.
+fn install_window_modes(ui: &Rc<MainWindow>) {
+    let compact = gio::SimpleAction::new_stateful("compact", None, &load_window_flag("compact").to_variant());
+    let weak = Rc::downgrade(ui);
+    compact.connect_change_state(move |action, state| {
+        let (Some(ui), Some(on)) = (weak.upgrade(), state.and_then(|s| s.get::<bool>())) else {
+            return;
+        };
+        action.set_state(&on.to_variant());
+        ui.set_compact(on);
+        save_window_flag("compact", on);
+    });
+    ui.window.add_action(&compact);
+    ui.set_compact(load_window_flag("compact"));
+
+    let preview = gio::SimpleAction::new_stateful("preview", None, &load_window_flag("preview").to_variant());
+    let weak = Rc::downgrade(ui);
+    preview.connect_change_state(move |action, state| {
+        let (Some(ui), Some(on)) = (weak.upgrade(), state.and_then(|s| s.get::<bool>())) else {
+            return;
+        };
+        action.set_state(&on.to_variant());
+        ui.preview_box.set_visible(on);
+        ui.refresh_pipeline(true);
+        save_window_flag("preview", on);
+    });
+    ui.window.add_action(&preview);
+    if load_window_flag("preview") {
+        ui.preview_box.set_visible(true);
+        ui.refresh_pipeline(true);
+    }
+
+    let weak = Rc::downgrade(ui);
+    ui.buffer.connect_changed(move |_| {
+        if let Some(ui) = weak.upgrade().filter(|ui| ui.lbl_summary.is_visible()) {
+            ui.update_summary();
+        }
+    });
+
+    let on_top = gio::SimpleAction::new_stateful("keep-on-top", None, &load_window_flag("keep_on_top").to_variant());
+    on_top.set_enabled(keep_above::supported(&ui.window));
+    let window = ui.window.clone();
+    on_top.connect_change_state(move |action, state| {
+        let Some(on) = state.and_then(|s| s.get::<bool>()) else {
+            return;
+        };
+        action.set_state(&on.to_variant());
+        keep_above::set(&window, on);
+        save_window_flag("keep_on_top", on);
+    });
+    // The window manager drops the state whenever the window is unmapped.
+    let action = on_top.clone();
+    ui.window.connect_map(move |window| {
+        if action.state().and_then(|s| s.get::<bool>()) == Some(true) {
+            keep_above::set(window, true);
+        }
+    });
+    ui.window.add_action(&on_top);
+}
+
+/// One "win.<name>" action per [`Transform`], working on the selection or,
+/// without one, the whole text.
+fn install_transforms(ui: &Rc<MainWindow>) {
+    for transform in Transform::ALL {
+        let action = gio::SimpleAction::new(transform.name(), None);
+        let weak = Rc::downgrade(ui);
+        action.connect_activate(move |_, _| {
+            if let Some(ui) = weak.upgrade() {
+                ui.apply_transform(transform);
+            }
+        });
+        ui.window.add_action(&action);
+    }
+}
+
+/// "1,284 chars · 214 words · 37 lines · ~43s at current speed".
+pub fn counts_text(text: &str, options: &TypingOptions) -> String {
+    let summary = summarize(text);
+    let count = |n: usize, text: String| args(&text, &[&format_count(n)]);
+    let (chars, words, lines, skipped) = (summary.chars, summary.words, summary.lines, summary.skipped);
+    let mut parts = vec![
+        count(chars, ngettext("{0} char", "{0} chars", chars as u64)),
+        count(words, ngettext("{0} word", "{0} words", words as u64)),
+        count(lines, ngettext("{0} line", "{0} lines", lines as u64)),
+        args(&gettext("~{0} at current speed"), &[&format_duration(estimate(text, options))]),
+    ];
+    if skipped > 0 {
+        parts.push(count(skipped, ngettext("{0} will be skipped", "{0} will be skipped", skipped as u64)));
+    }
+    parts.join(" · ")
+}
+
+/// Keeps the counts (and the preview, when it updates automatically)
+/// following the buffer and its selection, recomputing once edits pause so large pastes are
+/// not processed on every change.
+fn watch_text(ui: &Rc<MainWindow>) {
+    let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::default();
+    ui.refresh_pipeline(false);
+    let weak = Rc::downgrade(ui);
+    let schedule = Rc::new(move || {
+        if let Some(source) = pending.borrow_mut().take() {
+            source.remove();
+        }
+        let weak = weak.clone();
+        let pending_clone = pending.clone();
+        *pending.borrow_mut() = Some(timeout_add_local_once(Duration::from_millis(250), move || {
+            pending_clone.borrow_mut().take();
+            if let Some(ui) = weak.upgrade() {
+                ui.refresh_pipeline(false);
+            }
+        }));
+    });
+    let on_change = schedule.clone();
+    ui.buffer.connect_changed(move |_| on_change());
+    // A run from the buffer types the selection, so the counts follow it.
+    ui.buffer.connect_mark_set(move |buffer, _, mark| {
+        if *mark == buffer.get_insert() || *mark == buffer.selection_bound() {
+            schedule();
+        }
+    });
+}
+
+/// Totals over every run, from stats.ini.
+fn show_statistics(parent: &ApplicationWindow) {
+    let totals = load_totals();
+    let dialog = MessageDialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .message_type(MessageType::Info)
+        .buttons(ButtonsType::Close)
+        .text(gettext("Statistics"))
+        .secondary_text(args(
+            &gettext("{0}, {1} typed in total."),
+            &[
+                &args(&ngettext("{0} run", "{0} runs", totals.sessions), &[&format_count(totals.sessions as usize)]),
+                &args(
+                    &ngettext("{0} character", "{0} characters", totals.characters),
+                    &[&format_count(totals.characters as usize)],
+                ),
+            ],
+        ))
+        .build();
+    dialog.connect_response(|dialog, _| dialog.destroy());
+    dialog.present();
+}
+
+/// "System details" for bug reports: which backend a run would use and
+/// what every backend's probe says.
+fn system_details() -> String {
+    let statuses = probe_all();
+    let selected = match load_backend_setting() {
+        Ok(Some(backend)) => format!("{} (configured)", backend.name()),
+        Ok(None) => first_available(&statuses).map_or("none".to_string(), |b| format!("{} (automatic)", b.name())),
+        Err(e) => format!("{} in config.toml", e),
+    };
+    let mut details = format!("Active backend: {}\n", selected);
+    for (backend, status) in &statuses {
+        details.push_str(&format!("{}: {}\n", backend, status));
+    }
+    details
+}
+
+fn show_about(parent: &ApplicationWindow) {
+    let dialog = AboutDialog::builder()
+        .transient_for(parent)
+        .modal(true)
+        .program_name(APP_NAME)
+        .version(env!("CARGO_PKG_VERSION"))
+        .comments(env!("CARGO_PKG_DESCRIPTION"))
+        .website(env!("CARGO_PKG_REPOSITORY"))
+        .license_type(gtk4::License::MitX11)
+        .system_information(system_details())
+        .build();
+    dialog.present();
+}
+
+impl MainWindow {
+    /// Collapses the window to a summary line, the Start button, status and
+    /// progress, or restores the full layout. The buffer is never touched.
+    fn set_compact(&self, compact: bool) {
+        if compact == self.lbl_summary.is_visible() {
+            return;
+        }
+        for widget in &self.full_layout {
+            widget.set_visible(!compact);
+        }
+        if let Some(sidebar) = self.paned.start_child() {
+            sidebar.set_visible(!compact);
+        }
+        self.lbl_summary.set_visible(compact);
+        if compact {
+            self.update_summary();
+            self.normal_size.set(Some(self.window.default_size()));
+            // -1 lets the height shrink to what is left.
+            self.window.set_default_size(COMPACT_WIDTH, -1);
+        } else if let Some((width, height)) = self.normal_size.take() {
+            self.window.set_default_size(width, height);
+        }
+    }
+
+    fn update_summary(&self) {
+        let text = self.buffer.text(&self.buffer.start_iter(), &self.buffer.end_iter(), true);
+        let summary = match text.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => {
+                let chars = text.chars().count();
+                args(&ngettext("{0} ({1} char)", "{0} ({1} chars)", chars as u64), &[&line.trim(), &format_count(chars)])
+            }
+            None => gettext("No text"),
+        };
+        self.lbl_summary.set_text(&summary);
+    }
+
+    /// Whether a countdown or typing run is in progress.
+    pub fn is_busy(&self) -> bool {
+        self.run_state.borrow().is_busy()
+    }
+
+    /// Stops the current countdown or run, whoever started it. The run ends
+    /// with a `Failed` event carrying [`TypingError::Cancelled`].
+    pub fn cancel_run(&self) {
+        if self.is_busy() {
+            self.cancel.borrow().store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether `run` is the latest run, so its callbacks may still act.
+    fn is_current_run(&self, run: RunId) -> bool {
+        self.run_id.get() == run
+    }
+
+    /// Keeps the screen from blanking and the system from suspending until
+    /// [`Self::allow_idle`]. Some compositors ignore the request; the run
+    /// then still pauses at the lock screen rather than type into it.
+    fn inhibit_idle(&self) {
+        if self.idle_inhibit.get() != 0 {
+            return;
+        }
+        let Some(app) = self.window.application() else {
+            return;
+        };
+        let flags = ApplicationInhibitFlags::SUSPEND | ApplicationInhibitFlags::IDLE;
+        let cookie = app.inhibit(Some(&self.window), flags, Some(&gettext("Typing text into another window")));
+        if cookie == 0 {
+            self.log.add("The desktop did not agree to keep the screen on; typing pauses if the session locks.");
+        }
+        self.idle_inhibit.set(cookie);
+    }
+
+    fn allow_idle(&self) {
+        let cookie = self.idle_inhibit.replace(0);
+        if let Some(app) = self.window.application().filter(|_| cookie != 0) {
+            app.uninhibit(cookie);
+        }
+    }
+
+    /// Minimizes the window until the run ends, unless it is hidden
+    /// (background mode).
+    pub fn minimize_for_run(&self) {
+        if self.window.is_visible() {
+            self.window.minimize();
+            self.minimized_for_run.set(true);
+        }
+    }
+
+    /// Switches Start and the Cancel action (Escape) between idle and running.
+    fn set_running(&self, running: bool) {
+        // Keep keyboard focus on whichever of the two buttons is usable, so
+        // Cancel is one Tab stop away and focus is not lost when it hides.
+        let move_focus = if running { self.btn_start.has_focus() } else { self.btn_cancel.has_focus() };
+        self.btn_start.set_sensitive(!running);
+        self.btn_cancel.set_visible(running);
+        if !running {
+            self.overlay.replace(None);
+        }
+        if move_focus {
+            if running {
+                self.btn_cancel.grab_focus();
+            } else {
+                self.btn_start.grab_focus();
+            }
+        }
+        if let Some(action) = self.window.lookup_action("cancel").and_then(|a| a.downcast::<gio::SimpleAction>().ok()) {
+            action.set_enabled(running);
+        }
+    }
+
+    /// The Start button: validates the buffer and delay, then starts a run
+    /// with the selection, or everything when there is none or `whole` is
+    /// set. The text is taken now, so later edits do not change the run.
+    pub fn start_from_buffer(self: &Rc<Self>, whole: bool) {
+        let delay_sec = self.delay_seconds();
+
+        match self.pipeline_text(whole) {
+            Ok((stamped, selection)) => {
+                // Saved once the run is accepted; a `--set delay_seconds` is
+                // for this run only.
+                if !has_override("delay_seconds") {
+                    if let Err(e) = save_settings(&delay_sec.to_string()) {
+                        return self.set_error(&format!("{}.", e));
+                    }
+                }
+                self.selection_run.set(selection);
+                *self.counter_run.borrow_mut() = stamped.counters;
+                self.start_templated(stamped.text, delay_sec, !self.tabs.is_secret(), None)
+            }
+            Err(e) => self.set_error(&format!("{}.", e)),
+        }
+    }
+
+    /// Like [`Self::start_from_buffer`], bringing the window titled `title`
+    /// to the front before the first key.
+    pub fn start_from_buffer_into(self: &Rc<Self>, title: String) {
+        let snippet = SnippetSettings { focus_window: Some(title), ..SnippetSettings::default() };
+        let delay_sec = self.delay_seconds();
+        match self.pipeline_text(false) {
+            Ok((stamped, selection)) => {
+                if !has_override("delay_seconds") {
+                    if let Err(e) = save_settings(&delay_sec.to_string()) {
+                        return self.set_error(&format!("{}.", e));
+                    }
+                }
+                self.selection_run.set(selection);
+                *self.counter_run.borrow_mut() = stamped.counters;
+                self.start_templated(stamped.text, delay_sec, !self.tabs.is_secret(), Some(snippet))
+            }
+            Err(e) => self.set_error(&format!("{}.", e)),
+        }
+    }
+
+    /// Starts a run once the text's `{{placeholders}}` and `{{if}}` blocks
+    /// are filled in, when templates are turned on or `--var` gave values;
+    /// with `remember` the values are kept for the next time. A snippet's settings override the global ones.
+    pub fn start_templated(
+        self: &Rc<Self>,
+        text: String,
+        delay_sec: u64,
+        remember: bool,
+        snippet: Option<SnippetSettings>,
+    ) {
+        // Taken now so it cannot apply to a different run if this one never starts.
+        let selection = self.selection_run.take();
+        let counters = self.counter_run.take();
+        let given = self.template_vars.take();
+        if !Settings::load().templates && given.is_empty() {
+            self.secret_run.set(!remember);
+            self.selection_run.set(selection);
+            *self.counter_run.borrow_mut() = counters;
+            return self.start_configured(text, delay_sec, snippet.as_ref());
+        }
+        if self.is_busy() {
+            return self.set_warning(&gettext("A typing run is already in progress."));
+        }
+        let variables = match variables(&text) {
+            Ok(variables) => variables,
+            Err(e) => return self.set_error(&format!("{}.", e)),
+        };
+        // Without variables this still unescapes \{{; `--var` values that
+        // are all the text needs skip the dialog.
+        if variables.is_empty() || !given.is_empty() {
+            if let Ok(text) = fill(&text, &given) {
+                self.secret_run.set(!remember);
+                self.selection_run.set(selection);
+                *self.counter_run.borrow_mut() = counters;
+                return self.start_configured(text, delay_sec, snippet.as_ref());
+            }
+        }
+        placeholder_dialog::show(self, text, variables, given, remember, move |ui, text| {
+            ui.secret_run.set(!remember);
+            ui.selection_run.set(selection);
+            *ui.counter_run.borrow_mut() = counters.clone();
+            ui.start_configured(text, delay_sec, snippet.as_ref());
+        });
+    }
+
+    /// The text as a run from the buffer would receive it, and whether it is
+    /// only the selection (see [`Self::start_from_buffer`]): its dates,
+    /// times and counters filled in when templates are on, expanded, then
+    /// line-filtered. The expanded text only lives in the run and the
+    /// preview: it may contain secrets from the environment, so it never
+    /// goes back into the buffer.
+    fn pipeline_text(&self, whole: bool) -> anyhow::Result<(Stamped, bool)> {
+        let buffer = &self.buffer;
+        let selection = buffer.selection_bounds().filter(|_| !whole);
+        let (start, end) = selection.unwrap_or_else(|| buffer.bounds());
+        let Stamped { mut text, counters } = stamp(buffer.text(&start, &end, true).to_string())?;
+        if self.chk_expand.is_active() {
+            text = expand_variables(&text, env_lookup, Settings::load().strict_variables)?;
+        }
+        if self.chk_smart_punctuation.is_active() {
+            text = smart_punctuation::fix(&text);
+        }
+        let filter = LineFilter {
+            skip_blank_lines: self.chk_skip_blank.is_active(),
+            comment_prefixes: if self.chk_strip_comments.is_active() {
+                parse_prefixes(&load_comment_prefixes_setting())
+            } else {
+                Vec::new()
+            },
+        };
+        Ok((Stamped { text: filter.apply(&text), counters }, selection.is_some()))
+    }
+
+    /// Recomputes the counts from the text as it will be typed, so they agree
+    /// with the preview, which is regenerated too if it is shown and either
+    /// updates automatically or `force` is set.
+    fn refresh_pipeline(&self, force: bool) {
+        let text = self.pipeline_text(false).map(|(stamped, selection)| (stamped.text, selection));
+        match &text {
+            Ok((text, selection)) => {
+                let counts = counts_text(text, &load_typing_options().unwrap_or_default());
+                if *selection {
+                    self.lbl_counts.set_text(&args(&gettext("Selection: {0}"), &[&counts]));
+                } else {
+                    self.lbl_counts.set_text(&counts);
+                }
+            }
+            Err(e) => self.lbl_counts.set_text(&format!("{}.", e)),
+        }
+        if self.preview_box.is_visible() && (force || self.chk_preview_auto.is_active()) {
+            match &text {
+                Ok((text, _)) => self.preview_buffer.set_text(&mark_skipped(text)),
+                Err(e) => self.preview_buffer.set_text(&format!("{}.", e)),
+            }
+        }
+    }
+
+    /// Types `text` after the delay in the window, bypassing the buffer, as
+    /// for a secret command's output: placeholder values are not kept.
+    pub fn start_text(self: &Rc<Self>, text: String) {
+        self.start_templated(text, self.delay_seconds(), false, None);
+    }
+
+    /// Types a keyring item's secret after the delay in the window, as a
+    /// secret run: the log gets the item's label and the secret's length.
+    pub fn type_secret(self: &Rc<Self>, label: &str, secret: Zeroizing<String>) {
+        if self.is_busy() {
+            return self.set_warning(&gettext("A typing run is already in progress."));
+        }
+        let options = match load_typing_options() {
+            Ok(options) => options,
+            Err(e) => return self.set_error(&format!("{} in config.toml.", e)),
+        };
+        let chars = secret.chars().count();
+        self.secret_run.set(true);
+        self.start_run(secret, self.delay_seconds(), options);
+        let typing = ngettext("Typing keyring item '{0}' ({1} char).", "Typing keyring item '{0}' ({1} chars).", chars as u64);
+        self.log.add(&args(&typing, &[&label, &format_count(chars)]));
+    }
+
+    /// Types a snippet with its own settings; `default_delay` is used when
+    /// it has no delay of its own.
+    pub fn start_snippet(self: &Rc<Self>, name: &str, default_delay: u64) {
+        if self.is_busy() {
+            return self.set_warning(&gettext("A typing run is already in progress."));
+        }
+        let text = match snippet_text_for_typing(name) {
+            Ok(text) => text,
+            Err(e) => return self.set_error(&format!("{:#}.", e)),
+        };
+        let settings = load_snippet_settings(name);
+        remember_snippet_used(name);
+        let delay_sec = settings.delay_seconds.unwrap_or(default_delay);
+        if settings.auto_type {
+            return self.start_auto_type(name, &text, delay_sec, settings);
+        }
+        let stamped = match stamp(text) {
+            Ok(stamped) => stamped,
+            Err(e) => return self.set_error(&format!("{}.", e)),
+        };
+        *self.counter_run.borrow_mut() = stamped.counters;
+        // A snippet from the keyring or an encrypted one is a secret run, so
+        // it is not kept in the recent texts or for resuming.
+        let secret = is_secure(name) || is_encrypted(name);
+        self.start_templated(stamped.text, delay_sec, !secret, Some(settings));
+    }
+
+    /// Types an auto-type snippet once its login fields have values: the
+    /// login saved with it, and what the user enters for the rest.
+    fn start_auto_type(self: &Rc<Self>, name: &str, text: &str, delay_sec: u64, settings: SnippetSettings) {
+        let tokens = match parse_sequence(text) {
+            Ok(tokens) => tokens,
+            Err(e) => return self.set_error(&format!("{}.", e)),
+        };
+        let saved = match load_credentials(name) {
+            Ok(credentials) => credentials,
+            Err(e) => return self.set_error(&format!("{:#}.", e)),
+        };
+        let missing: Vec<_> = fields(&tokens).into_iter().filter(|field| saved.get(*field).is_none()).collect();
+        if missing.is_empty() {
+            return self.fetch_pass_and_type(tokens, saved, delay_sec, settings);
+        }
+        auto_type_dialog::show(self, name, missing, move |ui, entered| {
+            let credentials = Credentials {
+                username: saved.username.or(entered.username),
+                password: saved.password.or(entered.password),
+                totp_seed: saved.totp_seed,
+                pass: Vec::new(),
+                hotp: Vec::new(),
+            };
+            ui.fetch_pass_and_type(tokens, credentials, delay_sec, settings);
+        });
+    }
+
+    /// Loads the sequence's HOTP tokens from the keyring and reads its
+    /// `{PASS:…}` entries on a worker thread, since gpg may first ask for a
+    /// passphrase, then types it. A token or entry that cannot be read ends
+    /// it before the first key.
+    fn fetch_pass_and_type(
+        self: &Rc<Self>,
+        tokens: Vec<Token>,
+        mut credentials: Credentials,
+        delay_sec: u64,
+        settings: SnippetSettings,
+    ) {
+        for label in hotp_labels(&tokens) {
+            match hotp::load_token(&label) {
+                Ok(token) => credentials.hotp.push((label, token)),
+                Err(e) => return self.set_error(&format!("{:#}.", e)),
+            }
+        }
+        let paths = pass_paths(&tokens);
+        if paths.is_empty() {
+            return self.type_auto_type(&tokens, &credentials, delay_sec, &settings);
+        }
+        if self.is_busy() {
+            return self.set_warning(&gettext("A typing run is already in progress."));
+        }
+        self.set_status(&gettext("Reading the password store…"));
+        let command = load_pass_command();
+        let (sender, receiver) = flume::bounded(1);
+        thread::spawn(move || {
+            let _ = sender.send(fetch_pass(&command, &paths));
+        });
+        let ui = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            let fetched = receiver
+                .recv_async()
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Reading the password store stopped unexpectedly")));
+            match fetched {
+                Ok(pass) => {
+                    credentials.pass = pass;
+                    ui.type_auto_type(&tokens, &credentials, delay_sec, &settings);
+                }
+                Err(e) => ui.set_error(&format!("{:#}.", e)),
+            }
+        });
+    }
+
+    /// Fills the sequence in and starts the run, as a secret one: the log
+    /// gets only the fields' lengths, and the text is not remembered.
+    fn type_auto_type(
+        self: &Rc<Self>,
+        tokens: &[Token],
+        credentials: &Credentials,
+        delay_sec: u64,
+        snippet: &SnippetSettings,
+    ) {
+        if self.is_busy() {
+            return self.set_warning(&gettext("A typing run is already in progress."));
+        }
+        let mut options = match load_typing_options() {
+            Ok(options) => options,
+            Err(e) => return self.set_error(&format!("{} in config.toml.", e)),
+        };
+        let resolved = match resolve(tokens, credentials) {
+            Ok(resolved) => resolved,
+            Err(e) => return self.set_error(&format!("{}.", e)),
+        };
+        snippet.apply(&mut options);
+        options.pauses = resolved.pauses;
+        options.codes = resolved.codes;
+        self.secret_run.set(true);
+        *self.hotp_run.borrow_mut() = resolved.hotp.clone();
+        self.start_run(resolved.text, delay_sec, options);
+        for field in fields(tokens) {
+            self.log.add(&format!("Auto-type {}.", credentials.describe(field)));
+        }
+        for path in pass_paths(tokens) {
+            self.log.add(&format!("Auto-type {}.", credentials.describe_pass(&path)));
+        }
+        if tokens.contains(&Token::Totp) {
+            self.log.add("Auto-type placeholder TOTP, worked out when typing reaches it.");
+        }
+        for (label, next) in &resolved.hotp {
+            let first = credentials.get_hotp(label).map_or(0, |token| token.counter);
+            let used = match next - first {
+                1 => format!("counter {}", first),
+                _ => format!("counters {}–{}", first, next - 1),
+            };
+            self.log.add(&format!("Auto-type placeholder HOTP:{}, code for {}.", label, used));
+        }
+    }
+
+    /// Lists the values in config.toml that are not used as written in a
+    /// banner that stays until it is closed, or removes the banner once
+    /// there are none.
+    pub fn show_config_problems(&self, problems: &[String]) {
+        if problems.is_empty() {
+            self.status.clear_banner();
+            return;
+        }
+        let path = config_path().map(|path| path.display().to_string()).unwrap_or_else(|| "config.toml".to_string());
+        let count = problems.len() as u64;
+        let text = ngettext(
+            "{0} has a problem; see Details. Run pasteclipboard --check-config after fixing it.",
+            "{0} has {1} problems; see Details. Run pasteclipboard --check-config after fixing them.",
+            count,
+        );
+        self.status.banner(&args(&text, &[&path, &count]), problems.join("\n"));
+    }
+
+    /// Re-reads the settings the window shows after the preferences dialog
+    /// or a switch of profile changed them.
+    pub fn settings_changed(&self) {
+        let settings = Settings::load();
+        *self.applied_settings.borrow_mut() = settings.clone();
+        self.entry_delay.set_value(countdown_seconds(settings.delay_seconds) as f64);
+        self.chk_expand.set_active(settings.expand_variables);
+        self.chk_skip_blank.set_active(settings.skip_blank_lines);
+        self.chk_strip_comments.set_active(settings.strip_comments);
+        self.chk_smart_punctuation.set_active(settings.fix_smart_punctuation);
+        self.btn_command.set_visible(settings.allow_commands);
+        self.refresh_pipeline(false);
+        self.skipped_marks.rescan();
+        recent_menu::refresh(self);
+        profile_switcher::refresh(self);
+        self.assistive.refresh();
+        self.readiness.check();
+    }
+
+    /// Replaces the whole text as one undoable step, for text loaded from a
+    /// file, snippet, command or another process. The tab's text is no
+    /// longer secret (see [`Tabs::set_secret`]); replacing a secret cannot
+    /// be undone, so undo cannot bring it back unmarked.
+    pub fn replace_text(&self, text: &str) {
+        let buffer = &self.buffer;
+        if self.tabs.is_secret() {
+            self.tabs.set_secret(false);
+            buffer.begin_irreversible_action();
+            buffer.set_text(text);
+            buffer.end_irreversible_action();
+            return;
+        }
+        buffer.begin_user_action();
+        buffer.delete(&mut buffer.start_iter(), &mut buffer.end_iter());
+        buffer.insert(&mut buffer.start_iter(), text);
+        buffer.end_user_action();
+    }
+
+    /// Transforms the selection, or the whole text, as one undoable step; the
+    /// result stays selected.
+    fn apply_transform(&self, transform: Transform) {
+        let buffer = &self.buffer;
+        let (mut start, mut end) = buffer.selection_bounds().unwrap_or_else(|| buffer.bounds());
+        let text = buffer.text(&start, &end, true);
+        let transformed = transform.apply(&text);
+        if transformed == text.as_str() {
+            self.set_status(&gettext("Nothing to change."));
+            return;
+        }
+        let offset = start.offset();
+        buffer.begin_user_action();
+        buffer.delete(&mut start, &mut end);
+        buffer.insert(&mut start, &transformed);
+        buffer.end_user_action();
+        buffer.select_range(&buffer.iter_at_offset(offset), &start);
+    }
+
+    /// A routine message in the status area.
+    pub fn set_status(&self, text: &str) {
+        self.status.info(text);
+    }
+
+    /// A warning that stays until it is closed or the next run starts.
+    pub fn set_warning(&self, text: &str) {
+        self.status.warning(text);
+    }
+
+    /// An error that stays until it is closed or the next run starts.
+    pub fn set_error(&self, text: &str) {
+        self.status.error(text, None);
+    }
+
+    /// The delay field's value. The spin button only holds whole numbers in
+    /// range, so there is nothing left to validate.
+    pub fn delay_seconds(&self) -> u64 {
+        // Commits text typed into the spin button that has not been applied yet.
+        self.entry_delay.update();
+        self.entry_delay.value_as_int().max(0) as u64
+    }
+
+    /// Types the lines `lines` brings as they come in, after the delay in
+    /// the window, for `--stream`. Returns whether the run started.
+    pub fn start_stream(self: &Rc<Self>, lines: flume::Receiver<String>) -> bool {
+        if self.is_busy() {
+            self.set_warning(&gettext("A typing run is already in progress."));
+            return false;
+        }
+        let options = match load_typing_options() {
+            Ok(options) => options,
+            Err(e) => {
+                self.set_error(&format!("{} in config.toml.", e));
+                return false;
+            }
+        };
+        if options.remote.is_some() {
+            self.set_error(&gettext("Streams are only typed on this machine; turn off remote typing for --stream."));
+            return false;
+        }
+        self.stream_run.replace(Some(lines));
+        self.start_run(Zeroizing::new(String::new()), self.delay_seconds(), options);
+        self.stream_run.take();
+        self.is_busy()
+    }
+
+    /// Starts a run with the options from config.toml, and `snippet`'s on top.
+    pub fn start_configured(self: &Rc<Self>, text: String, delay_sec: u64, snippet: Option<&SnippetSettings>) {
+        match load_typing_options() {
+            Ok(mut options) => {
+                if let Some(snippet) = snippet {
+                    snippet.apply(&mut options);
+                }
+                self.start_scanned(text.into(), delay_sec, options)
+            }
+            Err(e) => {
+                self.secret_run.set(false);
+                self.counter_run.take();
+                self.set_error(&format!("{} in config.toml.", e));
+            }
+        }
+    }
+
+    /// Starts the run once the user has confirmed it if `secret_scan` finds
+    /// what looks like a secret in `text`, unless it is a secret run with a
+    /// target window already. The confirmation can make it either.
+    fn start_scanned(self: &Rc<Self>, text: Zeroizing<String>, delay_sec: u64, options: TypingOptions) {
+        let secret = self.secret_run.get();
+        if !Settings::load().secret_scan || (secret && options.target_window.is_some()) {
+            return self.start_run(text, delay_sec, options);
+        }
+        let findings = scan(&text, &load_secret_patterns());
+        if findings.is_empty() {
+            return self.start_run(text, delay_sec, options);
+        }
+        if self.is_busy() {
+            self.secret_run.set(false);
+            self.counter_run.take();
+            return self.set_warning(&gettext("A typing run is already in progress."));
+        }
+        // Taken now so they cannot apply to a different run if this one never starts.
+        self.secret_run.set(false);
+        let selection = self.selection_run.take();
+        let counters = self.counter_run.take();
+        let asking = ngettext(
+            "The text looks like it holds a secret ({0} finding); asking before typing it.",
+            "The text looks like it holds a secret ({0} findings); asking before typing it.",
+            findings.len() as u64,
+        );
+        self.log.add(&args(&asking, &[&format_count(findings.len())]));
+        let target_window = options.target_window.clone();
+        secret_scan_dialog::show(self, &findings, secret, target_window, move |ui, choice| {
+            let mut options = options.clone();
+            options.target_window = choice.target_window;
+            ui.secret_run.set(secret || choice.secret);
+            ui.selection_run.set(selection);
+            *ui.counter_run.borrow_mut() = counters.clone();
+            ui.start_run(text.clone(), delay_sec, options);
+        });
+    }
+
+    /// Types the rest of a run that stopped part way, after the delay in the
+    /// window.
+    pub fn start_resumed(self: &Rc<Self>, interrupted: Interrupted) {
+        if self.is_busy() {
+            return self.set_warning(&gettext("A typing run is already in progress."));
+        }
+        let mut options = rest_options(&interrupted.options, interrupted.at);
+        if options.readback.take().is_some() {
+            self.log.add("The rest of the run is not read back: select-all would take in what was typed before.");
+        }
+        let text = Zeroizing::new(rest(&interrupted.text, interrupted.at).to_string());
+        self.log.add(&format!(
+            "Resuming the stopped run from character {}.",
+            format_count(typeable_before(&interrupted.text, interrupted.at, &interrupted.options.keymap) + 1)
+        ));
+        *self.counter_run.borrow_mut() = interrupted.counters.clone();
+        self.resume_run.replace(Some(interrupted));
+        self.start_run(text, self.delay_seconds(), options);
+    }
+
+    /// Offers to type the rest of `run`, whose part of the text stopped
+    /// after `typed` characters, unless nothing of it was typed or nothing
+    /// is left.
+    fn offer_resume(&self, run: &Interrupted, typed: usize) {
+        let Some(next) = next_position(rest(&run.text, run.at), typed, &run.options.keymap) else {
+            return;
+        };
+        if run.at + next == 0 {
+            return;
+        }
+        let line_start = Settings::load().resume_from_line_start;
+        let at = snap(&run.text, run.at + next, &run.options, line_start);
+        self.log.add(&format!(
+            "The rest can be typed with \"Resume\", from character {}{}.",
+            format_count(typeable_before(&run.text, at, &run.options.keymap) + 1),
+            if line_start { ", the start of the line it stopped in" } else { "" }
+        ));
+        self.resume.offer(Interrupted { at, ..run.clone() });
+    }
+
+    /// Runs the countdown and then types `text` on a worker thread. Does
+    /// nothing while another run is in progress; callers validate
+    /// `delay_sec` first. The run shares the one copy of the text, which is
+    /// wiped and freed when the run ends.
+    pub fn start_run(self: &Rc<Self>, text: Zeroizing<String>, delay_sec: u64, mut options: TypingOptions) {
+        let text = Arc::new(text);
+        let secret = self.secret_run.replace(false);
+        let selection = self.selection_run.replace(false).then(|| text.chars().count());
+        let counters = self.counter_run.take();
+        let hotp_counters = self.hotp_run.take();
+        let stream = self.stream_run.take();
+        let streaming = stream.is_some();
+        let resumed = self.resume_run.take();
+        if !self.run_state.borrow_mut().start(delay_sec) {
+            return;
+        }
+        self.resume.withdraw();
+        let settings = Settings::load();
+        // Assistive mode holds whatever pause a profile, snippet or --set
+        // asks for to its floor.
+        if settings.assistive_mode {
+            let floor = Duration::from_millis(u64::from(ASSISTIVE_MIN_CHAR_DELAY_MS));
+            options.char_delay = options.char_delay.max(floor);
+            options.assistive = true;
+        }
+        options.confirm_enter = settings.confirm_enter;
+        // The windows and the clipboard are this machine's, and a stream
+        // has no text to compare the clipboard with.
+        let elsewhere = if options.remote.is_some() {
+            Some("it is typed on another machine")
+        } else if options.backend == Some(Backend::Libvirt) {
+            Some("it is typed into a virtual machine")
+        } else {
+            None
+        };
+        if let Some(reason) = elsewhere {
+            if let Some(title) = options.focus_window.take() {
+                self.log.add(&format!("The window '{}' is not brought to the front: {}.", title, reason));
+            }
+        }
+        if options.readback.is_some() {
+            if let Some(reason) = elsewhere.or(streaming.then_some("standard input is typed as it comes in")) {
+                self.log.add(&format!("The run is not read back: {}.", reason));
+                options.readback = None;
+            }
+        }
+        let readback = options.readback.as_ref().map(|readback| {
+            (ReadbackCheck::start(self), format!(", read back with {} and {}", readback.select_all, readback.copy))
+        });
+        let target = options.target_window.as_ref().map(|p| format!(", target window '{}'", p)).unwrap_or_default();
+        let focus = options.focus_window.as_ref().map(|t| format!(", bringing '{}' to the front", t)).unwrap_or_default();
+        let assistive = match (options.assistive, options.confirm_enter) {
+            (true, true) => ", assistive mode, confirming each Enter",
+            (true, false) => ", assistive mode",
+            (false, true) => ", confirming each Enter",
+            (false, false) => "",
+        };
+        let profile = active_profile().map(|name| format!(", profile '{}'", name)).unwrap_or_default();
+        let backend = match &options.remote {
+            Some(address) => format!("remote {}", address),
+            None => options.backend.map_or("automatic", |backend| backend.name()).to_string(),
+        };
+        self.log.add(&format!(
+            "Run started: {}{}, backend {}, delay {}s, {} ms/char, line breaks with {}{}{}{}{}{}.",
+            if selection.is_some() { "selection " } else { "" },
+            if streaming { "standard input".to_string() } else { describe_text(&text, secret) },
+            backend,
+            delay_sec,
+            options.char_delay.as_millis(),
+            options.enter_key.label(),
+            profile,
+            target,
+            focus,
+            assistive,
+            readback.as_ref().map(|(_, chords)| chords.as_str()).unwrap_or_default()
+        ));
+        let mut readback = readback.map(|(check, _)| (check, text.clone()));
+        // What stopping part way leaves to resume: the whole text, and where
+        // this run's part of it begins.
+        let resumable = (!secret && !streaming).then(|| {
+            resumed.unwrap_or_else(|| Interrupted {
+                text: text.clone(),
+                at: 0,
+                options: options.clone(),
+                counters: counters.clone(),
+            })
+        });
+        let run = self.run_id.get().next();
+        self.run_id.set(run);
+        // Each run gets its own flag, so cancelling one cannot stop the
+        // next one's worker.
+        let cancel = Arc::new(AtomicBool::new(false));
+        *self.cancel.borrow_mut() = cancel.clone();
+        self.set_running(true);
+        self.assistive.start(options.confirmations.clone());
+        if settings.inhibit_idle_countdown {
+            self.inhibit_idle();
+        }
+        self.progress.set_visible(false);
+        self.progress.remove_css_class("failed");
+        self.status.clear();
+        self.status.info(&countdown_text(delay_sec, selection));
+        file_actions::update_title(self);
+        if delay_sec > 0 {
+            self.overlay.replace(CountdownOverlay::show(self, delay_sec));
+        }
+        // A window that is hidden (background mode) stays hidden.
+        if self.chk_minimize.is_active() || options.assistive {
+            self.minimize_for_run();
+        }
+
+        let remaining_seconds = Rc::new(RefCell::new(delay_sec));
+        // Sent once, when the countdown reaches the threshold; a shorter delay
+        // gets it straight away.
+        let notify_before = load_notify_before_setting();
+        if delay_sec > 0 && delay_sec <= notify_before {
+            notify_countdown(self, delay_sec);
+        }
+
+        if delay_sec > 0 {
+            let status = self.status.clone();
+            // Every status change is read out; this keeps it to one in five.
+            let quiet_countdown = settings.quiet_countdown;
+            let remaining_seconds_clone = remaining_seconds.clone();
+            let weak = Rc::downgrade(self);
+            let cancel = cancel.clone();
+            timeout_add_local(Duration::from_secs(1), move || {
+                let Some(ui) = weak.upgrade().filter(|ui| ui.is_current_run(run)) else {
+                    return ControlFlow::Break;
+                };
+                // A failed keyboard ends the countdown too, as does typing
+                // that began a moment before the last tick.
+                if cancel.load(Ordering::Relaxed) || !matches!(*ui.run_state.borrow(), RunState::CountingDown { .. }) {
+                    return ControlFlow::Break;
+                }
+                let mut current = remaining_seconds_clone.borrow_mut();
+                *current -= 1;
+                ui.run_state.borrow_mut().tick();
+                file_actions::update_title(&ui);
+                if *current > 0 && *current == notify_before {
+                    notify_countdown(&ui, *current);
+                }
+                if *current > 0 {
+                    if let Some(overlay) = ui.overlay.borrow().as_ref() {
+                        overlay.set_remaining(*current);
+                    }
+                    if !quiet_countdown || current.is_multiple_of(5) {
+                        status.info(&countdown_text(*current, selection));
+                    }
+                    ControlFlow::Continue
+                } else {
+                    // Typing begins; the overlay has done its job.
+                    ui.overlay.replace(None);
+                    ControlFlow::Break
+                }
+            });
+        }
+
+        let (sender, receiver) = flume::unbounded::<TypingEvent>();
+        let weak: Weak<Self> = Rc::downgrade(self);
+        let planned = estimate(&text, &options);
+        let recent_text = (!secret && !streaming).then(|| text.clone());
+        // `total` stays unknown until the worker has connected to the
+        // backend.
+        let mut total: Option<usize> = None;
+        let mut started_at = Instant::now();
+        let mut last_typed = 0;
+        // Each event is handled as soon as it is sent. The receiver goes
+        // with the run's last event, so nothing later reaches the window.
+        glib::MainContext::default().spawn_local(async move {
+            loop {
+                let event = receiver.recv_async().await.unwrap_or_else(|_| TypingEvent::Failed {
+                    error: TypingError::Backend("The typing worker stopped unexpectedly".to_string()),
+                    stats: RunStats::default(),
+                });
+                let Some(ui) = weak.upgrade().filter(|ui| ui.is_current_run(run)) else {
+                    return;
+                };
+
+                match &event {
+                    TypingEvent::Started { total_chars } => {
+                        ui.overlay.replace(None);
+                        ui.inhibit_idle();
+                        ui.status.info(&match selection {
+                            Some(chars) => args(&gettext("Typing selection, {0} chars..."), &[&format_count(chars)]),
+                            None => gettext("Typing now..."),
+                        });
+                        total = Some(*total_chars);
+                        started_at = Instant::now();
+                        ui.progress.set_fraction(0.0);
+                        ui.progress.set_visible(true);
+                        if streaming {
+                            ui.progress.set_text(Some(&stream_bar_text(0, 0)));
+                            ui.log.add("Typing standard input as it comes in.");
+                        } else {
+                            let eta = remaining_time(0, *total_chars, Duration::ZERO, planned);
+                            ui.progress.set_text(Some(&progress_bar_text(0, *total_chars, eta)));
+                            ui.log.add(&format!("Typing {} characters.", format_count(*total_chars)));
+                        }
+                    }
+                    // A stream's total is what came in so far, so the bar
+                    // shows how far typing is behind.
+                    TypingEvent::Progress { typed, total: received } if streaming => {
+                        ui.progress.set_fraction(*typed as f64 / (*received).max(1) as f64);
+                        ui.progress.set_text(Some(&stream_bar_text(*typed, *received)));
+                        total = Some(*received);
+                    }
+                    TypingEvent::Progress { typed, total } => {
+                        let eta = remaining_time(*typed, *total, started_at.elapsed(), planned);
+                        ui.progress.set_fraction(*typed as f64 / (*total).max(1) as f64);
+                        ui.progress.set_text(Some(&progress_bar_text(*typed, *total, eta)));
+                        if let Some(percent) = milestone(last_typed, *typed, *total) {
+                            ui.log.add(&format!("{}% typed ({}).", percent, progress_text(*typed, *total)));
+                        }
+                        last_typed = *typed;
+                    }
+                    TypingEvent::Skipped { count } => {
+                        let skipped = args(
+                            &ngettext(
+                                "{0} unsupported character will be skipped.",
+                                "{0} unsupported characters will be skipped.",
+                                *count as u64,
+                            ),
+                            &[&format_count(*count)],
+                        );
+                        ui.set_warning(&skipped);
+                        ui.log.add(&skipped);
+                    }
+                    // The worker logged it, which puts it in the activity log.
+                    TypingEvent::Notice { .. } => {}
+                    TypingEvent::AwaitingConfirmation { wait } => {
+                        ui.log.add(&format!("{}.", wait));
+                        ui.assistive.ask(&ui, wait, secret);
+                    }
+                    TypingEvent::Finished { stats } => {
+                        // The outcome counts the skipped characters.
+                        ui.status.clear_warnings();
+                        ui.set_status(&args(&gettext("✓ Done typing: {0}."), &[&stats_text(stats)]));
+                        notify_run(&ui, &gettext("Done typing"), &format!("{}.", stats_text(stats)));
+                        sounds::play_if_enabled(Sound::Success, &ui.applied_settings.borrow());
+                        ui.progress.set_visible(false);
+                        ui.log.add(&format!("Done: {}.", stats_text(stats)));
+                        record_run(stats);
+                        commit_counters(&counters);
+                        if let Err(e) = hotp::commit_counters(&hotp_counters) {
+                            ui.log.add(&format!(
+                                "Could not save the HOTP counters: {:#}. Set the next counter in the snippet's properties \
+                                 before the next run, or the server may take the same code again.",
+                                e
+                            ));
+                        }
+                        if let Some(text) = &recent_text {
+                            remember_text(text);
+                            recent_menu::refresh(&ui);
+                        }
+                        if let Some((check, text)) = readback.take() {
+                            check.finish(&ui, text, secret);
+                        }
+                    }
+                    TypingEvent::Failed { error, stats } if error.is_cancelled() => {
+                        ui.status.clear_warnings();
+                        ui.set_status(&args(&gettext("Cancelled after {0}."), &[&stats_text(stats)]));
+                        ui.progress.set_visible(false);
+                        ui.log.add(&format!("Cancelled after {}.", stats_text(stats)));
+                        record_run(stats);
+                        if let Some(run) = &resumable {
+                            ui.offer_resume(run, stats.typed);
+                        }
+                    }
+                    TypingEvent::Failed { error, stats } => {
+                        ui.status.clear_warnings();
+                        let message = failure_text(error);
+                        ui.status.error(
+                            &args(&gettext("Typing failed after {0}: {1}"), &[&stats_text(stats), &message]),
+                            Some(failure_details(error, &backend)),
+                        );
+                        notify_run(&ui, &gettext("Typing failed"), &args(&gettext("After {0}: {1}"), &[&stats_text(stats), &message]));
+                        sounds::play_if_enabled(Sound::Failure, &ui.applied_settings.borrow());
+                        ui.log.add(&format!("Failed after {}: {}", stats_text(stats), error));
+                        match error.remedy() {
+                            Some(Remedy::FixPermissions) => permission_dialog::show(&ui),
+                            Some(Remedy::RunDiagnostics) => diagnostics_dialog::show(&ui, false),
+                            None => {}
+                        }
+                        if error.remedy().is_some() {
+                            ui.readiness.check();
+                        }
+                        let reached = match total {
+                            Some(total) => {
+                                ui.progress.set_fraction(stats.typed as f64 / total.max(1) as f64);
+                                args(&gettext("Failed at {0} / {1}"), &[&format_count(stats.typed), &format_count(total)])
+                            }
+                            None => {
+                                ui.progress.set_fraction(0.0);
+                                gettext("Failed before typing started")
+                            }
+                        };
+                        ui.progress.set_text(Some(&reached));
+                        ui.progress.add_css_class("failed");
+                        ui.progress.set_visible(true);
+                        let weak = Rc::downgrade(&ui);
+                        timeout_add_local_once(FAILED_PROGRESS_TIMEOUT, move || {
+                            // A new run resets the bar itself.
+                            if let Some(ui) = weak.upgrade().filter(|ui| ui.is_current_run(run)) {
+                                ui.progress.set_visible(false);
+                                ui.progress.remove_css_class("failed");
+                            }
+                        });
+                        record_run(stats);
+                        if let Some(run) = &resumable {
+                            ui.offer_resume(run, stats.typed);
+                        }
+                    }
+                }
+
+                ui.run_state.borrow_mut().apply(&event);
+                let finished = event.is_terminal();
+                for listener in ui.run_listeners.borrow_mut().iter_mut() {
+                    listener(&event);
+                }
+                if finished {
+                    ui.run_listeners.borrow_mut().clear();
+                    ui.allow_idle();
+                    ui.assistive.finish(&ui);
+                    ui.set_running(false);
+                    file_actions::update_title(&ui);
+                    if ui.minimized_for_run.replace(false) {
+                        ui.window.present();
+                    }
+                    return;
+                }
+            }
+        });
+
+        // The keyboard is made during the countdown; should it still be
+        // on its way when the countdown ends, the bar pulses until typing
+        // starts.
+        let weak: Weak<Self> = Rc::downgrade(self);
+        let pulse_cancel = cancel.clone();
+        timeout_add_local_once(Duration::from_secs(delay_sec), move || {
+            let Some(ui) = weak.upgrade().filter(|ui| ui.is_current_run(run)) else {
+                return;
+            };
+            if pulse_cancel.load(Ordering::Relaxed) || !matches!(*ui.run_state.borrow(), RunState::CountingDown { .. }) {
+                return;
+            }
+            ui.progress.set_fraction(0.0);
+            ui.progress.set_text(Some(&gettext("Starting…")));
+            ui.progress.set_visible(true);
+            let weak = Rc::downgrade(&ui);
+            timeout_add_local(Duration::from_millis(100), move || {
+                match weak.upgrade() {
+                    Some(ui)
+                        if ui.is_current_run(run)
+                            && !pulse_cancel.load(Ordering::Relaxed)
+                            && matches!(*ui.run_state.borrow(), RunState::CountingDown { .. }) =>
+                    {
+                        ui.progress.pulse();
+                        ControlFlow::Continue
+                    }
+                    _ => ControlFlow::Break,
+                }
+            });
+        });
+        let start_at = Instant::now() + Duration::from_secs(delay_sec);
+        let sentinel = load_stream_sentinel();
+        thread::spawn(move || match (stream, options.remote.clone()) {
+            (Some(lines), _) => stream_typing(lines, &sentinel, &options, start_at, &cancel, &sender),
+            (None, Some(address)) => type_remote(&text, &options, &address, start_at, &cancel, &sender),
+            (None, None) => simulate_typing(text, &options, start_at, &cancel, &sender),
+        });
+    }
+}
+
+/// The application's single main window, built on first use so repeated
+/// activations, remote command lines and D-Bus calls all share it.
+#[derive(Default)]
+pub struct WindowSlot {
+    window: RefCell<Option<Rc<MainWindow>>>,
+    hold: RefCell<Option<gio::ApplicationHoldGuard>>,
+}
+
+impl WindowSlot {
+    /// Returns the main window without presenting it.
+    pub fn get(&self, app: &Application) -> Rc<MainWindow> {
+        let ui = self.window.borrow_mut().get_or_insert_with(|| build_ui(app)).clone();
+        ui.window.set_hide_on_close(self.hold.borrow().is_some());
+        ui
+    }
+
+    /// Returns the main window if it has been built.
+    pub fn peek(&self) -> Option<Rc<MainWindow>> {
+        self.window.borrow().clone()
+    }
+
+    /// Keeps the application running with no window open (background mode).
+    /// Closing the window then only hides it, so its text survives until the
+    /// next activation.
+    pub fn keep_running(&self, app: &Application) {
+        self.hold.borrow_mut().get_or_insert_with(|| app.hold());
+        if let Some(ui) = self.window.borrow().as_ref() {
+            ui.window.set_hide_on_close(true);
+        }
+    }
+}
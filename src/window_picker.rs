@@ -0,0 +1,133 @@
+// "Type Into Window…" and a snippet's "Pick…": lists the other
+// applications' windows so one can be chosen as the run's target, which is
+// brought to the front right before the first key (see
+// `TypingOptions::focus_window`). Only X11 lets a client list and raise
+// windows; elsewhere the dialog says so.
+
+use gtk4::prelude::*;
+use gtk4::{gio::SimpleAction, Button, Label, ListBox, Orientation, ScrolledWindow, SelectionMode, Window};
+use std::cell::RefCell;
+use std::rc::Rc;
+use pasteclipboard::backend::{top_level_windows, TopLevelWindow};
+
+use crate::window::MainWindow;
+
+pub fn install(ui: &Rc<MainWindow>) {
+    let action = SimpleAction::new("type-into-window", None);
+    let weak = Rc::downgrade(ui);
+    action.connect_activate(move |_, _| {
+        if let Some(ui) = weak.upgrade() {
+            let weak = Rc::downgrade(&ui);
+            show(&ui.window, "Type", move |title| {
+                if let Some(ui) = weak.upgrade() {
+                    ui.start_from_buffer_into(title);
+                }
+            });
+        }
+    });
+    ui.window.add_action(&action);
+}
+
+/// Shows the windows open now, top-most first, and calls `then` with the
+/// chosen one's title; `choose` labels the button that chooses.
+pub fn show(parent: &impl IsA<gtk4::Window>, choose: &str, then: impl Fn(String) + 'static) {
+    let dialog = Window::builder()
+        .transient_for(parent)
+        .modal(true)
+        .title("Choose a window")
+        .default_width(480)
+        .default_height(400)
+        .build();
+    let vbox = gtk4::Box::new(Orientation::Vertical, 8);
+    vbox.set_margin_start(12);
+    vbox.set_margin_end(12);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+    let intro = Label::new(Some(
+        "The chosen window is brought to the front right before typing begins. If it has been closed by then, or \
+         does not get the focus, nothing is typed.",
+    ));
+    intro.set_xalign(0.0);
+    intro.set_wrap(true);
+    vbox.append(&intro);
+    let list = ListBox::new();
+    list.set_selection_mode(SelectionMode::Single);
+    let scroller = ScrolledWindow::builder().child(&list).vexpand(true).min_content_height(200).build();
+    vbox.append(&scroller);
+    let lbl_status = Label::new(None);
+    lbl_status.set_xalign(0.0);
+    lbl_status.set_wrap(true);
+    vbox.append(&lbl_status);
+
+    let buttons = gtk4::Box::new(Orientation::Horizontal, 6);
+    buttons.set_halign(gtk4::Align::End);
+    let btn_refresh = Button::with_label("Refresh");
+    let btn_cancel = Button::with_label("Cancel");
+    let btn_choose = Button::with_label(choose);
+    btn_choose.add_css_class("suggested-action");
+    btn_choose.set_sensitive(false);
+    buttons.append(&btn_refresh);
+    buttons.append(&btn_cancel);
+    buttons.append(&btn_choose);
+    vbox.append(&buttons);
+    dialog.set_child(Some(&vbox));
+
+    let windows: Rc<RefCell<Vec<TopLevelWindow>>> = Rc::default();
+    let refresh = {
+        let (windows, list, lbl_status) = (windows.clone(), list.clone(), lbl_status.clone());
+        move || {
+            while let Some(row) = list.row_at_index(0) {
+                list.remove(&row);
+            }
+            let found = match top_level_windows() {
+                Ok(found) => found,
+                Err(e) => {
+                    lbl_status.set_text(&format!("{:#}.", e));
+                    windows.borrow_mut().clear();
+                    return;
+                }
+            };
+            for window in &found {
+                let label = Label::new(Some(&window.title));
+                label.set_xalign(0.0);
+                label.set_margin_top(4);
+                label.set_margin_bottom(4);
+                label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+                list.append(&label);
+            }
+            lbl_status.set_text(&match found.len() {
+                0 => "No other window is open.".to_string(),
+                1 => "1 window.".to_string(),
+                count => format!("{} windows.", count),
+            });
+            *windows.borrow_mut() = found;
+        }
+    };
+    refresh();
+    btn_refresh.connect_clicked(move |_| refresh());
+
+    let btn_choose_clone = btn_choose.clone();
+    list.connect_row_selected(move |_, row| btn_choose_clone.set_sensitive(row.is_some()));
+
+    let dialog_clone = dialog.clone();
+    btn_cancel.connect_clicked(move |_| dialog_clone.close());
+
+    let choose_selected = {
+        let (dialog, list) = (dialog.clone(), list.clone());
+        move || {
+            let Some(window) = list.selected_row().and_then(|row| windows.borrow().get(row.index() as usize).cloned())
+            else {
+                return;
+            };
+            dialog.close();
+            then(window.title);
+        }
+    };
+    let choose_selected = Rc::new(choose_selected);
+    let choose_clone = choose_selected.clone();
+    list.connect_row_activated(move |_, _| choose_clone());
+    btn_choose.connect_clicked(move |_| choose_selected());
+
+    list.grab_focus();
+    dialog.present();
+}